@@ -0,0 +1,99 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasmdbg2::generate;
+use wasmdbg2::module::Module;
+use wasmdbg2::runtime::exec::Execution;
+use wasmdbg2::runtime::instance::Instance;
+use wasmdbg2::runtime::linker::{Linker, StubMode};
+use wasmdbg2::runtime::snapshot::Snapshot;
+use wasmdbg2::runtime::store::{Extern, Store};
+use wasmdbg2::runtime::trap::Trap;
+use wasmdbg2::runtime::value::Value;
+
+const FUEL: u64 = 100_000;
+
+fn instantiate(module: &Module) -> Option<(Store, Instance)> {
+    let mut store = Store::new();
+    let instance = Linker::new()
+        .stub_missing(StubMode::Zeros)
+        .instantiate(&mut store, module)
+        .ok()?;
+    Some((store, instance))
+}
+
+fn same(a: &Result<Vec<Value>, Trap>, b: &Result<Vec<Value>, Trap>) -> bool {
+    match (a, b) {
+        (Ok(a), Ok(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.same_bits(b)),
+        (Err(a), Err(b)) => a.kind() == b.kind(),
+        _ => false,
+    }
+}
+
+// Runs every exported function of a generated module two ways, which must
+// agree: with `invoke`, and one instruction at a time, stopping partway
+// to take a snapshot and go on from it in a fresh store. Both stores see
+// the same calls in the same order, so their state stays the same too
+fuzz_target!(|data: &[u8]| {
+    let module = generate::module(data);
+    let (Some((mut reference, instance)), Some((mut stepped, _))) =
+        (instantiate(&module), instantiate(&module))
+    else {
+        return;
+    };
+    let funcs = instance
+        .exports(&reference)
+        .filter_map(|(_, ext)| match ext {
+            Extern::Func(func) => Some(func),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // Where the snapshot is taken varies with the input
+    let stop = data.first().map_or(0, |&b| u64::from(b));
+    for func in funcs {
+        let args = reference
+            .func(func)
+            .ty()
+            .params()
+            .iter()
+            .map(|t| Value::default_of(*t))
+            .collect::<Vec<_>>();
+        reference.set_fuel(Some(FUEL));
+        let expected = reference.invoke(func, &args);
+
+        stepped.set_fuel(Some(FUEL));
+        let mut execution = match Execution::new(&stepped, func, &args) {
+            Ok(execution) => execution,
+            Err(trap) => {
+                assert!(same(&expected, &Err(trap)), "Execution::new disagrees");
+                continue;
+            }
+        };
+        let mut result = Ok(());
+        while result.is_ok() && !execution.is_finished() {
+            if execution.steps() == stop {
+                // Snapshots can't hold exceptions, so some calls go on as
+                // they are
+                if let Ok(snapshot) = Snapshot::capture(&stepped, instance, Some(&execution), None)
+                {
+                    let (mut store, instance) =
+                        instantiate(&module).expect("the module instantiated before");
+                    execution = snapshot
+                        .restore(&mut store, instance)
+                        .expect("the snapshot doesn't fit a fresh instance")
+                        .expect("the snapshot has no call");
+                    store.set_fuel(stepped.fuel());
+                    stepped = store;
+                }
+            }
+            result = execution.step(&mut stepped);
+        }
+        let actual = result.map(|()| execution.stack().to_vec());
+        assert!(
+            same(&expected, &actual),
+            "invoke gave {:?}, stepping gave {:?}",
+            expected,
+            actual
+        );
+    }
+});
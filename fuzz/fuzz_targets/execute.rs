@@ -0,0 +1,40 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasmdbg2::generate;
+use wasmdbg2::runtime::linker::{Linker, StubMode};
+use wasmdbg2::runtime::store::{Extern, Store};
+use wasmdbg2::runtime::value::Value;
+
+// Runs every exported function of a generated module with zero arguments.
+// Traps are fine, but the interpreter must not panic, and fuel keeps
+// generated loops from running forever
+fuzz_target!(|data: &[u8]| {
+    let module = generate::module(data);
+    let mut store = Store::new();
+    store.set_fuel(Some(100_000));
+    let Ok(instance) = Linker::new()
+        .stub_missing(StubMode::Zeros)
+        .instantiate(&mut store, &module)
+    else {
+        return;
+    };
+    let funcs = instance
+        .exports(&store)
+        .filter_map(|(_, ext)| match ext {
+            Extern::Func(func) => Some(func),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for func in funcs {
+        let args = store
+            .func(func)
+            .ty()
+            .params()
+            .iter()
+            .map(|t| Value::default_of(*t))
+            .collect::<Vec<_>>();
+        store.set_fuel(Some(100_000));
+        let _ = store.invoke(func, &args);
+    }
+});
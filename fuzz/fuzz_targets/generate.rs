@@ -0,0 +1,30 @@
+#![no_main]
+
+use std::io::{BufReader, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use wasmdbg2::generate;
+use wasmdbg2::module::Module;
+use wasmdbg2::validate::validate;
+use wasmdbg2::wat::parse::parse_module;
+use wasmdbg2::wat::print::{PrintOptions, print};
+
+// Generated modules are valid, so they get past the checks that stop most
+// random input, and must survive encoding and the text format unchanged
+fuzz_target!(|data: &[u8]| {
+    let module = generate::module(data);
+    if let Err(err) = validate(&module) {
+        panic!("generated module is invalid: {}", err);
+    }
+
+    let mut encoded = Vec::new();
+    module.encode(&mut encoded).unwrap();
+    let Ok(reparsed) = Module::parse(&mut BufReader::new(Cursor::new(encoded))) else {
+        panic!("generated module doesn't parse");
+    };
+    assert!(validate(&reparsed).is_ok());
+
+    let text = print(&reparsed, &PrintOptions::default());
+    let from_text = parse_module(&text).expect("printed module doesn't parse");
+    assert_eq!(print(&from_text, &PrintOptions::default()), text);
+});
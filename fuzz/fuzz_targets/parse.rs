@@ -0,0 +1,17 @@
+#![no_main]
+
+use std::io::{BufReader, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use wasmdbg2::component::Component;
+use wasmdbg2::module::Module;
+
+// Any input must give a module or an error, never a panic, and the same
+// goes for components
+fuzz_target!(|data: &[u8]| {
+    let mut reader = BufReader::new(Cursor::new(data.to_vec()));
+    let _ = Module::parse(&mut reader);
+
+    let mut reader = BufReader::new(Cursor::new(data.to_vec()));
+    let _ = Component::parse(&mut reader);
+});
@@ -0,0 +1,32 @@
+#![no_main]
+
+use std::io::{BufReader, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use wasmdbg2::module::Module;
+
+fn parse(bytes: &[u8]) -> Option<Module> {
+    Module::parse(&mut BufReader::new(Cursor::new(bytes.to_vec()))).ok()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some(module) = parse(data) else {
+        return;
+    };
+
+    // Keeping the original LEB widths gives back the input exactly
+    let mut preserved = Vec::new();
+    module.encode_preserving_lebs(&mut preserved).unwrap();
+    assert!(
+        preserved == data,
+        "encode_preserving_lebs changed the module"
+    );
+
+    // Minimal encoding parses again, and encodes to the same bytes
+    let mut encoded = Vec::new();
+    module.encode(&mut encoded).unwrap();
+    let reparsed = parse(&encoded).expect("encoded module doesn't parse");
+    let mut reencoded = Vec::new();
+    reparsed.encode(&mut reencoded).unwrap();
+    assert!(reencoded == encoded, "encoding isn't stable");
+});
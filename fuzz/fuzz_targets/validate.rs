@@ -0,0 +1,14 @@
+#![no_main]
+
+use std::io::{BufReader, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use wasmdbg2::module::Module;
+use wasmdbg2::validate::validate;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = BufReader::new(Cursor::new(data.to_vec()));
+    if let Ok(module) = Module::parse(&mut reader) {
+        let _ = validate(&module);
+    }
+});
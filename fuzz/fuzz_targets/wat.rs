@@ -0,0 +1,34 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasmdbg2::validate::validate;
+use wasmdbg2::wat::parse::parse_module;
+use wasmdbg2::wat::print::{PrintOptions, print};
+use wasmdbg2::wat::wast::{Script, run};
+
+// Text is parsed as a module and as a script. Printing a valid module and
+// parsing it back must be stable. It's checked from the second printing on,
+// since empty sections in a binary module don't survive the first.
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(module) = parse_module(text)
+        && validate(&module).is_ok()
+    {
+        for folded in [false, true] {
+            let options = PrintOptions {
+                folded,
+                ..PrintOptions::default()
+            };
+            let first = print(&module, &options);
+            let reparsed = parse_module(&first).expect("printed module doesn't parse");
+            let printed = print(&reparsed, &options);
+            let reparsed = parse_module(&printed).expect("reprinted module doesn't parse");
+            assert_eq!(print(&reparsed, &options), printed);
+        }
+    }
+    if let Ok(script) = Script::parse(text) {
+        let _ = run(&script);
+    }
+});
@@ -0,0 +1,413 @@
+use std::fmt::Display;
+
+use crate::instr::Instr;
+use crate::instr::expr::Expr;
+use crate::module::Module;
+use crate::section::code::{Code, CodeSec, Locals};
+use crate::section::data::{Data, DataMode, DataSec};
+use crate::section::data_count::DataCountSec;
+use crate::section::export::{Export, ExportSec};
+use crate::section::function::FunctionSec;
+use crate::section::global::{Global, GlobalSec};
+use crate::section::import::{Import, ImportSec};
+use crate::section::memory::MemSec;
+use crate::section::start::StartSec;
+use crate::section::r#type::TypeSec;
+use crate::types::comp_type::CompType;
+use crate::types::export_desc::ExportDesc;
+use crate::types::func_type::FuncType;
+use crate::types::global_type::GlobalType;
+use crate::types::import_desc::ImportDesc;
+use crate::types::mem_type::MemType;
+use crate::types::primitives::{DataIdx, FuncIdx, GlobalIdx, LocalIdx, MemIdx, TypeIdx};
+use crate::types::rec_type::RecType;
+use crate::types::sub_type::SubType;
+use crate::types::val_type::ValType;
+
+/// Builds a `Module` without going through the binary format. Each method
+/// returns the index of what it added, so the results can be used directly
+/// in instructions.
+///
+/// Imports take the lowest indices in their index space, so all imports of
+/// a kind have to be added before any definitions of that kind. Adding one
+/// later is an error rather than renumbering indices already handed out.
+#[derive(Default)]
+pub struct ModuleBuilder {
+    types: Vec<FuncType>,
+    imports: Vec<Import>,
+    num_imported_funcs: u32,
+    num_imported_mems: u32,
+    num_imported_globals: u32,
+    funcs: Vec<TypeIdx>,
+    codes: Vec<Code>,
+    mems: Vec<MemType>,
+    globals: Vec<Global>,
+    exports: Vec<Export>,
+    start: Option<FuncIdx>,
+    datas: Vec<Data>,
+}
+
+/// Why something could not be added to a module being built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// An import of a kind, e.g. "function", after a definition of that
+    /// kind took the index it would have needed.
+    LateImport(&'static str),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::LateImport(kind) => {
+                write!(f, "{}s must be imported before any are defined", kind)
+            }
+        }
+    }
+}
+
+/// The body of a function being built. The final `end` is added when the
+/// function is finished, but blocks inside it need their own.
+pub struct FuncBody {
+    idx: FuncIdx,
+    num_params: u32,
+    locals: Vec<Locals>,
+    instrs: Vec<Instr>,
+}
+
+impl FuncBody {
+    /// The index of the function itself, for recursive calls.
+    pub fn index(&self) -> FuncIdx {
+        self.idx
+    }
+
+    /// Declares a new local. Its index comes after the parameters and any
+    /// locals declared before it.
+    pub fn local(&mut self, t: ValType) -> LocalIdx {
+        let declared: u32 = self.locals.iter().map(|l| l.count()).sum();
+        match self.locals.last_mut() {
+            Some(last) if last.val_type() == t => *last = Locals::new(last.count() + 1, t),
+            _ => self.locals.push(Locals::new(1, t)),
+        }
+        LocalIdx(self.num_params + declared)
+    }
+
+    pub fn instr(&mut self, instr: Instr) -> &mut Self {
+        self.instrs.push(instr);
+        self
+    }
+
+    pub fn instrs(&mut self, instrs: impl IntoIterator<Item = Instr>) -> &mut Self {
+        self.instrs.extend(instrs);
+        self
+    }
+}
+
+// Constant expressions are given without their final `end`
+fn const_expr(instrs: Vec<Instr>) -> Expr {
+    let mut instrs = instrs;
+    instrs.push(Instr::End);
+    Expr::new(instrs)
+}
+
+impl ModuleBuilder {
+    pub fn new() -> ModuleBuilder {
+        ModuleBuilder::default()
+    }
+
+    /// Adds a function type, or returns the index of an identical type that
+    /// was added before.
+    pub fn add_type(&mut self, ty: FuncType) -> TypeIdx {
+        let idx = match self.types.iter().position(|other| *other == ty) {
+            Some(idx) => idx,
+            None => {
+                self.types.push(ty);
+                self.types.len() - 1
+            }
+        };
+        TypeIdx(idx as u32)
+    }
+
+    /// Imports a function, which takes the next index after the functions
+    /// imported so far. Fails with `LateImport` once a function has been
+    /// defined with `func`, as the import would need an index before it.
+    pub fn import_func(
+        &mut self,
+        module: &str,
+        name: &str,
+        ty: FuncType,
+    ) -> Result<FuncIdx, BuildError> {
+        if !self.funcs.is_empty() {
+            return Err(BuildError::LateImport("function"));
+        }
+        let ty = self.add_type(ty);
+        self.imports
+            .push(Import::new(module, name, ImportDesc::Func(ty)));
+        self.num_imported_funcs += 1;
+        Ok(FuncIdx(self.num_imported_funcs - 1))
+    }
+
+    /// Imports a memory, which takes the next index after the memories
+    /// imported so far. Fails with `LateImport` once a memory has been
+    /// defined with `memory`.
+    pub fn import_memory(
+        &mut self,
+        module: &str,
+        name: &str,
+        ty: MemType,
+    ) -> Result<MemIdx, BuildError> {
+        if !self.mems.is_empty() {
+            return Err(BuildError::LateImport("memory"));
+        }
+        self.imports
+            .push(Import::new(module, name, ImportDesc::Mem(ty)));
+        self.num_imported_mems += 1;
+        Ok(MemIdx(self.num_imported_mems - 1))
+    }
+
+    /// Imports a global, which takes the next index after the globals
+    /// imported so far. Fails with `LateImport` once a global has been
+    /// defined with `global`.
+    pub fn import_global(
+        &mut self,
+        module: &str,
+        name: &str,
+        ty: GlobalType,
+    ) -> Result<GlobalIdx, BuildError> {
+        if !self.globals.is_empty() {
+            return Err(BuildError::LateImport("global"));
+        }
+        self.imports
+            .push(Import::new(module, name, ImportDesc::Global(ty)));
+        self.num_imported_globals += 1;
+        Ok(GlobalIdx(self.num_imported_globals - 1))
+    }
+
+    /// Defines a function whose body is filled in by `f`. Its index comes
+    /// after every imported function, so no more can be imported.
+    pub fn func(&mut self, ty: FuncType, f: impl FnOnce(&mut FuncBody)) -> FuncIdx {
+        let idx = FuncIdx(self.num_imported_funcs + self.funcs.len() as u32);
+        let mut body = FuncBody {
+            idx,
+            num_params: ty.params().len() as u32,
+            locals: Vec::new(),
+            instrs: Vec::new(),
+        };
+        f(&mut body);
+        body.instrs.push(Instr::End);
+
+        let ty = self.add_type(ty);
+        self.funcs.push(ty);
+        self.codes
+            .push(Code::new(body.locals, Expr::new(body.instrs)));
+        idx
+    }
+
+    /// Defines a memory. Its index comes after every imported memory, so
+    /// no more can be imported.
+    pub fn memory(&mut self, ty: MemType) -> MemIdx {
+        self.mems.push(ty);
+        MemIdx(self.num_imported_mems + self.mems.len() as u32 - 1)
+    }
+
+    /// Defines a global initialized by the constant expression `init`. Its
+    /// index comes after every imported global, so no more can be imported.
+    pub fn global(&mut self, ty: GlobalType, init: Vec<Instr>) -> GlobalIdx {
+        self.globals.push(Global::new(ty, const_expr(init)));
+        GlobalIdx(self.num_imported_globals + self.globals.len() as u32 - 1)
+    }
+
+    pub fn export(&mut self, name: &str, desc: ExportDesc) -> &mut Self {
+        self.exports.push(Export::new(name, desc));
+        self
+    }
+
+    pub fn start(&mut self, func: FuncIdx) -> &mut Self {
+        self.start = Some(func);
+        self
+    }
+
+    /// Adds a segment that is copied into `mem` at the address computed by
+    /// the constant expression `offset` during instantiation.
+    pub fn data(&mut self, mem: MemIdx, offset: Vec<Instr>, init: impl Into<Vec<u8>>) -> DataIdx {
+        let mode = DataMode::Active {
+            mem,
+            offset: const_expr(offset),
+        };
+        self.datas.push(Data::new(mode, init.into()));
+        DataIdx(self.datas.len() as u32 - 1)
+    }
+
+    /// Adds a segment that is only used by `memory.init`.
+    pub fn passive_data(&mut self, init: impl Into<Vec<u8>>) -> DataIdx {
+        self.datas.push(Data::new(DataMode::Passive, init.into()));
+        DataIdx(self.datas.len() as u32 - 1)
+    }
+
+    pub fn build(self) -> Module {
+        // Only bodies that refer to segments by index need the data count
+        let needs_data_count = self.codes.iter().any(|code| {
+            code.body()
+                .instrs()
+                .iter()
+                .any(|instr| matches!(instr, Instr::MemoryInit(..) | Instr::DataDrop(_)))
+        });
+
+        let mut module = Module::default();
+        module.version = 1;
+        if !self.types.is_empty() {
+            let recs = self
+                .types
+                .into_iter()
+                .map(|ty| RecType::new(vec![SubType::from(CompType::Func(ty))]))
+                .collect();
+            module.typesec = Some(TypeSec::new(recs));
+        }
+        if !self.imports.is_empty() {
+            module.importsec = Some(ImportSec::new(self.imports));
+        }
+        if !self.funcs.is_empty() {
+            module.functionsec = Some(FunctionSec::new(self.funcs));
+            module.codesec = Some(CodeSec::new(self.codes));
+        }
+        if !self.mems.is_empty() {
+            module.memsec = Some(MemSec::new(self.mems));
+        }
+        if !self.globals.is_empty() {
+            module.globalsec = Some(GlobalSec::new(self.globals));
+        }
+        if !self.exports.is_empty() {
+            module.exportsec = Some(ExportSec::new(self.exports));
+        }
+        module.startsec = self.start.map(StartSec::new);
+        if needs_data_count {
+            module.datacountsec = Some(DataCountSec::new(self.datas.len() as u32));
+        }
+        if !self.datas.is_empty() {
+            module.datasec = Some(DataSec::new(self.datas));
+        }
+        module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instr::numeric::NumericInstr;
+    use crate::parseable::ParseError;
+    use crate::types::limits::Limits;
+    use crate::types::r#mut::VAR;
+    use crate::types::num_type::NumType;
+    use crate::validate::validate;
+    use std::io::{BufReader, Cursor};
+
+    const I32: ValType = ValType::Num(NumType::I32);
+
+    #[test]
+    fn test_builder() {
+        let mut builder = ModuleBuilder::new();
+        let log = builder
+            .import_func("env", "log", FuncType::new(vec![I32], vec![]))
+            .unwrap();
+        let mem = builder.memory(MemType::new(Limits::new(1, None)));
+        let counter = builder.global(GlobalType::new(I32, VAR), vec![Instr::I32Const(0)]);
+        let hello = builder.data(mem, vec![Instr::I32Const(16)], "hello");
+        let bump = builder.func(FuncType::new(vec![I32], vec![I32]), |body| {
+            let sum = body.local(I32);
+            body.instrs([
+                Instr::GlobalGet(counter),
+                Instr::LocalGet(LocalIdx(0)),
+                Instr::Numeric(NumericInstr::I32Add),
+                Instr::LocalTee(sum),
+                Instr::GlobalSet(counter),
+                Instr::LocalGet(sum),
+                Instr::Call(log),
+                Instr::LocalGet(sum),
+            ]);
+        });
+        let init = builder.func(FuncType::new(vec![], vec![]), |body| {
+            body.instrs([
+                Instr::I32Const(0),
+                Instr::I32Const(0),
+                Instr::I32Const(5),
+                Instr::MemoryInit(hello, mem),
+                Instr::DataDrop(hello),
+            ]);
+        });
+        builder
+            .export("bump", ExportDesc::Func(bump))
+            .export("memory", ExportDesc::Mem(mem))
+            .start(init);
+
+        assert_eq!((log, bump, init), (FuncIdx(0), FuncIdx(1), FuncIdx(2)));
+        assert_eq!((mem, counter, hello), (MemIdx(0), GlobalIdx(0), DataIdx(0)));
+
+        let module = builder.build();
+        if let Err(err) = validate(&module) {
+            panic!("{}", err);
+        }
+        let typesec = module.typesec.as_ref().expect("A type section");
+        assert_eq!(typesec.types().count(), 3);
+        assert_eq!(module.datacountsec.as_ref().map(|sec| sec.count()), Some(1));
+
+        // The built module survives a trip through the binary format,
+        // offsets included.
+        let mut bytes = Vec::new();
+        module.encode(&mut bytes).expect("The encoded module");
+        let mut reader = BufReader::new(Cursor::new(bytes.clone()));
+        let parsed = match Module::parse(&mut reader) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+        let mut reencoded = Vec::new();
+        parsed.encode(&mut reencoded).expect("The encoded module");
+        assert_eq!(reencoded, bytes);
+
+        let built = module.codesec.as_ref().expect("A code section");
+        let parsed = parsed.codesec.expect("A code section");
+        for (built, parsed) in built.codes().iter().zip(parsed.codes()) {
+            assert_eq!(built.size(), parsed.size());
+            assert_eq!(built.locals(), parsed.locals());
+            assert_eq!(built.offsets(), parsed.offsets());
+        }
+    }
+
+    #[test]
+    fn test_late_import() {
+        let mut builder = ModuleBuilder::new();
+        let unit = FuncType::new(vec![], vec![]);
+        let first = builder.import_func("env", "a", unit.clone()).unwrap();
+        let second = builder.import_func("env", "b", unit.clone()).unwrap();
+        let defined = builder.func(unit.clone(), |body| {
+            body.instrs([Instr::Call(first), Instr::Call(second)]);
+        });
+        assert_eq!(
+            (first, second, defined),
+            (FuncIdx(0), FuncIdx(1), FuncIdx(2))
+        );
+        let result = builder.import_func("env", "f", unit);
+        assert_eq!(result, Err(BuildError::LateImport("function")));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "functions must be imported before any are defined"
+        );
+
+        // Other kinds can still be imported
+        let global = builder.import_global("env", "g", GlobalType::new(I32, VAR));
+        assert_eq!(global, Ok(GlobalIdx(0)));
+        builder.global(GlobalType::new(I32, VAR), vec![Instr::I32Const(0)]);
+        let result = builder.import_global("env", "h", GlobalType::new(I32, VAR));
+        assert_eq!(result, Err(BuildError::LateImport("global")));
+        builder.memory(MemType::new(Limits::new(1, None)));
+        let result = builder.import_memory("env", "m", MemType::new(Limits::new(1, None)));
+        assert_eq!(result, Err(BuildError::LateImport("memory")));
+
+        // Failed imports leave nothing behind, and the indices handed out
+        // still hold
+        let module = builder.build();
+        if let Err(err) = validate(&module) {
+            panic!("{}", err);
+        }
+        let imports = module.importsec.as_ref().expect("An import section");
+        assert_eq!(imports.imports().len(), 3);
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read};
+
+use crate::component::parse_idx;
+use crate::component::sort::{CoreSort, Sort};
+use crate::parseable::{ParseError, Parseable, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AliasTarget {
+    /// An export of a component instance.
+    Export { instance: u32, name: String },
+    /// An export of a core instance.
+    CoreExport { instance: u32, name: String },
+    /// A definition of an enclosing component, `count` levels up.
+    Outer { count: u32, idx: u32 },
+}
+
+/// Brings a definition from an instance or an enclosing component into the
+/// current index space of `sort`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    pub sort: Sort,
+    pub target: AliasTarget,
+}
+
+impl Parseable for Alias {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let sort = Sort::parse(reader)?;
+        let target = match u8::parse(reader)? {
+            0x00 => {
+                let instance = parse_idx(reader)?;
+                AliasTarget::Export {
+                    instance,
+                    name: String::parse(reader)?,
+                }
+            }
+            0x01 => {
+                let instance = parse_idx(reader)?;
+                AliasTarget::CoreExport {
+                    instance,
+                    name: String::parse(reader)?,
+                }
+            }
+            0x02 => {
+                let count = parse_idx(reader)?;
+                AliasTarget::Outer {
+                    count,
+                    idx: parse_idx(reader)?,
+                }
+            }
+            target => {
+                return Err(ParseError::Other(format!(
+                    "invalid alias target: {:#04x}",
+                    target
+                )));
+            }
+        };
+        Ok(Alias { sort, target })
+    }
+}
+
+impl Display for Alias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.target {
+            AliasTarget::Export { instance, name } => {
+                write!(
+                    f,
+                    "{}: export \"{}\" of instance {}",
+                    self.sort, name, instance
+                )
+            }
+            AliasTarget::CoreExport { instance, name } => write!(
+                f,
+                "{}: export \"{}\" of core instance {}",
+                self.sort, name, instance
+            ),
+            AliasTarget::Outer { count, idx } => {
+                write!(f, "{}: outer {} {}", self.sort, count, idx)
+            }
+        }
+    }
+}
+
+/// An alias inside a core module type, which may only refer to an outer
+/// definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreAlias {
+    pub sort: CoreSort,
+    pub count: u32,
+    pub idx: u32,
+}
+
+impl Parseable for CoreAlias {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let sort = CoreSort::parse(reader)?;
+        let target = u8::parse(reader)?;
+        if target != 0x01 {
+            return Err(ParseError::Other(format!(
+                "invalid core alias target: {:#04x}",
+                target
+            )));
+        }
+        let count = parse_idx(reader)?;
+        let idx = parse_idx(reader)?;
+        Ok(CoreAlias { sort, count, idx })
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read};
+
+use crate::component::parse_idx;
+use crate::parseable::{ParseError, Parseable, Result};
+
+/// Options controlling how values are lifted and lowered by the canonical
+/// ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanonOpt {
+    Utf8,
+    Utf16,
+    CompactUtf16,
+    Memory(u32),
+    Realloc(u32),
+    PostReturn(u32),
+    Async,
+    Callback(u32),
+}
+
+impl Parseable for CanonOpt {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let opt = u8::parse(reader)?;
+        match opt {
+            0x00 => Ok(CanonOpt::Utf8),
+            0x01 => Ok(CanonOpt::Utf16),
+            0x02 => Ok(CanonOpt::CompactUtf16),
+            0x03 => Ok(CanonOpt::Memory(parse_idx(reader)?)),
+            0x04 => Ok(CanonOpt::Realloc(parse_idx(reader)?)),
+            0x05 => Ok(CanonOpt::PostReturn(parse_idx(reader)?)),
+            0x06 => Ok(CanonOpt::Async),
+            0x07 => Ok(CanonOpt::Callback(parse_idx(reader)?)),
+            opt => Err(ParseError::Other(format!(
+                "invalid canonical option: {:#04x}",
+                opt
+            ))),
+        }
+    }
+}
+
+impl Display for CanonOpt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanonOpt::Utf8 => write!(f, "string-encoding=utf8"),
+            CanonOpt::Utf16 => write!(f, "string-encoding=utf16"),
+            CanonOpt::CompactUtf16 => write!(f, "string-encoding=latin1+utf16"),
+            CanonOpt::Memory(idx) => write!(f, "(memory {})", idx),
+            CanonOpt::Realloc(idx) => write!(f, "(realloc {})", idx),
+            CanonOpt::PostReturn(idx) => write!(f, "(post-return {})", idx),
+            CanonOpt::Async => write!(f, "async"),
+            CanonOpt::Callback(idx) => write!(f, "(callback {})", idx),
+        }
+    }
+}
+
+/// A canonical function, converting between core functions and component
+/// functions or operating on resources.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Canon {
+    /// Wraps core function `core_func` as a component function of type
+    /// `ty`.
+    Lift {
+        core_func: u32,
+        opts: Vec<CanonOpt>,
+        ty: u32,
+    },
+    /// Wraps component function `func` as a core function.
+    Lower {
+        func: u32,
+        opts: Vec<CanonOpt>,
+    },
+    ResourceNew(u32),
+    ResourceDrop(u32),
+    ResourceRep(u32),
+}
+
+impl Parseable for Canon {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let kind = u8::parse(reader)?;
+        let canon = match kind {
+            0x00 | 0x01 => {
+                let sort = u8::parse(reader)?;
+                if sort != 0x00 {
+                    return Err(ParseError::Other(format!(
+                        "invalid canonical function sort: {:#04x}",
+                        sort
+                    )));
+                }
+                let func = parse_idx(reader)?;
+                let opts = Vec::<CanonOpt>::parse(reader)?;
+                if kind == 0x00 {
+                    Canon::Lift {
+                        core_func: func,
+                        opts,
+                        ty: parse_idx(reader)?,
+                    }
+                } else {
+                    Canon::Lower { func, opts }
+                }
+            }
+            0x02 => Canon::ResourceNew(parse_idx(reader)?),
+            0x03 => Canon::ResourceDrop(parse_idx(reader)?),
+            0x04 => Canon::ResourceRep(parse_idx(reader)?),
+            kind => {
+                return Err(ParseError::Other(format!(
+                    "unsupported canonical function: {:#04x}",
+                    kind
+                )));
+            }
+        };
+        Ok(canon)
+    }
+}
+
+impl Display for Canon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Canon::Lift {
+                core_func,
+                opts,
+                ty,
+            } => {
+                write!(f, "lift core func {}", core_func)?;
+                for opt in opts {
+                    write!(f, " {}", opt)?;
+                }
+                write!(f, " (type {})", ty)
+            }
+            Canon::Lower { func, opts } => {
+                write!(f, "lower func {}", func)?;
+                for opt in opts {
+                    write!(f, " {}", opt)?;
+                }
+                Ok(())
+            }
+            Canon::ResourceNew(ty) => write!(f, "resource.new {}", ty),
+            Canon::ResourceDrop(ty) => write!(f, "resource.drop {}", ty),
+            Canon::ResourceRep(ty) => write!(f, "resource.rep {}", ty),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read};
+
+use crate::component::parse_idx;
+use crate::component::sort::SortIdx;
+use crate::component::types::{ComponentValType, parse_optional};
+use crate::parseable::{ParseError, Parseable, Result};
+
+/// Reads an import or export name. The leading byte distinguishes plain
+/// names from interface names in older encodings; both are strings.
+pub(crate) fn parse_extern_name(reader: &mut BufReader<dyn Read>) -> Result<String> {
+    match u8::parse(reader)? {
+        0x00 | 0x01 => String::parse(reader),
+        kind => Err(ParseError::Other(format!(
+            "invalid extern name kind: {:#04x}",
+            kind
+        ))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeBound {
+    /// The type is equal to type `idx`.
+    Eq(u32),
+    /// A fresh abstract resource type.
+    SubResource,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueBound {
+    Eq(u32),
+    Type(ComponentValType),
+}
+
+/// The type of an imported or exported definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternDesc {
+    /// A core module of core type `idx`.
+    Module(u32),
+    Func(u32),
+    Value(ValueBound),
+    Type(TypeBound),
+    Component(u32),
+    Instance(u32),
+}
+
+impl Parseable for ExternDesc {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let kind = u8::parse(reader)?;
+        let desc = match kind {
+            0x00 => {
+                let sort = u8::parse(reader)?;
+                if sort != 0x11 {
+                    return Err(ParseError::Other(format!(
+                        "invalid core extern sort: {:#04x}",
+                        sort
+                    )));
+                }
+                ExternDesc::Module(parse_idx(reader)?)
+            }
+            0x01 => ExternDesc::Func(parse_idx(reader)?),
+            0x02 => match u8::parse(reader)? {
+                0x00 => ExternDesc::Value(ValueBound::Eq(parse_idx(reader)?)),
+                0x01 => ExternDesc::Value(ValueBound::Type(ComponentValType::parse(reader)?)),
+                bound => {
+                    return Err(ParseError::Other(format!(
+                        "invalid value bound: {:#04x}",
+                        bound
+                    )));
+                }
+            },
+            0x03 => match u8::parse(reader)? {
+                0x00 => ExternDesc::Type(TypeBound::Eq(parse_idx(reader)?)),
+                0x01 => ExternDesc::Type(TypeBound::SubResource),
+                bound => {
+                    return Err(ParseError::Other(format!(
+                        "invalid type bound: {:#04x}",
+                        bound
+                    )));
+                }
+            },
+            0x04 => ExternDesc::Component(parse_idx(reader)?),
+            0x05 => ExternDesc::Instance(parse_idx(reader)?),
+            kind => {
+                return Err(ParseError::Other(format!(
+                    "invalid extern kind: {:#04x}",
+                    kind
+                )));
+            }
+        };
+        Ok(desc)
+    }
+}
+
+impl Display for ExternDesc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExternDesc::Module(ty) => write!(f, "core module (type {})", ty),
+            ExternDesc::Func(ty) => write!(f, "func (type {})", ty),
+            ExternDesc::Value(ValueBound::Eq(idx)) => write!(f, "value (eq {})", idx),
+            ExternDesc::Value(ValueBound::Type(ty)) => write!(f, "value {}", ty),
+            ExternDesc::Type(TypeBound::Eq(idx)) => write!(f, "type (eq {})", idx),
+            ExternDesc::Type(TypeBound::SubResource) => write!(f, "type (sub resource)"),
+            ExternDesc::Component(ty) => write!(f, "component (type {})", ty),
+            ExternDesc::Instance(ty) => write!(f, "instance (type {})", ty),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentImport {
+    name: String,
+    desc: ExternDesc,
+}
+
+impl ComponentImport {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn desc(&self) -> &ExternDesc {
+        &self.desc
+    }
+}
+
+impl Parseable for ComponentImport {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = parse_extern_name(reader)?;
+        let desc = ExternDesc::parse(reader)?;
+        Ok(ComponentImport { name, desc })
+    }
+}
+
+impl Display for ComponentImport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\": {}", self.name, self.desc)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentExport {
+    name: String,
+    item: SortIdx,
+    desc: Option<ExternDesc>,
+}
+
+impl ComponentExport {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The exported definition.
+    pub fn item(&self) -> SortIdx {
+        self.item
+    }
+
+    /// The type the definition is exported as, when given explicitly.
+    pub fn desc(&self) -> Option<&ExternDesc> {
+        self.desc.as_ref()
+    }
+}
+
+impl Parseable for ComponentExport {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = parse_extern_name(reader)?;
+        let item = SortIdx::parse(reader)?;
+        let desc = parse_optional::<ExternDesc>(reader)?;
+        Ok(ComponentExport { name, item, desc })
+    }
+}
+
+impl Display for ComponentExport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\": {}", self.name, self.item)?;
+        if let Some(desc) = &self.desc {
+            write!(f, " as {}", desc)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read};
+
+use crate::component::import::parse_extern_name;
+use crate::component::parse_idx;
+use crate::component::sort::{CoreSortIdx, SortIdx};
+use crate::parseable::{ParseError, Parseable, Result};
+
+/// A core instance passed to `instantiate` under the import module name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreInstantiateArg {
+    pub name: String,
+    pub instance: u32,
+}
+
+impl Parseable for CoreInstantiateArg {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = String::parse(reader)?;
+        let sort = u8::parse(reader)?;
+        if sort != 0x12 {
+            return Err(ParseError::Other(format!(
+                "core instantiate argument must be an instance, got sort {:#04x}",
+                sort
+            )));
+        }
+        let instance = parse_idx(reader)?;
+        Ok(CoreInstantiateArg { name, instance })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreInlineExport {
+    pub name: String,
+    pub item: CoreSortIdx,
+}
+
+impl Parseable for CoreInlineExport {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = String::parse(reader)?;
+        let item = CoreSortIdx::parse(reader)?;
+        Ok(CoreInlineExport { name, item })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoreInstance {
+    /// Instantiates the core module `module`, satisfying its imports from
+    /// the given instances.
+    Instantiate {
+        module: u32,
+        args: Vec<CoreInstantiateArg>,
+    },
+    /// Bundles existing core definitions into an instance.
+    FromExports(Vec<CoreInlineExport>),
+}
+
+impl Parseable for CoreInstance {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => {
+                let module = parse_idx(reader)?;
+                Ok(CoreInstance::Instantiate {
+                    module,
+                    args: Vec::<CoreInstantiateArg>::parse(reader)?,
+                })
+            }
+            0x01 => Ok(CoreInstance::FromExports(Vec::<CoreInlineExport>::parse(
+                reader,
+            )?)),
+            kind => Err(ParseError::Other(format!(
+                "invalid core instance kind: {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+impl Display for CoreInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoreInstance::Instantiate { module, args } => {
+                write!(f, "instantiate module {}", module)?;
+                for arg in args {
+                    write!(f, " (with \"{}\" instance {})", arg.name, arg.instance)?;
+                }
+                Ok(())
+            }
+            CoreInstance::FromExports(exports) => {
+                write!(f, "exports")?;
+                for export in exports {
+                    write!(f, " (\"{}\" {})", export.name, export.item)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A component-level definition passed to `instantiate` under an import
+/// name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstantiateArg {
+    pub name: String,
+    pub item: SortIdx,
+}
+
+impl Parseable for InstantiateArg {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = String::parse(reader)?;
+        let item = SortIdx::parse(reader)?;
+        Ok(InstantiateArg { name, item })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineExport {
+    pub name: String,
+    pub item: SortIdx,
+}
+
+impl Parseable for InlineExport {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = parse_extern_name(reader)?;
+        let item = SortIdx::parse(reader)?;
+        Ok(InlineExport { name, item })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instance {
+    Instantiate {
+        component: u32,
+        args: Vec<InstantiateArg>,
+    },
+    FromExports(Vec<InlineExport>),
+}
+
+impl Parseable for Instance {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => {
+                let component = parse_idx(reader)?;
+                Ok(Instance::Instantiate {
+                    component,
+                    args: Vec::<InstantiateArg>::parse(reader)?,
+                })
+            }
+            0x01 => Ok(Instance::FromExports(Vec::<InlineExport>::parse(reader)?)),
+            kind => Err(ParseError::Other(format!(
+                "invalid instance kind: {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+impl Display for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instance::Instantiate { component, args } => {
+                write!(f, "instantiate component {}", component)?;
+                for arg in args {
+                    write!(f, " (with \"{}\" {})", arg.name, arg.item)?;
+                }
+                Ok(())
+            }
+            Instance::FromExports(exports) => {
+                write!(f, "exports")?;
+                for export in exports {
+                    write!(f, " (\"{}\" {})", export.name, export.item)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
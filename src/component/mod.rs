@@ -0,0 +1,596 @@
+pub mod alias;
+pub mod canon;
+pub mod import;
+pub mod instance;
+pub mod sort;
+pub mod types;
+pub mod wit;
+
+use std::fmt::Display;
+use std::io::{BufReader, Cursor, Read};
+use std::result::Result;
+
+use crate::component::alias::Alias;
+use crate::component::canon::Canon;
+use crate::component::import::{ComponentExport, ComponentImport};
+use crate::component::instance::{CoreInstance, Instance};
+use crate::component::types::{ComponentTypeDef, CoreType};
+use crate::component::wit::World;
+use crate::module::{MAGIC, Module, ModuleParseError};
+use crate::parseable::{Asked, ParseError, Parseable, ParserLimits, Received};
+use crate::section::custom::{CustomSec, CustomSecParseError};
+use crate::types::leb128::Leb128;
+use crate::types::primitives::TypeIdx;
+
+pub const CUSTOM_SECTION_ID: TypeIdx = TypeIdx(0);
+pub const CORE_MODULE_SECTION_ID: TypeIdx = TypeIdx(1);
+pub const CORE_INSTANCE_SECTION_ID: TypeIdx = TypeIdx(2);
+pub const CORE_TYPE_SECTION_ID: TypeIdx = TypeIdx(3);
+pub const COMPONENT_SECTION_ID: TypeIdx = TypeIdx(4);
+pub const INSTANCE_SECTION_ID: TypeIdx = TypeIdx(5);
+pub const ALIAS_SECTION_ID: TypeIdx = TypeIdx(6);
+pub const TYPE_SECTION_ID: TypeIdx = TypeIdx(7);
+pub const CANON_SECTION_ID: TypeIdx = TypeIdx(8);
+pub const START_SECTION_ID: TypeIdx = TypeIdx(9);
+pub const IMPORT_SECTION_ID: TypeIdx = TypeIdx(10);
+pub const EXPORT_SECTION_ID: TypeIdx = TypeIdx(11);
+
+/// The component binary format version, in the low half of the word that
+/// holds a core module's version.
+pub const COMPONENT_VERSION: u16 = 0x0d;
+/// The layer distinguishing components (1) from core modules (0).
+pub const COMPONENT_LAYER: u16 = 0x01;
+
+/// Reads a component-level index, which is a plain u32.
+pub(crate) fn parse_idx(reader: &mut BufReader<dyn Read>) -> crate::parseable::Result<u32> {
+    Ok(u32::from(Leb128::<u32>::parse(reader)?))
+}
+
+/// Calls component function `func` with the given values at instantiation
+/// time, binding `results` new values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Start {
+    pub func: u32,
+    pub args: Vec<u32>,
+    pub results: u32,
+}
+
+impl Parseable for Start {
+    fn parse(reader: &mut BufReader<dyn Read>) -> crate::parseable::Result<Self>
+    where
+        Self: Sized,
+    {
+        let func = parse_idx(reader)?;
+        let args = Vec::<Leb128<u32>>::parse(reader)?
+            .iter()
+            .map(u32::from)
+            .collect();
+        let results = parse_idx(reader)?;
+        Ok(Start {
+            func,
+            args,
+            results,
+        })
+    }
+}
+
+/// A section of a component. Unlike a core module, a component may repeat
+/// and interleave sections, and the order determines the index spaces, so
+/// they are kept in the order they were read.
+pub enum ComponentSection {
+    Custom(CustomSec),
+    CoreModule(Box<Module>),
+    CoreInstance(Vec<CoreInstance>),
+    CoreType(Vec<CoreType>),
+    Component(Box<Component>),
+    Instance(Vec<Instance>),
+    Alias(Vec<Alias>),
+    Type(Vec<ComponentTypeDef>),
+    Canon(Vec<Canon>),
+    Start(Start),
+    Import(Vec<ComponentImport>),
+    Export(Vec<ComponentExport>),
+}
+
+impl ComponentSection {
+    pub fn section_type(&self) -> &str {
+        match self {
+            ComponentSection::Custom(_) => "custom",
+            ComponentSection::CoreModule(_) => "core module",
+            ComponentSection::CoreInstance(_) => "core instance",
+            ComponentSection::CoreType(_) => "core type",
+            ComponentSection::Component(_) => "component",
+            ComponentSection::Instance(_) => "instance",
+            ComponentSection::Alias(_) => "alias",
+            ComponentSection::Type(_) => "type",
+            ComponentSection::Canon(_) => "canon",
+            ComponentSection::Start(_) => "start",
+            ComponentSection::Import(_) => "import",
+            ComponentSection::Export(_) => "export",
+        }
+    }
+}
+
+fn write_items<T: Display>(f: &mut std::fmt::Formatter<'_>, items: &[T]) -> std::fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        writeln!(f, "* {}: {}", i, item)?;
+    }
+    Ok(())
+}
+
+impl Display for ComponentSection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentSection::Custom(sec) => write!(f, "{}", sec),
+            ComponentSection::CoreModule(module) => {
+                writeln!(f, "Version: {}", module.version)?;
+                for section in module.sections() {
+                    writeln!(f, "* {}", section.section_type())?;
+                }
+                Ok(())
+            }
+            ComponentSection::CoreInstance(instances) => write_items(f, instances),
+            ComponentSection::CoreType(types) => {
+                for (i, ty) in types.iter().enumerate() {
+                    write!(f, "* {}:\n{}", i, ty)?;
+                }
+                Ok(())
+            }
+            ComponentSection::Component(component) => write!(f, "{}", component.world()),
+            ComponentSection::Instance(instances) => write_items(f, instances),
+            ComponentSection::Alias(aliases) => write_items(f, aliases),
+            ComponentSection::Type(types) => write_items(f, types),
+            ComponentSection::Canon(canons) => write_items(f, canons),
+            ComponentSection::Start(start) => {
+                writeln!(
+                    f,
+                    "* func {} args {:?} results {}",
+                    start.func, start.args, start.results
+                )
+            }
+            ComponentSection::Import(imports) => write_items(f, imports),
+            ComponentSection::Export(exports) => write_items(f, exports),
+        }
+    }
+}
+
+pub enum ComponentParseError {
+    Parse(ParseError),
+    BadMagic([u8; 4]),
+    InvalidVersion(u16),
+    /// The header has layer 0: this is a core module.
+    NotAComponent,
+    UnknownSection(u8),
+    Module(ModuleParseError),
+}
+
+impl From<ParseError> for ComponentParseError {
+    fn from(value: ParseError) -> Self {
+        ComponentParseError::Parse(value)
+    }
+}
+
+impl From<ModuleParseError> for ComponentParseError {
+    fn from(value: ModuleParseError) -> Self {
+        ComponentParseError::Module(value)
+    }
+}
+
+impl From<CustomSecParseError> for ComponentParseError {
+    fn from(value: CustomSecParseError) -> Self {
+        ComponentParseError::Module(value.into())
+    }
+}
+
+impl From<ComponentParseError> for ParseError {
+    fn from(value: ComponentParseError) -> Self {
+        match value {
+            ComponentParseError::Parse(err) => err,
+            ComponentParseError::BadMagic(magic) => {
+                ParseError::Other(format!("bad magic: {:#?}", magic))
+            }
+            ComponentParseError::InvalidVersion(version) => {
+                ParseError::Other(format!("bad component version: {:#x}", version))
+            }
+            ComponentParseError::NotAComponent => {
+                ParseError::Other("not a component: this is a core module".to_string())
+            }
+            ComponentParseError::UnknownSection(id) => {
+                ParseError::Other(format!("malformed component section id: {}", id))
+            }
+            ComponentParseError::Module(err) => err.into(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Component {
+    pub version: u16,
+    pub sections: Vec<ComponentSection>,
+}
+
+impl Component {
+    /// Whether `bytes` start with a component header rather than a core
+    /// module header.
+    pub fn is_component(bytes: &[u8]) -> bool {
+        bytes.len() >= 8 && bytes[0..4] == MAGIC && bytes[6..8] == COMPONENT_LAYER.to_le_bytes()
+    }
+
+    /// Reads a component header, returning its version.
+    fn parse_header(reader: &mut BufReader<dyn Read>) -> Result<u16, ComponentParseError> {
+        let magic = u32::parse(reader)?.to_le_bytes();
+        if magic != MAGIC {
+            return Err(ComponentParseError::BadMagic(magic));
+        }
+        let word = u32::parse(reader)?;
+        let version = (word & 0xffff) as u16;
+        let layer = (word >> 16) as u16;
+        if layer != COMPONENT_LAYER {
+            return Err(ComponentParseError::NotAComponent);
+        }
+        if version != COMPONENT_VERSION {
+            return Err(ComponentParseError::InvalidVersion(version));
+        }
+        Ok(version)
+    }
+
+    /// Reads the LEB128 size of a section, returning its bytes along with
+    /// the size.
+    fn read_size(reader: &mut BufReader<dyn Read>) -> Result<(Vec<u8>, u32), ParseError> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = u8::parse(reader)?;
+            bytes.push(byte);
+            if byte & 0x80 == 0 || bytes.len() > 5 {
+                break;
+            }
+        }
+        let mut leb = BufReader::new(Cursor::new(bytes.clone()));
+        let size = u32::from(Leb128::<u32>::parse(&mut leb)?);
+        Ok((bytes, size))
+    }
+
+    /// Reads one section's size and contents so that it can be parsed on
+    /// its own and checked against its declared size. The size's LEB128
+    /// bytes are kept in front, as custom sections parse their own size.
+    /// The section, which starts after `offset` bytes of the component,
+    /// has to end within the module size limit before anything is read.
+    fn read_section(reader: &mut BufReader<dyn Read>, offset: u64) -> Result<Vec<u8>, ParseError> {
+        let (mut bytes, size) = Self::read_size(reader)?;
+        let prefix = bytes.len();
+        ParserLimits::current().check_module_size(offset + (prefix as u64) + u64::from(size))?;
+        reader.take(u64::from(size)).read_to_end(&mut bytes)?;
+        if bytes.len() - prefix != size as usize {
+            return Err(ParseError::wrong_num_bytes_read(
+                Asked(size as usize),
+                Received(bytes.len() - prefix),
+            ));
+        }
+        Ok(bytes)
+    }
+
+    /// A reader for the contents of a section read by `read_section`,
+    /// past its size.
+    fn contents(bytes: &[u8]) -> Result<BufReader<Cursor<Vec<u8>>>, ParseError> {
+        let mut reader = BufReader::new(Cursor::new(bytes.to_vec()));
+        Leb128::<u32>::parse(&mut reader)?;
+        Ok(reader)
+    }
+
+    fn parse_items<T: Parseable>(bytes: Vec<u8>) -> Result<Vec<T>, ParseError> {
+        let len = bytes.len() as u64;
+        let mut reader = Self::contents(&bytes)?;
+        let items = Vec::<T>::parse(&mut reader)?;
+        if reader.get_ref().position() != len || !reader.buffer().is_empty() {
+            return Err(ParseError::Other("section size mismatch".to_string()));
+        }
+        Ok(items)
+    }
+
+    fn parse_section(id: u8, bytes: Vec<u8>) -> Result<ComponentSection, ComponentParseError> {
+        let section_type = TypeIdx(u32::from(id));
+        let section = if section_type == CUSTOM_SECTION_ID {
+            let mut reader = BufReader::new(Cursor::new(bytes));
+            ComponentSection::Custom(CustomSec::parse(&mut reader)?)
+        } else if section_type == CORE_MODULE_SECTION_ID {
+            let mut nested = Self::contents(&bytes)?;
+            ComponentSection::CoreModule(Box::new(Module::parse(&mut nested)?))
+        } else if section_type == CORE_INSTANCE_SECTION_ID {
+            ComponentSection::CoreInstance(Self::parse_items(bytes)?)
+        } else if section_type == CORE_TYPE_SECTION_ID {
+            ComponentSection::CoreType(Self::parse_items(bytes)?)
+        } else if section_type == INSTANCE_SECTION_ID {
+            ComponentSection::Instance(Self::parse_items(bytes)?)
+        } else if section_type == ALIAS_SECTION_ID {
+            ComponentSection::Alias(Self::parse_items(bytes)?)
+        } else if section_type == TYPE_SECTION_ID {
+            ComponentSection::Type(Self::parse_items(bytes)?)
+        } else if section_type == CANON_SECTION_ID {
+            ComponentSection::Canon(Self::parse_items(bytes)?)
+        } else if section_type == START_SECTION_ID {
+            let mut nested = Self::contents(&bytes)?;
+            ComponentSection::Start(Start::parse(&mut nested)?)
+        } else if section_type == IMPORT_SECTION_ID {
+            ComponentSection::Import(Self::parse_items(bytes)?)
+        } else if section_type == EXPORT_SECTION_ID {
+            ComponentSection::Export(Self::parse_items(bytes)?)
+        } else {
+            return Err(ComponentParseError::UnknownSection(id));
+        };
+        Ok(section)
+    }
+
+    /// Parses a component. Nested components are read from the same reader
+    /// rather than recursively, so that deep nesting can't overflow the
+    /// stack, and their depth is held to the nesting limit.
+    pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Component, ComponentParseError> {
+        let mut component = Component {
+            version: Self::parse_header(reader)?,
+            ..Default::default()
+        };
+        let mut offset: u64 = 8;
+        // The components that the one being parsed is nested in, innermost
+        // last, each with the offset that ends the nested one.
+        let mut outer: Vec<(Component, u64)> = Vec::new();
+
+        loop {
+            if let Some((_, end)) = outer.last() {
+                if offset > *end {
+                    return Err(ParseError::Other("section size mismatch".to_string()).into());
+                }
+                if offset == *end {
+                    let (parent, _) = outer.pop().unwrap();
+                    let nested = std::mem::replace(&mut component, parent);
+                    component
+                        .sections
+                        .push(ComponentSection::Component(Box::new(nested)));
+                    continue;
+                }
+            }
+
+            let id = match u8::parse(reader) {
+                Ok(n) => n,
+                Err(ParseError::WrongNumBytesRead(_, Received(0))) if outer.is_empty() => {
+                    // We are out of bytes.
+                    break;
+                }
+                Err(e) => return Err(ComponentParseError::Parse(e)),
+            };
+            offset += 1;
+
+            if TypeIdx(u32::from(id)) == COMPONENT_SECTION_ID {
+                let (prefix, size) = Self::read_size(reader)?;
+                offset += prefix.len() as u64;
+                let end = offset + u64::from(size);
+                let limits = ParserLimits::current();
+                limits
+                    .check_nesting(outer.len() + 1)
+                    .and_then(|_| limits.check_module_size(end))
+                    .map_err(ModuleParseError::Parse)?;
+                let nested = Component {
+                    version: Self::parse_header(reader)?,
+                    ..Default::default()
+                };
+                offset += 8;
+                outer.push((std::mem::replace(&mut component, nested), end));
+                continue;
+            }
+
+            let bytes = Self::read_section(reader, offset)?;
+            offset += bytes.len() as u64;
+            component.sections.push(Self::parse_section(id, bytes)?);
+        }
+
+        Ok(component)
+    }
+
+    /// The core modules embedded directly in this component.
+    pub fn modules(&self) -> impl Iterator<Item = &Module> {
+        self.sections.iter().filter_map(|section| match section {
+            ComponentSection::CoreModule(module) => Some(module.as_ref()),
+            _ => None,
+        })
+    }
+
+    pub fn imports(&self) -> impl Iterator<Item = &ComponentImport> {
+        self.sections
+            .iter()
+            .filter_map(|section| match section {
+                ComponentSection::Import(imports) => Some(imports),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    pub fn exports(&self) -> impl Iterator<Item = &ComponentExport> {
+        self.sections
+            .iter()
+            .filter_map(|section| match section {
+                ComponentSection::Export(exports) => Some(exports),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// The component's interface as WIT: its imports and exports with their
+    /// types resolved.
+    pub fn world(&self) -> World {
+        World::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::leb128;
+
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00];
+
+    fn parse(sections: &[u8]) -> Result<Component, ComponentParseError> {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(sections);
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        Component::parse(&mut reader)
+    }
+
+    #[test]
+    fn test_component() {
+        let sections = [
+            // A nested core module with no sections
+            0x01, 0x08, 0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            // (type (func (param "x" string) (result u32)))
+            // (type (result (error string)))
+            0x07, 0x0c, 0x02, 0x40, 0x01, 0x01, 0x78, 0x73, 0x00, 0x79, 0x6a, 0x00, 0x01, 0x73,
+            // (import "log" (func (type 0)))
+            0x0a, 0x08, 0x01, 0x00, 0x03, 0x6c, 0x6f, 0x67, 0x01, 0x00,
+            // (export "run" (func 0)) (export "err" (type 1))
+            0x0b, 0x11, 0x02, 0x00, 0x03, 0x72, 0x75, 0x6e, 0x01, 0x00, 0x00, 0x00, 0x03, 0x65,
+            0x72, 0x72, 0x03, 0x01, 0x00,
+        ];
+        let component = match parse(&sections) {
+            Ok(component) => component,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+        assert_eq!(component.sections.len(), 4);
+        assert_eq!(component.modules().count(), 1);
+        assert_eq!(component.imports().count(), 1);
+        assert_eq!(component.exports().count(), 2);
+        assert!(Component::is_component(&HEADER));
+
+        assert_eq!(
+            component.world().items(),
+            &[
+                "import log: func(x: string) -> u32;",
+                "export run: func(x: string) -> u32;",
+                "type err = result<_, string>;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_instance_import() {
+        // (type (instance
+        //   (export "stream" (type (sub resource)))
+        //   (type (own 0))
+        //   (type (func (result 1)))
+        //   (export "get" (func (type 2)))))
+        // (import "wasi:io/out" (instance (type 0)))
+        let sections = [
+            0x07, 0x1e, 0x01, 0x42, 0x04, 0x04, 0x00, 0x06, 0x73, 0x74, 0x72, 0x65, 0x61, 0x6d,
+            0x03, 0x01, 0x01, 0x69, 0x00, 0x01, 0x40, 0x00, 0x00, 0x01, 0x04, 0x00, 0x03, 0x67,
+            0x65, 0x74, 0x01, 0x02, 0x0a, 0x10, 0x01, 0x00, 0x0b, 0x77, 0x61, 0x73, 0x69, 0x3a,
+            0x69, 0x6f, 0x2f, 0x6f, 0x75, 0x74, 0x05, 0x00,
+        ];
+        let component = match parse(&sections) {
+            Ok(component) => component,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+        assert_eq!(
+            component.world().to_string(),
+            "world component {\n  import wasi:io/out: interface {\n    resource stream;\n    get: func() -> own<stream>;\n  }\n}\n"
+        );
+    }
+
+    // The sections of a component with `depth` components nested inside
+    // each other, the innermost one empty
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut lens = vec![HEADER.len() as u64];
+        for k in 0..depth {
+            let inner = lens[k];
+            lens.push(HEADER.len() as u64 + 1 + leb128::unsigned_len(inner) as u64 + inner);
+        }
+        let mut bytes = Vec::new();
+        for k in (0..depth).rev() {
+            bytes.push(0x04);
+            leb128::write_unsigned(&mut bytes, lens[k], 0).unwrap();
+            bytes.extend_from_slice(&HEADER);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let default = ParserLimits::default().max_nesting;
+        let component = match parse(&nested(default)) {
+            Ok(component) => component,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+        let mut depth = 0;
+        let mut innermost = &component;
+        while let Some(ComponentSection::Component(nested)) = innermost.sections.first() {
+            innermost = nested;
+            depth += 1;
+        }
+        assert_eq!(depth, default);
+
+        // Deeper nesting used to overflow the stack
+        match parse(&nested(20_000)) {
+            Ok(_) => panic!("Expected an error"),
+            Err(err) => assert_eq!(
+                ParseError::from(err),
+                ParseError::Other(format!("nesting exceeds the limit of {}", default))
+            ),
+        }
+
+        let limits = ParserLimits {
+            max_nesting: 2,
+            ..ParserLimits::default()
+        };
+        assert!(limits.apply(|| parse(&nested(2))).is_ok());
+        assert!(matches!(
+            limits.apply(|| parse(&nested(3))),
+            Err(ComponentParseError::Module(ModuleParseError::Parse(_)))
+        ));
+
+        // A nested component has to fit in the module size limit
+        let limits = ParserLimits {
+            max_module_size: 20,
+            ..ParserLimits::default()
+        };
+        assert!(limits.apply(|| parse(&nested(1))).is_ok());
+        assert!(limits.apply(|| parse(&nested(2))).is_err());
+
+        // So does every other section, before its contents are read
+        match limits.apply(|| parse(&[0x00, 0xff, 0x7f])) {
+            Ok(_) => panic!("Expected an error"),
+            Err(err) => assert_eq!(
+                ParseError::from(err),
+                ParseError::Other("module exceeds the size limit of 20 bytes".to_string())
+            ),
+        }
+
+        // A nested component may not run past the section holding it
+        let mut sections = nested(2);
+        sections[1] -= 1;
+        assert!(parse(&sections).is_err());
+    }
+
+    #[test]
+    fn test_core_module_header() {
+        let mut reader = BufReader::new(Cursor::new(
+            crate::module::MAGIC
+                .to_vec()
+                .into_iter()
+                .chain([0x01, 0x00, 0x00, 0x00])
+                .collect::<Vec<u8>>(),
+        ));
+        assert!(matches!(
+            Component::parse(&mut reader),
+            Err(ComponentParseError::NotAComponent)
+        ));
+
+        let mut reader = BufReader::new(Cursor::new(HEADER));
+        match Module::parse(&mut reader) {
+            Ok(_) => panic!("Expected an error"),
+            Err(err) => assert_eq!(
+                ParseError::from(err),
+                ParseError::Other(
+                    "unknown binary version 0x1000d (this is a component)".to_string()
+                )
+            ),
+        }
+
+        // Section contents must match the declared size
+        assert!(parse(&[0x0a, 0x03, 0x00, 0x00, 0x00]).is_err());
+    }
+}
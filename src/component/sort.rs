@@ -0,0 +1,145 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read};
+
+use crate::component::parse_idx;
+use crate::parseable::{ParseError, Parseable, Result};
+
+/// The kinds of core definitions a component can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreSort {
+    Func,
+    Table,
+    Memory,
+    Global,
+    Tag,
+    Type,
+    Module,
+    Instance,
+}
+
+impl Parseable for CoreSort {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => Ok(CoreSort::Func),
+            0x01 => Ok(CoreSort::Table),
+            0x02 => Ok(CoreSort::Memory),
+            0x03 => Ok(CoreSort::Global),
+            0x04 => Ok(CoreSort::Tag),
+            0x10 => Ok(CoreSort::Type),
+            0x11 => Ok(CoreSort::Module),
+            0x12 => Ok(CoreSort::Instance),
+            sort => Err(ParseError::Other(format!(
+                "invalid core sort: {:#04x}",
+                sort
+            ))),
+        }
+    }
+}
+
+impl Display for CoreSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CoreSort::Func => "func",
+            CoreSort::Table => "table",
+            CoreSort::Memory => "memory",
+            CoreSort::Global => "global",
+            CoreSort::Tag => "tag",
+            CoreSort::Type => "type",
+            CoreSort::Module => "module",
+            CoreSort::Instance => "instance",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The kinds of definitions in a component, each with its own index space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Core(CoreSort),
+    Func,
+    Value,
+    Type,
+    Component,
+    Instance,
+}
+
+impl Parseable for Sort {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => Ok(Sort::Core(CoreSort::parse(reader)?)),
+            0x01 => Ok(Sort::Func),
+            0x02 => Ok(Sort::Value),
+            0x03 => Ok(Sort::Type),
+            0x04 => Ok(Sort::Component),
+            0x05 => Ok(Sort::Instance),
+            sort => Err(ParseError::Other(format!("invalid sort: {:#04x}", sort))),
+        }
+    }
+}
+
+impl Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sort::Core(sort) => write!(f, "core {}", sort),
+            Sort::Func => write!(f, "func"),
+            Sort::Value => write!(f, "value"),
+            Sort::Type => write!(f, "type"),
+            Sort::Component => write!(f, "component"),
+            Sort::Instance => write!(f, "instance"),
+        }
+    }
+}
+
+/// A reference to a core definition by sort and index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreSortIdx {
+    pub sort: CoreSort,
+    pub idx: u32,
+}
+
+impl Parseable for CoreSortIdx {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let sort = CoreSort::parse(reader)?;
+        let idx = parse_idx(reader)?;
+        Ok(CoreSortIdx { sort, idx })
+    }
+}
+
+impl Display for CoreSortIdx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.sort, self.idx)
+    }
+}
+
+/// A reference to a component-level definition by sort and index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortIdx {
+    pub sort: Sort,
+    pub idx: u32,
+}
+
+impl Parseable for SortIdx {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let sort = Sort::parse(reader)?;
+        let idx = parse_idx(reader)?;
+        Ok(SortIdx { sort, idx })
+    }
+}
+
+impl Display for SortIdx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.sort, self.idx)
+    }
+}
@@ -0,0 +1,616 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read};
+
+use crate::component::alias::{Alias, CoreAlias};
+use crate::component::import::{ExternDesc, parse_extern_name};
+use crate::component::parse_idx;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::section::import::Import;
+use crate::types::import_desc::ImportDesc;
+use crate::types::leb128::Leb128;
+use crate::types::rec_type::RecType;
+
+/// Reads `<T>?`: a 0x00 byte for none, or 0x01 followed by the value.
+pub(crate) fn parse_optional<T: Parseable>(reader: &mut BufReader<dyn Read>) -> Result<Option<T>> {
+    match u8::parse(reader)? {
+        0x00 => Ok(None),
+        0x01 => Ok(Some(T::parse(reader)?)),
+        byte => Err(ParseError::Other(format!(
+            "invalid optional flag: {:#04x}",
+            byte
+        ))),
+    }
+}
+
+fn peek(reader: &mut BufReader<dyn Read>) -> Result<u8> {
+    match reader.fill_buf()?.first() {
+        Some(byte) => Ok(*byte),
+        None => Err(ParseError::Other("unexpected end".to_string())),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimValType {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+    ErrorContext,
+}
+
+impl PrimValType {
+    pub fn from_byte(byte: u8) -> Option<PrimValType> {
+        let prim = match byte {
+            0x7f => PrimValType::Bool,
+            0x7e => PrimValType::S8,
+            0x7d => PrimValType::U8,
+            0x7c => PrimValType::S16,
+            0x7b => PrimValType::U16,
+            0x7a => PrimValType::S32,
+            0x79 => PrimValType::U32,
+            0x78 => PrimValType::S64,
+            0x77 => PrimValType::U64,
+            0x76 => PrimValType::F32,
+            0x75 => PrimValType::F64,
+            0x74 => PrimValType::Char,
+            0x73 => PrimValType::String,
+            0x64 => PrimValType::ErrorContext,
+            _ => return None,
+        };
+        Some(prim)
+    }
+
+    /// The type's name in WIT.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PrimValType::Bool => "bool",
+            PrimValType::S8 => "s8",
+            PrimValType::U8 => "u8",
+            PrimValType::S16 => "s16",
+            PrimValType::U16 => "u16",
+            PrimValType::S32 => "s32",
+            PrimValType::U32 => "u32",
+            PrimValType::S64 => "s64",
+            PrimValType::U64 => "u64",
+            PrimValType::F32 => "f32",
+            PrimValType::F64 => "f64",
+            PrimValType::Char => "char",
+            PrimValType::String => "string",
+            PrimValType::ErrorContext => "error-context",
+        }
+    }
+}
+
+/// A value type: either a primitive written inline or a reference to a
+/// defined type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentValType {
+    Primitive(PrimValType),
+    Type(u32),
+}
+
+impl Parseable for ComponentValType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        if let Some(prim) = PrimValType::from_byte(peek(reader)?) {
+            reader.consume(1);
+            return Ok(ComponentValType::Primitive(prim));
+        }
+        // Type indices share the s33 encoding with the primitive bytes
+        let idx = i64::from(Leb128::<i64>::parse_s33(reader)?);
+        match u32::try_from(idx) {
+            Ok(idx) => Ok(ComponentValType::Type(idx)),
+            Err(_) => Err(ParseError::Other(format!("invalid value type: {}", idx))),
+        }
+    }
+}
+
+impl Display for ComponentValType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentValType::Primitive(prim) => write!(f, "{}", prim.name()),
+            ComponentValType::Type(idx) => write!(f, "(type {})", idx),
+        }
+    }
+}
+
+/// A record field or function parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedValType {
+    pub name: String,
+    pub ty: ComponentValType,
+}
+
+impl Parseable for NamedValType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = String::parse(reader)?;
+        let ty = ComponentValType::parse(reader)?;
+        Ok(NamedValType { name, ty })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub name: String,
+    pub ty: Option<ComponentValType>,
+}
+
+impl Parseable for Case {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let name = String::parse(reader)?;
+        let ty = parse_optional::<ComponentValType>(reader)?;
+        // The removed `refines` field must be absent
+        if u8::parse(reader)? != 0x00 {
+            return Err(ParseError::Other(
+                "variant case refines is not supported".to_string(),
+            ));
+        }
+        Ok(Case { name, ty })
+    }
+}
+
+/// A value type defined in a type section.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefValType {
+    Primitive(PrimValType),
+    Record(Vec<NamedValType>),
+    Variant(Vec<Case>),
+    List(ComponentValType),
+    Tuple(Vec<ComponentValType>),
+    Flags(Vec<String>),
+    Enum(Vec<String>),
+    Option(ComponentValType),
+    Result {
+        ok: Option<ComponentValType>,
+        err: Option<ComponentValType>,
+    },
+    Own(u32),
+    Borrow(u32),
+}
+
+impl Parseable for DefValType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let byte = u8::parse(reader)?;
+        if let Some(prim) = PrimValType::from_byte(byte) {
+            return Ok(DefValType::Primitive(prim));
+        }
+        let ty = match byte {
+            0x72 => DefValType::Record(Vec::<NamedValType>::parse(reader)?),
+            0x71 => DefValType::Variant(Vec::<Case>::parse(reader)?),
+            0x70 => DefValType::List(ComponentValType::parse(reader)?),
+            0x6f => DefValType::Tuple(Vec::<ComponentValType>::parse(reader)?),
+            0x6e => DefValType::Flags(Vec::<String>::parse(reader)?),
+            0x6d => DefValType::Enum(Vec::<String>::parse(reader)?),
+            0x6b => DefValType::Option(ComponentValType::parse(reader)?),
+            0x6a => {
+                let ok = parse_optional::<ComponentValType>(reader)?;
+                let err = parse_optional::<ComponentValType>(reader)?;
+                DefValType::Result { ok, err }
+            }
+            0x69 => DefValType::Own(parse_idx(reader)?),
+            0x68 => DefValType::Borrow(parse_idx(reader)?),
+            byte => {
+                return Err(ParseError::Other(format!(
+                    "invalid defined value type: {:#04x}",
+                    byte
+                )));
+            }
+        };
+        Ok(ty)
+    }
+}
+
+impl Display for DefValType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DefValType::Primitive(prim) => write!(f, "{}", prim.name()),
+            DefValType::Record(fields) => {
+                write!(f, "(record")?;
+                for field in fields {
+                    write!(f, " (field \"{}\" {})", field.name, field.ty)?;
+                }
+                write!(f, ")")
+            }
+            DefValType::Variant(cases) => {
+                write!(f, "(variant")?;
+                for case in cases {
+                    write!(f, " (case \"{}\"", case.name)?;
+                    if let Some(ty) = &case.ty {
+                        write!(f, " {}", ty)?;
+                    }
+                    write!(f, ")")?;
+                }
+                write!(f, ")")
+            }
+            DefValType::List(ty) => write!(f, "(list {})", ty),
+            DefValType::Tuple(tys) => {
+                write!(f, "(tuple")?;
+                for ty in tys {
+                    write!(f, " {}", ty)?;
+                }
+                write!(f, ")")
+            }
+            DefValType::Flags(names) | DefValType::Enum(names) => {
+                let kind = if matches!(self, DefValType::Flags(_)) {
+                    "flags"
+                } else {
+                    "enum"
+                };
+                write!(f, "({}", kind)?;
+                for name in names {
+                    write!(f, " \"{}\"", name)?;
+                }
+                write!(f, ")")
+            }
+            DefValType::Option(ty) => write!(f, "(option {})", ty),
+            DefValType::Result { ok, err } => {
+                write!(f, "(result")?;
+                if let Some(ok) = ok {
+                    write!(f, " {}", ok)?;
+                }
+                if let Some(err) = err {
+                    write!(f, " (error {})", err)?;
+                }
+                write!(f, ")")
+            }
+            DefValType::Own(idx) => write!(f, "(own {})", idx),
+            DefValType::Borrow(idx) => write!(f, "(borrow {})", idx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuncResults {
+    Unnamed(ComponentValType),
+    Named(Vec<NamedValType>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentFuncType {
+    pub params: Vec<NamedValType>,
+    pub results: FuncResults,
+}
+
+impl ComponentFuncType {
+    fn parse_body(reader: &mut BufReader<dyn Read>) -> Result<Self> {
+        let params = Vec::<NamedValType>::parse(reader)?;
+        let results = match u8::parse(reader)? {
+            0x00 => FuncResults::Unnamed(ComponentValType::parse(reader)?),
+            0x01 => FuncResults::Named(Vec::<NamedValType>::parse(reader)?),
+            byte => {
+                return Err(ParseError::Other(format!(
+                    "invalid result list: {:#04x}",
+                    byte
+                )));
+            }
+        };
+        Ok(ComponentFuncType { params, results })
+    }
+}
+
+/// A declaration inside a core module type.
+pub enum ModuleDecl {
+    Import(Import),
+    Type(CoreType),
+    Alias(CoreAlias),
+    Export(String, ImportDesc),
+}
+
+impl Parseable for ModuleDecl {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => Ok(ModuleDecl::Import(Import::parse(reader)?)),
+            0x01 => Ok(ModuleDecl::Type(CoreType::parse(reader)?)),
+            0x02 => Ok(ModuleDecl::Alias(CoreAlias::parse(reader)?)),
+            0x03 => {
+                let name = String::parse(reader)?;
+                Ok(ModuleDecl::Export(name, ImportDesc::parse(reader)?))
+            }
+            kind => Err(ParseError::Other(format!(
+                "invalid module type declaration: {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+/// A type in a component's core type section.
+pub enum CoreType {
+    Rec(RecType),
+    Module(Vec<ModuleDecl>),
+}
+
+impl Parseable for CoreType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match peek(reader)? {
+            // Module types were written as a bare 0x50 before GC claimed that
+            // byte for `sub`, and are now prefixed with 0x00.
+            0x00 => {
+                reader.consume(1);
+                if u8::parse(reader)? != 0x50 {
+                    return Err(ParseError::Other("expected a module type".to_string()));
+                }
+                Ok(CoreType::Module(Vec::<ModuleDecl>::parse(reader)?))
+            }
+            0x50 => {
+                reader.consume(1);
+                Ok(CoreType::Module(Vec::<ModuleDecl>::parse(reader)?))
+            }
+            _ => Ok(CoreType::Rec(RecType::parse(reader)?)),
+        }
+    }
+}
+
+impl Display for CoreType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoreType::Rec(rec) if rec.types().len() == 1 => {
+                write!(f, "{}", rec.types()[0].comp_type())
+            }
+            CoreType::Rec(rec) => writeln!(f, "* rec: {} types", rec.types().len()),
+            CoreType::Module(decls) => writeln!(f, "* module: {} declarations", decls.len()),
+        }
+    }
+}
+
+/// A declaration inside an instance type.
+pub enum InstanceDecl {
+    CoreType(CoreType),
+    Type(ComponentTypeDef),
+    Alias(Alias),
+    Export(String, ExternDesc),
+}
+
+impl Parseable for InstanceDecl {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => Ok(InstanceDecl::CoreType(CoreType::parse(reader)?)),
+            0x01 => Ok(InstanceDecl::Type(ComponentTypeDef::parse(reader)?)),
+            0x02 => Ok(InstanceDecl::Alias(Alias::parse(reader)?)),
+            0x04 => {
+                let name = parse_extern_name(reader)?;
+                Ok(InstanceDecl::Export(name, ExternDesc::parse(reader)?))
+            }
+            kind => Err(ParseError::Other(format!(
+                "invalid instance type declaration: {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+/// A declaration inside a component type: an instance declaration or an
+/// import.
+pub enum ComponentDecl {
+    Import(String, ExternDesc),
+    Instance(InstanceDecl),
+}
+
+impl Parseable for ComponentDecl {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        if peek(reader)? == 0x03 {
+            reader.consume(1);
+            let name = parse_extern_name(reader)?;
+            return Ok(ComponentDecl::Import(name, ExternDesc::parse(reader)?));
+        }
+        Ok(ComponentDecl::Instance(InstanceDecl::parse(reader)?))
+    }
+}
+
+/// A type in a component's type section.
+pub enum ComponentTypeDef {
+    Defined(DefValType),
+    Func(ComponentFuncType),
+    Component(Vec<ComponentDecl>),
+    Instance(Vec<InstanceDecl>),
+    /// A resource represented as an i32, with an optional destructor core
+    /// function.
+    Resource {
+        dtor: Option<u32>,
+    },
+}
+
+impl Parseable for ComponentTypeDef {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let ty = match peek(reader)? {
+            0x40 => {
+                reader.consume(1);
+                ComponentTypeDef::Func(ComponentFuncType::parse_body(reader)?)
+            }
+            0x41 => {
+                reader.consume(1);
+                ComponentTypeDef::Component(Vec::<ComponentDecl>::parse(reader)?)
+            }
+            0x42 => {
+                reader.consume(1);
+                ComponentTypeDef::Instance(Vec::<InstanceDecl>::parse(reader)?)
+            }
+            0x3f => {
+                reader.consume(1);
+                if u8::parse(reader)? != 0x7f {
+                    return Err(ParseError::Other(
+                        "resource representation must be i32".to_string(),
+                    ));
+                }
+                let dtor = match u8::parse(reader)? {
+                    0x00 => None,
+                    0x01 => Some(parse_idx(reader)?),
+                    byte => {
+                        return Err(ParseError::Other(format!(
+                            "invalid optional flag: {:#04x}",
+                            byte
+                        )));
+                    }
+                };
+                ComponentTypeDef::Resource { dtor }
+            }
+            _ => ComponentTypeDef::Defined(DefValType::parse(reader)?),
+        };
+        Ok(ty)
+    }
+}
+
+impl Display for ComponentTypeDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentTypeDef::Defined(ty) => write!(f, "{}", ty),
+            ComponentTypeDef::Func(func) => {
+                write!(f, "func")?;
+                for param in &func.params {
+                    write!(f, " (param \"{}\" {})", param.name, param.ty)?;
+                }
+                match &func.results {
+                    FuncResults::Unnamed(ty) => write!(f, " (result {})", ty),
+                    FuncResults::Named(results) => {
+                        for result in results {
+                            write!(f, " (result \"{}\" {})", result.name, result.ty)?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            ComponentTypeDef::Component(decls) => {
+                write!(f, "component: {} declarations", decls.len())
+            }
+            ComponentTypeDef::Instance(decls) => {
+                write!(f, "instance: {} declarations", decls.len())
+            }
+            ComponentTypeDef::Resource { dtor: Some(dtor) } => {
+                write!(f, "resource (rep i32) (dtor {})", dtor)
+            }
+            ComponentTypeDef::Resource { dtor: None } => write!(f, "resource (rep i32)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn parse<T: Parseable>(bytes: &[u8]) -> T {
+        let mut reader = BufReader::new(Cursor::new(bytes.to_vec()));
+        match T::parse(&mut reader) {
+            Ok(val) => val,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn test_def_val_types() {
+        // (record (field "a" u32) (field "b" (type 3)))
+        let ty: DefValType = parse(&[0x72, 0x02, 0x01, 0x61, 0x79, 0x01, 0x62, 0x03]);
+        assert_eq!(
+            ty,
+            DefValType::Record(vec![
+                NamedValType {
+                    name: "a".to_string(),
+                    ty: ComponentValType::Primitive(PrimValType::U32),
+                },
+                NamedValType {
+                    name: "b".to_string(),
+                    ty: ComponentValType::Type(3),
+                },
+            ])
+        );
+
+        // (variant (case "none") (case "some" string))
+        let ty: DefValType = parse(&[
+            0x71, 0x02, 0x04, 0x6e, 0x6f, 0x6e, 0x65, 0x00, 0x00, 0x04, 0x73, 0x6f, 0x6d, 0x65,
+            0x01, 0x73, 0x00,
+        ]);
+        match ty {
+            DefValType::Variant(cases) => {
+                assert_eq!(cases[0].ty, None);
+                assert_eq!(
+                    cases[1].ty,
+                    Some(ComponentValType::Primitive(PrimValType::String))
+                );
+            }
+            _ => panic!("Expected a variant"),
+        }
+
+        // (result (error string))
+        let ty: DefValType = parse(&[0x6a, 0x00, 0x01, 0x73]);
+        assert_eq!(
+            ty,
+            DefValType::Result {
+                ok: None,
+                err: Some(ComponentValType::Primitive(PrimValType::String)),
+            }
+        );
+
+        assert_eq!(ty.to_string(), "(result (error string))");
+
+        let ty: DefValType = parse(&[0x69, 0x02]);
+        assert_eq!(ty, DefValType::Own(2));
+    }
+
+    #[test]
+    fn test_type_defs() {
+        // (func (param "x" string) (result u32))
+        let ty: ComponentTypeDef = parse(&[0x40, 0x01, 0x01, 0x78, 0x73, 0x00, 0x79]);
+        assert_eq!(ty.to_string(), "func (param \"x\" string) (result u32)");
+
+        // (resource (rep i32) (dtor 4))
+        let ty: ComponentTypeDef = parse(&[0x3f, 0x7f, 0x01, 0x04]);
+        assert!(matches!(ty, ComponentTypeDef::Resource { dtor: Some(4) }));
+
+        // (instance (alias outer 1 0 (type)) (export "t" (type (sub resource))))
+        let ty: ComponentTypeDef = parse(&[
+            0x42, 0x02, 0x02, 0x03, 0x02, 0x01, 0x00, 0x04, 0x00, 0x01, 0x74, 0x03, 0x01,
+        ]);
+        match ty {
+            ComponentTypeDef::Instance(decls) => {
+                assert_eq!(decls.len(), 2);
+                assert!(matches!(decls[1], InstanceDecl::Export(ref name, _) if name == "t"));
+            }
+            _ => panic!("Expected an instance type"),
+        }
+
+        // Both encodings of an empty core module type
+        assert!(matches!(
+            parse::<CoreType>(&[0x50, 0x00]),
+            CoreType::Module(_)
+        ));
+        assert!(matches!(
+            parse::<CoreType>(&[0x00, 0x50, 0x00]),
+            CoreType::Module(_)
+        ));
+    }
+}
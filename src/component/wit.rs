@@ -0,0 +1,422 @@
+use std::fmt::Display;
+
+use crate::component::alias::AliasTarget;
+use crate::component::canon::Canon;
+use crate::component::import::{ExternDesc, TypeBound};
+use crate::component::sort::Sort;
+use crate::component::types::{
+    ComponentDecl, ComponentFuncType, ComponentTypeDef, ComponentValType, DefValType, FuncResults,
+    InstanceDecl,
+};
+use crate::component::{Component, ComponentSection};
+
+/// What the WIT view knows about an entry in a type index space.
+#[derive(Clone)]
+struct TypeEntry<'a> {
+    /// The name the type was imported or exported under. WIT refers to
+    /// named types by name rather than spelling them out.
+    name: Option<String>,
+    def: Option<&'a ComponentTypeDef>,
+    /// The scope that the definition's own type indices refer to.
+    level: usize,
+}
+
+/// The type index spaces of a component and of the instance and component
+/// types nested in it, innermost last.
+#[derive(Clone, Default)]
+struct Scopes<'a> {
+    levels: Vec<Vec<TypeEntry<'a>>>,
+}
+
+impl<'a> Scopes<'a> {
+    fn level(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    fn push(&mut self, entry: TypeEntry<'a>) {
+        if let Some(types) = self.levels.last_mut() {
+            types.push(entry);
+        }
+    }
+
+    fn push_def(&mut self, def: &'a ComponentTypeDef) {
+        let level = self.level();
+        self.push(TypeEntry {
+            name: None,
+            def: Some(def),
+            level,
+        });
+    }
+
+    fn push_named(&mut self, name: &str, def: Option<TypeEntry<'a>>) {
+        let level = self.level();
+        let entry = match def {
+            Some(entry) => TypeEntry {
+                name: Some(name.to_string()),
+                ..entry
+            },
+            None => TypeEntry {
+                name: Some(name.to_string()),
+                def: None,
+                level,
+            },
+        };
+        self.push(entry);
+    }
+
+    fn lookup(&self, level: usize, idx: u32) -> Option<&TypeEntry<'a>> {
+        self.levels.get(level)?.get(idx as usize)
+    }
+
+    /// The entry that an outer alias `count` levels up refers to.
+    fn outer(&self, count: u32, idx: u32) -> Option<TypeEntry<'a>> {
+        let level = self.level().checked_sub(count as usize)?;
+        self.lookup(level, idx).cloned()
+    }
+
+    fn val_type(&self, level: usize, ty: &ComponentValType) -> String {
+        match ty {
+            ComponentValType::Primitive(prim) => prim.name().to_string(),
+            ComponentValType::Type(idx) => self.type_ref(level, *idx),
+        }
+    }
+
+    /// Refers to type `idx` by name if it has one, and spells it out
+    /// otherwise.
+    fn type_ref(&self, level: usize, idx: u32) -> String {
+        match self.lookup(level, idx) {
+            Some(TypeEntry {
+                name: Some(name), ..
+            }) => name.clone(),
+            Some(TypeEntry {
+                def: Some(def),
+                level,
+                ..
+            }) => self.def_type(*level, def),
+            _ => format!("type{}", idx),
+        }
+    }
+
+    fn opt_type(&self, level: usize, ty: &Option<ComponentValType>) -> String {
+        match ty {
+            Some(ty) => self.val_type(level, ty),
+            None => "_".to_string(),
+        }
+    }
+
+    fn def_val_type(&self, level: usize, ty: &DefValType) -> String {
+        let list = |items: Vec<String>| items.join(", ");
+        match ty {
+            DefValType::Primitive(prim) => prim.name().to_string(),
+            DefValType::Record(fields) => format!(
+                "record {{ {} }}",
+                list(
+                    fields
+                        .iter()
+                        .map(|field| format!("{}: {}", field.name, self.val_type(level, &field.ty)))
+                        .collect()
+                )
+            ),
+            DefValType::Variant(cases) => format!(
+                "variant {{ {} }}",
+                list(
+                    cases
+                        .iter()
+                        .map(|case| match &case.ty {
+                            Some(ty) => format!("{}({})", case.name, self.val_type(level, ty)),
+                            None => case.name.clone(),
+                        })
+                        .collect()
+                )
+            ),
+            DefValType::List(ty) => format!("list<{}>", self.val_type(level, ty)),
+            DefValType::Tuple(tys) => format!(
+                "tuple<{}>",
+                list(tys.iter().map(|ty| self.val_type(level, ty)).collect())
+            ),
+            DefValType::Flags(names) => format!("flags {{ {} }}", names.join(", ")),
+            DefValType::Enum(names) => format!("enum {{ {} }}", names.join(", ")),
+            DefValType::Option(ty) => format!("option<{}>", self.val_type(level, ty)),
+            DefValType::Result {
+                ok: None,
+                err: None,
+            } => "result".to_string(),
+            DefValType::Result { ok, err: None } => {
+                format!("result<{}>", self.opt_type(level, ok))
+            }
+            DefValType::Result { ok, err } => format!(
+                "result<{}, {}>",
+                self.opt_type(level, ok),
+                self.opt_type(level, err)
+            ),
+            DefValType::Own(idx) => format!("own<{}>", self.type_ref(level, *idx)),
+            DefValType::Borrow(idx) => format!("borrow<{}>", self.type_ref(level, *idx)),
+        }
+    }
+
+    fn func_type(&self, level: usize, func: &ComponentFuncType) -> String {
+        let params: Vec<String> = func
+            .params
+            .iter()
+            .map(|param| format!("{}: {}", param.name, self.val_type(level, &param.ty)))
+            .collect();
+        let results = match &func.results {
+            FuncResults::Unnamed(ty) => format!(" -> {}", self.val_type(level, ty)),
+            FuncResults::Named(results) if results.is_empty() => String::new(),
+            FuncResults::Named(results) => format!(
+                " -> ({})",
+                results
+                    .iter()
+                    .map(|result| format!("{}: {}", result.name, self.val_type(level, &result.ty)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+        format!("func({}){}", params.join(", "), results)
+    }
+
+    fn def_type(&self, level: usize, def: &ComponentTypeDef) -> String {
+        match def {
+            ComponentTypeDef::Defined(ty) => self.def_val_type(level, ty),
+            ComponentTypeDef::Func(func) => self.func_type(level, func),
+            ComponentTypeDef::Resource { .. } => "resource".to_string(),
+            ComponentTypeDef::Instance(decls) => {
+                let mut inner = self.nested(level);
+                let items: Vec<String> = decls
+                    .iter()
+                    .filter_map(|decl| inner.instance_decl(decl))
+                    .collect();
+                format!("interface {}", block(&items))
+            }
+            ComponentTypeDef::Component(decls) => {
+                let mut inner = self.nested(level);
+                let items: Vec<String> = decls
+                    .iter()
+                    .filter_map(|decl| match decl {
+                        ComponentDecl::Import(name, desc) => {
+                            Some(inner.extern_item("import ", name, desc))
+                        }
+                        ComponentDecl::Instance(decl) => inner
+                            .instance_decl(decl)
+                            .map(|item| format!("export {}", item)),
+                    })
+                    .collect();
+                format!("world {}", block(&items))
+            }
+        }
+    }
+
+    /// A fresh scope for the declarations of a type defined at `level`.
+    fn nested(&self, level: usize) -> Scopes<'a> {
+        let mut levels = self.levels[..=level].to_vec();
+        levels.push(Vec::new());
+        Scopes { levels }
+    }
+
+    /// Records an instance type declaration and describes it if it is an
+    /// export.
+    fn instance_decl(&mut self, decl: &'a InstanceDecl) -> Option<String> {
+        match decl {
+            InstanceDecl::CoreType(_) => None,
+            InstanceDecl::Type(def) => {
+                self.push_def(def);
+                None
+            }
+            InstanceDecl::Alias(alias) => {
+                if alias.sort == Sort::Type {
+                    let entry = match &alias.target {
+                        AliasTarget::Outer { count, idx } => self.outer(*count, *idx),
+                        AliasTarget::Export { name, .. } => Some(TypeEntry {
+                            name: Some(name.clone()),
+                            def: None,
+                            level: self.level(),
+                        }),
+                        AliasTarget::CoreExport { .. } => None,
+                    };
+                    let level = self.level();
+                    self.push(entry.unwrap_or(TypeEntry {
+                        name: None,
+                        def: None,
+                        level,
+                    }));
+                }
+                None
+            }
+            InstanceDecl::Export(name, desc) => Some(self.extern_item("", name, desc)),
+        }
+    }
+
+    /// Describes an imported or exported item, adding any type it defines
+    /// to the current scope.
+    fn extern_item(&mut self, prefix: &str, name: &str, desc: &ExternDesc) -> String {
+        let level = self.level();
+        match desc {
+            ExternDesc::Type(TypeBound::SubResource) => {
+                self.push_named(name, None);
+                format!("resource {};", name)
+            }
+            ExternDesc::Type(TypeBound::Eq(idx)) => {
+                let entry = self.lookup(level, *idx).cloned();
+                let def = match &entry {
+                    Some(TypeEntry {
+                        def: Some(def),
+                        level,
+                        ..
+                    }) => self.def_type(*level, def),
+                    _ => self.type_ref(level, *idx),
+                };
+                self.push_named(name, entry);
+                match def.split_once(' ') {
+                    Some((kind, body))
+                        if ["record", "variant", "enum", "flags"].contains(&kind) =>
+                    {
+                        format!("{} {} {}", kind, name, body)
+                    }
+                    _ if def == "resource" => format!("resource {};", name),
+                    _ => format!("type {} = {};", name, def),
+                }
+            }
+            ExternDesc::Func(idx) => format!("{}{}: {};", prefix, name, self.type_ref(level, *idx)),
+            ExternDesc::Instance(idx) => {
+                format!("{}{}: {}", prefix, name, self.type_ref(level, *idx))
+            }
+            ExternDesc::Module(_) => format!("{}{}: core module;", prefix, name),
+            ExternDesc::Component(_) => format!("{}{}: component;", prefix, name),
+            ExternDesc::Value(_) => format!("{}{}: value;", prefix, name),
+        }
+    }
+}
+
+/// Formats items as the body of a braced block, indented by two spaces.
+fn block(items: &[String]) -> String {
+    if items.is_empty() {
+        return "{}".to_string();
+    }
+    let mut out = "{\n".to_string();
+    for item in items {
+        for line in item.lines() {
+            out.push_str("  ");
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out.push('}');
+    out
+}
+
+/// The WIT-level interface of a component: what it imports and exports,
+/// with types resolved through the component's index spaces.
+pub struct World {
+    items: Vec<String>,
+}
+
+impl World {
+    pub fn new(component: &Component) -> World {
+        let mut scopes = Scopes {
+            levels: vec![Vec::new()],
+        };
+        // The type of each component function and instance, where known
+        let mut funcs: Vec<Option<u32>> = Vec::new();
+        let mut instances: Vec<Option<u32>> = Vec::new();
+        let mut items = Vec::new();
+
+        for section in &component.sections {
+            match section {
+                ComponentSection::Type(types) => {
+                    for def in types {
+                        scopes.push_def(def);
+                    }
+                }
+                ComponentSection::Alias(aliases) => {
+                    for alias in aliases {
+                        match (alias.sort, &alias.target) {
+                            (Sort::Type, AliasTarget::Export { name, .. }) => {
+                                scopes.push_named(name, None)
+                            }
+                            (Sort::Type, AliasTarget::Outer { count, idx }) => {
+                                let entry = scopes.outer(*count, *idx);
+                                scopes.push(entry.unwrap_or(TypeEntry {
+                                    name: None,
+                                    def: None,
+                                    level: 0,
+                                }));
+                            }
+                            (Sort::Type, _) => scopes.push(TypeEntry {
+                                name: None,
+                                def: None,
+                                level: 0,
+                            }),
+                            (Sort::Func, _) => funcs.push(None),
+                            (Sort::Instance, _) => instances.push(None),
+                            _ => {}
+                        }
+                    }
+                }
+                ComponentSection::Canon(canons) => {
+                    for canon in canons {
+                        if let Canon::Lift { ty, .. } = canon {
+                            funcs.push(Some(*ty));
+                        }
+                    }
+                }
+                ComponentSection::Instance(created) => {
+                    instances.extend(created.iter().map(|_| None));
+                }
+                ComponentSection::Import(imports) => {
+                    for import in imports {
+                        match import.desc() {
+                            ExternDesc::Func(ty) => funcs.push(Some(*ty)),
+                            ExternDesc::Instance(ty) => instances.push(Some(*ty)),
+                            _ => {}
+                        }
+                        items.push(scopes.extern_item("import ", import.name(), import.desc()));
+                    }
+                }
+                ComponentSection::Export(exports) => {
+                    for export in exports {
+                        let item = export.item();
+                        let idx = item.idx as usize;
+                        let desc = match (export.desc(), item.sort) {
+                            (Some(desc), _) => Some(desc.clone()),
+                            (None, Sort::Func) => {
+                                funcs.get(idx).copied().flatten().map(ExternDesc::Func)
+                            }
+                            (None, Sort::Instance) => instances
+                                .get(idx)
+                                .copied()
+                                .flatten()
+                                .map(ExternDesc::Instance),
+                            (None, Sort::Type) => Some(ExternDesc::Type(TypeBound::Eq(item.idx))),
+                            (None, _) => None,
+                        };
+                        match item.sort {
+                            Sort::Func => funcs.push(funcs.get(idx).copied().flatten()),
+                            Sort::Instance => instances.push(instances.get(idx).copied().flatten()),
+                            _ => {}
+                        }
+                        match desc {
+                            Some(desc) => {
+                                items.push(scopes.extern_item("export ", export.name(), &desc))
+                            }
+                            None => items.push(format!("export {}: {};", export.name(), item.sort)),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        World { items }
+    }
+
+    /// One entry per import and export, in the order they appear.
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+}
+
+impl Display for World {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "world component {}", block(&self.items))
+    }
+}
@@ -0,0 +1,41 @@
+use std::io::{Error, ErrorKind, Result, Write};
+
+use crate::types::leb128::{self, Leb128};
+
+/// The inverse of `Parseable`: writes a value in the binary format, such
+/// that parsing the output gives back an equal value.
+pub trait Encode {
+    fn encode(&self, writer: &mut dyn Write) -> Result<()>;
+}
+
+/// Writes the length of a vector, which the binary format limits to a u32.
+pub(crate) fn encode_len(len: usize, writer: &mut dyn Write) -> Result<()> {
+    match u32::try_from(len) {
+        Ok(len) => Leb128::from(len).encode(writer),
+        Err(_) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("vector of {} elements is too long to encode", len),
+        )),
+    }
+}
+
+/// Writes whatever `f` writes, prefixed with its size in bytes, as sections
+/// and function bodies are.
+pub(crate) fn encode_sized(
+    writer: &mut dyn Write,
+    f: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    // The size comes before the contents when parsing, so it has to claim
+    // its recorded width before the contents claim theirs.
+    let width = leb128::next_width();
+    let mut contents = Vec::new();
+    f(&mut contents)?;
+    let size = u32::try_from(contents.len()).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} bytes is too large to encode", contents.len()),
+        )
+    })?;
+    leb128::write_unsigned(writer, u64::from(size), width)?;
+    writer.write_all(&contents)
+}
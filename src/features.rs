@@ -0,0 +1,588 @@
+use std::fmt::Display;
+
+use crate::instr::Instr;
+use crate::instr::expr::Expr;
+use crate::instr::simd::SimdImm;
+use crate::module::Module;
+use crate::section::data::DataMode;
+use crate::section::element::{ElemInit, ElemMode};
+use crate::types::block_type::BlockType;
+use crate::types::comp_type::CompType;
+use crate::types::export_desc::ExportDesc;
+use crate::types::heap_type::HeapType;
+use crate::types::import_desc::ImportDesc;
+use crate::types::limits::Limits;
+use crate::types::ref_type::RefType;
+use crate::types::val_type::ValType;
+
+/// A WebAssembly proposal beyond the MVP that a module may depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    MutableGlobals,
+    SignExt,
+    SaturatingFloatToInt,
+    MultiValue,
+    BulkMemory,
+    ReferenceTypes,
+    Simd,
+    RelaxedSimd,
+    Threads,
+    TailCall,
+    Memory64,
+    MultiMemory,
+    Exceptions,
+    FunctionReferences,
+    Gc,
+    ExtendedConst,
+}
+
+impl Feature {
+    /// The proposal's usual short name, e.g. `sign-ext`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::MutableGlobals => "mutable-globals",
+            Feature::SignExt => "sign-ext",
+            Feature::SaturatingFloatToInt => "saturating-float-to-int",
+            Feature::MultiValue => "multi-value",
+            Feature::BulkMemory => "bulk-memory",
+            Feature::ReferenceTypes => "reference-types",
+            Feature::Simd => "simd",
+            Feature::RelaxedSimd => "relaxed-simd",
+            Feature::Threads => "threads",
+            Feature::TailCall => "tail-call",
+            Feature::Memory64 => "memory64",
+            Feature::MultiMemory => "multi-memory",
+            Feature::Exceptions => "exceptions",
+            Feature::FunctionReferences => "function-references",
+            Feature::Gc => "gc",
+            Feature::ExtendedConst => "extended-const",
+        }
+    }
+}
+
+impl Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The proposals a module is allowed to use. The default enables all of
+/// them; `Features::mvp()` enables none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    pub mutable_globals: bool,
+    pub sign_ext: bool,
+    pub saturating_float_to_int: bool,
+    pub multi_value: bool,
+    pub bulk_memory: bool,
+    pub reference_types: bool,
+    pub simd: bool,
+    pub relaxed_simd: bool,
+    pub threads: bool,
+    pub tail_call: bool,
+    pub memory64: bool,
+    pub multi_memory: bool,
+    pub exceptions: bool,
+    pub function_references: bool,
+    pub gc: bool,
+    pub extended_const: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features::all()
+    }
+}
+
+impl Features {
+    /// Only the original WebAssembly 1.0 feature set.
+    pub fn mvp() -> Features {
+        Features {
+            mutable_globals: false,
+            sign_ext: false,
+            saturating_float_to_int: false,
+            multi_value: false,
+            bulk_memory: false,
+            reference_types: false,
+            simd: false,
+            relaxed_simd: false,
+            threads: false,
+            tail_call: false,
+            memory64: false,
+            multi_memory: false,
+            exceptions: false,
+            function_references: false,
+            gc: false,
+            extended_const: false,
+        }
+    }
+
+    /// The proposals standardized in WebAssembly 2.0.
+    pub fn wasm2() -> Features {
+        Features {
+            mutable_globals: true,
+            sign_ext: true,
+            saturating_float_to_int: true,
+            multi_value: true,
+            bulk_memory: true,
+            reference_types: true,
+            simd: true,
+            ..Features::mvp()
+        }
+    }
+
+    pub fn all() -> Features {
+        Features {
+            mutable_globals: true,
+            sign_ext: true,
+            saturating_float_to_int: true,
+            multi_value: true,
+            bulk_memory: true,
+            reference_types: true,
+            simd: true,
+            relaxed_simd: true,
+            threads: true,
+            tail_call: true,
+            memory64: true,
+            multi_memory: true,
+            exceptions: true,
+            function_references: true,
+            gc: true,
+            extended_const: true,
+        }
+    }
+
+    /// The same features with `feature` turned on or off.
+    pub fn with(mut self, feature: Feature, enabled: bool) -> Features {
+        *self.flag(feature) = enabled;
+        self
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        let mut features = *self;
+        *features.flag(feature)
+    }
+
+    fn flag(&mut self, feature: Feature) -> &mut bool {
+        match feature {
+            Feature::MutableGlobals => &mut self.mutable_globals,
+            Feature::SignExt => &mut self.sign_ext,
+            Feature::SaturatingFloatToInt => &mut self.saturating_float_to_int,
+            Feature::MultiValue => &mut self.multi_value,
+            Feature::BulkMemory => &mut self.bulk_memory,
+            Feature::ReferenceTypes => &mut self.reference_types,
+            Feature::Simd => &mut self.simd,
+            Feature::RelaxedSimd => &mut self.relaxed_simd,
+            Feature::Threads => &mut self.threads,
+            Feature::TailCall => &mut self.tail_call,
+            Feature::Memory64 => &mut self.memory64,
+            Feature::MultiMemory => &mut self.multi_memory,
+            Feature::Exceptions => &mut self.exceptions,
+            Feature::FunctionReferences => &mut self.function_references,
+            Feature::Gc => &mut self.gc,
+            Feature::ExtendedConst => &mut self.extended_const,
+        }
+    }
+
+    fn require(&self, feature: Feature) -> Result<(), Feature> {
+        if self.is_enabled(feature) {
+            Ok(())
+        } else {
+            Err(feature)
+        }
+    }
+
+    /// Checks that a module only uses enabled features, returning the first
+    /// one that is not.
+    pub fn check(&self, module: &Module) -> Result<(), Feature> {
+        if let Some(typesec) = &module.typesec {
+            for rec in typesec.rec_groups() {
+                if rec.types().len() != 1 {
+                    self.require(Feature::Gc)?;
+                }
+                for sub in rec.types() {
+                    if !sub.is_final() || !sub.supertypes().is_empty() {
+                        self.require(Feature::Gc)?;
+                    }
+                    match sub.comp_type() {
+                        CompType::Func(ty) => {
+                            if ty.results().len() > 1 {
+                                self.require(Feature::MultiValue)?;
+                            }
+                            for t in ty.params().iter().chain(ty.results()) {
+                                self.check_val_type(t)?;
+                            }
+                        }
+                        CompType::Struct(_) | CompType::Array(_) => self.require(Feature::Gc)?,
+                    }
+                }
+            }
+        }
+
+        let mut tables = 0;
+        let mut mems = 0;
+        let mut mutable_globals = Vec::new();
+        if let Some(importsec) = &module.importsec {
+            for import in importsec.imports() {
+                match import.desc() {
+                    ImportDesc::Func(_) => {}
+                    ImportDesc::Table(ty) => {
+                        tables += 1;
+                        self.check_ref_type(&ty.elem_type())?;
+                        self.check_limits(ty.limits())?;
+                    }
+                    ImportDesc::Mem(ty) => {
+                        mems += 1;
+                        self.check_limits(ty.limits())?;
+                    }
+                    ImportDesc::Global(ty) => {
+                        if ty.is_mutable() {
+                            self.require(Feature::MutableGlobals)?;
+                        }
+                        mutable_globals.push(ty.is_mutable());
+                        self.check_val_type(&ty.val_type())?;
+                    }
+                    ImportDesc::Tag(_) => self.require(Feature::Exceptions)?,
+                }
+            }
+        }
+        if let Some(tablesec) = &module.tablesec {
+            for table in tablesec.tables() {
+                tables += 1;
+                self.check_ref_type(&table.table_type().elem_type())?;
+                self.check_limits(table.table_type().limits())?;
+                if let Some(init) = table.init() {
+                    self.require(Feature::FunctionReferences)?;
+                    self.check_const_expr(init)?;
+                }
+            }
+        }
+        if tables > 1 {
+            self.require(Feature::ReferenceTypes)?;
+        }
+        if let Some(memsec) = &module.memsec {
+            for mem in memsec.mems() {
+                mems += 1;
+                self.check_limits(mem.limits())?;
+            }
+        }
+        if mems > 1 {
+            self.require(Feature::MultiMemory)?;
+        }
+        if module.tagsec.is_some() {
+            self.require(Feature::Exceptions)?;
+        }
+        if let Some(globalsec) = &module.globalsec {
+            for global in globalsec.globals() {
+                mutable_globals.push(global.global_type().is_mutable());
+                self.check_val_type(&global.global_type().val_type())?;
+                self.check_const_expr(global.init())?;
+            }
+        }
+        if let Some(exportsec) = &module.exportsec {
+            for export in exportsec.exports() {
+                if let ExportDesc::Global(idx) = export.desc()
+                    && mutable_globals.get(idx.0 as usize) == Some(&true)
+                {
+                    self.require(Feature::MutableGlobals)?;
+                }
+            }
+        }
+
+        if let Some(elemsec) = &module.elemsec {
+            for elem in elemsec.elems() {
+                self.check_ref_type(&elem.ref_type())?;
+                match elem.mode() {
+                    ElemMode::Passive => self.require(Feature::BulkMemory)?,
+                    ElemMode::Declarative => self.require(Feature::ReferenceTypes)?,
+                    ElemMode::Active { table, offset } => {
+                        if table.0 != 0 {
+                            self.require(Feature::ReferenceTypes)?;
+                        }
+                        self.check_const_expr(offset)?;
+                    }
+                }
+                if let ElemInit::Exprs(exprs) = elem.init() {
+                    self.require(Feature::BulkMemory)?;
+                    for expr in exprs {
+                        self.check_const_expr(expr)?;
+                    }
+                }
+            }
+        }
+        if module.datacountsec.is_some() {
+            self.require(Feature::BulkMemory)?;
+        }
+        if let Some(datasec) = &module.datasec {
+            for data in datasec.datas() {
+                match data.mode() {
+                    DataMode::Passive => self.require(Feature::BulkMemory)?,
+                    DataMode::Active { mem, offset } => {
+                        if mem.0 != 0 {
+                            self.require(Feature::MultiMemory)?;
+                        }
+                        self.check_const_expr(offset)?;
+                    }
+                }
+            }
+        }
+
+        if let Some(codesec) = &module.codesec {
+            for code in codesec.codes() {
+                for l in code.locals() {
+                    self.check_val_type(&l.val_type())?;
+                }
+                for instr in code.body().instrs() {
+                    self.check_instr(instr)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_limits(&self, limits: &Limits) -> Result<(), Feature> {
+        if limits.is_shared() {
+            self.require(Feature::Threads)?;
+        }
+        if limits.is_64() {
+            self.require(Feature::Memory64)?;
+        }
+        Ok(())
+    }
+
+    fn check_heap_type(&self, heap: HeapType) -> Result<(), Feature> {
+        match heap {
+            HeapType::Func => Ok(()),
+            HeapType::Extern => self.require(Feature::ReferenceTypes),
+            HeapType::Exn | HeapType::NoExn => self.require(Feature::Exceptions),
+            HeapType::Concrete(_) => self.require(Feature::FunctionReferences),
+            _ => self.require(Feature::Gc),
+        }
+    }
+
+    fn check_ref_type(&self, rt: &RefType) -> Result<(), Feature> {
+        if let RefType::Typed { .. } = rt {
+            self.require(Feature::FunctionReferences)?;
+        }
+        self.check_heap_type(rt.heap_type())
+    }
+
+    fn check_val_type(&self, t: &ValType) -> Result<(), Feature> {
+        match t {
+            ValType::Num(_) => Ok(()),
+            ValType::Vec(_) => self.require(Feature::Simd),
+            ValType::Ref(rt) => {
+                self.require(Feature::ReferenceTypes)?;
+                self.check_ref_type(rt)
+            }
+        }
+    }
+
+    fn check_block_type(&self, bt: &BlockType) -> Result<(), Feature> {
+        match bt {
+            BlockType::Empty => Ok(()),
+            BlockType::Value(t) => self.check_val_type(t),
+            BlockType::Type(_) => self.require(Feature::MultiValue),
+        }
+    }
+
+    fn check_const_expr(&self, expr: &Expr) -> Result<(), Feature> {
+        for instr in expr.instrs() {
+            if let Instr::Numeric(_) = instr {
+                self.require(Feature::ExtendedConst)?;
+            }
+            self.check_instr(instr)?;
+        }
+        Ok(())
+    }
+
+    fn check_instr(&self, instr: &Instr) -> Result<(), Feature> {
+        match instr {
+            Instr::Block(bt) | Instr::Loop(bt) | Instr::If(bt) => self.check_block_type(bt)?,
+            Instr::Try(bt) | Instr::TryTable(bt, _) => {
+                self.require(Feature::Exceptions)?;
+                self.check_block_type(bt)?;
+            }
+            Instr::Throw(_)
+            | Instr::ThrowRef
+            | Instr::Catch(_)
+            | Instr::CatchAll
+            | Instr::Delegate(_)
+            | Instr::Rethrow(_) => self.require(Feature::Exceptions)?,
+            Instr::ReturnCall(_) | Instr::ReturnCallIndirect(..) => {
+                self.require(Feature::TailCall)?
+            }
+            Instr::ReturnCallRef(_) => {
+                self.require(Feature::TailCall)?;
+                self.require(Feature::FunctionReferences)?;
+            }
+            Instr::CallRef(_)
+            | Instr::BrOnNull(_)
+            | Instr::BrOnNonNull(_)
+            | Instr::RefAsNonNull => self.require(Feature::FunctionReferences)?,
+            Instr::CallIndirect(_, table) if table.0 != 0 => {
+                self.require(Feature::ReferenceTypes)?
+            }
+            Instr::RefNull(heap) => {
+                self.require(Feature::ReferenceTypes)?;
+                self.check_heap_type(*heap)?;
+            }
+            Instr::RefIsNull | Instr::RefFunc(_) => self.require(Feature::ReferenceTypes)?,
+            Instr::RefEq
+            | Instr::StructNew(_)
+            | Instr::StructNewDefault(_)
+            | Instr::StructGet(..)
+            | Instr::StructGetS(..)
+            | Instr::StructGetU(..)
+            | Instr::StructSet(..)
+            | Instr::ArrayNew(_)
+            | Instr::ArrayNewDefault(_)
+            | Instr::ArrayNewFixed(..)
+            | Instr::ArrayNewData(..)
+            | Instr::ArrayNewElem(..)
+            | Instr::ArrayGet(_)
+            | Instr::ArrayGetS(_)
+            | Instr::ArrayGetU(_)
+            | Instr::ArraySet(_)
+            | Instr::ArrayLen
+            | Instr::ArrayFill(_)
+            | Instr::ArrayCopy(..)
+            | Instr::ArrayInitData(..)
+            | Instr::ArrayInitElem(..)
+            | Instr::RefTest(_)
+            | Instr::RefCast(_)
+            | Instr::BrOnCast(..)
+            | Instr::BrOnCastFail(..)
+            | Instr::AnyConvertExtern
+            | Instr::ExternConvertAny
+            | Instr::RefI31
+            | Instr::I31GetS
+            | Instr::I31GetU => self.require(Feature::Gc)?,
+            Instr::Select(Some(types)) => {
+                self.require(Feature::ReferenceTypes)?;
+                for t in types {
+                    self.check_val_type(t)?;
+                }
+            }
+            Instr::TableGet(_)
+            | Instr::TableSet(_)
+            | Instr::TableSize(_)
+            | Instr::TableGrow(_)
+            | Instr::TableFill(_) => self.require(Feature::ReferenceTypes)?,
+            Instr::TableInit(_, table) => {
+                self.require(Feature::BulkMemory)?;
+                if table.0 != 0 {
+                    self.require(Feature::ReferenceTypes)?;
+                }
+            }
+            Instr::TableCopy(dst, src) => {
+                self.require(Feature::BulkMemory)?;
+                if dst.0 != 0 || src.0 != 0 {
+                    self.require(Feature::ReferenceTypes)?;
+                }
+            }
+            Instr::ElemDrop(_) | Instr::DataDrop(_) => self.require(Feature::BulkMemory)?,
+            Instr::MemoryInit(_, mem) | Instr::MemoryFill(mem) => {
+                self.require(Feature::BulkMemory)?;
+                if mem.0 != 0 {
+                    self.require(Feature::MultiMemory)?;
+                }
+            }
+            Instr::MemoryCopy(dst, src) => {
+                self.require(Feature::BulkMemory)?;
+                if dst.0 != 0 || src.0 != 0 {
+                    self.require(Feature::MultiMemory)?;
+                }
+            }
+            Instr::MemorySize(mem) | Instr::MemoryGrow(mem) if mem.0 != 0 => {
+                self.require(Feature::MultiMemory)?
+            }
+            Instr::Load(_, memarg) | Instr::Store(_, memarg) if memarg.has_explicit_memidx() => {
+                self.require(Feature::MultiMemory)?
+            }
+            Instr::Numeric(op) => match op.opcode() {
+                0xC0..=0xC4 => self.require(Feature::SignExt)?,
+                0xFC00..=0xFC07 => self.require(Feature::SaturatingFloatToInt)?,
+                _ => {}
+            },
+            Instr::Simd(op, imm) => {
+                self.require(Feature::Simd)?;
+                if op.opcode() >= 0x100 {
+                    self.require(Feature::RelaxedSimd)?;
+                }
+                if let SimdImm::MemArg(memarg) | SimdImm::MemArgLane(memarg, _) = imm
+                    && memarg.has_explicit_memidx()
+                {
+                    self.require(Feature::MultiMemory)?;
+                }
+            }
+            Instr::Atomic(_, memarg) => {
+                self.require(Feature::Threads)?;
+                if memarg.has_explicit_memidx() {
+                    self.require(Feature::MultiMemory)?;
+                }
+            }
+            Instr::AtomicFence => self.require(Feature::Threads)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parseable::ParseError;
+    use std::io::{BufReader, Cursor};
+
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    fn parse(sections: &[u8], features: &Features) -> Result<Module, ParseError> {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(sections);
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        Module::parse_with_features(&mut reader, features).map_err(ParseError::from)
+    }
+
+    #[test]
+    fn test_features() {
+        // A shared memory with a maximum
+        let sections = [0x05, 0x04, 0x01, 0x03, 0x01, 0x01];
+        assert!(parse(&sections, &Features::all()).is_ok());
+        assert_eq!(
+            parse(&sections, &Features::wasm2()).err(),
+            Some(ParseError::Other("feature threads not enabled".to_string()))
+        );
+        let features = Features::wasm2().with(Feature::Threads, true);
+        assert!(parse(&sections, &features).is_ok());
+
+        // (func (result i32 i32)) needs multi-value
+        let sections = [0x01, 0x06, 0x01, 0x60, 0x00, 0x02, 0x7f, 0x7f];
+        assert!(parse(&sections, &Features::wasm2()).is_ok());
+        let features = Features::wasm2().with(Feature::MultiValue, false);
+        assert!(!features.is_enabled(Feature::MultiValue));
+        assert_eq!(
+            parse(&sections, &features).err(),
+            Some(ParseError::Other(
+                "feature multi-value not enabled".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_instr_features() {
+        // (func (i32.extend8_s (i32.const 0)) drop)
+        let sections = [
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x0a, 0x08, 0x01, 0x06, 0x00, 0x41, 0x00, 0xc0, 0x1a, 0x0b, // code section
+        ];
+        assert!(parse(&sections, &Features::wasm2()).is_ok());
+        assert_eq!(
+            parse(&sections, &Features::mvp()).err(),
+            Some(ParseError::Other(
+                "feature sign-ext not enabled".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use crate::builder::{FuncBody, ModuleBuilder};
+use crate::instr::Instr;
+use crate::instr::numeric::{LoadOp, NumericInstr, StoreOp};
+use crate::module::Module;
+use crate::types::block_type::BlockType;
+use crate::types::export_desc::ExportDesc;
+use crate::types::func_type::FuncType;
+use crate::types::global_type::GlobalType;
+use crate::types::limits::Limits;
+use crate::types::mem_arg::MemArg;
+use crate::types::mem_type::MemType;
+use crate::types::r#mut::{CONST, VAR};
+use crate::types::num_type::NumType;
+use crate::types::primitives::{FuncIdx, GlobalIdx, LabelIdx, LocalIdx, MemIdx};
+use crate::types::val_type::ValType;
+use crate::validate::{load_type, numeric_type, store_type};
+
+const I32: ValType = ValType::Num(NumType::I32);
+const I64: ValType = ValType::Num(NumType::I64);
+const F32: ValType = ValType::Num(NumType::F32);
+const F64: ValType = ValType::Num(NumType::F64);
+
+// How many instructions a body may have, and how deeply blocks may nest
+const MAX_INSTRS: usize = 200;
+const MAX_DEPTH: usize = 4;
+
+/// The choices made while generating a module, read from fuzzer input.
+/// Once the input runs out every choice is 0, so any input gives a module.
+pub struct Choices<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Choices<'a> {
+    pub fn new(data: &'a [u8]) -> Choices<'a> {
+        Choices { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((byte, rest)) => {
+                self.data = rest;
+                *byte
+            }
+            None => 0,
+        }
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.byte(), self.byte(), self.byte(), self.byte()])
+    }
+
+    /// A number in `0..n`, or 0 if `n` is 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        match n {
+            0 => 0,
+            n if n <= 256 => usize::from(self.byte()) % n,
+            n => self.u32() as usize % n,
+        }
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.byte() & 1 == 1
+    }
+}
+
+/// Generates a valid module from arbitrary bytes, for fuzzing the parts
+/// that only see valid modules. It has a few function types, an imported
+/// function, maybe a memory and some globals, and functions whose bodies
+/// are type-correct sequences of numeric, variable, memory, call and block
+/// instructions. Everything defined is exported.
+pub fn module(data: &[u8]) -> Module {
+    let mut choices = Choices::new(data);
+    let mut builder = ModuleBuilder::new();
+
+    let mut types = Vec::new();
+    for _ in 0..1 + choices.below(4) {
+        let params = (0..choices.below(4))
+            .map(|_| val_type(&mut choices))
+            .collect();
+        let results = (0..choices.below(3))
+            .map(|_| val_type(&mut choices))
+            .collect();
+        types.push(FuncType::new(params, results));
+    }
+
+    let mut funcs = Vec::new();
+    if choices.bool() {
+        let ty = types[choices.below(types.len())].clone();
+        builder
+            .import_func("env", "f", ty.clone())
+            .expect("imports come before any functions");
+        funcs.push(ty);
+    }
+    let memory = choices.bool().then(|| {
+        let min = choices.below(3) as u64;
+        builder.memory(MemType::new(Limits::new(min, Some(min + 1))))
+    });
+    let mut globals = Vec::new();
+    for _ in 0..choices.below(4) {
+        let t = val_type(&mut choices);
+        let mutable = choices.bool();
+        let ty = GlobalType::new(t, if mutable { VAR } else { CONST });
+        let idx = builder.global(ty, vec![constant(&mut choices, t)]);
+        builder.export(&format!("g{}", idx.0), ExportDesc::Global(idx));
+        globals.push((t, mutable));
+    }
+
+    for _ in 0..1 + choices.below(5) {
+        let ty = types[choices.below(types.len())].clone();
+        let context = Context {
+            funcs: &funcs,
+            globals: &globals,
+            memory,
+        };
+        let idx = builder.func(ty.clone(), |body| {
+            Body::new(&context, body, &ty).generate(&mut choices)
+        });
+        builder.export(&format!("f{}", idx.0), ExportDesc::Func(idx));
+        funcs.push(ty);
+    }
+
+    if let Some(mem) = memory {
+        builder.export("memory", ExportDesc::Mem(mem));
+        if choices.bool() {
+            let init: Vec<u8> = (0..choices.below(16)).map(|_| choices.byte()).collect();
+            builder.data(mem, vec![Instr::I32Const(0)], init);
+        }
+    }
+    builder.build()
+}
+
+fn val_type(choices: &mut Choices) -> ValType {
+    [I32, I64, F32, F64][choices.below(4)]
+}
+
+fn constant(choices: &mut Choices, t: ValType) -> Instr {
+    let bits = u64::from(choices.u32()) << 32 | u64::from(choices.u32());
+    match t {
+        I64 => Instr::I64Const(bits as i64),
+        F32 => Instr::F32Const(f32::from_bits(bits as u32)),
+        F64 => Instr::F64Const(f64::from_bits(bits)),
+        _ => Instr::I32Const(bits as i32),
+    }
+}
+
+// What the bodies can refer to
+struct Context<'a> {
+    funcs: &'a [FuncType],
+    globals: &'a [(ValType, bool)],
+    memory: Option<MemIdx>,
+}
+
+// A body being generated, with the types on the operand stack of the
+// innermost block
+struct Body<'a, 'b> {
+    context: &'a Context<'a>,
+    body: &'b mut FuncBody,
+    locals: Vec<ValType>,
+    results: Vec<ValType>,
+    stack: Vec<ValType>,
+    depth: usize,
+    count: usize,
+}
+
+impl<'a, 'b> Body<'a, 'b> {
+    fn new(context: &'a Context<'a>, body: &'b mut FuncBody, ty: &FuncType) -> Body<'a, 'b> {
+        Body {
+            context,
+            body,
+            locals: ty.params().to_vec(),
+            results: ty.results().to_vec(),
+            stack: Vec::new(),
+            depth: 0,
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, instr: Instr) {
+        self.body.instr(instr);
+        self.count += 1;
+    }
+
+    // Makes the top of the stack `types`, using the values already there if
+    // they match and constants otherwise
+    fn operands(&mut self, choices: &mut Choices, types: &[ValType]) {
+        if !self.stack.ends_with(types) {
+            for t in types {
+                self.push(constant(choices, *t));
+            }
+            self.stack.extend_from_slice(types);
+        }
+        self.stack.truncate(self.stack.len() - types.len());
+    }
+
+    // Drops whatever is left on the stack of the current block
+    fn drop_all(&mut self) {
+        while self.stack.pop().is_some() {
+            self.push(Instr::Drop);
+        }
+    }
+
+    fn generate(mut self, choices: &mut Choices) {
+        for _ in 0..self.locals.len().min(2) + choices.below(3) {
+            let t = val_type(choices);
+            self.body.local(t);
+            self.locals.push(t);
+        }
+        self.sequence(choices);
+        self.drop_all();
+        for t in self.results.clone() {
+            self.push(constant(choices, t));
+        }
+    }
+
+    fn sequence(&mut self, choices: &mut Choices) {
+        while !choices.is_empty() && self.count < MAX_INSTRS && choices.below(8) != 0 {
+            self.instr(choices);
+        }
+    }
+
+    fn block(&mut self, choices: &mut Choices) {
+        let outer = std::mem::take(&mut self.stack);
+        self.depth += 1;
+        self.sequence(choices);
+        self.drop_all();
+        self.depth -= 1;
+        self.stack = outer;
+    }
+
+    fn instr(&mut self, choices: &mut Choices) {
+        let memory = self.context.memory;
+        match choices.below(12) {
+            0 => {
+                let t = val_type(choices);
+                self.push(constant(choices, t));
+                self.stack.push(t);
+            }
+            1 => {
+                let idx = choices.below(self.locals.len());
+                if let Some(t) = self.locals.get(idx).copied() {
+                    self.push(Instr::LocalGet(LocalIdx(idx as u32)));
+                    self.stack.push(t);
+                }
+            }
+            2 => {
+                let idx = choices.below(self.locals.len());
+                if let Some(t) = self.locals.get(idx).copied() {
+                    self.operands(choices, &[t]);
+                    self.push(Instr::LocalSet(LocalIdx(idx as u32)));
+                }
+            }
+            3 => {
+                let idx = choices.below(self.context.globals.len());
+                if let Some(&(t, mutable)) = self.context.globals.get(idx) {
+                    if mutable && choices.bool() {
+                        self.operands(choices, &[t]);
+                        self.push(Instr::GlobalSet(GlobalIdx(idx as u32)));
+                    } else {
+                        self.push(Instr::GlobalGet(GlobalIdx(idx as u32)));
+                        self.stack.push(t);
+                    }
+                }
+            }
+            4..=6 => {
+                let op = NumericInstr::from_opcode(0x45 + choices.below(0xc5 - 0x45) as u32);
+                if let Some(op) = op {
+                    let (params, result) = numeric_type(op);
+                    self.operands(choices, params);
+                    self.push(Instr::Numeric(op));
+                    self.stack.push(result);
+                }
+            }
+            7 => {
+                if let Some(t) = self.stack.pop() {
+                    self.push(Instr::Drop);
+                    if choices.bool() {
+                        self.operands(choices, &[t, t, I32]);
+                        self.push(Instr::Select(None));
+                        self.stack.push(t);
+                    }
+                }
+            }
+            8 => {
+                let idx = choices.below(self.context.funcs.len());
+                if let Some(ty) = self.context.funcs.get(idx) {
+                    self.operands(choices, ty.params());
+                    self.push(Instr::Call(FuncIdx(idx as u32)));
+                    self.stack.extend_from_slice(ty.results());
+                }
+            }
+            9 if memory.is_some() => {
+                // Small addresses, so that some accesses are in bounds
+                let offset = u64::from(choices.byte());
+                if choices.bool() {
+                    let op = LoadOp::from_opcode(0x28 + choices.below(14) as u32);
+                    if let Some(op) = op {
+                        let (t, size) = load_type(op);
+                        self.operands(choices, &[I32]);
+                        self.push(Instr::Load(op, mem_arg(size, offset)));
+                        self.stack.push(t);
+                    }
+                } else {
+                    let op = StoreOp::from_opcode(0x36 + choices.below(9) as u32);
+                    if let Some(op) = op {
+                        let (t, size) = store_type(op);
+                        self.operands(choices, &[I32, t]);
+                        self.push(Instr::Store(op, mem_arg(size, offset)));
+                    }
+                }
+            }
+            10 if self.depth < MAX_DEPTH => {
+                if choices.bool() {
+                    self.push(Instr::Block(BlockType::Empty));
+                    self.block(choices);
+                } else {
+                    self.operands(choices, &[I32]);
+                    self.push(Instr::If(BlockType::Empty));
+                    self.block(choices);
+                    if choices.bool() {
+                        self.push(Instr::Else);
+                        self.block(choices);
+                    }
+                }
+                self.push(Instr::End);
+            }
+            11 if self.depth > 0 => {
+                // Leaves a block early; blocks take no values, so this is
+                // valid whatever else is on the stack
+                let label = choices.below(self.depth);
+                self.operands(choices, &[I32]);
+                self.push(Instr::BrIf(LabelIdx(label as u32)));
+            }
+            _ => self.push(Instr::Nop),
+        }
+    }
+}
+
+fn mem_arg(size: u32, offset: u64) -> MemArg {
+    MemArg::new(size.trailing_zeros(), offset, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::validate;
+
+    #[test]
+    fn test_generated_modules_validate() {
+        // A simple generator of inputs, so the test doesn't depend on a seed
+        // corpus
+        let mut state: u32 = 1;
+        for len in 0..200 {
+            let data: Vec<u8> = (0..len * 8)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect();
+            let module = module(&data);
+            if let Err(err) = validate(&module) {
+                panic!("{} for input {:02x?}", err, data);
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Atomic memory instructions from the threads proposal follow the 0xFE
+// prefix. All of them except `atomic.fence` take a memarg.
+opcodes! {
+    pub enum AtomicOp {
+        0x00 => MemoryAtomicNotify = "memory.atomic.notify",
+        0x01 => MemoryAtomicWait32 = "memory.atomic.wait32",
+        0x02 => MemoryAtomicWait64 = "memory.atomic.wait64",
+        0x10 => I32AtomicLoad = "i32.atomic.load",
+        0x11 => I64AtomicLoad = "i64.atomic.load",
+        0x12 => I32AtomicLoad8U = "i32.atomic.load8_u",
+        0x13 => I32AtomicLoad16U = "i32.atomic.load16_u",
+        0x14 => I64AtomicLoad8U = "i64.atomic.load8_u",
+        0x15 => I64AtomicLoad16U = "i64.atomic.load16_u",
+        0x16 => I64AtomicLoad32U = "i64.atomic.load32_u",
+        0x17 => I32AtomicStore = "i32.atomic.store",
+        0x18 => I64AtomicStore = "i64.atomic.store",
+        0x19 => I32AtomicStore8 = "i32.atomic.store8",
+        0x1A => I32AtomicStore16 = "i32.atomic.store16",
+        0x1B => I64AtomicStore8 = "i64.atomic.store8",
+        0x1C => I64AtomicStore16 = "i64.atomic.store16",
+        0x1D => I64AtomicStore32 = "i64.atomic.store32",
+        0x1E => I32AtomicRmwAdd = "i32.atomic.rmw.add",
+        0x1F => I64AtomicRmwAdd = "i64.atomic.rmw.add",
+        0x20 => I32AtomicRmw8AddU = "i32.atomic.rmw8.add_u",
+        0x21 => I32AtomicRmw16AddU = "i32.atomic.rmw16.add_u",
+        0x22 => I64AtomicRmw8AddU = "i64.atomic.rmw8.add_u",
+        0x23 => I64AtomicRmw16AddU = "i64.atomic.rmw16.add_u",
+        0x24 => I64AtomicRmw32AddU = "i64.atomic.rmw32.add_u",
+        0x25 => I32AtomicRmwSub = "i32.atomic.rmw.sub",
+        0x26 => I64AtomicRmwSub = "i64.atomic.rmw.sub",
+        0x27 => I32AtomicRmw8SubU = "i32.atomic.rmw8.sub_u",
+        0x28 => I32AtomicRmw16SubU = "i32.atomic.rmw16.sub_u",
+        0x29 => I64AtomicRmw8SubU = "i64.atomic.rmw8.sub_u",
+        0x2A => I64AtomicRmw16SubU = "i64.atomic.rmw16.sub_u",
+        0x2B => I64AtomicRmw32SubU = "i64.atomic.rmw32.sub_u",
+        0x2C => I32AtomicRmwAnd = "i32.atomic.rmw.and",
+        0x2D => I64AtomicRmwAnd = "i64.atomic.rmw.and",
+        0x2E => I32AtomicRmw8AndU = "i32.atomic.rmw8.and_u",
+        0x2F => I32AtomicRmw16AndU = "i32.atomic.rmw16.and_u",
+        0x30 => I64AtomicRmw8AndU = "i64.atomic.rmw8.and_u",
+        0x31 => I64AtomicRmw16AndU = "i64.atomic.rmw16.and_u",
+        0x32 => I64AtomicRmw32AndU = "i64.atomic.rmw32.and_u",
+        0x33 => I32AtomicRmwOr = "i32.atomic.rmw.or",
+        0x34 => I64AtomicRmwOr = "i64.atomic.rmw.or",
+        0x35 => I32AtomicRmw8OrU = "i32.atomic.rmw8.or_u",
+        0x36 => I32AtomicRmw16OrU = "i32.atomic.rmw16.or_u",
+        0x37 => I64AtomicRmw8OrU = "i64.atomic.rmw8.or_u",
+        0x38 => I64AtomicRmw16OrU = "i64.atomic.rmw16.or_u",
+        0x39 => I64AtomicRmw32OrU = "i64.atomic.rmw32.or_u",
+        0x3A => I32AtomicRmwXor = "i32.atomic.rmw.xor",
+        0x3B => I64AtomicRmwXor = "i64.atomic.rmw.xor",
+        0x3C => I32AtomicRmw8XorU = "i32.atomic.rmw8.xor_u",
+        0x3D => I32AtomicRmw16XorU = "i32.atomic.rmw16.xor_u",
+        0x3E => I64AtomicRmw8XorU = "i64.atomic.rmw8.xor_u",
+        0x3F => I64AtomicRmw16XorU = "i64.atomic.rmw16.xor_u",
+        0x40 => I64AtomicRmw32XorU = "i64.atomic.rmw32.xor_u",
+        0x41 => I32AtomicRmwXchg = "i32.atomic.rmw.xchg",
+        0x42 => I64AtomicRmwXchg = "i64.atomic.rmw.xchg",
+        0x43 => I32AtomicRmw8XchgU = "i32.atomic.rmw8.xchg_u",
+        0x44 => I32AtomicRmw16XchgU = "i32.atomic.rmw16.xchg_u",
+        0x45 => I64AtomicRmw8XchgU = "i64.atomic.rmw8.xchg_u",
+        0x46 => I64AtomicRmw16XchgU = "i64.atomic.rmw16.xchg_u",
+        0x47 => I64AtomicRmw32XchgU = "i64.atomic.rmw32.xchg_u",
+        0x48 => I32AtomicRmwCmpxchg = "i32.atomic.rmw.cmpxchg",
+        0x49 => I64AtomicRmwCmpxchg = "i64.atomic.rmw.cmpxchg",
+        0x4A => I32AtomicRmw8CmpxchgU = "i32.atomic.rmw8.cmpxchg_u",
+        0x4B => I32AtomicRmw16CmpxchgU = "i32.atomic.rmw16.cmpxchg_u",
+        0x4C => I64AtomicRmw8CmpxchgU = "i64.atomic.rmw8.cmpxchg_u",
+        0x4D => I64AtomicRmw16CmpxchgU = "i64.atomic.rmw16.cmpxchg_u",
+        0x4E => I64AtomicRmw32CmpxchgU = "i64.atomic.rmw32.cmpxchg_u",    }
+}
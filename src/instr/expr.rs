@@ -0,0 +1,133 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::instr::Instr;
+use crate::parseable::{ParseError, Parseable, ParserLimits, Result};
+
+/// A sequence of instructions terminated by `end`, as used for function
+/// bodies and constant expressions. The instructions are kept flat: blocks
+/// are delimited by their `block`/`loop`/`if`, `else` and `end` instructions,
+/// and the final `end` is included.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Expr {
+    instrs: Vec<Instr>,
+}
+
+impl Expr {
+    pub fn new(instrs: Vec<Instr>) -> Expr {
+        Expr { instrs }
+    }
+
+    pub fn instrs(&self) -> &[Instr] {
+        &self.instrs
+    }
+
+    /// Tracks block nesting while reading an expression. Returns true once
+    /// the `end` that closes the expression itself has been seen.
+    pub(crate) fn close(instr: &Instr, depth: &mut u32) -> Result<bool> {
+        if instr.opens_block() {
+            *depth += 1;
+            ParserLimits::current().check_nesting(*depth as usize)?;
+        } else if instr.closes_block() {
+            if *depth == 0 {
+                if *instr != Instr::End {
+                    return Err(ParseError::Other(format!("{} outside of try", instr)));
+                }
+                return Ok(true);
+            }
+            *depth -= 1;
+        } else if instr.continues_block() && *depth == 0 {
+            return Err(ParseError::Other(format!("{} outside of block", instr)));
+        }
+        Ok(false)
+    }
+}
+
+impl From<Expr> for Vec<Instr> {
+    fn from(value: Expr) -> Self {
+        value.instrs
+    }
+}
+
+impl Parseable for Expr {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut instrs = Vec::new();
+        let mut depth = 0;
+        loop {
+            let instr = Instr::parse(reader)?;
+            let done = Self::close(&instr, &mut depth)?;
+            instrs.push(instr);
+            if done {
+                break;
+            }
+        }
+        Ok(Expr { instrs })
+    }
+}
+
+impl Encode for Expr {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        for instr in &self.instrs {
+            instr.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, instr) in self.instrs.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", instr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::block_type::BlockType;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_expr() {
+        // block; i32.const 0; if; else; end; end; end -- and a trailing nop
+        // that must not be consumed.
+        let bytes = [
+            0x02, 0x40, 0x41, 0x00, 0x04, 0x40, 0x05, 0x0b, 0x0b, 0x0b, 0x01,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let expr = Expr::parse(&mut reader).expect("The parsed expression");
+        assert_eq!(
+            expr.instrs(),
+            &[
+                Instr::Block(BlockType::Empty),
+                Instr::I32Const(0),
+                Instr::If(BlockType::Empty),
+                Instr::Else,
+                Instr::End,
+                Instr::End,
+                Instr::End,
+            ]
+        );
+        assert_eq!(Instr::parse(&mut reader), Ok(Instr::Nop));
+
+        // A delegate closes its try block rather than the expression
+        let bytes = [0x06, 0x40, 0x18, 0x00, 0x0b];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let expr = Expr::parse(&mut reader).expect("The parsed expression");
+        assert_eq!(expr.instrs().len(), 3);
+
+        // Missing the final end
+        let bytes = [0x41, 0x00];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Expr::parse(&mut reader).is_err());
+    }
+}
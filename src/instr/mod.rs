@@ -0,0 +1,1412 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
+
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::block_type::BlockType;
+use crate::types::catch::Catch;
+use crate::types::heap_type::HeapType;
+use crate::types::leb128::Leb128;
+use crate::types::mem_arg::MemArg;
+use crate::types::primitives::{
+    DataIdx, ElemIdx, FieldIdx, FuncIdx, GlobalIdx, LabelIdx, LocalIdx, MemIdx, TableIdx, TagIdx,
+    TypeIdx,
+};
+use crate::types::ref_type::RefType;
+use crate::types::val_type::ValType;
+
+// Declares a fieldless enum of instructions together with its opcode and
+// text-format name, so that the three can never drift apart.
+macro_rules! opcodes {
+    (pub enum $name:ident { $($op:literal => $variant:ident = $text:literal,)* }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)*
+        }
+
+        impl $name {
+            pub fn from_opcode(op: u32) -> Option<Self> {
+                match op {
+                    $($op => Some($name::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn opcode(&self) -> u32 {
+                match self {
+                    $($name::$variant => $op,)*
+                }
+            }
+
+            /// The instruction's name in the text format.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text,)*
+                }
+            }
+
+            /// Looks up an instruction by its name in the text format.
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($text => Some($name::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.name())
+            }
+        }
+    };
+}
+
+pub mod atomic;
+pub mod expr;
+pub mod numeric;
+pub mod simd;
+
+use crate::encode::Encode;
+use crate::instr::atomic::AtomicOp;
+use crate::instr::numeric::{LoadOp, NumericInstr, StoreOp};
+use crate::instr::simd::{SimdImm, SimdOp};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    // Control instructions
+    Unreachable,
+    Nop,
+    Block(BlockType),
+    Loop(BlockType),
+    If(BlockType),
+    Else,
+    End,
+    Br(LabelIdx),
+    BrIf(LabelIdx),
+    BrTable(Vec<LabelIdx>, LabelIdx),
+    Return,
+    Call(FuncIdx),
+    CallIndirect(TypeIdx, TableIdx),
+    CallRef(TypeIdx),
+    ReturnCall(FuncIdx),
+    ReturnCallIndirect(TypeIdx, TableIdx),
+    ReturnCallRef(TypeIdx),
+    BrOnNull(LabelIdx),
+    BrOnNonNull(LabelIdx),
+
+    // Exception instructions
+    TryTable(BlockType, Vec<Catch>),
+    Throw(TagIdx),
+    ThrowRef,
+    // The legacy exception instructions, still emitted by older toolchains.
+    // `catch` and `catch_all` separate the handlers of a `try` block like
+    // `else` does for `if`, and `delegate` ends a `try` block in place of
+    // `end`.
+    Try(BlockType),
+    Catch(TagIdx),
+    CatchAll,
+    Delegate(LabelIdx),
+    Rethrow(LabelIdx),
+
+    // Reference instructions
+    RefNull(HeapType),
+    RefIsNull,
+    RefFunc(FuncIdx),
+    RefAsNonNull,
+    RefEq,
+
+    // Aggregate and cast instructions from the GC proposal
+    StructNew(TypeIdx),
+    StructNewDefault(TypeIdx),
+    StructGet(TypeIdx, FieldIdx),
+    StructGetS(TypeIdx, FieldIdx),
+    StructGetU(TypeIdx, FieldIdx),
+    StructSet(TypeIdx, FieldIdx),
+    ArrayNew(TypeIdx),
+    ArrayNewDefault(TypeIdx),
+    /// `array.new_fixed` with the number of operands to take.
+    ArrayNewFixed(TypeIdx, u32),
+    ArrayNewData(TypeIdx, DataIdx),
+    ArrayNewElem(TypeIdx, ElemIdx),
+    ArrayGet(TypeIdx),
+    ArrayGetS(TypeIdx),
+    ArrayGetU(TypeIdx),
+    ArraySet(TypeIdx),
+    ArrayLen,
+    ArrayFill(TypeIdx),
+    /// `array.copy` from an array of the second type into the first.
+    ArrayCopy(TypeIdx, TypeIdx),
+    ArrayInitData(TypeIdx, DataIdx),
+    ArrayInitElem(TypeIdx, ElemIdx),
+    RefTest(RefType),
+    RefCast(RefType),
+    /// `br_on_cast` with the label, the operand's type and the target type.
+    BrOnCast(LabelIdx, RefType, RefType),
+    BrOnCastFail(LabelIdx, RefType, RefType),
+    AnyConvertExtern,
+    ExternConvertAny,
+    RefI31,
+    I31GetS,
+    I31GetU,
+
+    // Parametric instructions
+    Drop,
+    /// `select`, with the explicit result types of the typed form.
+    Select(Option<Vec<ValType>>),
+
+    // Variable instructions
+    LocalGet(LocalIdx),
+    LocalSet(LocalIdx),
+    LocalTee(LocalIdx),
+    GlobalGet(GlobalIdx),
+    GlobalSet(GlobalIdx),
+
+    // Table instructions
+    TableGet(TableIdx),
+    TableSet(TableIdx),
+    TableInit(ElemIdx, TableIdx),
+    ElemDrop(ElemIdx),
+    /// `table.copy` from the second table into the first.
+    TableCopy(TableIdx, TableIdx),
+    TableGrow(TableIdx),
+    TableSize(TableIdx),
+    TableFill(TableIdx),
+
+    // Memory instructions
+    Load(LoadOp, MemArg),
+    Store(StoreOp, MemArg),
+    MemorySize(MemIdx),
+    MemoryGrow(MemIdx),
+    MemoryInit(DataIdx, MemIdx),
+    DataDrop(DataIdx),
+    /// `memory.copy` from the second memory into the first.
+    MemoryCopy(MemIdx, MemIdx),
+    MemoryFill(MemIdx),
+
+    // Numeric instructions
+    I32Const(i32),
+    I64Const(i64),
+    F32Const(f32),
+    F64Const(f64),
+    Numeric(NumericInstr),
+
+    // Vector instructions
+    Simd(SimdOp, SimdImm),
+
+    // Atomic instructions
+    Atomic(AtomicOp, MemArg),
+    AtomicFence,
+}
+
+// The names of the instructions that aren't in one of the opcode enums
+const NAMES: [&str; 87] = [
+    "unreachable",
+    "nop",
+    "block",
+    "loop",
+    "if",
+    "else",
+    "end",
+    "br",
+    "br_if",
+    "br_table",
+    "return",
+    "call",
+    "call_indirect",
+    "call_ref",
+    "return_call",
+    "return_call_indirect",
+    "return_call_ref",
+    "br_on_null",
+    "br_on_non_null",
+    "try_table",
+    "throw",
+    "throw_ref",
+    "try",
+    "catch",
+    "catch_all",
+    "delegate",
+    "rethrow",
+    "ref.null",
+    "ref.is_null",
+    "ref.func",
+    "ref.as_non_null",
+    "ref.eq",
+    "struct.new",
+    "struct.new_default",
+    "struct.get",
+    "struct.get_s",
+    "struct.get_u",
+    "struct.set",
+    "array.new",
+    "array.new_default",
+    "array.new_fixed",
+    "array.new_data",
+    "array.new_elem",
+    "array.get",
+    "array.get_s",
+    "array.get_u",
+    "array.set",
+    "array.len",
+    "array.fill",
+    "array.copy",
+    "array.init_data",
+    "array.init_elem",
+    "ref.test",
+    "ref.cast",
+    "br_on_cast",
+    "br_on_cast_fail",
+    "any.convert_extern",
+    "extern.convert_any",
+    "ref.i31",
+    "i31.get_s",
+    "i31.get_u",
+    "drop",
+    "select",
+    "local.get",
+    "local.set",
+    "local.tee",
+    "global.get",
+    "global.set",
+    "table.get",
+    "table.set",
+    "table.init",
+    "elem.drop",
+    "table.copy",
+    "table.grow",
+    "table.size",
+    "table.fill",
+    "memory.size",
+    "memory.grow",
+    "memory.init",
+    "data.drop",
+    "memory.copy",
+    "memory.fill",
+    "i32.const",
+    "i64.const",
+    "f32.const",
+    "f64.const",
+    "atomic.fence",
+];
+
+impl Instr {
+    /// The instruction's name in the text format, without its immediates.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instr::Unreachable => "unreachable",
+            Instr::Nop => "nop",
+            Instr::Block(_) => "block",
+            Instr::Loop(_) => "loop",
+            Instr::If(_) => "if",
+            Instr::Else => "else",
+            Instr::End => "end",
+            Instr::Br(_) => "br",
+            Instr::BrIf(_) => "br_if",
+            Instr::BrTable(..) => "br_table",
+            Instr::Return => "return",
+            Instr::Call(_) => "call",
+            Instr::CallIndirect(..) => "call_indirect",
+            Instr::CallRef(_) => "call_ref",
+            Instr::ReturnCall(_) => "return_call",
+            Instr::ReturnCallIndirect(..) => "return_call_indirect",
+            Instr::ReturnCallRef(_) => "return_call_ref",
+            Instr::BrOnNull(_) => "br_on_null",
+            Instr::BrOnNonNull(_) => "br_on_non_null",
+            Instr::TryTable(..) => "try_table",
+            Instr::Throw(_) => "throw",
+            Instr::ThrowRef => "throw_ref",
+            Instr::Try(_) => "try",
+            Instr::Catch(_) => "catch",
+            Instr::CatchAll => "catch_all",
+            Instr::Delegate(_) => "delegate",
+            Instr::Rethrow(_) => "rethrow",
+            Instr::RefNull(_) => "ref.null",
+            Instr::RefIsNull => "ref.is_null",
+            Instr::RefFunc(_) => "ref.func",
+            Instr::RefAsNonNull => "ref.as_non_null",
+            Instr::RefEq => "ref.eq",
+            Instr::StructNew(_) => "struct.new",
+            Instr::StructNewDefault(_) => "struct.new_default",
+            Instr::StructGet(..) => "struct.get",
+            Instr::StructGetS(..) => "struct.get_s",
+            Instr::StructGetU(..) => "struct.get_u",
+            Instr::StructSet(..) => "struct.set",
+            Instr::ArrayNew(_) => "array.new",
+            Instr::ArrayNewDefault(_) => "array.new_default",
+            Instr::ArrayNewFixed(..) => "array.new_fixed",
+            Instr::ArrayNewData(..) => "array.new_data",
+            Instr::ArrayNewElem(..) => "array.new_elem",
+            Instr::ArrayGet(_) => "array.get",
+            Instr::ArrayGetS(_) => "array.get_s",
+            Instr::ArrayGetU(_) => "array.get_u",
+            Instr::ArraySet(_) => "array.set",
+            Instr::ArrayLen => "array.len",
+            Instr::ArrayFill(_) => "array.fill",
+            Instr::ArrayCopy(..) => "array.copy",
+            Instr::ArrayInitData(..) => "array.init_data",
+            Instr::ArrayInitElem(..) => "array.init_elem",
+            Instr::RefTest(_) => "ref.test",
+            Instr::RefCast(_) => "ref.cast",
+            Instr::BrOnCast(..) => "br_on_cast",
+            Instr::BrOnCastFail(..) => "br_on_cast_fail",
+            Instr::AnyConvertExtern => "any.convert_extern",
+            Instr::ExternConvertAny => "extern.convert_any",
+            Instr::RefI31 => "ref.i31",
+            Instr::I31GetS => "i31.get_s",
+            Instr::I31GetU => "i31.get_u",
+            Instr::Drop => "drop",
+            Instr::Select(_) => "select",
+            Instr::LocalGet(_) => "local.get",
+            Instr::LocalSet(_) => "local.set",
+            Instr::LocalTee(_) => "local.tee",
+            Instr::GlobalGet(_) => "global.get",
+            Instr::GlobalSet(_) => "global.set",
+            Instr::TableGet(_) => "table.get",
+            Instr::TableSet(_) => "table.set",
+            Instr::TableInit(..) => "table.init",
+            Instr::ElemDrop(_) => "elem.drop",
+            Instr::TableCopy(..) => "table.copy",
+            Instr::TableGrow(_) => "table.grow",
+            Instr::TableSize(_) => "table.size",
+            Instr::TableFill(_) => "table.fill",
+            Instr::MemorySize(_) => "memory.size",
+            Instr::MemoryGrow(_) => "memory.grow",
+            Instr::MemoryInit(..) => "memory.init",
+            Instr::DataDrop(_) => "data.drop",
+            Instr::MemoryCopy(..) => "memory.copy",
+            Instr::MemoryFill(_) => "memory.fill",
+            Instr::I32Const(_) => "i32.const",
+            Instr::I64Const(_) => "i64.const",
+            Instr::F32Const(_) => "f32.const",
+            Instr::F64Const(_) => "f64.const",
+            Instr::AtomicFence => "atomic.fence",
+            Instr::Load(op, _) => op.name(),
+            Instr::Store(op, _) => op.name(),
+            Instr::Numeric(op) => op.name(),
+            Instr::Simd(op, _) => op.name(),
+            Instr::Atomic(op, _) => op.name(),
+        }
+    }
+
+    /// The name of an instruction in the text format, as `name` returns
+    /// it, or `None` if no instruction has it.
+    pub fn find_name(name: &str) -> Option<&'static str> {
+        NAMES
+            .into_iter()
+            .find(|n| *n == name)
+            .or_else(|| NumericInstr::from_name(name).map(|op| op.name()))
+            .or_else(|| LoadOp::from_name(name).map(|op| op.name()))
+            .or_else(|| StoreOp::from_name(name).map(|op| op.name()))
+            .or_else(|| SimdOp::from_name(name).map(|op| op.name()))
+            .or_else(|| AtomicOp::from_name(name).map(|op| op.name()))
+    }
+
+    /// Whether this instruction opens a structured block that is closed by
+    /// a matching `end`.
+    pub fn opens_block(&self) -> bool {
+        matches!(
+            self,
+            Instr::Block(_) | Instr::Loop(_) | Instr::If(_) | Instr::Try(_) | Instr::TryTable(..)
+        )
+    }
+
+    /// Whether this is a tail call, which replaces the calling frame
+    /// instead of pushing a new one.
+    pub fn is_tail_call(&self) -> bool {
+        matches!(
+            self,
+            Instr::ReturnCall(_) | Instr::ReturnCallIndirect(..) | Instr::ReturnCallRef(_)
+        )
+    }
+
+    /// Whether this instruction closes the innermost open block.
+    pub fn closes_block(&self) -> bool {
+        matches!(self, Instr::End | Instr::Delegate(_))
+    }
+
+    /// Whether this instruction starts another arm of the innermost open
+    /// block, like `else` in an `if` or `catch` in a `try`.
+    pub fn continues_block(&self) -> bool {
+        matches!(self, Instr::Else | Instr::Catch(_) | Instr::CatchAll)
+    }
+
+    fn parse_0xfb(reader: &mut BufReader<dyn Read>) -> Result<Self> {
+        let op = u32::from(Leb128::<u32>::parse(reader)?);
+        let instr = match op {
+            0 => Instr::StructNew(TypeIdx::parse(reader)?),
+            1 => Instr::StructNewDefault(TypeIdx::parse(reader)?),
+            2..=5 => {
+                let ty = TypeIdx::parse(reader)?;
+                let field = FieldIdx::parse(reader)?;
+                match op {
+                    2 => Instr::StructGet(ty, field),
+                    3 => Instr::StructGetS(ty, field),
+                    4 => Instr::StructGetU(ty, field),
+                    _ => Instr::StructSet(ty, field),
+                }
+            }
+            6 => Instr::ArrayNew(TypeIdx::parse(reader)?),
+            7 => Instr::ArrayNewDefault(TypeIdx::parse(reader)?),
+            8 => {
+                let ty = TypeIdx::parse(reader)?;
+                Instr::ArrayNewFixed(ty, u32::from(Leb128::<u32>::parse(reader)?))
+            }
+            9 => {
+                let ty = TypeIdx::parse(reader)?;
+                Instr::ArrayNewData(ty, DataIdx::parse(reader)?)
+            }
+            10 => {
+                let ty = TypeIdx::parse(reader)?;
+                Instr::ArrayNewElem(ty, ElemIdx::parse(reader)?)
+            }
+            11 => Instr::ArrayGet(TypeIdx::parse(reader)?),
+            12 => Instr::ArrayGetS(TypeIdx::parse(reader)?),
+            13 => Instr::ArrayGetU(TypeIdx::parse(reader)?),
+            14 => Instr::ArraySet(TypeIdx::parse(reader)?),
+            15 => Instr::ArrayLen,
+            16 => Instr::ArrayFill(TypeIdx::parse(reader)?),
+            17 => {
+                let dst = TypeIdx::parse(reader)?;
+                Instr::ArrayCopy(dst, TypeIdx::parse(reader)?)
+            }
+            18 => {
+                let ty = TypeIdx::parse(reader)?;
+                Instr::ArrayInitData(ty, DataIdx::parse(reader)?)
+            }
+            19 => {
+                let ty = TypeIdx::parse(reader)?;
+                Instr::ArrayInitElem(ty, ElemIdx::parse(reader)?)
+            }
+            20 => Instr::RefTest(RefType::new(false, HeapType::parse(reader)?)),
+            21 => Instr::RefTest(RefType::new(true, HeapType::parse(reader)?)),
+            22 => Instr::RefCast(RefType::new(false, HeapType::parse(reader)?)),
+            23 => Instr::RefCast(RefType::new(true, HeapType::parse(reader)?)),
+            24 | 25 => {
+                // Bit 0 makes the operand type nullable, bit 1 the target
+                let flags = u8::parse(reader)?;
+                if flags & !0x03 != 0 {
+                    return Err(ParseError::Other(format!(
+                        "invalid br_on_cast flags: {:#04x}",
+                        flags
+                    )));
+                }
+                let label = LabelIdx::parse(reader)?;
+                let rt1 = RefType::new(flags & 0x01 != 0, HeapType::parse(reader)?);
+                let rt2 = RefType::new(flags & 0x02 != 0, HeapType::parse(reader)?);
+                if op == 24 {
+                    Instr::BrOnCast(label, rt1, rt2)
+                } else {
+                    Instr::BrOnCastFail(label, rt1, rt2)
+                }
+            }
+            26 => Instr::AnyConvertExtern,
+            27 => Instr::ExternConvertAny,
+            28 => Instr::RefI31,
+            29 => Instr::I31GetS,
+            30 => Instr::I31GetU,
+            op => return Err(unknown_opcode(0xFB, op)),
+        };
+        Ok(instr)
+    }
+
+    fn parse_0xfc(reader: &mut BufReader<dyn Read>) -> Result<Self> {
+        let op = u32::from(Leb128::<u32>::parse(reader)?);
+        let instr = match op {
+            0..=7 => match NumericInstr::from_opcode(0xFC00 | op) {
+                Some(instr) => Instr::Numeric(instr),
+                None => return Err(unknown_opcode(0xFC, op)),
+            },
+            8 => {
+                let data = DataIdx::parse(reader)?;
+                Instr::MemoryInit(data, MemIdx::parse(reader)?)
+            }
+            9 => Instr::DataDrop(DataIdx::parse(reader)?),
+            10 => {
+                let dst = MemIdx::parse(reader)?;
+                Instr::MemoryCopy(dst, MemIdx::parse(reader)?)
+            }
+            11 => Instr::MemoryFill(MemIdx::parse(reader)?),
+            12 => {
+                let elem = ElemIdx::parse(reader)?;
+                Instr::TableInit(elem, TableIdx::parse(reader)?)
+            }
+            13 => Instr::ElemDrop(ElemIdx::parse(reader)?),
+            14 => {
+                let dst = TableIdx::parse(reader)?;
+                Instr::TableCopy(dst, TableIdx::parse(reader)?)
+            }
+            15 => Instr::TableGrow(TableIdx::parse(reader)?),
+            16 => Instr::TableSize(TableIdx::parse(reader)?),
+            17 => Instr::TableFill(TableIdx::parse(reader)?),
+            op => return Err(unknown_opcode(0xFC, op)),
+        };
+        Ok(instr)
+    }
+
+    fn parse_0xfd(reader: &mut BufReader<dyn Read>) -> Result<Self> {
+        let op = u32::from(Leb128::<u32>::parse(reader)?);
+        let simd = match SimdOp::from_opcode(op) {
+            Some(simd) => simd,
+            None => return Err(unknown_opcode(0xFD, op)),
+        };
+
+        let imm = if simd.has_bytes() {
+            let mut bytes: [u8; 16] = [0; 16];
+            for byte in bytes.iter_mut() {
+                *byte = u8::parse(reader)?;
+            }
+            SimdImm::Bytes(bytes)
+        } else if simd.has_mem_arg() && simd.has_lane() {
+            let memarg = MemArg::parse(reader)?;
+            SimdImm::MemArgLane(memarg, u8::parse(reader)?)
+        } else if simd.has_mem_arg() {
+            SimdImm::MemArg(MemArg::parse(reader)?)
+        } else if simd.has_lane() {
+            SimdImm::Lane(u8::parse(reader)?)
+        } else {
+            SimdImm::None
+        };
+        Ok(Instr::Simd(simd, imm))
+    }
+
+    fn parse_0xfe(reader: &mut BufReader<dyn Read>) -> Result<Self> {
+        let op = u32::from(Leb128::<u32>::parse(reader)?);
+        if op == 0x03 {
+            let reserved = u8::parse(reader)?;
+            if reserved != 0 {
+                return Err(ParseError::Other(
+                    "atomic.fence reserved byte must be 0".to_string(),
+                ));
+            }
+            return Ok(Instr::AtomicFence);
+        }
+
+        match AtomicOp::from_opcode(op) {
+            Some(atomic) => Ok(Instr::Atomic(atomic, MemArg::parse(reader)?)),
+            None => Err(unknown_opcode(0xFE, op)),
+        }
+    }
+}
+
+fn unknown_opcode(prefix: u8, op: u32) -> ParseError {
+    ParseError::Other(format!("unknown opcode: {:#04x} {}", prefix, op))
+}
+
+impl Parseable for Instr {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let opcode = u8::parse(reader)?;
+        let instr = match opcode {
+            0x00 => Instr::Unreachable,
+            0x01 => Instr::Nop,
+            0x02 => Instr::Block(BlockType::parse(reader)?),
+            0x03 => Instr::Loop(BlockType::parse(reader)?),
+            0x04 => Instr::If(BlockType::parse(reader)?),
+            0x05 => Instr::Else,
+            0x06 => Instr::Try(BlockType::parse(reader)?),
+            0x07 => Instr::Catch(TagIdx::parse(reader)?),
+            0x08 => Instr::Throw(TagIdx::parse(reader)?),
+            0x09 => Instr::Rethrow(LabelIdx::parse(reader)?),
+            0x0A => Instr::ThrowRef,
+            0x0B => Instr::End,
+            0x0C => Instr::Br(LabelIdx::parse(reader)?),
+            0x0D => Instr::BrIf(LabelIdx::parse(reader)?),
+            0x0E => {
+                let labels = Vec::<LabelIdx>::parse(reader)?;
+                Instr::BrTable(labels, LabelIdx::parse(reader)?)
+            }
+            0x0F => Instr::Return,
+            0x10 => Instr::Call(FuncIdx::parse(reader)?),
+            0x11 => {
+                let ty = TypeIdx::parse(reader)?;
+                Instr::CallIndirect(ty, TableIdx::parse(reader)?)
+            }
+            0x12 => Instr::ReturnCall(FuncIdx::parse(reader)?),
+            0x13 => {
+                let ty = TypeIdx::parse(reader)?;
+                Instr::ReturnCallIndirect(ty, TableIdx::parse(reader)?)
+            }
+            0x14 => Instr::CallRef(TypeIdx::parse(reader)?),
+            0x15 => Instr::ReturnCallRef(TypeIdx::parse(reader)?),
+            0x18 => Instr::Delegate(LabelIdx::parse(reader)?),
+            0x19 => Instr::CatchAll,
+            0x1A => Instr::Drop,
+            0x1B => Instr::Select(None),
+            0x1C => Instr::Select(Some(Vec::<ValType>::parse(reader)?)),
+            0x1F => {
+                let bt = BlockType::parse(reader)?;
+                Instr::TryTable(bt, Vec::<Catch>::parse(reader)?)
+            }
+            0x20 => Instr::LocalGet(LocalIdx::parse(reader)?),
+            0x21 => Instr::LocalSet(LocalIdx::parse(reader)?),
+            0x22 => Instr::LocalTee(LocalIdx::parse(reader)?),
+            0x23 => Instr::GlobalGet(GlobalIdx::parse(reader)?),
+            0x24 => Instr::GlobalSet(GlobalIdx::parse(reader)?),
+            0x25 => Instr::TableGet(TableIdx::parse(reader)?),
+            0x26 => Instr::TableSet(TableIdx::parse(reader)?),
+            0x28..=0x35 => match LoadOp::from_opcode(u32::from(opcode)) {
+                Some(op) => Instr::Load(op, MemArg::parse(reader)?),
+                None => return Err(unknown_opcode(opcode, 0)),
+            },
+            0x36..=0x3E => match StoreOp::from_opcode(u32::from(opcode)) {
+                Some(op) => Instr::Store(op, MemArg::parse(reader)?),
+                None => return Err(unknown_opcode(opcode, 0)),
+            },
+            0x3F => Instr::MemorySize(MemIdx::parse(reader)?),
+            0x40 => Instr::MemoryGrow(MemIdx::parse(reader)?),
+            0x41 => Instr::I32Const(i32::from(Leb128::<i32>::parse(reader)?)),
+            0x42 => Instr::I64Const(i64::from(Leb128::<i64>::parse(reader)?)),
+            0x43 => {
+                let mut buf: [u8; 4] = [0; 4];
+                for byte in buf.iter_mut() {
+                    *byte = u8::parse(reader)?;
+                }
+                Instr::F32Const(f32::from_le_bytes(buf))
+            }
+            0x44 => {
+                let mut buf: [u8; 8] = [0; 8];
+                for byte in buf.iter_mut() {
+                    *byte = u8::parse(reader)?;
+                }
+                Instr::F64Const(f64::from_le_bytes(buf))
+            }
+            0x45..=0xC4 => match NumericInstr::from_opcode(u32::from(opcode)) {
+                Some(instr) => Instr::Numeric(instr),
+                None => return Err(unknown_opcode(opcode, 0)),
+            },
+            0xD0 => Instr::RefNull(HeapType::parse(reader)?),
+            0xD1 => Instr::RefIsNull,
+            0xD2 => Instr::RefFunc(FuncIdx::parse(reader)?),
+            0xD3 => Instr::RefEq,
+            0xD4 => Instr::RefAsNonNull,
+            0xD5 => Instr::BrOnNull(LabelIdx::parse(reader)?),
+            0xD6 => Instr::BrOnNonNull(LabelIdx::parse(reader)?),
+            0xFB => Self::parse_0xfb(reader)?,
+            0xFC => Self::parse_0xfc(reader)?,
+            0xFD => Self::parse_0xfd(reader)?,
+            0xFE => Self::parse_0xfe(reader)?,
+            _ => {
+                return Err(ParseError::Other(format!(
+                    "unknown opcode: {:#04x}",
+                    opcode
+                )));
+            }
+        };
+        Ok(instr)
+    }
+}
+
+impl Instr {
+    // Writes the opcode of an instruction behind one of the prefix bytes
+    fn encode_prefixed(writer: &mut dyn Write, prefix: u8, op: u32) -> std::io::Result<()> {
+        writer.write_all(&[prefix])?;
+        Leb128::from(op).encode(writer)
+    }
+
+    fn encode_heap_op(
+        writer: &mut dyn Write,
+        op: u32,
+        nullable_op: u32,
+        rt: &RefType,
+    ) -> std::io::Result<()> {
+        let op = if rt.is_nullable() { nullable_op } else { op };
+        Self::encode_prefixed(writer, 0xFB, op)?;
+        rt.heap_type().encode(writer)
+    }
+
+    fn encode_br_on_cast(
+        writer: &mut dyn Write,
+        op: u32,
+        label: &LabelIdx,
+        rt1: &RefType,
+        rt2: &RefType,
+    ) -> std::io::Result<()> {
+        Self::encode_prefixed(writer, 0xFB, op)?;
+        let flags = u8::from(rt1.is_nullable()) | u8::from(rt2.is_nullable()) << 1;
+        writer.write_all(&[flags])?;
+        label.encode(writer)?;
+        rt1.heap_type().encode(writer)?;
+        rt2.heap_type().encode(writer)
+    }
+}
+
+impl Encode for Instr {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            Instr::Unreachable => writer.write_all(&[0x00]),
+            Instr::Nop => writer.write_all(&[0x01]),
+            Instr::Else => writer.write_all(&[0x05]),
+            Instr::ThrowRef => writer.write_all(&[0x0A]),
+            Instr::End => writer.write_all(&[0x0B]),
+            Instr::Return => writer.write_all(&[0x0F]),
+            Instr::CatchAll => writer.write_all(&[0x19]),
+            Instr::Drop => writer.write_all(&[0x1A]),
+            Instr::Select(None) => writer.write_all(&[0x1B]),
+            Instr::RefIsNull => writer.write_all(&[0xD1]),
+            Instr::RefEq => writer.write_all(&[0xD3]),
+            Instr::RefAsNonNull => writer.write_all(&[0xD4]),
+            Instr::Numeric(instr) => match instr.opcode() {
+                op @ 0..=0xFF => writer.write_all(&[op as u8]),
+                op => Self::encode_prefixed(writer, 0xFC, op & 0xFF),
+            },
+            Instr::Block(bt) => {
+                writer.write_all(&[0x02])?;
+                bt.encode(writer)
+            }
+            Instr::Loop(bt) => {
+                writer.write_all(&[0x03])?;
+                bt.encode(writer)
+            }
+            Instr::If(bt) => {
+                writer.write_all(&[0x04])?;
+                bt.encode(writer)
+            }
+            Instr::Try(bt) => {
+                writer.write_all(&[0x06])?;
+                bt.encode(writer)
+            }
+            Instr::Catch(tag) => {
+                writer.write_all(&[0x07])?;
+                tag.encode(writer)
+            }
+            Instr::Throw(tag) => {
+                writer.write_all(&[0x08])?;
+                tag.encode(writer)
+            }
+            Instr::Rethrow(label) => {
+                writer.write_all(&[0x09])?;
+                label.encode(writer)
+            }
+            Instr::Br(label) => {
+                writer.write_all(&[0x0C])?;
+                label.encode(writer)
+            }
+            Instr::BrIf(label) => {
+                writer.write_all(&[0x0D])?;
+                label.encode(writer)
+            }
+            Instr::BrTable(labels, default) => {
+                writer.write_all(&[0x0E])?;
+                labels.encode(writer)?;
+                default.encode(writer)
+            }
+            Instr::Call(func) => {
+                writer.write_all(&[0x10])?;
+                func.encode(writer)
+            }
+            Instr::CallIndirect(ty, table) => {
+                writer.write_all(&[0x11])?;
+                ty.encode(writer)?;
+                table.encode(writer)
+            }
+            Instr::ReturnCall(func) => {
+                writer.write_all(&[0x12])?;
+                func.encode(writer)
+            }
+            Instr::ReturnCallIndirect(ty, table) => {
+                writer.write_all(&[0x13])?;
+                ty.encode(writer)?;
+                table.encode(writer)
+            }
+            Instr::CallRef(ty) => {
+                writer.write_all(&[0x14])?;
+                ty.encode(writer)
+            }
+            Instr::ReturnCallRef(ty) => {
+                writer.write_all(&[0x15])?;
+                ty.encode(writer)
+            }
+            Instr::Delegate(label) => {
+                writer.write_all(&[0x18])?;
+                label.encode(writer)
+            }
+            Instr::Select(Some(types)) => {
+                writer.write_all(&[0x1C])?;
+                types.encode(writer)
+            }
+            Instr::TryTable(bt, catches) => {
+                writer.write_all(&[0x1F])?;
+                bt.encode(writer)?;
+                catches.encode(writer)
+            }
+            Instr::LocalGet(x) => {
+                writer.write_all(&[0x20])?;
+                x.encode(writer)
+            }
+            Instr::LocalSet(x) => {
+                writer.write_all(&[0x21])?;
+                x.encode(writer)
+            }
+            Instr::LocalTee(x) => {
+                writer.write_all(&[0x22])?;
+                x.encode(writer)
+            }
+            Instr::GlobalGet(x) => {
+                writer.write_all(&[0x23])?;
+                x.encode(writer)
+            }
+            Instr::GlobalSet(x) => {
+                writer.write_all(&[0x24])?;
+                x.encode(writer)
+            }
+            Instr::TableGet(x) => {
+                writer.write_all(&[0x25])?;
+                x.encode(writer)
+            }
+            Instr::TableSet(x) => {
+                writer.write_all(&[0x26])?;
+                x.encode(writer)
+            }
+            Instr::Load(op, memarg) => {
+                writer.write_all(&[op.opcode() as u8])?;
+                memarg.encode(writer)
+            }
+            Instr::Store(op, memarg) => {
+                writer.write_all(&[op.opcode() as u8])?;
+                memarg.encode(writer)
+            }
+            Instr::MemorySize(mem) => {
+                writer.write_all(&[0x3F])?;
+                mem.encode(writer)
+            }
+            Instr::MemoryGrow(mem) => {
+                writer.write_all(&[0x40])?;
+                mem.encode(writer)
+            }
+            Instr::I32Const(n) => {
+                writer.write_all(&[0x41])?;
+                Leb128::from(*n).encode(writer)
+            }
+            Instr::I64Const(n) => {
+                writer.write_all(&[0x42])?;
+                Leb128::from(*n).encode(writer)
+            }
+            Instr::F32Const(n) => {
+                writer.write_all(&[0x43])?;
+                writer.write_all(&n.to_le_bytes())
+            }
+            Instr::F64Const(n) => {
+                writer.write_all(&[0x44])?;
+                writer.write_all(&n.to_le_bytes())
+            }
+            Instr::RefNull(heap) => {
+                writer.write_all(&[0xD0])?;
+                heap.encode(writer)
+            }
+            Instr::RefFunc(func) => {
+                writer.write_all(&[0xD2])?;
+                func.encode(writer)
+            }
+            Instr::BrOnNull(label) => {
+                writer.write_all(&[0xD5])?;
+                label.encode(writer)
+            }
+            Instr::BrOnNonNull(label) => {
+                writer.write_all(&[0xD6])?;
+                label.encode(writer)
+            }
+            Instr::StructNew(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 0)?;
+                ty.encode(writer)
+            }
+            Instr::StructNewDefault(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 1)?;
+                ty.encode(writer)
+            }
+            Instr::StructGet(ty, field)
+            | Instr::StructGetS(ty, field)
+            | Instr::StructGetU(ty, field)
+            | Instr::StructSet(ty, field) => {
+                let op = match self {
+                    Instr::StructGet(..) => 2,
+                    Instr::StructGetS(..) => 3,
+                    Instr::StructGetU(..) => 4,
+                    _ => 5,
+                };
+                Self::encode_prefixed(writer, 0xFB, op)?;
+                ty.encode(writer)?;
+                field.encode(writer)
+            }
+            Instr::ArrayNew(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 6)?;
+                ty.encode(writer)
+            }
+            Instr::ArrayNewDefault(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 7)?;
+                ty.encode(writer)
+            }
+            Instr::ArrayNewFixed(ty, n) => {
+                Self::encode_prefixed(writer, 0xFB, 8)?;
+                ty.encode(writer)?;
+                Leb128::from(*n).encode(writer)
+            }
+            Instr::ArrayNewData(ty, data) => {
+                Self::encode_prefixed(writer, 0xFB, 9)?;
+                ty.encode(writer)?;
+                data.encode(writer)
+            }
+            Instr::ArrayNewElem(ty, elem) => {
+                Self::encode_prefixed(writer, 0xFB, 10)?;
+                ty.encode(writer)?;
+                elem.encode(writer)
+            }
+            Instr::ArrayGet(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 11)?;
+                ty.encode(writer)
+            }
+            Instr::ArrayGetS(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 12)?;
+                ty.encode(writer)
+            }
+            Instr::ArrayGetU(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 13)?;
+                ty.encode(writer)
+            }
+            Instr::ArraySet(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 14)?;
+                ty.encode(writer)
+            }
+            Instr::ArrayLen => Self::encode_prefixed(writer, 0xFB, 15),
+            Instr::ArrayFill(ty) => {
+                Self::encode_prefixed(writer, 0xFB, 16)?;
+                ty.encode(writer)
+            }
+            Instr::ArrayCopy(dst, src) => {
+                Self::encode_prefixed(writer, 0xFB, 17)?;
+                dst.encode(writer)?;
+                src.encode(writer)
+            }
+            Instr::ArrayInitData(ty, data) => {
+                Self::encode_prefixed(writer, 0xFB, 18)?;
+                ty.encode(writer)?;
+                data.encode(writer)
+            }
+            Instr::ArrayInitElem(ty, elem) => {
+                Self::encode_prefixed(writer, 0xFB, 19)?;
+                ty.encode(writer)?;
+                elem.encode(writer)
+            }
+            Instr::RefTest(rt) => Self::encode_heap_op(writer, 20, 21, rt),
+            Instr::RefCast(rt) => Self::encode_heap_op(writer, 22, 23, rt),
+            Instr::BrOnCast(label, rt1, rt2) => {
+                Self::encode_br_on_cast(writer, 24, label, rt1, rt2)
+            }
+            Instr::BrOnCastFail(label, rt1, rt2) => {
+                Self::encode_br_on_cast(writer, 25, label, rt1, rt2)
+            }
+            Instr::AnyConvertExtern => Self::encode_prefixed(writer, 0xFB, 26),
+            Instr::ExternConvertAny => Self::encode_prefixed(writer, 0xFB, 27),
+            Instr::RefI31 => Self::encode_prefixed(writer, 0xFB, 28),
+            Instr::I31GetS => Self::encode_prefixed(writer, 0xFB, 29),
+            Instr::I31GetU => Self::encode_prefixed(writer, 0xFB, 30),
+            Instr::MemoryInit(data, mem) => {
+                Self::encode_prefixed(writer, 0xFC, 8)?;
+                data.encode(writer)?;
+                mem.encode(writer)
+            }
+            Instr::DataDrop(data) => {
+                Self::encode_prefixed(writer, 0xFC, 9)?;
+                data.encode(writer)
+            }
+            Instr::MemoryCopy(dst, src) => {
+                Self::encode_prefixed(writer, 0xFC, 10)?;
+                dst.encode(writer)?;
+                src.encode(writer)
+            }
+            Instr::MemoryFill(mem) => {
+                Self::encode_prefixed(writer, 0xFC, 11)?;
+                mem.encode(writer)
+            }
+            Instr::TableInit(elem, table) => {
+                Self::encode_prefixed(writer, 0xFC, 12)?;
+                elem.encode(writer)?;
+                table.encode(writer)
+            }
+            Instr::ElemDrop(elem) => {
+                Self::encode_prefixed(writer, 0xFC, 13)?;
+                elem.encode(writer)
+            }
+            Instr::TableCopy(dst, src) => {
+                Self::encode_prefixed(writer, 0xFC, 14)?;
+                dst.encode(writer)?;
+                src.encode(writer)
+            }
+            Instr::TableGrow(table) => {
+                Self::encode_prefixed(writer, 0xFC, 15)?;
+                table.encode(writer)
+            }
+            Instr::TableSize(table) => {
+                Self::encode_prefixed(writer, 0xFC, 16)?;
+                table.encode(writer)
+            }
+            Instr::TableFill(table) => {
+                Self::encode_prefixed(writer, 0xFC, 17)?;
+                table.encode(writer)
+            }
+            Instr::Simd(op, imm) => {
+                Self::encode_prefixed(writer, 0xFD, op.opcode())?;
+                imm.encode(writer)
+            }
+            Instr::AtomicFence => {
+                Self::encode_prefixed(writer, 0xFE, 0x03)?;
+                writer.write_all(&[0x00])
+            }
+            Instr::Atomic(op, memarg) => {
+                Self::encode_prefixed(writer, 0xFE, op.opcode())?;
+                memarg.encode(writer)
+            }
+        }
+    }
+}
+
+impl Display for Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instr::Unreachable => write!(f, "unreachable"),
+            Instr::Nop => write!(f, "nop"),
+            Instr::Block(bt) => write!(f, "block{}", bt),
+            Instr::Loop(bt) => write!(f, "loop{}", bt),
+            Instr::If(bt) => write!(f, "if{}", bt),
+            Instr::Else => write!(f, "else"),
+            Instr::End => write!(f, "end"),
+            Instr::Br(l) => write!(f, "br {}", l.0),
+            Instr::BrIf(l) => write!(f, "br_if {}", l.0),
+            Instr::BrTable(labels, default) => {
+                write!(f, "br_table")?;
+                for l in labels {
+                    write!(f, " {}", l.0)?;
+                }
+                write!(f, " {}", default.0)
+            }
+            Instr::Return => write!(f, "return"),
+            Instr::Call(x) => write!(f, "call {}", x.0),
+            Instr::CallIndirect(ty, table) => {
+                write!(f, "call_indirect {} (type {})", table.0, ty.0)
+            }
+            Instr::CallRef(ty) => write!(f, "call_ref {}", ty.0),
+            Instr::ReturnCall(x) => write!(f, "return_call {}", x.0),
+            Instr::ReturnCallIndirect(ty, table) => {
+                write!(f, "return_call_indirect {} (type {})", table.0, ty.0)
+            }
+            Instr::ReturnCallRef(ty) => write!(f, "return_call_ref {}", ty.0),
+            Instr::BrOnNull(l) => write!(f, "br_on_null {}", l.0),
+            Instr::BrOnNonNull(l) => write!(f, "br_on_non_null {}", l.0),
+            Instr::TryTable(bt, catches) => {
+                write!(f, "try_table{}", bt)?;
+                for catch in catches {
+                    write!(f, " {}", catch)?;
+                }
+                Ok(())
+            }
+            Instr::Throw(tag) => write!(f, "throw {}", tag.0),
+            Instr::ThrowRef => write!(f, "throw_ref"),
+            Instr::Try(bt) => write!(f, "try{}", bt),
+            Instr::Catch(tag) => write!(f, "catch {}", tag.0),
+            Instr::CatchAll => write!(f, "catch_all"),
+            Instr::Delegate(l) => write!(f, "delegate {}", l.0),
+            Instr::Rethrow(l) => write!(f, "rethrow {}", l.0),
+            Instr::RefNull(t) => write!(f, "ref.null {}", t),
+            Instr::RefIsNull => write!(f, "ref.is_null"),
+            Instr::RefFunc(x) => write!(f, "ref.func {}", x.0),
+            Instr::RefAsNonNull => write!(f, "ref.as_non_null"),
+            Instr::RefEq => write!(f, "ref.eq"),
+            Instr::StructNew(ty) => write!(f, "struct.new {}", ty.0),
+            Instr::StructNewDefault(ty) => write!(f, "struct.new_default {}", ty.0),
+            Instr::StructGet(ty, x) => write!(f, "struct.get {} {}", ty.0, x.0),
+            Instr::StructGetS(ty, x) => write!(f, "struct.get_s {} {}", ty.0, x.0),
+            Instr::StructGetU(ty, x) => write!(f, "struct.get_u {} {}", ty.0, x.0),
+            Instr::StructSet(ty, x) => write!(f, "struct.set {} {}", ty.0, x.0),
+            Instr::ArrayNew(ty) => write!(f, "array.new {}", ty.0),
+            Instr::ArrayNewDefault(ty) => write!(f, "array.new_default {}", ty.0),
+            Instr::ArrayNewFixed(ty, n) => write!(f, "array.new_fixed {} {}", ty.0, n),
+            Instr::ArrayNewData(ty, d) => write!(f, "array.new_data {} {}", ty.0, d.0),
+            Instr::ArrayNewElem(ty, e) => write!(f, "array.new_elem {} {}", ty.0, e.0),
+            Instr::ArrayGet(ty) => write!(f, "array.get {}", ty.0),
+            Instr::ArrayGetS(ty) => write!(f, "array.get_s {}", ty.0),
+            Instr::ArrayGetU(ty) => write!(f, "array.get_u {}", ty.0),
+            Instr::ArraySet(ty) => write!(f, "array.set {}", ty.0),
+            Instr::ArrayLen => write!(f, "array.len"),
+            Instr::ArrayFill(ty) => write!(f, "array.fill {}", ty.0),
+            Instr::ArrayCopy(dst, src) => write!(f, "array.copy {} {}", dst.0, src.0),
+            Instr::ArrayInitData(ty, d) => write!(f, "array.init_data {} {}", ty.0, d.0),
+            Instr::ArrayInitElem(ty, e) => write!(f, "array.init_elem {} {}", ty.0, e.0),
+            Instr::RefTest(rt) => write!(f, "ref.test {}", rt),
+            Instr::RefCast(rt) => write!(f, "ref.cast {}", rt),
+            Instr::BrOnCast(l, rt1, rt2) => write!(f, "br_on_cast {} {} {}", l.0, rt1, rt2),
+            Instr::BrOnCastFail(l, rt1, rt2) => {
+                write!(f, "br_on_cast_fail {} {} {}", l.0, rt1, rt2)
+            }
+            Instr::AnyConvertExtern => write!(f, "any.convert_extern"),
+            Instr::ExternConvertAny => write!(f, "extern.convert_any"),
+            Instr::RefI31 => write!(f, "ref.i31"),
+            Instr::I31GetS => write!(f, "i31.get_s"),
+            Instr::I31GetU => write!(f, "i31.get_u"),
+            Instr::Drop => write!(f, "drop"),
+            Instr::Select(None) => write!(f, "select"),
+            Instr::Select(Some(types)) => {
+                write!(f, "select (result")?;
+                for t in types {
+                    write!(f, " {}", t.name())?;
+                }
+                write!(f, ")")
+            }
+            Instr::LocalGet(x) => write!(f, "local.get {}", x.0),
+            Instr::LocalSet(x) => write!(f, "local.set {}", x.0),
+            Instr::LocalTee(x) => write!(f, "local.tee {}", x.0),
+            Instr::GlobalGet(x) => write!(f, "global.get {}", x.0),
+            Instr::GlobalSet(x) => write!(f, "global.set {}", x.0),
+            Instr::TableGet(x) => write!(f, "table.get {}", x.0),
+            Instr::TableSet(x) => write!(f, "table.set {}", x.0),
+            Instr::TableInit(e, t) => write!(f, "table.init {} {}", t.0, e.0),
+            Instr::ElemDrop(e) => write!(f, "elem.drop {}", e.0),
+            Instr::TableCopy(dst, src) => write!(f, "table.copy {} {}", dst.0, src.0),
+            Instr::TableGrow(x) => write!(f, "table.grow {}", x.0),
+            Instr::TableSize(x) => write!(f, "table.size {}", x.0),
+            Instr::TableFill(x) => write!(f, "table.fill {}", x.0),
+            Instr::Load(op, memarg) => write!(f, "{} {}", op, memarg),
+            Instr::Store(op, memarg) => write!(f, "{} {}", op, memarg),
+            Instr::MemorySize(m) => write!(f, "memory.size {}", m.0),
+            Instr::MemoryGrow(m) => write!(f, "memory.grow {}", m.0),
+            Instr::MemoryInit(d, m) => write!(f, "memory.init {} {}", m.0, d.0),
+            Instr::DataDrop(d) => write!(f, "data.drop {}", d.0),
+            Instr::MemoryCopy(dst, src) => write!(f, "memory.copy {} {}", dst.0, src.0),
+            Instr::MemoryFill(m) => write!(f, "memory.fill {}", m.0),
+            Instr::I32Const(n) => write!(f, "i32.const {}", n),
+            Instr::I64Const(n) => write!(f, "i64.const {}", n),
+            Instr::F32Const(n) => write!(f, "f32.const {}", n),
+            Instr::F64Const(n) => write!(f, "f64.const {}", n),
+            Instr::Numeric(instr) => write!(f, "{}", instr),
+            Instr::Simd(op, imm) => write!(f, "{}{}", op, imm),
+            Instr::Atomic(op, memarg) => write!(f, "{} {}", op, memarg),
+            Instr::AtomicFence => write!(f, "atomic.fence"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::num_type::NumType;
+    use std::io::Cursor;
+
+    // Parses the instructions and checks that they encode back to `bytes`,
+    // and that their names are the ones they print with
+    fn parse_all(bytes: &[u8]) -> Vec<Instr> {
+        let len = bytes.len() as u64;
+        let mut reader = BufReader::new(Cursor::new(bytes.to_vec()));
+        let mut instrs = Vec::new();
+        while reader.get_ref().position() < len || !reader.buffer().is_empty() {
+            instrs.push(Instr::parse(&mut reader).expect("The parsed instruction"));
+        }
+
+        let mut encoded = Vec::new();
+        for instr in &instrs {
+            instr.encode(&mut encoded).expect("The encoded instruction");
+        }
+        assert_eq!(encoded, bytes);
+        for instr in &instrs {
+            let text = instr.to_string();
+            assert_eq!(text.split([' ', '(']).next(), Some(instr.name()));
+            assert_eq!(Instr::find_name(instr.name()), Some(instr.name()));
+        }
+        instrs
+    }
+
+    #[test]
+    fn test_control() {
+        let bytes = [
+            0x02, 0x7f, 0x41, 0x01, 0x0d, 0x00, 0x0e, 0x02, 0x00, 0x01, 0x02, 0x0b, 0x11, 0x03,
+            0x00,
+        ];
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::Block(BlockType::Value(ValType::Num(NumType::I32))),
+                Instr::I32Const(1),
+                Instr::BrIf(LabelIdx(0)),
+                Instr::BrTable(vec![LabelIdx(0), LabelIdx(1)], LabelIdx(2)),
+                Instr::End,
+                Instr::CallIndirect(TypeIdx(3), TableIdx(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memory() {
+        // i32.load offset=4 align=4; i64.store on memory 1; memory.grow 2;
+        // memory.copy 1 0
+        let bytes = [
+            0x28, 0x02, 0x04, 0x37, 0x43, 0x01, 0x00, 0x40, 0x02, 0xfc, 0x0a, 0x01, 0x00,
+        ];
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::Load(LoadOp::I32Load, MemArg::new(2, 4, None)),
+                Instr::Store(StoreOp::I64Store, MemArg::new(3, 0, Some(MemIdx(1)))),
+                Instr::MemoryGrow(MemIdx(2)),
+                Instr::MemoryCopy(MemIdx(1), MemIdx(0)),
+            ]
+        );
+        assert_eq!(instrs[1].to_string(), "i64.store 1 align=8");
+    }
+
+    #[test]
+    fn test_numeric() {
+        let bytes = [
+            0x42, 0x7f, 0x43, 0x00, 0x00, 0x80, 0x3f, 0x6a, 0xc4, 0xfc, 0x07,
+        ];
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::I64Const(-1),
+                Instr::F32Const(1.0),
+                Instr::Numeric(NumericInstr::I32Add),
+                Instr::Numeric(NumericInstr::I64Extend32S),
+                Instr::Numeric(NumericInstr::I64TruncSatF64U),
+            ]
+        );
+        assert_eq!(instrs[4].to_string(), "i64.trunc_sat_f64_u");
+    }
+
+    #[test]
+    fn test_simd_and_atomic() {
+        let mut bytes = vec![0xfd, 0x0c];
+        bytes.extend_from_slice(&[7; 16]);
+        bytes.extend_from_slice(&[0xfd, 0x54, 0x00, 0x08, 0x03]);
+        bytes.extend_from_slice(&[0xfd, 0x15, 0x0f]);
+        bytes.extend_from_slice(&[0xfd, 0xae, 0x01]);
+        bytes.extend_from_slice(&[0xfe, 0x03, 0x00]);
+        bytes.extend_from_slice(&[0xfe, 0x48, 0x02, 0x00]);
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::Simd(SimdOp::V128Const, SimdImm::Bytes([7; 16])),
+                Instr::Simd(
+                    SimdOp::V128Load8Lane,
+                    SimdImm::MemArgLane(MemArg::new(0, 8, None), 3)
+                ),
+                Instr::Simd(SimdOp::I8x16ExtractLaneS, SimdImm::Lane(15)),
+                Instr::Simd(SimdOp::I32x4Add, SimdImm::None),
+                Instr::AtomicFence,
+                Instr::Atomic(AtomicOp::I32AtomicRmwCmpxchg, MemArg::new(2, 0, None)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exceptions() {
+        // try_table (catch 0 0) (catch_all_ref 1); throw 0; end; throw_ref
+        let bytes = [
+            0x1f, 0x40, 0x02, 0x00, 0x00, 0x00, 0x03, 0x01, 0x08, 0x00, 0x0b, 0x0a,
+        ];
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::TryTable(
+                    BlockType::Empty,
+                    vec![
+                        Catch::Catch(TagIdx(0), LabelIdx(0)),
+                        Catch::CatchAllRef(LabelIdx(1))
+                    ]
+                ),
+                Instr::Throw(TagIdx(0)),
+                Instr::End,
+                Instr::ThrowRef,
+            ]
+        );
+        assert_eq!(
+            instrs[0].to_string(),
+            "try_table (catch 0 0) (catch_all_ref 1)"
+        );
+
+        // try; catch 1; rethrow 0; catch_all; end; try; delegate 0
+        let bytes = [
+            0x06, 0x40, 0x07, 0x01, 0x09, 0x00, 0x19, 0x0b, 0x06, 0x40, 0x18, 0x00,
+        ];
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::Try(BlockType::Empty),
+                Instr::Catch(TagIdx(1)),
+                Instr::Rethrow(LabelIdx(0)),
+                Instr::CatchAll,
+                Instr::End,
+                Instr::Try(BlockType::Empty),
+                Instr::Delegate(LabelIdx(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tail_calls_and_typed_refs() {
+        let bytes = [
+            0x12, 0x01, 0x13, 0x02, 0x00, 0x14, 0x03, 0x15, 0x03, 0xd0, 0x03, 0xd4, 0xd5, 0x00,
+            0xd6, 0x01,
+        ];
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::ReturnCall(FuncIdx(1)),
+                Instr::ReturnCallIndirect(TypeIdx(2), TableIdx(0)),
+                Instr::CallRef(TypeIdx(3)),
+                Instr::ReturnCallRef(TypeIdx(3)),
+                Instr::RefNull(HeapType::Concrete(TypeIdx(3))),
+                Instr::RefAsNonNull,
+                Instr::BrOnNull(LabelIdx(0)),
+                Instr::BrOnNonNull(LabelIdx(1)),
+            ]
+        );
+        assert!(instrs[0].is_tail_call());
+        assert!(!instrs[2].is_tail_call());
+    }
+
+    #[test]
+    fn test_gc() {
+        // struct.get 1 2; array.new_fixed 0 3; ref.test (ref null eq);
+        // ref.cast (ref 4); br_on_cast 0 anyref (ref i31); ref.i31; ref.eq
+        let bytes = [
+            0xfb, 0x02, 0x01, 0x02, 0xfb, 0x08, 0x00, 0x03, 0xfb, 0x15, 0x6d, 0xfb, 0x16, 0x04,
+            0xfb, 0x18, 0x01, 0x00, 0x6e, 0x6c, 0xfb, 0x1c, 0xd3,
+        ];
+        let instrs = parse_all(&bytes);
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::StructGet(TypeIdx(1), FieldIdx(2)),
+                Instr::ArrayNewFixed(TypeIdx(0), 3),
+                Instr::RefTest(RefType::new(true, HeapType::Eq)),
+                Instr::RefCast(RefType::new(false, HeapType::Concrete(TypeIdx(4)))),
+                Instr::BrOnCast(
+                    LabelIdx(0),
+                    RefType::new(true, HeapType::Any),
+                    RefType::new(false, HeapType::I31)
+                ),
+                Instr::RefI31,
+                Instr::RefEq,
+            ]
+        );
+        assert_eq!(instrs[2].to_string(), "ref.test eqref");
+        assert_eq!(instrs[4].to_string(), "br_on_cast 0 anyref (ref i31)");
+
+        // Only the two low bits of the br_on_cast flags are defined
+        let bytes = [0xfb, 0x19, 0x04, 0x00, 0x6e, 0x6c];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Instr::parse(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let bytes: [u8; 1] = [0xff];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Instr::parse(&mut reader).is_err());
+    }
+}
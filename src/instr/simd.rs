@@ -0,0 +1,327 @@
+use std::fmt::Display;
+use std::io::Write;
+
+use crate::encode::Encode;
+use crate::types::mem_arg::MemArg;
+
+// SIMD instructions follow the 0xFD prefix; the sub-opcode is a u32.
+// 0x100 and above are the relaxed-simd extension.
+opcodes! {
+    pub enum SimdOp {
+        0x00 => V128Load = "v128.load",
+        0x01 => V128Load8x8S = "v128.load8x8_s",
+        0x02 => V128Load8x8U = "v128.load8x8_u",
+        0x03 => V128Load16x4S = "v128.load16x4_s",
+        0x04 => V128Load16x4U = "v128.load16x4_u",
+        0x05 => V128Load32x2S = "v128.load32x2_s",
+        0x06 => V128Load32x2U = "v128.load32x2_u",
+        0x07 => V128Load8Splat = "v128.load8_splat",
+        0x08 => V128Load16Splat = "v128.load16_splat",
+        0x09 => V128Load32Splat = "v128.load32_splat",
+        0x0A => V128Load64Splat = "v128.load64_splat",
+        0x0B => V128Store = "v128.store",
+        0x0C => V128Const = "v128.const",
+        0x0D => I8x16Shuffle = "i8x16.shuffle",
+        0x0E => I8x16Swizzle = "i8x16.swizzle",
+        0x0F => I8x16Splat = "i8x16.splat",
+        0x10 => I16x8Splat = "i16x8.splat",
+        0x11 => I32x4Splat = "i32x4.splat",
+        0x12 => I64x2Splat = "i64x2.splat",
+        0x13 => F32x4Splat = "f32x4.splat",
+        0x14 => F64x2Splat = "f64x2.splat",
+        0x15 => I8x16ExtractLaneS = "i8x16.extract_lane_s",
+        0x16 => I8x16ExtractLaneU = "i8x16.extract_lane_u",
+        0x17 => I8x16ReplaceLane = "i8x16.replace_lane",
+        0x18 => I16x8ExtractLaneS = "i16x8.extract_lane_s",
+        0x19 => I16x8ExtractLaneU = "i16x8.extract_lane_u",
+        0x1A => I16x8ReplaceLane = "i16x8.replace_lane",
+        0x1B => I32x4ExtractLane = "i32x4.extract_lane",
+        0x1C => I32x4ReplaceLane = "i32x4.replace_lane",
+        0x1D => I64x2ExtractLane = "i64x2.extract_lane",
+        0x1E => I64x2ReplaceLane = "i64x2.replace_lane",
+        0x1F => F32x4ExtractLane = "f32x4.extract_lane",
+        0x20 => F32x4ReplaceLane = "f32x4.replace_lane",
+        0x21 => F64x2ExtractLane = "f64x2.extract_lane",
+        0x22 => F64x2ReplaceLane = "f64x2.replace_lane",
+        0x23 => I8x16Eq = "i8x16.eq",
+        0x24 => I8x16Ne = "i8x16.ne",
+        0x25 => I8x16LtS = "i8x16.lt_s",
+        0x26 => I8x16LtU = "i8x16.lt_u",
+        0x27 => I8x16GtS = "i8x16.gt_s",
+        0x28 => I8x16GtU = "i8x16.gt_u",
+        0x29 => I8x16LeS = "i8x16.le_s",
+        0x2A => I8x16LeU = "i8x16.le_u",
+        0x2B => I8x16GeS = "i8x16.ge_s",
+        0x2C => I8x16GeU = "i8x16.ge_u",
+        0x2D => I16x8Eq = "i16x8.eq",
+        0x2E => I16x8Ne = "i16x8.ne",
+        0x2F => I16x8LtS = "i16x8.lt_s",
+        0x30 => I16x8LtU = "i16x8.lt_u",
+        0x31 => I16x8GtS = "i16x8.gt_s",
+        0x32 => I16x8GtU = "i16x8.gt_u",
+        0x33 => I16x8LeS = "i16x8.le_s",
+        0x34 => I16x8LeU = "i16x8.le_u",
+        0x35 => I16x8GeS = "i16x8.ge_s",
+        0x36 => I16x8GeU = "i16x8.ge_u",
+        0x37 => I32x4Eq = "i32x4.eq",
+        0x38 => I32x4Ne = "i32x4.ne",
+        0x39 => I32x4LtS = "i32x4.lt_s",
+        0x3A => I32x4LtU = "i32x4.lt_u",
+        0x3B => I32x4GtS = "i32x4.gt_s",
+        0x3C => I32x4GtU = "i32x4.gt_u",
+        0x3D => I32x4LeS = "i32x4.le_s",
+        0x3E => I32x4LeU = "i32x4.le_u",
+        0x3F => I32x4GeS = "i32x4.ge_s",
+        0x40 => I32x4GeU = "i32x4.ge_u",
+        0x41 => F32x4Eq = "f32x4.eq",
+        0x42 => F32x4Ne = "f32x4.ne",
+        0x43 => F32x4Lt = "f32x4.lt",
+        0x44 => F32x4Gt = "f32x4.gt",
+        0x45 => F32x4Le = "f32x4.le",
+        0x46 => F32x4Ge = "f32x4.ge",
+        0x47 => F64x2Eq = "f64x2.eq",
+        0x48 => F64x2Ne = "f64x2.ne",
+        0x49 => F64x2Lt = "f64x2.lt",
+        0x4A => F64x2Gt = "f64x2.gt",
+        0x4B => F64x2Le = "f64x2.le",
+        0x4C => F64x2Ge = "f64x2.ge",
+        0x4D => V128Not = "v128.not",
+        0x4E => V128And = "v128.and",
+        0x4F => V128Andnot = "v128.andnot",
+        0x50 => V128Or = "v128.or",
+        0x51 => V128Xor = "v128.xor",
+        0x52 => V128Bitselect = "v128.bitselect",
+        0x53 => V128AnyTrue = "v128.any_true",
+        0x54 => V128Load8Lane = "v128.load8_lane",
+        0x55 => V128Load16Lane = "v128.load16_lane",
+        0x56 => V128Load32Lane = "v128.load32_lane",
+        0x57 => V128Load64Lane = "v128.load64_lane",
+        0x58 => V128Store8Lane = "v128.store8_lane",
+        0x59 => V128Store16Lane = "v128.store16_lane",
+        0x5A => V128Store32Lane = "v128.store32_lane",
+        0x5B => V128Store64Lane = "v128.store64_lane",
+        0x5C => V128Load32Zero = "v128.load32_zero",
+        0x5D => V128Load64Zero = "v128.load64_zero",
+        0x5E => F32x4DemoteF64x2Zero = "f32x4.demote_f64x2_zero",
+        0x5F => F64x2PromoteLowF32x4 = "f64x2.promote_low_f32x4",
+        0x60 => I8x16Abs = "i8x16.abs",
+        0x61 => I8x16Neg = "i8x16.neg",
+        0x62 => I8x16Popcnt = "i8x16.popcnt",
+        0x63 => I8x16AllTrue = "i8x16.all_true",
+        0x64 => I8x16Bitmask = "i8x16.bitmask",
+        0x65 => I8x16NarrowI16x8S = "i8x16.narrow_i16x8_s",
+        0x66 => I8x16NarrowI16x8U = "i8x16.narrow_i16x8_u",
+        0x67 => F32x4Ceil = "f32x4.ceil",
+        0x68 => F32x4Floor = "f32x4.floor",
+        0x69 => F32x4Trunc = "f32x4.trunc",
+        0x6A => F32x4Nearest = "f32x4.nearest",
+        0x6B => I8x16Shl = "i8x16.shl",
+        0x6C => I8x16ShrS = "i8x16.shr_s",
+        0x6D => I8x16ShrU = "i8x16.shr_u",
+        0x6E => I8x16Add = "i8x16.add",
+        0x6F => I8x16AddSatS = "i8x16.add_sat_s",
+        0x70 => I8x16AddSatU = "i8x16.add_sat_u",
+        0x71 => I8x16Sub = "i8x16.sub",
+        0x72 => I8x16SubSatS = "i8x16.sub_sat_s",
+        0x73 => I8x16SubSatU = "i8x16.sub_sat_u",
+        0x74 => F64x2Ceil = "f64x2.ceil",
+        0x75 => F64x2Floor = "f64x2.floor",
+        0x76 => I8x16MinS = "i8x16.min_s",
+        0x77 => I8x16MinU = "i8x16.min_u",
+        0x78 => I8x16MaxS = "i8x16.max_s",
+        0x79 => I8x16MaxU = "i8x16.max_u",
+        0x7A => F64x2Trunc = "f64x2.trunc",
+        0x7B => I8x16AvgrU = "i8x16.avgr_u",
+        0x7C => I16x8ExtaddPairwiseI8x16S = "i16x8.extadd_pairwise_i8x16_s",
+        0x7D => I16x8ExtaddPairwiseI8x16U = "i16x8.extadd_pairwise_i8x16_u",
+        0x7E => I32x4ExtaddPairwiseI16x8S = "i32x4.extadd_pairwise_i16x8_s",
+        0x7F => I32x4ExtaddPairwiseI16x8U = "i32x4.extadd_pairwise_i16x8_u",
+        0x80 => I16x8Abs = "i16x8.abs",
+        0x81 => I16x8Neg = "i16x8.neg",
+        0x82 => I16x8Q15mulrSatS = "i16x8.q15mulr_sat_s",
+        0x83 => I16x8AllTrue = "i16x8.all_true",
+        0x84 => I16x8Bitmask = "i16x8.bitmask",
+        0x85 => I16x8NarrowI32x4S = "i16x8.narrow_i32x4_s",
+        0x86 => I16x8NarrowI32x4U = "i16x8.narrow_i32x4_u",
+        0x87 => I16x8ExtendLowI8x16S = "i16x8.extend_low_i8x16_s",
+        0x88 => I16x8ExtendHighI8x16S = "i16x8.extend_high_i8x16_s",
+        0x89 => I16x8ExtendLowI8x16U = "i16x8.extend_low_i8x16_u",
+        0x8A => I16x8ExtendHighI8x16U = "i16x8.extend_high_i8x16_u",
+        0x8B => I16x8Shl = "i16x8.shl",
+        0x8C => I16x8ShrS = "i16x8.shr_s",
+        0x8D => I16x8ShrU = "i16x8.shr_u",
+        0x8E => I16x8Add = "i16x8.add",
+        0x8F => I16x8AddSatS = "i16x8.add_sat_s",
+        0x90 => I16x8AddSatU = "i16x8.add_sat_u",
+        0x91 => I16x8Sub = "i16x8.sub",
+        0x92 => I16x8SubSatS = "i16x8.sub_sat_s",
+        0x93 => I16x8SubSatU = "i16x8.sub_sat_u",
+        0x94 => F64x2Nearest = "f64x2.nearest",
+        0x95 => I16x8Mul = "i16x8.mul",
+        0x96 => I16x8MinS = "i16x8.min_s",
+        0x97 => I16x8MinU = "i16x8.min_u",
+        0x98 => I16x8MaxS = "i16x8.max_s",
+        0x99 => I16x8MaxU = "i16x8.max_u",
+        0x9B => I16x8AvgrU = "i16x8.avgr_u",
+        0x9C => I16x8ExtmulLowI8x16S = "i16x8.extmul_low_i8x16_s",
+        0x9D => I16x8ExtmulHighI8x16S = "i16x8.extmul_high_i8x16_s",
+        0x9E => I16x8ExtmulLowI8x16U = "i16x8.extmul_low_i8x16_u",
+        0x9F => I16x8ExtmulHighI8x16U = "i16x8.extmul_high_i8x16_u",
+        0xA0 => I32x4Abs = "i32x4.abs",
+        0xA1 => I32x4Neg = "i32x4.neg",
+        0xA3 => I32x4AllTrue = "i32x4.all_true",
+        0xA4 => I32x4Bitmask = "i32x4.bitmask",
+        0xA7 => I32x4ExtendLowI16x8S = "i32x4.extend_low_i16x8_s",
+        0xA8 => I32x4ExtendHighI16x8S = "i32x4.extend_high_i16x8_s",
+        0xA9 => I32x4ExtendLowI16x8U = "i32x4.extend_low_i16x8_u",
+        0xAA => I32x4ExtendHighI16x8U = "i32x4.extend_high_i16x8_u",
+        0xAB => I32x4Shl = "i32x4.shl",
+        0xAC => I32x4ShrS = "i32x4.shr_s",
+        0xAD => I32x4ShrU = "i32x4.shr_u",
+        0xAE => I32x4Add = "i32x4.add",
+        0xB1 => I32x4Sub = "i32x4.sub",
+        0xB5 => I32x4Mul = "i32x4.mul",
+        0xB6 => I32x4MinS = "i32x4.min_s",
+        0xB7 => I32x4MinU = "i32x4.min_u",
+        0xB8 => I32x4MaxS = "i32x4.max_s",
+        0xB9 => I32x4MaxU = "i32x4.max_u",
+        0xBA => I32x4DotI16x8S = "i32x4.dot_i16x8_s",
+        0xBC => I32x4ExtmulLowI16x8S = "i32x4.extmul_low_i16x8_s",
+        0xBD => I32x4ExtmulHighI16x8S = "i32x4.extmul_high_i16x8_s",
+        0xBE => I32x4ExtmulLowI16x8U = "i32x4.extmul_low_i16x8_u",
+        0xBF => I32x4ExtmulHighI16x8U = "i32x4.extmul_high_i16x8_u",
+        0xC0 => I64x2Abs = "i64x2.abs",
+        0xC1 => I64x2Neg = "i64x2.neg",
+        0xC3 => I64x2AllTrue = "i64x2.all_true",
+        0xC4 => I64x2Bitmask = "i64x2.bitmask",
+        0xC7 => I64x2ExtendLowI32x4S = "i64x2.extend_low_i32x4_s",
+        0xC8 => I64x2ExtendHighI32x4S = "i64x2.extend_high_i32x4_s",
+        0xC9 => I64x2ExtendLowI32x4U = "i64x2.extend_low_i32x4_u",
+        0xCA => I64x2ExtendHighI32x4U = "i64x2.extend_high_i32x4_u",
+        0xCB => I64x2Shl = "i64x2.shl",
+        0xCC => I64x2ShrS = "i64x2.shr_s",
+        0xCD => I64x2ShrU = "i64x2.shr_u",
+        0xCE => I64x2Add = "i64x2.add",
+        0xD1 => I64x2Sub = "i64x2.sub",
+        0xD5 => I64x2Mul = "i64x2.mul",
+        0xD6 => I64x2Eq = "i64x2.eq",
+        0xD7 => I64x2Ne = "i64x2.ne",
+        0xD8 => I64x2LtS = "i64x2.lt_s",
+        0xD9 => I64x2GtS = "i64x2.gt_s",
+        0xDA => I64x2LeS = "i64x2.le_s",
+        0xDB => I64x2GeS = "i64x2.ge_s",
+        0xDC => I64x2ExtmulLowI32x4S = "i64x2.extmul_low_i32x4_s",
+        0xDD => I64x2ExtmulHighI32x4S = "i64x2.extmul_high_i32x4_s",
+        0xDE => I64x2ExtmulLowI32x4U = "i64x2.extmul_low_i32x4_u",
+        0xDF => I64x2ExtmulHighI32x4U = "i64x2.extmul_high_i32x4_u",
+        0xE0 => F32x4Abs = "f32x4.abs",
+        0xE1 => F32x4Neg = "f32x4.neg",
+        0xE3 => F32x4Sqrt = "f32x4.sqrt",
+        0xE4 => F32x4Add = "f32x4.add",
+        0xE5 => F32x4Sub = "f32x4.sub",
+        0xE6 => F32x4Mul = "f32x4.mul",
+        0xE7 => F32x4Div = "f32x4.div",
+        0xE8 => F32x4Min = "f32x4.min",
+        0xE9 => F32x4Max = "f32x4.max",
+        0xEA => F32x4Pmin = "f32x4.pmin",
+        0xEB => F32x4Pmax = "f32x4.pmax",
+        0xEC => F64x2Abs = "f64x2.abs",
+        0xED => F64x2Neg = "f64x2.neg",
+        0xEF => F64x2Sqrt = "f64x2.sqrt",
+        0xF0 => F64x2Add = "f64x2.add",
+        0xF1 => F64x2Sub = "f64x2.sub",
+        0xF2 => F64x2Mul = "f64x2.mul",
+        0xF3 => F64x2Div = "f64x2.div",
+        0xF4 => F64x2Min = "f64x2.min",
+        0xF5 => F64x2Max = "f64x2.max",
+        0xF6 => F64x2Pmin = "f64x2.pmin",
+        0xF7 => F64x2Pmax = "f64x2.pmax",
+        0xF8 => I32x4TruncSatF32x4S = "i32x4.trunc_sat_f32x4_s",
+        0xF9 => I32x4TruncSatF32x4U = "i32x4.trunc_sat_f32x4_u",
+        0xFA => F32x4ConvertI32x4S = "f32x4.convert_i32x4_s",
+        0xFB => F32x4ConvertI32x4U = "f32x4.convert_i32x4_u",
+        0xFC => I32x4TruncSatF64x2SZero = "i32x4.trunc_sat_f64x2_s_zero",
+        0xFD => I32x4TruncSatF64x2UZero = "i32x4.trunc_sat_f64x2_u_zero",
+        0xFE => F64x2ConvertLowI32x4S = "f64x2.convert_low_i32x4_s",
+        0xFF => F64x2ConvertLowI32x4U = "f64x2.convert_low_i32x4_u",
+        0x100 => I8x16RelaxedSwizzle = "i8x16.relaxed_swizzle",
+        0x101 => I32x4RelaxedTruncF32x4S = "i32x4.relaxed_trunc_f32x4_s",
+        0x102 => I32x4RelaxedTruncF32x4U = "i32x4.relaxed_trunc_f32x4_u",
+        0x103 => I32x4RelaxedTruncF64x2SZero = "i32x4.relaxed_trunc_f64x2_s_zero",
+        0x104 => I32x4RelaxedTruncF64x2UZero = "i32x4.relaxed_trunc_f64x2_u_zero",
+        0x105 => F32x4RelaxedMadd = "f32x4.relaxed_madd",
+        0x106 => F32x4RelaxedNmadd = "f32x4.relaxed_nmadd",
+        0x107 => F64x2RelaxedMadd = "f64x2.relaxed_madd",
+        0x108 => F64x2RelaxedNmadd = "f64x2.relaxed_nmadd",
+        0x109 => I8x16RelaxedLaneselect = "i8x16.relaxed_laneselect",
+        0x10A => I16x8RelaxedLaneselect = "i16x8.relaxed_laneselect",
+        0x10B => I32x4RelaxedLaneselect = "i32x4.relaxed_laneselect",
+        0x10C => I64x2RelaxedLaneselect = "i64x2.relaxed_laneselect",
+        0x10D => F32x4RelaxedMin = "f32x4.relaxed_min",
+        0x10E => F32x4RelaxedMax = "f32x4.relaxed_max",
+        0x10F => F64x2RelaxedMin = "f64x2.relaxed_min",
+        0x110 => F64x2RelaxedMax = "f64x2.relaxed_max",
+        0x111 => I16x8RelaxedQ15mulrS = "i16x8.relaxed_q15mulr_s",
+        0x112 => I16x8RelaxedDotI8x16I7x16S = "i16x8.relaxed_dot_i8x16_i7x16_s",
+        0x113 => I32x4RelaxedDotI8x16I7x16AddS = "i32x4.relaxed_dot_i8x16_i7x16_add_s",    }
+}
+
+/// The immediates of a SIMD instruction, which depend on its opcode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimdImm {
+    None,
+    MemArg(MemArg),
+    Lane(u8),
+    MemArgLane(MemArg, u8),
+    /// The 16 bytes of `v128.const` or the lane indices of `i8x16.shuffle`.
+    Bytes([u8; 16]),
+}
+
+impl SimdOp {
+    /// Whether the instruction takes a memarg immediate.
+    pub fn has_mem_arg(&self) -> bool {
+        matches!(self.opcode(), 0x00..=0x0B | 0x54..=0x5D)
+    }
+
+    /// Whether the instruction takes a lane index immediate.
+    pub fn has_lane(&self) -> bool {
+        matches!(self.opcode(), 0x15..=0x22 | 0x54..=0x5B)
+    }
+
+    /// Whether the instruction takes 16 immediate bytes.
+    pub fn has_bytes(&self) -> bool {
+        matches!(self, SimdOp::V128Const | SimdOp::I8x16Shuffle)
+    }
+}
+
+impl Encode for SimdImm {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            SimdImm::None => Ok(()),
+            SimdImm::MemArg(memarg) => memarg.encode(writer),
+            SimdImm::Lane(lane) => writer.write_all(&[*lane]),
+            SimdImm::MemArgLane(memarg, lane) => {
+                memarg.encode(writer)?;
+                writer.write_all(&[*lane])
+            }
+            SimdImm::Bytes(bytes) => writer.write_all(bytes),
+        }
+    }
+}
+
+impl Display for SimdImm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimdImm::None => Ok(()),
+            SimdImm::MemArg(m) => write!(f, " {}", m),
+            SimdImm::Lane(l) => write!(f, " {}", l),
+            SimdImm::MemArgLane(m, l) => write!(f, " {} {}", m, l),
+            SimdImm::Bytes(bytes) => {
+                for b in bytes {
+                    write!(f, " {}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
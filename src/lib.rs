@@ -0,0 +1,13 @@
+pub mod builder;
+pub mod component;
+pub mod encode;
+pub mod features;
+pub mod generate;
+pub mod instr;
+pub mod module;
+pub mod parseable;
+pub mod runtime;
+pub mod section;
+pub mod types;
+pub mod validate;
+pub mod wat;
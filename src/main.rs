@@ -1,30 +1,280 @@
 use std::result::Result;
 
-mod module;
-mod parseable;
-mod section;
-mod types;
+use wasmdbg2::component::Component;
+use wasmdbg2::module::Module;
+use wasmdbg2::parseable::ParseError;
+use wasmdbg2::runtime::debugger::Debugger;
+use wasmdbg2::runtime::exec::Execution;
+use wasmdbg2::runtime::linker::{Linker, StubMode};
+use wasmdbg2::runtime::snapshot::Snapshot;
+use wasmdbg2::runtime::store::Store;
+use wasmdbg2::runtime::trap::{Trap, TrapKind};
+use wasmdbg2::runtime::value::Value;
+use wasmdbg2::runtime::wasi::Wasi;
+use wasmdbg2::types::func_type::FuncType;
+use wasmdbg2::types::val_type::ValType;
+use wasmdbg2::validate::validate;
+use wasmdbg2::wat::parse::parse_module;
 
-use crate::module::Module;
-use crate::parseable::ParseError;
-
-use std::fs::File;
-use std::io::BufReader;
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, Cursor};
+use std::process;
+use std::rc::Rc;
 
 fn main() -> Result<(), ParseError> {
-    let file_path = "funcs.wasm";
-    let file = File::open(file_path)?;
-    let mut reader = BufReader::new(file);
-    let module = match Module::parse(&mut reader) {
-        Ok(module) => module,
-        Err(err) => return Err(err.into()),
-    };
+    let args = env::args().collect::<Vec<_>>();
+    if args.get(1).is_some_and(|arg| arg == "run") {
+        process::exit(run(&args[2..])?);
+    }
+    let file_path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "funcs.wasm".to_string());
+    if file_path.ends_with(".wat") {
+        let text = fs::read_to_string(&file_path)?;
+        let module = parse_module(&text)
+            .map_err(|err| ParseError::Other(format!("{}:{}", file_path, err)))?;
+        return print_module(&module);
+    }
+    let bytes = fs::read(&file_path)?;
+
+    if Component::is_component(&bytes) {
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let component = Component::parse(&mut reader)?;
+
+        println!("Component version: {:#x}", component.version);
+        println!("Sections:");
+        for section in &component.sections {
+            println!("* {}", section.section_type());
+            println!("{}", section);
+        }
+        println!("{}", component.world());
+        return Ok(());
+    }
+
+    let mut reader = BufReader::new(Cursor::new(bytes));
+    let module = Module::parse(&mut reader)?;
+    print_module(&module)
+}
 
+fn print_module(module: &Module) -> Result<(), ParseError> {
     println!("Version: {}", module.version);
     println!("Sections:");
     for section in module.sections() {
         println!("* {}", section.section_type());
         println!("{}", section);
     }
+    match validate(module) {
+        Ok(_) => println!("Module is valid"),
+        Err(err) => println!("Invalid module: {}", err),
+    }
     Ok(())
 }
+
+const USAGE: &str = "usage: run [options] <module> [args] [-- args]";
+
+/// Runs a WASI command module, as in
+/// `run app.wasm [--dir <host>[::<guest>]] [--env <key>=<value>]
+/// [--stub-imports trap|zeros|ask] [--fuel <instructions>]
+/// [--restore <snapshot>] [--preload <name>=<module>] [--debug]
+/// [--break [<module>::]<func>] [args]`, and returns its exit status.
+/// Options may come before or after the module. `--` ends them, so that
+/// the arguments after it go to the program as they are.
+/// Preloaded modules are instantiated first and registered under their
+/// names, for the program to import from. A trap drops into the debugger
+/// prompt, as do a breakpoint and restoring a snapshot taken there, and
+/// `--debug` starts at the prompt before anything has run.
+fn run(args: &[String]) -> Result<i32, ParseError> {
+    let mut wasi = Wasi::new();
+    let mut file_path = None;
+    let mut program_args = Vec::new();
+    let mut stubs = None;
+    let mut fuel = None;
+    let mut restore = None;
+    let mut preloads = Vec::new();
+    let mut debug = false;
+    let mut breakpoints = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| ParseError::Other(format!("{} needs a value", arg)))
+        };
+        match arg.as_str() {
+            "--dir" => {
+                let dir = value()?;
+                let (host, guest) = dir.split_once("::").unwrap_or((dir, dir));
+                wasi.preopen_dir(host, guest)?;
+            }
+            "--env" => {
+                let var = value()?;
+                let (key, value) = var
+                    .split_once('=')
+                    .ok_or_else(|| ParseError::Other(format!("expected key=value, got {}", var)))?;
+                wasi.env(key, value);
+            }
+            "--stub-imports" => {
+                stubs = Some(match value()?.as_str() {
+                    "trap" => StubMode::Trap,
+                    "zeros" => StubMode::Zeros,
+                    "ask" => StubMode::Ask(Rc::new(ask)),
+                    mode => {
+                        return Err(ParseError::Other(format!(
+                            "expected trap, zeros or ask, got {}",
+                            mode
+                        )));
+                    }
+                });
+            }
+            "--fuel" => {
+                let n = value()?;
+                fuel = Some(n.parse().map_err(|_| {
+                    ParseError::Other(format!("expected a number of instructions, got {}", n))
+                })?);
+            }
+            "--restore" => restore = Some(value()?.clone()),
+            "--preload" => {
+                let preload = value()?;
+                let (name, path) = preload.split_once('=').ok_or_else(|| {
+                    ParseError::Other(format!("expected name=module, got {}", preload))
+                })?;
+                preloads.push((name.to_string(), path.to_string()));
+            }
+            "--debug" => debug = true,
+            "--break" => breakpoints.push(value()?.clone()),
+            "--" => break,
+            _ if arg.starts_with("--") => {
+                return Err(ParseError::Other(format!(
+                    "unknown option {}, {}",
+                    arg, USAGE
+                )));
+            }
+            _ if file_path.is_none() => file_path = Some(arg.clone()),
+            _ => program_args.push(arg.clone()),
+        }
+    }
+    for arg in args {
+        match file_path {
+            None => file_path = Some(arg.clone()),
+            Some(_) => program_args.push(arg.clone()),
+        }
+    }
+    let Some(file_path) = file_path else {
+        return Err(ParseError::Other(USAGE.to_string()));
+    };
+
+    let module = load(&file_path)?;
+    wasi.arg(&file_path);
+    for arg in &program_args {
+        wasi.arg(arg);
+    }
+    let mut linker = Linker::new();
+    wasi.link(&mut linker);
+    if let Some(mode) = stubs {
+        linker.stub_missing(mode);
+    }
+    let mut store = Store::new();
+    store.set_fuel(fuel);
+    for (name, path) in &preloads {
+        let instance = linker
+            .instantiate(&mut store, &load(path)?)
+            .map_err(|err| ParseError::Other(format!("{}: {}", path, err)))?;
+        store.register(name, instance);
+    }
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .map_err(|err| ParseError::Other(format!("{}: {}", file_path, err)))?;
+    let start = instance
+        .func(&store, "_start")
+        .ok_or_else(|| ParseError::Other(format!("{}: no _start function", file_path)))?;
+    let execution = Execution::new(&store, start, &[])
+        .map_err(|trap| ParseError::Other(format!("{}: {}", file_path, trap)))?;
+    let mut debugger = Debugger::new(store, instance, execution);
+    if let Some(path) = restore {
+        let snapshot =
+            Snapshot::load(&path).map_err(|err| ParseError::Other(format!("{}: {}", path, err)))?;
+        debugger
+            .restore(&snapshot)
+            .map_err(|err| ParseError::Other(format!("{}: {}", path, err)))?;
+        // Pick up the call where the snapshot left it, at the prompt
+        if snapshot.has_execution() {
+            if let Some(trap) = debugger.trap() {
+                eprintln!("{}: trap: {}", file_path, trap);
+            }
+            debugger.print_exception(&mut io::stderr())?;
+            debugger.print_backtrace(&mut io::stderr())?;
+            debugger.prompt(&mut io::stdin().lock(), &mut io::stderr())?;
+            return Ok(status(&debugger));
+        }
+    }
+    for breakpoint in &breakpoints {
+        let (module, func) = match breakpoint.split_once("::") {
+            Some((module, func)) => (Some(module), func),
+            None => (None, breakpoint.as_str()),
+        };
+        let added = debugger
+            .find_func(module, func)
+            .is_some_and(|func| debugger.add_breakpoint(func));
+        if !added {
+            return Err(ParseError::Other(format!(
+                "{}: no module function {}",
+                file_path, breakpoint
+            )));
+        }
+    }
+    debugger.start(debug, &mut io::stdin().lock(), &mut io::stderr())?;
+    Ok(status(&debugger))
+}
+
+/// Reads a module from a binary or, by its extension, a text file.
+fn load(file_path: &str) -> Result<Module, ParseError> {
+    if file_path.ends_with(".wat") {
+        parse_module(&fs::read_to_string(file_path)?)
+            .map_err(|err| ParseError::Other(format!("{}:{}", file_path, err)))
+    } else {
+        Ok(Module::parse(&mut BufReader::new(Cursor::new(fs::read(
+            file_path,
+        )?)))?)
+    }
+}
+
+/// The exit status of a program that ran under `debugger`.
+fn status(debugger: &Debugger) -> i32 {
+    match debugger.trap().map(Trap::kind) {
+        None if debugger.execution().is_finished() => 0,
+        Some(TrapKind::Exit(status)) => *status,
+        _ => 1,
+    }
+}
+
+/// Asks at the terminal for the results of a call of a stubbed import.
+fn ask(module: &str, name: &str, ty: &FuncType, args: &[Value]) -> Result<Vec<Value>, Trap> {
+    let args = args.iter().map(Value::to_string).collect::<Vec<_>>();
+    let call = format!("{}.{}({})", module, name, args.join(", "));
+    if ty.results().is_empty() {
+        eprintln!("called {}", call);
+        return Ok(Vec::new());
+    }
+    let types = ty.results().iter().map(ValType::name).collect::<Vec<_>>();
+    loop {
+        eprint!("{} returns [{}]: ", call, types.join(" "));
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            return Err(Trap::new(TrapKind::Host(format!(
+                "no results given for {}",
+                call
+            ))));
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.len() == types.len()
+            && let Some(values) = words
+                .iter()
+                .zip(ty.results())
+                .map(|(word, t)| Value::parse(*t, word))
+                .collect::<Option<Vec<_>>>()
+        {
+            return Ok(values);
+        }
+        eprintln!("expected values of types {}", types.join(" "));
+    }
+}
@@ -1,28 +1,72 @@
-use std::io::{BufReader, Read};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
 use std::result::Result;
 
-use crate::parseable::{ParseError, Parseable, Received};
+use crate::encode::Encode;
+
+use crate::features::{Feature, Features};
+use crate::parseable::{ParseError, Parseable, ParserLimits, Received};
 use crate::section::code::CodeSec;
 use crate::section::custom::{CustomSec, CustomSecParseError};
 use crate::section::data::DataSec;
 use crate::section::data_count::DataCountSec;
+use crate::section::dwarf::LineTable;
 use crate::section::element::ElemSec;
 use crate::section::export::ExportSec;
 use crate::section::function::FunctionSec;
 use crate::section::global::GlobalSec;
 use crate::section::import::ImportSec;
 use crate::section::memory::MemSec;
+use crate::section::name::Names;
 use crate::section::start::StartSec;
 use crate::section::table::TableSec;
+use crate::section::tag::TagSec;
 use crate::section::r#type::TypeSec;
 use crate::section::{
     CODE_SECTION_ID, CUSTOM_SECTION_ID, DATA_COUNT_SECTION_ID, DATA_SECTION_ID, ELEMENT_SECTION_ID,
     EXPORT_SECTION_ID, FUNCTION_SECTION_ID, GLOBAL_SECTION_ID, IMPORT_SECTION_ID,
     MEMORY_SECTION_ID, START_SECTION_ID, Section, SectionParseError, TABLE_SECTION_ID,
-    TYPE_SECTION_ID,
+    TAG_SECTION_ID, TYPE_SECTION_ID,
 };
+use crate::types::leb128::{self, Leb128, LebWidths};
 use crate::types::primitives::TypeIdx;
 
+/// `\0asm`, shared by core modules and components.
+pub const MAGIC: [u8; 4] = [0, 97, 115, 109];
+
+/// The order the spec requires for the non-custom sections.
+pub(crate) const SECTION_ORDER: [TypeIdx; 13] = [
+    TYPE_SECTION_ID,
+    IMPORT_SECTION_ID,
+    FUNCTION_SECTION_ID,
+    TABLE_SECTION_ID,
+    MEMORY_SECTION_ID,
+    TAG_SECTION_ID,
+    GLOBAL_SECTION_ID,
+    EXPORT_SECTION_ID,
+    START_SECTION_ID,
+    ELEMENT_SECTION_ID,
+    DATA_COUNT_SECTION_ID,
+    CODE_SECTION_ID,
+    DATA_SECTION_ID,
+];
+
+/// A section as it appeared in the parsed binary.
+struct SectionLayout {
+    id: TypeIdx,
+    widths: LebWidths,
+}
+
+/// Where a custom section sits among the other sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomPlacement {
+    /// Before every other section.
+    First,
+    /// Right after the section with this id.
+    After(TypeIdx),
+    /// After every other section.
+    Last,
+}
+
 #[derive(Default)]
 pub struct Module {
     pub version: u32,
@@ -31,6 +75,7 @@ pub struct Module {
     pub importsec: Option<ImportSec>,
     pub functionsec: Option<FunctionSec>,
     pub tablesec: Option<TableSec>,
+    pub tagsec: Option<TagSec>,
     pub memsec: Option<MemSec>,
     pub globalsec: Option<GlobalSec>,
     pub exportsec: Option<ExportSec>,
@@ -39,13 +84,18 @@ pub struct Module {
     pub codesec: Option<CodeSec>,
     pub datasec: Option<DataSec>,
     pub datacountsec: Option<DataCountSec>,
+    // The sections in the order they were parsed, so that encoding can
+    // reproduce the original binary.
+    layout: Vec<SectionLayout>,
 }
 
 pub enum ModuleParseError {
     Parse(ParseError),
     BadMagic([u8; 4]),
     InvalidVersion(u32),
+    UnknownSection(u8),
     SectionParseError(SectionParseError),
+    FeatureNotEnabled(Feature),
 }
 
 impl From<ParseError> for ModuleParseError {
@@ -66,16 +116,31 @@ impl From<CustomSecParseError> for ModuleParseError {
     }
 }
 
-impl Into<ParseError> for ModuleParseError {
-    fn into(self) -> ParseError {
-        match self {
+impl From<ModuleParseError> for ParseError {
+    fn from(value: ModuleParseError) -> Self {
+        match value {
+            ModuleParseError::Parse(err) => err,
             ModuleParseError::BadMagic(magic) => {
-                ParseError::Other(format!("bad magic: {:#?}", magic))
+                ParseError::Other(format!("magic header not detected: {:02x?}", magic))
+            }
+            // Components share the magic, but put a layer of 1 in the high
+            // half of the version
+            ModuleParseError::InvalidVersion(version) if version >> 16 == 1 => {
+                ParseError::Other(format!(
+                    "unknown binary version {:#x} (this is a component)",
+                    version
+                ))
             }
             ModuleParseError::InvalidVersion(version) => {
-                ParseError::Other(format!("bad version: {}", version))
+                ParseError::Other(format!("unknown binary version {}", version))
+            }
+            ModuleParseError::UnknownSection(id) => {
+                ParseError::Other(format!("malformed section id: {}", id))
+            }
+            ModuleParseError::SectionParseError(err) => ParseError::Other(err.to_string()),
+            ModuleParseError::FeatureNotEnabled(feature) => {
+                ParseError::Other(format!("feature {} not enabled", feature))
             }
-            err => err.into(),
         }
     }
 }
@@ -85,8 +150,7 @@ impl Module {
         let magic = u32::parse(reader)?;
         let bytes = magic.to_le_bytes();
 
-        // `0x00 asm` in ASCII
-        if bytes != [0, 97, 115, 109] {
+        if bytes != MAGIC {
             return Err(ModuleParseError::BadMagic(bytes));
         }
 
@@ -94,10 +158,45 @@ impl Module {
     }
 
     fn parse_version(reader: &mut BufReader<dyn Read>) -> Result<u32, ModuleParseError> {
-        match u32::parse(reader) {
-            Err(e) => Err(ModuleParseError::Parse(e)),
-            Ok(version) => Ok(version),
+        Ok(u32::parse(reader)?)
+    }
+
+    // Reads a section's size and contents, so that the section can be
+    // parsed without reading past its end. The size's LEB128 bytes are
+    // kept in front, as the sections parse their own size. `offset` is
+    // where the size starts in the module, for the module size limit.
+    fn read_section(
+        reader: &mut BufReader<dyn Read>,
+        id: TypeIdx,
+        offset: u64,
+    ) -> Result<Vec<u8>, ModuleParseError> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = u8::parse(reader)?;
+            bytes.push(byte);
+            if byte & 0x80 == 0 || bytes.len() > 5 {
+                break;
+            }
+        }
+        let mut leb = BufReader::new(Cursor::new(bytes.clone()));
+        let size = u32::from(Leb128::<u32>::parse(&mut leb)?);
+
+        let limits = ParserLimits::current();
+        let prefix = bytes.len();
+        limits.check_module_size(offset + (prefix as u64) + u64::from(size))?;
+        if id == CUSTOM_SECTION_ID {
+            limits.check_custom_section_size(size)?;
         }
+        reader
+            .take(u64::from(size))
+            .read_to_end(&mut bytes)
+            .map_err(ParseError::from)?;
+        if bytes.len() - prefix != size as usize {
+            return Err(ModuleParseError::Parse(ParseError::Other(
+                "length out of bounds: section runs past the end of the module".to_string(),
+            )));
+        }
+        Ok(bytes)
     }
 
     pub fn sections(&self) -> Vec<&dyn Section> {
@@ -106,46 +205,295 @@ impl Module {
         for customsec in &self.customsecs {
             vec.push(customsec);
         }
-        if self.codesec.is_some() {
-            vec.push(self.codesec.as_ref().unwrap());
+        if let Some(codesec) = &self.codesec {
+            vec.push(codesec);
+        }
+        if let Some(datacountsec) = &self.datacountsec {
+            vec.push(datacountsec);
         }
-        if self.datacountsec.is_some() {
-            vec.push(self.datacountsec.as_ref().unwrap());
+        if let Some(datasec) = &self.datasec {
+            vec.push(datasec);
         }
-        if self.datasec.is_some() {
-            vec.push(self.datasec.as_ref().unwrap());
+        if let Some(elemsec) = &self.elemsec {
+            vec.push(elemsec);
         }
-        if self.elemsec.is_some() {
-            vec.push(self.elemsec.as_ref().unwrap());
+        if let Some(exportsec) = &self.exportsec {
+            vec.push(exportsec);
         }
-        if self.exportsec.is_some() {
-            vec.push(self.exportsec.as_ref().unwrap());
+        if let Some(functionsec) = &self.functionsec {
+            vec.push(functionsec);
         }
-        if self.functionsec.is_some() {
-            vec.push(self.functionsec.as_ref().unwrap());
+        if let Some(globalsec) = &self.globalsec {
+            vec.push(globalsec);
         }
-        if self.globalsec.is_some() {
-            vec.push(self.globalsec.as_ref().unwrap());
+        if let Some(importsec) = &self.importsec {
+            vec.push(importsec);
         }
-        if self.importsec.is_some() {
-            vec.push(self.importsec.as_ref().unwrap());
+        if let Some(memsec) = &self.memsec {
+            vec.push(memsec);
         }
-        if self.memsec.is_some() {
-            vec.push(self.memsec.as_ref().unwrap());
+        if let Some(startsec) = &self.startsec {
+            vec.push(startsec);
         }
-        if self.startsec.is_some() {
-            vec.push(self.startsec.as_ref().unwrap());
+        if let Some(tablesec) = &self.tablesec {
+            vec.push(tablesec);
         }
-        if self.tablesec.is_some() {
-            vec.push(self.tablesec.as_ref().unwrap());
+        if let Some(tagsec) = &self.tagsec {
+            vec.push(tagsec);
         }
-        if self.typesec.is_some() {
-            vec.push(self.typesec.as_ref().unwrap());
+        if let Some(typesec) = &self.typesec {
+            vec.push(typesec);
         }
 
         vec
     }
 
+    /// The debug names from the custom section called "name". A missing or
+    /// malformed name section gives no names at all.
+    pub fn names(&self) -> Names {
+        self.customsecs
+            .iter()
+            .find(|sec| sec.name() == "name")
+            .and_then(|sec| Names::parse(sec.data()).ok())
+            .unwrap_or_default()
+    }
+
+    /// The line tables from the DWARF debug info in the custom section
+    /// called ".debug_line", if there is one and it parses.
+    pub fn line_table(&self) -> Option<LineTable> {
+        let section = |name: &str| {
+            self.customsecs
+                .iter()
+                .find(|sec| sec.name() == name)
+                .map(|sec| sec.data())
+        };
+        LineTable::parse(
+            section(".debug_line")?,
+            section(".debug_line_str").unwrap_or_default(),
+            section(".debug_str").unwrap_or_default(),
+        )
+        .ok()
+    }
+
+    /// Where each custom section is encoded, in the order of `customsecs`.
+    pub fn custom_placements(&self) -> Vec<CustomPlacement> {
+        let order = self.section_order();
+        let mut placements = Vec::new();
+        let mut previous = None;
+        for (i, (id, _, _)) in order.iter().enumerate() {
+            if *id != CUSTOM_SECTION_ID {
+                previous = Some(*id);
+                continue;
+            }
+            let trailing = order[i..].iter().all(|(id, _, _)| *id == CUSTOM_SECTION_ID);
+            placements.push(match previous {
+                _ if trailing => CustomPlacement::Last,
+                None => CustomPlacement::First,
+                Some(id) => CustomPlacement::After(id),
+            });
+        }
+        placements
+    }
+
+    /// Moves the custom sections to where `placements` says, one placement
+    /// for each of `customsecs`, as the text format does with `@custom`.
+    /// Custom sections with the same placement keep their order.
+    pub(crate) fn place_customs(&mut self, placements: &[CustomPlacement]) {
+        let rank = |id: TypeIdx| SECTION_ORDER.iter().position(|other| *other == id);
+        let present: Vec<TypeIdx> = SECTION_ORDER
+            .into_iter()
+            .filter(|id| self.section(*id, 0).is_some())
+            .collect();
+        // The number of other sections before each custom section
+        let slot = |placement: &CustomPlacement| match placement {
+            CustomPlacement::First => 0,
+            CustomPlacement::After(id) => present
+                .iter()
+                .filter(|other| rank(**other) <= rank(*id))
+                .count(),
+            CustomPlacement::Last => present.len(),
+        };
+        let mut customs: Vec<(usize, CustomSec)> = std::mem::take(&mut self.customsecs)
+            .into_iter()
+            .zip(placements)
+            .map(|(section, placement)| (slot(placement), section))
+            .collect();
+        customs.sort_by_key(|(slot, _)| *slot);
+
+        self.layout.clear();
+        let mut slots = customs.iter().map(|(slot, _)| *slot).peekable();
+        for i in 0..=present.len() {
+            while slots.next_if_eq(&i).is_some() {
+                self.layout.push(SectionLayout {
+                    id: CUSTOM_SECTION_ID,
+                    widths: LebWidths::default(),
+                });
+            }
+            if let Some(id) = present.get(i) {
+                self.layout.push(SectionLayout {
+                    id: *id,
+                    widths: LebWidths::default(),
+                });
+            }
+        }
+        self.customsecs = customs.into_iter().map(|(_, section)| section).collect();
+    }
+
+    /// Like `parse`, but fails if the module uses a proposal that is not
+    /// enabled in `features`.
+    pub fn parse_with_features(
+        reader: &mut BufReader<dyn Read>,
+        features: &Features,
+    ) -> Result<Module, ModuleParseError> {
+        let module = Self::parse(reader)?;
+        features
+            .check(&module)
+            .map_err(ModuleParseError::FeatureNotEnabled)?;
+        Ok(module)
+    }
+
+    /// Like `parse`, but holds the module to `limits` rather than the
+    /// default ones.
+    pub fn parse_with_limits(
+        reader: &mut BufReader<dyn Read>,
+        limits: &ParserLimits,
+    ) -> Result<Module, ModuleParseError> {
+        limits.apply(|| Self::parse(reader))
+    }
+
+    fn parse_section(
+        &mut self,
+        section_type: TypeIdx,
+        reader: &mut BufReader<dyn Read>,
+    ) -> Result<(), ModuleParseError> {
+        if section_type == CODE_SECTION_ID {
+            self.codesec = Some(CodeSec::parse(reader)?);
+        } else if section_type == CUSTOM_SECTION_ID {
+            self.customsecs.push(CustomSec::parse(reader)?);
+        } else if section_type == DATA_COUNT_SECTION_ID {
+            self.datacountsec = Some(DataCountSec::parse(reader)?);
+        } else if section_type == DATA_SECTION_ID {
+            self.datasec = Some(DataSec::parse(reader)?);
+        } else if section_type == ELEMENT_SECTION_ID {
+            self.elemsec = Some(ElemSec::parse(reader)?);
+        } else if section_type == EXPORT_SECTION_ID {
+            self.exportsec = Some(ExportSec::parse(reader)?);
+        } else if section_type == FUNCTION_SECTION_ID {
+            self.functionsec = Some(FunctionSec::parse(reader)?);
+        } else if section_type == GLOBAL_SECTION_ID {
+            self.globalsec = Some(GlobalSec::parse(reader)?);
+        } else if section_type == IMPORT_SECTION_ID {
+            self.importsec = Some(ImportSec::parse(reader)?);
+        } else if section_type == MEMORY_SECTION_ID {
+            self.memsec = Some(MemSec::parse(reader)?);
+        } else if section_type == START_SECTION_ID {
+            self.startsec = Some(StartSec::parse(reader)?);
+        } else if section_type == TABLE_SECTION_ID {
+            self.tablesec = Some(TableSec::parse(reader)?);
+        } else if section_type == TAG_SECTION_ID {
+            self.tagsec = Some(TagSec::parse(reader)?);
+        } else if section_type == TYPE_SECTION_ID {
+            self.typesec = Some(TypeSec::parse(reader)?);
+        } else {
+            return Err(ModuleParseError::UnknownSection(section_type.0 as u8));
+        }
+        Ok(())
+    }
+
+    /// Writes the module in the binary format. Sections that came from
+    /// parsing keep their original order; any others go where the spec
+    /// requires them, with custom sections last. Numbers are written in
+    /// their shortest form.
+    pub fn encode(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        self.encode_sections(writer, false)
+    }
+
+    /// Like `encode`, but writes LEB128 numbers with the same number of
+    /// bytes they had in the parsed binary, so that an unmodified module
+    /// round-trips byte for byte.
+    pub fn encode_preserving_lebs(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        self.encode_sections(writer, true)
+    }
+
+    fn encode_sections(&self, writer: &mut dyn Write, preserve: bool) -> std::io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        self.version.encode(writer)?;
+        for (id, custom, widths) in self.section_order() {
+            let Some(section) = self.section(id, custom) else {
+                continue;
+            };
+            writer.write_all(&[id.0 as u8])?;
+            match widths {
+                Some(widths) if preserve => {
+                    leb128::replay_widths(widths, || section.encode(writer))?
+                }
+                _ => section.encode(writer)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn section(&self, id: TypeIdx, custom: usize) -> Option<&dyn Encode> {
+        fn encodable<T: Encode>(section: &Option<T>) -> Option<&dyn Encode> {
+            section.as_ref().map(|section| section as &dyn Encode)
+        }
+
+        match id {
+            CUSTOM_SECTION_ID => self
+                .customsecs
+                .get(custom)
+                .map(|section| section as &dyn Encode),
+            TYPE_SECTION_ID => encodable(&self.typesec),
+            IMPORT_SECTION_ID => encodable(&self.importsec),
+            FUNCTION_SECTION_ID => encodable(&self.functionsec),
+            TABLE_SECTION_ID => encodable(&self.tablesec),
+            MEMORY_SECTION_ID => encodable(&self.memsec),
+            TAG_SECTION_ID => encodable(&self.tagsec),
+            GLOBAL_SECTION_ID => encodable(&self.globalsec),
+            EXPORT_SECTION_ID => encodable(&self.exportsec),
+            START_SECTION_ID => encodable(&self.startsec),
+            ELEMENT_SECTION_ID => encodable(&self.elemsec),
+            DATA_COUNT_SECTION_ID => encodable(&self.datacountsec),
+            CODE_SECTION_ID => encodable(&self.codesec),
+            DATA_SECTION_ID => encodable(&self.datasec),
+            _ => None,
+        }
+    }
+
+    // The sections to encode, as (id, index among the custom sections,
+    // widths recorded when parsing).
+    fn section_order(&self) -> Vec<(TypeIdx, usize, Option<&LebWidths>)> {
+        let rank = |id: TypeIdx| SECTION_ORDER.iter().position(|other| *other == id);
+
+        let mut order = Vec::new();
+        let mut customs = 0;
+        for entry in &self.layout {
+            if entry.id == CUSTOM_SECTION_ID {
+                order.push((entry.id, customs, Some(&entry.widths)));
+                customs += 1;
+            } else if !order.iter().any(|(id, _, _)| *id == entry.id) {
+                order.push((entry.id, 0, Some(&entry.widths)));
+            }
+        }
+
+        // Sections added after parsing go before the first section that
+        // must come after them.
+        for (i, id) in SECTION_ORDER.into_iter().enumerate() {
+            if self.section(id, 0).is_none() || order.iter().any(|(other, _, _)| *other == id) {
+                continue;
+            }
+            let at = order
+                .iter()
+                .position(|(other, _, _)| rank(*other).is_some_and(|rank| rank > i))
+                .unwrap_or(order.len());
+            order.insert(at, (id, 0, None));
+        }
+        for custom in customs..self.customsecs.len() {
+            order.push((CUSTOM_SECTION_ID, custom, None));
+        }
+        order
+    }
+
     pub fn parse(
         reader: &mut std::io::BufReader<dyn std::io::Read>,
     ) -> Result<Module, ModuleParseError> {
@@ -157,10 +505,13 @@ impl Module {
         }
 
         let mut module = Module {
-            version: version,
+            version,
             ..Default::default()
         };
 
+        // The bytes read so far, starting with the magic and version
+        let mut offset = 8;
+        let mut next_rank = 0;
         loop {
             let res = match u8::parse(reader) {
                 Ok(n) => n,
@@ -171,37 +522,241 @@ impl Module {
                 Err(e) => return Err(ModuleParseError::Parse(e)),
             };
             let section_type = TypeIdx(u32::from(res));
-            if section_type == CODE_SECTION_ID {
-                module.codesec = Some(CodeSec::parse(reader)?);
-            } else if section_type == CUSTOM_SECTION_ID {
-                module.customsecs.push(CustomSec::parse(reader)?);
-            } else if section_type == DATA_COUNT_SECTION_ID {
-                module.datacountsec = Some(DataCountSec::parse(reader)?);
-            } else if section_type == DATA_SECTION_ID {
-                module.datasec = Some(DataSec::parse(reader)?);
-            } else if section_type == ELEMENT_SECTION_ID {
-                module.elemsec = Some(ElemSec::parse(reader)?);
-            } else if section_type == EXPORT_SECTION_ID {
-                module.exportsec = Some(ExportSec::parse(reader)?);
-            } else if section_type == FUNCTION_SECTION_ID {
-                module.functionsec = Some(FunctionSec::parse(reader)?);
-            } else if section_type == GLOBAL_SECTION_ID {
-                module.globalsec = Some(GlobalSec::parse(reader)?);
-            } else if section_type == IMPORT_SECTION_ID {
-                module.importsec = Some(ImportSec::parse(reader)?);
-            } else if section_type == MEMORY_SECTION_ID {
-                module.memsec = Some(MemSec::parse(reader)?);
-            } else if section_type == START_SECTION_ID {
-                module.startsec = Some(StartSec::parse(reader)?);
-            } else if section_type == TABLE_SECTION_ID {
-                module.tablesec = Some(TableSec::parse(reader)?);
-            } else if section_type == TYPE_SECTION_ID {
-                module.typesec = Some(TypeSec::parse(reader)?);
-            } else {
-                break;
+            // Each kind of section may appear once, in the spec's order
+            if let Some(rank) = SECTION_ORDER.iter().position(|id| *id == section_type) {
+                if rank < next_rank {
+                    return Err(ModuleParseError::Parse(ParseError::Other(
+                        "unexpected content after last section".to_string(),
+                    )));
+                }
+                next_rank = rank + 1;
             }
+            offset += 1;
+            let bytes = Self::read_section(reader, section_type, offset)?;
+            offset += bytes.len() as u64;
+            let mut section = BufReader::new(Cursor::new(bytes));
+            let (parsed, widths) =
+                leb128::record_widths(|| module.parse_section(section_type, &mut section));
+            parsed?;
+            if !section.fill_buf().map_err(ParseError::from)?.is_empty() {
+                return Err(ModuleParseError::Parse(ParseError::Other(
+                    "section size mismatch".to_string(),
+                )));
+            }
+            module.layout.push(SectionLayout {
+                id: section_type,
+                widths,
+            });
         }
 
         Ok(module)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    fn parse(sections: &[u8]) -> Result<Module, ModuleParseError> {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(sections);
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        Module::parse(&mut reader)
+    }
+
+    #[test]
+    fn test_tag_section() {
+        let module = match parse(&[0x0d, 0x03, 0x01, 0x00, 0x00]) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+        let tagsec = module.tagsec.expect("A tag section");
+        assert_eq!(tagsec.tags().len(), 1);
+    }
+
+    #[test]
+    fn test_unknown_section() {
+        match parse(&[0x0e, 0x00]) {
+            Ok(_) => panic!("Expected an error"),
+            Err(err) => assert_eq!(
+                ParseError::from(err),
+                ParseError::Other("malformed section id: 14".to_string())
+            ),
+        }
+    }
+
+    #[test]
+    fn test_malformed_sections() {
+        let cases: [(&[u8], &str); 4] = [
+            // A type section claiming more bytes than there are
+            (
+                &[0x01, 0x10, 0x00],
+                "length out of bounds: section runs past the end of the module",
+            ),
+            // A type section with a byte after its last type
+            (&[0x01, 0x02, 0x00, 0x00], "section size mismatch"),
+            // Two type sections
+            (
+                &[0x01, 0x01, 0x00, 0x01, 0x01, 0x00],
+                "unexpected content after last section",
+            ),
+            // A function section before the type section
+            (
+                &[0x03, 0x01, 0x00, 0x01, 0x01, 0x00],
+                "unexpected content after last section",
+            ),
+        ];
+        for (sections, message) in cases {
+            match parse(sections) {
+                Ok(_) => panic!("Expected an error for {:02x?}", sections),
+                Err(err) => assert_eq!(
+                    ParseError::from(err),
+                    ParseError::Other(message.to_string())
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parser_limits() {
+        let limits = ParserLimits {
+            max_module_size: 24,
+            max_vec_len: 2,
+            max_locals: 4,
+            max_nesting: 1,
+            max_custom_section_size: 4,
+        };
+        let cases: [(&[u8], &str); 5] = [
+            // A type section claiming 5 types
+            (
+                &[0x01, 0x01, 0x05],
+                "vector of 5 elements exceeds the limit of 2",
+            ),
+            (
+                &[0x00, 0x06, 0x01, b'a', 1, 2, 3, 4],
+                "custom section of 6 bytes exceeds the limit of 4",
+            ),
+            // A data section with 17 bytes of contents
+            (&[0x0b, 0x11], "module exceeds the size limit of 24 bytes"),
+            // A body declaring 5 i32 locals
+            (
+                &[0x0a, 0x06, 0x01, 0x04, 0x01, 0x05, 0x7f, 0x0b],
+                "5 locals exceed the limit of 4",
+            ),
+            // A body with a block in a block
+            (
+                &[
+                    0x0a, 0x0a, 0x01, 0x08, 0x00, 0x02, 0x40, 0x02, 0x40, 0x0b, 0x0b, 0x0b,
+                ],
+                "nesting exceeds the limit of 1",
+            ),
+        ];
+        for (sections, message) in cases {
+            let mut bytes = HEADER.to_vec();
+            bytes.extend_from_slice(sections);
+            let mut reader = BufReader::new(Cursor::new(bytes));
+            match Module::parse_with_limits(&mut reader, &limits) {
+                Ok(_) => panic!("Expected an error for {:02x?}", sections),
+                Err(err) => assert_eq!(
+                    ParseError::from(err),
+                    ParseError::Other(message.to_string())
+                ),
+            }
+        }
+
+        // Bytes aren't held to the vector limit, and the default limits are
+        // back in place afterwards
+        let data = [0x0b, 0x08, 0x01, 0x01, 0x05, 1, 2, 3, 4, 5];
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(&data);
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Module::parse_with_limits(&mut reader, &limits).is_ok());
+        assert_eq!(ParserLimits::current(), ParserLimits::default());
+
+        // Even when parsing panics
+        let panicked = std::panic::catch_unwind(|| limits.apply(|| panic!("In parsing")));
+        assert!(panicked.is_err());
+        assert_eq!(ParserLimits::current(), ParserLimits::default());
+    }
+
+    fn encode(module: &Module, preserve: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let result = if preserve {
+            module.encode_preserving_lebs(&mut bytes)
+        } else {
+            module.encode(&mut bytes)
+        };
+        result.expect("The encoded module");
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = include_bytes!("../funcs.wasm");
+        let mut reader = BufReader::new(Cursor::new(bytes.to_vec()));
+        let module = match Module::parse(&mut reader) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+        assert_eq!(encode(&module, false), bytes);
+        assert_eq!(encode(&module, true), bytes);
+    }
+
+    #[test]
+    fn test_preserve_lebs() {
+        // A type section whose size and count are padded, a custom section,
+        // and a body with `i32.const -1` in two bytes
+        let sections = [
+            0x01, 0x85, 0x80, 0x80, 0x80, 0x00, 0x81, 0x00, 0x60, 0x00, 0x00, 0x00, 0x04, 0x01,
+            0x78, 0xaa, 0xbb, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x08, 0x01, 0x06, 0x00, 0x41, 0xff,
+            0x7f, 0x1a, 0x0b,
+        ];
+        let module = match parse(&sections) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(&sections);
+        assert_eq!(encode(&module, true), bytes);
+
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(&[
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x00, 0x04, 0x01, 0x78, 0xaa, 0xbb, 0x03, 0x02,
+            0x01, 0x00, 0x0a, 0x07, 0x01, 0x05, 0x00, 0x41, 0x7f, 0x1a, 0x0b,
+        ]);
+        assert_eq!(encode(&module, false), bytes);
+
+        // `(ref null func)` written long-hand stays that way
+        let types = [0x01, 0x06, 0x01, 0x60, 0x01, 0x63, 0x70, 0x00];
+        let Ok(module) = parse(&types) else {
+            panic!("Expected the module to parse");
+        };
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(&types);
+        assert_eq!(encode(&module, true), bytes);
+    }
+
+    #[test]
+    fn test_added_section_order() {
+        let types = [0x01, 0x04, 0x01, 0x60, 0x00, 0x00];
+        let rest = [
+            0x00, 0x02, 0x01, 0x78, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let (Ok(mut with_types), Ok(mut module)) = (parse(&types), parse(&rest)) else {
+            panic!("Expected both modules to parse");
+        };
+
+        // The type section belongs before the function section, even though
+        // the custom section came first in the original binary.
+        module.typesec = with_types.typesec.take();
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(&rest[..4]);
+        bytes.extend_from_slice(&types);
+        bytes.extend_from_slice(&rest[4..]);
+        assert_eq!(encode(&module, false), bytes);
+    }
+}
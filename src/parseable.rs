@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::fmt::{Debug, Display};
 use std::io::{BufReader, Read};
 use std::string::FromUtf8Error;
@@ -45,6 +46,114 @@ impl From<FromUtf8Error> for ParseError {
 
 pub type Result<T> = std::result::Result<T, ParseError>;
 
+/// Bounds on the input that parsing accepts, for reading modules from
+/// untrusted sources. Each bound is checked against the sizes and counts
+/// the input claims, before anything is allocated for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserLimits {
+    /// The size of a binary module in bytes.
+    pub max_module_size: u64,
+    /// The number of elements in a vector, other than a vector of bytes.
+    pub max_vec_len: u32,
+    /// The number of locals a function body declares, not counting its
+    /// parameters.
+    pub max_locals: u64,
+    /// How deeply blocks may nest in a function body, and lists in the
+    /// text format.
+    pub max_nesting: usize,
+    /// The size of a custom section in bytes.
+    pub max_custom_section_size: u32,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        ParserLimits {
+            max_module_size: 1 << 30,
+            max_vec_len: 1_000_000,
+            max_locals: 50_000,
+            max_nesting: 1_000,
+            max_custom_section_size: 1 << 28,
+        }
+    }
+}
+
+thread_local! {
+    static LIMITS: Cell<ParserLimits> = Cell::new(ParserLimits::default());
+}
+
+impl ParserLimits {
+    /// The limits parsing on this thread is held to; the default unless
+    /// inside `apply`.
+    pub fn current() -> ParserLimits {
+        LIMITS.get()
+    }
+
+    /// Runs `f` with these limits in place of the current ones, e.g. to
+    /// parse a module in the text format.
+    pub fn apply<T>(&self, f: impl FnOnce() -> T) -> T {
+        // Puts the outer limits back even if `f` panics
+        struct Restore(ParserLimits);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                LIMITS.set(self.0);
+            }
+        }
+
+        let _restore = Restore(LIMITS.replace(*self));
+        f()
+    }
+
+    pub(crate) fn check_module_size(&self, size: u64) -> Result<()> {
+        if size > self.max_module_size {
+            return Err(ParseError::Other(format!(
+                "module exceeds the size limit of {} bytes",
+                self.max_module_size
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_vec_len(&self, len: u32) -> Result<()> {
+        if len > self.max_vec_len {
+            return Err(ParseError::Other(format!(
+                "vector of {} elements exceeds the limit of {}",
+                len, self.max_vec_len
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_locals(&self, count: u64) -> Result<()> {
+        if count > self.max_locals {
+            return Err(ParseError::Other(format!(
+                "{} locals exceed the limit of {}",
+                count, self.max_locals
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_nesting(&self, depth: usize) -> Result<()> {
+        if depth > self.max_nesting {
+            return Err(ParseError::Other(format!(
+                "nesting exceeds the limit of {}",
+                self.max_nesting
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_custom_section_size(&self, size: u32) -> Result<()> {
+        if size > self.max_custom_section_size {
+            return Err(ParseError::Other(format!(
+                "custom section of {} bytes exceeds the limit of {}",
+                size, self.max_custom_section_size
+            )));
+        }
+        Ok(())
+    }
+}
+
 pub trait Parseable {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
@@ -55,9 +164,10 @@ impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParseError::WrongNumBytesRead(asked, received) => {
+                // In the reference interpreter's words, for spec tests
                 write!(
                     f,
-                    "Requested {} bytes; received {} bytes",
+                    "unexpected end: requested {} bytes, received {}",
                     asked.0, received.0
                 )
             }
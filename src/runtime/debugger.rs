@@ -0,0 +1,797 @@
+use std::cell::Cell;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::runtime::exec::{Execution, Frame};
+use crate::runtime::instance::Instance;
+use crate::runtime::memory::MemoryGrowth;
+use crate::runtime::snapshot::{Snapshot, SnapshotError};
+use crate::runtime::store::{FuncAddr, FuncKind, Store, TagAddr};
+use crate::runtime::trap::{Trap, TrapFrame, TrapKind};
+use crate::runtime::value::Value;
+use crate::section::name::NameSpace;
+use crate::types::primitives::FuncIdx;
+
+const HELP: &str = "\
+bt                    print the call stack
+locals                print the locals of the innermost frame
+stack                 print the operands of the innermost frame
+memory <addr> [len]   dump memory 0 of the innermost frame's instance
+step [n]              execute n instructions, 1 by default
+next                  step over calls
+finish                run until the innermost frame returns
+continue              run until the call returns or traps
+break [module] <func> stop when a function is called, by export name,
+                      name or index, in a registered instance if given
+breakpoints           list the breakpoints
+delete <n>            remove breakpoint n
+fuel <n>              set the fuel left, for going on after running out
+catch grow            stop after memory.grow
+catch throw           stop before an exception is thrown
+save <file>           save a snapshot of the instance and the call
+load <file>           go back to a saved snapshot
+quit                  leave the debugger
+";
+
+/// An interactive debugger for a call in progress. It reads commands a line
+/// at a time and prints what they show, stopping the call where it traps so
+/// that its state can still be looked at.
+pub struct Debugger {
+    store: Store,
+    /// The instance whose state snapshots hold.
+    instance: Instance,
+    execution: Execution,
+    trap: Option<Trap>,
+    /// The `memory.grow` that stopped the call, once `catch grow` set the
+    /// store's grow hook.
+    growth: Rc<Cell<Option<MemoryGrowth>>>,
+    /// The functions to stop on entry to, in any instance.
+    breakpoints: Vec<FuncAddr>,
+    /// Whether to stop before exceptions are thrown.
+    catch_throws: bool,
+}
+
+impl Debugger {
+    /// Debugs `execution`, a call into `instance`. If the instance has no
+    /// name, it is registered as `main`, so that its frames show which
+    /// module they belong to as those of other instances do.
+    pub fn new(mut store: Store, instance: Instance, execution: Execution) -> Debugger {
+        if instance.name(&store).is_none() {
+            store.register("main", instance);
+        }
+        Debugger {
+            store,
+            instance,
+            execution,
+            trap: None,
+            growth: Rc::default(),
+            breakpoints: Vec::new(),
+            catch_throws: false,
+        }
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn execution(&self) -> &Execution {
+        &self.execution
+    }
+
+    /// The trap that stopped the call, if it trapped.
+    pub fn trap(&self) -> Option<&Trap> {
+        self.trap.as_ref()
+    }
+
+    /// A snapshot of the instance and the call, with the trap that stopped
+    /// it.
+    pub fn snapshot(&self) -> Result<Snapshot, SnapshotError> {
+        Snapshot::capture(
+            &self.store,
+            self.instance,
+            Some(&self.execution),
+            self.trap.as_ref(),
+        )
+    }
+
+    /// Puts the instance back into the state of `snapshot`, along with the
+    /// call if it has one, stopped by the trap it was stopped by.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if let Some(execution) = snapshot.restore(&mut self.store, self.instance)? {
+            self.execution = execution;
+            self.trap = snapshot.trap().cloned();
+        }
+        Ok(())
+    }
+
+    /// Stops execution after every `memory.grow`, through the store's grow
+    /// hook.
+    pub fn catch_grow(&mut self) {
+        let growth = self.growth.clone();
+        self.store
+            .set_grow_hook(Some(Rc::new(move |g: &MemoryGrowth| growth.set(Some(*g)))));
+    }
+
+    /// The `memory.grow` that execution last stopped after, which only
+    /// happens once [`Debugger::catch_grow`] asked for it.
+    pub fn take_growth(&mut self) -> Option<MemoryGrowth> {
+        self.growth.take()
+    }
+
+    /// Stops execution before every `throw`, `throw_ref` and `rethrow`
+    /// that throws an exception.
+    pub fn catch_throw(&mut self) {
+        self.catch_throws = true;
+    }
+
+    /// The tag and fields of the exception execution stopped before
+    /// throwing, which only happens once [`Debugger::catch_throw`] asked
+    /// for it.
+    pub fn throwing(&self) -> Option<(TagAddr, Vec<Value>)> {
+        if !self.catch_throws {
+            return None;
+        }
+        self.execution.next_throw(&self.store)
+    }
+
+    /// Executes up to `steps` instructions, or until the call returns if
+    /// `None`. A trapped call can't go on, except after running out of
+    /// fuel. Execution also stops on entry to a function with a breakpoint,
+    /// after a caught `memory.grow` and before a caught throw.
+    pub fn resume(&mut self, steps: Option<u64>) -> Result<(), Trap> {
+        self.resume_to(steps, None)
+    }
+
+    /// Executes until the innermost frame gets to its next instruction,
+    /// running any call it makes to completion.
+    pub fn step_over(&mut self) -> Result<(), Trap> {
+        let depth = self.execution.frames().len();
+        self.resume_to(None, Some(depth))
+    }
+
+    /// Executes until the innermost frame returns.
+    pub fn step_out(&mut self) -> Result<(), Trap> {
+        let depth = self.execution.frames().len();
+        self.resume_to(None, Some(depth.saturating_sub(1)))
+    }
+
+    /// Resumes as [`Debugger::resume`] does, also stopping once no more than
+    /// `depth` frames are left, if given.
+    fn resume_to(&mut self, steps: Option<u64>, depth: Option<usize>) -> Result<(), Trap> {
+        match &self.trap {
+            Some(trap) if trap.kind() != &TrapKind::OutOfFuel => return Err(trap.clone()),
+            _ => self.trap = None,
+        }
+        let mut n = 0;
+        while !self.execution.is_finished() && steps.is_none_or(|steps| n < steps) {
+            if let Err(trap) = self.execution.step(&mut self.store) {
+                self.trap = Some(trap.clone());
+                return Err(trap);
+            }
+            n += 1;
+            if self.growth.get().is_some()
+                || self.breakpoint().is_some()
+                || self.throwing().is_some()
+                || depth.is_some_and(|depth| self.execution.frames().len() <= depth)
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The breakpoint the call is stopped at, which is when the innermost
+    /// frame has just been entered.
+    fn breakpoint(&self) -> Option<usize> {
+        let frame = self.execution.frames().last()?;
+        if frame.pc() != 0 {
+            return None;
+        }
+        self.breakpoints
+            .iter()
+            .position(|func| *func == frame.func())
+    }
+
+    /// Stops the call whenever `func` is called. Host functions have no
+    /// frames to stop in, so they can't have breakpoints.
+    pub fn add_breakpoint(&mut self, func: FuncAddr) -> bool {
+        if self.store.func(func).is_host() {
+            return false;
+        }
+        if !self.breakpoints.contains(&func) {
+            self.breakpoints.push(func);
+        }
+        true
+    }
+
+    pub fn breakpoints(&self) -> &[FuncAddr] {
+        &self.breakpoints
+    }
+
+    pub fn remove_breakpoint(&mut self, i: usize) -> Option<FuncAddr> {
+        (i < self.breakpoints.len()).then(|| self.breakpoints.remove(i))
+    }
+
+    /// Finds the function `func` of the instance registered as `module`, or
+    /// of the debugged instance: by export name, by its name from the name
+    /// section, with or without the `$` frames show it with, or by index.
+    pub fn find_func(&self, module: Option<&str>, func: &str) -> Option<FuncAddr> {
+        let instance = match module {
+            Some(module) => self.store.instance(module)?,
+            None => self.instance,
+        };
+        if let Some(addr) = instance.func(&self.store, func) {
+            return Some(addr);
+        }
+        let data = instance.data(&self.store);
+        let name = func.strip_prefix('$').unwrap_or(func);
+        let idx = data
+            .names
+            .all()
+            .find(|((space, _), n)| *space == NameSpace::Func && *n == name)
+            .map(|((_, idx), _)| idx)
+            .or_else(|| parse_number(func).and_then(|idx| u32::try_from(idx).ok()))?;
+        data.funcs.get(idx as usize).copied()
+    }
+
+    /// The module function `func` as a frame would show it.
+    fn describe(&self, func: FuncAddr) -> String {
+        let FuncKind::Wasm { instance, idx, .. } = &self.store.func(func).kind else {
+            return "host function".to_string();
+        };
+        let instance = Instance(*instance);
+        let name = instance.data(&self.store).names.func(*idx);
+        TrapFrame::new(
+            instance,
+            instance.name(&self.store).map(str::to_string),
+            FuncIdx(idx.0),
+            None,
+            name.map(str::to_string),
+            None,
+        )
+        .to_string()
+    }
+
+    /// The frames of the call, innermost first. Once it traps, these are the
+    /// frames of the trap, which unwinding may have removed from the stack.
+    pub fn backtrace(&self) -> Vec<TrapFrame> {
+        match &self.trap {
+            Some(trap) => trap.backtrace().to_vec(),
+            None => self.execution.backtrace(&self.store),
+        }
+    }
+
+    /// Runs the call until it traps or stops, e.g. at a breakpoint, says
+    /// why, and then reads commands as [`Debugger::prompt`] does. A call
+    /// that returns or exits doesn't get to the prompt. If `paused`, the
+    /// prompt comes first, before the call has run at all.
+    pub fn start(
+        &mut self,
+        paused: bool,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        if !paused {
+            let result = self.resume(None);
+            let done = match &result {
+                Ok(()) => self.execution.is_finished(),
+                Err(trap) => matches!(trap.kind(), TrapKind::Exit(_)),
+            };
+            if done {
+                return Ok(());
+            }
+            self.report(output, result)?;
+        }
+        self.prompt(input, output)
+    }
+
+    /// Reads and runs commands from `input` until it ends or says to quit.
+    pub fn prompt(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        loop {
+            write!(output, "(wasmdbg) ")?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                [] => {}
+                ["quit" | "q"] => return Ok(()),
+                words => self.command(words, output)?,
+            }
+        }
+    }
+
+    /// Runs the command made of `words`, other than quitting.
+    fn command(&mut self, words: &[&str], output: &mut impl Write) -> io::Result<()> {
+        match words {
+            ["help" | "h"] => write!(output, "{}", HELP),
+            ["bt" | "backtrace"] => self.print_backtrace(output),
+            ["locals"] => print_values(output, self.execution.locals()),
+            ["stack"] => print_values(output, self.execution.operands()),
+            ["memory" | "x", addr] => self.dump(output, addr, "64"),
+            ["memory" | "x", addr, len] => self.dump(output, addr, len),
+            ["step" | "s"] => self.run(output, Some(1)),
+            ["step" | "s", n] => match parse_number(n) {
+                Some(n) => self.run(output, Some(n)),
+                None => writeln!(output, "expected a number of steps, got {}", n),
+            },
+            ["continue" | "c"] => self.run(output, None),
+            ["next" | "n"] => {
+                let result = self.step_over();
+                self.report(output, result)
+            }
+            ["finish"] => {
+                let result = self.step_out();
+                self.report(output, result)
+            }
+            ["break" | "b", func] => self.add_breakpoint_at(output, None, func),
+            ["break" | "b", module, func] => self.add_breakpoint_at(output, Some(module), func),
+            ["breakpoints"] => {
+                for (i, func) in self.breakpoints.iter().enumerate() {
+                    writeln!(output, "{}: {}", i + 1, self.describe(*func))?;
+                }
+                Ok(())
+            }
+            ["delete" | "d", n] => match parse_number(n)
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| self.remove_breakpoint(i as usize))
+            {
+                Some(_) => Ok(()),
+                None => writeln!(output, "no breakpoint {}", n),
+            },
+            ["fuel", n] => match parse_number(n) {
+                Some(n) => {
+                    self.store.set_fuel(Some(n));
+                    Ok(())
+                }
+                None => writeln!(output, "expected an amount of fuel, got {}", n),
+            },
+            ["catch", "grow"] => {
+                self.catch_grow();
+                Ok(())
+            }
+            ["catch", "throw"] => {
+                self.catch_throw();
+                Ok(())
+            }
+            ["save", path] => match self.snapshot() {
+                Ok(snapshot) => match snapshot.save(path) {
+                    Ok(()) => writeln!(output, "saved {}", path),
+                    Err(err) => writeln!(output, "{}: {}", path, err),
+                },
+                Err(err) => writeln!(output, "{}", err),
+            },
+            ["load", path] => {
+                let restored = Snapshot::load(path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|snapshot| self.restore(&snapshot).map_err(|err| err.to_string()));
+                match restored {
+                    Ok(()) => self.print_backtrace(output),
+                    Err(err) => writeln!(output, "{}: {}", path, err),
+                }
+            }
+            _ => writeln!(output, "unknown command {}, try help", words.join(" ")),
+        }
+    }
+
+    fn add_breakpoint_at(
+        &mut self,
+        output: &mut impl Write,
+        module: Option<&str>,
+        func: &str,
+    ) -> io::Result<()> {
+        let Some(addr) = self.find_func(module, func) else {
+            return writeln!(output, "no function {}", func);
+        };
+        if !self.add_breakpoint(addr) {
+            return writeln!(output, "{} is a host function", func);
+        }
+        let n = self.breakpoints.len();
+        writeln!(output, "breakpoint {} at {}", n, self.describe(addr))
+    }
+
+    fn run(&mut self, output: &mut impl Write, steps: Option<u64>) -> io::Result<()> {
+        let result = self.resume(steps);
+        self.report(output, result)
+    }
+
+    /// Says why the call stopped after running.
+    fn report(&mut self, output: &mut impl Write, result: Result<(), Trap>) -> io::Result<()> {
+        if let Some(i) = self.breakpoint() {
+            write!(output, "breakpoint {}, ", i + 1)?;
+        }
+        if let Some(growth) = self.take_growth() {
+            writeln!(output, "{}", growth)?;
+        }
+        if let Some((tag, fields)) = self.throwing() {
+            writeln!(output, "throwing {}", self.describe_exception(tag, &fields))?;
+        }
+        match result {
+            Ok(()) if self.execution.is_finished() => {
+                write!(output, "returned ")?;
+                print_values(output, self.execution.stack())
+            }
+            Ok(()) => match self.backtrace().first() {
+                Some(frame) => writeln!(output, "stopped in {}", frame),
+                None => writeln!(output, "stopped"),
+            },
+            Err(trap) => {
+                writeln!(output, "trap: {}", trap)?;
+                self.print_exception(output)?;
+                self.print_backtrace(output)
+            }
+        }
+    }
+
+    /// Prints the exception nothing caught, if that is what trapped.
+    pub fn print_exception(&self, output: &mut impl Write) -> io::Result<()> {
+        let uncaught = self
+            .trap
+            .as_ref()
+            .is_some_and(|trap| trap.kind() == &TrapKind::UncaughtException);
+        match self.execution.thrown() {
+            Some(exn) if uncaught => {
+                let exn = self.store.exception(exn);
+                writeln!(
+                    output,
+                    "{}",
+                    self.describe_exception(exn.tag(), exn.fields())
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// An exception as its tag, by its index in the innermost frame's
+    /// instance, and its fields.
+    fn describe_exception(&self, tag: TagAddr, fields: &[Value]) -> String {
+        let instance = self
+            .execution
+            .frames()
+            .last()
+            .map_or(self.instance, Frame::instance);
+        let data = instance.data(&self.store);
+        let tag = match data.tags.iter().position(|t| *t == tag) {
+            Some(idx) => match data.names.get(NameSpace::Tag, idx as u32) {
+                Some(name) => format!("${} (tag {})", name, idx),
+                None => format!("tag {}", idx),
+            },
+            None => format!("tag at address {}", tag.0),
+        };
+        let fields = fields.iter().map(Value::to_string).collect::<Vec<_>>();
+        format!("exception {} [{}]", tag, fields.join(", "))
+    }
+
+    pub fn print_backtrace(&self, output: &mut impl Write) -> io::Result<()> {
+        for (i, frame) in self.backtrace().iter().enumerate() {
+            writeln!(output, "#{} {}", i, frame)?;
+        }
+        Ok(())
+    }
+
+    fn dump(&self, output: &mut impl Write, addr: &str, len: &str) -> io::Result<()> {
+        let (Some(addr), Some(len)) = (parse_number(addr), parse_number(len)) else {
+            return writeln!(output, "expected an address and a length");
+        };
+        let memory = self
+            .execution
+            .frames()
+            .last()
+            .and_then(|frame| frame.instance().data(&self.store).mems.first().copied());
+        let Some(memory) = memory else {
+            return writeln!(output, "no memory");
+        };
+        let Ok(bytes) = self.store.memory(memory).bytes(addr, len) else {
+            return writeln!(output, "out of bounds");
+        };
+        for (i, line) in bytes.chunks(16).enumerate() {
+            let hex = line
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>();
+            writeln!(output, "{:08x}: {}", addr + 16 * i as u64, hex.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+fn print_values(output: &mut impl Write, values: &[Value]) -> io::Result<()> {
+    let values = values.iter().map(Value::to_string).collect::<Vec<_>>();
+    writeln!(output, "[{}]", values.join(", "))
+}
+
+/// Parses a decimal or `0x` hexadecimal number.
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::debugger::Debugger;
+    use crate::runtime::exec::Execution;
+    use crate::runtime::imports::Imports;
+    use crate::runtime::instance::Instance;
+    use crate::runtime::linker::Linker;
+    use crate::runtime::store::Store;
+    use crate::runtime::trap::TrapKind;
+    use crate::runtime::value::Value;
+    use crate::wat::parse::parse_module;
+
+    fn debugger(text: &str, name: &str, args: &[Value]) -> Debugger {
+        let module = parse_module(text).unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, &module, &Imports::new()).unwrap();
+        let func = instance.func(&store, name).unwrap();
+        let execution = Execution::new(&store, func, args).unwrap();
+        Debugger::new(store, instance, execution)
+    }
+
+    fn session_from(debugger: &mut Debugger, paused: bool, commands: &str) -> String {
+        let mut output = Vec::new();
+        debugger
+            .start(paused, &mut commands.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn session(debugger: &mut Debugger, commands: &str) -> String {
+        let mut output = Vec::new();
+        debugger
+            .prompt(&mut commands.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_debugger() {
+        let text = r#"
+            (memory 1)
+            (data (i32.const 16) "\01\02\03")
+            (func $inner (param i32) (result i32) (local i32)
+              (local.set 1 (i32.const 7))
+              (i32.add (i32.const 5) (i32.div_u (i32.const 1) (local.get 0))))
+            (func (export "outer") (param i32) (result i32)
+              (call $inner (local.get 0)))
+        "#;
+        let mut debugger = debugger(text, "outer", &[Value::I32(0)]);
+        let trap = debugger.resume(None).unwrap_err();
+        assert_eq!(trap.kind(), &TrapKind::IntegerDivideByZero);
+        assert_eq!(debugger.backtrace().len(), 2);
+
+        // The trapped call's state stays for looking at, but it can't go on
+        let output = session(
+            &mut debugger,
+            "bt\nlocals\nstack\nmemory 16 3\nstep\nbogus\nquit\nlocals\n",
+        );
+        let lines = output
+            .split("(wasmdbg) ")
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        assert_eq!(lines[1], "#0 main!func 0 at 0xf\n#1 main!func 1 at 0x16");
+        assert_eq!(lines[2], "[i32 0, i32 7]");
+        assert_eq!(lines[3], "[i32 5]");
+        assert_eq!(lines[4], "00000010: 01 02 03");
+        assert!(lines[5].starts_with("trap: integer divide by zero\n#0 main!func 0"));
+        assert_eq!(lines[6], "unknown command bogus, try help");
+        assert_eq!(lines.len(), 8);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let text = r#"
+            (global $g (mut i32) (i32.const 0))
+            (func (export "run") (result i32)
+              (global.set $g (i32.const 42))
+              (unreachable))
+        "#;
+        let path = std::env::temp_dir().join(format!("wasmdbg-debugger-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut debugger = debugger(text, "run", &[]);
+        debugger.resume(None).unwrap_err();
+        session(&mut debugger, &format!("save {}\n", path));
+
+        // A fresh instance goes back to the trap, without running up to it
+        let mut restored = self::debugger(text, "run", &[]);
+        let output = session(&mut restored, &format!("load {}\n", path));
+        std::fs::remove_file(path).unwrap();
+        assert!(output.contains("#0 main!func 0 at"));
+        assert_eq!(
+            restored.trap().map(|trap| trap.kind()),
+            Some(&TrapKind::Unreachable)
+        );
+        assert_eq!(restored.backtrace(), debugger.backtrace());
+        let global = restored
+            .store()
+            .global(restored.instance.data(restored.store()).globals[0]);
+        assert_eq!(global.get(), Value::I32(42));
+    }
+
+    #[test]
+    fn test_stepping() {
+        let text = r#"
+            (func (export "add") (param i32 i32) (result i32)
+              (i32.add (local.get 0) (local.get 1)))
+        "#;
+        let mut debugger = debugger(text, "add", &[Value::I32(2), Value::I32(3)]);
+        let output = session(&mut debugger, "step 2\nstack\ncontinue\n");
+        let lines = output
+            .split("(wasmdbg) ")
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        assert!(lines[1].starts_with("stopped in main!func 0 at"));
+        assert_eq!(lines[2], "[i32 2, i32 3]");
+        assert_eq!(lines[3], "returned [i32 5]");
+    }
+
+    #[test]
+    fn test_tail_call_backtrace() {
+        let text = r#"
+            (func $last (result i32) (unreachable))
+            (func $middle (result i32) (return_call $last))
+            (func (export "first") (result i32) (return_call $middle))
+        "#;
+        let mut debugger = debugger(text, "first", &[]);
+        debugger.resume(None).unwrap_err();
+        let output = session(&mut debugger, "bt\n");
+        let lines = output
+            .split("(wasmdbg) ")
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines[1],
+            "#0 main!func 0 at 0x3 (2 frames replaced by tail calls)"
+        );
+    }
+
+    #[test]
+    fn test_catch_grow() {
+        let text = r#"
+            (memory 1 2)
+            (func (export "grow") (result i32)
+              (drop (memory.grow (i32.const 1)))
+              (memory.grow (i32.const 1)))
+        "#;
+        let mut debugger = debugger(text, "grow", &[]);
+        let output = session(&mut debugger, "catch grow\ncontinue\ncontinue\ncontinue\n");
+        let lines = output
+            .split("(wasmdbg) ")
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        assert!(lines[2].starts_with("memory 0 grew from 1 to 2 pages\nstopped in main!func 0"));
+        assert!(lines[3].starts_with("memory 0 failed to grow from 2 pages by 1\nstopped"));
+        assert_eq!(lines[4], "returned [i32 -1]");
+    }
+
+    #[test]
+    fn test_catch_throw() {
+        let text = r#"
+            (tag $e (param i32 i64))
+            (func $throw (param i32) (throw $e (local.get 0) (i64.const -1)))
+            (func (export "run") (result i32)
+              (block $caught (result i32 i64)
+                (try_table (catch $e $caught) (call $throw (i32.const 7)))
+                (return (i32.const 0)))
+              (drop) (drop)
+              (call $throw (i32.const 8))
+              (i32.const 1))
+        "#;
+        let mut debugger = debugger(text, "run", &[]);
+        let output = session(
+            &mut debugger,
+            "catch throw\ncontinue\nbt\ncontinue\ncontinue\n",
+        );
+        let lines = output
+            .split("(wasmdbg) ")
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        assert!(
+            lines[2]
+                .starts_with("throwing exception tag 0 [i32 7, i64 -1]\nstopped in main!func 0")
+        );
+        assert_eq!(lines[3].lines().count(), 2);
+        // Caught, then thrown again where nothing catches it
+        assert!(lines[4].starts_with("throwing exception tag 0 [i32 8, i64 -1]\n"));
+        assert!(
+            lines[5].starts_with("trap: uncaught exception\nexception tag 0 [i32 8, i64 -1]\n#0")
+        );
+    }
+
+    #[test]
+    fn test_start() {
+        let text = r#"
+            (func $inner (param i32) (result i32)
+              (i32.div_u (i32.const 1) (local.get 0)))
+            (func (export "outer") (param i32) (result i32)
+              (call $inner (local.get 0)))
+        "#;
+        let mut dbg = debugger(text, "outer", &[Value::I32(1)]);
+        let output = session_from(
+            &mut dbg, false, "bt
+",
+        );
+        assert_eq!(output, "");
+        assert_eq!(dbg.execution().stack(), &[Value::I32(1)]);
+
+        // Paused, nothing runs before the first command
+        let mut dbg = debugger(text, "outer", &[Value::I32(1)]);
+        let output = session_from(
+            &mut dbg, true, "step
+",
+        );
+        assert!(output.starts_with("(wasmdbg) stopped in main!func 1 at 0x"));
+
+        let mut dbg = debugger(text, "outer", &[Value::I32(1)]);
+        let inner = dbg.find_func(None, "0").unwrap();
+        dbg.add_breakpoint(inner);
+        let output = session_from(
+            &mut dbg,
+            false,
+            "continue
+",
+        );
+        let lines = output.split("(wasmdbg) ").collect::<Vec<_>>();
+        assert!(lines[0].starts_with("breakpoint 1, stopped in main!func 0 at 0x"));
+        assert!(lines[1].starts_with("returned [i32 1]"));
+
+        let mut dbg = debugger(text, "outer", &[Value::I32(0)]);
+        let output = session_from(&mut dbg, false, "");
+        assert!(output.starts_with("trap: integer divide by zero\n#0 main!func 0 at 0x"));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let plugin = parse_module(
+            r#"
+            (func $double (export "double") (param i32) (result i32)
+              (i32.mul (local.get 0) (i32.const 2)))
+            (func $twice (param i32) (result i32)
+              (call $double (call $double (local.get 0))))
+            (export "twice" (func $twice))
+        "#,
+        )
+        .unwrap();
+        let app = parse_module(
+            r#"
+            (import "plugin" "twice" (func $twice (param i32) (result i32)))
+            (func (export "run") (result i32)
+              (i32.add (call $twice (i32.const 3)) (i32.const 1)))
+        "#,
+        )
+        .unwrap();
+        let mut store = Store::new();
+        let linker = Linker::new();
+        let instance = linker.instantiate(&mut store, &plugin).unwrap();
+        store.register("plugin", instance);
+        let instance = linker.instantiate(&mut store, &app).unwrap();
+        let func = instance.func(&store, "run").unwrap();
+        let execution = Execution::new(&store, func, &[]).unwrap();
+        let mut debugger = Debugger::new(store, instance, execution);
+        let output = session(
+            &mut debugger,
+            "break plugin double\nbreak nothing\nbreakpoints\ncontinue\nbt\n\
+             finish\ndelete 1\nnext\nstack\ncontinue\n",
+        );
+        let lines = output
+            .split("(wasmdbg) ")
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        assert_eq!(lines[1], "breakpoint 1 at plugin!func 0");
+        assert_eq!(lines[2], "no function nothing");
+        assert_eq!(lines[3], "1: plugin!func 0");
+        assert!(lines[4].starts_with("breakpoint 1, stopped in plugin!func 0 at 0x"));
+        let frames = lines[5].lines().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 3);
+        assert!(frames[1].starts_with("#1 plugin!func 1"));
+        assert!(frames[2].starts_with("#2 main!func 1"));
+        // Back in the caller, then over its second call
+        assert!(lines[6].starts_with("stopped in plugin!func 1"));
+        assert!(lines[8].starts_with("stopped in plugin!func 1"));
+        assert_eq!(lines[9], "[i32 12]");
+        assert_eq!(lines[10], "returned [i32 13]");
+    }
+}
@@ -0,0 +1,1479 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::instr::Instr;
+use crate::instr::atomic::AtomicOp;
+use crate::instr::numeric::{LoadOp, StoreOp};
+use crate::instr::simd::{SimdImm, SimdOp};
+use crate::runtime::host::Caller;
+use crate::runtime::instance::Instance;
+use crate::runtime::numeric;
+use crate::runtime::simd;
+use crate::runtime::store::{ExnAddr, FuncAddr, FuncKind, MemAddr, Store, TableAddr, TagAddr};
+use crate::runtime::trap::{Trap, TrapFrame, TrapKind};
+use crate::runtime::value::{Ref, Value};
+use crate::section::code::Code;
+use crate::types::block_type::BlockType;
+use crate::types::func_type::FuncType;
+use crate::types::mem_arg::MemArg;
+use crate::types::primitives::{LabelIdx, TypeIdx};
+use crate::types::val_type::ValType;
+use crate::validate::FuncStacks;
+use crate::validate::func::{atomic_type, store_type};
+
+// Calls nest this deep at most before execution traps, which keeps runaway
+// recursion from exhausting the host's memory
+const MAX_FRAMES: usize = 50_000;
+const MAX_VALUES: usize = 1 << 20;
+
+/// Where a block ends and what it takes and leaves on the stack.
+struct BlockInfo {
+    /// The index of the block's `end`, or its `delegate`.
+    end: usize,
+    /// The indices of the `else`, `catch` and `catch_all` instructions that
+    /// start the block's other clauses.
+    clauses: Vec<usize>,
+    params: usize,
+    results: usize,
+}
+
+/// A function body prepared for execution, with its blocks matched up
+/// ahead of time so that branches don't have to search for their targets.
+pub(crate) struct Body {
+    instrs: Vec<Instr>,
+    offsets: Vec<usize>,
+    /// The declared locals, after the parameters.
+    locals: Vec<ValType>,
+    /// The blocks, by the index of the instruction that opens them.
+    blocks: HashMap<usize, BlockInfo>,
+    /// What the type checker found on the stacks before each instruction.
+    stacks: FuncStacks,
+}
+
+impl Body {
+    /// Prepares `code` given the function types of the module, by type
+    /// index, and what the type checker found on its stacks.
+    pub(crate) fn new(code: &Code, types: &[Option<FuncType>], stacks: FuncStacks) -> Body {
+        let instrs = code.body().instrs().to_vec();
+        let mut blocks = HashMap::new();
+        let mut open: Vec<(usize, BlockInfo)> = Vec::new();
+        for (pc, instr) in instrs.iter().enumerate() {
+            match instr {
+                Instr::Block(bt)
+                | Instr::Loop(bt)
+                | Instr::If(bt)
+                | Instr::Try(bt)
+                | Instr::TryTable(bt, _) => {
+                    let (params, results) = match bt {
+                        BlockType::Empty => (0, 0),
+                        BlockType::Value(_) => (0, 1),
+                        BlockType::Type(idx) => types
+                            .get(idx.0 as usize)
+                            .and_then(Option::as_ref)
+                            .map_or((0, 0), |ty| (ty.params().len(), ty.results().len())),
+                    };
+                    let info = BlockInfo {
+                        end: pc,
+                        clauses: Vec::new(),
+                        params,
+                        results,
+                    };
+                    open.push((pc, info));
+                }
+                Instr::Else | Instr::Catch(_) | Instr::CatchAll => {
+                    if let Some((_, info)) = open.last_mut() {
+                        info.clauses.push(pc);
+                    }
+                }
+                // The function's own `end` has no block to close
+                Instr::End | Instr::Delegate(_) => {
+                    if let Some((start, mut info)) = open.pop() {
+                        info.end = pc;
+                        blocks.insert(start, info);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Body {
+            instrs,
+            offsets: code.offsets().to_vec(),
+            locals: code.local_types().collect(),
+            blocks,
+            stacks,
+        }
+    }
+
+    /// The number of instructions.
+    pub(crate) fn len(&self) -> usize {
+        self.instrs.len()
+    }
+
+    /// The number of declared locals, after the parameters.
+    pub(crate) fn num_locals(&self) -> usize {
+        self.locals.len()
+    }
+
+    /// Whether the instruction at `pc` opens a block.
+    pub(crate) fn opens_block(&self, pc: usize) -> bool {
+        self.blocks.contains_key(&pc)
+    }
+
+    /// The declared locals, after the parameters.
+    pub(crate) fn locals(&self) -> &[ValType] {
+        &self.locals
+    }
+
+    pub(crate) fn instr(&self, pc: usize) -> Option<&Instr> {
+        self.instrs.get(pc)
+    }
+
+    pub(crate) fn stacks(&self) -> &FuncStacks {
+        &self.stacks
+    }
+
+    /// The kind of the label of the block opened at `start` while the
+    /// function is at instruction `pc`. `None` in the handlers of a `try`,
+    /// whose label depends on the exception caught.
+    pub(crate) fn label_kind(&self, start: usize, pc: usize) -> Option<LabelKind> {
+        match self.instrs.get(start)? {
+            Instr::Block(_) => Some(LabelKind::Block),
+            Instr::Loop(_) => Some(LabelKind::Loop),
+            Instr::If(_) => Some(LabelKind::If),
+            Instr::Try(_) => {
+                let handler = self.blocks.get(&start)?.clauses.first();
+                handler
+                    .is_none_or(|&clause| pc <= clause)
+                    .then_some(LabelKind::Try)
+            }
+            Instr::TryTable(..) => Some(LabelKind::TryTable),
+            _ => None,
+        }
+    }
+
+    fn block(&self, start: usize) -> &BlockInfo {
+        &self.blocks[&start]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LabelKind {
+    Block,
+    Loop,
+    If,
+    Try,
+    TryTable,
+    /// A `try` block running the handler that caught an exception, which
+    /// `rethrow` refers to.
+    Catch(ExnAddr),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Label {
+    pub(crate) kind: LabelKind,
+    /// The index of the instruction that opened the block.
+    pub(crate) start: usize,
+    /// The height of the operand stack below the block's parameters.
+    pub(crate) height: usize,
+}
+
+/// A function activation on the call stack.
+pub struct Frame {
+    pub(crate) func: FuncAddr,
+    pub(crate) instance: usize,
+    pub(crate) body: Rc<Body>,
+    pub(crate) pc: usize,
+    /// The position of the first local on the operand stack.
+    pub(crate) base: usize,
+    /// The number of locals, including the parameters.
+    pub(crate) locals: usize,
+    /// The number of labels that belong to the callers.
+    pub(crate) label_base: usize,
+    pub(crate) arity: usize,
+    /// How many frames this one replaced through tail calls.
+    pub(crate) replaced: u32,
+}
+
+impl Frame {
+    pub fn func(&self) -> FuncAddr {
+        self.func
+    }
+
+    /// The instance the function belongs to.
+    pub fn instance(&self) -> Instance {
+        Instance(self.instance)
+    }
+
+    /// The index of the next instruction in the function body.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The offset of the next instruction, relative to the start of the
+    /// code section's contents.
+    pub fn offset(&self) -> Option<usize> {
+        self.body.offsets.get(self.pc).copied()
+    }
+
+    /// The number of callers that tail calls removed from the stack below
+    /// this frame.
+    pub fn replaced(&self) -> u32 {
+        self.replaced
+    }
+
+    /// The frame as part of a backtrace, at instruction `pc`.
+    fn trace(&self, store: &Store, pc: usize) -> TrapFrame {
+        let func = store
+            .func(self.func)
+            .idx()
+            .expect("frames are for module functions");
+        let data = &store.instances[self.instance];
+        let offset = self.body.offsets.get(pc).copied();
+        let location = offset
+            .zip(data.lines.as_ref())
+            .and_then(|(offset, lines)| lines.location(offset as u64));
+        let instance = Instance(self.instance);
+        TrapFrame::new(
+            instance,
+            instance.name(store).map(str::to_string),
+            func,
+            offset,
+            data.names.func(func).map(str::to_string),
+            location,
+        )
+        .with_replaced(self.replaced)
+    }
+}
+
+/// A call in progress, which runs one instruction at a time.
+pub struct Execution {
+    pub(crate) stack: Vec<Value>,
+    pub(crate) labels: Vec<Label>,
+    pub(crate) frames: Vec<Frame>,
+    /// A host function called from outside, which runs on the first step.
+    pub(crate) pending: Option<FuncAddr>,
+    /// The number of instructions executed.
+    pub(crate) steps: u64,
+    /// The exception thrown last.
+    pub(crate) thrown: Option<ExnAddr>,
+}
+
+impl Execution {
+    /// Prepares a call of `func` with `args`, which must match its
+    /// parameters.
+    pub fn new(store: &Store, func: FuncAddr, args: &[Value]) -> Result<Execution, Trap> {
+        let params = store.func(func).ty().params();
+        if args.len() != params.len() {
+            return Err(Trap::new(TrapKind::Host(format!(
+                "expected {} arguments, got {}",
+                params.len(),
+                args.len()
+            ))));
+        }
+        if let Some((arg, t)) = args.iter().zip(params).find(|(arg, t)| !arg.has_type(**t)) {
+            return Err(Trap::new(TrapKind::Host(format!(
+                "argument {} does not have type {}",
+                arg, t
+            ))));
+        }
+        let mut execution = Execution {
+            stack: args.to_vec(),
+            labels: Vec::new(),
+            frames: Vec::new(),
+            pending: None,
+            steps: 0,
+            thrown: None,
+        };
+        match &store.func(func).kind {
+            FuncKind::Wasm { .. } => execution.push_frame(store, func, 0)?,
+            FuncKind::Host(_) => execution.pending = Some(func),
+        }
+        Ok(execution)
+    }
+
+    /// The call stack, innermost frame last.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// The operand stack, including the locals of every frame.
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// The locals of the innermost frame, parameters first.
+    pub fn locals(&self) -> &[Value] {
+        self.frames.last().map_or(&[], |frame| {
+            &self.stack[frame.base..frame.base + frame.locals]
+        })
+    }
+
+    /// The operands of the innermost frame, above its locals.
+    pub fn operands(&self) -> &[Value] {
+        let start = self
+            .frames
+            .last()
+            .map_or(0, |frame| frame.base + frame.locals);
+        &self.stack[start..]
+    }
+
+    /// The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The exception thrown last, which after a trap for
+    /// [`TrapKind::UncaughtException`] is the one nothing caught.
+    pub fn thrown(&self) -> Option<ExnAddr> {
+        self.thrown
+    }
+
+    /// The tag and fields of the exception the next instruction throws, if
+    /// it is a `throw`, `throw_ref` or `rethrow` that throws one.
+    pub fn next_throw(&self, store: &Store) -> Option<(TagAddr, Vec<Value>)> {
+        let frame = self.frames.last()?;
+        let exn = match frame.body.instrs.get(frame.pc)? {
+            Instr::Throw(idx) => {
+                let tag = store.instances[frame.instance].tags[idx.0 as usize];
+                let n = store.tag(tag).ty().params().len();
+                return Some((tag, self.stack[self.stack.len() - n..].to_vec()));
+            }
+            Instr::ThrowRef => match self.stack.last()? {
+                Value::Ref(Ref::Exn(exn)) => *exn,
+                _ => return None,
+            },
+            Instr::Rethrow(l) => match self.labels[self.labels.len() - 1 - l.0 as usize].kind {
+                LabelKind::Catch(exn) => exn,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let exn = store.exception(exn);
+        Some((exn.tag(), exn.fields().to_vec()))
+    }
+
+    /// Whether the call has returned.
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty() && self.pending.is_none()
+    }
+
+    /// Runs the call to completion and returns its results.
+    pub fn run(mut self, store: &mut Store) -> Result<Vec<Value>, Trap> {
+        while !self.is_finished() {
+            self.step(store)?;
+        }
+        Ok(self.stack)
+    }
+
+    /// Executes up to `steps` instructions, stopping early if the call
+    /// returns.
+    pub fn run_for(&mut self, store: &mut Store, steps: u64) -> Result<(), Trap> {
+        for _ in 0..steps {
+            if self.is_finished() {
+                break;
+            }
+            self.step(store)?;
+        }
+        Ok(())
+    }
+
+    /// Executes the next instruction. A trap carries a backtrace of the
+    /// frames that were active, innermost first.
+    pub fn step(&mut self, store: &mut Store) -> Result<(), Trap> {
+        let depth = self.frames.len();
+        let pc = self.frames.last().map(|frame| frame.pc);
+        self.execute(store).map_err(|trap| {
+            // The innermost frame is still at the instruction that trapped,
+            // unless the trap unwound it
+            let pc = pc.filter(|_| self.frames.len() == depth);
+            trap.with_frames(self.trace(store, pc))
+        })
+    }
+
+    /// The call stack as a backtrace, innermost frame first.
+    pub fn backtrace(&self, store: &Store) -> Vec<TrapFrame> {
+        self.trace(store, self.frames.last().map(|frame| frame.pc))
+    }
+
+    /// The frames from the innermost out, the innermost at instruction `pc`
+    /// if given. The others are at the call they're waiting on.
+    fn trace(&self, store: &Store, pc: Option<usize>) -> Vec<TrapFrame> {
+        self.frames
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, frame)| match pc {
+                Some(pc) if i == 0 => frame.trace(store, pc),
+                _ => frame.trace(store, frame.pc.saturating_sub(1)),
+            })
+            .collect()
+    }
+
+    fn execute(&mut self, store: &mut Store) -> Result<(), Trap> {
+        if let Some(func) = self.pending.take() {
+            return self.call(store, func, 0);
+        }
+        let Some(frame) = self.frames.last_mut() else {
+            return Ok(());
+        };
+        let body = frame.body.clone();
+        let pc = frame.pc;
+        if let Some(fuel) = store.fuel {
+            let cost = store.fuel_costs.cost(&body.instrs[pc]);
+            if cost > fuel {
+                return Err(Trap::new(TrapKind::OutOfFuel));
+            }
+            store.fuel = Some(fuel - cost);
+        }
+        frame.pc += 1;
+        self.steps += 1;
+        let instance = frame.instance;
+        let base = frame.base;
+        let data = &store.instances[instance];
+        match &body.instrs[pc] {
+            Instr::Unreachable => return Err(Trap::new(TrapKind::Unreachable)),
+            Instr::Nop => {}
+            Instr::Block(_) => self.enter(&body, LabelKind::Block, pc),
+            Instr::Loop(_) => self.enter(&body, LabelKind::Loop, pc),
+            Instr::If(_) => {
+                let block = body.block(pc);
+                if self.pop_i32() != 0 {
+                    self.enter(&body, LabelKind::If, pc);
+                } else if let Some(&clause) = block.clauses.first() {
+                    self.enter(&body, LabelKind::If, pc);
+                    self.jump(clause + 1);
+                } else {
+                    self.jump(block.end + 1);
+                }
+            }
+            Instr::Try(_) => self.enter(&body, LabelKind::Try, pc),
+            Instr::TryTable(..) => self.enter(&body, LabelKind::TryTable, pc),
+            // Reaching the next clause ends the one before it
+            Instr::Else | Instr::Catch(_) | Instr::CatchAll => {
+                let label = self.labels[self.labels.len() - 1];
+                self.jump(body.block(label.start).end);
+            }
+            Instr::End | Instr::Delegate(_) => {
+                if self.labels.len() > self.frame().label_base {
+                    self.labels.pop();
+                } else {
+                    self.ret();
+                }
+            }
+            Instr::Br(l) => self.branch(*l),
+            Instr::BrIf(l) => {
+                if self.pop_i32() != 0 {
+                    self.branch(*l);
+                }
+            }
+            Instr::BrTable(labels, default) => {
+                let i = self.pop_i32() as u32 as usize;
+                self.branch(*labels.get(i).unwrap_or(default));
+            }
+            Instr::Return => self.ret(),
+            Instr::Call(idx) => {
+                let func = data.funcs[idx.0 as usize];
+                self.call(store, func, 0)?;
+            }
+            Instr::CallIndirect(ty, table) => {
+                let table = data.tables[table.0 as usize];
+                let func = self.indirect(store, instance, *ty, table)?;
+                self.call(store, func, 0)?;
+            }
+            Instr::CallRef(_) => {
+                let func = self.pop_func_ref()?;
+                self.call(store, func, 0)?;
+            }
+            Instr::ReturnCall(idx) => {
+                let func = data.funcs[idx.0 as usize];
+                self.tail_call(store, func)?;
+            }
+            Instr::ReturnCallIndirect(ty, table) => {
+                let table = data.tables[table.0 as usize];
+                let func = self.indirect(store, instance, *ty, table)?;
+                self.tail_call(store, func)?;
+            }
+            Instr::ReturnCallRef(_) => {
+                let func = self.pop_func_ref()?;
+                self.tail_call(store, func)?;
+            }
+            Instr::BrOnNull(l) => {
+                let r = self.pop_ref();
+                if r.is_null() {
+                    self.branch(*l);
+                } else {
+                    self.push(Value::Ref(r));
+                }
+            }
+            Instr::BrOnNonNull(l) => {
+                let r = self.pop_ref();
+                if !r.is_null() {
+                    self.push(Value::Ref(r));
+                    self.branch(*l);
+                }
+            }
+
+            Instr::Throw(idx) => {
+                let tag = data.tags[idx.0 as usize];
+                let n = store.tag(tag).ty().params().len();
+                let fields = self.stack.split_off(self.stack.len() - n);
+                let exn = store.add_exception(tag, fields);
+                self.throw(store, exn)?;
+            }
+            Instr::ThrowRef => match self.pop_ref() {
+                Ref::Exn(exn) => self.throw(store, exn)?,
+                _ => return Err(Trap::new(TrapKind::NullReference)),
+            },
+            Instr::Rethrow(l) => {
+                let label = self.labels[self.labels.len() - 1 - l.0 as usize];
+                if let LabelKind::Catch(exn) = label.kind {
+                    self.throw(store, exn)?;
+                }
+            }
+
+            Instr::RefNull(_) => self.push(Value::Ref(Ref::Null)),
+            Instr::RefIsNull => {
+                let r = self.pop_ref();
+                self.push(Value::I32(r.is_null() as i32));
+            }
+            Instr::RefFunc(idx) => self.push(Value::Ref(Ref::Func(data.funcs[idx.0 as usize]))),
+            Instr::RefAsNonNull => {
+                let r = self.pop_ref();
+                if r.is_null() {
+                    return Err(Trap::new(TrapKind::NullReference));
+                }
+                self.push(Value::Ref(r));
+            }
+            Instr::RefEq => {
+                let b = self.pop_ref();
+                let a = self.pop_ref();
+                self.push(Value::I32((a == b) as i32));
+            }
+
+            Instr::Drop => {
+                self.pop();
+            }
+            Instr::Select(_) => {
+                let c = self.pop_i32();
+                let b = self.pop();
+                let a = self.pop();
+                self.push(if c != 0 { a } else { b });
+            }
+
+            Instr::LocalGet(idx) => self.push(self.stack[base + idx.0 as usize]),
+            Instr::LocalSet(idx) => {
+                let v = self.pop();
+                self.stack[base + idx.0 as usize] = v;
+            }
+            Instr::LocalTee(idx) => {
+                let v = self.stack[self.stack.len() - 1];
+                self.stack[base + idx.0 as usize] = v;
+            }
+            Instr::GlobalGet(idx) => {
+                let v = store.global(data.globals[idx.0 as usize]).get();
+                self.push(v);
+            }
+            Instr::GlobalSet(idx) => {
+                let global = data.globals[idx.0 as usize];
+                let v = self.pop();
+                store.global_mut(global).set(v);
+            }
+
+            Instr::TableGet(idx) => {
+                let table = store.table(data.tables[idx.0 as usize]);
+                let i = self.pop_index();
+                let r = table.get(i)?;
+                self.push(Value::Ref(r));
+            }
+            Instr::TableSet(idx) => {
+                let table = data.tables[idx.0 as usize];
+                let r = self.pop_ref();
+                let i = self.pop_index();
+                store.table_mut(table).set(i, r)?;
+            }
+            Instr::TableInit(elem, table) => {
+                let table = data.tables[table.0 as usize];
+                let elem = data.elems[elem.0 as usize];
+                let n = self.pop_index();
+                let s = self.pop_index();
+                let d = self.pop_index();
+                let refs = &store.elems[elem.0 as usize];
+                let refs = s
+                    .checked_add(n)
+                    .and_then(|end| refs.get(s as usize..end as usize))
+                    .ok_or(TrapKind::TableOutOfBounds)?
+                    .to_vec();
+                store.table_mut(table).init(d, &refs)?;
+            }
+            Instr::ElemDrop(idx) => {
+                let elem = data.elems[idx.0 as usize];
+                store.elems[elem.0 as usize] = Vec::new();
+            }
+            Instr::TableCopy(dst, src) => {
+                let (dst, src) = (data.tables[dst.0 as usize], data.tables[src.0 as usize]);
+                let n = self.pop_index();
+                let s = self.pop_index();
+                let d = self.pop_index();
+                if dst == src {
+                    store.table_mut(dst).copy_within(d, s, n)?;
+                } else {
+                    let refs = store.table(src).elems_at(s, n)?.to_vec();
+                    store.table_mut(dst).init(d, &refs)?;
+                }
+            }
+            Instr::TableGrow(idx) => {
+                let table = store.table_mut(data.tables[idx.0 as usize]);
+                let n = self.pop_index();
+                let init = self.pop_ref();
+                let old = table.grow(n, init).map_or(-1, |old| old as i64);
+                let is_64 = table.is_64();
+                self.push_index(is_64, old);
+            }
+            Instr::TableSize(idx) => {
+                let table = store.table(data.tables[idx.0 as usize]);
+                self.push_index(table.is_64(), table.size() as i64);
+            }
+            Instr::TableFill(idx) => {
+                let table = data.tables[idx.0 as usize];
+                let n = self.pop_index();
+                let r = self.pop_ref();
+                let i = self.pop_index();
+                store.table_mut(table).fill(i, r, n)?;
+            }
+
+            Instr::Load(op, memarg) => {
+                let mem = data.mems[memarg.memidx().0 as usize];
+                let addr = self.effective_address(memarg)?;
+                let v = load(store, mem, *op, addr)?;
+                self.push(v);
+            }
+            Instr::Store(op, memarg) => {
+                let mem = data.mems[memarg.memidx().0 as usize];
+                let v = self.pop();
+                let addr = self.effective_address(memarg)?;
+                let bytes = store_bytes(*op, v);
+                store.memory_mut(mem).write(addr, &bytes)?;
+            }
+            Instr::MemorySize(idx) => {
+                let memory = store.memory(data.mems[idx.0 as usize]);
+                self.push_index(memory.is_64(), memory.size() as i64);
+            }
+            Instr::MemoryGrow(idx) => {
+                let mem = data.mems[idx.0 as usize];
+                let n = self.pop_index();
+                let old = store.grow_memory(mem, n).map_or(-1, |old| old as i64);
+                self.push_index(store.memory(mem).is_64(), old);
+            }
+            Instr::MemoryInit(seg, idx) => {
+                let mem = data.mems[idx.0 as usize];
+                let seg = data.datas[seg.0 as usize];
+                let n = self.pop_index();
+                let s = self.pop_index();
+                let d = self.pop_index();
+                let bytes = &store.datas[seg.0 as usize];
+                let bytes = s
+                    .checked_add(n)
+                    .and_then(|end| bytes.get(s as usize..end as usize))
+                    .ok_or(TrapKind::MemoryOutOfBounds)?
+                    .to_vec();
+                store.memory_mut(mem).write(d, &bytes)?;
+            }
+            Instr::DataDrop(idx) => {
+                let seg = data.datas[idx.0 as usize];
+                store.datas[seg.0 as usize] = Vec::new();
+            }
+            Instr::MemoryCopy(dst, src) => {
+                let (dst, src) = (data.mems[dst.0 as usize], data.mems[src.0 as usize]);
+                let n = self.pop_index();
+                let s = self.pop_index();
+                let d = self.pop_index();
+                if dst == src {
+                    store.memory_mut(dst).copy_within(d, s, n)?;
+                } else {
+                    let bytes = store.memory(src).bytes(s, n)?;
+                    store.memory_mut(dst).write(d, &bytes)?;
+                }
+            }
+            Instr::MemoryFill(idx) => {
+                let mem = data.mems[idx.0 as usize];
+                let n = self.pop_index();
+                let v = self.pop_i32();
+                let d = self.pop_index();
+                store.memory_mut(mem).fill(d, v as u8, n)?;
+            }
+
+            Instr::I32Const(v) => self.push(Value::I32(*v)),
+            Instr::I64Const(v) => self.push(Value::I64(*v)),
+            Instr::F32Const(v) => self.push(Value::F32(*v)),
+            Instr::F64Const(v) => self.push(Value::F64(*v)),
+            Instr::Numeric(op) => numeric::eval(*op, &mut self.stack)?,
+
+            Instr::Atomic(op, memarg) => {
+                let mem = data.mems[memarg.memidx().0 as usize];
+                self.atomic(store, mem, *op, memarg)?;
+            }
+            Instr::AtomicFence => {}
+
+            Instr::Simd(op, imm) if op.has_mem_arg() => {
+                let (SimdImm::MemArg(memarg) | SimdImm::MemArgLane(memarg, _)) = imm else {
+                    panic!("expected a memarg for {}, found {:?}", op.name(), imm);
+                };
+                let mem = data.mems[memarg.memidx().0 as usize];
+                self.simd_memory(store, mem, *op, *imm)?;
+            }
+            Instr::Simd(op, imm) => simd::eval(*op, *imm, &mut self.stack),
+
+            // The GC proposal's heap types
+            instr => return Err(Trap::new(TrapKind::Unsupported(instr.to_string()))),
+        }
+        Ok(())
+    }
+
+    fn frame(&self) -> &Frame {
+        &self.frames[self.frames.len() - 1]
+    }
+
+    fn jump(&mut self, pc: usize) {
+        let last = self.frames.len() - 1;
+        self.frames[last].pc = pc;
+    }
+
+    fn push(&mut self, v: Value) {
+        self.stack.push(v);
+    }
+
+    // Validation guarantees that operands are there and of the right type,
+    // so a mismatch is a bug in the interpreter rather than in the module
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    fn pop_i32(&mut self) -> i32 {
+        match self.pop() {
+            Value::I32(v) => v,
+            v => panic!("expected an i32 operand, found {}", v),
+        }
+    }
+
+    fn pop_v128(&mut self) -> u128 {
+        match self.pop() {
+            Value::V128(v) => v,
+            v => panic!("expected a v128 operand, found {}", v),
+        }
+    }
+
+    fn pop_ref(&mut self) -> Ref {
+        match self.pop() {
+            Value::Ref(r) => r,
+            v => panic!("expected a reference operand, found {}", v),
+        }
+    }
+
+    /// Pops an address, index or length, which is an `i64` for 64-bit
+    /// memories and tables.
+    fn pop_index(&mut self) -> u64 {
+        match self.pop() {
+            Value::I32(v) => u64::from(v as u32),
+            Value::I64(v) => v as u64,
+            v => panic!("expected an index operand, found {}", v),
+        }
+    }
+
+    fn push_index(&mut self, is_64: bool, v: i64) {
+        self.push(if is_64 {
+            Value::I64(v)
+        } else {
+            Value::I32(v as i32)
+        });
+    }
+
+    fn pop_func_ref(&mut self) -> Result<FuncAddr, Trap> {
+        match self.pop_ref() {
+            Ref::Func(func) => Ok(func),
+            _ => Err(Trap::new(TrapKind::NullFunctionReference)),
+        }
+    }
+
+    fn effective_address(&mut self, memarg: &MemArg) -> Result<u64, Trap> {
+        let addr = self.pop_index();
+        Ok(addr
+            .checked_add(memarg.offset())
+            .ok_or(TrapKind::MemoryOutOfBounds)?)
+    }
+
+    /// Opens the block starting at `pc`.
+    fn enter(&mut self, body: &Body, kind: LabelKind, pc: usize) {
+        let height = self.stack.len() - body.block(pc).params;
+        self.labels.push(Label {
+            kind,
+            start: pc,
+            height,
+        });
+    }
+
+    /// Branches to the label `l`, which returns from the function if it is
+    /// the function's own.
+    fn branch(&mut self, l: LabelIdx) {
+        let depth = l.0 as usize;
+        let frame = self.frame();
+        if depth >= self.labels.len() - frame.label_base {
+            self.ret();
+            return;
+        }
+        let i = self.labels.len() - 1 - depth;
+        let label = self.labels[i];
+        let block = frame.body.block(label.start);
+        let (arity, pc, keep) = if label.kind == LabelKind::Loop {
+            (block.params, label.start + 1, i + 1)
+        } else {
+            (block.results, block.end + 1, i)
+        };
+        let top = self.stack.len() - arity;
+        self.stack.drain(label.height..top);
+        self.labels.truncate(keep);
+        self.jump(pc);
+    }
+
+    /// Returns from the current function, leaving its results on the stack.
+    fn ret(&mut self) {
+        let frame = self.frames.pop().expect("no frame to return from");
+        let top = self.stack.len() - frame.arity;
+        self.stack.drain(frame.base..top);
+        self.labels.truncate(frame.label_base);
+    }
+
+    /// Calls `func`, whose arguments are on top of the stack. Host
+    /// functions run to completion right away.
+    fn call(&mut self, store: &mut Store, func: FuncAddr, replaced: u32) -> Result<(), Trap> {
+        match store.func(func).kind {
+            FuncKind::Wasm { .. } => self.push_frame(store, func, replaced),
+            FuncKind::Host(_) => {
+                let instance = self.frames.last().map(|frame| Instance(frame.instance));
+                self.call_host(store, func, instance)
+            }
+        }
+    }
+
+    /// Calls the host function `func` on behalf of `instance`.
+    fn call_host(
+        &mut self,
+        store: &mut Store,
+        func: FuncAddr,
+        instance: Option<Instance>,
+    ) -> Result<(), Trap> {
+        let f = store.func(func);
+        let FuncKind::Host(host) = &f.kind else {
+            unreachable!("only host functions are called directly");
+        };
+        let host = host.clone();
+        let results = f.ty().results().to_vec();
+        let args = self
+            .stack
+            .split_off(self.stack.len() - f.ty().params().len());
+        let values = host(&mut Caller::new(store, instance), &args)?;
+        if values.len() != results.len()
+            || values.iter().zip(&results).any(|(v, t)| !v.has_type(*t))
+        {
+            return Err(Trap::new(TrapKind::Host(
+                "host function returned results of the wrong type".to_string(),
+            )));
+        }
+        self.stack.extend(values);
+        Ok(())
+    }
+
+    /// Pushes a frame for a call of the module function `func`.
+    fn push_frame(&mut self, store: &Store, func: FuncAddr, replaced: u32) -> Result<(), Trap> {
+        let f = store.func(func);
+        let FuncKind::Wasm { instance, body, .. } = &f.kind else {
+            unreachable!("host functions have no frames");
+        };
+        let body = body.clone();
+        let base = self.stack.len() - f.ty().params().len();
+        if self.frames.len() >= MAX_FRAMES || base + body.locals.len() > MAX_VALUES {
+            return Err(Trap::new(TrapKind::StackExhausted));
+        }
+        self.stack
+            .extend(body.locals.iter().map(|t| Value::default_of(*t)));
+        self.frames.push(Frame {
+            func,
+            instance: *instance,
+            body,
+            pc: 0,
+            base,
+            locals: self.stack.len() - base,
+            label_base: self.labels.len(),
+            arity: f.ty().results().len(),
+            replaced,
+        });
+        Ok(())
+    }
+
+    /// Replaces the current frame with a call of `func`.
+    fn tail_call(&mut self, store: &mut Store, func: FuncAddr) -> Result<(), Trap> {
+        let frame = self.frames.pop().expect("no frame to call from");
+        let n = store.func(func).ty().params().len();
+        let top = self.stack.len() - n;
+        self.stack.drain(frame.base..top);
+        self.labels.truncate(frame.label_base);
+        // A host function still sees the instance that made the call
+        match store.func(func).kind {
+            FuncKind::Wasm { .. } => self.push_frame(store, func, frame.replaced.saturating_add(1)),
+            FuncKind::Host(_) => self.call_host(store, func, Some(Instance(frame.instance))),
+        }
+    }
+
+    /// Pops a table index and finds the function it refers to, checking
+    /// that it has type `ty`.
+    fn indirect(
+        &mut self,
+        store: &Store,
+        instance: usize,
+        ty: TypeIdx,
+        table: TableAddr,
+    ) -> Result<FuncAddr, Trap> {
+        let i = self.pop_index();
+        let func = match store.table(table).get(i) {
+            Ok(Ref::Func(func)) => func,
+            Ok(_) => return Err(Trap::new(TrapKind::UninitializedElement)),
+            Err(_) => return Err(Trap::new(TrapKind::UndefinedElement)),
+        };
+        let expected = store.instances[instance].types[ty.0 as usize].as_ref();
+        if expected != Some(store.func(func).ty()) {
+            return Err(Trap::new(TrapKind::IndirectCallTypeMismatch));
+        }
+        Ok(func)
+    }
+
+    /// Unwinds to the innermost handler of `exn`, or traps if there is none.
+    fn throw(&mut self, store: &Store, exn: ExnAddr) -> Result<(), Trap> {
+        self.thrown = Some(exn);
+        let tag = store.exception(exn).tag();
+        let fields = store.exception(exn).fields();
+        // Handlers are searched for below this label
+        let mut next = self.labels.len();
+        // The frames unwound so far, for the backtrace if nothing catches
+        let mut unwound = Vec::new();
+        while let Some(frame) = self.frames.last() {
+            let body = frame.body.clone();
+            let tags = &store.instances[frame.instance].tags;
+            let label_base = frame.label_base;
+            while next > label_base {
+                let i = next - 1;
+                let label = self.labels[i];
+                let block = body.block(label.start);
+                match label.kind {
+                    LabelKind::Try => {
+                        for &clause in &block.clauses {
+                            let caught = match &body.instrs[clause] {
+                                Instr::Catch(idx) => tags[idx.0 as usize] == tag,
+                                _ => true,
+                            };
+                            if caught {
+                                self.stack.truncate(label.height);
+                                if let Instr::Catch(_) = &body.instrs[clause] {
+                                    self.stack.extend_from_slice(fields);
+                                }
+                                self.labels.truncate(i + 1);
+                                self.labels[i].kind = LabelKind::Catch(exn);
+                                self.jump(clause + 1);
+                                return Ok(());
+                            }
+                        }
+                        // Carry on from the label `delegate` names, which
+                        // may be the function's, i.e. the caller's handlers
+                        if let Instr::Delegate(l) = &body.instrs[block.end] {
+                            next = (i - label_base)
+                                .checked_sub(l.0 as usize)
+                                .map_or(label_base, |n| label_base + n);
+                            continue;
+                        }
+                    }
+                    LabelKind::TryTable => {
+                        let Instr::TryTable(_, catches) = &body.instrs[label.start] else {
+                            unreachable!("try_table label without a try_table");
+                        };
+                        let catch = catches
+                            .iter()
+                            .find(|c| c.tag().is_none_or(|idx| tags[idx.0 as usize] == tag));
+                        if let Some(catch) = catch {
+                            self.stack.truncate(label.height);
+                            if catch.tag().is_some() {
+                                self.stack.extend_from_slice(fields);
+                            }
+                            if catch.is_ref() {
+                                self.push(Value::Ref(Ref::Exn(exn)));
+                            }
+                            // Catch labels are relative to the try_table's
+                            // surroundings
+                            self.labels.truncate(i);
+                            self.branch(catch.label());
+                            return Ok(());
+                        }
+                    }
+                    _ => {}
+                }
+                next = i;
+            }
+            let frame = self.frames.pop().expect("a frame to unwind");
+            unwound.push(frame.trace(store, frame.pc.saturating_sub(1)));
+            self.stack.truncate(frame.base);
+            self.labels.truncate(frame.label_base);
+            next = self.labels.len();
+        }
+        Err(Trap::new(TrapKind::UncaughtException).with_frames(unwound))
+    }
+
+    /// Runs a vector instruction that loads from or stores to `mem`.
+    fn simd_memory(
+        &mut self,
+        store: &mut Store,
+        mem: MemAddr,
+        op: SimdOp,
+        imm: SimdImm,
+    ) -> Result<(), Trap> {
+        let (SimdImm::MemArg(memarg) | SimdImm::MemArgLane(memarg, _)) = imm else {
+            panic!("expected a memarg for {}, found {:?}", op.name(), imm);
+        };
+        let lane = match imm {
+            SimdImm::MemArgLane(_, lane) => lane as usize,
+            _ => 0,
+        };
+        let opcode = op.opcode();
+        match opcode {
+            0x0B => {
+                let v = self.pop_v128();
+                let addr = self.effective_address(&memarg)?;
+                store.memory_mut(mem).write(addr, &v.to_le_bytes())?;
+            }
+            // The lane's bytes go to or come from memory as they are
+            0x54..=0x57 => {
+                let width = 1 << (opcode - 0x54);
+                let mut bytes = self.pop_v128().to_le_bytes();
+                let addr = self.effective_address(&memarg)?;
+                store
+                    .memory(mem)
+                    .read(addr, &mut bytes[lane * width..(lane + 1) * width])?;
+                self.push(Value::V128(u128::from_le_bytes(bytes)));
+            }
+            0x58..=0x5B => {
+                let width = 1 << (opcode - 0x58);
+                let bytes = self.pop_v128().to_le_bytes();
+                let addr = self.effective_address(&memarg)?;
+                store
+                    .memory_mut(mem)
+                    .write(addr, &bytes[lane * width..(lane + 1) * width])?;
+            }
+            _ => {
+                let addr = self.effective_address(&memarg)?;
+                let v = simd::load(store.memory(mem), op, addr)?;
+                self.push(Value::V128(v));
+            }
+        }
+        Ok(())
+    }
+
+    fn atomic(
+        &mut self,
+        store: &mut Store,
+        mem: MemAddr,
+        op: AtomicOp,
+        memarg: &MemArg,
+    ) -> Result<(), Trap> {
+        let opcode = op.opcode();
+        let (_, _, size) = atomic_type(op);
+        let is_i64 = op.name().starts_with("i64");
+        let width = size as usize;
+        let mask = if width == 8 {
+            u64::MAX
+        } else {
+            (1u64 << (width * 8)) - 1
+        };
+        let to_value = |v: u64| {
+            if is_i64 {
+                Value::I64(v as i64)
+            } else {
+                Value::I32(v as u32 as i32)
+            }
+        };
+        let operand = |v: Value| match v {
+            Value::I32(v) => u64::from(v as u32),
+            Value::I64(v) => v as u64,
+            v => panic!("expected an integer operand, found {}", v),
+        };
+        let operands = match opcode {
+            0x00 | 0x17..=0x47 => 1,
+            0x01 | 0x02 | 0x48..=0x4E => 2,
+            _ => 0,
+        };
+        let mut args = self.stack.split_off(self.stack.len() - operands);
+        let addr = self.effective_address(memarg)?;
+        if addr % u64::from(size) != 0 {
+            return Err(Trap::new(TrapKind::UnalignedAtomic));
+        }
+        let memory = store.memory_mut(mem);
+        let mut buf = [0; 8];
+        memory.read(addr, &mut buf[..width])?;
+        let old = u64::from_le_bytes(buf);
+        let new = match opcode {
+            // No other thread can be waiting
+            0x00 => {
+                self.push(Value::I32(0));
+                return Ok(());
+            }
+            0x01 | 0x02 => return Err(Trap::new(TrapKind::Unsupported(op.name().to_string()))),
+            0x10..=0x16 => None,
+            0x17..=0x1D => Some(operand(args[0])),
+            0x48..=0x4E => {
+                let replacement = operand(args.pop().expect("the replacement"));
+                let expected = operand(args[0]) & mask;
+                (old == expected).then_some(replacement)
+            }
+            _ => {
+                let v = operand(args[0]);
+                Some(match (opcode - 0x1E) / 7 {
+                    0 => old.wrapping_add(v),
+                    1 => old.wrapping_sub(v),
+                    2 => old & v,
+                    3 => old | v,
+                    4 => old ^ v,
+                    _ => v,
+                })
+            }
+        };
+        if let Some(new) = new {
+            memory.write(addr, &(new & mask).to_le_bytes()[..width])?;
+        }
+        if !(0x17..=0x1D).contains(&opcode) {
+            self.push(to_value(old));
+        }
+        Ok(())
+    }
+}
+
+fn load(store: &Store, mem: MemAddr, op: LoadOp, addr: u64) -> Result<Value, TrapKind> {
+    let memory = store.memory(mem);
+    Ok(match op {
+        LoadOp::I32Load => Value::I32(i32::from_le_bytes(memory.load(addr)?)),
+        LoadOp::I64Load => Value::I64(i64::from_le_bytes(memory.load(addr)?)),
+        LoadOp::F32Load => Value::F32(f32::from_le_bytes(memory.load(addr)?)),
+        LoadOp::F64Load => Value::F64(f64::from_le_bytes(memory.load(addr)?)),
+        LoadOp::I32Load8S => Value::I32(i8::from_le_bytes(memory.load(addr)?) as i32),
+        LoadOp::I32Load8U => Value::I32(u8::from_le_bytes(memory.load(addr)?) as i32),
+        LoadOp::I32Load16S => Value::I32(i16::from_le_bytes(memory.load(addr)?) as i32),
+        LoadOp::I32Load16U => Value::I32(u16::from_le_bytes(memory.load(addr)?) as i32),
+        LoadOp::I64Load8S => Value::I64(i8::from_le_bytes(memory.load(addr)?) as i64),
+        LoadOp::I64Load8U => Value::I64(u8::from_le_bytes(memory.load(addr)?) as i64),
+        LoadOp::I64Load16S => Value::I64(i16::from_le_bytes(memory.load(addr)?) as i64),
+        LoadOp::I64Load16U => Value::I64(u16::from_le_bytes(memory.load(addr)?) as i64),
+        LoadOp::I64Load32S => Value::I64(i32::from_le_bytes(memory.load(addr)?) as i64),
+        LoadOp::I64Load32U => Value::I64(u32::from_le_bytes(memory.load(addr)?) as i64),
+    })
+}
+
+fn store_bytes(op: StoreOp, v: Value) -> Vec<u8> {
+    let bytes = match v {
+        Value::I32(v) => v.to_le_bytes().to_vec(),
+        Value::I64(v) => v.to_le_bytes().to_vec(),
+        Value::F32(v) => v.to_le_bytes().to_vec(),
+        Value::F64(v) => v.to_le_bytes().to_vec(),
+        v => panic!("expected a number to store, found {}", v),
+    };
+    let (_, size) = store_type(op);
+    bytes[..size as usize].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::exec::Execution;
+    use crate::runtime::imports::Imports;
+    use crate::runtime::instance::Instance;
+    use crate::runtime::store::{FuelCosts, Store};
+    use crate::runtime::trap::TrapKind;
+    use crate::runtime::value::Value;
+    use crate::types::primitives::FuncIdx;
+    use crate::wat::parse::parse_module;
+
+    fn instantiate(text: &str) -> (Store, Instance) {
+        let module = parse_module(text).expect("The module");
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, &module, &Imports::new()).expect("The instance");
+        (store, instance)
+    }
+
+    fn call(text: &str, name: &str, args: &[Value]) -> Result<Vec<Value>, TrapKind> {
+        let (mut store, instance) = instantiate(text);
+        instance
+            .invoke(&mut store, name, args)
+            .map_err(|trap| trap.kind().clone())
+    }
+
+    #[test]
+    fn test_calls_and_loops() {
+        let text = r#"
+            (func $fac (export "fac") (param i64) (result i64)
+              (if (result i64) (i64.eqz (local.get 0))
+                (then (i64.const 1))
+                (else (i64.mul (local.get 0) (call $fac (i64.sub (local.get 0) (i64.const 1)))))))
+            (func (export "sum") (param i32) (result i32) (local i32)
+              (block $done
+                (loop $next
+                  (br_if $done (i32.eqz (local.get 0)))
+                  (local.set 1 (i32.add (local.get 1) (local.get 0)))
+                  (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                  (br $next)))
+              (local.get 1))
+            (func (export "pick") (param i32) (result i32)
+              (block (block (block (br_table 0 1 2 (local.get 0)))
+                  (return (i32.const 10)))
+                (return (i32.const 11)))
+              (i32.const 12))
+            (func (export "multi") (param i32 i32) (result i32 i32)
+              (local.get 0) (local.get 1)
+              (block (param i32 i32) (result i32 i32) (br 0)))
+        "#;
+        assert_eq!(
+            call(text, "fac", &[Value::I64(20)]),
+            Ok(vec![Value::I64(2432902008176640000)])
+        );
+        assert_eq!(
+            call(text, "sum", &[Value::I32(100)]),
+            Ok(vec![Value::I32(5050)])
+        );
+        for (i, expected) in [(0, 10), (1, 11), (2, 12), (99, 12)] {
+            assert_eq!(
+                call(text, "pick", &[Value::I32(i)]),
+                Ok(vec![Value::I32(expected)])
+            );
+        }
+        assert_eq!(
+            call(text, "multi", &[Value::I32(1), Value::I32(2)]),
+            Ok(vec![Value::I32(1), Value::I32(2)])
+        );
+        assert!(matches!(
+            call(text, "fac", &[Value::I32(1)]),
+            Err(TrapKind::Host(_))
+        ));
+    }
+
+    #[test]
+    fn test_traps() {
+        let text = r#"
+            (type $v (func))
+            (type $i (func (result i32)))
+            (table 3 funcref)
+            (elem (i32.const 0) $f $g)
+            (memory 1)
+            (func $f)
+            (func $g (result i32) (i32.const 7))
+            (func (export "unreachable") unreachable)
+            (func (export "div") (param i32) (result i32) (i32.div_u (i32.const 1) (local.get 0)))
+            (func (export "load") (param i32) (result i32) (i32.load offset=4 (local.get 0)))
+            (func (export "indirect") (param i32) (result i32)
+              (call_indirect (type $i) (local.get 0)))
+            (func $loop (export "recurse") (call $loop))
+        "#;
+        assert_eq!(call(text, "unreachable", &[]), Err(TrapKind::Unreachable));
+        assert_eq!(
+            call(text, "div", &[Value::I32(0)]),
+            Err(TrapKind::IntegerDivideByZero)
+        );
+        assert_eq!(
+            call(text, "load", &[Value::I32(65528)]),
+            Ok(vec![Value::I32(0)])
+        );
+        assert_eq!(
+            call(text, "load", &[Value::I32(65529)]),
+            Err(TrapKind::MemoryOutOfBounds)
+        );
+        assert_eq!(
+            call(text, "load", &[Value::I32(-1)]),
+            Err(TrapKind::MemoryOutOfBounds)
+        );
+        assert_eq!(
+            call(text, "indirect", &[Value::I32(1)]),
+            Ok(vec![Value::I32(7)])
+        );
+        assert_eq!(
+            call(text, "indirect", &[Value::I32(0)]),
+            Err(TrapKind::IndirectCallTypeMismatch)
+        );
+        assert_eq!(
+            call(text, "indirect", &[Value::I32(2)]),
+            Err(TrapKind::UninitializedElement)
+        );
+        assert_eq!(
+            call(text, "indirect", &[Value::I32(3)]),
+            Err(TrapKind::UndefinedElement)
+        );
+        assert_eq!(call(text, "recurse", &[]), Err(TrapKind::StackExhausted));
+    }
+
+    #[test]
+    fn test_memory_and_tables() {
+        let text = r#"
+            (memory 1 2)
+            (data (i32.const 8) "\01\02\03\04")
+            (table 1 funcref)
+            (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+            (func (export "size") (result i32) (memory.size))
+            (func (export "load16") (result i32) (i32.load16_s (i32.const 9)))
+            (func (export "copy") (result i64)
+              (memory.copy (i32.const 0) (i32.const 8) (i32.const 4))
+              (i64.load (i32.const 0)))
+            (func (export "table") (result i32)
+              (drop (table.grow (ref.null func) (i32.const 2)))
+              (table.size))
+        "#;
+        let (mut store, instance) = instantiate(text);
+        let mut invoke = |name, args: &[Value]| instance.invoke(&mut store, name, args).unwrap();
+        assert_eq!(invoke("load16", &[]), vec![Value::I32(0x0302)]);
+        assert_eq!(invoke("copy", &[]), vec![Value::I64(0x04030201)]);
+        assert_eq!(invoke("grow", &[Value::I32(1)]), vec![Value::I32(1)]);
+        assert_eq!(invoke("grow", &[Value::I32(1)]), vec![Value::I32(-1)]);
+        assert_eq!(invoke("size", &[]), vec![Value::I32(2)]);
+        assert_eq!(invoke("table", &[]), vec![Value::I32(3)]);
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let text = r#"
+            (tag $e (param i32))
+            (tag $f)
+            (func $throw (param i32) (throw $e (local.get 0)))
+            (func (export "try_table") (param i32) (result i32)
+              (block $caught (result i32)
+                (try_table (catch $e $caught) (call $throw (local.get 0)))
+                (i32.const -1)))
+            (func (export "legacy") (result i32)
+              (try (result i32)
+                (do (try (do (call $throw (i32.const 5))) (delegate 0)) (i32.const 0))
+                (catch $f (i32.const 1))
+                (catch $e (i32.add (i32.const 100)))))
+            (func (export "rethrow") (result i32)
+              (try (result i32)
+                (do (try (do (throw $f)) (catch_all (rethrow 0))) (i32.const 0))
+                (catch $f (i32.const 2))))
+            (func (export "uncaught") (throw $f))
+        "#;
+        assert_eq!(
+            call(text, "try_table", &[Value::I32(3)]),
+            Ok(vec![Value::I32(3)])
+        );
+        assert_eq!(call(text, "legacy", &[]), Ok(vec![Value::I32(105)]));
+        assert_eq!(call(text, "rethrow", &[]), Ok(vec![Value::I32(2)]));
+        assert_eq!(
+            call(text, "uncaught", &[]),
+            Err(TrapKind::UncaughtException)
+        );
+    }
+
+    #[test]
+    fn test_backtraces() {
+        let text = r#"
+            (tag $e)
+            (func $inner (param i32) (result i32) (i32.div_u (i32.const 1) (local.get 0)))
+            (func $outer (export "outer") (param i32) (result i32)
+              (i32.add (call $inner (local.get 0)) (i32.const 1)))
+            (func (export "uncaught") (call $throw))
+            (func $throw (throw $e))
+            (@custom "name" "\01\0f\02\00\05inner\01\05outer")
+        "#;
+        let (mut store, instance) = instantiate(text);
+        let trap = instance
+            .invoke(&mut store, "outer", &[Value::I32(0)])
+            .unwrap_err();
+        assert_eq!(trap.kind(), &TrapKind::IntegerDivideByZero);
+        assert_eq!(trap.func(), Some(FuncIdx(0)));
+        let frames = trap.backtrace();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].name(), Some("inner"));
+        assert_eq!(frames[1].name(), Some("outer"));
+        assert!(frames[0].offset() < frames[1].offset());
+        assert!(frames[0].to_string().starts_with("$inner (func 0) at 0x"));
+
+        // Unwinding keeps the frames the exception passed through
+        let trap = instance.invoke(&mut store, "uncaught", &[]).unwrap_err();
+        let funcs: Vec<_> = trap.backtrace().iter().map(|frame| frame.func()).collect();
+        assert_eq!(funcs, [FuncIdx(3), FuncIdx(2)]);
+        assert_eq!(
+            trap.backtrace()[0].to_string().split(" at ").next(),
+            Some("func 3")
+        );
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        let text = r#"
+            (func $count (export "count") (param i64) (result i64)
+              (if (result i64) (i64.eqz (local.get 0))
+                (then (i64.const 42))
+                (else (return_call $count (i64.sub (local.get 0) (i64.const 1))))))
+        "#;
+        assert_eq!(
+            call(text, "count", &[Value::I64(1_000_000)]),
+            Ok(vec![Value::I64(42)])
+        );
+
+        let (mut store, instance) = instantiate(text);
+        let func = instance.func(&store, "count").unwrap();
+        let mut execution = super::Execution::new(&store, func, &[Value::I64(3)]).unwrap();
+        let mut deepest = 0;
+        while !execution.is_finished() {
+            assert_eq!(execution.frames().len(), 1);
+            deepest = deepest.max(execution.frames()[0].replaced());
+            execution.step(&mut store).unwrap();
+        }
+        assert_eq!(deepest, 3);
+    }
+
+    #[test]
+    fn test_fuel() {
+        let text = r#"
+            (func (export "spin") (loop (br 0)))
+            (func (export "sum") (param i32) (result i32) (local i32)
+              (block
+                (loop
+                  (br_if 1 (i32.eqz (local.get 0)))
+                  (local.set 1 (i32.add (local.get 1) (local.get 0)))
+                  (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                  (br 0)))
+              (local.get 1))
+        "#;
+        let (mut store, instance) = instantiate(text);
+        store.set_fuel(Some(1000));
+        assert_eq!(
+            instance
+                .invoke(&mut store, "spin", &[])
+                .map_err(|trap| trap.kind().clone()),
+            Err(TrapKind::OutOfFuel)
+        );
+        assert_eq!(store.fuel(), Some(0));
+
+        // Running out pauses a stepped execution, which goes on with more
+        let sum = instance.func(&store, "sum").unwrap();
+        store.set_fuel(Some(10));
+        let mut execution = Execution::new(&store, sum, &[Value::I32(10)]).unwrap();
+        let trap = execution.run_for(&mut store, u64::MAX).unwrap_err();
+        assert_eq!(trap.kind(), &TrapKind::OutOfFuel);
+        assert_eq!(execution.steps(), 10);
+        store.set_fuel(Some(1_000));
+        execution.run_for(&mut store, 5).unwrap();
+        assert_eq!(execution.steps(), 15);
+        assert_eq!(execution.run(&mut store), Ok(vec![Value::I32(55)]));
+
+        let mut costs = FuelCosts::new(0);
+        costs.set("i32.add", 10).unwrap();
+        assert_eq!(
+            costs.set("i32.plus", 1).unwrap_err().to_string(),
+            "unknown instruction i32.plus"
+        );
+        store.set_fuel_costs(costs);
+        store.set_fuel(Some(100));
+        assert_eq!(
+            instance.invoke(&mut store, "sum", &[Value::I32(10)]),
+            Ok(vec![Value::I32(55)])
+        );
+        assert_eq!(store.fuel(), Some(0));
+    }
+}
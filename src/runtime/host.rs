@@ -0,0 +1,177 @@
+use std::rc::Rc;
+
+use crate::runtime::instance::Instance;
+use crate::runtime::memory::Memory;
+use crate::runtime::store::{Extern, HostFunc, Store};
+use crate::runtime::trap::{Trap, TrapKind};
+use crate::runtime::value::Value;
+use crate::types::func_type::FuncType;
+use crate::types::num_type::NumType;
+use crate::types::val_type::ValType;
+
+/// What a host function sees of its caller: the store, and the instance
+/// whose code made the call, if the call didn't come from the host.
+pub struct Caller<'a> {
+    store: &'a mut Store,
+    instance: Option<Instance>,
+}
+
+impl<'a> Caller<'a> {
+    pub(crate) fn new(store: &'a mut Store, instance: Option<Instance>) -> Caller<'a> {
+        Caller { store, instance }
+    }
+
+    pub fn store(&mut self) -> &mut Store {
+        self.store
+    }
+
+    pub fn instance(&self) -> Option<Instance> {
+        self.instance
+    }
+
+    /// The calling instance's memory 0, whether it defines or imports it.
+    pub fn memory(&mut self) -> Option<&mut Memory> {
+        let instance = self.instance?;
+        let mem = *instance.data(self.store).mems.first()?;
+        Some(self.store.memory_mut(mem))
+    }
+
+    /// The calling instance's export called `name`.
+    pub fn export(&self, name: &str) -> Option<Extern> {
+        self.instance?.export(self.store, name)
+    }
+}
+
+/// A Rust type that host functions can take and return as a value of the
+/// corresponding value type. Unsigned integers stand for the same bits as
+/// the signed ones.
+pub trait WasmType: Sized {
+    fn val_type() -> ValType;
+    fn from_value(value: Value) -> Option<Self>;
+    fn into_value(self) -> Value;
+}
+
+macro_rules! wasm_type {
+    ($t:ty, $variant:ident, $num:ident) => {
+        impl WasmType for $t {
+            fn val_type() -> ValType {
+                ValType::Num(NumType::$num)
+            }
+
+            #[allow(clippy::unnecessary_cast)]
+            fn from_value(value: Value) -> Option<$t> {
+                match value {
+                    Value::$variant(v) => Some(v as $t),
+                    _ => None,
+                }
+            }
+
+            #[allow(clippy::unnecessary_cast)]
+            fn into_value(self) -> Value {
+                Value::$variant(self as _)
+            }
+        }
+
+        impl WasmResults for $t {
+            fn val_types() -> Vec<ValType> {
+                vec![<$t>::val_type()]
+            }
+
+            fn into_values(self) -> Result<Vec<Value>, Trap> {
+                Ok(vec![self.into_value()])
+            }
+        }
+    };
+}
+
+/// What a host function can return: nothing, a value, a tuple of values,
+/// or any of those or a trap.
+pub trait WasmResults {
+    fn val_types() -> Vec<ValType>;
+    fn into_values(self) -> Result<Vec<Value>, Trap>;
+}
+
+wasm_type!(i32, I32, I32);
+wasm_type!(i64, I64, I64);
+wasm_type!(u32, I32, I32);
+wasm_type!(u64, I64, I64);
+wasm_type!(f32, F32, F32);
+wasm_type!(f64, F64, F64);
+
+macro_rules! wasm_results {
+    ($($t:ident),*) => {
+        impl<$($t: WasmType),*> WasmResults for ($($t,)*) {
+            fn val_types() -> Vec<ValType> {
+                vec![$($t::val_type()),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_values(self) -> Result<Vec<Value>, Trap> {
+                let ($($t,)*) = self;
+                Ok(vec![$($t.into_value()),*])
+            }
+        }
+    };
+}
+
+wasm_results!();
+wasm_results!(A, B);
+wasm_results!(A, B, C);
+wasm_results!(A, B, C, D);
+
+impl<R: WasmResults> WasmResults for Result<R, Trap> {
+    fn val_types() -> Vec<ValType> {
+        R::val_types()
+    }
+
+    fn into_values(self) -> Result<Vec<Value>, Trap> {
+        self?.into_values()
+    }
+}
+
+/// A Rust closure that can be made into a host function, taking a
+/// [`Caller`] followed by parameters of [`WasmType`]s. `Params` is the
+/// tuple of parameter types, which tells the implementations for different
+/// numbers of parameters apart.
+///
+/// Rust doesn't infer closure parameter types through this trait, so they
+/// need annotations: `|caller: &mut Caller<'_>, x: i32| ...`.
+pub trait IntoFunc<Params, Results> {
+    fn into_func(self) -> (FuncType, HostFunc);
+}
+
+macro_rules! into_func {
+    ($($t:ident),*) => {
+        impl<H, $($t: WasmType,)* R: WasmResults> IntoFunc<($($t,)*), R> for H
+        where
+            H: Fn(&mut Caller<'_>, $($t),*) -> R + 'static,
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_func(self) -> (FuncType, HostFunc) {
+                let ty = FuncType::new(vec![$($t::val_type()),*], R::val_types());
+                let f = move |caller: &mut Caller<'_>, args: &[Value]| {
+                    let mut args = args.iter();
+                    $(
+                        let $t = args.next().copied().and_then($t::from_value).ok_or_else(|| {
+                            Trap::new(TrapKind::Host("host function argument mismatch".to_string()))
+                        })?;
+                    )*
+                    self(caller, $($t),*).into_values()
+                };
+                (ty, Rc::new(f))
+            }
+        }
+    };
+}
+
+into_func!();
+into_func!(A);
+into_func!(A, B);
+into_func!(A, B, C);
+into_func!(A, B, C, D);
+into_func!(A, B, C, D, E);
+into_func!(A, B, C, D, E, F);
+into_func!(A, B, C, D, E, F, G);
+into_func!(A, B, C, D, E, F, G, I);
+into_func!(A, B, C, D, E, F, G, I, J);
+into_func!(A, B, C, D, E, F, G, I, J, K);
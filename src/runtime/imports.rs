@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use crate::runtime::instance::Instance;
+use crate::runtime::store::{Extern, Store};
+use crate::types::func_type::FuncType;
+use crate::types::import_desc::ImportDesc;
+use crate::types::limits::Limits;
+use crate::types::val_type::ValType;
+
+/// The externs provided for a module's imports, by the module and field
+/// names an import section refers to them by.
+#[derive(Default)]
+pub struct Imports {
+    externs: HashMap<(String, String), Extern>,
+}
+
+impl Imports {
+    pub fn new() -> Imports {
+        Imports::default()
+    }
+
+    /// Provides `ext` for imports of `module`.`name`, replacing whatever was
+    /// provided before.
+    pub fn define(&mut self, module: &str, name: &str, ext: Extern) {
+        self.externs
+            .insert((module.to_string(), name.to_string()), ext);
+    }
+
+    /// Provides every export of `instance` as a field of `module`.
+    pub fn define_instance(&mut self, store: &Store, module: &str, instance: Instance) {
+        for (name, ext) in instance.exports(store) {
+            self.define(module, name, ext);
+        }
+    }
+
+    pub fn get(&self, module: &str, name: &str) -> Option<Extern> {
+        self.externs
+            .get(&(module.to_string(), name.to_string()))
+            .copied()
+    }
+}
+
+fn signature(ty: &FuncType) -> String {
+    let names = |types: &[ValType]| types.iter().map(ValType::name).collect::<Vec<_>>();
+    format!(
+        "[{}] -> [{}]",
+        names(ty.params()).join(" "),
+        names(ty.results()).join(" ")
+    )
+}
+
+// The limits of a memory or table of `size` with the declared `max` match
+// `limits` if it is at least as large and may grow no further
+fn limits_match(limits: &Limits, size: u64, max: Option<u64>) -> Result<(), String> {
+    if size < limits.min() {
+        return Err(format!(
+            "expected at least {}, found {}",
+            limits.min(),
+            size
+        ));
+    }
+    match (limits.max(), max) {
+        (Some(expected), Some(max)) if max > expected => Err(format!(
+            "expected a maximum of at most {}, found {}",
+            expected, max
+        )),
+        (Some(expected), None) => Err(format!(
+            "expected a maximum of at most {}, found none",
+            expected
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `ext` can be provided for an import described by `desc`,
+/// given the function types of the importing module, or says why not.
+pub(crate) fn check(
+    store: &Store,
+    desc: &ImportDesc,
+    ext: Extern,
+    types: &[Option<FuncType>],
+) -> Result<(), String> {
+    let func_type = |idx: u32| types.get(idx as usize).and_then(Option::as_ref);
+    match (desc, ext) {
+        (ImportDesc::Func(idx), Extern::Func(func)) => {
+            let actual = store.func(func).ty();
+            match func_type(idx.0) {
+                Some(expected) if expected == actual => Ok(()),
+                Some(expected) => Err(format!(
+                    "expected a function of type {}, found {}",
+                    signature(expected),
+                    signature(actual)
+                )),
+                None => Err("expected a function of a struct or array type".to_string()),
+            }
+        }
+        (ImportDesc::Table(ty), Extern::Table(table)) => {
+            let table = store.table(table);
+            if table.elem_type() != ty.elem_type() {
+                return Err(format!(
+                    "expected a table of {}, found {}",
+                    ty.elem_type(),
+                    table.elem_type()
+                ));
+            }
+            if table.is_64() != ty.limits().is_64() {
+                return Err("expected a table with other index type".to_string());
+            }
+            limits_match(ty.limits(), table.size(), table.max())
+                .map_err(|err| format!("table size: {}", err))
+        }
+        (ImportDesc::Mem(ty), Extern::Mem(memory)) => {
+            let memory = store.memory(memory);
+            if memory.is_64() != ty.is_64() {
+                return Err("expected a memory with other index type".to_string());
+            }
+            if memory.is_shared() != ty.limits().is_shared() {
+                return Err("expected a memory with other sharing".to_string());
+            }
+            limits_match(ty.limits(), memory.size(), memory.max())
+                .map_err(|err| format!("memory size: {}", err))
+        }
+        (ImportDesc::Global(ty), Extern::Global(global)) => {
+            let global = store.global(global);
+            if global.ty() != ty.val_type() || global.is_mutable() != ty.is_mutable() {
+                let describe = |t: ValType, mutable: bool| match mutable {
+                    true => format!("(mut {})", t.name()),
+                    false => t.name(),
+                };
+                return Err(format!(
+                    "expected a global of type {}, found {}",
+                    describe(ty.val_type(), ty.is_mutable()),
+                    describe(global.ty(), global.is_mutable())
+                ));
+            }
+            Ok(())
+        }
+        (ImportDesc::Tag(ty), Extern::Tag(tag)) => {
+            let actual = store.tag(tag).ty();
+            match func_type(ty.type_idx().0) {
+                Some(expected) if expected == actual => Ok(()),
+                Some(expected) => Err(format!(
+                    "expected a tag of type {}, found {}",
+                    signature(expected),
+                    signature(actual)
+                )),
+                None => Err("expected a tag of a struct or array type".to_string()),
+            }
+        }
+        (desc, ext) => Err(format!(
+            "expected a {}, found a {}",
+            kind(desc),
+            match ext {
+                Extern::Func(_) => "function",
+                Extern::Table(_) => "table",
+                Extern::Mem(_) => "memory",
+                Extern::Global(_) => "global",
+                Extern::Tag(_) => "tag",
+            }
+        )),
+    }
+}
+
+fn kind(desc: &ImportDesc) -> &'static str {
+    match desc {
+        ImportDesc::Func(_) => "function",
+        ImportDesc::Table(_) => "table",
+        ImportDesc::Mem(_) => "memory",
+        ImportDesc::Global(_) => "global",
+        ImportDesc::Tag(_) => "tag",
+    }
+}
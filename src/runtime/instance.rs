@@ -0,0 +1,557 @@
+use std::fmt::Display;
+
+use crate::encode::Encode;
+
+use crate::instr::Instr;
+use crate::instr::expr::Expr;
+use crate::instr::simd::{SimdImm, SimdOp};
+use crate::module::Module;
+use crate::runtime::exec::Body;
+use crate::runtime::imports::{self, Imports};
+use crate::runtime::memory::Memory;
+use crate::runtime::numeric;
+use crate::runtime::store::{
+    DataAddr, ElemAddr, Extern, Func, FuncAddr, Global, GlobalAddr, MemAddr, Store, TableAddr, Tag,
+    TagAddr,
+};
+use crate::runtime::table::Table;
+use crate::runtime::trap::{Trap, TrapKind};
+use crate::runtime::value::{Ref, Value};
+use crate::section::data::DataMode;
+use crate::section::dwarf::LineTable;
+use crate::section::element::{ElemInit, ElemMode};
+use crate::section::name::Names;
+use crate::types::export_desc::ExportDesc;
+use crate::types::func_type::FuncType;
+use crate::types::primitives::FuncIdx;
+use crate::validate::{ValidationError, validate};
+
+/// Why a module could not be instantiated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstantiationError {
+    Invalid(ValidationError),
+    /// An import nothing was provided for.
+    UnknownImport {
+        module: String,
+        name: String,
+    },
+    /// An import provided with something of the wrong kind or type.
+    IncompatibleImport {
+        module: String,
+        name: String,
+        reason: String,
+    },
+    /// A memory or table is larger than the host can allocate.
+    OutOfMemory,
+    /// Initializing a segment or running the start function trapped.
+    Trap(Trap),
+}
+
+impl From<Trap> for InstantiationError {
+    fn from(trap: Trap) -> Self {
+        InstantiationError::Trap(trap)
+    }
+}
+
+impl From<TrapKind> for InstantiationError {
+    fn from(kind: TrapKind) -> Self {
+        InstantiationError::Trap(Trap::new(kind))
+    }
+}
+
+impl Display for InstantiationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstantiationError::Invalid(err) => write!(f, "invalid module: {}", err),
+            InstantiationError::UnknownImport { module, name } => {
+                write!(f, "unknown import {}.{}", module, name)
+            }
+            InstantiationError::IncompatibleImport {
+                module,
+                name,
+                reason,
+            } => write!(
+                f,
+                "incompatible import type for {}.{}: {}",
+                module, name, reason
+            ),
+            InstantiationError::OutOfMemory => write!(f, "out of memory"),
+            InstantiationError::Trap(trap) => write!(f, "{}", trap),
+        }
+    }
+}
+
+/// The runtime counterpart of a module's index spaces: the address of each
+/// function, table and so on, imports first.
+#[derive(Default)]
+pub(crate) struct InstanceData {
+    /// The function type of each type index, or `None` for struct and array
+    /// types.
+    pub(crate) types: Vec<Option<FuncType>>,
+    pub(crate) funcs: Vec<FuncAddr>,
+    pub(crate) tables: Vec<TableAddr>,
+    pub(crate) mems: Vec<MemAddr>,
+    pub(crate) globals: Vec<GlobalAddr>,
+    pub(crate) tags: Vec<TagAddr>,
+    pub(crate) elems: Vec<ElemAddr>,
+    pub(crate) datas: Vec<DataAddr>,
+    pub(crate) exports: Vec<(String, Extern)>,
+    /// The name the instance was registered under in the store.
+    pub(crate) name: Option<String>,
+    /// The module's debug names and line tables, for symbolicating
+    /// backtraces.
+    pub(crate) names: Names,
+    pub(crate) lines: Option<LineTable>,
+    /// A hash of the module's types and code, which snapshots record so
+    /// that they are only restored into an instance of the same code.
+    pub(crate) code_hash: u64,
+}
+
+/// An instance of a module in a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instance(pub(crate) usize);
+
+impl Instance {
+    /// Instantiates `module` following the spec: resolves its imports from
+    /// `imports`, allocates its functions, tables, memories and globals,
+    /// initializes the tables and memories from the active segments, and
+    /// runs the start function.
+    pub fn new(
+        store: &mut Store,
+        module: &Module,
+        imports: &Imports,
+    ) -> Result<Instance, InstantiationError> {
+        let validated = validate(module).map_err(InstantiationError::Invalid)?;
+        let instance = store.instances.len();
+        let mut data = InstanceData {
+            types: module
+                .typesec
+                .iter()
+                .flat_map(|sec| sec.types())
+                .map(|sub| sub.comp_type().as_func().cloned())
+                .collect(),
+            code_hash: code_hash(module),
+            ..InstanceData::default()
+        };
+
+        // Nothing is allocated until every import checks out
+        for import in module.importsec.iter().flat_map(|sec| sec.imports()) {
+            let ext = imports.get(import.module(), import.name()).ok_or_else(|| {
+                InstantiationError::UnknownImport {
+                    module: import.module().to_string(),
+                    name: import.name().to_string(),
+                }
+            })?;
+            imports::check(store, import.desc(), ext, &data.types).map_err(|reason| {
+                InstantiationError::IncompatibleImport {
+                    module: import.module().to_string(),
+                    name: import.name().to_string(),
+                    reason,
+                }
+            })?;
+            match ext {
+                Extern::Func(addr) => data.funcs.push(addr),
+                Extern::Table(addr) => data.tables.push(addr),
+                Extern::Mem(addr) => data.mems.push(addr),
+                Extern::Global(addr) => data.globals.push(addr),
+                Extern::Tag(addr) => data.tags.push(addr),
+            }
+        }
+
+        for i in 0..validated.num_funcs() {
+            let idx = FuncIdx(i as u32);
+            let (Some(ty), Some(code)) = (validated.func_type(idx), validated.code(idx)) else {
+                continue;
+            };
+            let stacks = validated.stacks(idx).cloned().unwrap_or_default();
+            let body = Body::new(code, &data.types, stacks);
+            let func = Func::new(ty.clone(), instance, idx, body);
+            data.funcs.push(store.add_func(func));
+        }
+        if let Some(tagsec) = &module.tagsec {
+            for tag in tagsec.tags() {
+                let ty = data.types[tag.type_idx().0 as usize]
+                    .clone()
+                    .unwrap_or_else(|| FuncType::new(Vec::new(), Vec::new()));
+                data.tags.push(store.add_tag(Tag::new(ty)));
+            }
+        }
+        // Globals may refer to the globals before them
+        if let Some(globalsec) = &module.globalsec {
+            for global in globalsec.globals() {
+                let ty = global.global_type();
+                let value = eval_const(store, &data, global.init())?;
+                let global = Global::new(ty.val_type(), ty.is_mutable(), value);
+                data.globals.push(store.add_global(global));
+            }
+        }
+        if let Some(tablesec) = &module.tablesec {
+            for table in tablesec.tables() {
+                let init = match table.init() {
+                    Some(expr) => eval_ref(store, &data, expr)?,
+                    None => Ref::Null,
+                };
+                let table =
+                    Table::new(table.table_type(), init).ok_or(InstantiationError::OutOfMemory)?;
+                data.tables.push(store.add_table(table));
+            }
+        }
+        if let Some(memsec) = &module.memsec {
+            for ty in memsec.mems() {
+                let memory = Memory::new(ty).ok_or(InstantiationError::OutOfMemory)?;
+                data.mems.push(store.add_memory(memory));
+            }
+        }
+        if let Some(elemsec) = &module.elemsec {
+            for elem in elemsec.elems() {
+                let refs = match elem.init() {
+                    ElemInit::Funcs(funcs) => funcs
+                        .iter()
+                        .map(|idx| Ref::Func(data.funcs[idx.0 as usize]))
+                        .collect(),
+                    ElemInit::Exprs(exprs) => exprs
+                        .iter()
+                        .map(|expr| eval_ref(store, &data, expr))
+                        .collect::<Result<_, _>>()?,
+                };
+                data.elems.push(store.add_elem(refs));
+            }
+        }
+        if let Some(datasec) = &module.datasec {
+            for segment in datasec.datas() {
+                data.datas.push(store.add_data(segment.init().to_vec()));
+            }
+        }
+        if let Some(exportsec) = &module.exportsec {
+            for export in exportsec.exports() {
+                let ext = match export.desc() {
+                    ExportDesc::Func(idx) => Extern::Func(data.funcs[idx.0 as usize]),
+                    ExportDesc::Table(idx) => Extern::Table(data.tables[idx.0 as usize]),
+                    ExportDesc::Mem(idx) => Extern::Mem(data.mems[idx.0 as usize]),
+                    ExportDesc::Global(idx) => Extern::Global(data.globals[idx.0 as usize]),
+                    ExportDesc::Tag(idx) => Extern::Tag(data.tags[idx.0 as usize]),
+                };
+                data.exports.push((export.name().to_string(), ext));
+            }
+        }
+
+        // Segments are applied in order, and those before a trap stay
+        // applied, as the spec requires
+        if let Some(elemsec) = &module.elemsec {
+            for (i, elem) in elemsec.elems().iter().enumerate() {
+                let addr = data.elems[i];
+                match elem.mode() {
+                    ElemMode::Active { table, offset } => {
+                        let offset = eval_index(store, &data, offset)?;
+                        let table = data.tables[table.0 as usize];
+                        let refs = std::mem::take(&mut store.elems[addr.0 as usize]);
+                        store.table_mut(table).init(offset, &refs)?;
+                    }
+                    ElemMode::Declarative => store.elems[addr.0 as usize].clear(),
+                    ElemMode::Passive => {}
+                }
+            }
+        }
+        if let Some(datasec) = &module.datasec {
+            for (i, segment) in datasec.datas().iter().enumerate() {
+                if let DataMode::Active { mem, offset } = segment.mode() {
+                    let offset = eval_index(store, &data, offset)?;
+                    let mem = data.mems[mem.0 as usize];
+                    let bytes = std::mem::take(&mut store.datas[data.datas[i].0 as usize]);
+                    store.memory_mut(mem).write(offset, &bytes)?;
+                }
+            }
+        }
+
+        data.names = module.names();
+        data.lines = module.line_table();
+
+        let start = module
+            .startsec
+            .as_ref()
+            .map(|sec| data.funcs[sec.func().0 as usize]);
+        store.instances.push(data);
+        if let Some(start) = start {
+            store.invoke(start, &[])?;
+        }
+        Ok(Instance(instance))
+    }
+
+    pub(crate) fn data<'a>(&self, store: &'a Store) -> &'a InstanceData {
+        &store.instances[self.0]
+    }
+
+    /// The name the instance was registered under, or else the module's
+    /// name from its name section.
+    pub fn name<'a>(&self, store: &'a Store) -> Option<&'a str> {
+        let data = self.data(store);
+        data.name.as_deref().or(data.names.module())
+    }
+
+    /// The instance's exports, in the order of the export section.
+    pub fn exports<'a>(&self, store: &'a Store) -> impl Iterator<Item = (&'a str, Extern)> {
+        self.data(store)
+            .exports
+            .iter()
+            .map(|(name, ext)| (name.as_str(), *ext))
+    }
+
+    pub fn export(&self, store: &Store, name: &str) -> Option<Extern> {
+        self.exports(store)
+            .find(|(export, _)| *export == name)
+            .map(|(_, ext)| ext)
+    }
+
+    /// The exported function called `name`.
+    pub fn func(&self, store: &Store, name: &str) -> Option<FuncAddr> {
+        match self.export(store, name)? {
+            Extern::Func(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Calls the exported function called `name`.
+    pub fn invoke(
+        &self,
+        store: &mut Store,
+        name: &str,
+        args: &[Value],
+    ) -> Result<Vec<Value>, Trap> {
+        match self.func(store, name) {
+            Some(func) => store.invoke(func, args),
+            None => Err(Trap::new(TrapKind::Host(format!(
+                "unknown function {}",
+                name
+            )))),
+        }
+    }
+}
+
+fn code_hash(module: &Module) -> u64 {
+    let mut bytes = Vec::new();
+    let sections: [Option<&dyn Encode>; 3] = [
+        module.typesec.as_ref().map(|sec| sec as &dyn Encode),
+        module.functionsec.as_ref().map(|sec| sec as &dyn Encode),
+        module.codesec.as_ref().map(|sec| sec as &dyn Encode),
+    ];
+    for section in sections.into_iter().flatten() {
+        // Writing to memory can only fail for sections too long to encode
+        // at all, which a module that validated doesn't have
+        let _ = section.encode(&mut bytes);
+    }
+    // FNV-1a, which unlike the standard library's hashers is the same in
+    // every build, as a hash saved in a file has to be
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Evaluates a constant expression, as found in global initializers and
+/// segment offsets.
+fn eval_const(store: &Store, data: &InstanceData, expr: &Expr) -> Result<Value, Trap> {
+    let mut stack = Vec::new();
+    for instr in expr.instrs() {
+        let value = match instr {
+            Instr::I32Const(v) => Value::I32(*v),
+            Instr::I64Const(v) => Value::I64(*v),
+            Instr::F32Const(v) => Value::F32(*v),
+            Instr::F64Const(v) => Value::F64(*v),
+            Instr::Simd(SimdOp::V128Const, SimdImm::Bytes(bytes)) => {
+                Value::V128(u128::from_le_bytes(*bytes))
+            }
+            Instr::RefNull(_) => Value::Ref(Ref::Null),
+            Instr::RefFunc(idx) => Value::Ref(Ref::Func(data.funcs[idx.0 as usize])),
+            Instr::GlobalGet(idx) => store.global(data.globals[idx.0 as usize]).get(),
+            // The extended constant instructions
+            Instr::Numeric(op) => {
+                numeric::eval(*op, &mut stack)?;
+                continue;
+            }
+            Instr::End => break,
+            instr => return Err(Trap::new(TrapKind::Unsupported(instr.to_string()))),
+        };
+        stack.push(value);
+    }
+    Ok(stack.pop().unwrap_or(Value::I32(0)))
+}
+
+fn eval_ref(store: &Store, data: &InstanceData, expr: &Expr) -> Result<Ref, Trap> {
+    match eval_const(store, data, expr)? {
+        Value::Ref(r) => Ok(r),
+        _ => Ok(Ref::Null),
+    }
+}
+
+// An offset into a table or memory, which is an i64 for 64-bit ones
+fn eval_index(store: &Store, data: &InstanceData, expr: &Expr) -> Result<u64, Trap> {
+    match eval_const(store, data, expr)? {
+        Value::I64(offset) => Ok(offset as u64),
+        Value::I32(offset) => Ok(u64::from(offset as u32)),
+        _ => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wat::parse::parse_module;
+
+    fn instantiate(store: &mut Store, text: &str) -> Result<Instance, InstantiationError> {
+        Instance::new(
+            store,
+            &parse_module(text).expect("The module"),
+            &Imports::new(),
+        )
+    }
+
+    #[test]
+    fn test_instantiation() {
+        let mut store = Store::new();
+        let instance = instantiate(
+            &mut store,
+            r#"
+            (global $c i32 (i32.const 1))
+            (global $g (mut i32) (i32.const 0))
+            (global (export "h") i32 (i32.add (global.get $c) (i32.const 1)))
+            (memory (export "mem") 1)
+            (data (i32.const 0) "\2a")
+            (func $start (global.set $g (i32.load8_u (i32.const 0))))
+            (func (export "g") (result i32) (global.get $g))
+            (start $start)
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            instance.invoke(&mut store, "g", &[]),
+            Ok(vec![Value::I32(42)])
+        );
+        let Some(Extern::Global(h)) = instance.export(&store, "h") else {
+            panic!("expected a global export");
+        };
+        assert_eq!(store.global(h).get(), Value::I32(2));
+        assert!(matches!(
+            instance.export(&store, "mem"),
+            Some(Extern::Mem(_))
+        ));
+        assert_eq!(instance.export(&store, "missing"), None);
+    }
+
+    #[test]
+    fn test_imports() {
+        let mut store = Store::new();
+        let mut imports = Imports::new();
+        let exporter = instantiate(
+            &mut store,
+            r#"
+            (table (export "t") 2 funcref)
+            (memory (export "m") 1 4)
+            (global (export "g") (mut i32) (i32.const 0))
+            (func (export "seven") (result i32) (i32.const 7))
+        "#,
+        )
+        .unwrap();
+        imports.define_instance(&store, "lib", exporter);
+
+        let module = parse_module(
+            r#"
+            (import "lib" "t" (table 1 funcref))
+            (import "lib" "m" (memory 1 8))
+            (import "lib" "g" (global $g (mut i32)))
+            (import "lib" "seven" (func $seven (result i32)))
+            (elem (i32.const 1) $seven)
+            (func (export "set") (global.set $g (call_indirect (result i32) (i32.const 1))))
+        "#,
+        )
+        .unwrap();
+        let importer = Instance::new(&mut store, &module, &imports).unwrap();
+        importer.invoke(&mut store, "set", &[]).unwrap();
+        let Some(Extern::Global(g)) = exporter.export(&store, "g") else {
+            panic!("expected a global export");
+        };
+        assert_eq!(store.global(g).get(), Value::I32(7));
+        let Some(Extern::Table(t)) = exporter.export(&store, "t") else {
+            panic!("expected a table export");
+        };
+        assert!(matches!(store.table(t).get(1), Ok(Ref::Func(_))));
+
+        let link = |store: &mut Store, text: &str| {
+            Instance::new(store, &parse_module(text).unwrap(), &imports)
+                .map_err(|err| err.to_string())
+        };
+        assert_eq!(
+            link(&mut store, r#"(import "lib" "m" (memory 2))"#)
+                .err()
+                .unwrap(),
+            "incompatible import type for lib.m: memory size: expected at least 2, found 1"
+        );
+        assert_eq!(
+            link(&mut store, r#"(import "lib" "m" (memory 1 2))"#)
+                .err()
+                .unwrap(),
+            "incompatible import type for lib.m: memory size: expected a maximum of at most 2, \
+             found 4"
+        );
+        assert_eq!(
+            link(&mut store, r#"(import "lib" "t" (table 1 2 funcref))"#)
+                .err()
+                .unwrap(),
+            "incompatible import type for lib.t: table size: expected a maximum of at most 2, \
+             found none"
+        );
+        assert_eq!(
+            link(&mut store, r#"(import "lib" "g" (global i32))"#)
+                .err()
+                .unwrap(),
+            "incompatible import type for lib.g: expected a global of type i32, found (mut i32)"
+        );
+        assert_eq!(
+            link(&mut store, r#"(import "lib" "seven" (func (param i32)))"#)
+                .err()
+                .unwrap(),
+            "incompatible import type for lib.seven: expected a function of type [i32] -> [], \
+             found [] -> [i32]"
+        );
+        assert_eq!(
+            link(&mut store, r#"(import "lib" "seven" (memory 1))"#)
+                .err()
+                .unwrap(),
+            "incompatible import type for lib.seven: expected a memory, found a function"
+        );
+    }
+
+    #[test]
+    fn test_instantiation_errors() {
+        let mut store = Store::new();
+        assert_eq!(
+            instantiate(&mut store, r#"(import "env" "f" (func))"#),
+            Err(InstantiationError::UnknownImport {
+                module: "env".to_string(),
+                name: "f".to_string()
+            })
+        );
+        assert_eq!(
+            instantiate(&mut store, r#"(memory 1) (data (i32.const 65535) "ab")"#),
+            Err(InstantiationError::Trap(Trap::new(
+                TrapKind::MemoryOutOfBounds
+            )))
+        );
+        assert_eq!(
+            instantiate(
+                &mut store,
+                "(table 1 funcref) (func) (elem (i32.const 1) 0)"
+            ),
+            Err(InstantiationError::Trap(Trap::new(
+                TrapKind::TableOutOfBounds
+            )))
+        );
+        assert!(matches!(
+            instantiate(&mut store, "(func unreachable) (start 0)"),
+            Err(InstantiationError::Trap(trap))
+                if trap.kind() == &TrapKind::Unreachable && trap.backtrace().len() == 1
+        ));
+        assert!(matches!(
+            instantiate(&mut store, "(func (result i32))"),
+            Err(InstantiationError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::module::Module;
+use crate::runtime::host::{Caller, IntoFunc};
+use crate::runtime::imports::Imports;
+use crate::runtime::instance::{Instance, InstantiationError};
+use crate::runtime::memory::Memory;
+use crate::runtime::store::{Extern, Func, Global, HostFunc, Store, Tag};
+use crate::runtime::table::Table;
+use crate::runtime::trap::{Trap, TrapKind};
+use crate::runtime::value::{Ref, Value};
+use crate::section::import::Import;
+use crate::types::func_type::FuncType;
+use crate::types::import_desc::ImportDesc;
+
+enum Definition {
+    Extern(Extern),
+    /// A host function, which is added to the store of each instance that
+    /// imports it.
+    Func(FuncType, HostFunc),
+}
+
+/// Asks for the results of a call of a stubbed function, given the
+/// import's module and field name, its type and the arguments.
+pub type AskFunc = Rc<dyn Fn(&str, &str, &FuncType, &[Value]) -> Result<Vec<Value>, Trap>>;
+
+/// What stubbed functions do when they are called.
+#[derive(Clone)]
+pub enum StubMode {
+    /// Trap with "called unimplemented import env.foo".
+    Trap,
+    /// Return zeros, and nulls for references.
+    Zeros,
+    /// Return whatever the function says.
+    Ask(AskFunc),
+}
+
+/// Host functions and externs to provide for imports, by module and field
+/// name. Unlike [`Imports`], a linker doesn't belong to a store, so host
+/// functions can be defined before there is one.
+#[derive(Default)]
+pub struct Linker {
+    definitions: HashMap<(String, String), Definition>,
+    stubs: Option<StubMode>,
+}
+
+impl Linker {
+    pub fn new() -> Linker {
+        Linker::default()
+    }
+
+    /// Provides the closure `f` as the function `module`.`name`, with a
+    /// type given by its parameter and result types, e.g.
+    /// `linker.func("env", "log", |caller: &mut Caller<'_>, x: i32| ...)`.
+    pub fn func<Params, Results>(
+        &mut self,
+        module: &str,
+        name: &str,
+        f: impl IntoFunc<Params, Results>,
+    ) -> &mut Linker {
+        let (ty, f) = f.into_func();
+        self.insert(module, name, Definition::Func(ty, f))
+    }
+
+    /// Provides `f` as the function `module`.`name` of type `ty`, taking
+    /// and returning untyped values.
+    pub fn func_new(
+        &mut self,
+        module: &str,
+        name: &str,
+        ty: FuncType,
+        f: impl Fn(&mut Caller<'_>, &[Value]) -> Result<Vec<Value>, Trap> + 'static,
+    ) -> &mut Linker {
+        self.insert(module, name, Definition::Func(ty, Rc::new(f)))
+    }
+
+    /// Provides `ext`, such as a global, memory or table the host added to
+    /// the store, as `module`.`name`.
+    pub fn define(&mut self, module: &str, name: &str, ext: Extern) -> &mut Linker {
+        self.insert(module, name, Definition::Extern(ext))
+    }
+
+    /// Provides every export of `instance` as a field of `module`.
+    pub fn instance(&mut self, store: &Store, module: &str, instance: Instance) -> &mut Linker {
+        for (name, ext) in instance.exports(store) {
+            self.define(module, name, ext);
+        }
+        self
+    }
+
+    /// Makes up the imports that nothing was defined for, so that modules
+    /// can be instantiated without all of their host environment. Functions
+    /// behave as `mode` says, and memories, tables and globals are new ones
+    /// of the imported type, as empty as it allows.
+    pub fn stub_missing(&mut self, mode: StubMode) -> &mut Linker {
+        self.stubs = Some(mode);
+        self
+    }
+
+    /// Resolves the imports of `module`, adding the host functions it
+    /// imports to `store`. Imports nothing was defined for come from the
+    /// instances registered in the store under their module name.
+    pub fn imports(&self, store: &mut Store, module: &Module) -> Imports {
+        let types = module
+            .typesec
+            .iter()
+            .flat_map(|sec| sec.types())
+            .map(|sub| sub.comp_type().as_func().cloned())
+            .collect::<Vec<_>>();
+        let mut imports = Imports::new();
+        for import in module.importsec.iter().flat_map(|sec| sec.imports()) {
+            let key = (import.module().to_string(), import.name().to_string());
+            if imports.get(&key.0, &key.1).is_some() {
+                continue;
+            }
+            let registered = store
+                .instance(&key.0)
+                .and_then(|instance| instance.export(store, &key.1));
+            let ext = match self.definitions.get(&key) {
+                Some(Definition::Extern(ext)) => *ext,
+                Some(Definition::Func(ty, f)) => {
+                    Extern::Func(store.add_func(Func::from_host(ty.clone(), f.clone())))
+                }
+                None => match registered.or_else(|| {
+                    self.stubs
+                        .as_ref()
+                        .and_then(|mode| stub(store, &types, import, mode))
+                }) {
+                    Some(ext) => ext,
+                    None => continue,
+                },
+            };
+            imports.define(&key.0, &key.1, ext);
+        }
+        imports
+    }
+
+    /// Instantiates `module` with the definitions of this linker.
+    pub fn instantiate(
+        &self,
+        store: &mut Store,
+        module: &Module,
+    ) -> Result<Instance, InstantiationError> {
+        let imports = self.imports(store, module);
+        Instance::new(store, module, &imports)
+    }
+
+    fn insert(&mut self, module: &str, name: &str, definition: Definition) -> &mut Linker {
+        self.definitions
+            .insert((module.to_string(), name.to_string()), definition);
+        self
+    }
+}
+
+/// Makes up an extern for `import`, unless its type can't be had.
+fn stub(
+    store: &mut Store,
+    types: &[Option<FuncType>],
+    import: &Import,
+    mode: &StubMode,
+) -> Option<Extern> {
+    let func_type = |idx: u32| types.get(idx as usize).cloned().flatten();
+    Some(match import.desc() {
+        ImportDesc::Func(idx) => {
+            let ty = func_type(idx.0)?;
+            let (module, name) = (import.module().to_string(), import.name().to_string());
+            let mode = mode.clone();
+            let results = ty.results().to_vec();
+            let func = Func::host(ty.clone(), move |_, args| match &mode {
+                StubMode::Trap => Err(Trap::new(TrapKind::Host(format!(
+                    "called unimplemented import {}.{}",
+                    module, name
+                )))),
+                StubMode::Zeros => Ok(results.iter().map(|t| Value::default_of(*t)).collect()),
+                StubMode::Ask(ask) => ask(&module, &name, &ty, args),
+            });
+            Extern::Func(store.add_func(func))
+        }
+        ImportDesc::Table(ty) => Extern::Table(store.add_table(Table::new(ty, Ref::Null)?)),
+        ImportDesc::Mem(ty) => Extern::Mem(store.add_memory(Memory::new(ty)?)),
+        ImportDesc::Global(ty) => Extern::Global(store.add_global(Global::new(
+            ty.val_type(),
+            ty.is_mutable(),
+            Value::default_of(ty.val_type()),
+        ))),
+        ImportDesc::Tag(ty) => Extern::Tag(store.add_tag(Tag::new(func_type(ty.type_idx().0)?))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::runtime::host::Caller;
+    use crate::runtime::linker::{Linker, StubMode};
+    use crate::runtime::memory::Memory;
+    use crate::runtime::store::{Extern, Func, Global, Store};
+    use crate::runtime::table::Table;
+    use crate::runtime::trap::{Trap, TrapKind};
+    use crate::runtime::value::{Ref, Value};
+    use crate::types::func_type::FuncType;
+    use crate::types::limits::Limits;
+    use crate::types::mem_type::MemType;
+    use crate::types::num_type::NumType;
+    use crate::types::ref_type::RefType;
+    use crate::types::table_type::TableType;
+    use crate::types::val_type::ValType;
+    use crate::wat::parse::parse_module;
+
+    #[test]
+    fn test_host_functions() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut linker = Linker::new();
+        let sink = log.clone();
+        linker
+            .func(
+                "env",
+                "log",
+                move |caller: &mut Caller<'_>, ptr: i32, len: i32| -> Result<(), Trap> {
+                    let memory = caller.memory().expect("a memory");
+                    let bytes = memory.bytes(ptr as u64, len as u64)?;
+                    sink.borrow_mut()
+                        .push(String::from_utf8_lossy(&bytes).to_string());
+                    Ok(())
+                },
+            )
+            .func("env", "add", |_: &mut Caller<'_>, a: i64, b: i64| a + b)
+            .func("env", "pair", |_: &mut Caller<'_>| (1i32, 2.5f64))
+            .func("env", "fail", |_: &mut Caller<'_>| -> Result<i32, Trap> {
+                Err(Trap::new(TrapKind::Host("failed".to_string())))
+            })
+            .func("env", "twice", |caller: &mut Caller<'_>, x: i32| {
+                let Some(Extern::Func(double)) = caller.export("double") else {
+                    return Err(Trap::new(TrapKind::Host("no double".to_string())));
+                };
+                match caller.store().invoke(double, &[Value::I32(x)])?[..] {
+                    [Value::I32(v)] => Ok(v),
+                    _ => Err(Trap::new(TrapKind::Host("bad results".to_string()))),
+                }
+            });
+
+        let module = parse_module(
+            r#"
+            (import "env" "log" (func $log (param i32 i32)))
+            (import "env" "add" (func $add (param i64 i64) (result i64)))
+            (import "env" "pair" (func $pair (result i32 f64)))
+            (import "env" "fail" (func $fail (result i32)))
+            (import "env" "twice" (func $twice (param i32) (result i32)))
+            (memory 1)
+            (data (i32.const 8) "hello")
+            (func (export "hello") (call $log (i32.const 8) (i32.const 5)))
+            (func (export "sum") (result i64) (call $add (i64.const 40) (i64.const 2)))
+            (func (export "first") (result i32) (drop (call $pair)))
+            (func (export "fail") (result i32) (call $fail))
+            (func (export "double") (param i32) (result i32)
+              (i32.mul (local.get 0) (i32.const 2)))
+            (func (export "quadruple") (param i32) (result i32)
+              (return_call $twice (call $twice (local.get 0))))
+        "#,
+        )
+        .unwrap();
+        let mut store = Store::new();
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+
+        instance.invoke(&mut store, "hello", &[]).unwrap();
+        assert_eq!(*log.borrow(), ["hello"]);
+        let invoke = |store: &mut Store, name: &str, args: &[Value]| {
+            instance
+                .invoke(store, name, args)
+                .map_err(|trap| trap.to_string())
+        };
+        assert_eq!(invoke(&mut store, "sum", &[]), Ok(vec![Value::I64(42)]));
+        assert_eq!(invoke(&mut store, "first", &[]), Ok(vec![Value::I32(1)]));
+        assert_eq!(invoke(&mut store, "fail", &[]), Err("failed".to_string()));
+        assert_eq!(
+            invoke(&mut store, "quadruple", &[Value::I32(3)]),
+            Ok(vec![Value::I32(12)])
+        );
+
+        // Host functions can also be called from outside, without a caller
+        let add = store.add_func(Func::host(
+            FuncType::new(vec![ValType::Num(NumType::I32)], vec![]),
+            |caller, _| match caller.instance() {
+                Some(_) => Ok(vec![Value::I32(1)]),
+                None => Ok(vec![]),
+            },
+        ));
+        assert_eq!(store.invoke(add, &[Value::I32(1)]), Ok(vec![]));
+        assert!(store.invoke(add, &[]).is_err());
+    }
+
+    #[test]
+    fn test_host_externs() {
+        let mut store = Store::new();
+        let i32_type = ValType::Num(NumType::I32);
+        let global = store.add_global(Global::new(i32_type, true, Value::I32(5)));
+        let memory = store.add_memory(Memory::new(&MemType::new(Limits::new(1, Some(2)))).unwrap());
+        let table = store.add_table(
+            Table::new(
+                &TableType::new(RefType::Func, Limits::new(2, None)),
+                Ref::Null,
+            )
+            .unwrap(),
+        );
+        let mut linker = Linker::new();
+        linker
+            .define("host", "g", Extern::Global(global))
+            .define("host", "m", Extern::Mem(memory))
+            .define("host", "t", Extern::Table(table))
+            .func_new(
+                "host",
+                "wrong",
+                FuncType::new(vec![], vec![i32_type]),
+                |_, _| Ok(vec![Value::I64(0)]),
+            );
+
+        let module = parse_module(
+            r#"
+            (import "host" "g" (global $g (mut i32)))
+            (import "host" "m" (memory 1))
+            (import "host" "t" (table 1 funcref))
+            (import "host" "wrong" (func $wrong (result i32)))
+            (func $f)
+            (elem (i32.const 1) $f)
+            (func (export "run")
+              (global.set $g (i32.add (global.get $g) (i32.const 1)))
+              (i32.store (i32.const 0) (global.get $g)))
+            (func (export "wrong") (result i32) (call $wrong))
+        "#,
+        )
+        .unwrap();
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        instance.invoke(&mut store, "run", &[]).unwrap();
+        assert_eq!(store.global(global).get(), Value::I32(6));
+        assert_eq!(store.memory(memory).load::<4>(0), Ok(6i32.to_le_bytes()));
+        assert!(matches!(store.table(table).get(1), Ok(Ref::Func(_))));
+        assert_eq!(
+            instance
+                .invoke(&mut store, "wrong", &[])
+                .map_err(|trap| trap.to_string()),
+            Err("host function returned results of the wrong type".to_string())
+        );
+
+        let unlinked = parse_module(r#"(import "host" "missing" (func))"#).unwrap();
+        assert_eq!(
+            linker
+                .instantiate(&mut store, &unlinked)
+                .map_err(|err| err.to_string()),
+            Err("unknown import host.missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_stub_imports() {
+        let module = parse_module(
+            r#"
+            (import "env" "memory" (memory 1))
+            (import "env" "table" (table 2 funcref))
+            (import "env" "g" (global i64))
+            (import "env" "get" (func $get (param i32) (result i32 f64)))
+            (func (export "get") (result f64) (local $x f64)
+              (local.set $x (call $get (i32.const 5)))
+              (f64.add (f64.convert_i32_s) (local.get $x)))
+            (func (export "size") (result i32) (memory.size))
+        "#,
+        )
+        .unwrap();
+        let run = |mode: StubMode| {
+            let mut store = Store::new();
+            let instance = Linker::new()
+                .stub_missing(mode)
+                .instantiate(&mut store, &module)
+                .map_err(|err| err.to_string())?;
+            assert_eq!(
+                instance.invoke(&mut store, "size", &[]),
+                Ok(vec![Value::I32(1)])
+            );
+            instance
+                .invoke(&mut store, "get", &[])
+                .map_err(|trap| trap.to_string())
+        };
+        assert_eq!(
+            run(StubMode::Trap),
+            Err("called unimplemented import env.get".to_string())
+        );
+        assert_eq!(run(StubMode::Zeros), Ok(vec![Value::F64(0.0)]));
+        let ask = |module: &str, name: &str, ty: &FuncType, args: &[Value]| {
+            assert_eq!((module, name, args), ("env", "get", &[Value::I32(5)][..]));
+            Ok(["-0x10", "2.5"]
+                .iter()
+                .zip(ty.results())
+                .map(|(text, t)| Value::parse(*t, text).unwrap())
+                .collect())
+        };
+        assert_eq!(
+            run(StubMode::Ask(Rc::new(ask))),
+            Ok(vec![Value::F64(-13.5)])
+        );
+
+        let mut store = Store::new();
+        assert_eq!(
+            Linker::new()
+                .instantiate(&mut store, &module)
+                .map_err(|err| err.to_string()),
+            Err("unknown import env.memory".to_string())
+        );
+    }
+
+    #[test]
+    fn test_registered_instances() {
+        let plugin = parse_module(
+            r#"
+            (memory (export "memory") 1)
+            (func $check (export "check") (param i32) (result i32)
+              (i32.div_u (i32.const 100) (local.get 0)))
+        "#,
+        )
+        .unwrap();
+        let app = parse_module(
+            r#"
+            (import "plugin" "check" (func $check (param i32) (result i32)))
+            (import "plugin" "memory" (memory 1))
+            (func (export "run") (param i32) (result i32)
+              (i32.store (i32.const 0) (i32.const 9))
+              (call $check (local.get 0)))
+        "#,
+        )
+        .unwrap();
+        let mut store = Store::new();
+        let linker = Linker::new();
+        let instance = linker.instantiate(&mut store, &plugin).unwrap();
+        store.register("plugin", instance);
+        assert_eq!(store.instance("plugin"), Some(instance));
+        let app = linker.instantiate(&mut store, &app).unwrap();
+        assert_eq!(
+            app.invoke(&mut store, "run", &[Value::I32(4)]),
+            Ok(vec![Value::I32(25)])
+        );
+        let Some(Extern::Mem(memory)) = instance.export(&store, "memory") else {
+            panic!("no memory export");
+        };
+        assert_eq!(store.memory(memory).load::<1>(0), Ok([9]));
+
+        // Frames say which module they are in, when it was registered
+        let trap = app.invoke(&mut store, "run", &[Value::I32(0)]).unwrap_err();
+        let modules = trap
+            .backtrace()
+            .iter()
+            .map(|frame| frame.module())
+            .collect::<Vec<_>>();
+        assert_eq!(modules, [Some("plugin"), None]);
+        assert!(
+            trap.backtrace()[0]
+                .to_string()
+                .starts_with("plugin!func 0 at")
+        );
+    }
+}
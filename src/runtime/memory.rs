@@ -0,0 +1,311 @@
+use std::fmt::Display;
+
+use crate::runtime::store::MemAddr;
+use crate::runtime::trap::TrapKind;
+use crate::types::mem_type::{MemType, PAGE_SIZE};
+
+const PAGE: usize = PAGE_SIZE as usize;
+
+/// A linear memory: a byte array that grows in pages of 64 KiB. Pages only
+/// take up host memory once something is written to them, so a memory can
+/// be as large as its type allows while the program only uses a little.
+pub struct Memory {
+    /// Each page, or `None` for one that is still all zeros.
+    pages: Vec<Option<Box<[u8; PAGE]>>>,
+    /// The maximum its type declares, in pages.
+    max: Option<u64>,
+    /// The most pages the memory may grow to.
+    limit: u64,
+    is_64: bool,
+    shared: bool,
+}
+
+impl Memory {
+    /// A memory of the type's minimum size, or `None` if the host can't
+    /// provide that much.
+    pub fn new(ty: &MemType) -> Option<Memory> {
+        let limits = ty.limits();
+        let mut memory = Memory {
+            pages: Vec::new(),
+            max: limits.max(),
+            limit: limits.max().unwrap_or(u64::MAX).min(ty.max_pages()),
+            is_64: ty.is_64(),
+            shared: limits.is_shared(),
+        };
+        memory.grow(limits.min())?;
+        Some(memory)
+    }
+
+    /// The size in pages.
+    pub fn size(&self) -> u64 {
+        self.pages.len() as u64
+    }
+
+    /// The maximum size in pages its type declares.
+    pub fn max(&self) -> Option<u64> {
+        self.max
+    }
+
+    /// Whether addresses are `i64` rather than `i32`.
+    pub fn is_64(&self) -> bool {
+        self.is_64
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// The number of pages that have been written to, which are the ones
+    /// that take up host memory.
+    pub fn resident_pages(&self) -> u64 {
+        self.pages.iter().filter(|page| page.is_some()).count() as u64
+    }
+
+    /// The pages that have been written to, by page number.
+    pub(crate) fn resident(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.pages
+            .iter()
+            .enumerate()
+            .filter_map(|(i, page)| Some((i as u64, &page.as_ref()?[..])))
+    }
+
+    /// Empties the memory and resizes it to `size` pages, or returns `false`
+    /// if its type doesn't allow that size.
+    pub(crate) fn reset(&mut self, size: u64) -> bool {
+        self.pages.clear();
+        self.grow(size).is_some()
+    }
+
+    /// Grows the memory by `delta` pages, returning the old size, or `None`
+    /// if that would pass the maximum or the host is out of memory.
+    pub fn grow(&mut self, delta: u64) -> Option<u64> {
+        let old = self.size();
+        let new = old.checked_add(delta).filter(|new| *new <= self.limit)?;
+        let new = usize::try_from(new).ok()?;
+        self.pages.try_reserve_exact(new - self.pages.len()).ok()?;
+        self.pages.resize_with(new, || None);
+        Some(old)
+    }
+
+    /// Whether the `len` bytes at `addr` are in bounds.
+    pub(crate) fn check(&self, addr: u64, len: u64) -> Result<(), TrapKind> {
+        match addr.checked_add(len) {
+            Some(end) if end.div_ceil(PAGE_SIZE) <= self.size() => Ok(()),
+            _ => Err(TrapKind::MemoryOutOfBounds),
+        }
+    }
+
+    /// Checks that the `len` bytes at `addr` are in bounds, and splits them
+    /// at page boundaries into the page, the offset in it and the length.
+    fn chunks(
+        &self,
+        addr: u64,
+        len: u64,
+    ) -> Result<impl Iterator<Item = (usize, usize, usize)> + use<>, TrapKind> {
+        self.check(addr, len)?;
+        let mut addr = addr;
+        let end = addr + len;
+        Ok(std::iter::from_fn(move || {
+            if addr == end {
+                return None;
+            }
+            let page = (addr / PAGE_SIZE) as usize;
+            let offset = (addr % PAGE_SIZE) as usize;
+            let n = (PAGE - offset).min((end - addr) as usize);
+            addr += n as u64;
+            Some((page, offset, n))
+        }))
+    }
+
+    /// The page `i`, allocating it if it is still all zeros.
+    fn page_mut(&mut self, i: usize) -> &mut [u8; PAGE] {
+        self.pages[i].get_or_insert_with(|| {
+            vec![0; PAGE]
+                .into_boxed_slice()
+                .try_into()
+                .expect("a page-sized buffer")
+        })
+    }
+
+    pub fn read(&self, addr: u64, buf: &mut [u8]) -> Result<(), TrapKind> {
+        let mut pos = 0;
+        for (page, offset, n) in self.chunks(addr, buf.len() as u64)? {
+            let dst = &mut buf[pos..pos + n];
+            match &self.pages[page] {
+                Some(page) => dst.copy_from_slice(&page[offset..offset + n]),
+                None => dst.fill(0),
+            }
+            pos += n;
+        }
+        Ok(())
+    }
+
+    /// A copy of the `len` bytes at `addr`.
+    pub fn bytes(&self, addr: u64, len: u64) -> Result<Vec<u8>, TrapKind> {
+        // Check first, so that a bad length doesn't allocate
+        self.check(addr, len)?;
+        let mut buf = vec![0; len as usize];
+        self.read(addr, &mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn write(&mut self, addr: u64, bytes: &[u8]) -> Result<(), TrapKind> {
+        let mut pos = 0;
+        for (page, offset, n) in self.chunks(addr, bytes.len() as u64)? {
+            self.page_mut(page)[offset..offset + n].copy_from_slice(&bytes[pos..pos + n]);
+            pos += n;
+        }
+        Ok(())
+    }
+
+    /// Reads `N` bytes, as a little-endian load does.
+    pub fn load<const N: usize>(&self, addr: u64) -> Result<[u8; N], TrapKind> {
+        let mut buf = [0; N];
+        self.read(addr, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Sets `len` bytes at `addr` to `value`.
+    pub fn fill(&mut self, addr: u64, value: u8, len: u64) -> Result<(), TrapKind> {
+        for (page, offset, n) in self.chunks(addr, len)? {
+            // Zeroing a page that is all zeros leaves it unallocated
+            if value != 0 || self.pages[page].is_some() {
+                self.page_mut(page)[offset..offset + n].fill(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `len` bytes within the memory. The ranges may overlap.
+    pub fn copy_within(&mut self, dst: u64, src: u64, len: u64) -> Result<(), TrapKind> {
+        self.check(src, len)?;
+        self.check(dst, len)?;
+        // A page at a time, from the end when copying upwards so that
+        // overlapping bytes are read before they are overwritten
+        let mut buf = vec![0; PAGE.min(len as usize)];
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(PAGE_SIZE);
+            let at = if dst > src { len - done - n } else { done };
+            let buf = &mut buf[..n as usize];
+            self.read(src + at, buf)?;
+            self.write(dst + at, buf)?;
+            done += n;
+        }
+        Ok(())
+    }
+}
+
+/// A `memory.grow`, as reported to the hook set with
+/// [`Store::set_grow_hook`].
+///
+/// [`Store::set_grow_hook`]: crate::runtime::store::Store::set_grow_hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryGrowth {
+    memory: MemAddr,
+    old: u64,
+    delta: u64,
+    grew: bool,
+}
+
+impl MemoryGrowth {
+    pub(crate) fn new(memory: MemAddr, old: u64, delta: u64, grew: bool) -> MemoryGrowth {
+        MemoryGrowth {
+            memory,
+            old,
+            delta,
+            grew,
+        }
+    }
+
+    pub fn memory(&self) -> MemAddr {
+        self.memory
+    }
+
+    /// The size before, in pages.
+    pub fn old(&self) -> u64 {
+        self.old
+    }
+
+    /// The number of pages asked for.
+    pub fn delta(&self) -> u64 {
+        self.delta
+    }
+
+    /// Whether the memory grew, rather than `memory.grow` failing.
+    pub fn grew(&self) -> bool {
+        self.grew
+    }
+}
+
+impl Display for MemoryGrowth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.grew {
+            write!(
+                f,
+                "memory {} grew from {} to {} pages",
+                self.memory.0,
+                self.old,
+                self.old + self.delta
+            )
+        } else {
+            write!(
+                f,
+                "memory {} failed to grow from {} pages by {}",
+                self.memory.0, self.old, self.delta
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::memory::{Memory, PAGE};
+    use crate::runtime::trap::TrapKind;
+    use crate::types::limits::Limits;
+    use crate::types::mem_type::MemType;
+
+    #[test]
+    fn test_memory() {
+        let mut memory = Memory::new(&MemType::new(Limits::new(1, Some(3)))).unwrap();
+        assert_eq!(memory.grow(1), Some(1));
+        assert_eq!(memory.grow(2), None);
+        assert_eq!(memory.size(), 2);
+        assert_eq!(memory.resident_pages(), 0);
+
+        // Accesses across a page boundary
+        let addr = PAGE as u64 - 2;
+        memory.write(addr, &0x0403_0201u32.to_le_bytes()).unwrap();
+        assert_eq!(memory.load::<4>(addr), Ok([1, 2, 3, 4]));
+        assert_eq!(memory.resident_pages(), 2);
+        assert_eq!(
+            memory.load::<8>(2 * PAGE as u64 - 4),
+            Err(TrapKind::MemoryOutOfBounds)
+        );
+        assert_eq!(
+            memory.write(u64::MAX, &[0]),
+            Err(TrapKind::MemoryOutOfBounds)
+        );
+
+        memory.copy_within(addr + 1, addr, 4).unwrap();
+        assert_eq!(memory.bytes(addr, 5), Ok(vec![1, 1, 2, 3, 4]));
+        memory.copy_within(addr - 1, addr, 5).unwrap();
+        assert_eq!(memory.bytes(addr - 1, 6), Ok(vec![1, 1, 2, 3, 4, 4]));
+        memory.fill(addr, 0, 8).unwrap();
+        assert_eq!(memory.load::<2>(addr - 1), Ok([1, 0]));
+    }
+
+    #[test]
+    fn test_untouched_pages() {
+        // 4 GiB, of which only what is written takes up host memory
+        let mut memory = Memory::new(&MemType::new(Limits::new(0, None))).unwrap();
+        assert_eq!(memory.grow(1 << 16), Some(0));
+        assert_eq!(memory.grow(1), None);
+        let last = (1 << 32) - 1;
+        assert_eq!(memory.load::<1>(last), Ok([0]));
+        memory.fill(0, 0, 1 << 32).unwrap();
+        memory.write(last, &[7]).unwrap();
+        assert_eq!(memory.load::<1>(last), Ok([7]));
+        assert_eq!(memory.resident_pages(), 1);
+    }
+}
@@ -0,0 +1,24 @@
+//! An interpreter for modules. A [`store::Store`] holds everything that
+//! instances create at runtime, [`instance::Instance`] instantiates a
+//! module into a store given the [`imports::Imports`] it links to, and
+//! [`exec::Execution`] runs a call one instruction at a time. A
+//! [`linker::Linker`] provides imports from the host, including Rust
+//! closures as functions, and [`wasi::Wasi`] adds WASI preview1 to one.
+//! [`debugger::Debugger`] lets a user look at a call that trapped, and a
+//! [`snapshot::Snapshot`] saves an instance's state to look at later.
+
+pub mod debugger;
+pub mod exec;
+pub mod host;
+pub mod imports;
+pub mod instance;
+pub mod linker;
+pub mod memory;
+mod numeric;
+mod simd;
+pub mod snapshot;
+pub mod store;
+pub mod table;
+pub mod trap;
+pub mod value;
+pub mod wasi;
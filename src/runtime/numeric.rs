@@ -0,0 +1,349 @@
+use crate::instr::numeric::NumericInstr;
+use crate::runtime::trap::TrapKind;
+use crate::runtime::value::Value;
+
+// Validation guarantees the operand types, so a mismatch here is a bug in
+// the interpreter rather than in the module
+macro_rules! pop {
+    ($name:ident, $variant:ident, $t:ty) => {
+        pub(crate) fn $name(stack: &mut Vec<Value>) -> $t {
+            match stack.pop() {
+                Some(Value::$variant(v)) => v,
+                v => panic!("expected {} operand, found {:?}", stringify!($t), v),
+            }
+        }
+    };
+}
+
+pop!(pop_i32, I32, i32);
+pop!(pop_i64, I64, i64);
+pop!(pop_f32, F32, f32);
+pop!(pop_f64, F64, f64);
+pop!(pop_v128, V128, u128);
+
+macro_rules! unop {
+    ($stack:ident, $pop:ident, $result:ident, |$a:ident| $e:expr) => {{
+        let $a = $pop($stack);
+        $stack.push(Value::$result($e));
+    }};
+}
+
+macro_rules! binop {
+    ($stack:ident, $pop:ident, $result:ident, |$a:ident, $b:ident| $e:expr) => {{
+        let $b = $pop($stack);
+        let $a = $pop($stack);
+        $stack.push(Value::$result($e));
+    }};
+}
+
+/// Applies a numeric instruction to the operands on top of `stack`.
+pub(crate) fn eval(op: NumericInstr, stack: &mut Vec<Value>) -> Result<(), TrapKind> {
+    use NumericInstr::*;
+    match op {
+        I32Eqz => unop!(stack, pop_i32, I32, |a| (a == 0) as i32),
+        I32Eq => binop!(stack, pop_i32, I32, |a, b| (a == b) as i32),
+        I32Ne => binop!(stack, pop_i32, I32, |a, b| (a != b) as i32),
+        I32LtS => binop!(stack, pop_i32, I32, |a, b| (a < b) as i32),
+        I32LtU => binop!(stack, pop_i32, I32, |a, b| ((a as u32) < (b as u32)) as i32),
+        I32GtS => binop!(stack, pop_i32, I32, |a, b| (a > b) as i32),
+        I32GtU => binop!(stack, pop_i32, I32, |a, b| ((a as u32) > (b as u32)) as i32),
+        I32LeS => binop!(stack, pop_i32, I32, |a, b| (a <= b) as i32),
+        I32LeU => binop!(stack, pop_i32, I32, |a, b| ((a as u32) <= (b as u32))
+            as i32),
+        I32GeS => binop!(stack, pop_i32, I32, |a, b| (a >= b) as i32),
+        I32GeU => binop!(stack, pop_i32, I32, |a, b| ((a as u32) >= (b as u32))
+            as i32),
+        I64Eqz => unop!(stack, pop_i64, I32, |a| (a == 0) as i32),
+        I64Eq => binop!(stack, pop_i64, I32, |a, b| (a == b) as i32),
+        I64Ne => binop!(stack, pop_i64, I32, |a, b| (a != b) as i32),
+        I64LtS => binop!(stack, pop_i64, I32, |a, b| (a < b) as i32),
+        I64LtU => binop!(stack, pop_i64, I32, |a, b| ((a as u64) < (b as u64)) as i32),
+        I64GtS => binop!(stack, pop_i64, I32, |a, b| (a > b) as i32),
+        I64GtU => binop!(stack, pop_i64, I32, |a, b| ((a as u64) > (b as u64)) as i32),
+        I64LeS => binop!(stack, pop_i64, I32, |a, b| (a <= b) as i32),
+        I64LeU => binop!(stack, pop_i64, I32, |a, b| ((a as u64) <= (b as u64))
+            as i32),
+        I64GeS => binop!(stack, pop_i64, I32, |a, b| (a >= b) as i32),
+        I64GeU => binop!(stack, pop_i64, I32, |a, b| ((a as u64) >= (b as u64))
+            as i32),
+        F32Eq => binop!(stack, pop_f32, I32, |a, b| (a == b) as i32),
+        F32Ne => binop!(stack, pop_f32, I32, |a, b| (a != b) as i32),
+        F32Lt => binop!(stack, pop_f32, I32, |a, b| (a < b) as i32),
+        F32Gt => binop!(stack, pop_f32, I32, |a, b| (a > b) as i32),
+        F32Le => binop!(stack, pop_f32, I32, |a, b| (a <= b) as i32),
+        F32Ge => binop!(stack, pop_f32, I32, |a, b| (a >= b) as i32),
+        F64Eq => binop!(stack, pop_f64, I32, |a, b| (a == b) as i32),
+        F64Ne => binop!(stack, pop_f64, I32, |a, b| (a != b) as i32),
+        F64Lt => binop!(stack, pop_f64, I32, |a, b| (a < b) as i32),
+        F64Gt => binop!(stack, pop_f64, I32, |a, b| (a > b) as i32),
+        F64Le => binop!(stack, pop_f64, I32, |a, b| (a <= b) as i32),
+        F64Ge => binop!(stack, pop_f64, I32, |a, b| (a >= b) as i32),
+
+        I32Clz => unop!(stack, pop_i32, I32, |a| a.leading_zeros() as i32),
+        I32Ctz => unop!(stack, pop_i32, I32, |a| a.trailing_zeros() as i32),
+        I32Popcnt => unop!(stack, pop_i32, I32, |a| a.count_ones() as i32),
+        I32Add => binop!(stack, pop_i32, I32, |a, b| a.wrapping_add(b)),
+        I32Sub => binop!(stack, pop_i32, I32, |a, b| a.wrapping_sub(b)),
+        I32Mul => binop!(stack, pop_i32, I32, |a, b| a.wrapping_mul(b)),
+        I32DivS => binop!(stack, pop_i32, I32, |a, b| div_s32(a, b)?),
+        I32DivU => binop!(stack, pop_i32, I32, |a, b| {
+            (a as u32)
+                .checked_div(b as u32)
+                .ok_or(TrapKind::IntegerDivideByZero)? as i32
+        }),
+        I32RemS => binop!(stack, pop_i32, I32, |a, b| {
+            nonzero(b != 0)?;
+            a.wrapping_rem(b)
+        }),
+        I32RemU => binop!(stack, pop_i32, I32, |a, b| {
+            (a as u32)
+                .checked_rem(b as u32)
+                .ok_or(TrapKind::IntegerDivideByZero)? as i32
+        }),
+        I32And => binop!(stack, pop_i32, I32, |a, b| a & b),
+        I32Or => binop!(stack, pop_i32, I32, |a, b| a | b),
+        I32Xor => binop!(stack, pop_i32, I32, |a, b| a ^ b),
+        I32Shl => binop!(stack, pop_i32, I32, |a, b| a.wrapping_shl(b as u32)),
+        I32ShrS => binop!(stack, pop_i32, I32, |a, b| a.wrapping_shr(b as u32)),
+        I32ShrU => binop!(stack, pop_i32, I32, |a, b| {
+            (a as u32).wrapping_shr(b as u32) as i32
+        }),
+        I32Rotl => binop!(stack, pop_i32, I32, |a, b| a.rotate_left(b as u32)),
+        I32Rotr => binop!(stack, pop_i32, I32, |a, b| a.rotate_right(b as u32)),
+        I64Clz => unop!(stack, pop_i64, I64, |a| a.leading_zeros() as i64),
+        I64Ctz => unop!(stack, pop_i64, I64, |a| a.trailing_zeros() as i64),
+        I64Popcnt => unop!(stack, pop_i64, I64, |a| a.count_ones() as i64),
+        I64Add => binop!(stack, pop_i64, I64, |a, b| a.wrapping_add(b)),
+        I64Sub => binop!(stack, pop_i64, I64, |a, b| a.wrapping_sub(b)),
+        I64Mul => binop!(stack, pop_i64, I64, |a, b| a.wrapping_mul(b)),
+        I64DivS => binop!(stack, pop_i64, I64, |a, b| div_s64(a, b)?),
+        I64DivU => binop!(stack, pop_i64, I64, |a, b| {
+            (a as u64)
+                .checked_div(b as u64)
+                .ok_or(TrapKind::IntegerDivideByZero)? as i64
+        }),
+        I64RemS => binop!(stack, pop_i64, I64, |a, b| {
+            nonzero(b != 0)?;
+            a.wrapping_rem(b)
+        }),
+        I64RemU => binop!(stack, pop_i64, I64, |a, b| {
+            (a as u64)
+                .checked_rem(b as u64)
+                .ok_or(TrapKind::IntegerDivideByZero)? as i64
+        }),
+        I64And => binop!(stack, pop_i64, I64, |a, b| a & b),
+        I64Or => binop!(stack, pop_i64, I64, |a, b| a | b),
+        I64Xor => binop!(stack, pop_i64, I64, |a, b| a ^ b),
+        I64Shl => binop!(stack, pop_i64, I64, |a, b| a.wrapping_shl(b as u32)),
+        I64ShrS => binop!(stack, pop_i64, I64, |a, b| a.wrapping_shr(b as u32)),
+        I64ShrU => binop!(stack, pop_i64, I64, |a, b| {
+            (a as u64).wrapping_shr(b as u32) as i64
+        }),
+        I64Rotl => binop!(stack, pop_i64, I64, |a, b| a.rotate_left((b & 63) as u32)),
+        I64Rotr => binop!(stack, pop_i64, I64, |a, b| a.rotate_right((b & 63) as u32)),
+
+        F32Abs => unop!(stack, pop_f32, F32, |a| a.abs()),
+        F32Neg => unop!(stack, pop_f32, F32, |a| -a),
+        F32Ceil => unop!(stack, pop_f32, F32, |a| a.ceil()),
+        F32Floor => unop!(stack, pop_f32, F32, |a| a.floor()),
+        F32Trunc => unop!(stack, pop_f32, F32, |a| a.trunc()),
+        F32Nearest => unop!(stack, pop_f32, F32, |a| a.round_ties_even()),
+        F32Sqrt => unop!(stack, pop_f32, F32, |a| a.sqrt()),
+        F32Add => binop!(stack, pop_f32, F32, |a, b| a + b),
+        F32Sub => binop!(stack, pop_f32, F32, |a, b| a - b),
+        F32Mul => binop!(stack, pop_f32, F32, |a, b| a * b),
+        F32Div => binop!(stack, pop_f32, F32, |a, b| a / b),
+        F32Min => binop!(stack, pop_f32, F32, |a, b| min_f32(a, b)),
+        F32Max => binop!(stack, pop_f32, F32, |a, b| max_f32(a, b)),
+        F32Copysign => binop!(stack, pop_f32, F32, |a, b| a.copysign(b)),
+        F64Abs => unop!(stack, pop_f64, F64, |a| a.abs()),
+        F64Neg => unop!(stack, pop_f64, F64, |a| -a),
+        F64Ceil => unop!(stack, pop_f64, F64, |a| a.ceil()),
+        F64Floor => unop!(stack, pop_f64, F64, |a| a.floor()),
+        F64Trunc => unop!(stack, pop_f64, F64, |a| a.trunc()),
+        F64Nearest => unop!(stack, pop_f64, F64, |a| a.round_ties_even()),
+        F64Sqrt => unop!(stack, pop_f64, F64, |a| a.sqrt()),
+        F64Add => binop!(stack, pop_f64, F64, |a, b| a + b),
+        F64Sub => binop!(stack, pop_f64, F64, |a, b| a - b),
+        F64Mul => binop!(stack, pop_f64, F64, |a, b| a * b),
+        F64Div => binop!(stack, pop_f64, F64, |a, b| a / b),
+        F64Min => binop!(stack, pop_f64, F64, |a, b| min_f64(a, b)),
+        F64Max => binop!(stack, pop_f64, F64, |a, b| max_f64(a, b)),
+        F64Copysign => binop!(stack, pop_f64, F64, |a, b| a.copysign(b)),
+
+        I32WrapI64 => unop!(stack, pop_i64, I32, |a| a as i32),
+        I32TruncF32S => unop!(stack, pop_f32, I32, |a| trunc(a as f64, true, 32)? as i32),
+        I32TruncF32U => unop!(stack, pop_f32, I32, |a| trunc(a as f64, false, 32)? as u32
+            as i32),
+        I32TruncF64S => unop!(stack, pop_f64, I32, |a| trunc(a, true, 32)? as i32),
+        I32TruncF64U => unop!(stack, pop_f64, I32, |a| trunc(a, false, 32)? as u32 as i32),
+        I64ExtendI32S => unop!(stack, pop_i32, I64, |a| a as i64),
+        I64ExtendI32U => unop!(stack, pop_i32, I64, |a| a as u32 as i64),
+        I64TruncF32S => unop!(stack, pop_f32, I64, |a| trunc(a as f64, true, 64)? as i64),
+        I64TruncF32U => unop!(stack, pop_f32, I64, |a| trunc(a as f64, false, 64)? as u64
+            as i64),
+        I64TruncF64S => unop!(stack, pop_f64, I64, |a| trunc(a, true, 64)? as i64),
+        I64TruncF64U => unop!(stack, pop_f64, I64, |a| trunc(a, false, 64)? as u64 as i64),
+        F32ConvertI32S => unop!(stack, pop_i32, F32, |a| a as f32),
+        F32ConvertI32U => unop!(stack, pop_i32, F32, |a| a as u32 as f32),
+        F32ConvertI64S => unop!(stack, pop_i64, F32, |a| a as f32),
+        F32ConvertI64U => unop!(stack, pop_i64, F32, |a| a as u64 as f32),
+        F32DemoteF64 => unop!(stack, pop_f64, F32, |a| a as f32),
+        F64ConvertI32S => unop!(stack, pop_i32, F64, |a| a as f64),
+        F64ConvertI32U => unop!(stack, pop_i32, F64, |a| a as u32 as f64),
+        F64ConvertI64S => unop!(stack, pop_i64, F64, |a| a as f64),
+        F64ConvertI64U => unop!(stack, pop_i64, F64, |a| a as u64 as f64),
+        F64PromoteF32 => unop!(stack, pop_f32, F64, |a| a as f64),
+        I32ReinterpretF32 => unop!(stack, pop_f32, I32, |a| a.to_bits() as i32),
+        I64ReinterpretF64 => unop!(stack, pop_f64, I64, |a| a.to_bits() as i64),
+        F32ReinterpretI32 => unop!(stack, pop_i32, F32, |a| f32::from_bits(a as u32)),
+        F64ReinterpretI64 => unop!(stack, pop_i64, F64, |a| f64::from_bits(a as u64)),
+        I32Extend8S => unop!(stack, pop_i32, I32, |a| a as i8 as i32),
+        I32Extend16S => unop!(stack, pop_i32, I32, |a| a as i16 as i32),
+        I64Extend8S => unop!(stack, pop_i64, I64, |a| a as i8 as i64),
+        I64Extend16S => unop!(stack, pop_i64, I64, |a| a as i16 as i64),
+        I64Extend32S => unop!(stack, pop_i64, I64, |a| a as i32 as i64),
+        // Rust's casts saturate and send NaN to zero, as these do
+        I32TruncSatF32S => unop!(stack, pop_f32, I32, |a| a as i32),
+        I32TruncSatF32U => unop!(stack, pop_f32, I32, |a| a as u32 as i32),
+        I32TruncSatF64S => unop!(stack, pop_f64, I32, |a| a as i32),
+        I32TruncSatF64U => unop!(stack, pop_f64, I32, |a| a as u32 as i32),
+        I64TruncSatF32S => unop!(stack, pop_f32, I64, |a| a as i64),
+        I64TruncSatF32U => unop!(stack, pop_f32, I64, |a| a as u64 as i64),
+        I64TruncSatF64S => unop!(stack, pop_f64, I64, |a| a as i64),
+        I64TruncSatF64U => unop!(stack, pop_f64, I64, |a| a as u64 as i64),
+    }
+    Ok(())
+}
+
+macro_rules! min_max {
+    ($min:ident, $max:ident, $t:ty) => {
+        /// The lesser of `a` and `b`, which is NaN if either is.
+        pub(crate) fn $min(a: $t, b: $t) -> $t {
+            if a.is_nan() || b.is_nan() {
+                a + b
+            } else if a == b {
+                // Only differs from either for zeros, where -0 is the lesser
+                <$t>::from_bits(a.to_bits() | b.to_bits())
+            } else {
+                a.min(b)
+            }
+        }
+
+        /// The greater of `a` and `b`, which is NaN if either is.
+        pub(crate) fn $max(a: $t, b: $t) -> $t {
+            if a.is_nan() || b.is_nan() {
+                a + b
+            } else if a == b {
+                <$t>::from_bits(a.to_bits() & b.to_bits())
+            } else {
+                a.max(b)
+            }
+        }
+    };
+}
+
+min_max!(min_f32, max_f32, f32);
+min_max!(min_f64, max_f64, f64);
+
+fn nonzero(ok: bool) -> Result<(), TrapKind> {
+    if ok {
+        Ok(())
+    } else {
+        Err(TrapKind::IntegerDivideByZero)
+    }
+}
+
+fn div_s32(a: i32, b: i32) -> Result<i32, TrapKind> {
+    nonzero(b != 0)?;
+    a.checked_div(b).ok_or(TrapKind::IntegerOverflow)
+}
+
+fn div_s64(a: i64, b: i64) -> Result<i64, TrapKind> {
+    nonzero(b != 0)?;
+    a.checked_div(b).ok_or(TrapKind::IntegerOverflow)
+}
+
+/// Truncates `x` towards zero, trapping unless the result fits a `bits`-wide
+/// integer. Every f32 is exactly an f64, and the bounds are powers of two,
+/// so checking in f64 is exact.
+fn trunc(x: f64, signed: bool, bits: u32) -> Result<f64, TrapKind> {
+    if x.is_nan() {
+        return Err(TrapKind::InvalidConversion);
+    }
+    let t = x.trunc();
+    let (min, max) = if signed {
+        (-(2f64.powi(bits as i32 - 1)), 2f64.powi(bits as i32 - 1))
+    } else {
+        (0.0, 2f64.powi(bits as i32))
+    };
+    if t >= min && t < max {
+        Ok(t)
+    } else {
+        Err(TrapKind::IntegerOverflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(op: NumericInstr, args: &[Value]) -> Result<Value, TrapKind> {
+        let mut stack = args.to_vec();
+        eval(op, &mut stack)?;
+        Ok(stack.pop().unwrap())
+    }
+
+    #[test]
+    fn test_integer_traps() {
+        use NumericInstr::*;
+        let (min, neg) = (Value::I32(i32::MIN), Value::I32(-1));
+        assert_eq!(run(I32DivS, &[min, neg]), Err(TrapKind::IntegerOverflow));
+        assert_eq!(run(I32RemS, &[min, neg]), Ok(Value::I32(0)));
+        assert_eq!(
+            run(I64DivU, &[Value::I64(1), Value::I64(0)]),
+            Err(TrapKind::IntegerDivideByZero)
+        );
+        assert_eq!(
+            run(I32ShrU, &[neg, Value::I32(33)]),
+            Ok(Value::I32(i32::MAX))
+        );
+    }
+
+    #[test]
+    fn test_truncation() {
+        use NumericInstr::*;
+        assert_eq!(
+            run(I32TruncF32S, &[Value::F32(f32::NAN)]),
+            Err(TrapKind::InvalidConversion)
+        );
+        assert_eq!(
+            run(I32TruncF64S, &[Value::F64(2147483648.0)]),
+            Err(TrapKind::IntegerOverflow)
+        );
+        assert_eq!(
+            run(I32TruncF64S, &[Value::F64(-2147483648.9)]),
+            Ok(Value::I32(i32::MIN))
+        );
+        assert_eq!(run(I32TruncF32U, &[Value::F32(-0.9)]), Ok(Value::I32(0)));
+        assert_eq!(
+            run(I64TruncF64U, &[Value::F64(-1.0)]),
+            Err(TrapKind::IntegerOverflow)
+        );
+        assert_eq!(run(I64TruncSatF64U, &[Value::F64(-1.0)]), Ok(Value::I64(0)));
+    }
+
+    #[test]
+    fn test_min_max() {
+        use NumericInstr::*;
+        let min = run(F32Min, &[Value::F32(0.0), Value::F32(-0.0)]).unwrap();
+        assert!(min.same_bits(&Value::F32(-0.0)));
+        let max = run(F64Max, &[Value::F64(-0.0), Value::F64(0.0)]).unwrap();
+        assert!(max.same_bits(&Value::F64(0.0)));
+        let Ok(Value::F64(nan)) = run(F64Min, &[Value::F64(1.0), Value::F64(f64::NAN)]) else {
+            panic!("expected an f64");
+        };
+        assert!(nan.is_nan());
+        assert_eq!(run(F32Nearest, &[Value::F32(2.5)]), Ok(Value::F32(2.0)));
+    }
+}
@@ -0,0 +1,633 @@
+use crate::instr::simd::{SimdImm, SimdOp};
+use crate::runtime::memory::Memory;
+use crate::runtime::numeric::{
+    max_f32, max_f64, min_f32, min_f64, pop_f32, pop_f64, pop_i32, pop_i64, pop_v128,
+};
+use crate::runtime::trap::TrapKind;
+use crate::runtime::value::Value;
+
+// Conversions between a vector and its lanes, the first lane being the
+// lowest-addressed bytes
+macro_rules! lanes {
+    ($($to:ident, $from:ident: $t:ty, $n:literal;)*) => {$(
+        fn $to(v: u128) -> [$t; $n] {
+            const WIDTH: usize = 16 / $n;
+            let bytes = v.to_le_bytes();
+            std::array::from_fn(|i| {
+                let mut lane = [0; WIDTH];
+                lane.copy_from_slice(&bytes[i * WIDTH..(i + 1) * WIDTH]);
+                <$t>::from_le_bytes(lane)
+            })
+        }
+
+        fn $from(lanes: [$t; $n]) -> u128 {
+            const WIDTH: usize = 16 / $n;
+            let mut bytes = [0; 16];
+            for (i, lane) in lanes.iter().enumerate() {
+                bytes[i * WIDTH..(i + 1) * WIDTH].copy_from_slice(&lane.to_le_bytes());
+            }
+            u128::from_le_bytes(bytes)
+        }
+    )*};
+}
+
+lanes! {
+    i8x16, from_i8x16: i8, 16;
+    u8x16, from_u8x16: u8, 16;
+    i16x8, from_i16x8: i16, 8;
+    u16x8, from_u16x8: u16, 8;
+    i32x4, from_i32x4: i32, 4;
+    u32x4, from_u32x4: u32, 4;
+    i64x2, from_i64x2: i64, 2;
+    u64x2, from_u64x2: u64, 2;
+    f32x4, from_f32x4: f32, 4;
+    f64x2, from_f64x2: f64, 2;
+}
+
+macro_rules! unop {
+    ($stack:ident, $to:ident, $from:ident, |$a:ident| $e:expr) => {{
+        let a = $to(pop_v128($stack));
+        $stack.push(Value::V128($from(a.map(|$a| $e))));
+    }};
+}
+
+macro_rules! binop {
+    ($stack:ident, $to:ident, $from:ident, |$a:ident, $b:ident| $e:expr) => {{
+        let b = $to(pop_v128($stack));
+        let a = $to(pop_v128($stack));
+        $stack.push(Value::V128($from(std::array::from_fn(|i| {
+            let ($a, $b) = (a[i], b[i]);
+            $e
+        }))));
+    }};
+}
+
+// Lanes are all ones where the comparison holds, and zeros where it doesn't
+macro_rules! compare {
+    ($stack:ident, $to:ident, $from:ident, |$a:ident, $b:ident| $e:expr) => {
+        binop!($stack, $to, $from, |$a, $b| if $e { -1 } else { 0 })
+    };
+}
+
+// The shift count is taken modulo the lane width, as `wrapping_shl` does
+macro_rules! shift {
+    ($stack:ident, $to:ident, $from:ident, $method:ident) => {{
+        let n = pop_i32($stack) as u32;
+        unop!($stack, $to, $from, |a| a.$method(n))
+    }};
+}
+
+// Lanes of the result are built from `n` lanes of the operands each
+macro_rules! widen {
+    ($stack:ident, $to:ident, $from:ident, |$a:ident, $i:ident| $e:expr) => {{
+        let $a = $to(pop_v128($stack));
+        $stack.push(Value::V128($from(std::array::from_fn(|$i| $e))));
+    }};
+    ($stack:ident, $to:ident, $from:ident, |$a:ident, $b:ident, $i:ident| $e:expr) => {{
+        let $b = $to(pop_v128($stack));
+        let $a = $to(pop_v128($stack));
+        $stack.push(Value::V128($from(std::array::from_fn(|$i| $e))));
+    }};
+}
+
+macro_rules! all_true {
+    ($stack:ident, $to:ident) => {{
+        let a = $to(pop_v128($stack));
+        $stack.push(Value::I32(a.iter().all(|&x| x != 0) as i32));
+    }};
+}
+
+macro_rules! bitmask {
+    ($stack:ident, $to:ident) => {{
+        let a = $to(pop_v128($stack));
+        let mask = a
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (i, &x)| mask | (((x < 0) as i32) << i));
+        $stack.push(Value::I32(mask));
+    }};
+}
+
+macro_rules! extract {
+    ($stack:ident, $imm:ident, $to:ident, $variant:ident, $t:ty) => {{
+        let a = $to(pop_v128($stack));
+        $stack.push(Value::$variant(a[lane($imm)] as $t));
+    }};
+}
+
+macro_rules! replace {
+    ($stack:ident, $imm:ident, $pop:ident, $to:ident, $from:ident, $t:ty) => {{
+        let x = $pop($stack);
+        let mut a = $to(pop_v128($stack));
+        a[lane($imm)] = x as $t;
+        $stack.push(Value::V128($from(a)));
+    }};
+}
+
+fn lane(imm: SimdImm) -> usize {
+    match imm {
+        SimdImm::Lane(lane) | SimdImm::MemArgLane(_, lane) => lane as usize,
+        imm => panic!("expected a lane index, found {:?}", imm),
+    }
+}
+
+/// The bits of `a` where `mask` is set and of `b` where it isn't.
+fn bitselect(a: u128, b: u128, mask: u128) -> u128 {
+    (a & mask) | (b & !mask)
+}
+
+/// Lane `i` of the result is lane `s[i]` of `a`, or zero if there is no
+/// such lane.
+fn swizzle(a: u128, s: u128) -> u128 {
+    let (a, s) = (u8x16(a), u8x16(s));
+    from_u8x16(s.map(|i| a.get(i as usize).copied().unwrap_or(0)))
+}
+
+fn q15mulr(a: i16, b: i16) -> i16 {
+    ((i32::from(a) * i32::from(b) + 0x4000) >> 15).clamp(i16::MIN.into(), i16::MAX.into()) as i16
+}
+
+/// Applies a vector instruction that doesn't access memory to the operands
+/// on top of `stack`. None of them trap.
+pub(crate) fn eval(op: SimdOp, imm: SimdImm, stack: &mut Vec<Value>) {
+    use SimdOp::*;
+    match op {
+        V128Const => {
+            let SimdImm::Bytes(bytes) = imm else {
+                panic!("expected the bytes of v128.const, found {:?}", imm);
+            };
+            stack.push(Value::V128(u128::from_le_bytes(bytes)));
+        }
+        I8x16Shuffle => {
+            let SimdImm::Bytes(indices) = imm else {
+                panic!("expected the lane indices of a shuffle, found {:?}", imm);
+            };
+            let b = u8x16(pop_v128(stack));
+            let a = u8x16(pop_v128(stack));
+            let lanes = indices.map(|i| match i {
+                0..16 => a[i as usize],
+                _ => b[i as usize - 16],
+            });
+            stack.push(Value::V128(from_u8x16(lanes)));
+        }
+        I8x16Swizzle | I8x16RelaxedSwizzle => {
+            let s = pop_v128(stack);
+            let a = pop_v128(stack);
+            stack.push(Value::V128(swizzle(a, s)));
+        }
+
+        I8x16Splat => {
+            let x = pop_i32(stack);
+            stack.push(Value::V128(from_i8x16([x as i8; 16])));
+        }
+        I16x8Splat => {
+            let x = pop_i32(stack);
+            stack.push(Value::V128(from_i16x8([x as i16; 8])));
+        }
+        I32x4Splat => {
+            let x = pop_i32(stack);
+            stack.push(Value::V128(from_i32x4([x; 4])));
+        }
+        I64x2Splat => {
+            let x = pop_i64(stack);
+            stack.push(Value::V128(from_i64x2([x; 2])));
+        }
+        F32x4Splat => {
+            let x = pop_f32(stack);
+            stack.push(Value::V128(from_f32x4([x; 4])));
+        }
+        F64x2Splat => {
+            let x = pop_f64(stack);
+            stack.push(Value::V128(from_f64x2([x; 2])));
+        }
+
+        I8x16ExtractLaneS => extract!(stack, imm, i8x16, I32, i32),
+        I8x16ExtractLaneU => extract!(stack, imm, u8x16, I32, i32),
+        I16x8ExtractLaneS => extract!(stack, imm, i16x8, I32, i32),
+        I16x8ExtractLaneU => extract!(stack, imm, u16x8, I32, i32),
+        I32x4ExtractLane => extract!(stack, imm, i32x4, I32, i32),
+        I64x2ExtractLane => extract!(stack, imm, i64x2, I64, i64),
+        F32x4ExtractLane => extract!(stack, imm, f32x4, F32, f32),
+        F64x2ExtractLane => extract!(stack, imm, f64x2, F64, f64),
+        I8x16ReplaceLane => replace!(stack, imm, pop_i32, i8x16, from_i8x16, i8),
+        I16x8ReplaceLane => replace!(stack, imm, pop_i32, i16x8, from_i16x8, i16),
+        I32x4ReplaceLane => replace!(stack, imm, pop_i32, i32x4, from_i32x4, i32),
+        I64x2ReplaceLane => replace!(stack, imm, pop_i64, i64x2, from_i64x2, i64),
+        F32x4ReplaceLane => replace!(stack, imm, pop_f32, f32x4, from_f32x4, f32),
+        F64x2ReplaceLane => replace!(stack, imm, pop_f64, f64x2, from_f64x2, f64),
+
+        I8x16Eq => compare!(stack, i8x16, from_i8x16, |a, b| a == b),
+        I8x16Ne => compare!(stack, i8x16, from_i8x16, |a, b| a != b),
+        I8x16LtS => compare!(stack, i8x16, from_i8x16, |a, b| a < b),
+        I8x16LtU => compare!(stack, u8x16, from_i8x16, |a, b| a < b),
+        I8x16GtS => compare!(stack, i8x16, from_i8x16, |a, b| a > b),
+        I8x16GtU => compare!(stack, u8x16, from_i8x16, |a, b| a > b),
+        I8x16LeS => compare!(stack, i8x16, from_i8x16, |a, b| a <= b),
+        I8x16LeU => compare!(stack, u8x16, from_i8x16, |a, b| a <= b),
+        I8x16GeS => compare!(stack, i8x16, from_i8x16, |a, b| a >= b),
+        I8x16GeU => compare!(stack, u8x16, from_i8x16, |a, b| a >= b),
+        I16x8Eq => compare!(stack, i16x8, from_i16x8, |a, b| a == b),
+        I16x8Ne => compare!(stack, i16x8, from_i16x8, |a, b| a != b),
+        I16x8LtS => compare!(stack, i16x8, from_i16x8, |a, b| a < b),
+        I16x8LtU => compare!(stack, u16x8, from_i16x8, |a, b| a < b),
+        I16x8GtS => compare!(stack, i16x8, from_i16x8, |a, b| a > b),
+        I16x8GtU => compare!(stack, u16x8, from_i16x8, |a, b| a > b),
+        I16x8LeS => compare!(stack, i16x8, from_i16x8, |a, b| a <= b),
+        I16x8LeU => compare!(stack, u16x8, from_i16x8, |a, b| a <= b),
+        I16x8GeS => compare!(stack, i16x8, from_i16x8, |a, b| a >= b),
+        I16x8GeU => compare!(stack, u16x8, from_i16x8, |a, b| a >= b),
+        I32x4Eq => compare!(stack, i32x4, from_i32x4, |a, b| a == b),
+        I32x4Ne => compare!(stack, i32x4, from_i32x4, |a, b| a != b),
+        I32x4LtS => compare!(stack, i32x4, from_i32x4, |a, b| a < b),
+        I32x4LtU => compare!(stack, u32x4, from_i32x4, |a, b| a < b),
+        I32x4GtS => compare!(stack, i32x4, from_i32x4, |a, b| a > b),
+        I32x4GtU => compare!(stack, u32x4, from_i32x4, |a, b| a > b),
+        I32x4LeS => compare!(stack, i32x4, from_i32x4, |a, b| a <= b),
+        I32x4LeU => compare!(stack, u32x4, from_i32x4, |a, b| a <= b),
+        I32x4GeS => compare!(stack, i32x4, from_i32x4, |a, b| a >= b),
+        I32x4GeU => compare!(stack, u32x4, from_i32x4, |a, b| a >= b),
+        I64x2Eq => compare!(stack, i64x2, from_i64x2, |a, b| a == b),
+        I64x2Ne => compare!(stack, i64x2, from_i64x2, |a, b| a != b),
+        I64x2LtS => compare!(stack, i64x2, from_i64x2, |a, b| a < b),
+        I64x2GtS => compare!(stack, i64x2, from_i64x2, |a, b| a > b),
+        I64x2LeS => compare!(stack, i64x2, from_i64x2, |a, b| a <= b),
+        I64x2GeS => compare!(stack, i64x2, from_i64x2, |a, b| a >= b),
+        F32x4Eq => compare!(stack, f32x4, from_i32x4, |a, b| a == b),
+        F32x4Ne => compare!(stack, f32x4, from_i32x4, |a, b| a != b),
+        F32x4Lt => compare!(stack, f32x4, from_i32x4, |a, b| a < b),
+        F32x4Gt => compare!(stack, f32x4, from_i32x4, |a, b| a > b),
+        F32x4Le => compare!(stack, f32x4, from_i32x4, |a, b| a <= b),
+        F32x4Ge => compare!(stack, f32x4, from_i32x4, |a, b| a >= b),
+        F64x2Eq => compare!(stack, f64x2, from_i64x2, |a, b| a == b),
+        F64x2Ne => compare!(stack, f64x2, from_i64x2, |a, b| a != b),
+        F64x2Lt => compare!(stack, f64x2, from_i64x2, |a, b| a < b),
+        F64x2Gt => compare!(stack, f64x2, from_i64x2, |a, b| a > b),
+        F64x2Le => compare!(stack, f64x2, from_i64x2, |a, b| a <= b),
+        F64x2Ge => compare!(stack, f64x2, from_i64x2, |a, b| a >= b),
+
+        V128Not => {
+            let a = pop_v128(stack);
+            stack.push(Value::V128(!a));
+        }
+        V128And => binop!(stack, u64x2, from_u64x2, |a, b| a & b),
+        V128Andnot => binop!(stack, u64x2, from_u64x2, |a, b| a & !b),
+        V128Or => binop!(stack, u64x2, from_u64x2, |a, b| a | b),
+        V128Xor => binop!(stack, u64x2, from_u64x2, |a, b| a ^ b),
+        V128Bitselect
+        | I8x16RelaxedLaneselect
+        | I16x8RelaxedLaneselect
+        | I32x4RelaxedLaneselect
+        | I64x2RelaxedLaneselect => {
+            let mask = pop_v128(stack);
+            let b = pop_v128(stack);
+            let a = pop_v128(stack);
+            stack.push(Value::V128(bitselect(a, b, mask)));
+        }
+        V128AnyTrue => {
+            let a = pop_v128(stack);
+            stack.push(Value::I32((a != 0) as i32));
+        }
+
+        I8x16Abs => unop!(stack, i8x16, from_i8x16, |a| a.wrapping_abs()),
+        I8x16Neg => unop!(stack, i8x16, from_i8x16, |a| a.wrapping_neg()),
+        I8x16Popcnt => unop!(stack, u8x16, from_u8x16, |a| a.count_ones() as u8),
+        I8x16AllTrue => all_true!(stack, i8x16),
+        I8x16Bitmask => bitmask!(stack, i8x16),
+        I8x16NarrowI16x8S => widen!(stack, i16x8, from_i8x16, |a, b, i| {
+            let x = if i < 8 { a[i] } else { b[i - 8] };
+            x.clamp(i8::MIN.into(), i8::MAX.into()) as i8
+        }),
+        I8x16NarrowI16x8U => widen!(stack, i16x8, from_u8x16, |a, b, i| {
+            let x = if i < 8 { a[i] } else { b[i - 8] };
+            x.clamp(0, u8::MAX.into()) as u8
+        }),
+        I8x16Shl => shift!(stack, i8x16, from_i8x16, wrapping_shl),
+        I8x16ShrS => shift!(stack, i8x16, from_i8x16, wrapping_shr),
+        I8x16ShrU => shift!(stack, u8x16, from_u8x16, wrapping_shr),
+        I8x16Add => binop!(stack, i8x16, from_i8x16, |a, b| a.wrapping_add(b)),
+        I8x16AddSatS => binop!(stack, i8x16, from_i8x16, |a, b| a.saturating_add(b)),
+        I8x16AddSatU => binop!(stack, u8x16, from_u8x16, |a, b| a.saturating_add(b)),
+        I8x16Sub => binop!(stack, i8x16, from_i8x16, |a, b| a.wrapping_sub(b)),
+        I8x16SubSatS => binop!(stack, i8x16, from_i8x16, |a, b| a.saturating_sub(b)),
+        I8x16SubSatU => binop!(stack, u8x16, from_u8x16, |a, b| a.saturating_sub(b)),
+        I8x16MinS => binop!(stack, i8x16, from_i8x16, |a, b| a.min(b)),
+        I8x16MinU => binop!(stack, u8x16, from_u8x16, |a, b| a.min(b)),
+        I8x16MaxS => binop!(stack, i8x16, from_i8x16, |a, b| a.max(b)),
+        I8x16MaxU => binop!(stack, u8x16, from_u8x16, |a, b| a.max(b)),
+        I8x16AvgrU => binop!(stack, u8x16, from_u8x16, |a, b| {
+            (u16::from(a) + u16::from(b)).div_ceil(2) as u8
+        }),
+
+        I16x8ExtaddPairwiseI8x16S => widen!(stack, i8x16, from_i16x8, |a, i| {
+            i16::from(a[2 * i]) + i16::from(a[2 * i + 1])
+        }),
+        I16x8ExtaddPairwiseI8x16U => widen!(stack, u8x16, from_i16x8, |a, i| {
+            i16::from(a[2 * i]) + i16::from(a[2 * i + 1])
+        }),
+        I32x4ExtaddPairwiseI16x8S => widen!(stack, i16x8, from_i32x4, |a, i| {
+            i32::from(a[2 * i]) + i32::from(a[2 * i + 1])
+        }),
+        I32x4ExtaddPairwiseI16x8U => widen!(stack, u16x8, from_i32x4, |a, i| {
+            i32::from(a[2 * i]) + i32::from(a[2 * i + 1])
+        }),
+
+        I16x8Abs => unop!(stack, i16x8, from_i16x8, |a| a.wrapping_abs()),
+        I16x8Neg => unop!(stack, i16x8, from_i16x8, |a| a.wrapping_neg()),
+        I16x8Q15mulrSatS | I16x8RelaxedQ15mulrS => {
+            binop!(stack, i16x8, from_i16x8, |a, b| q15mulr(a, b))
+        }
+        I16x8AllTrue => all_true!(stack, i16x8),
+        I16x8Bitmask => bitmask!(stack, i16x8),
+        I16x8NarrowI32x4S => widen!(stack, i32x4, from_i16x8, |a, b, i| {
+            let x = if i < 4 { a[i] } else { b[i - 4] };
+            x.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+        }),
+        I16x8NarrowI32x4U => widen!(stack, i32x4, from_u16x8, |a, b, i| {
+            let x = if i < 4 { a[i] } else { b[i - 4] };
+            x.clamp(0, u16::MAX.into()) as u16
+        }),
+        I16x8ExtendLowI8x16S => widen!(stack, i8x16, from_i16x8, |a, i| a[i].into()),
+        I16x8ExtendHighI8x16S => widen!(stack, i8x16, from_i16x8, |a, i| a[i + 8].into()),
+        I16x8ExtendLowI8x16U => widen!(stack, u8x16, from_i16x8, |a, i| a[i].into()),
+        I16x8ExtendHighI8x16U => widen!(stack, u8x16, from_i16x8, |a, i| a[i + 8].into()),
+        I16x8Shl => shift!(stack, i16x8, from_i16x8, wrapping_shl),
+        I16x8ShrS => shift!(stack, i16x8, from_i16x8, wrapping_shr),
+        I16x8ShrU => shift!(stack, u16x8, from_u16x8, wrapping_shr),
+        I16x8Add => binop!(stack, i16x8, from_i16x8, |a, b| a.wrapping_add(b)),
+        I16x8AddSatS => binop!(stack, i16x8, from_i16x8, |a, b| a.saturating_add(b)),
+        I16x8AddSatU => binop!(stack, u16x8, from_u16x8, |a, b| a.saturating_add(b)),
+        I16x8Sub => binop!(stack, i16x8, from_i16x8, |a, b| a.wrapping_sub(b)),
+        I16x8SubSatS => binop!(stack, i16x8, from_i16x8, |a, b| a.saturating_sub(b)),
+        I16x8SubSatU => binop!(stack, u16x8, from_u16x8, |a, b| a.saturating_sub(b)),
+        I16x8Mul => binop!(stack, i16x8, from_i16x8, |a, b| a.wrapping_mul(b)),
+        I16x8MinS => binop!(stack, i16x8, from_i16x8, |a, b| a.min(b)),
+        I16x8MinU => binop!(stack, u16x8, from_u16x8, |a, b| a.min(b)),
+        I16x8MaxS => binop!(stack, i16x8, from_i16x8, |a, b| a.max(b)),
+        I16x8MaxU => binop!(stack, u16x8, from_u16x8, |a, b| a.max(b)),
+        I16x8AvgrU => binop!(stack, u16x8, from_u16x8, |a, b| {
+            (u32::from(a) + u32::from(b)).div_ceil(2) as u16
+        }),
+        I16x8ExtmulLowI8x16S => widen!(stack, i8x16, from_i16x8, |a, b, i| {
+            i16::from(a[i]) * i16::from(b[i])
+        }),
+        I16x8ExtmulHighI8x16S => widen!(stack, i8x16, from_i16x8, |a, b, i| {
+            i16::from(a[i + 8]) * i16::from(b[i + 8])
+        }),
+        I16x8ExtmulLowI8x16U => widen!(stack, u8x16, from_u16x8, |a, b, i| {
+            u16::from(a[i]) * u16::from(b[i])
+        }),
+        I16x8ExtmulHighI8x16U => widen!(stack, u8x16, from_u16x8, |a, b, i| {
+            u16::from(a[i + 8]) * u16::from(b[i + 8])
+        }),
+
+        I32x4Abs => unop!(stack, i32x4, from_i32x4, |a| a.wrapping_abs()),
+        I32x4Neg => unop!(stack, i32x4, from_i32x4, |a| a.wrapping_neg()),
+        I32x4AllTrue => all_true!(stack, i32x4),
+        I32x4Bitmask => bitmask!(stack, i32x4),
+        I32x4ExtendLowI16x8S => widen!(stack, i16x8, from_i32x4, |a, i| a[i].into()),
+        I32x4ExtendHighI16x8S => widen!(stack, i16x8, from_i32x4, |a, i| a[i + 4].into()),
+        I32x4ExtendLowI16x8U => widen!(stack, u16x8, from_i32x4, |a, i| a[i].into()),
+        I32x4ExtendHighI16x8U => widen!(stack, u16x8, from_i32x4, |a, i| a[i + 4].into()),
+        I32x4Shl => shift!(stack, i32x4, from_i32x4, wrapping_shl),
+        I32x4ShrS => shift!(stack, i32x4, from_i32x4, wrapping_shr),
+        I32x4ShrU => shift!(stack, u32x4, from_u32x4, wrapping_shr),
+        I32x4Add => binop!(stack, i32x4, from_i32x4, |a, b| a.wrapping_add(b)),
+        I32x4Sub => binop!(stack, i32x4, from_i32x4, |a, b| a.wrapping_sub(b)),
+        I32x4Mul => binop!(stack, i32x4, from_i32x4, |a, b| a.wrapping_mul(b)),
+        I32x4MinS => binop!(stack, i32x4, from_i32x4, |a, b| a.min(b)),
+        I32x4MinU => binop!(stack, u32x4, from_u32x4, |a, b| a.min(b)),
+        I32x4MaxS => binop!(stack, i32x4, from_i32x4, |a, b| a.max(b)),
+        I32x4MaxU => binop!(stack, u32x4, from_u32x4, |a, b| a.max(b)),
+        // Only -32768 * -32768 twice overflows, and wraps as the spec says
+        I32x4DotI16x8S => widen!(stack, i16x8, from_i32x4, |a, b, i| {
+            (i32::from(a[2 * i]) * i32::from(b[2 * i]))
+                .wrapping_add(i32::from(a[2 * i + 1]) * i32::from(b[2 * i + 1]))
+        }),
+        I32x4ExtmulLowI16x8S => widen!(stack, i16x8, from_i32x4, |a, b, i| {
+            i32::from(a[i]) * i32::from(b[i])
+        }),
+        I32x4ExtmulHighI16x8S => widen!(stack, i16x8, from_i32x4, |a, b, i| {
+            i32::from(a[i + 4]) * i32::from(b[i + 4])
+        }),
+        I32x4ExtmulLowI16x8U => widen!(stack, u16x8, from_u32x4, |a, b, i| {
+            u32::from(a[i]) * u32::from(b[i])
+        }),
+        I32x4ExtmulHighI16x8U => widen!(stack, u16x8, from_u32x4, |a, b, i| {
+            u32::from(a[i + 4]) * u32::from(b[i + 4])
+        }),
+
+        I64x2Abs => unop!(stack, i64x2, from_i64x2, |a| a.wrapping_abs()),
+        I64x2Neg => unop!(stack, i64x2, from_i64x2, |a| a.wrapping_neg()),
+        I64x2AllTrue => all_true!(stack, i64x2),
+        I64x2Bitmask => bitmask!(stack, i64x2),
+        I64x2ExtendLowI32x4S => widen!(stack, i32x4, from_i64x2, |a, i| a[i].into()),
+        I64x2ExtendHighI32x4S => widen!(stack, i32x4, from_i64x2, |a, i| a[i + 2].into()),
+        I64x2ExtendLowI32x4U => widen!(stack, u32x4, from_i64x2, |a, i| a[i].into()),
+        I64x2ExtendHighI32x4U => widen!(stack, u32x4, from_i64x2, |a, i| a[i + 2].into()),
+        I64x2Shl => shift!(stack, i64x2, from_i64x2, wrapping_shl),
+        I64x2ShrS => shift!(stack, i64x2, from_i64x2, wrapping_shr),
+        I64x2ShrU => shift!(stack, u64x2, from_u64x2, wrapping_shr),
+        I64x2Add => binop!(stack, i64x2, from_i64x2, |a, b| a.wrapping_add(b)),
+        I64x2Sub => binop!(stack, i64x2, from_i64x2, |a, b| a.wrapping_sub(b)),
+        I64x2Mul => binop!(stack, i64x2, from_i64x2, |a, b| a.wrapping_mul(b)),
+        I64x2ExtmulLowI32x4S => widen!(stack, i32x4, from_i64x2, |a, b, i| {
+            i64::from(a[i]) * i64::from(b[i])
+        }),
+        I64x2ExtmulHighI32x4S => widen!(stack, i32x4, from_i64x2, |a, b, i| {
+            i64::from(a[i + 2]) * i64::from(b[i + 2])
+        }),
+        I64x2ExtmulLowI32x4U => widen!(stack, u32x4, from_u64x2, |a, b, i| {
+            u64::from(a[i]) * u64::from(b[i])
+        }),
+        I64x2ExtmulHighI32x4U => widen!(stack, u32x4, from_u64x2, |a, b, i| {
+            u64::from(a[i + 2]) * u64::from(b[i + 2])
+        }),
+
+        F32x4Ceil => unop!(stack, f32x4, from_f32x4, |a| a.ceil()),
+        F32x4Floor => unop!(stack, f32x4, from_f32x4, |a| a.floor()),
+        F32x4Trunc => unop!(stack, f32x4, from_f32x4, |a| a.trunc()),
+        F32x4Nearest => unop!(stack, f32x4, from_f32x4, |a| a.round_ties_even()),
+        F32x4Abs => unop!(stack, f32x4, from_f32x4, |a| a.abs()),
+        F32x4Neg => unop!(stack, f32x4, from_f32x4, |a| -a),
+        F32x4Sqrt => unop!(stack, f32x4, from_f32x4, |a| a.sqrt()),
+        F32x4Add => binop!(stack, f32x4, from_f32x4, |a, b| a + b),
+        F32x4Sub => binop!(stack, f32x4, from_f32x4, |a, b| a - b),
+        F32x4Mul => binop!(stack, f32x4, from_f32x4, |a, b| a * b),
+        F32x4Div => binop!(stack, f32x4, from_f32x4, |a, b| a / b),
+        F32x4Min | F32x4RelaxedMin => binop!(stack, f32x4, from_f32x4, |a, b| min_f32(a, b)),
+        F32x4Max | F32x4RelaxedMax => binop!(stack, f32x4, from_f32x4, |a, b| max_f32(a, b)),
+        F32x4Pmin => binop!(stack, f32x4, from_f32x4, |a, b| if b < a { b } else { a }),
+        F32x4Pmax => binop!(stack, f32x4, from_f32x4, |a, b| if a < b { b } else { a }),
+        F64x2Ceil => unop!(stack, f64x2, from_f64x2, |a| a.ceil()),
+        F64x2Floor => unop!(stack, f64x2, from_f64x2, |a| a.floor()),
+        F64x2Trunc => unop!(stack, f64x2, from_f64x2, |a| a.trunc()),
+        F64x2Nearest => unop!(stack, f64x2, from_f64x2, |a| a.round_ties_even()),
+        F64x2Abs => unop!(stack, f64x2, from_f64x2, |a| a.abs()),
+        F64x2Neg => unop!(stack, f64x2, from_f64x2, |a| -a),
+        F64x2Sqrt => unop!(stack, f64x2, from_f64x2, |a| a.sqrt()),
+        F64x2Add => binop!(stack, f64x2, from_f64x2, |a, b| a + b),
+        F64x2Sub => binop!(stack, f64x2, from_f64x2, |a, b| a - b),
+        F64x2Mul => binop!(stack, f64x2, from_f64x2, |a, b| a * b),
+        F64x2Div => binop!(stack, f64x2, from_f64x2, |a, b| a / b),
+        F64x2Min | F64x2RelaxedMin => binop!(stack, f64x2, from_f64x2, |a, b| min_f64(a, b)),
+        F64x2Max | F64x2RelaxedMax => binop!(stack, f64x2, from_f64x2, |a, b| max_f64(a, b)),
+        F64x2Pmin => binop!(stack, f64x2, from_f64x2, |a, b| if b < a { b } else { a }),
+        F64x2Pmax => binop!(stack, f64x2, from_f64x2, |a, b| if a < b { b } else { a }),
+
+        // Rust's casts saturate and send NaN to zero, as these do
+        I32x4TruncSatF32x4S | I32x4RelaxedTruncF32x4S => {
+            unop!(stack, f32x4, from_i32x4, |a| a as i32)
+        }
+        I32x4TruncSatF32x4U | I32x4RelaxedTruncF32x4U => {
+            unop!(stack, f32x4, from_u32x4, |a| a as u32)
+        }
+        I32x4TruncSatF64x2SZero | I32x4RelaxedTruncF64x2SZero => {
+            widen!(stack, f64x2, from_i32x4, |a, i| a
+                .get(i)
+                .map_or(0, |&x| x as i32))
+        }
+        I32x4TruncSatF64x2UZero | I32x4RelaxedTruncF64x2UZero => {
+            widen!(stack, f64x2, from_u32x4, |a, i| a
+                .get(i)
+                .map_or(0, |&x| x as u32))
+        }
+        F32x4ConvertI32x4S => unop!(stack, i32x4, from_f32x4, |a| a as f32),
+        F32x4ConvertI32x4U => unop!(stack, u32x4, from_f32x4, |a| a as f32),
+        F64x2ConvertLowI32x4S => widen!(stack, i32x4, from_f64x2, |a, i| a[i].into()),
+        F64x2ConvertLowI32x4U => widen!(stack, u32x4, from_f64x2, |a, i| a[i].into()),
+        F32x4DemoteF64x2Zero => {
+            widen!(stack, f64x2, from_f32x4, |a, i| a
+                .get(i)
+                .map_or(0.0, |&x| x as f32))
+        }
+        F64x2PromoteLowF32x4 => widen!(stack, f32x4, from_f64x2, |a, i| a[i].into()),
+
+        // The relaxed instructions may give any of a few results; these
+        // give the fused one, where there is a choice
+        F32x4RelaxedMadd | F32x4RelaxedNmadd | F64x2RelaxedMadd | F64x2RelaxedNmadd => {
+            let c = pop_v128(stack);
+            let b = pop_v128(stack);
+            let a = pop_v128(stack);
+            let negate = matches!(op, F32x4RelaxedNmadd | F64x2RelaxedNmadd);
+            let v = if matches!(op, F32x4RelaxedMadd | F32x4RelaxedNmadd) {
+                let (a, b, c) = (f32x4(a), f32x4(b), f32x4(c));
+                from_f32x4(std::array::from_fn(|i| {
+                    let a = if negate { -a[i] } else { a[i] };
+                    a.mul_add(b[i], c[i])
+                }))
+            } else {
+                let (a, b, c) = (f64x2(a), f64x2(b), f64x2(c));
+                from_f64x2(std::array::from_fn(|i| {
+                    let a = if negate { -a[i] } else { a[i] };
+                    a.mul_add(b[i], c[i])
+                }))
+            };
+            stack.push(Value::V128(v));
+        }
+        I16x8RelaxedDotI8x16I7x16S => widen!(stack, i8x16, from_i16x8, |a, b, i| {
+            (i16::from(a[2 * i]) * i16::from(b[2 * i]))
+                .saturating_add(i16::from(a[2 * i + 1]) * i16::from(b[2 * i + 1]))
+        }),
+        I32x4RelaxedDotI8x16I7x16AddS => {
+            let c = i32x4(pop_v128(stack));
+            let b = i8x16(pop_v128(stack));
+            let a = i8x16(pop_v128(stack));
+            let lanes = std::array::from_fn(|i| {
+                (4 * i..4 * i + 4)
+                    .map(|j| i32::from(a[j]) * i32::from(b[j]))
+                    .fold(c[i], i32::wrapping_add)
+            });
+            stack.push(Value::V128(from_i32x4(lanes)));
+        }
+
+        op => panic!("{} accesses memory", op.name()),
+    }
+}
+
+/// Loads a vector from `addr` for one of the loads that doesn't also take
+/// a vector operand.
+pub(crate) fn load(memory: &Memory, op: SimdOp, addr: u64) -> Result<u128, TrapKind> {
+    use SimdOp::*;
+    // The 8 bytes that the extending loads widen, as the low half of a vector
+    let half = || -> Result<u128, TrapKind> { Ok(u64::from_le_bytes(memory.load(addr)?).into()) };
+    Ok(match op {
+        V128Load => u128::from_le_bytes(memory.load(addr)?),
+        V128Load8x8S => {
+            let a = i8x16(half()?);
+            from_i16x8(std::array::from_fn(|i| a[i].into()))
+        }
+        V128Load8x8U => {
+            let a = u8x16(half()?);
+            from_u16x8(std::array::from_fn(|i| a[i].into()))
+        }
+        V128Load16x4S => {
+            let a = i16x8(half()?);
+            from_i32x4(std::array::from_fn(|i| a[i].into()))
+        }
+        V128Load16x4U => {
+            let a = u16x8(half()?);
+            from_u32x4(std::array::from_fn(|i| a[i].into()))
+        }
+        V128Load32x2S => {
+            let a = i32x4(half()?);
+            from_i64x2(std::array::from_fn(|i| a[i].into()))
+        }
+        V128Load32x2U => {
+            let a = u32x4(half()?);
+            from_u64x2(std::array::from_fn(|i| a[i].into()))
+        }
+        V128Load8Splat => from_u8x16([u8::from_le_bytes(memory.load(addr)?); 16]),
+        V128Load16Splat => from_u16x8([u16::from_le_bytes(memory.load(addr)?); 8]),
+        V128Load32Splat => from_u32x4([u32::from_le_bytes(memory.load(addr)?); 4]),
+        V128Load64Splat => from_u64x2([u64::from_le_bytes(memory.load(addr)?); 2]),
+        V128Load32Zero => u32::from_le_bytes(memory.load(addr)?).into(),
+        V128Load64Zero => half()?,
+        op => panic!("{} is not a plain vector load", op.name()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(op: SimdOp, args: &[Value]) -> Value {
+        let mut stack = args.to_vec();
+        eval(op, SimdImm::None, &mut stack);
+        stack.pop().unwrap()
+    }
+
+    #[test]
+    fn test_lanes() {
+        let v = from_i16x8([1, -1, 2, -2, 3, -3, 4, -4]);
+        assert_eq!(v & 0xffff_ffff, 0xffff_0001);
+        assert_eq!(i16x8(v), [1, -1, 2, -2, 3, -3, 4, -4]);
+        assert_eq!(u8x16(v)[3], 0xff);
+        assert_eq!(f64x2(from_f64x2([1.5, -0.0])), [1.5, -0.0]);
+    }
+
+    #[test]
+    fn test_relaxed() {
+        use SimdOp::*;
+        let v = |lanes: [f32; 4]| Value::V128(from_f32x4(lanes));
+        assert_eq!(
+            run(F32x4RelaxedNmadd, &[v([2.0; 4]), v([3.0; 4]), v([1.0; 4])]),
+            v([-5.0; 4])
+        );
+        let mask = Value::V128(from_i32x4([-1, 0, -1, 0]));
+        let (a, b) = (from_i32x4([1, 2, 3, 4]), from_i32x4([5, 6, 7, 8]));
+        assert_eq!(
+            run(
+                I32x4RelaxedLaneselect,
+                &[Value::V128(a), Value::V128(b), mask]
+            ),
+            Value::V128(from_i32x4([1, 6, 3, 8]))
+        );
+        let a = Value::V128(from_i8x16([-128; 16]));
+        let b = Value::V128(from_i8x16([127; 16]));
+        let c = Value::V128(from_i32x4([1, 0, 0, 0]));
+        assert_eq!(
+            run(I32x4RelaxedDotI8x16I7x16AddS, &[a, b, c]),
+            Value::V128(from_i32x4([-65023, -65024, -65024, -65024]))
+        );
+    }
+}
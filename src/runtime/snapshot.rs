@@ -0,0 +1,1132 @@
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, BufReader, Cursor, Read, Write};
+use std::path::Path;
+
+use crate::encode::Encode;
+use crate::encode::encode_len;
+use crate::instr::Instr;
+use crate::parseable::{ParseError, Parseable, ParserLimits, Result};
+use crate::runtime::exec::{Body, Execution, Frame, Label, LabelKind};
+use crate::runtime::instance::Instance;
+use crate::runtime::store::{Func, FuncAddr, FuncKind, Store};
+use crate::runtime::trap::{Trap, TrapFrame, TrapKind};
+use crate::runtime::value::{Ref, Value};
+use crate::section::dwarf::Location;
+use crate::types::leb128::Leb128;
+use crate::types::mem_type::PAGE_SIZE;
+use crate::types::primitives::{FuncIdx, parse_bytes};
+use crate::types::val_type::ValType;
+
+// Snapshot files start with this, then the format version
+const MAGIC: &[u8; 4] = b"\0wsn";
+const VERSION: u32 = 4;
+
+/// Why a snapshot could not be taken or restored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+    /// The state holds something a snapshot can't, e.g. an exception.
+    Unsupported(String),
+    /// The snapshot doesn't fit the instance it is restored into, which
+    /// must be of a module with the same code, set up the same way.
+    Mismatch(String),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Unsupported(what) => write!(f, "can't snapshot {}", what),
+            SnapshotError::Mismatch(reason) => {
+                write!(f, "snapshot doesn't fit the instance: {}", reason)
+            }
+        }
+    }
+}
+
+/// The contents of a memory: its size in pages, and the pages that aren't
+/// all zeros.
+#[derive(Debug, Clone, PartialEq)]
+struct MemoryState {
+    size: u64,
+    pages: Vec<(u64, Vec<u8>)>,
+}
+
+/// A paused call: the operand stack, the labels and the frames, as
+/// [`Execution`] holds them.
+#[derive(Debug, Clone, PartialEq)]
+struct ExecutionState {
+    stack: Vec<Value>,
+    /// Each label's kind as the index of a [`LabelKind`] that has no
+    /// payload, its start and its height.
+    labels: Vec<(u8, u64, u64)>,
+    frames: Vec<FrameState>,
+    pending: Option<FuncAddr>,
+    steps: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FrameState {
+    func: FuncAddr,
+    pc: u64,
+    base: u64,
+    locals: u64,
+    label_base: u64,
+    arity: u64,
+    replaced: u32,
+}
+
+/// The state of an instance at one point: its memories, tables and globals,
+/// which of its segments were dropped, and the call it was in the middle
+/// of, if any. Restoring a snapshot into an instance of the same module
+/// puts it back the way it was, e.g. to look at a crash again under the
+/// debugger without running up to it.
+///
+/// Functions are referred to by their address in the store, so the store
+/// has to be set up the same way as the one the snapshot was taken in, by
+/// instantiating the same modules with the same imports in the same order.
+/// Anything outside of the instance, like the state of WASI's files, is
+/// not part of the snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// A hash of the code of the instance the snapshot was taken of.
+    code_hash: u64,
+    memories: Vec<MemoryState>,
+    tables: Vec<Vec<Ref>>,
+    globals: Vec<Value>,
+    dropped_elems: Vec<u32>,
+    dropped_datas: Vec<u32>,
+    execution: Option<ExecutionState>,
+    trap: Option<Trap>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of `instance` and, if given, of the call in
+    /// progress, with the trap that stopped it.
+    pub fn capture(
+        store: &Store,
+        instance: Instance,
+        execution: Option<&Execution>,
+        trap: Option<&Trap>,
+    ) -> std::result::Result<Snapshot, SnapshotError> {
+        let data = instance.data(store);
+        let memories = data
+            .mems
+            .iter()
+            .map(|addr| {
+                let memory = store.memory(*addr);
+                MemoryState {
+                    size: memory.size(),
+                    pages: memory
+                        .resident()
+                        .map(|(i, page)| (i, page.to_vec()))
+                        .collect(),
+                }
+            })
+            .collect();
+        let tables = data
+            .tables
+            .iter()
+            .map(|addr| store.table(*addr).elems().to_vec())
+            .collect::<Vec<_>>();
+        let globals = data
+            .globals
+            .iter()
+            .map(|addr| store.global(*addr).get())
+            .collect::<Vec<_>>();
+        let refs = tables
+            .iter()
+            .flatten()
+            .chain(globals.iter().filter_map(|value| match value {
+                Value::Ref(r) => Some(r),
+                _ => None,
+            }));
+        if refs.into_iter().any(|r| matches!(r, Ref::Exn(_))) {
+            return Err(SnapshotError::Unsupported(
+                "exception references".to_string(),
+            ));
+        }
+        let dropped_elems = data
+            .elems
+            .iter()
+            .enumerate()
+            .filter(|(_, addr)| store.elems[addr.0 as usize].is_empty())
+            .map(|(i, _)| i as u32)
+            .collect();
+        let dropped_datas = data
+            .datas
+            .iter()
+            .enumerate()
+            .filter(|(_, addr)| store.datas[addr.0 as usize].is_empty())
+            .map(|(i, _)| i as u32)
+            .collect();
+        Ok(Snapshot {
+            code_hash: data.code_hash,
+            memories,
+            tables,
+            globals,
+            dropped_elems,
+            dropped_datas,
+            execution: execution.map(ExecutionState::capture).transpose()?,
+            trap: trap.cloned(),
+        })
+    }
+
+    /// Whether the snapshot has a call in progress.
+    pub fn has_execution(&self) -> bool {
+        self.execution.is_some()
+    }
+
+    /// The trap that had stopped the call when the snapshot was taken.
+    pub fn trap(&self) -> Option<&Trap> {
+        self.trap.as_ref()
+    }
+
+    /// Puts `instance` back into the state of the snapshot, and returns the
+    /// call that was in progress, if any. Nothing changes if the snapshot
+    /// doesn't fit.
+    pub fn restore(
+        &self,
+        store: &mut Store,
+        instance: Instance,
+    ) -> std::result::Result<Option<Execution>, SnapshotError> {
+        let mismatch = |reason: String| Err(SnapshotError::Mismatch(reason));
+        let data = instance.data(store);
+        // Frames and the operand stack only make sense for the code they
+        // were taken in
+        if data.code_hash != self.code_hash {
+            return mismatch("the instance's module has different code".to_string());
+        }
+        let counts = [
+            ("memories", data.mems.len(), self.memories.len()),
+            ("tables", data.tables.len(), self.tables.len()),
+            ("globals", data.globals.len(), self.globals.len()),
+        ];
+        for (what, expected, found) in counts {
+            if expected != found {
+                return mismatch(format!("expected {} {}, found {}", expected, what, found));
+            }
+        }
+        for (i, memory) in self.memories.iter().enumerate() {
+            let limit = store.memory(data.mems[i]).max().unwrap_or(u64::MAX);
+            let fits = |(page, bytes): &(u64, Vec<u8>)| {
+                *page < memory.size && bytes.len() as u64 == PAGE_SIZE
+            };
+            if memory.size > limit || !memory.pages.iter().all(fits) {
+                return mismatch(format!("memory {} doesn't fit", i));
+            }
+        }
+        for (i, table) in self.tables.iter().enumerate() {
+            let limit = store.table(data.tables[i]).max().unwrap_or(u64::MAX);
+            if table.len() as u64 > limit || !table.iter().all(|r| is_valid_ref(store, r)) {
+                return mismatch(format!("table {} doesn't fit", i));
+            }
+        }
+        for (i, value) in self.globals.iter().enumerate() {
+            if !value.has_type(store.global(data.globals[i]).ty()) || !is_valid(store, value) {
+                return mismatch(format!("global {} doesn't have its type", i));
+            }
+        }
+        let segments = [
+            ("element", data.elems.len(), &self.dropped_elems),
+            ("data", data.datas.len(), &self.dropped_datas),
+        ];
+        for (what, len, dropped) in segments {
+            if let Some(i) = dropped.iter().find(|i| **i as usize >= len) {
+                return mismatch(format!("no {} segment {}", what, i));
+            }
+        }
+        // Only a call that ran out of fuel can go on after a trap
+        let resumable = self
+            .trap
+            .as_ref()
+            .is_none_or(|trap| trap.kind() == &TrapKind::OutOfFuel);
+        let execution = self
+            .execution
+            .as_ref()
+            .map(|execution| execution.restore(store, resumable))
+            .transpose()?;
+
+        let data = instance.data(store);
+        let (mems, tables, globals) =
+            (data.mems.clone(), data.tables.clone(), data.globals.clone());
+        let (elems, datas) = (data.elems.clone(), data.datas.clone());
+        for (addr, memory) in mems.iter().zip(&self.memories) {
+            let target = store.memory_mut(*addr);
+            if !target.reset(memory.size) {
+                return mismatch("the host is out of memory".to_string());
+            }
+            for (page, bytes) in &memory.pages {
+                target
+                    .write(page * PAGE_SIZE, bytes)
+                    .map_err(|_| SnapshotError::Mismatch("a page doesn't fit".to_string()))?;
+            }
+        }
+        for (addr, refs) in tables.iter().zip(&self.tables) {
+            if !store.table_mut(*addr).reset(refs) {
+                return mismatch("the host is out of memory".to_string());
+            }
+        }
+        for (addr, value) in globals.iter().zip(&self.globals) {
+            store.global_mut(*addr).set(*value);
+        }
+        for i in &self.dropped_elems {
+            store.elems[elems[*i as usize].0 as usize].clear();
+        }
+        for i in &self.dropped_datas {
+            store.datas[datas[*i as usize].0 as usize].clear();
+        }
+        Ok(execution)
+    }
+
+    /// Reads a snapshot from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Snapshot> {
+        let bytes = fs::read(path)?;
+        Snapshot::parse(&mut BufReader::new(Cursor::new(bytes)))
+    }
+
+    /// Writes the snapshot to the file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes)?;
+        fs::write(path, bytes)
+    }
+}
+
+fn is_valid_ref(store: &Store, r: &Ref) -> bool {
+    match r {
+        Ref::Func(addr) => (addr.0 as usize) < store.funcs.len(),
+        Ref::Exn(_) => false,
+        Ref::Null | Ref::Extern(_) => true,
+    }
+}
+
+fn is_valid(store: &Store, value: &Value) -> bool {
+    match value {
+        Value::Ref(r) => is_valid_ref(store, r),
+        _ => true,
+    }
+}
+
+impl ExecutionState {
+    fn capture(execution: &Execution) -> std::result::Result<ExecutionState, SnapshotError> {
+        if execution
+            .stack
+            .iter()
+            .any(|value| matches!(value, Value::Ref(Ref::Exn(_))))
+        {
+            return Err(SnapshotError::Unsupported(
+                "exception references".to_string(),
+            ));
+        }
+        let labels = execution
+            .labels
+            .iter()
+            .map(|label| {
+                let kind = match label.kind {
+                    LabelKind::Block => 0,
+                    LabelKind::Loop => 1,
+                    LabelKind::If => 2,
+                    LabelKind::Try => 3,
+                    LabelKind::TryTable => 4,
+                    LabelKind::Catch(_) => {
+                        return Err(SnapshotError::Unsupported(
+                            "a call inside an exception handler".to_string(),
+                        ));
+                    }
+                };
+                Ok((kind, label.start as u64, label.height as u64))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        let frames = execution
+            .frames
+            .iter()
+            .map(|frame| FrameState {
+                func: frame.func,
+                pc: frame.pc as u64,
+                base: frame.base as u64,
+                locals: frame.locals as u64,
+                label_base: frame.label_base as u64,
+                arity: frame.arity as u64,
+                replaced: frame.replaced,
+            })
+            .collect();
+        Ok(ExecutionState {
+            stack: execution.stack.clone(),
+            labels,
+            frames,
+            pending: execution.pending,
+            steps: execution.steps,
+        })
+    }
+
+    /// Rebuilds the execution, checking that its frames, labels and
+    /// operands fit the functions they belong to. A call that can go on
+    /// must be between two instructions, with the stacks the type checker
+    /// found there. A call stopped by any other trap may be partway
+    /// through an instruction, so only its shape is checked.
+    fn restore(
+        &self,
+        store: &Store,
+        resumable: bool,
+    ) -> std::result::Result<Execution, SnapshotError> {
+        let mismatch = |reason: &str| SnapshotError::Mismatch(reason.to_string());
+        let stack = self.stack.clone();
+        if !stack.iter().all(|value| is_valid(store, value)) {
+            return Err(mismatch("an operand refers to a missing function"));
+        }
+        let func = |addr: FuncAddr| {
+            store
+                .funcs
+                .get(addr.0 as usize)
+                .ok_or_else(|| mismatch("a frame's function is missing"))
+        };
+        if let Some(addr) = self.pending {
+            let f = func(addr)?;
+            if !f.is_host() || !self.frames.is_empty() {
+                return Err(mismatch("the pending call isn't of a host function"));
+            }
+            if !fits(&stack, f.ty().params()) {
+                return Err(mismatch("the pending call's arguments don't fit"));
+            }
+        }
+        let mut frames = Vec::new();
+        let mut labels = Vec::new();
+        let (mut min_base, mut min_label) = (0, 0);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let f = func(frame.func)?;
+            let FuncKind::Wasm { instance, body, .. } = &f.kind else {
+                return Err(mismatch("a frame is of a host function"));
+            };
+            let [pc, base, locals, label_base, arity] = [
+                frame.pc,
+                frame.base,
+                frame.locals,
+                frame.label_base,
+                frame.arity,
+            ]
+            .map(|n| usize::try_from(n).unwrap_or(usize::MAX));
+            let next = self.frames.get(i + 1);
+            let label_end = next.map_or(self.labels.len() as u64, |next| next.label_base);
+            let label_end = usize::try_from(label_end).unwrap_or(usize::MAX);
+            if pc >= body.len()
+                || arity != f.ty().results().len()
+                || locals != f.ty().params().len() + body.num_locals()
+                || base < min_base
+                || base.saturating_add(locals) > stack.len()
+                || label_base < min_label
+                || label_end < label_base
+                || label_end > self.labels.len()
+            {
+                return Err(mismatch("a frame doesn't fit its function"));
+            }
+            let local_types = f.ty().params().iter().chain(body.locals());
+            // Non-defaultable locals are null until they are set
+            let local_fits = |(value, t): (&Value, &ValType)| {
+                value.has_type(*t) || matches!((value, t), (Value::Ref(Ref::Null), ValType::Ref(_)))
+            };
+            if !stack[base..base + locals]
+                .iter()
+                .zip(local_types)
+                .all(local_fits)
+            {
+                return Err(mismatch("a frame's locals don't have their types"));
+            }
+            let frame_labels = &self.labels[label_base..label_end];
+            let mut blocks = None;
+            if resumable {
+                let callee = match next {
+                    Some(next) => Some((func(next.func)?, next.base)),
+                    None => None,
+                };
+                blocks = Some(check_stacks(&stack, (base + locals, pc), callee, body)?);
+            }
+            for (j, &(kind, start, height)) in frame_labels.iter().enumerate() {
+                let start = usize::try_from(start).unwrap_or(usize::MAX);
+                let height = usize::try_from(height).unwrap_or(usize::MAX);
+                let kind = match kind {
+                    0 => LabelKind::Block,
+                    1 => LabelKind::Loop,
+                    2 => LabelKind::If,
+                    3 => LabelKind::Try,
+                    4 => LabelKind::TryTable,
+                    _ => return Err(mismatch("a label has an unknown kind")),
+                };
+                let fits = match &blocks {
+                    Some(blocks) => {
+                        blocks.get(j) == Some(&(start, height.wrapping_sub(base + locals)))
+                            && body.label_kind(start, pc) == Some(kind)
+                    }
+                    None => {
+                        body.opens_block(start) && height >= base + locals && height <= stack.len()
+                    }
+                };
+                if !fits {
+                    return Err(mismatch("a label doesn't fit its function"));
+                }
+                labels.push(Label {
+                    kind,
+                    start,
+                    height,
+                });
+            }
+            if blocks.is_some_and(|blocks| blocks.len() != frame_labels.len()) {
+                return Err(mismatch("a frame's labels don't match its blocks"));
+            }
+            frames.push(Frame {
+                func: frame.func,
+                instance: *instance,
+                body: body.clone(),
+                pc,
+                base,
+                locals,
+                label_base,
+                arity,
+                replaced: frame.replaced,
+            });
+            (min_base, min_label) = (base + locals, label_end);
+        }
+        if resumable
+            && let Some(frame) = self.frames.first()
+            && frame.base != 0
+        {
+            return Err(mismatch("operands don't belong to a frame"));
+        }
+        if self
+            .frames
+            .first()
+            .is_some_and(|frame| frame.label_base != 0)
+        {
+            return Err(mismatch("labels don't belong to a frame"));
+        }
+        Ok(Execution {
+            stack,
+            labels,
+            frames,
+            pending: self.pending,
+            steps: self.steps,
+            thrown: None,
+        })
+    }
+}
+
+/// Whether `values` have the types `types`.
+fn fits(values: &[Value], types: &[ValType]) -> bool {
+    values.len() == types.len() && values.iter().zip(types).all(|(v, t)| v.has_type(*t))
+}
+
+/// Checks that the operands of a frame, from `start` on the stack, are
+/// those the type checker found before instruction `pc`, and returns the
+/// blocks it found open. A frame with a callee, given by its function and
+/// base, is past the call, with the callee's results still to come.
+fn check_stacks(
+    stack: &[Value],
+    (start, pc): (usize, usize),
+    callee: Option<(&Func, u64)>,
+    body: &Body,
+) -> std::result::Result<Vec<(usize, usize)>, SnapshotError> {
+    let mismatch = |reason: &str| SnapshotError::Mismatch(reason.to_string());
+    let (Some(mut types), Some(blocks)) = (body.stacks().operands(pc), body.stacks().blocks(pc))
+    else {
+        return Err(mismatch("a frame is at an instruction that can't run"));
+    };
+    let mut end = stack.len();
+    if let Some((callee, callee_base)) = callee {
+        let is_call = matches!(
+            pc.checked_sub(1).and_then(|pc| body.instr(pc)),
+            Some(Instr::Call(_) | Instr::CallIndirect(..) | Instr::CallRef(_))
+        );
+        let results = callee.ty().results();
+        let n = types.len().wrapping_sub(results.len());
+        if !is_call || n > types.len() || types[n..] != *results {
+            return Err(mismatch("a frame isn't at a call of the frame it waits on"));
+        }
+        types.truncate(n);
+        end = usize::try_from(callee_base).unwrap_or(usize::MAX);
+    }
+    if start > end || end > stack.len() || !fits(&stack[start..end], &types) {
+        return Err(mismatch("a frame's operands don't match its function"));
+    }
+    Ok(blocks)
+}
+
+fn encode_u64(v: u64, writer: &mut dyn Write) -> io::Result<()> {
+    Leb128::from(v).encode(writer)
+}
+
+fn parse_u64(reader: &mut BufReader<dyn Read>) -> Result<u64> {
+    Ok(u64::from(Leb128::<u64>::parse(reader)?))
+}
+
+fn encode_option<T>(
+    value: Option<&T>,
+    writer: &mut dyn Write,
+    f: impl FnOnce(&T, &mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    match value {
+        Some(value) => {
+            writer.write_all(&[1])?;
+            f(value, writer)
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn parse_option<T>(
+    reader: &mut BufReader<dyn Read>,
+    f: impl FnOnce(&mut BufReader<dyn Read>) -> Result<T>,
+) -> Result<Option<T>> {
+    match u8::parse(reader)? {
+        0 => Ok(None),
+        1 => Ok(Some(f(reader)?)),
+        b => Err(ParseError::Other(format!("malformed option: {:#x}", b))),
+    }
+}
+
+fn parse_vec<T>(
+    reader: &mut BufReader<dyn Read>,
+    mut f: impl FnMut(&mut BufReader<dyn Read>) -> Result<T>,
+) -> Result<Vec<T>> {
+    let len = u32::from(Leb128::<u32>::parse(reader)?);
+    ParserLimits::current().check_vec_len(len)?;
+    (0..len).map(|_| f(reader)).collect()
+}
+
+impl Encode for Ref {
+    fn encode(&self, writer: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Ref::Null => writer.write_all(&[0]),
+            Ref::Func(addr) => {
+                writer.write_all(&[1])?;
+                Leb128::from(addr.0).encode(writer)
+            }
+            Ref::Extern(v) => {
+                writer.write_all(&[2])?;
+                Leb128::from(*v).encode(writer)
+            }
+            Ref::Exn(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "exception references can't be encoded",
+            )),
+        }
+    }
+}
+
+impl Parseable for Ref {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Ref> {
+        match u8::parse(reader)? {
+            0 => Ok(Ref::Null),
+            1 => Ok(Ref::Func(FuncAddr(u32::from(Leb128::<u32>::parse(
+                reader,
+            )?)))),
+            2 => Ok(Ref::Extern(u32::from(Leb128::<u32>::parse(reader)?))),
+            b => Err(ParseError::Other(format!("malformed reference: {:#x}", b))),
+        }
+    }
+}
+
+// Values are tagged with the encoding of their type
+impl Encode for Value {
+    fn encode(&self, writer: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Value::I32(v) => {
+                writer.write_all(&[0x7F])?;
+                Leb128::from(*v).encode(writer)
+            }
+            Value::I64(v) => {
+                writer.write_all(&[0x7E])?;
+                Leb128::from(*v).encode(writer)
+            }
+            Value::F32(v) => {
+                writer.write_all(&[0x7D])?;
+                v.to_bits().encode(writer)
+            }
+            Value::F64(v) => {
+                writer.write_all(&[0x7C])?;
+                writer.write_all(&v.to_bits().to_le_bytes())
+            }
+            Value::V128(v) => {
+                writer.write_all(&[0x7B])?;
+                writer.write_all(&v.to_le_bytes())
+            }
+            Value::Ref(r) => {
+                writer.write_all(&[0x70])?;
+                r.encode(writer)
+            }
+        }
+    }
+}
+
+fn parse_array<const N: usize>(reader: &mut BufReader<dyn Read>) -> Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|_| ParseError::Other("snapshot ends unexpectedly".to_string()))?;
+    Ok(buf)
+}
+
+impl Parseable for Value {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Value> {
+        match u8::parse(reader)? {
+            0x7F => Ok(Value::I32(i32::from(Leb128::<i32>::parse(reader)?))),
+            0x7E => Ok(Value::I64(i64::from(Leb128::<i64>::parse(reader)?))),
+            0x7D => Ok(Value::F32(f32::from_bits(u32::parse(reader)?))),
+            0x7C => Ok(Value::F64(f64::from_bits(u64::from_le_bytes(parse_array(
+                reader,
+            )?)))),
+            0x7B => Ok(Value::V128(u128::from_le_bytes(parse_array(reader)?))),
+            0x70 => Ok(Value::Ref(Ref::parse(reader)?)),
+            b => Err(ParseError::Other(format!("malformed value: {:#x}", b))),
+        }
+    }
+}
+
+impl Encode for TrapKind {
+    fn encode(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let tag = match self {
+            TrapKind::Unreachable => 0,
+            TrapKind::MemoryOutOfBounds => 1,
+            TrapKind::TableOutOfBounds => 2,
+            TrapKind::UndefinedElement => 3,
+            TrapKind::UninitializedElement => 4,
+            TrapKind::IndirectCallTypeMismatch => 5,
+            TrapKind::NullFunctionReference => 6,
+            TrapKind::NullReference => 7,
+            TrapKind::IntegerDivideByZero => 8,
+            TrapKind::IntegerOverflow => 9,
+            TrapKind::InvalidConversion => 10,
+            TrapKind::UnalignedAtomic => 11,
+            TrapKind::StackExhausted => 12,
+            TrapKind::UncaughtException => 13,
+            TrapKind::OutOfFuel => 14,
+            TrapKind::Unsupported(_) => 15,
+            TrapKind::Exit(_) => 16,
+            TrapKind::Host(_) => 17,
+        };
+        writer.write_all(&[tag])?;
+        match self {
+            TrapKind::Unsupported(message) | TrapKind::Host(message) => message.encode(writer),
+            TrapKind::Exit(status) => Leb128::from(*status).encode(writer),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Parseable for TrapKind {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<TrapKind> {
+        Ok(match u8::parse(reader)? {
+            0 => TrapKind::Unreachable,
+            1 => TrapKind::MemoryOutOfBounds,
+            2 => TrapKind::TableOutOfBounds,
+            3 => TrapKind::UndefinedElement,
+            4 => TrapKind::UninitializedElement,
+            5 => TrapKind::IndirectCallTypeMismatch,
+            6 => TrapKind::NullFunctionReference,
+            7 => TrapKind::NullReference,
+            8 => TrapKind::IntegerDivideByZero,
+            9 => TrapKind::IntegerOverflow,
+            10 => TrapKind::InvalidConversion,
+            11 => TrapKind::UnalignedAtomic,
+            12 => TrapKind::StackExhausted,
+            13 => TrapKind::UncaughtException,
+            14 => TrapKind::OutOfFuel,
+            15 => TrapKind::Unsupported(String::parse(reader)?),
+            16 => TrapKind::Exit(i32::from(Leb128::<i32>::parse(reader)?)),
+            17 => TrapKind::Host(String::parse(reader)?),
+            b => return Err(ParseError::Other(format!("malformed trap: {:#x}", b))),
+        })
+    }
+}
+
+impl Encode for TrapFrame {
+    fn encode(&self, writer: &mut dyn Write) -> io::Result<()> {
+        Leb128::from(self.instance().0 as u32).encode(writer)?;
+        encode_option(
+            self.module().map(str::to_string).as_ref(),
+            writer,
+            |module, w| module.encode(w),
+        )?;
+        self.func().encode(writer)?;
+        encode_option(self.offset().as_ref(), writer, |offset, w| {
+            encode_u64(*offset as u64, w)
+        })?;
+        encode_option(
+            self.name().map(str::to_string).as_ref(),
+            writer,
+            |name, w| name.encode(w),
+        )?;
+        encode_option(self.location(), writer, |location, w| {
+            location.file().to_string().encode(w)?;
+            encode_u64(location.line(), w)?;
+            encode_u64(location.column(), w)
+        })?;
+        Leb128::from(self.replaced()).encode(writer)
+    }
+}
+
+impl Parseable for TrapFrame {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<TrapFrame> {
+        let instance = Instance(u32::from(Leb128::<u32>::parse(reader)?) as usize);
+        let module = parse_option(reader, String::parse)?;
+        let func = FuncIdx::parse(reader)?;
+        let offset = parse_option(reader, |r| Ok(parse_u64(r)? as usize))?;
+        let name = parse_option(reader, String::parse)?;
+        let location = parse_option(reader, |r| {
+            Ok(Location::new(
+                String::parse(r)?,
+                parse_u64(r)?,
+                parse_u64(r)?,
+            ))
+        })?;
+        let replaced = u32::from(Leb128::<u32>::parse(reader)?);
+        Ok(TrapFrame::new(instance, module, func, offset, name, location).with_replaced(replaced))
+    }
+}
+
+impl Encode for Snapshot {
+    fn encode(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        VERSION.encode(writer)?;
+        writer.write_all(&self.code_hash.to_le_bytes())?;
+        encode_len(self.memories.len(), writer)?;
+        for memory in &self.memories {
+            encode_u64(memory.size, writer)?;
+            encode_len(memory.pages.len(), writer)?;
+            for (i, bytes) in &memory.pages {
+                encode_u64(*i, writer)?;
+                encode_len(bytes.len(), writer)?;
+                writer.write_all(bytes)?;
+            }
+        }
+        self.tables.encode(writer)?;
+        self.globals.encode(writer)?;
+        self.dropped_elems
+            .iter()
+            .map(|i| Leb128::from(*i))
+            .collect::<Vec<_>>()
+            .encode(writer)?;
+        self.dropped_datas
+            .iter()
+            .map(|i| Leb128::from(*i))
+            .collect::<Vec<_>>()
+            .encode(writer)?;
+        encode_option(self.execution.as_ref(), writer, |execution, w| {
+            execution.stack.encode(w)?;
+            encode_len(execution.labels.len(), w)?;
+            for (kind, start, height) in &execution.labels {
+                kind.encode(w)?;
+                encode_u64(*start, w)?;
+                encode_u64(*height, w)?;
+            }
+            encode_len(execution.frames.len(), w)?;
+            for frame in &execution.frames {
+                Leb128::from(frame.func.0).encode(w)?;
+                for n in [
+                    frame.pc,
+                    frame.base,
+                    frame.locals,
+                    frame.label_base,
+                    frame.arity,
+                ] {
+                    encode_u64(n, w)?;
+                }
+                Leb128::from(frame.replaced).encode(w)?;
+            }
+            encode_option(execution.pending.as_ref(), w, |addr, w| {
+                Leb128::from(addr.0).encode(w)
+            })?;
+            encode_u64(execution.steps, w)
+        })?;
+        encode_option(self.trap.as_ref(), writer, |trap, w| {
+            trap.kind().encode(w)?;
+            trap.backtrace().encode(w)
+        })
+    }
+}
+
+impl Parseable for Snapshot {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Snapshot> {
+        if &parse_array::<4>(reader)? != MAGIC {
+            return Err(ParseError::Other("not a snapshot".to_string()));
+        }
+        let version = u32::parse(reader)?;
+        if version != VERSION {
+            return Err(ParseError::Other(format!(
+                "unsupported snapshot version {}",
+                version
+            )));
+        }
+        let code_hash = u64::from_le_bytes(parse_array(reader)?);
+        let memories = parse_vec(reader, |r| {
+            Ok(MemoryState {
+                size: parse_u64(r)?,
+                pages: parse_vec(r, |r| Ok((parse_u64(r)?, parse_bytes(r)?)))?,
+            })
+        })?;
+        let tables = parse_vec(reader, Vec::<Ref>::parse)?;
+        let globals = Vec::<Value>::parse(reader)?;
+        let dropped_elems = parse_vec(reader, |r| Ok(u32::from(Leb128::<u32>::parse(r)?)))?;
+        let dropped_datas = parse_vec(reader, |r| Ok(u32::from(Leb128::<u32>::parse(r)?)))?;
+        let execution = parse_option(reader, |r| {
+            let stack = Vec::<Value>::parse(r)?;
+            let labels = parse_vec(r, |r| Ok((u8::parse(r)?, parse_u64(r)?, parse_u64(r)?)))?;
+            let frames = parse_vec(r, |r| {
+                let func = FuncAddr(u32::from(Leb128::<u32>::parse(r)?));
+                let [pc, base, locals, label_base, arity] = [
+                    parse_u64(r)?,
+                    parse_u64(r)?,
+                    parse_u64(r)?,
+                    parse_u64(r)?,
+                    parse_u64(r)?,
+                ];
+                Ok(FrameState {
+                    func,
+                    pc,
+                    base,
+                    locals,
+                    label_base,
+                    arity,
+                    replaced: u32::from(Leb128::<u32>::parse(r)?),
+                })
+            })?;
+            let pending = parse_option(r, |r| Ok(FuncAddr(u32::from(Leb128::<u32>::parse(r)?))))?;
+            Ok(ExecutionState {
+                stack,
+                labels,
+                frames,
+                pending,
+                steps: parse_u64(r)?,
+            })
+        })?;
+        let trap = parse_option(reader, |r| {
+            let kind = TrapKind::parse(r)?;
+            let backtrace = Vec::<TrapFrame>::parse(r)?;
+            Ok(Trap::new(kind).with_frames(backtrace))
+        })?;
+        Ok(Snapshot {
+            code_hash,
+            memories,
+            tables,
+            globals,
+            dropped_elems,
+            dropped_datas,
+            execution,
+            trap,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::encode::Encode;
+    use crate::parseable::Parseable;
+    use crate::runtime::exec::Execution;
+    use crate::runtime::imports::Imports;
+    use crate::runtime::instance::Instance;
+    use crate::runtime::snapshot::{Snapshot, SnapshotError};
+    use crate::runtime::store::Store;
+    use crate::runtime::trap::TrapKind;
+    use crate::runtime::value::{Ref, Value};
+    use crate::wat::parse::parse_module;
+
+    const TEXT: &str = r#"
+        (memory 1 4)
+        (table 2 funcref)
+        (global $count (mut i32) (i32.const 0))
+        (data $init "init")
+        (elem (table 0) (i32.const 1) func $count)
+        (func $count (export "count") (param i32) (result i32)
+          (loop $again
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (i32.store (i32.const 70000) (global.get $count))
+            (br_if $again (i32.lt_u (global.get $count) (local.get 0))))
+          (global.get $count))
+        (func (export "setup")
+          (drop (memory.grow (i32.const 1)))
+          (memory.init $init (i32.const 4) (i32.const 0) (i32.const 4))
+          (data.drop $init))
+        (func (export "next") (param i32) (result i32)
+          (i32.add (i32.const 1) (call $count (local.get 0))))
+        (func (export "crash") (result i32)
+          (i32.div_u (global.get $count) (i32.const 0)))
+    "#;
+
+    fn instantiate() -> (Store, Instance) {
+        let module = parse_module(TEXT).unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, &module, &Imports::new()).unwrap();
+        (store, instance)
+    }
+
+    fn roundtrip(snapshot: &Snapshot) -> Snapshot {
+        let mut bytes = Vec::new();
+        snapshot.encode(&mut bytes).unwrap();
+        Snapshot::parse(&mut BufReader::new(Cursor::new(bytes))).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let (mut store, instance) = instantiate();
+        instance.invoke(&mut store, "setup", &[]).unwrap();
+        let count = instance.func(&store, "count").unwrap();
+        let mut execution = Execution::new(&store, count, &[Value::I32(10)]).unwrap();
+        execution.run_for(&mut store, 30).unwrap();
+        let snapshot = Snapshot::capture(&store, instance, Some(&execution), None).unwrap();
+        assert_eq!(roundtrip(&snapshot), snapshot);
+        assert_eq!(execution.run(&mut store).unwrap(), [Value::I32(10)]);
+
+        // The call goes on from where the snapshot was taken, in a store set
+        // up the same way
+        let (mut store, instance) = instantiate();
+        let execution = roundtrip(&snapshot)
+            .restore(&mut store, instance)
+            .unwrap()
+            .unwrap();
+        let mem = instance.data(&store).mems[0];
+        assert_eq!(store.memory(mem).size(), 2);
+        assert_eq!(store.memory(mem).bytes(4, 4), Ok(b"init".to_vec()));
+        assert_eq!(store.memory(mem).resident_pages(), 2);
+        let table = instance.data(&store).tables[0];
+        assert_eq!(store.table(table).get(1), Ok(Ref::Func(count)));
+        assert_eq!(execution.run(&mut store).unwrap(), [Value::I32(10)]);
+        assert_eq!(store.memory(mem).load::<4>(70000), Ok(10i32.to_le_bytes()));
+        assert!(store.datas[instance.data(&store).datas[0].0 as usize].is_empty());
+    }
+
+    #[test]
+    fn test_trapped_snapshot() {
+        let (mut store, instance) = instantiate();
+        let crash = instance.func(&store, "crash").unwrap();
+        let mut execution = Execution::new(&store, crash, &[]).unwrap();
+        let trap = execution.run_for(&mut store, 10).unwrap_err();
+        let snapshot = Snapshot::capture(&store, instance, Some(&execution), Some(&trap)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("wasmdbg-snapshot-{}", std::process::id()));
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert_eq!(
+            loaded.trap().map(|trap| trap.kind()),
+            Some(&TrapKind::IntegerDivideByZero)
+        );
+        assert_eq!(loaded.trap().unwrap().backtrace(), trap.backtrace());
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let (mut store, instance) = instantiate();
+        let snapshot = Snapshot::capture(&store, instance, None, None).unwrap();
+        assert!(matches!(snapshot.restore(&mut store, instance), Ok(None)));
+
+        let module = parse_module("(memory 1)").unwrap();
+        let other = Instance::new(&mut store, &module, &Imports::new()).unwrap();
+        assert!(matches!(
+            snapshot.restore(&mut store, other),
+            Err(SnapshotError::Mismatch(_))
+        ));
+
+        // A module of the same shape whose code differs doesn't fit either,
+        // even though its memories, tables and globals would
+        instance.invoke(&mut store, "setup", &[]).unwrap();
+        let count = instance.func(&store, "count").unwrap();
+        let mut execution = Execution::new(&store, count, &[Value::I32(10)]).unwrap();
+        execution.run_for(&mut store, 30).unwrap();
+        let snapshot = Snapshot::capture(&store, instance, Some(&execution), None).unwrap();
+        let module = parse_module(&TEXT.replace("70000", "70004")).unwrap();
+        let mut store = Store::new();
+        let other = Instance::new(&mut store, &module, &Imports::new()).unwrap();
+        let Err(err) = snapshot.restore(&mut store, other) else {
+            panic!("restored into a module with different code");
+        };
+        assert_eq!(
+            err.to_string(),
+            "snapshot doesn't fit the instance: the instance's module has different code"
+        );
+        assert!(Snapshot::parse(&mut BufReader::new(Cursor::new(b"\0asm".to_vec()))).is_err());
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let (mut store, instance) = instantiate();
+        instance.invoke(&mut store, "setup", &[]).unwrap();
+        let count = instance.func(&store, "count").unwrap();
+        let mut execution = Execution::new(&store, count, &[Value::I32(10)]).unwrap();
+        execution.run_for(&mut store, 7).unwrap();
+        let snapshot = Snapshot::capture(&store, instance, Some(&execution), None).unwrap();
+        let restore = |snapshot: &Snapshot| {
+            let (mut store, instance) = instantiate();
+            snapshot.restore(&mut store, instance).map(|_| ())
+        };
+        assert_eq!(restore(&snapshot), Ok(()));
+
+        // An operand of another type than the code expects
+        let mut corrupted = snapshot.clone();
+        let state = corrupted.execution.as_mut().unwrap();
+        assert_eq!(state.stack.last(), Some(&Value::I32(1)));
+        *state.stack.last_mut().unwrap() = Value::I64(1);
+        assert!(matches!(
+            restore(&corrupted),
+            Err(SnapshotError::Mismatch(_))
+        ));
+
+        // A frame past the end of its function, or where the stacks differ
+        for pc in [15, 1_000, 1] {
+            let mut corrupted = snapshot.clone();
+            corrupted.execution.as_mut().unwrap().frames[0].pc = pc;
+            assert!(matches!(
+                restore(&corrupted),
+                Err(SnapshotError::Mismatch(_))
+            ));
+        }
+
+        // A label that isn't of the block the code is in
+        let mut corrupted = snapshot.clone();
+        corrupted.execution.as_mut().unwrap().labels[0].0 = 0;
+        assert!(matches!(
+            restore(&corrupted),
+            Err(SnapshotError::Mismatch(_))
+        ));
+
+        // A frame waiting on a call is checked past the call
+        let (mut store, instance) = instantiate();
+        instance.invoke(&mut store, "setup", &[]).unwrap();
+        let next = instance.func(&store, "next").unwrap();
+        let mut execution = Execution::new(&store, next, &[Value::I32(3)]).unwrap();
+        execution.run_for(&mut store, 9).unwrap();
+        assert_eq!(execution.frames().len(), 2);
+        let snapshot = Snapshot::capture(&store, instance, Some(&execution), None).unwrap();
+        let (mut store, instance) = instantiate();
+        let execution = snapshot.restore(&mut store, instance).unwrap().unwrap();
+        assert_eq!(execution.run(&mut store).unwrap(), [Value::I32(4)]);
+
+        // Whatever byte of the call is changed, restoring and running it
+        // doesn't panic
+        let mut bytes = Vec::new();
+        Snapshot {
+            execution: None,
+            ..snapshot.clone()
+        }
+        .encode(&mut bytes)
+        .unwrap();
+        let start = bytes.len() - 2;
+        bytes.clear();
+        snapshot.encode(&mut bytes).unwrap();
+        for i in start..bytes.len() {
+            for byte in [bytes[i] ^ 1, bytes[i].wrapping_add(1), 0, 0x7e] {
+                let mut corrupted = bytes.clone();
+                corrupted[i] = byte;
+                let reader = &mut BufReader::new(Cursor::new(corrupted));
+                let Ok(snapshot) = Snapshot::parse(reader) else {
+                    continue;
+                };
+                let (mut store, instance) = instantiate();
+                if let Ok(Some(execution)) = snapshot.restore(&mut store, instance) {
+                    store.set_fuel(Some(1_000));
+                    let _ = execution.run(&mut store);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::instr::Instr;
+use crate::runtime::exec::{Body, Execution};
+use crate::runtime::host::Caller;
+use crate::runtime::instance::{Instance, InstanceData};
+use crate::runtime::memory::{Memory, MemoryGrowth};
+use crate::runtime::table::Table;
+use crate::runtime::trap::Trap;
+use crate::runtime::value::{Ref, Value};
+use crate::types::func_type::FuncType;
+use crate::types::primitives::FuncIdx;
+use crate::types::val_type::ValType;
+
+// Addresses of the objects in a store. Unlike indices, which are local to
+// a module, they are shared by every instance in the store.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FuncAddr(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TableAddr(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemAddr(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GlobalAddr(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TagAddr(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ElemAddr(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DataAddr(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ExnAddr(pub u32);
+
+/// Something an instance exports, or that is provided for an import.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Extern {
+    Func(FuncAddr),
+    Table(TableAddr),
+    Mem(MemAddr),
+    Global(GlobalAddr),
+    Tag(TagAddr),
+}
+
+/// The closure behind a host function. It gets the function's arguments,
+/// which match its parameters, and returns its results or a trap.
+pub type HostFunc = Rc<dyn Fn(&mut Caller<'_>, &[Value]) -> Result<Vec<Value>, Trap>>;
+
+pub(crate) enum FuncKind {
+    Wasm {
+        instance: usize,
+        idx: FuncIdx,
+        body: Rc<Body>,
+    },
+    Host(HostFunc),
+}
+
+/// A function, either defined by a module, with the instance it belongs
+/// to, or provided by the host.
+pub struct Func {
+    ty: FuncType,
+    pub(crate) kind: FuncKind,
+}
+
+impl Func {
+    pub(crate) fn new(ty: FuncType, instance: usize, idx: FuncIdx, body: Body) -> Func {
+        Func {
+            ty,
+            kind: FuncKind::Wasm {
+                instance,
+                idx,
+                body: Rc::new(body),
+            },
+        }
+    }
+
+    /// A host function of type `ty` that calls `f`.
+    pub fn host(
+        ty: FuncType,
+        f: impl Fn(&mut Caller<'_>, &[Value]) -> Result<Vec<Value>, Trap> + 'static,
+    ) -> Func {
+        Func::from_host(ty, Rc::new(f))
+    }
+
+    pub(crate) fn from_host(ty: FuncType, f: HostFunc) -> Func {
+        Func {
+            ty,
+            kind: FuncKind::Host(f),
+        }
+    }
+
+    pub fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+
+    /// The function's index in its module, or `None` for host functions.
+    pub fn idx(&self) -> Option<FuncIdx> {
+        match &self.kind {
+            FuncKind::Wasm { idx, .. } => Some(*idx),
+            FuncKind::Host(_) => None,
+        }
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.kind, FuncKind::Host(_))
+    }
+}
+
+pub struct Global {
+    ty: ValType,
+    mutable: bool,
+    value: Value,
+}
+
+impl Global {
+    pub fn new(ty: ValType, mutable: bool, value: Value) -> Global {
+        Global { ty, mutable, value }
+    }
+
+    pub fn ty(&self) -> ValType {
+        self.ty
+    }
+
+    pub fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+
+    pub fn get(&self) -> Value {
+        self.value
+    }
+
+    pub fn set(&mut self, value: Value) {
+        self.value = value;
+    }
+}
+
+/// An exception tag, whose type gives the exception's fields as
+/// parameters.
+pub struct Tag {
+    ty: FuncType,
+}
+
+impl Tag {
+    pub fn new(ty: FuncType) -> Tag {
+        Tag { ty }
+    }
+
+    pub fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+}
+
+/// A thrown exception.
+pub struct Exception {
+    tag: TagAddr,
+    fields: Vec<Value>,
+}
+
+impl Exception {
+    pub fn tag(&self) -> TagAddr {
+        self.tag
+    }
+
+    pub fn fields(&self) -> &[Value] {
+        &self.fields
+    }
+}
+
+/// What executing each instruction costs in fuel: a default, and
+/// overrides for particular instructions by their mnemonic, e.g.
+/// `i32.div_s` or `call`.
+#[derive(Debug, Clone)]
+pub struct FuelCosts {
+    default: u64,
+    // Keyed by `Instr::name`, so that looking up an instruction's cost
+    // doesn't allocate
+    costs: HashMap<&'static str, u64>,
+}
+
+/// A fuel cost was set for a mnemonic that no instruction has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownInstr(pub String);
+
+impl Display for UnknownInstr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown instruction {}", self.0)
+    }
+}
+
+impl Default for FuelCosts {
+    fn default() -> Self {
+        FuelCosts::new(1)
+    }
+}
+
+impl FuelCosts {
+    pub fn new(default: u64) -> FuelCosts {
+        FuelCosts {
+            default,
+            costs: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, mnemonic: &str, cost: u64) -> Result<&mut FuelCosts, UnknownInstr> {
+        let name = Instr::find_name(mnemonic).ok_or_else(|| UnknownInstr(mnemonic.to_string()))?;
+        self.costs.insert(name, cost);
+        Ok(self)
+    }
+
+    pub fn cost(&self, instr: &Instr) -> u64 {
+        if self.costs.is_empty() {
+            return self.default;
+        }
+        self.costs
+            .get(instr.name())
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// A hook told of every `memory.grow`, e.g. for a debugger to stop at.
+pub type GrowHook = Rc<dyn Fn(&MemoryGrowth)>;
+
+/// Everything the instances of modules create at runtime. Instances refer
+/// to their functions, memories and so on by address, so that they can be
+/// shared between instances.
+#[derive(Default)]
+pub struct Store {
+    pub(crate) funcs: Vec<Func>,
+    pub(crate) tables: Vec<Table>,
+    pub(crate) mems: Vec<Memory>,
+    pub(crate) globals: Vec<Global>,
+    pub(crate) tags: Vec<Tag>,
+    /// Element segments, emptied when dropped.
+    pub(crate) elems: Vec<Vec<Ref>>,
+    /// Data segments, emptied when dropped.
+    pub(crate) datas: Vec<Vec<u8>>,
+    pub(crate) exns: Vec<Exception>,
+    pub(crate) instances: Vec<InstanceData>,
+    /// The instances registered by name.
+    pub(crate) registered: HashMap<String, Instance>,
+    /// The fuel left, when execution is metered.
+    pub(crate) fuel: Option<u64>,
+    pub(crate) fuel_costs: FuelCosts,
+    pub(crate) grow_hook: Option<GrowHook>,
+}
+
+impl Store {
+    pub fn new() -> Store {
+        Store::default()
+    }
+
+    /// Registers `instance` as `name`, so that linkers resolve imports from
+    /// the module `name` against its exports, and frames of its functions
+    /// show which module they belong to. Registering another instance as
+    /// the same name replaces it.
+    pub fn register(&mut self, name: &str, instance: Instance) {
+        self.instances[instance.0].name = Some(name.to_string());
+        self.registered.insert(name.to_string(), instance);
+    }
+
+    /// The instance registered as `name`.
+    pub fn instance(&self, name: &str) -> Option<Instance> {
+        self.registered.get(name).copied()
+    }
+
+    /// The fuel left, or `None` if execution isn't metered.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Meters execution with `fuel`, or stops metering it. Every
+    /// instruction uses up fuel, and once there isn't enough left for the
+    /// next one, execution stops with [`TrapKind::OutOfFuel`].
+    ///
+    /// [`TrapKind::OutOfFuel`]: crate::runtime::trap::TrapKind::OutOfFuel
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub fn set_fuel_costs(&mut self, costs: FuelCosts) {
+        self.fuel_costs = costs;
+    }
+
+    /// Calls `hook` after every `memory.grow`, whether or not the memory
+    /// grew, or stops calling one.
+    pub fn set_grow_hook(&mut self, hook: Option<GrowHook>) {
+        self.grow_hook = hook;
+    }
+
+    /// Grows the memory at `addr` by `delta` pages as `memory.grow` does,
+    /// returning the old size or `None` if it can't, and tells the grow
+    /// hook.
+    pub fn grow_memory(&mut self, addr: MemAddr, delta: u64) -> Option<u64> {
+        let memory = self.memory_mut(addr);
+        let old = memory.size();
+        let result = memory.grow(delta);
+        if let Some(hook) = &self.grow_hook {
+            hook(&MemoryGrowth::new(addr, old, delta, result.is_some()));
+        }
+        result
+    }
+
+    pub fn func(&self, addr: FuncAddr) -> &Func {
+        &self.funcs[addr.0 as usize]
+    }
+
+    pub fn table(&self, addr: TableAddr) -> &Table {
+        &self.tables[addr.0 as usize]
+    }
+
+    pub fn table_mut(&mut self, addr: TableAddr) -> &mut Table {
+        &mut self.tables[addr.0 as usize]
+    }
+
+    pub fn memory(&self, addr: MemAddr) -> &Memory {
+        &self.mems[addr.0 as usize]
+    }
+
+    pub fn memory_mut(&mut self, addr: MemAddr) -> &mut Memory {
+        &mut self.mems[addr.0 as usize]
+    }
+
+    pub fn global(&self, addr: GlobalAddr) -> &Global {
+        &self.globals[addr.0 as usize]
+    }
+
+    pub fn global_mut(&mut self, addr: GlobalAddr) -> &mut Global {
+        &mut self.globals[addr.0 as usize]
+    }
+
+    pub fn tag(&self, addr: TagAddr) -> &Tag {
+        &self.tags[addr.0 as usize]
+    }
+
+    pub fn exception(&self, addr: ExnAddr) -> &Exception {
+        &self.exns[addr.0 as usize]
+    }
+
+    /// Adds a function to the store, such as a host function to provide
+    /// for an import, and returns its address.
+    pub fn add_func(&mut self, func: Func) -> FuncAddr {
+        self.funcs.push(func);
+        FuncAddr(self.funcs.len() as u32 - 1)
+    }
+
+    pub fn add_table(&mut self, table: Table) -> TableAddr {
+        self.tables.push(table);
+        TableAddr(self.tables.len() as u32 - 1)
+    }
+
+    pub fn add_memory(&mut self, memory: Memory) -> MemAddr {
+        self.mems.push(memory);
+        MemAddr(self.mems.len() as u32 - 1)
+    }
+
+    pub fn add_global(&mut self, global: Global) -> GlobalAddr {
+        self.globals.push(global);
+        GlobalAddr(self.globals.len() as u32 - 1)
+    }
+
+    pub fn add_tag(&mut self, tag: Tag) -> TagAddr {
+        self.tags.push(tag);
+        TagAddr(self.tags.len() as u32 - 1)
+    }
+
+    pub(crate) fn add_elem(&mut self, elem: Vec<Ref>) -> ElemAddr {
+        self.elems.push(elem);
+        ElemAddr(self.elems.len() as u32 - 1)
+    }
+
+    pub(crate) fn add_data(&mut self, data: Vec<u8>) -> DataAddr {
+        self.datas.push(data);
+        DataAddr(self.datas.len() as u32 - 1)
+    }
+
+    pub(crate) fn add_exception(&mut self, tag: TagAddr, fields: Vec<Value>) -> ExnAddr {
+        self.exns.push(Exception { tag, fields });
+        ExnAddr(self.exns.len() as u32 - 1)
+    }
+
+    /// Calls a function with `args` and runs it to completion.
+    pub fn invoke(&mut self, func: FuncAddr, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        Execution::new(self, func, args)?.run(self)
+    }
+}
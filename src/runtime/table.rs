@@ -0,0 +1,123 @@
+use crate::runtime::trap::TrapKind;
+use crate::runtime::value::Ref;
+use crate::types::ref_type::RefType;
+use crate::types::table_type::TableType;
+
+// Tables are vectors of references held in host memory, so growth stops
+// well short of what a 64-bit table type allows
+const MAX_ELEMS: u64 = 1 << 28;
+
+/// A table of references.
+pub struct Table {
+    elem_type: RefType,
+    elems: Vec<Ref>,
+    /// The maximum its type declares.
+    max: Option<u64>,
+    is_64: bool,
+}
+
+impl Table {
+    /// A table of the type's minimum size filled with `init`, or `None` if
+    /// the host can't provide that much.
+    pub fn new(ty: &TableType, init: Ref) -> Option<Table> {
+        let limits = ty.limits();
+        let mut table = Table {
+            elem_type: ty.elem_type(),
+            elems: Vec::new(),
+            max: limits.max(),
+            is_64: limits.is_64(),
+        };
+        table.grow(limits.min(), init)?;
+        Some(table)
+    }
+
+    pub fn elem_type(&self) -> RefType {
+        self.elem_type
+    }
+
+    pub fn size(&self) -> u64 {
+        self.elems.len() as u64
+    }
+
+    /// The maximum size its type declares.
+    pub fn max(&self) -> Option<u64> {
+        self.max
+    }
+
+    /// Whether indices are `i64` rather than `i32`.
+    pub fn is_64(&self) -> bool {
+        self.is_64
+    }
+
+    pub fn elems(&self) -> &[Ref] {
+        &self.elems
+    }
+
+    /// Grows the table by `delta` elements set to `init`, returning the old
+    /// size, or `None` if that would pass the maximum.
+    pub fn grow(&mut self, delta: u64, init: Ref) -> Option<u64> {
+        let old = self.size();
+        let limit = self.max.unwrap_or(u64::MAX).min(MAX_ELEMS);
+        let new = old.checked_add(delta).filter(|new| *new <= limit)?;
+        self.elems.try_reserve_exact(delta as usize).ok()?;
+        self.elems.resize(new as usize, init);
+        Some(old)
+    }
+
+    /// Replaces the elements with `refs`, or returns `false` if its type
+    /// doesn't allow that many.
+    pub(crate) fn reset(&mut self, refs: &[Ref]) -> bool {
+        self.elems.clear();
+        if self.grow(refs.len() as u64, Ref::Null).is_none() {
+            return false;
+        }
+        self.elems.copy_from_slice(refs);
+        true
+    }
+
+    // The range of `len` elements at `i`, if it is in bounds
+    fn range(&self, i: u64, len: u64) -> Result<std::ops::Range<usize>, TrapKind> {
+        match i.checked_add(len) {
+            Some(end) if end <= self.size() => Ok(i as usize..end as usize),
+            _ => Err(TrapKind::TableOutOfBounds),
+        }
+    }
+
+    pub fn get(&self, i: u64) -> Result<Ref, TrapKind> {
+        let range = self.range(i, 1)?;
+        Ok(self.elems[range.start])
+    }
+
+    /// The `len` elements at `i`.
+    pub fn elems_at(&self, i: u64, len: u64) -> Result<&[Ref], TrapKind> {
+        let range = self.range(i, len)?;
+        Ok(&self.elems[range])
+    }
+
+    pub fn set(&mut self, i: u64, r: Ref) -> Result<(), TrapKind> {
+        let range = self.range(i, 1)?;
+        self.elems[range.start] = r;
+        Ok(())
+    }
+
+    pub fn fill(&mut self, i: u64, r: Ref, len: u64) -> Result<(), TrapKind> {
+        let range = self.range(i, len)?;
+        self.elems[range].fill(r);
+        Ok(())
+    }
+
+    /// Writes `refs` to the table at `i`.
+    pub fn init(&mut self, i: u64, refs: &[Ref]) -> Result<(), TrapKind> {
+        let range = self.range(i, refs.len() as u64)?;
+        self.elems[range].copy_from_slice(refs);
+        Ok(())
+    }
+
+    /// Copies `len` elements within the table. The ranges may overlap.
+    pub fn copy_within(&mut self, dst: u64, src: u64, len: u64) -> Result<(), TrapKind> {
+        let src = self.range(src, len)?;
+        let dst = self.range(dst, len)?;
+        self.elems.copy_within(src, dst.start);
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt::Display;
+
+use crate::runtime::instance::Instance;
+use crate::section::dwarf::Location;
+use crate::types::primitives::FuncIdx;
+
+/// Why execution trapped. The messages follow the wording of the reference
+/// interpreter, which is what spec test scripts expect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrapKind {
+    Unreachable,
+    MemoryOutOfBounds,
+    TableOutOfBounds,
+    /// `call_indirect` on a table index past the end of the table.
+    UndefinedElement,
+    /// `call_indirect` on a null table element.
+    UninitializedElement,
+    IndirectCallTypeMismatch,
+    NullFunctionReference,
+    NullReference,
+    IntegerDivideByZero,
+    IntegerOverflow,
+    InvalidConversion,
+    UnalignedAtomic,
+    /// The call stack or the operand stack grew past its limit.
+    StackExhausted,
+    /// An exception was thrown and nothing caught it.
+    UncaughtException,
+    /// The store ran out of fuel. Stepping an execution stops before the
+    /// instruction that needs more, so it can go on with more fuel.
+    OutOfFuel,
+    /// An instruction the interpreter does not implement.
+    Unsupported(String),
+    /// The program asked to exit with a status, e.g. through WASI's
+    /// `proc_exit`.
+    Exit(i32),
+    /// An error raised by the host, e.g. calling a function with
+    /// arguments of the wrong type.
+    Host(String),
+}
+
+impl Display for TrapKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrapKind::Unreachable => write!(f, "unreachable"),
+            TrapKind::MemoryOutOfBounds => write!(f, "out of bounds memory access"),
+            TrapKind::TableOutOfBounds => write!(f, "out of bounds table access"),
+            TrapKind::UndefinedElement => write!(f, "undefined element"),
+            TrapKind::UninitializedElement => write!(f, "uninitialized element"),
+            TrapKind::IndirectCallTypeMismatch => write!(f, "indirect call type mismatch"),
+            TrapKind::NullFunctionReference => write!(f, "null function reference"),
+            TrapKind::NullReference => write!(f, "null reference"),
+            TrapKind::IntegerDivideByZero => write!(f, "integer divide by zero"),
+            TrapKind::IntegerOverflow => write!(f, "integer overflow"),
+            TrapKind::InvalidConversion => write!(f, "invalid conversion to integer"),
+            TrapKind::UnalignedAtomic => write!(f, "unaligned atomic"),
+            TrapKind::StackExhausted => write!(f, "call stack exhausted"),
+            TrapKind::UncaughtException => write!(f, "uncaught exception"),
+            TrapKind::OutOfFuel => write!(f, "out of fuel"),
+            TrapKind::Unsupported(instr) => write!(f, "unsupported instruction {}", instr),
+            TrapKind::Exit(status) => write!(f, "exit with status {}", status),
+            TrapKind::Host(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// A function activation in a trap's backtrace, symbolicated with the
+/// module's debug names and line tables where it has them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrapFrame {
+    instance: Instance,
+    module: Option<String>,
+    func: FuncIdx,
+    offset: Option<usize>,
+    name: Option<String>,
+    location: Option<Location>,
+    /// How many frames tail calls replaced with this one.
+    replaced: u32,
+}
+
+impl TrapFrame {
+    pub(crate) fn new(
+        instance: Instance,
+        module: Option<String>,
+        func: FuncIdx,
+        offset: Option<usize>,
+        name: Option<String>,
+        location: Option<Location>,
+    ) -> TrapFrame {
+        TrapFrame {
+            instance,
+            module,
+            func,
+            offset,
+            name,
+            location,
+            replaced: 0,
+        }
+    }
+
+    pub(crate) fn with_replaced(mut self, replaced: u32) -> TrapFrame {
+        self.replaced = replaced;
+        self
+    }
+
+    pub fn instance(&self) -> Instance {
+        self.instance
+    }
+
+    /// The name of the instance's module, as [`Instance::name`] gives it.
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    pub fn func(&self) -> FuncIdx {
+        self.func
+    }
+
+    /// The offset of the instruction the frame was at, relative to the
+    /// start of the code section's contents.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// The function's name from the name section.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The source position from the DWARF line tables.
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+
+    /// The number of callers that tail calls removed from the stack below
+    /// this frame, which the backtrace no longer shows.
+    pub fn replaced(&self) -> u32 {
+        self.replaced
+    }
+}
+
+impl Display for TrapFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(module) = &self.module {
+            write!(f, "{}!", module)?;
+        }
+        match &self.name {
+            Some(name) => write!(f, "${} (func {})", name, self.func.0)?,
+            None => write!(f, "func {}", self.func.0)?,
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at {:#x}", offset)?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, " in {}", location)?;
+        }
+        match self.replaced {
+            0 => {}
+            1 => write!(f, " (1 frame replaced by tail calls)")?,
+            n => write!(f, " ({} frames replaced by tail calls)", n)?,
+        }
+        Ok(())
+    }
+}
+
+/// A trap, which aborts execution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trap {
+    kind: TrapKind,
+    backtrace: Vec<TrapFrame>,
+}
+
+impl Trap {
+    pub fn new(kind: TrapKind) -> Trap {
+        Trap {
+            kind,
+            backtrace: Vec::new(),
+        }
+    }
+
+    pub fn kind(&self) -> &TrapKind {
+        &self.kind
+    }
+
+    /// The module functions that were active when the trap happened,
+    /// innermost first. Traps raised outside of any module function, e.g.
+    /// by the host, have none.
+    pub fn backtrace(&self) -> &[TrapFrame] {
+        &self.backtrace
+    }
+
+    /// The module function that trapped.
+    pub fn func(&self) -> Option<FuncIdx> {
+        self.backtrace.first().map(TrapFrame::func)
+    }
+
+    /// The offset of the instruction that trapped.
+    pub fn offset(&self) -> Option<usize> {
+        self.backtrace.first().and_then(TrapFrame::offset)
+    }
+
+    /// Adds `frames`, which called the frames already in the backtrace.
+    pub(crate) fn with_frames(mut self, frames: impl IntoIterator<Item = TrapFrame>) -> Trap {
+        self.backtrace.extend(frames);
+        self
+    }
+}
+
+impl From<TrapKind> for Trap {
+    fn from(kind: TrapKind) -> Self {
+        Trap::new(kind)
+    }
+}
+
+impl Display for Trap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
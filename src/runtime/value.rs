@@ -0,0 +1,136 @@
+use std::fmt::Display;
+
+use crate::runtime::store::{ExnAddr, FuncAddr};
+use crate::types::heap_type::HeapType;
+use crate::types::num_type::NumType;
+use crate::types::val_type::ValType;
+use crate::types::vec_type::VecType;
+
+/// A reference value. Functions and exceptions are referred to by their
+/// address in the store; external references are opaque numbers chosen by
+/// the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ref {
+    Null,
+    Func(FuncAddr),
+    Extern(u32),
+    Exn(ExnAddr),
+}
+
+impl Ref {
+    pub fn is_null(&self) -> bool {
+        *self == Ref::Null
+    }
+}
+
+impl Display for Ref {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ref::Null => write!(f, "null"),
+            Ref::Func(addr) => write!(f, "func {}", addr.0),
+            Ref::Extern(n) => write!(f, "extern {}", n),
+            Ref::Exn(addr) => write!(f, "exn {}", addr.0),
+        }
+    }
+}
+
+/// A value on the operand stack, or in a local or global. Floats keep
+/// their exact bits, NaN payloads included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    V128(u128),
+    Ref(Ref),
+}
+
+impl Value {
+    /// The value locals and table elements of type `t` start out with:
+    /// zero, or null.
+    pub fn default_of(t: ValType) -> Value {
+        match t {
+            ValType::Num(NumType::I32) => Value::I32(0),
+            ValType::Num(NumType::I64) => Value::I64(0),
+            ValType::Num(NumType::F32) => Value::F32(0.0),
+            ValType::Num(NumType::F64) => Value::F64(0.0),
+            ValType::Vec(VecType::V128) => Value::V128(0),
+            ValType::Ref(_) => Value::Ref(Ref::Null),
+        }
+    }
+
+    /// Whether the value can be stored in a location of type `t`. A
+    /// function reference is taken to match any concrete type.
+    pub fn has_type(&self, t: ValType) -> bool {
+        match (self, t) {
+            (Value::I32(_), ValType::Num(NumType::I32))
+            | (Value::I64(_), ValType::Num(NumType::I64))
+            | (Value::F32(_), ValType::Num(NumType::F32))
+            | (Value::F64(_), ValType::Num(NumType::F64))
+            | (Value::V128(_), ValType::Vec(VecType::V128)) => true,
+            (Value::Ref(r), ValType::Ref(rt)) => match r {
+                Ref::Null => rt.is_nullable(),
+                Ref::Func(_) => matches!(rt.heap_type(), HeapType::Func | HeapType::Concrete(_)),
+                Ref::Extern(_) => rt.heap_type() == HeapType::Extern,
+                Ref::Exn(_) => rt.heap_type() == HeapType::Exn,
+            },
+            _ => false,
+        }
+    }
+
+    /// Compares values by their bits, so that NaNs with the same payload
+    /// are equal.
+    pub fn same_bits(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+            (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+            (a, b) => a == b,
+        }
+    }
+
+    /// Parses a value of type `t` as typed at a prompt: an integer, signed
+    /// or not and decimal or `0x` hexadecimal, a float, or `null`.
+    pub fn parse(t: ValType, text: &str) -> Option<Value> {
+        let text = text.trim();
+        let int = |bits: u32| -> Option<u64> {
+            let (negative, digits) = match text.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, text),
+            };
+            let v = match digits.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+                None => digits.parse::<u64>().ok()?,
+            };
+            let fits = match negative {
+                true => v <= 1 << (bits - 1),
+                false => bits == 64 || v < 1 << bits,
+            };
+            fits.then_some(if negative { v.wrapping_neg() } else { v })
+        };
+        match t {
+            ValType::Num(NumType::I32) => Some(Value::I32(int(32)? as i32)),
+            ValType::Num(NumType::I64) => Some(Value::I64(int(64)? as i64)),
+            ValType::Num(NumType::F32) => text.parse().ok().map(Value::F32),
+            ValType::Num(NumType::F64) => text.parse().ok().map(Value::F64),
+            ValType::Vec(VecType::V128) => {
+                let hex = text.strip_prefix("0x")?;
+                u128::from_str_radix(hex, 16).ok().map(Value::V128)
+            }
+            ValType::Ref(_) => (text == "null").then_some(Value::Ref(Ref::Null)),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::I32(v) => write!(f, "i32 {}", v),
+            Value::I64(v) => write!(f, "i64 {}", v),
+            Value::F32(v) => write!(f, "f32 {}", v),
+            Value::F64(v) => write!(f, "f64 {}", v),
+            Value::V128(v) => write!(f, "v128 {:#034x}", v),
+            Value::Ref(r) => write!(f, "ref {}", r),
+        }
+    }
+}
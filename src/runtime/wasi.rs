@@ -0,0 +1,918 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::runtime::host::Caller;
+use crate::runtime::linker::Linker;
+use crate::runtime::memory::Memory;
+use crate::runtime::trap::{Trap, TrapKind};
+use crate::types::mem_type::PAGE_SIZE;
+
+/// The module name WASI preview1 functions are imported from.
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
+/// An error number returned by WASI functions. Success is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum Errno {
+    Acces = 2,
+    Badf = 8,
+    Exist = 20,
+    Fault = 21,
+    Inval = 28,
+    Io = 29,
+    Isdir = 31,
+    Loop = 32,
+    Noent = 44,
+    Nosys = 52,
+    Notdir = 54,
+    Notempty = 55,
+    Spipe = 70,
+    Notcapable = 76,
+}
+
+impl From<io::Error> for Errno {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Errno::Noent,
+            io::ErrorKind::PermissionDenied => Errno::Acces,
+            io::ErrorKind::AlreadyExists => Errno::Exist,
+            io::ErrorKind::InvalidInput => Errno::Inval,
+            io::ErrorKind::NotADirectory => Errno::Notdir,
+            io::ErrorKind::IsADirectory => Errno::Isdir,
+            io::ErrorKind::DirectoryNotEmpty => Errno::Notempty,
+            _ => Errno::Io,
+        }
+    }
+}
+
+// Only memory accesses trap in the functions below, and WASI reports
+// those as bad addresses rather than trapping
+impl From<TrapKind> for Errno {
+    fn from(_: TrapKind) -> Self {
+        Errno::Fault
+    }
+}
+
+type WasiResult<T> = Result<T, Errno>;
+
+// File types, as found in fdstat, filestat and dirent
+const FILETYPE_UNKNOWN: u8 = 0;
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+const FILETYPE_SYMBOLIC_LINK: u8 = 7;
+
+const OFLAGS_CREAT: u32 = 1;
+const OFLAGS_DIRECTORY: u32 = 2;
+const OFLAGS_EXCL: u32 = 4;
+const OFLAGS_TRUNC: u32 = 8;
+const FDFLAGS_APPEND: u32 = 1;
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+// Descriptors don't restrict what can be done with them beyond what the
+// host allows, so they report every right
+const RIGHTS_ALL: u64 = (1 << 30) - 1;
+
+enum Descriptor {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(File),
+    /// A directory, under the preopened directory `root` it was opened
+    /// from. `name` is the guest's name for preopened directories.
+    Dir {
+        path: PathBuf,
+        root: PathBuf,
+        name: Option<String>,
+    },
+}
+
+/// A WASI preview1 implementation for running command modules. Programs
+/// only get at host files through directories preopened with
+/// [`Wasi::preopen_dir`], and can't open paths outside them.
+pub struct Wasi {
+    args: Vec<String>,
+    env: Vec<String>,
+    stdin: Box<dyn Read>,
+    stdout: Box<dyn Write>,
+    stderr: Box<dyn Write>,
+    fds: BTreeMap<u32, Descriptor>,
+    start: Instant,
+    rng: u64,
+}
+
+impl Default for Wasi {
+    fn default() -> Self {
+        Wasi::new()
+    }
+}
+
+impl Wasi {
+    /// A WASI environment with no arguments, no environment variables and
+    /// no preopened directories, which inherits the host's standard
+    /// streams.
+    pub fn new() -> Wasi {
+        let fds = BTreeMap::from([
+            (0, Descriptor::Stdin),
+            (1, Descriptor::Stdout),
+            (2, Descriptor::Stderr),
+        ]);
+        Wasi {
+            args: Vec::new(),
+            env: Vec::new(),
+            stdin: Box::new(io::stdin()),
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            fds,
+            start: Instant::now(),
+            rng: RandomState::new().hash_one(0u64) | 1,
+        }
+    }
+
+    /// Adds a command line argument. The first one is the program name.
+    pub fn arg(&mut self, arg: &str) -> &mut Wasi {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Wasi {
+        self.env.push(format!("{}={}", key, value));
+        self
+    }
+
+    pub fn stdin(&mut self, stdin: impl Read + 'static) -> &mut Wasi {
+        self.stdin = Box::new(stdin);
+        self
+    }
+
+    pub fn stdout(&mut self, stdout: impl Write + 'static) -> &mut Wasi {
+        self.stdout = Box::new(stdout);
+        self
+    }
+
+    pub fn stderr(&mut self, stderr: impl Write + 'static) -> &mut Wasi {
+        self.stderr = Box::new(stderr);
+        self
+    }
+
+    /// Gives the program access to the host directory `host`, which it
+    /// sees as `guest`.
+    pub fn preopen_dir(&mut self, host: impl AsRef<Path>, guest: &str) -> io::Result<&mut Wasi> {
+        let root = host.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::from(io::ErrorKind::NotADirectory));
+        }
+        let fd = self.next_fd();
+        self.fds.insert(
+            fd,
+            Descriptor::Dir {
+                path: root.clone(),
+                root,
+                name: Some(guest.to_string()),
+            },
+        );
+        Ok(self)
+    }
+
+    /// Provides the WASI functions to modules linked by `linker`.
+    pub fn link(self, linker: &mut Linker) {
+        let wasi = Rc::new(RefCell::new(self));
+
+        // Each function gets the caller's memory, and returns an errno
+        macro_rules! wasi {
+            ($name:ident($($arg:ident: $t:ty),*)) => {{
+                let wasi = wasi.clone();
+                linker.func(
+                    MODULE,
+                    stringify!($name),
+                    move |caller: &mut Caller<'_>, $($arg: $t),*| -> Result<u32, Trap> {
+                        let memory = caller.memory().ok_or_else(|| {
+                            Trap::new(TrapKind::Host("WASI needs a memory to work with".to_string()))
+                        })?;
+                        Ok(match wasi.borrow_mut().$name(memory, $($arg),*) {
+                            Ok(()) => 0,
+                            Err(errno) => errno as u32,
+                        })
+                    },
+                );
+            }};
+        }
+
+        wasi!(args_get(argv: u32, buf: u32));
+        wasi!(args_sizes_get(argc: u32, buf_size: u32));
+        wasi!(environ_get(environ: u32, buf: u32));
+        wasi!(environ_sizes_get(count: u32, buf_size: u32));
+        wasi!(clock_res_get(id: u32, resolution: u32));
+        wasi!(clock_time_get(id: u32, precision: u64, time: u32));
+        wasi!(random_get(buf: u32, len: u32));
+        wasi!(fd_read(fd: u32, iovs: u32, iovs_len: u32, nread: u32));
+        wasi!(fd_write(fd: u32, iovs: u32, iovs_len: u32, nwritten: u32));
+        wasi!(fd_seek(fd: u32, offset: i64, whence: u32, new_offset: u32));
+        wasi!(fd_tell(fd: u32, offset: u32));
+        wasi!(fd_close(fd: u32));
+        wasi!(fd_fdstat_get(fd: u32, stat: u32));
+        wasi!(fd_filestat_get(fd: u32, stat: u32));
+        wasi!(fd_prestat_get(fd: u32, prestat: u32));
+        wasi!(fd_prestat_dir_name(fd: u32, path: u32, path_len: u32));
+        wasi!(fd_readdir(fd: u32, buf: u32, buf_len: u32, cookie: u64, buf_used: u32));
+        wasi!(path_open(
+            fd: u32,
+            dirflags: u32,
+            path: u32,
+            path_len: u32,
+            oflags: u32,
+            rights: u64,
+            rights_inheriting: u64,
+            fdflags: u32,
+            opened: u32
+        ));
+        linker.func(
+            MODULE,
+            "proc_exit",
+            |_: &mut Caller<'_>, status: i32| -> Result<(), Trap> {
+                Err(Trap::new(TrapKind::Exit(status)))
+            },
+        );
+        linker.func(MODULE, "sched_yield", |_: &mut Caller<'_>| 0u32);
+    }
+
+    fn next_fd(&self) -> u32 {
+        (0..)
+            .find(|fd| !self.fds.contains_key(fd))
+            .unwrap_or(u32::MAX)
+    }
+
+    fn args_get(&mut self, memory: &mut Memory, argv: u32, buf: u32) -> WasiResult<()> {
+        write_strings(memory, &self.args, argv, buf)
+    }
+
+    fn args_sizes_get(&mut self, memory: &mut Memory, argc: u32, buf_size: u32) -> WasiResult<()> {
+        write_sizes(memory, &self.args, argc, buf_size)
+    }
+
+    fn environ_get(&mut self, memory: &mut Memory, environ: u32, buf: u32) -> WasiResult<()> {
+        write_strings(memory, &self.env, environ, buf)
+    }
+
+    fn environ_sizes_get(
+        &mut self,
+        memory: &mut Memory,
+        count: u32,
+        buf_size: u32,
+    ) -> WasiResult<()> {
+        write_sizes(memory, &self.env, count, buf_size)
+    }
+
+    fn clock_res_get(&mut self, memory: &mut Memory, id: u32, resolution: u32) -> WasiResult<()> {
+        if id > 3 {
+            return Err(Errno::Inval);
+        }
+        write_u64(memory, resolution, 1)
+    }
+
+    /// Reads the realtime clock (0) or, for the monotonic and CPU time
+    /// clocks, the time since the environment was created.
+    fn clock_time_get(
+        &mut self,
+        memory: &mut Memory,
+        id: u32,
+        _precision: u64,
+        time: u32,
+    ) -> WasiResult<()> {
+        let nanos = match id {
+            0 => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| Errno::Io)?
+                .as_nanos(),
+            1..=3 => self.start.elapsed().as_nanos(),
+            _ => return Err(Errno::Inval),
+        };
+        write_u64(memory, time, nanos as u64)
+    }
+
+    // An xorshift generator, seeded from the randomness std keys hash maps
+    // with, which is good enough for programs being debugged
+    fn random_get(&mut self, memory: &mut Memory, buf: u32, len: u32) -> WasiResult<()> {
+        let mut bytes = Vec::with_capacity(len as usize);
+        while bytes.len() < len as usize {
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            bytes.extend_from_slice(&self.rng.to_le_bytes());
+        }
+        bytes.truncate(len as usize);
+        Ok(memory.write(u64::from(buf), &bytes)?)
+    }
+
+    fn fd_read(
+        &mut self,
+        memory: &mut Memory,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nread: u32,
+    ) -> WasiResult<()> {
+        let reader: &mut dyn Read = match self.fds.get_mut(&fd).ok_or(Errno::Badf)? {
+            Descriptor::Stdin => &mut self.stdin,
+            Descriptor::File(file) => file,
+            Descriptor::Dir { .. } => return Err(Errno::Isdir),
+            Descriptor::Stdout | Descriptor::Stderr => return Err(Errno::Badf),
+        };
+        // Every buffer is checked before anything is read, and reading goes
+        // a page at a time, so that a buffer's length can't make the host
+        // allocate more than a page
+        let iovs = iovecs(memory, iovs, iovs_len)?;
+        for &(ptr, len) in &iovs {
+            memory.check(u64::from(ptr), u64::from(len))?;
+        }
+        let mut buf = Vec::new();
+        let mut total = 0;
+        'iovs: for (ptr, len) in iovs {
+            let mut done = 0;
+            while done < len {
+                let want = (len - done).min(PAGE_SIZE as u32) as usize;
+                buf.resize(buf.len().max(want), 0);
+                let n = reader.read(&mut buf[..want])?;
+                memory.write(u64::from(ptr) + u64::from(done), &buf[..n])?;
+                done += n as u32;
+                total += n as u32;
+                if n < want {
+                    break 'iovs;
+                }
+            }
+        }
+        write_u32(memory, nread, total)
+    }
+
+    fn fd_write(
+        &mut self,
+        memory: &mut Memory,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nwritten: u32,
+    ) -> WasiResult<()> {
+        let writer: &mut dyn Write = match self.fds.get_mut(&fd).ok_or(Errno::Badf)? {
+            Descriptor::Stdout => &mut self.stdout,
+            Descriptor::Stderr => &mut self.stderr,
+            Descriptor::File(file) => file,
+            Descriptor::Dir { .. } => return Err(Errno::Isdir),
+            Descriptor::Stdin => return Err(Errno::Badf),
+        };
+        let mut total = 0;
+        for (ptr, len) in iovecs(memory, iovs, iovs_len)? {
+            let bytes = memory.bytes(u64::from(ptr), u64::from(len))?;
+            writer.write_all(&bytes)?;
+            total += len;
+        }
+        writer.flush()?;
+        write_u32(memory, nwritten, total)
+    }
+
+    fn fd_seek(
+        &mut self,
+        memory: &mut Memory,
+        fd: u32,
+        offset: i64,
+        whence: u32,
+        new_offset: u32,
+    ) -> WasiResult<()> {
+        let pos = match whence {
+            0 => SeekFrom::Start(u64::try_from(offset).map_err(|_| Errno::Inval)?),
+            1 => SeekFrom::Current(offset),
+            2 => SeekFrom::End(offset),
+            _ => return Err(Errno::Inval),
+        };
+        let offset = self.file(fd)?.seek(pos)?;
+        write_u64(memory, new_offset, offset)
+    }
+
+    fn fd_tell(&mut self, memory: &mut Memory, fd: u32, offset: u32) -> WasiResult<()> {
+        let pos = self.file(fd)?.stream_position()?;
+        write_u64(memory, offset, pos)
+    }
+
+    fn fd_close(&mut self, _: &mut Memory, fd: u32) -> WasiResult<()> {
+        self.fds.remove(&fd).map(|_| ()).ok_or(Errno::Badf)
+    }
+
+    /// Writes an fdstat: the file type, flags and rights of `fd`.
+    fn fd_fdstat_get(&mut self, memory: &mut Memory, fd: u32, stat: u32) -> WasiResult<()> {
+        let filetype = match self.fds.get(&fd).ok_or(Errno::Badf)? {
+            Descriptor::Stdin | Descriptor::Stdout | Descriptor::Stderr => {
+                FILETYPE_CHARACTER_DEVICE
+            }
+            Descriptor::File(_) => FILETYPE_REGULAR_FILE,
+            Descriptor::Dir { .. } => FILETYPE_DIRECTORY,
+        };
+        let mut bytes = [0; 24];
+        bytes[0] = filetype;
+        bytes[8..16].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+        bytes[16..24].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+        Ok(memory.write(u64::from(stat), &bytes)?)
+    }
+
+    /// Writes a filestat, with the size and times of the file.
+    fn fd_filestat_get(&mut self, memory: &mut Memory, fd: u32, stat: u32) -> WasiResult<()> {
+        let mut bytes = [0; 64];
+        match self.fds.get(&fd).ok_or(Errno::Badf)? {
+            Descriptor::Stdin | Descriptor::Stdout | Descriptor::Stderr => {
+                bytes[16] = FILETYPE_CHARACTER_DEVICE;
+            }
+            Descriptor::File(file) => filestat(&file.metadata()?, &mut bytes),
+            Descriptor::Dir { path, .. } => filestat(&fs::metadata(path)?, &mut bytes),
+        }
+        Ok(memory.write(u64::from(stat), &bytes)?)
+    }
+
+    fn fd_prestat_get(&mut self, memory: &mut Memory, fd: u32, prestat: u32) -> WasiResult<()> {
+        let name = self.preopen_name(fd)?;
+        let mut bytes = [0; 8];
+        bytes[4..].copy_from_slice(&(name.len() as u32).to_le_bytes());
+        Ok(memory.write(u64::from(prestat), &bytes)?)
+    }
+
+    fn fd_prestat_dir_name(
+        &mut self,
+        memory: &mut Memory,
+        fd: u32,
+        path: u32,
+        path_len: u32,
+    ) -> WasiResult<()> {
+        let name = self.preopen_name(fd)?.as_bytes();
+        let len = name.len().min(path_len as usize);
+        Ok(memory.write(u64::from(path), &name[..len])?)
+    }
+
+    /// Writes as many directory entries as fit into the buffer, starting
+    /// with the one at `cookie`. The last one is cut short if it doesn't
+    /// fit, which tells the program to try again with a larger buffer.
+    fn fd_readdir(
+        &mut self,
+        memory: &mut Memory,
+        fd: u32,
+        buf: u32,
+        buf_len: u32,
+        cookie: u64,
+        buf_used: u32,
+    ) -> WasiResult<()> {
+        let Descriptor::Dir { path, .. } = self.fds.get(&fd).ok_or(Errno::Badf)? else {
+            return Err(Errno::Notdir);
+        };
+        let mut entries = vec![
+            (".".to_string(), FILETYPE_DIRECTORY),
+            ("..".to_string(), FILETYPE_DIRECTORY),
+        ];
+        let mut children = fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+                let ty = entry.file_type()?;
+                let filetype = if ty.is_dir() {
+                    FILETYPE_DIRECTORY
+                } else if ty.is_file() {
+                    FILETYPE_REGULAR_FILE
+                } else if ty.is_symlink() {
+                    FILETYPE_SYMBOLIC_LINK
+                } else {
+                    FILETYPE_UNKNOWN
+                };
+                Ok((entry.file_name().to_string_lossy().to_string(), filetype))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        children.sort();
+        entries.extend(children);
+
+        let mut bytes = Vec::new();
+        for (i, (name, filetype)) in entries.iter().enumerate().skip(cookie as usize) {
+            if bytes.len() >= buf_len as usize {
+                break;
+            }
+            bytes.extend_from_slice(&(i as u64 + 1).to_le_bytes());
+            bytes.extend_from_slice(&0u64.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&[*filetype, 0, 0, 0]);
+            bytes.extend_from_slice(name.as_bytes());
+        }
+        bytes.truncate(buf_len as usize);
+        memory.write(u64::from(buf), &bytes)?;
+        write_u32(memory, buf_used, bytes.len() as u32)
+    }
+
+    /// Opens `path` relative to the directory `fd`, as a file or, if it is
+    /// one, a directory.
+    #[allow(clippy::too_many_arguments)]
+    fn path_open(
+        &mut self,
+        memory: &mut Memory,
+        fd: u32,
+        _dirflags: u32,
+        path: u32,
+        path_len: u32,
+        oflags: u32,
+        rights: u64,
+        _rights_inheriting: u64,
+        fdflags: u32,
+        opened: u32,
+    ) -> WasiResult<()> {
+        let Descriptor::Dir {
+            path: dir, root, ..
+        } = self.fds.get(&fd).ok_or(Errno::Badf)?
+        else {
+            return Err(Errno::Notdir);
+        };
+        let path = read_string(memory, path, path_len)?;
+        let root = root.clone();
+        let host = resolve(dir, &root, &path)?;
+
+        let creat = oflags & OFLAGS_CREAT != 0;
+        let descriptor = if oflags & OFLAGS_DIRECTORY != 0 || (!creat && host.is_dir()) {
+            if !host.is_dir() {
+                return Err(if host.exists() {
+                    Errno::Notdir
+                } else {
+                    Errno::Noent
+                });
+            }
+            Descriptor::Dir {
+                path: host,
+                root,
+                name: None,
+            }
+        } else {
+            let append = fdflags & FDFLAGS_APPEND != 0;
+            let write = rights & RIGHTS_FD_WRITE != 0 || append;
+            let file = OpenOptions::new()
+                .read(rights & RIGHTS_FD_READ != 0 || !write)
+                .write(write && !append)
+                .append(append)
+                .create(creat)
+                .create_new(creat && oflags & OFLAGS_EXCL != 0)
+                .truncate(oflags & OFLAGS_TRUNC != 0)
+                .open(&host)?;
+            Descriptor::File(file)
+        };
+        let fd = self.next_fd();
+        self.fds.insert(fd, descriptor);
+        write_u32(memory, opened, fd)
+    }
+
+    fn file(&mut self, fd: u32) -> WasiResult<&mut File> {
+        match self.fds.get_mut(&fd).ok_or(Errno::Badf)? {
+            Descriptor::File(file) => Ok(file),
+            Descriptor::Dir { .. } => Err(Errno::Isdir),
+            _ => Err(Errno::Spipe),
+        }
+    }
+
+    fn preopen_name(&self, fd: u32) -> WasiResult<&str> {
+        match self.fds.get(&fd) {
+            Some(Descriptor::Dir {
+                name: Some(name), ..
+            }) => Ok(name),
+            _ => Err(Errno::Badf),
+        }
+    }
+}
+
+/// Finds the host path for the guest's `path` relative to `dir`, which
+/// must stay inside `root`, including through symbolic links. The path
+/// returned has its links resolved, so opening it opens what was checked.
+fn resolve(dir: &Path, root: &Path, path: &str) -> WasiResult<PathBuf> {
+    let mut host = dir.to_path_buf();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => host.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if host == root {
+                    return Err(Errno::Notcapable);
+                }
+                host.pop();
+            }
+            Component::RootDir | Component::Prefix(_) => return Err(Errno::Notcapable),
+        }
+    }
+    // As many links as Linux follows before giving up with ELOOP
+    for _ in 0..40 {
+        let real = match host.canonicalize() {
+            Ok(real) => real,
+            // A link to a file that doesn't exist yet, which creating the
+            // file would follow
+            Err(_)
+                if host
+                    .symlink_metadata()
+                    .is_ok_and(|m| m.file_type().is_symlink()) =>
+            {
+                let target = fs::read_link(&host)?;
+                host = host.parent().ok_or(Errno::Noent)?.join(target);
+                continue;
+            }
+            // A file that doesn't exist yet is checked through its parent
+            Err(_) => match (host.parent(), host.file_name()) {
+                (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+                _ => return Err(Errno::Noent),
+            },
+        };
+        if !real.starts_with(root) {
+            return Err(Errno::Notcapable);
+        }
+        return Ok(real);
+    }
+    Err(Errno::Loop)
+}
+
+fn filestat(metadata: &Metadata, bytes: &mut [u8; 64]) {
+    let ty = metadata.file_type();
+    bytes[16] = if ty.is_dir() {
+        FILETYPE_DIRECTORY
+    } else if ty.is_file() {
+        FILETYPE_REGULAR_FILE
+    } else if ty.is_symlink() {
+        FILETYPE_SYMBOLIC_LINK
+    } else {
+        FILETYPE_UNKNOWN
+    };
+    let nanos = |time: io::Result<SystemTime>| {
+        time.ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos() as u64)
+    };
+    bytes[24..32].copy_from_slice(&1u64.to_le_bytes());
+    bytes[32..40].copy_from_slice(&metadata.len().to_le_bytes());
+    bytes[40..48].copy_from_slice(&nanos(metadata.accessed()).to_le_bytes());
+    bytes[48..56].copy_from_slice(&nanos(metadata.modified()).to_le_bytes());
+    bytes[56..64].copy_from_slice(&nanos(metadata.modified()).to_le_bytes());
+}
+
+fn write_u32(memory: &mut Memory, addr: u32, v: u32) -> WasiResult<()> {
+    Ok(memory.write(u64::from(addr), &v.to_le_bytes())?)
+}
+
+fn write_u64(memory: &mut Memory, addr: u32, v: u64) -> WasiResult<()> {
+    Ok(memory.write(u64::from(addr), &v.to_le_bytes())?)
+}
+
+fn read_string(memory: &Memory, ptr: u32, len: u32) -> WasiResult<String> {
+    let bytes = memory.bytes(u64::from(ptr), u64::from(len))?;
+    String::from_utf8(bytes).map_err(|_| Errno::Inval)
+}
+
+/// Reads an array of `len` (pointer, length) pairs.
+fn iovecs(memory: &Memory, iovs: u32, len: u32) -> WasiResult<Vec<(u32, u32)>> {
+    let bytes = memory.bytes(u64::from(iovs), u64::from(len) * 8)?;
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|iov| {
+            let word = |i: usize| u32::from_le_bytes(iov[i..i + 4].try_into().unwrap());
+            (word(0), word(4))
+        })
+        .collect())
+}
+
+/// Writes `strings` NUL-terminated to `buf`, and pointers to them to
+/// `ptrs`, as `args_get` and `environ_get` do.
+fn write_strings(memory: &mut Memory, strings: &[String], ptrs: u32, buf: u32) -> WasiResult<()> {
+    let mut ptr = u64::from(buf);
+    for (i, s) in strings.iter().enumerate() {
+        let addr = ptr.try_into().map_err(|_| Errno::Fault)?;
+        memory.write(u64::from(ptrs) + 4 * i as u64, &u32::to_le_bytes(addr))?;
+        memory.write(ptr, s.as_bytes())?;
+        memory.write(ptr + s.len() as u64, &[0])?;
+        ptr += s.len() as u64 + 1;
+    }
+    Ok(())
+}
+
+fn write_sizes(memory: &mut Memory, strings: &[String], count: u32, size: u32) -> WasiResult<()> {
+    write_u32(memory, count, strings.len() as u32)?;
+    let total = strings.iter().map(|s| s.len() + 1).sum::<usize>();
+    write_u32(memory, size, total as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use crate::runtime::instance::Instance;
+    use crate::runtime::linker::Linker;
+    use crate::runtime::store::Store;
+    use crate::runtime::trap::TrapKind;
+    use crate::runtime::value::Value;
+    use crate::runtime::wasi::{Errno, Wasi};
+    use crate::wat::parse::parse_module;
+
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn instantiate(wasi: Wasi, text: &str) -> (Store, Instance) {
+        let mut linker = Linker::new();
+        wasi.link(&mut linker);
+        let mut store = Store::new();
+        let instance = linker
+            .instantiate(&mut store, &parse_module(text).unwrap())
+            .unwrap();
+        (store, instance)
+    }
+
+    #[test]
+    fn test_stdio_and_args() {
+        let stdout = Output::default();
+        let mut wasi = Wasi::new();
+        wasi.arg("prog").arg("world").stdout(stdout.clone());
+        let (mut store, instance) = instantiate(
+            wasi,
+            r#"
+            (import "wasi_snapshot_preview1" "fd_write"
+              (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "args_get"
+              (func $args_get (param i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+            (memory 1)
+            (data (i32.const 16) "hello\n")
+            (func (export "_start")
+              (i32.store (i32.const 0) (i32.const 16))
+              (i32.store (i32.const 4) (i32.const 6))
+              (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+              (drop (call $args_get (i32.const 100) (i32.const 200)))
+              (i32.store (i32.const 0) (i32.load (i32.const 104)))
+              (i32.store (i32.const 4) (i32.const 5))
+              (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+              (call $proc_exit (i32.load (i32.const 8))))
+        "#,
+        );
+        let trap = instance.invoke(&mut store, "_start", &[]).unwrap_err();
+        assert_eq!(trap.kind(), &TrapKind::Exit(5));
+        assert_eq!(*stdout.0.borrow(), b"hello\nworld");
+    }
+
+    #[test]
+    fn test_read_bounds() {
+        let mut wasi = Wasi::new();
+        wasi.stdin(io::Cursor::new(b"abcdef".to_vec()));
+        let (mut store, instance) = instantiate(
+            wasi,
+            r#"
+            (import "wasi_snapshot_preview1" "fd_read"
+              (func $fd_read (param i32 i32 i32 i32) (result i32)))
+            (memory 1)
+            ;; Reads into the buffers of the iovecs at 0, leaving the
+            ;; count at 100
+            (func (export "read") (param i32 i32 i32 i32) (result i32 i32)
+              (i32.store (i32.const 0) (local.get 0))
+              (i32.store (i32.const 4) (local.get 1))
+              (i32.store (i32.const 8) (local.get 2))
+              (i32.store (i32.const 12) (local.get 3))
+              (call $fd_read (i32.const 0) (i32.const 0) (i32.const 2) (i32.const 100))
+              (i32.load (i32.const 100)))
+            (func (export "byte") (param i32) (result i32) (i32.load8_u (local.get 0)))
+        "#,
+        );
+        let mut read = |args: [i32; 4]| {
+            let args = args.map(Value::I32);
+            instance.invoke(&mut store, "read", &args).unwrap()
+        };
+        // A buffer past the end of memory fails before anything is read,
+        // however long it claims to be
+        let fault = Value::I32(Errno::Fault as i32);
+        assert_eq!(read([200, 2, 256, -256])[0], fault);
+        assert_eq!(read([200, 2, 65535, 2])[0], fault);
+        // Reading stops at the end of the input
+        assert_eq!(read([200, 2, 300, 100]), [Value::I32(0), Value::I32(6)]);
+        let byte = |store: &mut Store, addr| {
+            instance.invoke(store, "byte", &[Value::I32(addr)]).unwrap()[0]
+        };
+        assert_eq!(byte(&mut store, 201), Value::I32(i32::from(b'b')));
+        assert_eq!(byte(&mut store, 303), Value::I32(i32::from(b'f')));
+    }
+
+    #[test]
+    fn test_preopened_dirs() {
+        let dir = std::env::temp_dir().join(format!("wasmdbg-wasi-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("in.txt"), "data").unwrap();
+        let stdout = Output::default();
+        let mut wasi = Wasi::new();
+        wasi.stdout(stdout.clone());
+        wasi.preopen_dir(&dir, ".").unwrap();
+        let (mut store, instance) = instantiate(
+            wasi,
+            r#"
+            (import "wasi_snapshot_preview1" "fd_prestat_dir_name"
+              (func $dir_name (param i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "path_open"
+              (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_read"
+              (func $fd_read (param i32 i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_write"
+              (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_readdir"
+              (func $fd_readdir (param i32 i32 i32 i64 i32) (result i32)))
+            (memory 1)
+            (data (i32.const 1000) "in.txt../in.txtout.txt")
+            (func (export "dir_name") (result i32)
+              (drop (call $dir_name (i32.const 3) (i32.const 0) (i32.const 1)))
+              (i32.load8_u (i32.const 0)))
+            ;; Opens a path from the data segment, leaving the fd at 300
+            (func $open (param i32 i32 i32 i64) (result i32)
+              (call $path_open (i32.const 3) (i32.const 0) (local.get 0) (local.get 1)
+                (local.get 2) (local.get 3) (i64.const 0) (i32.const 0) (i32.const 300)))
+            (func (export "cat") (result i32)
+              (local $errno i32)
+              (local.set $errno (call $open (i32.const 1000) (i32.const 6) (i32.const 0) (i64.const 2)))
+              (if (local.get $errno) (then (return (local.get $errno))))
+              (i32.store (i32.const 0) (i32.const 400))
+              (i32.store (i32.const 4) (i32.const 64))
+              (drop (call $fd_read (i32.load (i32.const 300)) (i32.const 0) (i32.const 1) (i32.const 8)))
+              (i32.store (i32.const 4) (i32.load (i32.const 8)))
+              (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+              (call $fd_close (i32.load (i32.const 300))))
+            (func (export "escape") (result i32)
+              (call $open (i32.const 1006) (i32.const 9) (i32.const 0) (i64.const 2)))
+            (func (export "create") (result i32)
+              (i32.store (i32.const 0) (i32.const 1000))
+              (i32.store (i32.const 4) (i32.const 6))
+              (drop (call $open (i32.const 1015) (i32.const 7) (i32.const 9) (i64.const 64)))
+              (drop (call $fd_write (i32.load (i32.const 300)) (i32.const 0) (i32.const 1) (i32.const 8)))
+              (call $fd_close (i32.load (i32.const 300))))
+            (func (export "readdir") (result i32)
+              (drop (call $fd_readdir (i32.const 3) (i32.const 500) (i32.const 256) (i64.const 0)
+                (i32.const 8)))
+              (i32.load (i32.const 8)))
+        "#,
+        );
+        let mut call = |name: &str| match instance.invoke(&mut store, name, &[]).unwrap()[..] {
+            [Value::I32(v)] => v,
+            ref results => panic!("unexpected results {:?}", results),
+        };
+        assert_eq!(call("dir_name"), i32::from(b'.'));
+        assert_eq!(call("cat"), 0);
+        assert_eq!(*stdout.0.borrow(), b"data");
+        assert_eq!(call("escape"), Errno::Notcapable as i32);
+        assert_eq!(call("create"), 0);
+        assert_eq!(fs::read_to_string(dir.join("out.txt")).unwrap(), "in.txt");
+        // ".", "..", "in.txt" and "out.txt", each after a 24 byte header
+        assert_eq!(call("readdir"), 24 * 4 + 1 + 2 + 6 + 7);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dangling_symlink() {
+        use std::os::unix::fs::symlink;
+        let base = std::env::temp_dir().join(format!("wasmdbg-wasi-link-{}", std::process::id()));
+        let (dir, outside) = (base.join("dir"), base.join("outside.txt"));
+        fs::create_dir_all(&dir).unwrap();
+        // Neither target exists, so both links dangle
+        symlink(&outside, dir.join("escape")).unwrap();
+        symlink("new.txt", dir.join("inside")).unwrap();
+        let mut wasi = Wasi::new();
+        wasi.preopen_dir(&dir, ".").unwrap();
+        let (mut store, instance) = instantiate(
+            wasi,
+            r#"
+            (import "wasi_snapshot_preview1" "path_open"
+              (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+            (memory 1)
+            (data (i32.const 1000) "escapeinside")
+            ;; Creates the file at a path from the data segment
+            (func (export "create") (param i32 i32) (result i32)
+              (call $path_open (i32.const 3) (i32.const 0) (local.get 0) (local.get 1)
+                (i32.const 1) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 300)))
+        "#,
+        );
+        let mut create = |path: i32, len: i32| match instance.invoke(
+            &mut store,
+            "create",
+            &[Value::I32(path), Value::I32(len)],
+        ) {
+            Ok(results) => results,
+            Err(trap) => panic!("{}", trap),
+        };
+        assert_eq!(create(1000, 6), [Value::I32(Errno::Notcapable as i32)]);
+        assert!(!outside.exists());
+        assert_eq!(create(1006, 6), [Value::I32(0)]);
+        assert!(dir.join("new.txt").is_file());
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,13 +1,163 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Cursor, Read, Write};
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
+use crate::encode::{Encode, encode_sized};
+use crate::instr::Instr;
+use crate::instr::expr::Expr;
+use crate::parseable::{ParseError, Parseable, ParserLimits};
 use crate::section::Section;
-use crate::types::leb128::Leb128;
+use crate::types::leb128::{self, Leb128};
 use crate::types::primitives::Size;
+use crate::types::val_type::ValType;
+
+/// A run of `n` locals of the same type, as declared in a function body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locals {
+    n: u32,
+    t: ValType,
+}
+
+impl Locals {
+    pub fn new(n: u32, t: ValType) -> Locals {
+        Locals { n, t }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.n
+    }
+
+    pub fn val_type(&self) -> ValType {
+        self.t
+    }
+}
+
+impl Parseable for Locals {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
+    where
+        Self: Sized,
+    {
+        let n = u32::from(Leb128::<u32>::parse(reader)?);
+        let t = ValType::parse(reader)?;
+        Ok(Locals { n, t })
+    }
+}
+
+impl Encode for Locals {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        Leb128::from(self.n).encode(writer)?;
+        self.t.encode(writer)
+    }
+}
+
+/// The body of a single function.
+pub struct Code {
+    size: Size,
+    locals: Vec<Locals>,
+    body: Expr,
+    // Offset of each instruction in `body`, relative to the start of the
+    // code section's contents. This is the address space DWARF uses for
+    // WebAssembly.
+    offsets: Vec<usize>,
+}
+
+impl Code {
+    /// A function body with the given locals. `body` must include the final
+    /// `end`. Its offsets are relative to the body until it is added to a
+    /// `CodeSec`.
+    pub fn new(locals: Vec<Locals>, body: Expr) -> Code {
+        let mut bytes = Vec::new();
+        let mut offsets = Vec::new();
+        let _ = locals.encode(&mut bytes);
+        for instr in body.instrs() {
+            offsets.push(bytes.len());
+            let _ = instr.encode(&mut bytes);
+        }
+        Code {
+            size: Size(bytes.len() as u32),
+            locals,
+            body,
+            offsets,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn locals(&self) -> &[Locals] {
+        &self.locals
+    }
+
+    pub fn body(&self) -> &Expr {
+        &self.body
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// All locals declared by the body, expanded one entry per local. This
+    /// does not include the function's parameters.
+    pub fn local_types(&self) -> impl Iterator<Item = ValType> + '_ {
+        self.locals
+            .iter()
+            .flat_map(|l| std::iter::repeat_n(l.t, l.n as usize))
+    }
+
+    fn parse(reader: &mut BufReader<Cursor<Vec<u8>>>) -> Result<Self, ParseError> {
+        let size = u32::from(Leb128::<u32>::parse(reader)?);
+        let start = position(reader);
+
+        let locals = Vec::<Locals>::parse(reader)?;
+        let count = locals.iter().map(|l| u64::from(l.n)).sum();
+        ParserLimits::current().check_locals(count)?;
+        let mut instrs = Vec::new();
+        let mut offsets = Vec::new();
+        let mut depth = 0;
+        loop {
+            offsets.push(position(reader));
+            let instr = Instr::parse(reader)?;
+            let done = Expr::close(&instr, &mut depth)?;
+            instrs.push(instr);
+            if done {
+                break;
+            }
+        }
+
+        let read = position(reader) - start;
+        if read != size as usize {
+            return Err(ParseError::Other(format!(
+                "function body size mismatch: expected {} bytes, read {}",
+                size, read
+            )));
+        }
+
+        Ok(Code {
+            size: Size(size),
+            locals,
+            body: Expr::new(instrs),
+            offsets,
+        })
+    }
+}
+
+impl Encode for Code {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| {
+            self.locals.encode(writer)?;
+            self.body.encode(writer)
+        })
+    }
+}
+
+// The number of bytes consumed from the section so far.
+fn position(reader: &BufReader<Cursor<Vec<u8>>>) -> usize {
+    reader.get_ref().position() as usize - reader.buffer().len()
+}
 
 pub struct CodeSec {
     size: Size,
+    codes: Vec<Code>,
 }
 
 impl Section for CodeSec {
@@ -20,38 +170,141 @@ impl Section for CodeSec {
     }
 }
 
+impl Encode for CodeSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.codes.encode(writer))
+    }
+}
+
 impl Display for CodeSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Codes:")?;
+        for (i, code) in self.codes.iter().enumerate() {
+            write!(f, "* code {}: size {}, locals: [", i, code.size)?;
+            for (j, local) in code.local_types().enumerate() {
+                if j > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", local.name())?;
+            }
+            writeln!(f, "]")?;
+            for (offset, instr) in code.offsets.iter().zip(code.body.instrs()) {
+                writeln!(f, "  {:#06x}: {}", offset, instr)?;
+            }
+        }
+        Ok(())
     }
 }
 
 impl CodeSec {
+    pub fn new(mut codes: Vec<Code>) -> CodeSec {
+        // Rebase each body's offsets onto the start of the section
+        let mut start = leb128::unsigned_len(codes.len() as u64);
+        for code in &mut codes {
+            start += leb128::unsigned_len(u64::from(code.size.0));
+            for offset in &mut code.offsets {
+                *offset += start;
+            }
+            start += code.size.0 as usize;
+        }
+        CodeSec {
+            size: Size(start as u32),
+            codes,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
-        let bytes_remaining = usize::try_from(size).unwrap();
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(ParseError::WrongNumBytesRead(Asked(1), Received(n)));
-            }
+        // Read the whole section up front so that instruction offsets can be
+        // recorded relative to its start. The buffer grows as bytes arrive,
+        // rather than trusting the size.
+        let mut bytes = Vec::new();
+        reader.take(u64::from(size)).read_to_end(&mut bytes)?;
+        if bytes.len() != size as usize {
+            return Err(ParseError::Other(format!(
+                "code section size mismatch: expected {} bytes, read {}",
+                size,
+                bytes.len()
+            )));
+        }
+        let mut section = BufReader::new(Cursor::new(bytes));
 
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
+        let num = u32::from(Leb128::<u32>::parse(&mut section)?);
+        let mut codes = Vec::new();
+        for _ in 0..num {
+            codes.push(Code::parse(&mut section)?);
+        }
+
+        if position(&section) != size as usize {
+            return Err(ParseError::Other(
+                "section size mismatch in code section".to_string(),
+            ));
         }
 
-        Ok(CodeSec { size: Size(size) })
+        Ok(CodeSec {
+            size: Size(size),
+            codes,
+        })
+    }
+
+    pub fn codes(&self) -> &[Code] {
+        &self.codes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instr::numeric::NumericInstr;
+    use crate::types::num_type::NumType;
+    use crate::types::primitives::LocalIdx;
+
+    #[test]
+    fn test_code_sec() {
+        // Two bodies: one with two i32 locals and one i64 local that adds
+        // its first two locals, and an empty one.
+        let bytes: [u8; 17] = [
+            0x10, 0x02, 0x0b, 0x02, 0x02, 0x7f, 0x01, 0x7e, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+            0x02, 0x00, 0x0b,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let codesec = CodeSec::parse(&mut reader).expect("The parsed section");
+        assert_eq!(codesec.codes().len(), 2);
+
+        let code = &codesec.codes()[0];
+        assert_eq!(
+            code.local_types().collect::<Vec<_>>(),
+            vec![
+                ValType::Num(NumType::I32),
+                ValType::Num(NumType::I32),
+                ValType::Num(NumType::I64),
+            ]
+        );
+        assert_eq!(
+            code.body().instrs(),
+            &[
+                Instr::LocalGet(LocalIdx(0)),
+                Instr::LocalGet(LocalIdx(1)),
+                Instr::Numeric(NumericInstr::I32Add),
+                Instr::End,
+            ]
+        );
+        assert_eq!(code.offsets(), &[7, 9, 11, 12]);
+
+        let code = &codesec.codes()[1];
+        assert_eq!(code.body().instrs(), &[Instr::End]);
+        assert_eq!(code.offsets(), &[15]);
+    }
+
+    #[test]
+    fn test_body_size_mismatch() {
+        let bytes: [u8; 6] = [0x05, 0x01, 0x04, 0x00, 0x01, 0x0b];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(CodeSec::parse(&mut reader).is_err());
     }
 }
@@ -1,9 +1,10 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read as IoRead};
+use std::io::{BufReader, Cursor, Read as IoRead, Write};
 use std::result::Result;
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
-use crate::section::{Section, SectionParseError};
+use crate::encode::{Encode, encode_sized};
+use crate::parseable::{ParseError, Parseable, ParserLimits};
+use crate::section::{Section, SectionParseError, encoded_size};
 use crate::types::leb128::Leb128;
 use crate::types::primitives::Size;
 
@@ -23,6 +24,15 @@ impl Section for CustomSec {
     }
 }
 
+impl Encode for CustomSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| {
+            self.name.encode(writer)?;
+            writer.write_all(&self.data)
+        })
+    }
+}
+
 pub struct Read(usize);
 pub struct Remaining(usize);
 
@@ -44,13 +54,13 @@ impl From<std::io::Error> for CustomSecParseError {
     }
 }
 
-impl Into<SectionParseError> for CustomSecParseError {
-    fn into(self) -> SectionParseError {
-        let s: String = match self {
+impl From<CustomSecParseError> for SectionParseError {
+    fn from(value: CustomSecParseError) -> Self {
+        let s: String = match value {
             CustomSecParseError::Parse(e) => e.to_string(),
             CustomSecParseError::IoError(e) => e.to_string(),
             CustomSecParseError::ByteCount(read, remaining) => {
-                format!("Should have read {}, but read {}", read.0, remaining.0)
+                format!("Should have read {}, but read {}", remaining.0, read.0)
             }
         };
         SectionParseError(s)
@@ -62,46 +72,52 @@ impl CustomSec {
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
-
-        let name = String::parse(reader)?;
-        let size_usize = usize::try_from(size).unwrap();
-        let bytes_remaining = size_usize - name.len();
-
-        let mut data = Vec::<u8>::with_capacity(bytes_remaining);
-
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(CustomSecParseError::Parse(ParseError::WrongNumBytesRead(
-                    Asked(1),
-                    Received(n),
-                )));
-            }
-            data.extend_from_slice(&buf);
-
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
-
-        if bytes_read != bytes_remaining {
+        ParserLimits::current().check_custom_section_size(size)?;
+
+        // The size covers the name's length prefix as well, so read the
+        // whole section before splitting off the name. The size comes from
+        // the input, so the buffer only grows as bytes actually arrive.
+        let mut bytes = Vec::<u8>::new();
+        reader.take(u64::from(size)).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != u64::from(size) {
             return Err(CustomSecParseError::ByteCount(
-                Read(bytes_read),
-                Remaining(bytes_remaining),
+                Read(bytes.len()),
+                Remaining(size as usize),
             ));
         }
 
+        let mut contents = BufReader::new(Cursor::new(bytes));
+        let name = String::parse(&mut contents)?;
+        let mut data = Vec::<u8>::new();
+        contents.read_to_end(&mut data)?;
+
         Ok(CustomSec {
             size: Size(size),
-            name: name,
-            data: data,
+            name,
+            data,
         })
     }
 }
 
+impl CustomSec {
+    pub fn new(name: &str, data: Vec<u8>) -> CustomSec {
+        let name = name.to_string();
+        CustomSec {
+            size: Size(encoded_size(&name).0 + data.len() as u32),
+            name,
+            data,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
 impl Display for CustomSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Size: {}", self.size)?;
@@ -109,3 +125,27 @@ impl Display for CustomSec {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_section() {
+        // The size counts the name's length byte; the trailing 0x0a belongs
+        // to the next section.
+        let bytes = [0x07, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x01, 0x02, 0x0a];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = match CustomSec::parse(&mut reader) {
+            Ok(sec) => sec,
+            Err(err) => panic!("{}", SectionParseError::from(err)),
+        };
+        assert_eq!(sec.name(), "name");
+        assert_eq!(sec.data(), &[0x01, 0x02]);
+        assert_eq!(u8::parse(&mut reader), Ok(0x0a));
+
+        let bytes = [0x07, 0x04, 0x6e, 0x61, 0x6d, 0x65];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(CustomSec::parse(&mut reader).is_err());
+    }
+}
@@ -1,14 +1,119 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::result::Result;
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
+use crate::encode::{Encode, encode_len, encode_sized};
+use crate::instr::expr::Expr;
+use crate::parseable::{ParseError, Parseable};
 use crate::section::Section;
+use crate::section::encoded_size;
 use crate::types::leb128::Leb128;
-use crate::types::primitives::Size;
+use crate::types::primitives::{DataIdx, MemIdx, Size, parse_bytes};
+
+pub enum DataMode {
+    Passive,
+    /// Copied into `mem` at `offset` during instantiation.
+    Active {
+        mem: MemIdx,
+        offset: Expr,
+    },
+}
+
+pub struct Data {
+    init: Vec<u8>,
+    mode: DataMode,
+    // Whether an active segment for memory 0 spelled out the index anyway.
+    explicit_mem: bool,
+}
+
+impl Data {
+    pub fn new(mode: DataMode, init: Vec<u8>) -> Data {
+        Data {
+            init,
+            mode,
+            explicit_mem: false,
+        }
+    }
+
+    pub fn init(&self) -> &[u8] {
+        &self.init
+    }
+
+    pub fn mode(&self) -> &DataMode {
+        &self.mode
+    }
+
+    /// The same segment, spelling out its memory index even if it is 0.
+    pub(crate) fn with_explicit_mem(self) -> Data {
+        Data {
+            explicit_mem: true,
+            ..self
+        }
+    }
+
+    /// Whether an active segment spells out its memory index.
+    pub(crate) fn explicit_mem(&self) -> bool {
+        self.explicit_mem
+    }
+}
+
+impl Parseable for Data {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
+    where
+        Self: Sized,
+    {
+        let kind = u32::from(Leb128::<u32>::parse(reader)?);
+        let mode = match kind {
+            0 => DataMode::Active {
+                mem: MemIdx(0),
+                offset: Expr::parse(reader)?,
+            },
+            1 => DataMode::Passive,
+            2 => {
+                let mem = MemIdx::parse(reader)?;
+                DataMode::Active {
+                    mem,
+                    offset: Expr::parse(reader)?,
+                }
+            }
+            kind => {
+                return Err(ParseError::Other(format!(
+                    "malformed data segment kind: {}",
+                    kind
+                )));
+            }
+        };
+        let init = parse_bytes(reader)?;
+        Ok(Data {
+            init,
+            mode,
+            explicit_mem: kind == 2,
+        })
+    }
+}
+
+impl Encode for Data {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match &self.mode {
+            DataMode::Passive => Leb128::from(1u32).encode(writer)?,
+            DataMode::Active { mem, offset } => {
+                if self.explicit_mem || mem.0 != 0 {
+                    Leb128::from(2u32).encode(writer)?;
+                    mem.encode(writer)?;
+                } else {
+                    Leb128::from(0u32).encode(writer)?;
+                }
+                offset.encode(writer)?;
+            }
+        }
+        encode_len(self.init.len(), writer)?;
+        writer.write_all(&self.init)
+    }
+}
 
 pub struct DataSec {
     size: Size,
+    datas: Vec<Data>,
 }
 
 impl Section for DataSec {
@@ -21,38 +126,100 @@ impl Section for DataSec {
     }
 }
 
+impl Encode for DataSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.datas.encode(writer))
+    }
+}
+
 impl DataSec {
+    pub fn new(datas: Vec<Data>) -> DataSec {
+        DataSec {
+            size: encoded_size(&datas),
+            datas,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
-        let bytes_remaining = usize::try_from(size).unwrap();
-
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(ParseError::WrongNumBytesRead(Asked(1), Received(n)));
-            }
 
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+        Ok(DataSec {
+            size: Size(size),
+            datas: Vec::<Data>::parse(reader)?,
+        })
+    }
+
+    pub fn datas(&self) -> &[Data] {
+        &self.datas
+    }
 
-        Ok(DataSec { size: Size(size) })
+    pub fn get(&self, idx: DataIdx) -> Option<&Data> {
+        self.datas.get(usize::try_from(idx.0).ok()?)
     }
 }
 
 impl Display for DataSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Datas:")?;
+        for (i, data) in self.datas.iter().enumerate() {
+            match &data.mode {
+                DataMode::Passive => write!(f, "* data {}: passive", i)?,
+                DataMode::Active { mem, offset } => {
+                    write!(f, "* data {}: mem {}, offset: {}", i, mem.0, offset)?
+                }
+            }
+            writeln!(f, ", {} bytes", data.init.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instr::Instr;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_data_section() {
+        // (data (i32.const 8) "hi") (data "!") (data (memory 1) (i32.const 0))
+        let bytes = [
+            0x11, 0x03, 0x00, 0x41, 0x08, 0x0b, 0x02, 0x68, 0x69, 0x01, 0x01, 0x21, 0x02, 0x01,
+            0x41, 0x00, 0x0b, 0x00,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = DataSec::parse(&mut reader).expect("The parsed section");
+        let datas = sec.datas();
+        assert_eq!(datas.len(), 3);
+        assert_eq!(datas[0].init(), b"hi");
+        match datas[0].mode() {
+            DataMode::Active { mem, offset } => {
+                assert_eq!(*mem, MemIdx(0));
+                assert_eq!(offset.instrs(), &[Instr::I32Const(8), Instr::End]);
+            }
+            DataMode::Passive => panic!("Expected an active segment"),
+        }
+        assert!(matches!(datas[1].mode(), DataMode::Passive));
+        assert!(matches!(
+            datas[2].mode(),
+            DataMode::Active { mem: MemIdx(1), .. }
+        ));
+        assert!(datas[2].init().is_empty());
+
+        let mut encoded = Vec::new();
+        sec.encode(&mut encoded).expect("The encoded section");
+        assert_eq!(encoded, bytes);
+
+        // Memory 0 given explicitly keeps its longer form
+        let bytes = [0x07, 0x01, 0x02, 0x00, 0x41, 0x00, 0x0b, 0x00];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = DataSec::parse(&mut reader).expect("The parsed section");
+        let mut encoded = Vec::new();
+        sec.encode(&mut encoded).expect("The encoded section");
+        assert_eq!(encoded, bytes);
     }
 }
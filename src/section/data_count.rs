@@ -1,14 +1,17 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::result::Result;
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
+use crate::encode::{Encode, encode_sized};
+use crate::parseable::{ParseError, Parseable};
 use crate::section::Section;
+use crate::section::encoded_size;
 use crate::types::leb128::Leb128;
 use crate::types::primitives::Size;
 
 pub struct DataCountSec {
     size: Size,
+    count: u32,
 }
 
 impl Section for DataCountSec {
@@ -21,38 +24,42 @@ impl Section for DataCountSec {
     }
 }
 
+impl Encode for DataCountSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| Leb128::from(self.count).encode(writer))
+    }
+}
+
 impl DataCountSec {
+    pub fn new(count: u32) -> DataCountSec {
+        DataCountSec {
+            size: encoded_size(&Leb128::from(count)),
+            count,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let bytes_remaining = usize::try_from(size).unwrap();
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(ParseError::WrongNumBytesRead(Asked(1), Received(n)));
-            }
-
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+        Ok(DataCountSec {
+            size: Size(size),
+            count: u32::from(Leb128::<u32>::parse(reader)?),
+        })
+    }
 
-        Ok(DataCountSec { size: Size(size) })
+    /// The number of segments in the data section, announced ahead of the
+    /// code section so that `memory.init` and `data.drop` can be validated.
+    pub fn count(&self) -> u32 {
+        self.count
     }
 }
 
 impl Display for DataCountSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Count: {}", self.count)
     }
 }
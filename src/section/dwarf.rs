@@ -0,0 +1,535 @@
+use std::fmt::Display;
+
+use crate::parseable::{ParseError, Result};
+
+// Content types and forms of the DWARF 5 directory and file name tables
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+// Standard and extended opcodes of the line number program
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_SET_COLUMN: u8 = 5;
+const DW_LNS_NEGATE_STMT: u8 = 6;
+const DW_LNS_SET_BASIC_BLOCK: u8 = 7;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+const DW_LNE_DEFINE_FILE: u8 = 3;
+
+/// A position in the source a module was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    file: String,
+    line: u64,
+    column: u64,
+}
+
+impl Location {
+    pub fn new(file: String, line: u64, column: u64) -> Location {
+        Location { file, line, column }
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The column, or 0 if only the line is known.
+    pub fn column(&self) -> u64 {
+        self.column
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if self.column != 0 {
+            write!(f, ":{}", self.column)?;
+        }
+        Ok(())
+    }
+}
+
+/// A range of code addresses that one source position was compiled to.
+struct Range {
+    start: u64,
+    end: u64,
+    file: usize,
+    line: u64,
+    column: u64,
+}
+
+/// The line tables from the `.debug_line` custom section, which map code
+/// addresses to source positions. Addresses of WebAssembly code are
+/// offsets relative to the start of the code section's contents.
+#[derive(Default)]
+pub struct LineTable {
+    files: Vec<String>,
+    ranges: Vec<Range>,
+}
+
+/// Reads DWARF's fixed size and variable length encodings from a section.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| ParseError::Other("line table ends unexpectedly".to_string()))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// A little-endian unsigned integer of `n` bytes.
+    fn uint(&mut self, n: usize) -> Result<u64> {
+        let bytes = self.bytes(n)?;
+        Ok(bytes.iter().rev().fold(0, |v, b| (v << 8) | u64::from(*b)))
+    }
+
+    fn uleb(&mut self) -> Result<u64> {
+        let mut v = 0u64;
+        for shift in (0..).step_by(7) {
+            let b = self.u8()?;
+            if shift < 64 {
+                v |= u64::from(b & 0x7f) << shift;
+            }
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(v)
+    }
+
+    fn sleb(&mut self) -> Result<i64> {
+        let mut v = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {
+                v |= i64::from(b & 0x7f) << shift;
+            }
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    v |= -1 << shift;
+                }
+                return Ok(v);
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.data[self.pos..]
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| ParseError::Other("unterminated string".to_string()))?;
+        let s = String::from_utf8_lossy(self.bytes(len)?).to_string();
+        self.pos += 1;
+        Ok(s)
+    }
+}
+
+/// Reads the NUL-terminated string at `offset` in a string section.
+fn string_at(section: &[u8], offset: u64) -> Result<String> {
+    let mut reader = Reader {
+        data: section,
+        pos: usize::try_from(offset).unwrap_or(usize::MAX),
+    };
+    if reader.pos >= section.len() {
+        return Err(ParseError::Other("string offset out of range".to_string()));
+    }
+    reader.string()
+}
+
+/// The header fields the line number program needs.
+struct Header {
+    version: u16,
+    offset_size: usize,
+    min_inst_length: u64,
+    line_base: i8,
+    line_range: u8,
+    opcode_base: u8,
+    standard_opcode_lengths: Vec<u8>,
+}
+
+impl LineTable {
+    /// Parses the contents of `.debug_line`, looking up the file names of
+    /// DWARF 5 tables in `.debug_line_str` and `.debug_str`.
+    pub fn parse(debug_line: &[u8], line_str: &[u8], str: &[u8]) -> Result<LineTable> {
+        let mut table = LineTable::default();
+        let mut reader = Reader {
+            data: debug_line,
+            pos: 0,
+        };
+        while reader.pos < debug_line.len() {
+            let (unit_length, offset_size) = match reader.uint(4)? {
+                0xffff_ffff => (reader.uint(8)?, 8),
+                len => (len, 4),
+            };
+            let start = reader.pos;
+            let unit = reader.bytes(usize::try_from(unit_length).unwrap_or(usize::MAX))?;
+            let mut unit = Reader { data: unit, pos: 0 };
+            table
+                .parse_unit(&mut unit, offset_size, line_str, str)
+                .map_err(|err| ParseError::Other(format!("line table at {}: {}", start, err)))?;
+        }
+        table.ranges.sort_by_key(|range| range.start);
+        Ok(table)
+    }
+
+    fn parse_unit(
+        &mut self,
+        reader: &mut Reader<'_>,
+        offset_size: usize,
+        line_str: &[u8],
+        str: &[u8],
+    ) -> Result<()> {
+        let version = reader.uint(2)? as u16;
+        if !(2..=5).contains(&version) {
+            return Err(ParseError::Other(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        if version >= 5 {
+            // The address and segment selector sizes
+            reader.bytes(2)?;
+        }
+        let header_length = reader.uint(offset_size)?;
+        let program = reader
+            .pos
+            .saturating_add(usize::try_from(header_length).unwrap_or(usize::MAX));
+        let min_inst_length = u64::from(reader.u8()?);
+        if version >= 4 {
+            // The maximum number of operations per instruction, which only
+            // matters for VLIW architectures
+            reader.u8()?;
+        }
+        // Whether rows are statements by default, which doesn't matter for
+        // looking up positions
+        reader.u8()?;
+        let mut header = Header {
+            version,
+            offset_size,
+            min_inst_length,
+            line_base: reader.u8()? as i8,
+            line_range: reader.u8()?,
+            opcode_base: reader.u8()?,
+            standard_opcode_lengths: Vec::new(),
+        };
+        if header.line_range == 0 {
+            return Err(ParseError::Other("line range of 0".to_string()));
+        }
+        header.standard_opcode_lengths = reader
+            .bytes(usize::from(header.opcode_base.saturating_sub(1)))?
+            .to_vec();
+
+        let files = if version >= 5 {
+            let dirs = entries(reader, &header, line_str, str)?;
+            entries(reader, &header, line_str, str)?
+                .into_iter()
+                .map(|(name, dir)| join(dirs.get(dir as usize).map(|(dir, _)| dir), name))
+                .collect::<Vec<_>>()
+        } else {
+            let mut dirs = Vec::new();
+            loop {
+                let dir = reader.string()?;
+                if dir.is_empty() {
+                    break;
+                }
+                dirs.push(dir);
+            }
+            // Index 0 is the file the compilation unit was compiled from,
+            // which only DWARF 5 lists
+            let mut files = vec![String::new()];
+            loop {
+                let name = reader.string()?;
+                if name.is_empty() {
+                    break;
+                }
+                let dir = reader.uleb()?;
+                reader.uleb()?;
+                reader.uleb()?;
+                // Directory 0 is the compilation directory, which isn't
+                // listed
+                files.push(join(
+                    dir.checked_sub(1).and_then(|i| dirs.get(i as usize)),
+                    name,
+                ));
+            }
+            files
+        };
+        reader.pos = program;
+        self.run_program(reader, &header, files)
+    }
+
+    /// Runs the line number program, recording which source position each
+    /// range of addresses belongs to.
+    fn run_program(
+        &mut self,
+        reader: &mut Reader<'_>,
+        header: &Header,
+        mut files: Vec<String>,
+    ) -> Result<()> {
+        let first_file = self.files.len();
+        let mut rows: Vec<(u64, u64, u64, u64)> = Vec::new();
+        let mut address = 0u64;
+        let mut file = 1u64;
+        let mut line = 1u64;
+        let mut column = 0u64;
+        let advance = |address: &mut u64, operation_advance: u64| {
+            *address = address.wrapping_add(operation_advance.wrapping_mul(header.min_inst_length));
+        };
+
+        while reader.pos < reader.data.len() {
+            let opcode = reader.u8()?;
+            if opcode >= header.opcode_base {
+                let adjusted = opcode - header.opcode_base;
+                advance(&mut address, u64::from(adjusted / header.line_range));
+                line = line.wrapping_add_signed(
+                    i64::from(header.line_base) + i64::from(adjusted % header.line_range),
+                );
+                rows.push((address, file, line, column));
+                continue;
+            }
+            match opcode {
+                0 => {
+                    let len = reader.uleb()?;
+                    let len = usize::try_from(len).unwrap_or(usize::MAX);
+                    let mut ext = Reader {
+                        data: reader.bytes(len)?,
+                        pos: 0,
+                    };
+                    match ext.u8()? {
+                        DW_LNE_END_SEQUENCE => {
+                            rows.push((address, file, line, column));
+                            self.add_sequence(&rows, first_file);
+                            rows.clear();
+                            address = 0;
+                            file = 1;
+                            line = 1;
+                            column = 0;
+                        }
+                        DW_LNE_SET_ADDRESS => address = ext.uint(len - 1)?,
+                        DW_LNE_DEFINE_FILE if header.version < 5 => files.push(ext.string()?),
+                        _ => {}
+                    }
+                }
+                DW_LNS_COPY => rows.push((address, file, line, column)),
+                DW_LNS_ADVANCE_PC => advance(&mut address, reader.uleb()?),
+                DW_LNS_ADVANCE_LINE => line = line.wrapping_add_signed(reader.sleb()?),
+                DW_LNS_SET_FILE => file = reader.uleb()?,
+                DW_LNS_SET_COLUMN => column = reader.uleb()?,
+                DW_LNS_NEGATE_STMT | DW_LNS_SET_BASIC_BLOCK => {}
+                DW_LNS_CONST_ADD_PC => {
+                    advance(
+                        &mut address,
+                        u64::from((255 - header.opcode_base) / header.line_range),
+                    );
+                }
+                DW_LNS_FIXED_ADVANCE_PC => address = address.wrapping_add(reader.uint(2)?),
+                // Other standard opcodes, whose operands are skipped
+                _ => {
+                    let n = header.standard_opcode_lengths[usize::from(opcode) - 1];
+                    for _ in 0..n {
+                        reader.uleb()?;
+                    }
+                }
+            }
+        }
+        self.files.extend(files);
+        Ok(())
+    }
+
+    /// Records the ranges between the rows of a sequence, each of which
+    /// starts where the row before it ends.
+    fn add_sequence(&mut self, rows: &[(u64, u64, u64, u64)], first_file: usize) {
+        for pair in rows.windows(2) {
+            let (start, file, line, column) = pair[0];
+            let end = pair[1].0;
+            if start < end {
+                self.ranges.push(Range {
+                    start,
+                    end,
+                    file: first_file.saturating_add(file as usize),
+                    line,
+                    column,
+                });
+            }
+        }
+    }
+
+    /// The source position that the code at `address` was compiled from.
+    pub fn location(&self, address: u64) -> Option<Location> {
+        let i = self.ranges.partition_point(|range| range.start <= address);
+        let range = self.ranges[..i]
+            .iter()
+            .rev()
+            .find(|range| address < range.end)?;
+        Some(Location {
+            file: self.files.get(range.file).cloned().unwrap_or_default(),
+            line: range.line,
+            column: range.column,
+        })
+    }
+}
+
+/// A value of an entry in a DWARF 5 directory or file name table.
+enum Attr {
+    Str(String),
+    Num(u64),
+    Other,
+}
+
+/// Reads a DWARF 5 directory or file name table, as (path, directory)
+/// pairs.
+fn entries(
+    reader: &mut Reader<'_>,
+    header: &Header,
+    line_str: &[u8],
+    str: &[u8],
+) -> Result<Vec<(String, u64)>> {
+    let format_count = reader.u8()?;
+    let mut format = Vec::new();
+    for _ in 0..format_count {
+        format.push((reader.uleb()?, reader.uleb()?));
+    }
+    let count = reader.uleb()?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let mut path = String::new();
+        let mut dir = 0;
+        for (content, form) in &format {
+            let attr = match *form {
+                DW_FORM_STRING => Attr::Str(reader.string()?),
+                DW_FORM_LINE_STRP => {
+                    Attr::Str(string_at(line_str, reader.uint(header.offset_size)?)?)
+                }
+                DW_FORM_STRP => Attr::Str(string_at(str, reader.uint(header.offset_size)?)?),
+                DW_FORM_UDATA => Attr::Num(reader.uleb()?),
+                DW_FORM_DATA1 => Attr::Num(reader.uint(1)?),
+                DW_FORM_DATA2 => Attr::Num(reader.uint(2)?),
+                DW_FORM_DATA4 => Attr::Num(reader.uint(4)?),
+                DW_FORM_DATA8 => Attr::Num(reader.uint(8)?),
+                DW_FORM_DATA16 => {
+                    reader.bytes(16)?;
+                    Attr::Other
+                }
+                DW_FORM_BLOCK => {
+                    let len = reader.uleb()?;
+                    reader.bytes(usize::try_from(len).unwrap_or(usize::MAX))?;
+                    Attr::Other
+                }
+                form => return Err(ParseError::Other(format!("unsupported form {:#x}", form))),
+            };
+            match (*content, attr) {
+                (DW_LNCT_PATH, Attr::Str(s)) => path = s,
+                (DW_LNCT_DIRECTORY_INDEX, Attr::Num(n)) => dir = n,
+                _ => {}
+            }
+        }
+        entries.push((path, dir));
+    }
+    Ok(entries)
+}
+
+fn join(dir: Option<&String>, name: String) -> String {
+    match dir {
+        Some(dir) if !name.starts_with('/') && !dir.is_empty() => format!("{}/{}", dir, name),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Standard opcode lengths for opcode base 13
+    const LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+    fn unit(version: u16, header: &[u8], program: &[u8]) -> Vec<u8> {
+        let mut fields = version.to_le_bytes().to_vec();
+        if version >= 5 {
+            fields.extend([4, 0]);
+        }
+        fields.extend((header.len() as u32 + 6 + LENGTHS.len() as u32).to_le_bytes());
+        // min_inst_length, max_ops, default_is_stmt, line_base, line_range
+        // and opcode_base
+        fields.extend([1, 1, 1, -5i8 as u8, 14, 13]);
+        fields.extend(LENGTHS);
+        fields.extend(header);
+        fields.extend(program);
+        let mut unit = (fields.len() as u32).to_le_bytes().to_vec();
+        unit.extend(fields);
+        unit
+    }
+
+    #[test]
+    fn test_line_table() {
+        let header = b"src\0\0a.c\0\x01\0\0\0";
+        let program = [
+            0x00, 0x05, 0x02, 0x10, 0x00, 0x00, 0x00, // set_address 0x10
+            0x05, 0x03, // set_column 3
+            0x03, 0x04, // advance_line 4
+            0x01, // copy
+            103,  // address += 6, line += 1
+            0x02, 0x04, // advance_pc 4
+            0x00, 0x01, 0x01, // end_sequence
+        ];
+        let table = LineTable::parse(&unit(4, header, &program), &[], &[]).unwrap();
+        let location = |address| table.location(address).map(|loc| loc.to_string());
+        assert_eq!(location(0x0f), None);
+        assert_eq!(location(0x10).as_deref(), Some("src/a.c:5:3"));
+        assert_eq!(location(0x15).as_deref(), Some("src/a.c:5:3"));
+        assert_eq!(location(0x16).as_deref(), Some("src/a.c:6:3"));
+        assert_eq!(location(0x19).as_deref(), Some("src/a.c:6:3"));
+        assert_eq!(location(0x1a), None);
+
+        // DWARF 5 lists directories and files by format, with the paths
+        // here in .debug_line_str and inline
+        let header = [
+            0x01, 0x01, 0x1f, 0x01, 0x00, 0x00, 0x00, 0x00, // directory "/work"
+            0x02, 0x01, 0x08, 0x02, 0x0b, 0x01, b'b', b'.', b'c', 0x00, 0x00, // file "b.c"
+        ];
+        let program = [
+            0x00, 0x05, 0x02, 0x20, 0x00, 0x00, 0x00, // set_address 0x20
+            0x04, 0x00, // set_file 0
+            0x01, // copy
+            0x02, 0x02, // advance_pc 2
+            0x00, 0x01, 0x01, // end_sequence
+        ];
+        let table = LineTable::parse(&unit(5, &header, &program), b"/work\0", &[]).unwrap();
+        let location = table.location(0x21).unwrap();
+        assert_eq!((location.file(), location.line()), ("/work/b.c", 1));
+        assert_eq!(location.to_string(), "/work/b.c:1");
+
+        assert!(LineTable::parse(&[0x10, 0x00, 0x00, 0x00, 0x04], &[], &[]).is_err());
+    }
+}
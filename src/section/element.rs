@@ -1,14 +1,232 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::result::Result;
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
-use crate::section::Section;
+use crate::encode::{Encode, encode_sized};
+use crate::instr::expr::Expr;
+use crate::parseable::{ParseError, Parseable};
+use crate::section::{Section, encoded_size};
 use crate::types::leb128::Leb128;
-use crate::types::primitives::Size;
+use crate::types::primitives::{ElemIdx, FuncIdx, Size, TableIdx};
+use crate::types::ref_type::RefType;
+
+// Bits of the flags that start an element segment
+/// Passive or declarative rather than active.
+const NOT_ACTIVE: u32 = 0x01;
+/// Active with an explicit table index, or declarative if not active.
+const EXPLICIT_TABLE: u32 = 0x02;
+/// The elements are given as expressions rather than function indices.
+const EXPRS: u32 = 0x04;
+
+pub enum ElemMode {
+    Passive,
+    /// Copied into `table` at `offset` during instantiation.
+    Active {
+        table: TableIdx,
+        offset: Expr,
+    },
+    /// Only declares functions for `ref.func`; never copied anywhere.
+    Declarative,
+}
+
+pub enum ElemInit {
+    Funcs(Vec<FuncIdx>),
+    Exprs(Vec<Expr>),
+}
+
+pub struct Elem {
+    ty: RefType,
+    init: ElemInit,
+    mode: ElemMode,
+    // Whether an active segment spelled out its table index and element
+    // type even though they could have been left out.
+    explicit_table: bool,
+}
+
+impl Elem {
+    /// A segment of elements of type `ty`. Active segments for table 0
+    /// leave out the table index and element type where they can.
+    pub fn new(ty: RefType, init: ElemInit, mode: ElemMode) -> Elem {
+        Elem {
+            ty,
+            init,
+            mode,
+            explicit_table: false,
+        }
+    }
+
+    /// The same segment, spelling out its table index and element type.
+    pub(crate) fn with_explicit_table(self) -> Elem {
+        Elem {
+            explicit_table: true,
+            ..self
+        }
+    }
+
+    pub fn ref_type(&self) -> RefType {
+        self.ty
+    }
+
+    pub fn init(&self) -> &ElemInit {
+        &self.init
+    }
+
+    pub fn mode(&self) -> &ElemMode {
+        &self.mode
+    }
+
+    /// The number of elements in the segment.
+    pub fn len(&self) -> usize {
+        match &self.init {
+            ElemInit::Funcs(funcs) => funcs.len(),
+            ElemInit::Exprs(exprs) => exprs.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether an active segment spells out its table and element type.
+    pub(crate) fn explicit_table(&self) -> bool {
+        self.explicit_table
+    }
+}
+
+impl Parseable for Elem {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
+    where
+        Self: Sized,
+    {
+        let flags = u32::from(Leb128::<u32>::parse(reader)?);
+        if flags > (NOT_ACTIVE | EXPLICIT_TABLE | EXPRS) {
+            return Err(ParseError::Other(format!(
+                "malformed elements segment kind: {}",
+                flags
+            )));
+        }
+
+        let mode = if flags & NOT_ACTIVE == 0 {
+            let table = if flags & EXPLICIT_TABLE != 0 {
+                TableIdx::parse(reader)?
+            } else {
+                TableIdx(0)
+            };
+            ElemMode::Active {
+                table,
+                offset: Expr::parse(reader)?,
+            }
+        } else if flags & EXPLICIT_TABLE != 0 {
+            ElemMode::Declarative
+        } else {
+            ElemMode::Passive
+        };
+
+        // Segments for table 0 in the original MVP encoding leave out the
+        // element type.
+        let has_type = flags & (NOT_ACTIVE | EXPLICIT_TABLE) != 0;
+        let (ty, init) = if flags & EXPRS == 0 {
+            if has_type {
+                let kind = u8::parse(reader)?;
+                if kind != 0x00 {
+                    return Err(ParseError::Other(format!(
+                        "malformed element kind: {:#04x}",
+                        kind
+                    )));
+                }
+            }
+            (
+                RefType::Func,
+                ElemInit::Funcs(Vec::<FuncIdx>::parse(reader)?),
+            )
+        } else {
+            let ty = if has_type {
+                RefType::parse(reader)?
+            } else {
+                RefType::Func
+            };
+            (ty, ElemInit::Exprs(Vec::<Expr>::parse(reader)?))
+        };
+
+        Ok(Elem {
+            ty,
+            init,
+            mode,
+            explicit_table: flags & EXPLICIT_TABLE != 0,
+        })
+    }
+}
+
+impl Encode for Elem {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        let mut flags = match &self.init {
+            ElemInit::Funcs(_) => 0,
+            ElemInit::Exprs(_) => EXPRS,
+        };
+        match &self.mode {
+            ElemMode::Passive => flags |= NOT_ACTIVE,
+            ElemMode::Declarative => flags |= NOT_ACTIVE | EXPLICIT_TABLE,
+            ElemMode::Active { table, .. } => {
+                if self.explicit_table || table.0 != 0 || self.ty != RefType::Func {
+                    flags |= EXPLICIT_TABLE;
+                }
+            }
+        }
+        Leb128::from(flags).encode(writer)?;
+
+        if let ElemMode::Active { table, offset } = &self.mode {
+            if flags & EXPLICIT_TABLE != 0 {
+                table.encode(writer)?;
+            }
+            offset.encode(writer)?;
+        }
+        let has_type = flags & (NOT_ACTIVE | EXPLICIT_TABLE) != 0;
+        match &self.init {
+            ElemInit::Funcs(funcs) => {
+                if has_type {
+                    writer.write_all(&[0x00])?;
+                }
+                funcs.encode(writer)
+            }
+            ElemInit::Exprs(exprs) => {
+                if has_type {
+                    self.ty.encode(writer)?;
+                }
+                exprs.encode(writer)
+            }
+        }
+    }
+}
+
+impl Display for Elem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.mode {
+            ElemMode::Passive => write!(f, "passive")?,
+            ElemMode::Active { table, offset } => {
+                write!(f, "table {}, offset: {}", table.0, offset)?
+            }
+            ElemMode::Declarative => write!(f, "declare")?,
+        }
+        write!(f, ", {}:", self.ty)?;
+        match &self.init {
+            ElemInit::Funcs(funcs) => {
+                for func in funcs {
+                    write!(f, " {}", func.0)?;
+                }
+            }
+            ElemInit::Exprs(exprs) => {
+                for expr in exprs {
+                    write!(f, " ({})", expr)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
 pub struct ElemSec {
     size: Size,
+    elems: Vec<Elem>,
 }
 
 impl Section for ElemSec {
@@ -21,38 +239,101 @@ impl Section for ElemSec {
     }
 }
 
+impl Encode for ElemSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.elems.encode(writer))
+    }
+}
+
 impl ElemSec {
+    pub fn new(elems: Vec<Elem>) -> ElemSec {
+        ElemSec {
+            size: encoded_size(&elems),
+            elems,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let bytes_remaining = usize::try_from(size).unwrap();
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(ParseError::WrongNumBytesRead(Asked(1), Received(n)));
-            }
+        Ok(ElemSec {
+            size: Size(size),
+            elems: Vec::<Elem>::parse(reader)?,
+        })
+    }
 
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+    pub fn elems(&self) -> &[Elem] {
+        &self.elems
+    }
 
-        Ok(ElemSec { size: Size(size) })
+    pub fn get(&self, idx: ElemIdx) -> Option<&Elem> {
+        self.elems.get(usize::try_from(idx.0).ok()?)
     }
 }
 
 impl Display for ElemSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Elems:")?;
+        for (i, elem) in self.elems.iter().enumerate() {
+            writeln!(f, "* elem {}: {}", i, elem)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instr::Instr;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_elem_section() {
+        // (elem (i32.const 1) func 0 2)
+        // (elem declare func 3)
+        // (elem externref (ref.null extern))
+        let bytes = [
+            0x12, 0x03, 0x00, 0x41, 0x01, 0x0b, 0x02, 0x00, 0x02, 0x03, 0x00, 0x01, 0x03, 0x05,
+            0x6f, 0x01, 0xd0, 0x6f, 0x0b,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = ElemSec::parse(&mut reader).expect("The parsed section");
+        let elems = sec.elems();
+        assert_eq!(elems.len(), 3);
+
+        match elems[0].mode() {
+            ElemMode::Active { table, offset } => {
+                assert_eq!(*table, TableIdx(0));
+                assert_eq!(offset.instrs(), &[Instr::I32Const(1), Instr::End]);
+            }
+            _ => panic!("Expected an active segment"),
+        }
+        assert!(
+            matches!(elems[0].init(), ElemInit::Funcs(funcs) if funcs == &[FuncIdx(0), FuncIdx(2)])
+        );
+        assert!(matches!(elems[1].mode(), ElemMode::Declarative));
+        assert!(matches!(elems[2].mode(), ElemMode::Passive));
+        assert_eq!(elems[2].ref_type(), RefType::Extern);
+        assert_eq!(elems[2].len(), 1);
+
+        let mut encoded = Vec::new();
+        sec.encode(&mut encoded).expect("The encoded section");
+        assert_eq!(encoded, bytes);
+
+        // Table 0 given explicitly keeps its longer form
+        let bytes = [0x09, 0x01, 0x02, 0x00, 0x41, 0x00, 0x0b, 0x00, 0x01, 0x05];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = ElemSec::parse(&mut reader).expect("The parsed section");
+        let mut encoded = Vec::new();
+        sec.encode(&mut encoded).expect("The encoded section");
+        assert_eq!(encoded, bytes);
+
+        let bytes = [0x02, 0x01, 0x08];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(ElemSec::parse(&mut reader).is_err());
     }
 }
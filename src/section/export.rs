@@ -1,14 +1,59 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::result::Result;
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
+use crate::encode::{Encode, encode_sized};
+use crate::parseable::{ParseError, Parseable};
 use crate::section::Section;
+use crate::section::encoded_size;
+use crate::types::export_desc::ExportDesc;
 use crate::types::leb128::Leb128;
 use crate::types::primitives::Size;
 
+pub struct Export {
+    name: String,
+    d: ExportDesc,
+}
+
+impl Parseable for Export {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
+    where
+        Self: Sized,
+    {
+        let name = String::parse(reader)?;
+        let d = ExportDesc::parse(reader)?;
+
+        Ok(Export { name, d })
+    }
+}
+
+impl Encode for Export {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.name.encode(writer)?;
+        self.d.encode(writer)
+    }
+}
+
+impl Export {
+    pub fn new(name: &str, d: ExportDesc) -> Export {
+        Export {
+            name: name.to_string(),
+            d,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn desc(&self) -> ExportDesc {
+        self.d
+    }
+}
+
 pub struct ExportSec {
     size: Size,
+    exs: Vec<Export>,
 }
 
 impl Section for ExportSec {
@@ -21,38 +66,67 @@ impl Section for ExportSec {
     }
 }
 
+impl Encode for ExportSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.exs.encode(writer))
+    }
+}
+
 impl ExportSec {
+    pub fn new(exs: Vec<Export>) -> ExportSec {
+        ExportSec {
+            size: encoded_size(&exs),
+            exs,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let bytes_remaining = usize::try_from(size).unwrap();
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(ParseError::WrongNumBytesRead(Asked(1), Received(n)));
-            }
-
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+        Ok(ExportSec {
+            size: Size(size),
+            exs: Vec::<Export>::parse(reader)?,
+        })
+    }
 
-        Ok(ExportSec { size: Size(size) })
+    pub fn exports(&self) -> &[Export] {
+        &self.exs
     }
 }
 
 impl Display for ExportSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Exports:")?;
+        for ex in &self.exs {
+            writeln!(f, "* ex: {}: {}", ex.name, ex.d)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::primitives::{FuncIdx, TagIdx};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_export_sec() {
+        let bytes: [u8; 12] = [
+            0x0b, 0x02, 0x03, 0x66, 0x69, 0x62, 0x00, 0x03, 0x01, 0x65, 0x04, 0x00,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let exportsec = ExportSec::parse(&mut reader).expect("The parsed section");
+        let exports = exportsec.exports();
+        assert_eq!(exports.len(), 2);
+        assert_eq!(exports[0].name(), "fib");
+        assert_eq!(exports[0].desc(), ExportDesc::Func(FuncIdx(3)));
+        assert_eq!(exports[1].name(), "e");
+        assert_eq!(exports[1].desc(), ExportDesc::Tag(TagIdx(0)));
     }
 }
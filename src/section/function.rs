@@ -1,14 +1,17 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::result::Result;
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
+use crate::encode::{Encode, encode_sized};
+use crate::parseable::{ParseError, Parseable};
 use crate::section::Section;
+use crate::section::encoded_size;
 use crate::types::leb128::Leb128;
-use crate::types::primitives::Size;
+use crate::types::primitives::{Size, TypeIdx};
 
 pub struct FunctionSec {
     size: Size,
+    funcs: Vec<TypeIdx>,
 }
 
 impl Section for FunctionSec {
@@ -21,38 +24,46 @@ impl Section for FunctionSec {
     }
 }
 
+impl Encode for FunctionSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.funcs.encode(writer))
+    }
+}
+
 impl FunctionSec {
+    pub fn new(funcs: Vec<TypeIdx>) -> FunctionSec {
+        FunctionSec {
+            size: encoded_size(&funcs),
+            funcs,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let bytes_remaining = usize::try_from(size).unwrap();
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(ParseError::WrongNumBytesRead(Asked(1), Received(n)));
-            }
-
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+        Ok(FunctionSec {
+            size: Size(size),
+            funcs: Vec::<TypeIdx>::parse(reader)?,
+        })
+    }
 
-        Ok(FunctionSec { size: Size(size) })
+    /// The type index of each function defined in the module, in the same
+    /// order as the bodies in the code section.
+    pub fn funcs(&self) -> &[TypeIdx] {
+        &self.funcs
     }
 }
 
 impl Display for FunctionSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Funcs:")?;
+        for (i, ty) in self.funcs.iter().enumerate() {
+            writeln!(f, "* func {}: type {}", i, ty.0)?;
+        }
+        Ok(())
     }
 }
@@ -1,14 +1,55 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
-use std::result::Result;
+use std::io::{BufReader, Read, Write};
 
-use crate::parseable::{Asked, ParseError, Parseable, Received};
+use crate::encode::{Encode, encode_sized};
+use crate::instr::expr::Expr;
+use crate::parseable::{Parseable, Result};
 use crate::section::Section;
+use crate::section::encoded_size;
+use crate::types::global_type::GlobalType;
 use crate::types::leb128::Leb128;
-use crate::types::primitives::Size;
+use crate::types::primitives::{GlobalIdx, Size};
+
+pub struct Global {
+    ty: GlobalType,
+    init: Expr,
+}
+
+impl Global {
+    pub fn new(ty: GlobalType, init: Expr) -> Global {
+        Global { ty, init }
+    }
+
+    pub fn global_type(&self) -> &GlobalType {
+        &self.ty
+    }
+
+    pub fn init(&self) -> &Expr {
+        &self.init
+    }
+}
+
+impl Parseable for Global {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let ty = GlobalType::parse(reader)?;
+        let init = Expr::parse(reader)?;
+        Ok(Global { ty, init })
+    }
+}
+
+impl Encode for Global {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.ty.encode(writer)?;
+        self.init.encode(writer)
+    }
+}
 
 pub struct GlobalSec {
     size: Size,
+    globals: Vec<Global>,
 }
 
 impl Section for GlobalSec {
@@ -21,38 +62,68 @@ impl Section for GlobalSec {
     }
 }
 
+impl Encode for GlobalSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.globals.encode(writer))
+    }
+}
+
+impl Display for GlobalSec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Globals:")?;
+        for (i, global) in self.globals.iter().enumerate() {
+            writeln!(f, "* global {}: {}, init: {}", i, global.ty, global.init)?;
+        }
+        Ok(())
+    }
+}
+
 impl GlobalSec {
-    pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
+    pub fn new(globals: Vec<Global>) -> GlobalSec {
+        GlobalSec {
+            size: encoded_size(&globals),
+            globals,
+        }
+    }
+
+    pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let bytes_remaining = usize::try_from(size).unwrap();
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(ParseError::WrongNumBytesRead(Asked(1), Received(n)));
-            }
-
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+        Ok(GlobalSec {
+            size: Size(size),
+            globals: Vec::<Global>::parse(reader)?,
+        })
+    }
 
-        Ok(GlobalSec { size: Size(size) })
+    pub fn globals(&self) -> &[Global] {
+        &self.globals
+    }
+
+    /// Looks up a global by its index among the globals defined in this
+    /// section, not counting imported globals.
+    pub fn get(&self, idx: GlobalIdx) -> Option<&Global> {
+        self.globals.get(usize::try_from(idx.0).ok()?)
     }
 }
 
-impl Display for GlobalSec {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instr::Instr;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_global_section() {
+        // (global (mut i32) (i32.const 42))
+        let bytes = [0x06, 0x01, 0x7f, 0x01, 0x41, 0x2a, 0x0b];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = GlobalSec::parse(&mut reader).expect("The parsed section");
+        let global = sec.get(GlobalIdx(0)).expect("A global");
+        assert!(global.global_type().is_mutable());
+        assert_eq!(global.init().instrs(), &[Instr::I32Const(42), Instr::End]);
     }
 }
@@ -1,18 +1,18 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::result::Result;
 
+use crate::encode::{Encode, encode_sized};
 use crate::parseable::{ParseError, Parseable};
 use crate::section::Section;
+use crate::section::encoded_size;
 use crate::types::import_desc::ImportDesc;
 use crate::types::leb128::Leb128;
 use crate::types::primitives::Size;
 
 pub struct Import {
-    // module name
+    module: String,
     name: String,
-    // name
-    nm: String,
     d: ImportDesc,
 }
 
@@ -21,15 +21,41 @@ impl Parseable for Import {
     where
         Self: Sized,
     {
+        let module = String::parse(reader)?;
         let name = String::parse(reader)?;
-        let nm = String::parse(reader)?;
         let d = ImportDesc::parse(reader)?;
 
-        Ok(Import {
-            name: name,
-            nm: nm,
-            d: d,
-        })
+        Ok(Import { module, name, d })
+    }
+}
+
+impl Encode for Import {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.module.encode(writer)?;
+        self.name.encode(writer)?;
+        self.d.encode(writer)
+    }
+}
+
+impl Import {
+    pub fn new(module: &str, name: &str, d: ImportDesc) -> Import {
+        Import {
+            module: module.to_string(),
+            name: name.to_string(),
+            d,
+        }
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn desc(&self) -> &ImportDesc {
+        &self.d
     }
 }
 
@@ -48,7 +74,20 @@ impl Section for ImportSec {
     }
 }
 
+impl Encode for ImportSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.ims.encode(writer))
+    }
+}
+
 impl ImportSec {
+    pub fn new(ims: Vec<Import>) -> ImportSec {
+        ImportSec {
+            size: encoded_size(&ims),
+            ims,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self, ParseError>
     where
         Self: Sized,
@@ -60,6 +99,10 @@ impl ImportSec {
             ims: Vec::<Import>::parse(reader)?,
         })
     }
+
+    pub fn imports(&self) -> &[Import] {
+        &self.ims
+    }
 }
 
 impl Display for ImportSec {
@@ -67,7 +110,7 @@ impl Display for ImportSec {
         writeln!(f, "Size: {}", self.size)?;
         writeln!(f, "Imports:")?;
         for im in &self.ims {
-            writeln!(f, "* im: {}/{}", im.name, im.nm)?;
+            writeln!(f, "* im: {}/{}: {}", im.module, im.name, im.d)?;
         }
 
         Ok(())
@@ -1,11 +1,13 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
-use crate::parseable::{Asked, Parseable, Received, Result};
+use crate::encode::{Encode, encode_sized};
+use crate::parseable::{Parseable, Result};
 use crate::section::Section;
+use crate::section::encoded_size;
 use crate::types::leb128::Leb128;
 use crate::types::mem_type::MemType;
-use crate::types::primitives::Size;
+use crate::types::primitives::{MemIdx, Size};
 
 pub struct MemSec {
     size: Size,
@@ -22,7 +24,20 @@ impl Section for MemSec {
     }
 }
 
+impl Encode for MemSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.mems.encode(writer))
+    }
+}
+
 impl MemSec {
+    pub fn new(mems: Vec<MemType>) -> MemSec {
+        MemSec {
+            size: encoded_size(&mems),
+            mems,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
         Self: Sized,
@@ -35,15 +50,59 @@ impl MemSec {
             mems,
         })
     }
+
+    /// The memories defined by this module, in index order. With the
+    /// multi-memory proposal there may be more than one.
+    pub fn mems(&self) -> &[MemType] {
+        &self.mems
+    }
+
+    /// Looks up a memory by its index among the memories defined in this
+    /// section. Imported memories come first in the memory index space, so
+    /// callers must subtract the number of memory imports beforehand.
+    pub fn get(&self, idx: MemIdx) -> Option<&MemType> {
+        self.mems.get(usize::try_from(idx.0).ok()?)
+    }
 }
 
 impl Display for MemSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Size: {}", self.size)?;
         writeln!(f, "Mems:")?;
-        for mem in &self.mems {
-            writeln!(f, "* {}", mem)?;
+        for (i, mem) in self.mems.iter().enumerate() {
+            writeln!(f, "* mem {}: {}", i, mem)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::limits::Limits;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_multiple_memories() {
+        // Three memories: i32 min 1, i64 min 2 max 3, shared i32 min 0 max 1
+        let bytes: [u8; 10] = [0x09, 0x03, 0x00, 0x01, 0x05, 0x02, 0x03, 0x03, 0x00, 0x01];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let memsec = MemSec::parse(&mut reader).expect("The parsed section");
+        assert_eq!(memsec.mems().len(), 3);
+
+        let mem = memsec.get(MemIdx(0)).expect("Memory 0");
+        assert_eq!(mem.limits(), &Limits::new(1, None));
+        assert!(!mem.is_64());
+
+        let mem = memsec.get(MemIdx(1)).expect("Memory 1");
+        assert!(mem.is_64());
+        assert_eq!(mem.limits().min(), 2);
+        assert_eq!(mem.limits().max(), Some(3));
+
+        let mem = memsec.get(MemIdx(2)).expect("Memory 2");
+        assert!(mem.limits().is_shared());
+        assert_eq!(mem.limits().max(), Some(1));
+
+        assert!(memsec.get(MemIdx(3)).is_none());
+    }
+}
@@ -2,16 +2,20 @@ pub mod code;
 pub mod custom;
 pub mod data;
 pub mod data_count;
+pub mod dwarf;
 pub mod element;
 pub mod export;
 pub mod function;
 pub mod global;
 pub mod import;
 pub mod memory;
+pub mod name;
 pub mod start;
 pub mod table;
+pub mod tag;
 pub mod r#type;
 
+use crate::encode::Encode;
 use crate::types::primitives::{Size, TypeIdx};
 
 use std::fmt;
@@ -29,10 +33,27 @@ pub const ELEMENT_SECTION_ID: TypeIdx = TypeIdx(9);
 pub const CODE_SECTION_ID: TypeIdx = TypeIdx(10);
 pub const DATA_SECTION_ID: TypeIdx = TypeIdx(11);
 pub const DATA_COUNT_SECTION_ID: TypeIdx = TypeIdx(12);
+pub const TAG_SECTION_ID: TypeIdx = TypeIdx(13);
 
 pub trait Section: fmt::Display {
     fn section_type(&self) -> &str;
     fn size(&self) -> Size;
 }
 
+/// The size of a section's contents, for sections that are built rather
+/// than parsed.
+pub(crate) fn encoded_size(contents: &dyn Encode) -> Size {
+    let mut bytes = Vec::new();
+    // Writing to memory can only fail for vectors that are too long to be
+    // encoded at all, and those are caught when the module is encoded.
+    let _ = contents.encode(&mut bytes);
+    Size(bytes.len() as u32)
+}
+
 pub struct SectionParseError(String);
+
+impl fmt::Display for SectionParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::io::{BufReader, Cursor, Read};
+
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::leb128::Leb128;
+use crate::types::primitives::{FuncIdx, LocalIdx};
+
+// Subsection ids of the name section, including the ones added by the
+// extended name section proposal.
+const MODULE: u8 = 0;
+const FUNCS: u8 = 1;
+const LOCALS: u8 = 2;
+const LABELS: u8 = 3;
+const TYPES: u8 = 4;
+const TABLES: u8 = 5;
+const MEMS: u8 = 6;
+const GLOBALS: u8 = 7;
+const ELEMS: u8 = 8;
+const DATAS: u8 = 9;
+const FIELDS: u8 = 10;
+const TAGS: u8 = 11;
+
+/// The index spaces that can be named in the name section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NameSpace {
+    Func,
+    Type,
+    Table,
+    Mem,
+    Global,
+    Elem,
+    Data,
+    Tag,
+}
+
+/// The debug names from the custom section called "name". Names are only
+/// hints: they need not be unique, and any of them may be missing.
+#[derive(Debug, Default)]
+pub struct Names {
+    module: Option<String>,
+    names: HashMap<(NameSpace, u32), String>,
+    locals: HashMap<(u32, u32), String>,
+    // Keyed by function and the label's position among all the blocks in
+    // the function's body.
+    labels: HashMap<(u32, u32), String>,
+    fields: HashMap<(u32, u32), String>,
+}
+
+fn parse_name_map(reader: &mut BufReader<dyn Read>, mut f: impl FnMut(u32, String)) -> Result<()> {
+    let num = u32::from(Leb128::<u32>::parse(reader)?);
+    for _ in 0..num {
+        let idx = u32::from(Leb128::<u32>::parse(reader)?);
+        f(idx, String::parse(reader)?);
+    }
+    Ok(())
+}
+
+fn parse_indirect_name_map(
+    reader: &mut BufReader<dyn Read>,
+    map: &mut HashMap<(u32, u32), String>,
+) -> Result<()> {
+    let num = u32::from(Leb128::<u32>::parse(reader)?);
+    for _ in 0..num {
+        let outer = u32::from(Leb128::<u32>::parse(reader)?);
+        parse_name_map(reader, |inner, name| {
+            map.insert((outer, inner), name);
+        })?;
+    }
+    Ok(())
+}
+
+impl Names {
+    /// Parses the contents of a name section. Unknown subsections are
+    /// skipped.
+    pub fn parse(data: &[u8]) -> Result<Names> {
+        let len = data.len() as u64;
+        let mut reader = BufReader::new(Cursor::new(data.to_vec()));
+        let mut names = Names::default();
+        while reader.get_ref().position() < len || !reader.buffer().is_empty() {
+            let id = u8::parse(&mut reader)?;
+            let size = u32::from(Leb128::<u32>::parse(&mut reader)?);
+            let mut contents = Vec::new();
+            (&mut reader)
+                .take(u64::from(size))
+                .read_to_end(&mut contents)?;
+            if contents.len() != size as usize {
+                return Err(ParseError::Other(
+                    "name subsection extends past the end".to_string(),
+                ));
+            }
+            let mut sub = BufReader::new(Cursor::new(contents));
+            let space = match id {
+                MODULE => {
+                    names.module = Some(String::parse(&mut sub)?);
+                    continue;
+                }
+                LOCALS => {
+                    parse_indirect_name_map(&mut sub, &mut names.locals)?;
+                    continue;
+                }
+                LABELS => {
+                    parse_indirect_name_map(&mut sub, &mut names.labels)?;
+                    continue;
+                }
+                FIELDS => {
+                    parse_indirect_name_map(&mut sub, &mut names.fields)?;
+                    continue;
+                }
+                FUNCS => NameSpace::Func,
+                TYPES => NameSpace::Type,
+                TABLES => NameSpace::Table,
+                MEMS => NameSpace::Mem,
+                GLOBALS => NameSpace::Global,
+                ELEMS => NameSpace::Elem,
+                DATAS => NameSpace::Data,
+                TAGS => NameSpace::Tag,
+                _ => continue,
+            };
+            parse_name_map(&mut sub, |idx, name| {
+                names.names.insert((space, idx), name);
+            })?;
+        }
+        Ok(names)
+    }
+
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    pub fn get(&self, space: NameSpace, idx: u32) -> Option<&str> {
+        self.names.get(&(space, idx)).map(String::as_str)
+    }
+
+    pub fn func(&self, idx: FuncIdx) -> Option<&str> {
+        self.get(NameSpace::Func, idx.0)
+    }
+
+    pub fn local(&self, func: FuncIdx, idx: LocalIdx) -> Option<&str> {
+        self.locals.get(&(func.0, idx.0)).map(String::as_str)
+    }
+
+    /// The name of the `n`th block, loop, if or try in the body of `func`.
+    pub fn label(&self, func: FuncIdx, n: u32) -> Option<&str> {
+        self.labels.get(&(func.0, n)).map(String::as_str)
+    }
+
+    pub fn field(&self, ty: u32, idx: u32) -> Option<&str> {
+        self.fields.get(&(ty, idx)).map(String::as_str)
+    }
+
+    /// Every name in the index spaces, as ((space, index), name), in no
+    /// particular order.
+    pub fn all(&self) -> impl Iterator<Item = ((NameSpace, u32), &str)> {
+        self.names.iter().map(|(key, name)| (*key, name.as_str()))
+    }
+
+    /// Every local name, as ((function, local), name), in no particular
+    /// order.
+    pub fn all_locals(&self) -> impl Iterator<Item = ((u32, u32), &str)> {
+        self.locals.iter().map(|(key, name)| (*key, name.as_str()))
+    }
+
+    /// Every label name, as ((function, block), name), in no particular
+    /// order.
+    pub fn all_labels(&self) -> impl Iterator<Item = ((u32, u32), &str)> {
+        self.labels.iter().map(|(key, name)| (*key, name.as_str()))
+    }
+
+    /// Every field name, as ((type, field), name), in no particular order.
+    pub fn all_fields(&self) -> impl Iterator<Item = ((u32, u32), &str)> {
+        self.fields.iter().map(|(key, name)| (*key, name.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        let data = [
+            // module "m"
+            0x00, 0x02, 0x01, 0x6d, //
+            // func 1 "f"
+            0x01, 0x04, 0x01, 0x01, 0x01, 0x66, //
+            // func 1, local 0 "x"
+            0x02, 0x06, 0x01, 0x01, 0x01, 0x00, 0x01, 0x78, //
+            // an unknown subsection
+            0x20, 0x01, 0xff, //
+            // global 0 "g"
+            0x07, 0x04, 0x01, 0x00, 0x01, 0x67,
+        ];
+        let names = Names::parse(&data).expect("The parsed names");
+        assert_eq!(names.module(), Some("m"));
+        assert_eq!(names.func(FuncIdx(1)), Some("f"));
+        assert_eq!(names.func(FuncIdx(0)), None);
+        assert_eq!(names.local(FuncIdx(1), LocalIdx(0)), Some("x"));
+        assert_eq!(names.get(NameSpace::Global, 0), Some("g"));
+
+        assert!(Names::parse(&[0x01, 0x05, 0x01]).is_err());
+    }
+}
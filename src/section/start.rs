@@ -1,13 +1,16 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
-use crate::parseable::{Asked, Parseable, Received, Result};
+use crate::encode::{Encode, encode_sized};
+use crate::parseable::{Parseable, Result};
 use crate::section::Section;
+use crate::section::encoded_size;
 use crate::types::leb128::Leb128;
-use crate::types::primitives::Size;
+use crate::types::primitives::{FuncIdx, Size};
 
 pub struct StartSec {
     size: Size,
+    func: FuncIdx,
 }
 
 impl Section for StartSec {
@@ -20,41 +23,41 @@ impl Section for StartSec {
     }
 }
 
+impl Encode for StartSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.func.encode(writer))
+    }
+}
+
 impl Display for StartSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Start: func {}", self.func.0)
     }
 }
 
 impl StartSec {
+    pub fn new(func: FuncIdx) -> StartSec {
+        StartSec {
+            size: encoded_size(&func),
+            func,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let bytes_remaining = usize::try_from(size).unwrap();
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(crate::parseable::ParseError::WrongNumBytesRead(
-                    Asked(1),
-                    Received(n),
-                ));
-            }
-
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+        Ok(StartSec {
+            size: Size(size),
+            func: FuncIdx::parse(reader)?,
+        })
+    }
 
-        Ok(StartSec { size: Size(size) })
+    /// The function called when the module is instantiated.
+    pub fn func(&self) -> FuncIdx {
+        self.func
     }
 }
@@ -1,13 +1,77 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufRead, BufReader, Read, Write};
 
-use crate::parseable::{Asked, Parseable, Received, Result};
-use crate::section::Section;
+use crate::encode::{Encode, encode_sized};
+use crate::instr::expr::Expr;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::section::{Section, encoded_size};
 use crate::types::leb128::Leb128;
-use crate::types::primitives::Size;
+use crate::types::primitives::{Size, TableIdx};
+use crate::types::table_type::TableType;
+
+pub struct Table {
+    ty: TableType,
+    init: Option<Expr>,
+}
+
+impl Table {
+    pub fn new(ty: TableType, init: Option<Expr>) -> Table {
+        Table { ty, init }
+    }
+
+    pub fn table_type(&self) -> &TableType {
+        &self.ty
+    }
+
+    /// The expression every element is initialized with. Without one, the
+    /// elements start out null.
+    pub fn init(&self) -> Option<&Expr> {
+        self.init.as_ref()
+    }
+}
+
+impl Parseable for Table {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let first = match reader.fill_buf()?.first() {
+            Some(byte) => *byte,
+            None => return Err(ParseError::Other("unexpected end".to_string())),
+        };
+        // Tables with an initializer expression start with 0x40 0x00
+        if first != 0x40 {
+            return Ok(Table {
+                ty: TableType::parse(reader)?,
+                init: None,
+            });
+        }
+        reader.consume(1);
+        if u8::parse(reader)? != 0x00 {
+            return Err(ParseError::Other("malformed table".to_string()));
+        }
+        let ty = TableType::parse(reader)?;
+        let init = Some(Expr::parse(reader)?);
+        Ok(Table { ty, init })
+    }
+}
+
+impl Encode for Table {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match &self.init {
+            Some(init) => {
+                writer.write_all(&[0x40, 0x00])?;
+                self.ty.encode(writer)?;
+                init.encode(writer)
+            }
+            None => self.ty.encode(writer),
+        }
+    }
+}
 
 pub struct TableSec {
     size: Size,
+    tables: Vec<Table>,
 }
 
 impl Section for TableSec {
@@ -20,41 +84,85 @@ impl Section for TableSec {
     }
 }
 
+impl Encode for TableSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.tables.encode(writer))
+    }
+}
+
 impl Display for TableSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "UNIMPLEMENTED")?;
-        writeln!(f, "Size: {}", self.size)
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Tables:")?;
+        for (i, table) in self.tables.iter().enumerate() {
+            write!(f, "* table {}: {}", i, table.ty)?;
+            if let Some(init) = &table.init {
+                write!(f, ", init: {}", init)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
 impl TableSec {
+    pub fn new(tables: Vec<Table>) -> TableSec {
+        TableSec {
+            size: encoded_size(&tables),
+            tables,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
         Self: Sized,
     {
         let size = u32::from(Leb128::<u32>::parse(reader)?);
 
-        // TODO: this is temporary code and should be replaced by
-        // actual parsing. We are just consuming bytes for the sake
-        // of testing!
-        let bytes_remaining = usize::try_from(size).unwrap();
-        let mut bytes_read = 0;
-        let mut buf: [u8; 1] = [0; 1];
-        loop {
-            let n = reader.read(&mut buf)?;
-            if n != 1 {
-                return Err(crate::parseable::ParseError::WrongNumBytesRead(
-                    Asked(1),
-                    Received(n),
-                ));
-            }
+        Ok(TableSec {
+            size: Size(size),
+            tables: Vec::<Table>::parse(reader)?,
+        })
+    }
 
-            bytes_read += n;
-            if bytes_read == bytes_remaining {
-                break;
-            }
-        }
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    /// Looks up a table by its index among the tables defined in this
+    /// section, not counting imported tables.
+    pub fn get(&self, idx: TableIdx) -> Option<&Table> {
+        self.tables.get(usize::try_from(idx.0).ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instr::Instr;
+    use crate::types::ref_type::RefType;
+    use std::io::Cursor;
 
-        Ok(TableSec { size: Size(size) })
+    #[test]
+    fn test_table_section() {
+        // (table 1 funcref) (table 0 2 (ref null func) (ref.null func))
+        let bytes = [
+            0x0d, 0x02, 0x70, 0x00, 0x01, 0x40, 0x00, 0x70, 0x01, 0x00, 0x02, 0xd0, 0x70, 0x0b,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = TableSec::parse(&mut reader).expect("The parsed section");
+        assert_eq!(sec.tables().len(), 2);
+        assert_eq!(sec.tables()[0].table_type().elem_type(), RefType::Func);
+        assert!(sec.tables()[0].init().is_none());
+        let init = sec.get(TableIdx(1)).and_then(|t| t.init());
+        assert_eq!(
+            init.map(|e| e.instrs()),
+            Some(
+                &[
+                    Instr::RefNull(crate::types::heap_type::HeapType::Func),
+                    Instr::End
+                ][..]
+            )
+        );
     }
 }
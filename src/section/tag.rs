@@ -0,0 +1,91 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
+
+use crate::encode::{Encode, encode_sized};
+use crate::parseable::{Parseable, Result};
+use crate::section::{Section, encoded_size};
+use crate::types::leb128::Leb128;
+use crate::types::primitives::{Size, TagIdx};
+use crate::types::tag_type::TagType;
+
+/// The tag section of the exception handling proposal, declaring the
+/// exception tags defined by the module.
+pub struct TagSec {
+    size: Size,
+    tags: Vec<TagType>,
+}
+
+impl Section for TagSec {
+    fn section_type(&self) -> &str {
+        "tag"
+    }
+
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl Encode for TagSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.tags.encode(writer))
+    }
+}
+
+impl TagSec {
+    pub fn new(tags: Vec<TagType>) -> TagSec {
+        TagSec {
+            size: encoded_size(&tags),
+            tags,
+        }
+    }
+
+    pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let size = u32::from(Leb128::<u32>::parse(reader)?);
+
+        Ok(TagSec {
+            size: Size(size),
+            tags: Vec::<TagType>::parse(reader)?,
+        })
+    }
+
+    pub fn tags(&self) -> &[TagType] {
+        &self.tags
+    }
+
+    /// Looks up a tag by its index among the tags defined in this section.
+    /// Imported tags come first in the tag index space.
+    pub fn get(&self, idx: TagIdx) -> Option<&TagType> {
+        self.tags.get(usize::try_from(idx.0).ok()?)
+    }
+}
+
+impl Display for TagSec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Size: {}", self.size)?;
+        writeln!(f, "Tags:")?;
+        for (i, tag) in self.tags.iter().enumerate() {
+            writeln!(f, "* tag {}: {}", i, tag)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::primitives::TypeIdx;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tag_sec() {
+        let bytes: [u8; 6] = [0x05, 0x02, 0x00, 0x00, 0x00, 0x03];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let tagsec = TagSec::parse(&mut reader).expect("The parsed section");
+        assert_eq!(tagsec.tags().len(), 2);
+        assert_eq!(tagsec.get(TagIdx(1)), Some(&TagType::new(TypeIdx(3))));
+        assert_eq!(tagsec.get(TagIdx(2)), None);
+    }
+}
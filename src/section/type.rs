@@ -1,23 +1,32 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::{Encode, encode_sized};
 use crate::parseable::{Parseable, Result};
 use crate::section::Section;
+use crate::section::encoded_size;
 use crate::types::func_type::FuncType;
 use crate::types::leb128::Leb128;
-use crate::types::primitives::Size;
+use crate::types::primitives::{Size, TypeIdx};
+use crate::types::rec_type::RecType;
+use crate::types::sub_type::SubType;
 
 pub struct TypeSec {
     size: Size,
-    funcs: Vec<FuncType>,
+    recs: Vec<RecType>,
 }
 
 impl Display for TypeSec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Size: {}", self.size)?;
         writeln!(f, "Funcs:")?;
-        for functype in &self.funcs {
-            writeln!(f, "{}", functype)?;
+        for rec in &self.recs {
+            if rec.types().len() > 1 {
+                writeln!(f, "* rec: {} types", rec.types().len())?;
+            }
+            for ty in rec.types() {
+                writeln!(f, "{}", ty)?;
+            }
         }
 
         Ok(())
@@ -34,7 +43,20 @@ impl Section for TypeSec {
     }
 }
 
+impl Encode for TypeSec {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_sized(writer, |writer| self.recs.encode(writer))
+    }
+}
+
 impl TypeSec {
+    pub fn new(recs: Vec<RecType>) -> TypeSec {
+        TypeSec {
+            size: encoded_size(&recs),
+            recs,
+        }
+    }
+
     pub fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
         Self: Sized,
@@ -43,7 +65,61 @@ impl TypeSec {
 
         Ok(TypeSec {
             size: Size(size),
-            funcs: Vec::<FuncType>::parse(reader)?,
+            recs: Vec::<RecType>::parse(reader)?,
         })
     }
+
+    /// The recursion groups as written in the section.
+    pub fn rec_groups(&self) -> &[RecType] {
+        &self.recs
+    }
+
+    /// All defined types in type index order, with recursion groups
+    /// flattened.
+    pub fn types(&self) -> impl Iterator<Item = &SubType> {
+        self.recs.iter().flat_map(|rec| rec.types())
+    }
+
+    pub fn get(&self, idx: TypeIdx) -> Option<&SubType> {
+        self.types().nth(idx.0 as usize)
+    }
+
+    /// The function type at `idx`, or `None` if the index is out of range or
+    /// refers to a struct or array type.
+    pub fn func_type(&self, idx: TypeIdx) -> Option<&FuncType> {
+        self.get(idx).and_then(|ty| ty.comp_type().as_func())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::comp_type::CompType;
+    use crate::types::num_type::NumType;
+    use crate::types::val_type::ValType;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_type_section() {
+        // (type (func (param i32)))
+        // (rec (type (struct)) (type (array (mut i64))))
+        let bytes = [
+            0x0c, 0x02, 0x60, 0x01, 0x7f, 0x00, 0x4e, 0x02, 0x5f, 0x00, 0x5e, 0x7e, 0x01,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let sec = TypeSec::parse(&mut reader).expect("The parsed section");
+        assert_eq!(sec.rec_groups().len(), 2);
+        assert_eq!(sec.types().count(), 3);
+
+        let func = sec.func_type(TypeIdx(0)).expect("A function type");
+        assert_eq!(func.params(), &[ValType::Num(NumType::I32)]);
+        assert!(func.results().is_empty());
+
+        assert_eq!(
+            sec.get(TypeIdx(1)).map(|ty| ty.comp_type()),
+            Some(&CompType::Struct(Vec::new()))
+        );
+        assert!(sec.func_type(TypeIdx(2)).is_none());
+        assert!(sec.get(TypeIdx(3)).is_none());
+    }
 }
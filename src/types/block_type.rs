@@ -0,0 +1,91 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::leb128::Leb128;
+use crate::types::primitives::TypeIdx;
+use crate::types::val_type::ValType;
+
+/// The type of a `block`, `loop` or `if`. It displays with a leading space
+/// so that an empty block type prints as nothing at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockType {
+    Empty,
+    Value(ValType),
+    Type(TypeIdx),
+}
+
+impl Parseable for BlockType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let first = match reader.fill_buf()?.first() {
+            Some(byte) => *byte,
+            None => return Err(ParseError::Other("unexpected end".to_string())),
+        };
+
+        if first == 0x40 {
+            reader.consume(1);
+            return Ok(BlockType::Empty);
+        }
+
+        // Value types are encoded as negative single-byte s33 values, so a
+        // byte with bit 6 set and no continuation bit is a value type.
+        if first & 0xc0 == 0x40 {
+            return Ok(BlockType::Value(ValType::parse(reader)?));
+        }
+
+        let idx = i64::from(Leb128::<i64>::parse_s33(reader)?);
+        match u32::try_from(idx) {
+            Ok(idx) => Ok(BlockType::Type(TypeIdx(idx))),
+            Err(_) => Err(ParseError::Other(format!("invalid block type: {}", idx))),
+        }
+    }
+}
+
+impl Encode for BlockType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            BlockType::Empty => writer.write_all(&[0x40]),
+            BlockType::Value(t) => t.encode(writer),
+            BlockType::Type(idx) => Leb128::from(i64::from(idx.0)).encode(writer),
+        }
+    }
+}
+
+impl Display for BlockType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockType::Empty => Ok(()),
+            BlockType::Value(t) => write!(f, " (result {})", t.name()),
+            BlockType::Type(idx) => write!(f, " (type {})", idx.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::num_type::NumType;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_block_type() {
+        let bytes: [u8; 5] = [0x40, 0x7f, 0x05, 0x80, 0x01];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+
+        let val = BlockType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val, BlockType::Empty);
+
+        let val = BlockType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val, BlockType::Value(ValType::Num(NumType::I32)));
+
+        let val = BlockType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val, BlockType::Type(TypeIdx(5)));
+
+        let val = BlockType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val, BlockType::Type(TypeIdx(128)));
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::primitives::{LabelIdx, TagIdx};
+
+/// A catch clause of a `try_table` instruction. The `Ref` forms also push
+/// the caught exception as an `exnref` so it can be rethrown with
+/// `throw_ref`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Catch {
+    Catch(TagIdx, LabelIdx),
+    CatchRef(TagIdx, LabelIdx),
+    CatchAll(LabelIdx),
+    CatchAllRef(LabelIdx),
+}
+
+impl Catch {
+    /// The tag this clause catches, or `None` for the catch-all forms.
+    pub fn tag(&self) -> Option<TagIdx> {
+        match self {
+            Catch::Catch(tag, _) | Catch::CatchRef(tag, _) => Some(*tag),
+            Catch::CatchAll(_) | Catch::CatchAllRef(_) => None,
+        }
+    }
+
+    /// The label the clause branches to when it catches an exception.
+    pub fn label(&self) -> LabelIdx {
+        match self {
+            Catch::Catch(_, l) | Catch::CatchRef(_, l) => *l,
+            Catch::CatchAll(l) | Catch::CatchAllRef(l) => *l,
+        }
+    }
+
+    pub fn is_ref(&self) -> bool {
+        matches!(self, Catch::CatchRef(..) | Catch::CatchAllRef(_))
+    }
+}
+
+impl Parseable for Catch {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => {
+                let tag = TagIdx::parse(reader)?;
+                Ok(Catch::Catch(tag, LabelIdx::parse(reader)?))
+            }
+            0x01 => {
+                let tag = TagIdx::parse(reader)?;
+                Ok(Catch::CatchRef(tag, LabelIdx::parse(reader)?))
+            }
+            0x02 => Ok(Catch::CatchAll(LabelIdx::parse(reader)?)),
+            0x03 => Ok(Catch::CatchAllRef(LabelIdx::parse(reader)?)),
+            kind => Err(ParseError::Other(format!(
+                "invalid catch clause: {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+impl Encode for Catch {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            Catch::Catch(tag, label) => {
+                writer.write_all(&[0x00])?;
+                tag.encode(writer)?;
+                label.encode(writer)
+            }
+            Catch::CatchRef(tag, label) => {
+                writer.write_all(&[0x01])?;
+                tag.encode(writer)?;
+                label.encode(writer)
+            }
+            Catch::CatchAll(label) => {
+                writer.write_all(&[0x02])?;
+                label.encode(writer)
+            }
+            Catch::CatchAllRef(label) => {
+                writer.write_all(&[0x03])?;
+                label.encode(writer)
+            }
+        }
+    }
+}
+
+impl Display for Catch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Catch::Catch(tag, l) => write!(f, "(catch {} {})", tag.0, l.0),
+            Catch::CatchRef(tag, l) => write!(f, "(catch_ref {} {})", tag.0, l.0),
+            Catch::CatchAll(l) => write!(f, "(catch_all {})", l.0),
+            Catch::CatchAllRef(l) => write!(f, "(catch_all_ref {})", l.0),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::field_type::FieldType;
+use crate::types::func_type::FuncType;
+
+/// A composite type: the body of an entry in the type section.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompType {
+    Func(FuncType),
+    Struct(Vec<FieldType>),
+    Array(FieldType),
+}
+
+impl CompType {
+    pub fn as_func(&self) -> Option<&FuncType> {
+        match self {
+            CompType::Func(func) => Some(func),
+            _ => None,
+        }
+    }
+}
+
+impl Parseable for CompType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let first = match reader.fill_buf()?.first() {
+            Some(byte) => *byte,
+            None => return Err(ParseError::Other("unexpected end".to_string())),
+        };
+        match first {
+            // FuncType checks its own 0x60 prefix
+            0x60 => Ok(CompType::Func(FuncType::parse(reader)?)),
+            0x5F => {
+                reader.consume(1);
+                Ok(CompType::Struct(Vec::<FieldType>::parse(reader)?))
+            }
+            0x5E => {
+                reader.consume(1);
+                Ok(CompType::Array(FieldType::parse(reader)?))
+            }
+            byte => Err(ParseError::Other(format!(
+                "invalid composite type: {:#04x}",
+                byte
+            ))),
+        }
+    }
+}
+
+impl Encode for CompType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            CompType::Func(func) => func.encode(writer),
+            CompType::Struct(fields) => {
+                writer.write_all(&[0x5F])?;
+                fields.encode(writer)
+            }
+            CompType::Array(field) => {
+                writer.write_all(&[0x5E])?;
+                field.encode(writer)
+            }
+        }
+    }
+}
+
+impl Display for CompType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompType::Func(func) => write!(f, "{}", func),
+            CompType::Struct(fields) => {
+                write!(f, "* struct: ")?;
+                for field in fields {
+                    write!(f, "{}, ", field)?;
+                }
+                writeln!(f)
+            }
+            CompType::Array(field) => writeln!(f, "* array: {}", field),
+        }
+    }
+}
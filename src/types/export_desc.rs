@@ -0,0 +1,73 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::primitives::{FuncIdx, GlobalIdx, MemIdx, TableIdx, TagIdx};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportDesc {
+    Func(FuncIdx),
+    Table(TableIdx),
+    Mem(MemIdx),
+    Global(GlobalIdx),
+    Tag(TagIdx),
+}
+
+impl Parseable for ExportDesc {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        match u8::parse(reader)? {
+            0x00 => Ok(ExportDesc::Func(FuncIdx::parse(reader)?)),
+            0x01 => Ok(ExportDesc::Table(TableIdx::parse(reader)?)),
+            0x02 => Ok(ExportDesc::Mem(MemIdx::parse(reader)?)),
+            0x03 => Ok(ExportDesc::Global(GlobalIdx::parse(reader)?)),
+            0x04 => Ok(ExportDesc::Tag(TagIdx::parse(reader)?)),
+            kind => Err(ParseError::Other(format!(
+                "invalid export kind: {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+impl Encode for ExportDesc {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            ExportDesc::Func(idx) => {
+                writer.write_all(&[0x00])?;
+                idx.encode(writer)
+            }
+            ExportDesc::Table(idx) => {
+                writer.write_all(&[0x01])?;
+                idx.encode(writer)
+            }
+            ExportDesc::Mem(idx) => {
+                writer.write_all(&[0x02])?;
+                idx.encode(writer)
+            }
+            ExportDesc::Global(idx) => {
+                writer.write_all(&[0x03])?;
+                idx.encode(writer)
+            }
+            ExportDesc::Tag(idx) => {
+                writer.write_all(&[0x04])?;
+                idx.encode(writer)
+            }
+        }
+    }
+}
+
+impl Display for ExportDesc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportDesc::Func(idx) => write!(f, "func {}", idx.0),
+            ExportDesc::Table(idx) => write!(f, "table {}", idx.0),
+            ExportDesc::Mem(idx) => write!(f, "memory {}", idx.0),
+            ExportDesc::Global(idx) => write!(f, "global {}", idx.0),
+            ExportDesc::Tag(idx) => write!(f, "tag {}", idx.0),
+        }
+    }
+}
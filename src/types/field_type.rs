@@ -0,0 +1,122 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::r#mut::{Mut, VAR};
+use crate::types::num_type::NumType;
+use crate::types::val_type::ValType;
+
+/// The type stored in a struct field or array element. The packed types
+/// only exist in storage; they are read as i32.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StorageType {
+    Val(ValType),
+    I8,
+    I16,
+}
+
+impl StorageType {
+    /// The type of the value on the operand stack when the field is read.
+    pub fn unpacked(&self) -> ValType {
+        match self {
+            StorageType::Val(t) => *t,
+            StorageType::I8 | StorageType::I16 => ValType::Num(NumType::I32),
+        }
+    }
+
+    pub fn is_packed(&self) -> bool {
+        !matches!(self, StorageType::Val(_))
+    }
+}
+
+impl Parseable for StorageType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let first = match reader.fill_buf()?.first() {
+            Some(byte) => *byte,
+            None => return Err(ParseError::Other("unexpected end".to_string())),
+        };
+        match first {
+            0x78 => {
+                reader.consume(1);
+                Ok(StorageType::I8)
+            }
+            0x77 => {
+                reader.consume(1);
+                Ok(StorageType::I16)
+            }
+            _ => Ok(StorageType::Val(ValType::parse(reader)?)),
+        }
+    }
+}
+
+impl Encode for StorageType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            StorageType::Val(t) => t.encode(writer),
+            StorageType::I8 => writer.write_all(&[0x78]),
+            StorageType::I16 => writer.write_all(&[0x77]),
+        }
+    }
+}
+
+impl Display for StorageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageType::Val(t) => write!(f, "{}", t.name()),
+            StorageType::I8 => write!(f, "i8"),
+            StorageType::I16 => write!(f, "i16"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FieldType {
+    st: StorageType,
+    r#mut: Mut,
+}
+
+impl FieldType {
+    pub fn new(st: StorageType, r#mut: Mut) -> FieldType {
+        FieldType { st, r#mut }
+    }
+
+    pub fn storage_type(&self) -> StorageType {
+        self.st
+    }
+
+    pub fn is_mutable(&self) -> bool {
+        self.r#mut == VAR
+    }
+}
+
+impl Parseable for FieldType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let st = StorageType::parse(reader)?;
+        let r#mut = Mut::parse(reader)?;
+        Ok(FieldType { st, r#mut })
+    }
+}
+
+impl Encode for FieldType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.st.encode(writer)?;
+        self.r#mut.encode(writer)
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_mutable() {
+            write!(f, "(mut {})", self.st)
+        } else {
+            write!(f, "{}", self.st)
+        }
+    }
+}
@@ -1,19 +1,36 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
 use crate::parseable::{Asked, ParseError, Parseable, Received, Result};
 use crate::types::result_type::ResultType;
+use crate::types::val_type::ValType;
 
+use crate::encode::Encode;
 #[cfg(test)]
 use crate::types::ref_type::RefType;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FuncType {
     rt1: ResultType,
     rt2: ResultType,
 }
 
 impl FuncType {
+    pub fn new(params: Vec<ValType>, results: Vec<ValType>) -> FuncType {
+        FuncType {
+            rt1: ResultType::from(params),
+            rt2: ResultType::from(results),
+        }
+    }
+
+    pub fn params(&self) -> &[ValType] {
+        self.rt1.vec()
+    }
+
+    pub fn results(&self) -> &[ValType] {
+        self.rt2.vec()
+    }
+
     fn parse_first_byte(reader: &mut BufReader<dyn Read>) -> Result<u8> {
         let mut buf: [u8; 1] = [0; 1];
         let n = reader.read(&mut buf)?;
@@ -57,15 +74,23 @@ impl Parseable for FuncType {
         let rt1 = ResultType::parse(reader)?;
         let rt2 = ResultType::parse(reader)?;
 
-        let func = FuncType { rt1: rt1, rt2: rt2 };
+        let func = FuncType { rt1, rt2 };
 
         Ok(func)
     }
 }
 
+impl Encode for FuncType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        writer.write_all(&[0x60])?;
+        self.rt1.encode(writer)?;
+        self.rt2.encode(writer)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::types::{num_type::NumType, val_type::ValType, vec_type::VecType};
+    use crate::types::{num_type::NumType, vec_type::VecType};
 
     use super::*;
     use std::io::Cursor;
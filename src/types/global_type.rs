@@ -1,7 +1,9 @@
-use std::io::{BufReader, Read};
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::Encode;
 use crate::parseable::{Parseable, Result};
-use crate::types::r#mut::Mut;
+use crate::types::r#mut::{Mut, VAR};
 use crate::types::val_type::ValType;
 
 pub struct GlobalType {
@@ -9,6 +11,20 @@ pub struct GlobalType {
     r#mut: Mut,
 }
 
+impl GlobalType {
+    pub fn new(t: ValType, r#mut: Mut) -> GlobalType {
+        GlobalType { t, r#mut }
+    }
+
+    pub fn val_type(&self) -> ValType {
+        self.t
+    }
+
+    pub fn is_mutable(&self) -> bool {
+        self.r#mut == VAR
+    }
+}
+
 impl Parseable for GlobalType {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
@@ -21,8 +37,19 @@ impl Parseable for GlobalType {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Cursor;
+impl Encode for GlobalType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.t.encode(writer)?;
+        self.r#mut.encode(writer)
+    }
+}
+
+impl Display for GlobalType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_mutable() {
+            write!(f, "globaltype: mut {}", self.t.name())
+        } else {
+            write!(f, "globaltype: {}", self.t.name())
+        }
+    }
 }
@@ -0,0 +1,155 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::leb128::Leb128;
+use crate::types::primitives::TypeIdx;
+
+/// What a reference points to: either one of the abstract heap types or a
+/// concrete type from the type section (typed function references).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeapType {
+    Func,
+    Extern,
+    Exn,
+    // Abstract heap types of the GC proposal
+    Any,
+    Eq,
+    I31,
+    Struct,
+    Array,
+    /// The bottom types of the any, func, extern and exn hierarchies.
+    None,
+    NoFunc,
+    NoExtern,
+    NoExn,
+    Concrete(TypeIdx),
+}
+
+impl HeapType {
+    /// Maps the byte of an abstract heap type to the heap type.
+    pub fn from_abstract_byte(byte: u8) -> Option<HeapType> {
+        match byte {
+            0x70 => Some(HeapType::Func),
+            0x6f => Some(HeapType::Extern),
+            0x69 => Some(HeapType::Exn),
+            0x6e => Some(HeapType::Any),
+            0x6d => Some(HeapType::Eq),
+            0x6c => Some(HeapType::I31),
+            0x6b => Some(HeapType::Struct),
+            0x6a => Some(HeapType::Array),
+            0x71 => Some(HeapType::None),
+            0x73 => Some(HeapType::NoFunc),
+            0x72 => Some(HeapType::NoExtern),
+            0x74 => Some(HeapType::NoExn),
+            _ => None,
+        }
+    }
+
+    /// The byte of an abstract heap type, or `None` for a concrete type.
+    pub fn abstract_byte(&self) -> Option<u8> {
+        match self {
+            HeapType::Func => Some(0x70),
+            HeapType::Extern => Some(0x6f),
+            HeapType::Exn => Some(0x69),
+            HeapType::Any => Some(0x6e),
+            HeapType::Eq => Some(0x6d),
+            HeapType::I31 => Some(0x6c),
+            HeapType::Struct => Some(0x6b),
+            HeapType::Array => Some(0x6a),
+            HeapType::None => Some(0x71),
+            HeapType::NoFunc => Some(0x73),
+            HeapType::NoExtern => Some(0x72),
+            HeapType::NoExn => Some(0x74),
+            HeapType::Concrete(_) => None,
+        }
+    }
+}
+
+impl Parseable for HeapType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let first = match reader.fill_buf()?.first() {
+            Some(byte) => *byte,
+            None => return Err(ParseError::Other("unexpected end".to_string())),
+        };
+
+        // Abstract heap types are encoded as negative single-byte s33
+        // values; anything else is a type index.
+        if first & 0xc0 == 0x40 {
+            reader.consume(1);
+            return match Self::from_abstract_byte(first) {
+                Some(heap) => Ok(heap),
+                None => Err(ParseError::Other(format!(
+                    "invalid heap type: {:#04x}",
+                    first
+                ))),
+            };
+        }
+
+        let idx = i64::from(Leb128::<i64>::parse_s33(reader)?);
+        match u32::try_from(idx) {
+            Ok(idx) => Ok(HeapType::Concrete(TypeIdx(idx))),
+            Err(_) => Err(ParseError::Other(format!("invalid heap type: {}", idx))),
+        }
+    }
+}
+
+impl Encode for HeapType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match (self, self.abstract_byte()) {
+            (_, Some(byte)) => writer.write_all(&[byte]),
+            (HeapType::Concrete(idx), None) => Leb128::from(i64::from(idx.0)).encode(writer),
+            (_, None) => unreachable!("every abstract heap type has a byte"),
+        }
+    }
+}
+
+impl Display for HeapType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeapType::Func => write!(f, "func"),
+            HeapType::Extern => write!(f, "extern"),
+            HeapType::Exn => write!(f, "exn"),
+            HeapType::Any => write!(f, "any"),
+            HeapType::Eq => write!(f, "eq"),
+            HeapType::I31 => write!(f, "i31"),
+            HeapType::Struct => write!(f, "struct"),
+            HeapType::Array => write!(f, "array"),
+            HeapType::None => write!(f, "none"),
+            HeapType::NoFunc => write!(f, "nofunc"),
+            HeapType::NoExtern => write!(f, "noextern"),
+            HeapType::NoExn => write!(f, "noexn"),
+            HeapType::Concrete(idx) => write!(f, "{}", idx.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_heap_type() {
+        let bytes: [u8; 8] = [0x70, 0x69, 0x6c, 0x71, 0x05, 0x80, 0x01, 0x7f];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert_eq!(HeapType::parse(&mut reader), Ok(HeapType::Func));
+        assert_eq!(HeapType::parse(&mut reader), Ok(HeapType::Exn));
+        assert_eq!(HeapType::parse(&mut reader), Ok(HeapType::I31));
+        assert_eq!(HeapType::parse(&mut reader), Ok(HeapType::None));
+        assert_eq!(
+            HeapType::parse(&mut reader),
+            Ok(HeapType::Concrete(TypeIdx(5)))
+        );
+        assert_eq!(
+            HeapType::parse(&mut reader),
+            Ok(HeapType::Concrete(TypeIdx(128)))
+        );
+        // i32 is a value type, not a heap type
+        assert!(HeapType::parse(&mut reader).is_err());
+    }
+}
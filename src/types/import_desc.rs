@@ -1,16 +1,20 @@
-use std::io::{BufReader, Read};
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
 
-use crate::parseable::{Parseable, Result};
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
 use crate::types::global_type::GlobalType;
 use crate::types::mem_type::MemType;
 use crate::types::primitives::TypeIdx;
 use crate::types::table_type::TableType;
+use crate::types::tag_type::TagType;
 
-pub struct ImportDesc {
-    func: TypeIdx,
-    table: TableType,
-    mem: MemType,
-    global: GlobalType,
+pub enum ImportDesc {
+    Func(TypeIdx),
+    Table(TableType),
+    Mem(MemType),
+    Global(GlobalType),
+    Tag(TagType),
 }
 
 impl Parseable for ImportDesc {
@@ -18,16 +22,89 @@ impl Parseable for ImportDesc {
     where
         Self: Sized,
     {
-        let func = TypeIdx::parse(reader)?;
-        let table = TableType::parse(reader)?;
-        let mem = MemType::parse(reader)?;
-        let global = GlobalType::parse(reader)?;
-
-        Ok(ImportDesc {
-            func,
-            table,
-            mem,
-            global,
-        })
+        match u8::parse(reader)? {
+            0x00 => Ok(ImportDesc::Func(TypeIdx::parse(reader)?)),
+            0x01 => Ok(ImportDesc::Table(TableType::parse(reader)?)),
+            0x02 => Ok(ImportDesc::Mem(MemType::parse(reader)?)),
+            0x03 => Ok(ImportDesc::Global(GlobalType::parse(reader)?)),
+            0x04 => Ok(ImportDesc::Tag(TagType::parse(reader)?)),
+            kind => Err(ParseError::Other(format!(
+                "invalid import kind: {:#04x}",
+                kind
+            ))),
+        }
+    }
+}
+
+impl Encode for ImportDesc {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            ImportDesc::Func(ty) => {
+                writer.write_all(&[0x00])?;
+                ty.encode(writer)
+            }
+            ImportDesc::Table(ty) => {
+                writer.write_all(&[0x01])?;
+                ty.encode(writer)
+            }
+            ImportDesc::Mem(ty) => {
+                writer.write_all(&[0x02])?;
+                ty.encode(writer)
+            }
+            ImportDesc::Global(ty) => {
+                writer.write_all(&[0x03])?;
+                ty.encode(writer)
+            }
+            ImportDesc::Tag(ty) => {
+                writer.write_all(&[0x04])?;
+                ty.encode(writer)
+            }
+        }
+    }
+}
+
+impl Display for ImportDesc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportDesc::Func(ty) => write!(f, "func type {}", ty.0),
+            ImportDesc::Table(table) => write!(f, "{}", table),
+            ImportDesc::Mem(mem) => write!(f, "{}", mem),
+            ImportDesc::Global(global) => write!(f, "{}", global),
+            ImportDesc::Tag(tag) => write!(f, "{}", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_import_desc() {
+        let bytes: [u8; 12] = [
+            0x00, 0x05, 0x02, 0x04, 0x01, 0x03, 0x7f, 0x01, 0x04, 0x00, 0x01, 0x05,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+
+        let desc = ImportDesc::parse(&mut reader).expect("The parsed value");
+        assert!(matches!(desc, ImportDesc::Func(TypeIdx(5))));
+
+        let desc = ImportDesc::parse(&mut reader).expect("The parsed value");
+        match desc {
+            ImportDesc::Mem(mem) => {
+                assert!(mem.is_64());
+                assert_eq!(mem.limits().min(), 1);
+            }
+            _ => panic!("Expected a memory import"),
+        }
+
+        let desc = ImportDesc::parse(&mut reader).expect("The parsed value");
+        assert!(matches!(desc, ImportDesc::Global(_)));
+
+        let desc = ImportDesc::parse(&mut reader).expect("The parsed value");
+        assert!(matches!(desc, ImportDesc::Tag(tag) if tag.type_idx() == TypeIdx(1)));
+
+        assert!(ImportDesc::parse(&mut reader).is_err());
     }
 }
@@ -1,28 +1,240 @@
-use std::io::{BufReader, Read};
+use std::cell::RefCell;
+use std::io::{BufReader, Read, Write};
 
-use crate::parseable::{Asked, ParseError, Parseable, Received, Result};
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
 
 pub struct Leb128<T>(T);
 
-impl Parseable for Leb128<u32> {
-    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Leb128<u32>> {
-        let mut num: u32 = 0;
-        let mut shift: u32 = 0;
-        let mut buf: [u8; 1] = [0];
-        let mut val: u8;
-        loop {
-            let n = reader.read(&mut buf[..]).unwrap();
-            if n != 1 {
-                return Err(ParseError::wrong_num_bytes_read(Asked(1), Received(n)));
+/// The widths of the LEB128 numbers that were written with more bytes than
+/// necessary, keyed by their position among all numbers read in the same
+/// stretch of parsing. Replaying them while encoding reproduces the padding.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LebWidths(Vec<(u32, u8)>);
+
+impl LebWidths {
+    /// Whether every number was written in its shortest form.
+    pub fn is_canonical(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+enum Tracking {
+    Record {
+        count: u32,
+        widths: LebWidths,
+    },
+    Replay {
+        count: u32,
+        next: usize,
+        widths: LebWidths,
+    },
+}
+
+thread_local! {
+    static TRACKING: RefCell<Option<Tracking>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, recording the width of every LEB128 number it parses.
+pub(crate) fn record_widths<T>(f: impl FnOnce() -> T) -> (T, LebWidths) {
+    let outer = TRACKING.replace(Some(Tracking::Record {
+        count: 0,
+        widths: LebWidths::default(),
+    }));
+    let result = f();
+    match TRACKING.replace(outer) {
+        Some(Tracking::Record { widths, .. }) => (result, widths),
+        _ => (result, LebWidths::default()),
+    }
+}
+
+/// Runs `f`, padding the LEB128 numbers it encodes to the widths in
+/// `widths`. `f` must encode the numbers in the order they were parsed.
+pub(crate) fn replay_widths<T>(widths: &LebWidths, f: impl FnOnce() -> T) -> T {
+    let outer = TRACKING.replace(Some(Tracking::Replay {
+        count: 0,
+        next: 0,
+        widths: widths.clone(),
+    }));
+    let result = f();
+    TRACKING.set(outer);
+    result
+}
+
+fn note_width(read: usize, canonical: usize) {
+    TRACKING.with_borrow_mut(|tracking| {
+        if let Some(Tracking::Record { count, widths }) = tracking {
+            if read != canonical {
+                widths.0.push((*count, read as u8));
+            }
+            *count += 1;
+        }
+    });
+}
+
+/// The width the next LEB128 number to be encoded should be padded to, or 0
+/// if it should be as short as possible.
+pub(crate) fn next_width() -> usize {
+    TRACKING.with_borrow_mut(|tracking| match tracking {
+        Some(Tracking::Replay {
+            count,
+            next,
+            widths,
+        }) => {
+            let ordinal = *count;
+            *count += 1;
+            while widths.0.get(*next).is_some_and(|(at, _)| *at < ordinal) {
+                *next += 1;
+            }
+            match widths.0.get(*next) {
+                Some((at, width)) if *at == ordinal => usize::from(*width),
+                _ => 0,
+            }
+        }
+        _ => 0,
+    })
+}
+
+pub(crate) fn unsigned_len(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    bits.div_ceil(7).max(1)
+}
+
+fn signed_len(value: i64) -> usize {
+    // One more bit than the magnitude needs, for the sign
+    let magnitude = if value < 0 { !value } else { value };
+    (65 - magnitude.leading_zeros() as usize).div_ceil(7)
+}
+
+/// Writes `value` in at least `width` bytes, padding with continuation
+/// bytes that leave the value unchanged.
+pub(crate) fn write_unsigned(
+    writer: &mut dyn Write,
+    mut value: u64,
+    width: usize,
+) -> std::io::Result<()> {
+    let mut written = 0;
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        written += 1;
+        let more = value != 0 || written < width;
+        if more {
+            byte |= 0x80;
+        }
+        writer.write_all(&[byte])?;
+        if !more {
+            return Ok(());
+        }
+    }
+}
+
+/// Like `write_unsigned`, but the padding repeats the sign bit.
+pub(crate) fn write_signed(
+    writer: &mut dyn Write,
+    mut value: i64,
+    width: usize,
+) -> std::io::Result<()> {
+    let mut written = 0;
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        // Arithmetic shift, so that negative numbers end in all ones
+        value >>= 7;
+        written += 1;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        let more = !done || written < width;
+        if more {
+            byte |= 0x80;
+        }
+        writer.write_all(&[byte])?;
+        if !more {
+            return Ok(());
+        }
+    }
+}
+
+// Reads an unsigned LEB128 number that must fit in `bits` bits. The
+// encoding may use at most ceil(bits / 7) bytes, and any bits of the last
+// byte beyond `bits` must be zero.
+fn parse_unsigned(reader: &mut BufReader<dyn Read>, bits: u32) -> Result<u64> {
+    let mut num: u64 = 0;
+    let mut shift: u32 = 0;
+    loop {
+        let val = u8::parse(reader)?;
+        let low = u64::from(val & 127);
+        if shift + 7 >= bits {
+            let remaining = bits - shift;
+            if val & 128 != 0 {
+                return Err(ParseError::Other(
+                    "integer representation too long".to_string(),
+                ));
+            }
+            if remaining < 7 && low >> remaining != 0 {
+                return Err(ParseError::Other("integer too large".to_string()));
+            }
+        }
+        num |= low << shift;
+        if val & 128 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    note_width(shift as usize / 7 + 1, unsigned_len(num));
+    Ok(num)
+}
+
+//    MSB ------------------ LSB
+//         11110001001000000  Binary encoding of 123456
+//     000011110001001000000  As a 21-bit number
+//     111100001110110111111  Negating all bits (ones' complement)
+//     111100001110111000000  Adding one (two's complement)
+// 1111000  0111011  1000000  Split into 7-bit groups
+//01111000 10111011 11000000  Add high 1 bits on all but last (most significant) group to form bytes
+//    0x78     0xBB     0xC0  In hexadecimal
+//
+//→ 0xC0 0xBB 0x78            Output stream (LSB to MSB)
+//
+// Like `parse_unsigned`, but the unused bits of the last byte must all
+// match the sign bit.
+fn parse_signed(reader: &mut BufReader<dyn Read>, bits: u32) -> Result<i64> {
+    let mut num: i64 = 0;
+    let mut shift: u32 = 0;
+    let mut val: u8;
+    loop {
+        val = u8::parse(reader)?;
+        let low = i64::from(val & 127);
+        if shift + 7 >= bits {
+            let remaining = bits - shift;
+            if val & 128 != 0 {
+                return Err(ParseError::Other(
+                    "integer representation too long".to_string(),
+                ));
             }
-            val = u8::from_le_bytes(buf);
-            num += u32::from(val & 127) << shift;
-            if val & 128 == 0 {
-                break ();
+            if remaining < 7 {
+                let unused = (val & 127) >> (remaining - 1);
+                if unused != 0 && unused != 127 >> (remaining - 1) {
+                    return Err(ParseError::Other("integer too large".to_string()));
+                }
             }
-            shift += 7;
         }
-        Ok(Leb128(num))
+        num |= low << shift;
+        shift += 7;
+        if val & 128 == 0 {
+            break;
+        }
+    }
+
+    if shift < 64 && val & 0x40 != 0 {
+        num |= -1 << shift;
+    }
+    note_width(shift as usize / 7, signed_len(num));
+    Ok(num)
+}
+
+impl Parseable for Leb128<u32> {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Leb128<u32>> {
+        let num = parse_unsigned(reader, 32)?;
+        Ok(Leb128(num as u32))
     }
 }
 
@@ -38,6 +250,18 @@ impl From<&Leb128<u32>> for u32 {
     }
 }
 
+impl From<u32> for Leb128<u32> {
+    fn from(value: u32) -> Self {
+        Leb128(value)
+    }
+}
+
+impl Encode for Leb128<u32> {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        write_unsigned(writer, u64::from(self.0), next_width())
+    }
+}
+
 // TODO: can we more elegantly convert a
 // vector of LEB types to their natural types?
 /* impl<T> From<Vec<Leb128<T>>> for Vec<T> {
@@ -47,40 +271,9 @@ impl From<&Leb128<u32>> for u32 {
 } */
 
 impl Parseable for Leb128<i32> {
-    //    MSB ------------------ LSB
-    //         11110001001000000  Binary encoding of 123456
-    //     000011110001001000000  As a 21-bit number
-    //     111100001110110111111  Negating all bits (ones' complement)
-    //     111100001110111000000  Adding one (two's complement)
-    // 1111000  0111011  1000000  Split into 7-bit groups
-    //01111000 10111011 11000000  Add high 1 bits on all but last (most significant) group to form bytes
-    //    0x78     0xBB     0xC0  In hexadecimal
-    //
-    //→ 0xC0 0xBB 0x78            Output stream (LSB to MSB)
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Leb128<i32>> {
-        let mut num: i32 = 0;
-        let mut shift: u32 = 0;
-        let mut buf: [u8; 1] = [0];
-        let mut val: u8;
-
-        loop {
-            let n = reader.read(&mut buf[..]).unwrap();
-            if n != 1 {
-                return Err(ParseError::wrong_num_bytes_read(Asked(1), Received(n)));
-            }
-            val = u8::from_le_bytes(buf);
-            num += i32::from(val & 127) << shift;
-            shift += 7;
-            if val & 128 == 0 {
-                break ();
-            }
-        }
-
-        let i32_size: u32 = size_of::<i32>().try_into().unwrap();
-        if shift < (i32_size * 8) && val & 0x40 != 0 {
-            num |= -(1 << shift);
-        }
-        Ok(Leb128(num))
+        let num = parse_signed(reader, 32)?;
+        Ok(Leb128(num as i32))
     }
 }
 
@@ -96,9 +289,21 @@ impl From<&Leb128<i32>> for i32 {
     }
 }
 
+impl From<i32> for Leb128<i32> {
+    fn from(value: i32) -> Self {
+        Leb128(value)
+    }
+}
+
+impl Encode for Leb128<i32> {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        write_signed(writer, i64::from(self.0), next_width())
+    }
+}
+
 impl Parseable for Leb128<u64> {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Leb128<u64>> {
-        Ok(Leb128(0))
+        Ok(Leb128(parse_unsigned(reader, 64)?))
     }
 }
 
@@ -114,9 +319,21 @@ impl From<&Leb128<u64>> for u64 {
     }
 }
 
+impl From<u64> for Leb128<u64> {
+    fn from(value: u64) -> Self {
+        Leb128(value)
+    }
+}
+
+impl Encode for Leb128<u64> {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        write_unsigned(writer, self.0, next_width())
+    }
+}
+
 impl Parseable for Leb128<i64> {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Leb128<i64>> {
-        Ok(Leb128(0))
+        Ok(Leb128(parse_signed(reader, 64)?))
     }
 }
 
@@ -132,6 +349,26 @@ impl From<&Leb128<i64>> for i64 {
     }
 }
 
+impl From<i64> for Leb128<i64> {
+    fn from(value: i64) -> Self {
+        Leb128(value)
+    }
+}
+
+impl Encode for Leb128<i64> {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        write_signed(writer, self.0, next_width())
+    }
+}
+
+impl Leb128<i64> {
+    /// Parses the signed 33-bit integer used for type indices in block
+    /// types, which must be non-negative to be a valid index.
+    pub fn parse_s33(reader: &mut BufReader<dyn Read>) -> Result<Leb128<i64>> {
+        Ok(Leb128(parse_signed(reader, 33)?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,6 +382,37 @@ mod tests {
         assert!(result.is_ok());
         let val = u32::from(result.expect("The parsed value"));
         assert_eq!(val, 624485);
+
+        // Non-canonical, but still within five bytes
+        let bytes: [u8; 5] = [0x83, 0x80, 0x80, 0x80, 0x00];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = u32::from(Leb128::<u32>::parse(&mut reader).expect("The parsed value"));
+        assert_eq!(val, 3);
+
+        let bytes: [u8; 5] = [0xFF, 0xFF, 0xFF, 0xFF, 0x0F];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = u32::from(Leb128::<u32>::parse(&mut reader).expect("The parsed value"));
+        assert_eq!(val, u32::MAX);
+
+        // Unused bits set in the last byte
+        let bytes: [u8; 5] = [0xFF, 0xFF, 0xFF, 0xFF, 0x1F];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let result = Leb128::<u32>::parse(&mut reader);
+        assert_eq!(
+            result.err(),
+            Some(ParseError::Other("integer too large".to_string()))
+        );
+
+        // Six bytes is too many for a u32
+        let bytes: [u8; 6] = [0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let result = Leb128::<u32>::parse(&mut reader);
+        assert_eq!(
+            result.err(),
+            Some(ParseError::Other(
+                "integer representation too long".to_string()
+            ))
+        );
     }
 
     #[test]
@@ -155,5 +423,102 @@ mod tests {
         assert!(result.is_ok());
         let val = i32::from(result.expect("The parsed value"));
         assert_eq!(val, -123456);
+
+        let bytes: [u8; 5] = [0x80, 0x80, 0x80, 0x80, 0x78];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = i32::from(Leb128::<i32>::parse(&mut reader).expect("The parsed value"));
+        assert_eq!(val, i32::MIN);
+
+        // The unused bits must match the sign bit
+        let bytes: [u8; 5] = [0x80, 0x80, 0x80, 0x80, 0x70];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Leb128::<i32>::parse(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_leb128_u64() {
+        let bytes: [u8; 10] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = u64::from(Leb128::<u64>::parse(&mut reader).expect("The parsed value"));
+        assert_eq!(val, u64::MAX);
+
+        let bytes: [u8; 10] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Leb128::<u64>::parse(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_leb128_i64() {
+        let bytes: [u8; 10] = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7F];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = i64::from(Leb128::<i64>::parse(&mut reader).expect("The parsed value"));
+        assert_eq!(val, i64::MIN);
+
+        let bytes: [u8; 1] = [0x7F];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = i64::from(Leb128::<i64>::parse(&mut reader).expect("The parsed value"));
+        assert_eq!(val, -1);
+    }
+
+    #[test]
+    fn test_write_padded() {
+        let mut bytes = Vec::new();
+        write_unsigned(&mut bytes, 624485, 0).expect("The written value");
+        assert_eq!(bytes, [0xE5, 0x8E, 0x26]);
+
+        let mut bytes = Vec::new();
+        write_unsigned(&mut bytes, 3, 5).expect("The written value");
+        assert_eq!(bytes, [0x83, 0x80, 0x80, 0x80, 0x00]);
+
+        let mut bytes = Vec::new();
+        write_signed(&mut bytes, -123456, 0).expect("The written value");
+        assert_eq!(bytes, [0xc0, 0xbb, 0x78]);
+
+        let mut bytes = Vec::new();
+        write_signed(&mut bytes, -1, 3).expect("The written value");
+        assert_eq!(bytes, [0xFF, 0xFF, 0x7F]);
+
+        let mut bytes = Vec::new();
+        write_signed(&mut bytes, 64, 0).expect("The written value");
+        assert_eq!(bytes, [0xC0, 0x00]);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        // 1 in two bytes, 2 in one, then -1 in three
+        let bytes: [u8; 6] = [0x81, 0x00, 0x02, 0xFF, 0xFF, 0x7F];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let (values, widths) = record_widths(|| {
+            let a = u32::from(Leb128::<u32>::parse(&mut reader).expect("The parsed value"));
+            let b = u32::from(Leb128::<u32>::parse(&mut reader).expect("The parsed value"));
+            let c = i32::from(Leb128::<i32>::parse(&mut reader).expect("The parsed value"));
+            (a, b, c)
+        });
+        assert_eq!(values, (1, 2, -1));
+        assert_eq!(widths, LebWidths(vec![(0, 2), (2, 3)]));
+
+        let mut encoded = Vec::new();
+        replay_widths(&widths, || {
+            Leb128::from(values.0).encode(&mut encoded)?;
+            Leb128::from(values.1).encode(&mut encoded)?;
+            Leb128::from(values.2).encode(&mut encoded)
+        })
+        .expect("The encoded values");
+        assert_eq!(encoded, bytes);
+
+        // Outside of a replay everything is written canonically
+        let mut encoded = Vec::new();
+        Leb128::from(values.0)
+            .encode(&mut encoded)
+            .expect("The encoded value");
+        assert_eq!(encoded, [0x01]);
+    }
+
+    #[test]
+    fn test_leb128_s33() {
+        let bytes: [u8; 5] = [0xFF, 0xFF, 0xFF, 0xFF, 0x0F];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = i64::from(Leb128::<i64>::parse_s33(&mut reader).expect("The parsed value"));
+        assert_eq!(val, 0xFFFF_FFFF);
     }
 }
@@ -1,13 +1,68 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
-use crate::parseable::{Parseable, Result};
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
 use crate::types::leb128::Leb128;
 
+/// Bit 0 of the limits flag: a maximum follows the minimum.
+const HAS_MAX: u8 = 0x01;
+/// Bit 1 of the limits flag: the memory is shared between threads.
+const SHARED: u8 = 0x02;
+/// Bit 2 of the limits flag: the limits use 64-bit indices (memory64).
+const IS_64: u8 = 0x04;
+
 #[derive(Debug, PartialEq)]
 pub struct Limits {
-    min: u32,
-    max: Option<u32>,
+    min: u64,
+    max: Option<u64>,
+    shared: bool,
+    is_64: bool,
+}
+
+impl Limits {
+    pub fn new(min: u64, max: Option<u64>) -> Limits {
+        Limits {
+            min,
+            max,
+            shared: false,
+            is_64: false,
+        }
+    }
+
+    /// Limits of a 64-bit index space, as used by memory64 and table64.
+    pub fn new_64(min: u64, max: Option<u64>) -> Limits {
+        Limits {
+            is_64: true,
+            ..Limits::new(min, max)
+        }
+    }
+
+    /// The same limits for a memory shared between threads.
+    pub fn shared(self) -> Limits {
+        Limits {
+            shared: true,
+            ..self
+        }
+    }
+
+    pub fn min(&self) -> u64 {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.max
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Whether the limits describe a 64-bit index space, in which case
+    /// addresses are `i64` rather than `i32`.
+    pub fn is_64(&self) -> bool {
+        self.is_64
+    }
 }
 
 impl Parseable for Limits {
@@ -16,24 +71,79 @@ impl Parseable for Limits {
         Self: Sized,
     {
         let flag = u8::parse(reader)?;
-        let min = u32::from(Leb128::<u32>::parse(reader)?);
-        let mut max: Option<u32> = None;
-        if flag == 0x1 {
-            let max_val = u32::from(Leb128::<u32>::parse(reader)?);
-            max = Some(max_val);
+        if flag & !(HAS_MAX | SHARED | IS_64) != 0 {
+            return Err(ParseError::Other(format!(
+                "invalid limits flag: {:#04x}",
+                flag
+            )));
+        }
+
+        let is_64 = flag & IS_64 != 0;
+        let parse_bound = |reader: &mut BufReader<dyn Read>| -> Result<u64> {
+            if is_64 {
+                Ok(u64::from(Leb128::<u64>::parse(reader)?))
+            } else {
+                Ok(u64::from(u32::from(Leb128::<u32>::parse(reader)?)))
+            }
+        };
+
+        let min = parse_bound(reader)?;
+        let mut max: Option<u64> = None;
+        if flag & HAS_MAX != 0 {
+            max = Some(parse_bound(reader)?);
+        }
+
+        Ok(Limits {
+            min,
+            max,
+            shared: flag & SHARED != 0,
+            is_64,
+        })
+    }
+}
+
+impl Encode for Limits {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        let mut flag = 0;
+        if self.max.is_some() {
+            flag |= HAS_MAX;
+        }
+        if self.shared {
+            flag |= SHARED;
+        }
+        if self.is_64 {
+            flag |= IS_64;
         }
+        writer.write_all(&[flag])?;
 
-        Ok(Limits { min, max })
+        let encode_bound = |writer: &mut dyn Write, bound: u64| {
+            if self.is_64 {
+                Leb128::from(bound).encode(writer)
+            } else {
+                Leb128::from(bound as u32).encode(writer)
+            }
+        };
+        encode_bound(writer, self.min)?;
+        if let Some(max) = self.max {
+            encode_bound(writer, max)?;
+        }
+        Ok(())
     }
 }
 
 impl Display for Limits {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "min: {}", self.min)?;
-        match self.max {
-            Some(max) => write!(f, ", max: {}", max),
-            None => Ok(()),
+        if let Some(max) = self.max {
+            write!(f, ", max: {}", max)?;
+        }
+        if self.is_64 {
+            write!(f, ", i64")?;
         }
+        if self.shared {
+            write!(f, ", shared")?;
+        }
+        Ok(())
     }
 }
 
@@ -49,25 +159,50 @@ mod tests {
         let result = Limits::parse(&mut reader);
         assert!(result.is_ok());
         let val = result.expect("The parsed value");
-        assert_eq!(
-            val,
-            Limits {
-                min: 2,
-                max: Some(624485)
-            }
-        );
+        assert_eq!(val, Limits::new(2, Some(624485)));
 
         let bytes: [u8; 4] = [0x00, 0xE5, 0x8E, 0x26];
         let mut reader = BufReader::new(Cursor::new(bytes));
         let result = Limits::parse(&mut reader);
         assert!(result.is_ok());
         let val = result.expect("The parsed value");
-        assert_eq!(
-            val,
-            Limits {
-                min: 624485,
-                max: None
-            }
-        )
+        assert_eq!(val, Limits::new(624485, None))
+    }
+
+    #[test]
+    fn test_limits_64() {
+        // 0x05: i64 index type with a maximum; 2^32 pages does not fit a u32
+        let bytes: [u8; 7] = [0x05, 0x01, 0x80, 0x80, 0x80, 0x80, 0x10];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = Limits::parse(&mut reader).expect("The parsed value");
+        assert!(val.is_64());
+        assert!(!val.is_shared());
+        assert_eq!(val.min(), 1);
+        assert_eq!(val.max(), Some(1 << 32));
+
+        // 0x04: i64 index type without a maximum
+        let bytes: [u8; 2] = [0x04, 0x03];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = Limits::parse(&mut reader).expect("The parsed value");
+        assert!(val.is_64());
+        assert_eq!(val.min(), 3);
+        assert_eq!(val.max(), None);
+
+        // 0x07: shared, i64, with a maximum
+        let bytes: [u8; 3] = [0x07, 0x01, 0x02];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = Limits::parse(&mut reader).expect("The parsed value");
+        assert!(val.is_64());
+        assert!(val.is_shared());
+        assert_eq!(val.max(), Some(2));
+
+        // 32-bit limits still reject values beyond u32
+        let bytes: [u8; 6] = [0x00, 0x80, 0x80, 0x80, 0x80, 0x10];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Limits::parse(&mut reader).is_err());
+
+        let bytes: [u8; 2] = [0x08, 0x00];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(Limits::parse(&mut reader).is_err());
     }
 }
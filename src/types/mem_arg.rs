@@ -0,0 +1,136 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::leb128::Leb128;
+use crate::types::primitives::MemIdx;
+
+/// Bit 6 of the alignment field signals that an explicit memory index
+/// follows (multi-memory proposal).
+const EXPLICIT_MEMIDX: u32 = 0x40;
+
+/// The immediate of a load or store instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemArg {
+    /// Alignment as a power of two.
+    align: u32,
+    offset: u64,
+    /// The memory index, when it was encoded explicitly.
+    mem: Option<MemIdx>,
+}
+
+impl MemArg {
+    pub fn new(align: u32, offset: u64, mem: Option<MemIdx>) -> MemArg {
+        MemArg { align, offset, mem }
+    }
+
+    pub fn align(&self) -> u32 {
+        self.align
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The memory the instruction accesses; memory 0 unless given
+    /// explicitly.
+    pub fn memidx(&self) -> MemIdx {
+        self.mem.unwrap_or(MemIdx(0))
+    }
+
+    pub fn has_explicit_memidx(&self) -> bool {
+        self.mem.is_some()
+    }
+}
+
+impl Parseable for MemArg {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut align = u32::from(Leb128::<u32>::parse(reader)?);
+        let mut mem: Option<MemIdx> = None;
+        if align & EXPLICIT_MEMIDX != 0 {
+            align &= !EXPLICIT_MEMIDX;
+            mem = Some(MemIdx::parse(reader)?);
+        }
+        if align >= EXPLICIT_MEMIDX {
+            return Err(ParseError::Other(format!(
+                "malformed memop flags: {:#x}",
+                align
+            )));
+        }
+        // Offsets are u64 so that memory64 accesses can address the whole
+        // memory; validation restricts them to u32 for 32-bit memories.
+        let offset = u64::from(Leb128::<u64>::parse(reader)?);
+
+        Ok(MemArg { align, offset, mem })
+    }
+}
+
+impl Encode for MemArg {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self.mem {
+            Some(mem) => {
+                Leb128::from(self.align | EXPLICIT_MEMIDX).encode(writer)?;
+                mem.encode(writer)?;
+            }
+            None => Leb128::from(self.align).encode(writer)?,
+        }
+        Leb128::from(self.offset).encode(writer)
+    }
+}
+
+impl Display for MemArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(mem) = self.mem {
+            write!(f, "{} ", mem.0)?;
+        }
+        if self.offset != 0 {
+            write!(f, "offset={} ", self.offset)?;
+        }
+        write!(f, "align={}", 1u64 << self.align)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_mem_arg() {
+        let bytes: [u8; 2] = [0x02, 0x10];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = MemArg::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val, MemArg::new(2, 16, None));
+        assert_eq!(val.memidx(), MemIdx(0));
+
+        // Bit 6 set: memory index 3 follows the alignment
+        let bytes: [u8; 3] = [0x42, 0x03, 0x10];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = MemArg::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val.align(), 2);
+        assert_eq!(val.memidx(), MemIdx(3));
+        assert!(val.has_explicit_memidx());
+        assert_eq!(val.offset(), 16);
+
+        // An explicit memory 0 is still recorded as explicit
+        let bytes: [u8; 3] = [0x40, 0x00, 0x00];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = MemArg::parse(&mut reader).expect("The parsed value");
+        assert!(val.has_explicit_memidx());
+        assert_eq!(val.memidx(), MemIdx(0));
+
+        // memory64 offsets beyond u32
+        let bytes: [u8; 6] = [0x03, 0x80, 0x80, 0x80, 0x80, 0x10];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = MemArg::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val.offset(), 1 << 32);
+
+        let bytes: [u8; 2] = [0x80, 0x01];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        assert!(MemArg::parse(&mut reader).is_err());
+    }
+}
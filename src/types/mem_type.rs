@@ -1,13 +1,49 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::Encode;
 use crate::parseable::{Parseable, Result};
 use crate::types::limits::Limits;
+use crate::types::num_type::NumType;
 
+/// Size of a WebAssembly page in bytes.
+pub const PAGE_SIZE: u64 = 65536;
+
+#[derive(Debug, PartialEq)]
 pub struct MemType {
     lim: Limits,
 }
 
+impl MemType {
+    pub fn new(lim: Limits) -> MemType {
+        MemType { lim }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.lim
+    }
+
+    pub fn is_64(&self) -> bool {
+        self.lim.is_64()
+    }
+
+    /// The type of addresses used by loads, stores, `memory.size` and
+    /// `memory.grow` on this memory.
+    pub fn index_type(&self) -> NumType {
+        if self.lim.is_64() {
+            NumType::I64
+        } else {
+            NumType::I32
+        }
+    }
+
+    /// The largest number of pages the memory may ever have, as allowed by
+    /// its index type.
+    pub fn max_pages(&self) -> u64 {
+        if self.lim.is_64() { 1 << 48 } else { 1 << 16 }
+    }
+}
+
 impl Parseable for MemType {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
@@ -19,6 +55,12 @@ impl Parseable for MemType {
     }
 }
 
+impl Encode for MemType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.lim.encode(writer)
+    }
+}
+
 impl Display for MemType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "memtype: {}", self.lim)
@@ -1,14 +1,24 @@
+pub mod block_type;
+pub mod catch;
+pub mod comp_type;
+pub mod export_desc;
+pub mod field_type;
 pub mod func_type;
 pub mod global_type;
+pub mod heap_type;
 pub mod import_desc;
 pub mod leb128;
 pub mod limits;
+pub mod mem_arg;
 pub mod mem_type;
 pub mod r#mut;
 pub mod num_type;
 pub mod primitives;
+pub mod rec_type;
 pub mod ref_type;
 pub mod result_type;
+pub mod sub_type;
 pub mod table_type;
+pub mod tag_type;
 pub mod val_type;
 pub mod vec_type;
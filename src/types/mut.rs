@@ -1,7 +1,9 @@
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::Encode;
 use crate::parseable::{Asked, ParseError, Parseable, Received, Result};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mut {
     Val(u8),
 }
@@ -26,3 +28,11 @@ impl Parseable for Mut {
         }
     }
 }
+
+impl Encode for Mut {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            Mut::Val(byte) => writer.write_all(&[*byte]),
+        }
+    }
+}
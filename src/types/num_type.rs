@@ -14,7 +14,7 @@ impl Display for NumType {
             NumType::I32 => write!(f, "i32"),
             NumType::I64 => write!(f, "i64"),
             NumType::F32 => write!(f, "f32"),
-            NumType::F64 => write!(f, "F64"),
+            NumType::F64 => write!(f, "f64"),
         }
     }
 }
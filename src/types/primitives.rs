@@ -1,7 +1,8 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
-use crate::parseable::{Asked, ParseError, Parseable, Received, Result};
+use crate::encode::{Encode, encode_len};
+use crate::parseable::{Asked, ParseError, Parseable, ParserLimits, Received, Result};
 use crate::types::leb128::Leb128;
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
@@ -24,7 +25,7 @@ impl Parseable for Size {
     where
         Self: Sized,
     {
-        Ok(Size(u32::parse(reader)?))
+        Ok(Size(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
 
@@ -46,10 +47,14 @@ pub struct DataIdx(pub u32);
 pub struct LocalIdx(pub u32);
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct LabelIdx(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TagIdx(pub u32);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FieldIdx(pub u32);
 
-impl Into<u32> for TypeIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<TypeIdx> for u32 {
+    fn from(value: TypeIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for TypeIdx {
@@ -57,12 +62,12 @@ impl Parseable for TypeIdx {
     where
         Self: Sized,
     {
-        Ok(TypeIdx(u32::parse(reader)?))
+        Ok(TypeIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for FuncIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<FuncIdx> for u32 {
+    fn from(value: FuncIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for FuncIdx {
@@ -70,12 +75,12 @@ impl Parseable for FuncIdx {
     where
         Self: Sized,
     {
-        Ok(FuncIdx(u32::parse(reader)?))
+        Ok(FuncIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for TableIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<TableIdx> for u32 {
+    fn from(value: TableIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for TableIdx {
@@ -83,12 +88,12 @@ impl Parseable for TableIdx {
     where
         Self: Sized,
     {
-        Ok(TableIdx(u32::parse(reader)?))
+        Ok(TableIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for MemIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<MemIdx> for u32 {
+    fn from(value: MemIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for MemIdx {
@@ -96,12 +101,12 @@ impl Parseable for MemIdx {
     where
         Self: Sized,
     {
-        Ok(MemIdx(u32::parse(reader)?))
+        Ok(MemIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for GlobalIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<GlobalIdx> for u32 {
+    fn from(value: GlobalIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for GlobalIdx {
@@ -109,12 +114,12 @@ impl Parseable for GlobalIdx {
     where
         Self: Sized,
     {
-        Ok(GlobalIdx(u32::parse(reader)?))
+        Ok(GlobalIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for ElemIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<ElemIdx> for u32 {
+    fn from(value: ElemIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for ElemIdx {
@@ -122,12 +127,12 @@ impl Parseable for ElemIdx {
     where
         Self: Sized,
     {
-        Ok(ElemIdx(u32::parse(reader)?))
+        Ok(ElemIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for DataIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<DataIdx> for u32 {
+    fn from(value: DataIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for DataIdx {
@@ -135,12 +140,12 @@ impl Parseable for DataIdx {
     where
         Self: Sized,
     {
-        Ok(DataIdx(u32::parse(reader)?))
+        Ok(DataIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for LocalIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<LocalIdx> for u32 {
+    fn from(value: LocalIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for LocalIdx {
@@ -148,12 +153,12 @@ impl Parseable for LocalIdx {
     where
         Self: Sized,
     {
-        Ok(LocalIdx(u32::parse(reader)?))
+        Ok(LocalIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
-impl Into<u32> for LabelIdx {
-    fn into(self) -> u32 {
-        self.0
+impl From<LabelIdx> for u32 {
+    fn from(value: LabelIdx) -> Self {
+        value.0
     }
 }
 impl Parseable for LabelIdx {
@@ -161,13 +166,56 @@ impl Parseable for LabelIdx {
     where
         Self: Sized,
     {
-        Ok(LabelIdx(u32::parse(reader)?))
+        Ok(LabelIdx(u32::from(Leb128::<u32>::parse(reader)?)))
+    }
+}
+impl From<TagIdx> for u32 {
+    fn from(value: TagIdx) -> Self {
+        value.0
+    }
+}
+impl Parseable for TagIdx {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(TagIdx(u32::from(Leb128::<u32>::parse(reader)?)))
+    }
+}
+impl From<FieldIdx> for u32 {
+    fn from(value: FieldIdx) -> Self {
+        value.0
+    }
+}
+impl Parseable for FieldIdx {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(FieldIdx(u32::from(Leb128::<u32>::parse(reader)?)))
     }
 }
 
+// Indices are all written as a plain u32
+macro_rules! encode_idx {
+    ($($name:ident),*) => {
+        $(impl Encode for $name {
+            fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+                Leb128::from(self.0).encode(writer)
+            }
+        })*
+    };
+}
+
+encode_idx!(
+    Size, TypeIdx, FuncIdx, TableIdx, MemIdx, GlobalIdx, ElemIdx, DataIdx, LocalIdx, LabelIdx,
+    TagIdx, FieldIdx
+);
+
 impl<T: Parseable> Parseable for Vec<T> {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Vec<T>> {
         let num = u32::from(Leb128::<u32>::parse(reader)?);
+        ParserLimits::current().check_vec_len(num)?;
         let mut vec: Vec<T> = Vec::new();
         for _ in 0..num {
             let elem: T = T::parse(reader)?;
@@ -177,21 +225,53 @@ impl<T: Parseable> Parseable for Vec<T> {
     }
 }
 
+/// Parses a vector of bytes. Unlike other vectors, its length is only
+/// bounded by the input: a data segment may be as large as the module.
+pub(crate) fn parse_bytes(reader: &mut BufReader<dyn Read>) -> Result<Vec<u8>> {
+    let len = u32::from(Leb128::<u32>::parse(reader)?);
+    ParserLimits::current().check_module_size(u64::from(len))?;
+    // The buffer grows as bytes arrive, so a length the input can't back
+    // costs nothing
+    let mut bytes = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(ParseError::Other(format!(
+            "length out of bounds: {} bytes, but only {} left",
+            len,
+            bytes.len()
+        )));
+    }
+    Ok(bytes)
+}
+
 impl Parseable for String {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<String> {
-        let vec = Vec::<u8>::parse(reader)?;
+        let vec = parse_bytes(reader)?;
         let string = String::from_utf8(vec)?;
         Ok(string)
     }
 }
 
+// Reads until `buf` is full or the input ends, returning how many bytes
+// were read. A single `read` may stop short at the end of the buffer.
+fn read_full(reader: &mut BufReader<dyn Read>, buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..])? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    Ok(n)
+}
+
 impl Parseable for u8 {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
         Self: Sized,
     {
         let mut buf: [u8; 1] = [0; 1];
-        let n = reader.read(&mut buf[..]).unwrap();
+        let n = read_full(reader, &mut buf)?;
         match n {
             1 => Ok(u8::from_le_bytes(buf)),
             n => Err(ParseError::wrong_num_bytes_read(Asked(1), Received(n))),
@@ -202,7 +282,7 @@ impl Parseable for u8 {
 impl Parseable for u32 {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<u32> {
         let mut buf: [u8; 4] = [0; 4];
-        let n = reader.read(&mut buf[..]).unwrap();
+        let n = read_full(reader, &mut buf)?;
         match n {
             4 => Ok(u32::from_le_bytes(buf)),
             n => Err(ParseError::wrong_num_bytes_read(Asked(4), Received(n))),
@@ -210,6 +290,41 @@ impl Parseable for u32 {
     }
 }
 
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_len(self.len(), writer)?;
+        for elem in self {
+            elem.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.as_slice().encode(writer)
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        encode_len(self.len(), writer)?;
+        writer.write_all(self.as_bytes())
+    }
+}
+
+impl Encode for u8 {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        writer.write_all(&[*self])
+    }
+}
+
+impl Encode for u32 {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -256,7 +371,7 @@ mod tests {
         let result = Vec::parse(&mut reader);
         assert!(result.is_ok());
         let val = result.expect("The parsed value");
-        let nums: Vec<u32> = val.iter().map(|v| u32::from(v)).collect();
+        let nums: Vec<u32> = val.iter().map(u32::from).collect();
         assert_eq!(nums, vec!(1, 2, 3));
     }
 
@@ -0,0 +1,111 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::sub_type::SubType;
+
+/// A recursive type group. Types in the same group may refer to each other;
+/// a type outside of any `rec` forms a group of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecType(Vec<SubType>);
+
+impl RecType {
+    pub fn new(types: Vec<SubType>) -> RecType {
+        RecType(types)
+    }
+
+    pub fn types(&self) -> &[SubType] {
+        &self.0
+    }
+}
+
+impl Parseable for RecType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let first = match reader.fill_buf()?.first() {
+            Some(byte) => *byte,
+            None => return Err(ParseError::Other("unexpected end".to_string())),
+        };
+        if first == 0x4E {
+            reader.consume(1);
+            return Ok(RecType(Vec::<SubType>::parse(reader)?));
+        }
+        Ok(RecType(vec![SubType::parse(reader)?]))
+    }
+}
+
+impl Encode for RecType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        // A group of one is written as just its type
+        match self.0.as_slice() {
+            [ty] => ty.encode(writer),
+            types => {
+                writer.write_all(&[0x4E])?;
+                types.encode(writer)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::comp_type::CompType;
+    use crate::types::field_type::{FieldType, StorageType};
+    use crate::types::r#mut::{CONST, VAR};
+    use crate::types::primitives::TypeIdx;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_rec_type() {
+        // A plain function type forms its own group
+        let bytes: [u8; 3] = [0x60, 0x00, 0x00];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let rec = RecType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(rec.types().len(), 1);
+        assert!(rec.types()[0].is_final());
+        assert!(matches!(rec.types()[0].comp_type(), CompType::Func(_)));
+
+        // rec (sub (struct (field (mut i8)) (field (ref null 1))))
+        //     (sub final 0 (array i16))
+        let bytes: [u8; 16] = [
+            0x4e, 0x02, 0x50, 0x00, 0x5f, 0x02, 0x78, 0x01, 0x63, 0x01, 0x00, 0x4f, 0x01, 0x00,
+            0x5e, 0x77,
+        ];
+        let mut reader = BufReader::new(Cursor::new(bytes.to_vec()));
+        let result = RecType::parse(&mut reader);
+        // The array field is missing its mutability byte
+        assert!(result.is_err());
+
+        let mut bytes = bytes.to_vec();
+        bytes.push(0x00);
+        let mut reader = BufReader::new(Cursor::new(bytes.clone()));
+        let rec = RecType::parse(&mut reader).expect("The parsed value");
+        let mut encoded = Vec::new();
+        rec.encode(&mut encoded).expect("The encoded value");
+        assert_eq!(encoded, bytes);
+        let types = rec.types();
+        assert_eq!(types.len(), 2);
+
+        assert!(!types[0].is_final());
+        assert!(types[0].supertypes().is_empty());
+        match types[0].comp_type() {
+            CompType::Struct(fields) => {
+                assert_eq!(fields.len(), 2);
+                assert_eq!(fields[0], FieldType::new(StorageType::I8, VAR));
+                assert!(!fields[1].is_mutable());
+                assert_eq!(fields[1].to_string(), "(ref null 1)");
+            }
+            _ => panic!("Expected a struct type"),
+        }
+
+        assert!(types[1].is_final());
+        assert_eq!(types[1].supertypes(), &[TypeIdx(0)]);
+        assert_eq!(
+            types[1].comp_type(),
+            &CompType::Array(FieldType::new(StorageType::I16, CONST))
+        );
+    }
+}
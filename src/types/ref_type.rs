@@ -1,12 +1,80 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::Encode;
 use crate::parseable::{Asked, ParseError, Parseable, Received, Result};
+use crate::types::heap_type::HeapType;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// A reference type. The shorthand forms `funcref`, `externref` and
+/// `exnref` have their own variants. Types compare equal whichever way
+/// they were written, so e.g. `(ref null func)` and `funcref` are the same
+/// type, but each encodes the way it was parsed.
+#[derive(Debug, Copy, Clone)]
 pub enum RefType {
     Func,
     Extern,
+    /// A caught exception, from the exception handling proposal.
+    Exn,
+    /// `(ref null? ht)` from the typed function references proposal.
+    Typed {
+        nullable: bool,
+        heap: HeapType,
+        /// Whether a nullable abstract type is written as its shorthand,
+        /// e.g. `anyref` rather than `(ref null any)`.
+        shorthand: bool,
+    },
+}
+
+impl RefType {
+    /// The type, in its shorthand form if it has one.
+    pub fn new(nullable: bool, heap: HeapType) -> RefType {
+        match (nullable, heap) {
+            (true, HeapType::Func) => RefType::Func,
+            (true, HeapType::Extern) => RefType::Extern,
+            (true, HeapType::Exn) => RefType::Exn,
+            (nullable, heap) => RefType::Typed {
+                nullable,
+                heap,
+                shorthand: nullable && heap.abstract_byte().is_some(),
+            },
+        }
+    }
+
+    /// The type written out as `(ref null? ht)`, even if it has a
+    /// shorthand.
+    pub fn long_form(nullable: bool, heap: HeapType) -> RefType {
+        RefType::Typed {
+            nullable,
+            heap,
+            shorthand: false,
+        }
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        match self {
+            RefType::Typed { nullable, .. } => *nullable,
+            _ => true,
+        }
+    }
+
+    pub fn heap_type(&self) -> HeapType {
+        match self {
+            RefType::Func => HeapType::Func,
+            RefType::Extern => HeapType::Extern,
+            RefType::Exn => HeapType::Exn,
+            RefType::Typed { heap, .. } => *heap,
+        }
+    }
+
+    /// The same reference type, but admitting null.
+    pub fn as_nullable(&self) -> RefType {
+        RefType::new(true, self.heap_type())
+    }
+
+    /// The same reference type, but excluding null.
+    pub fn as_non_nullable(&self) -> RefType {
+        RefType::new(false, self.heap_type())
+    }
 }
 
 impl Parseable for RefType {
@@ -20,18 +88,80 @@ impl Parseable for RefType {
             1 => match u8::from_le_bytes(buf) {
                 0x70 => Ok(RefType::Func),
                 0x6f => Ok(RefType::Extern),
-                _ => Err(ParseError::new("Value is not RefType".to_string())),
+                0x69 => Ok(RefType::Exn),
+                0x63 => Ok(RefType::long_form(true, HeapType::parse(reader)?)),
+                0x64 => Ok(RefType::long_form(false, HeapType::parse(reader)?)),
+                byte => match HeapType::from_abstract_byte(byte) {
+                    // Shorthands such as anyref for (ref null any)
+                    Some(heap) => Ok(RefType::new(true, heap)),
+                    None => Err(ParseError::new("Value is not RefType".to_string())),
+                },
             },
             n => Err(ParseError::wrong_num_bytes_read(Asked(1), Received(n))),
         }
     }
 }
 
+impl Encode for RefType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            RefType::Func => writer.write_all(&[0x70]),
+            RefType::Extern => writer.write_all(&[0x6f]),
+            RefType::Exn => writer.write_all(&[0x69]),
+            RefType::Typed {
+                nullable,
+                heap,
+                shorthand,
+            } => match (nullable, heap.abstract_byte()) {
+                // The shorthand, e.g. anyref for (ref null any)
+                (true, Some(byte)) if *shorthand => writer.write_all(&[byte]),
+                (true, _) => {
+                    writer.write_all(&[0x63])?;
+                    heap.encode(writer)
+                }
+                (false, _) => {
+                    writer.write_all(&[0x64])?;
+                    heap.encode(writer)
+                }
+            },
+        }
+    }
+}
+
+impl PartialEq for RefType {
+    fn eq(&self, other: &RefType) -> bool {
+        self.is_nullable() == other.is_nullable() && self.heap_type() == other.heap_type()
+    }
+}
+
 impl Display for RefType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RefType::Func => write!(f, "func"),
-            RefType::Extern => write!(f, "extern"),
+            RefType::Func => write!(f, "funcref"),
+            RefType::Extern => write!(f, "externref"),
+            RefType::Exn => write!(f, "exnref"),
+            RefType::Typed {
+                nullable: true,
+                heap,
+                shorthand: false,
+            } => write!(f, "(ref null {})", heap),
+            RefType::Typed {
+                nullable: true,
+                heap,
+                ..
+            } => match heap {
+                HeapType::Concrete(_) => write!(f, "(ref null {})", heap),
+                HeapType::None => write!(f, "nullref"),
+                HeapType::NoFunc => write!(f, "nullfuncref"),
+                HeapType::NoExtern => write!(f, "nullexternref"),
+                HeapType::NoExn => write!(f, "nullexnref"),
+                _ => write!(f, "{}ref", heap),
+            },
+            RefType::Typed {
+                nullable: false,
+                heap,
+                ..
+            } => write!(f, "(ref {})", heap),
         }
     }
 }
@@ -42,10 +172,11 @@ mod tests {
     use std::io::Cursor;
 
     use crate::parseable::ParseError;
+    use crate::types::primitives::TypeIdx;
 
     #[test]
     fn test_reftype() {
-        let bytes: [u8; 3] = [0x70, 0x6f, 0x6e];
+        let bytes: [u8; 3] = [0x70, 0x6f, 0x7f];
         let mut reader = BufReader::new(Cursor::new(bytes));
         let result = RefType::parse(&mut reader);
         assert!(result.is_ok());
@@ -62,4 +193,45 @@ mod tests {
         let result = result.expect_err("A parse error");
         assert_eq!(result, ParseError::new("Value is not RefType".to_string()));
     }
+
+    #[test]
+    fn test_typed_reftype() {
+        // (ref null 2), (ref func), (ref null func)
+        let bytes: [u8; 6] = [0x63, 0x02, 0x64, 0x70, 0x63, 0x70];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+
+        let result = RefType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(result, RefType::new(true, HeapType::Concrete(TypeIdx(2))));
+        assert_eq!(result.to_string(), "(ref null 2)");
+
+        let result = RefType::parse(&mut reader).expect("The parsed value");
+        assert!(!result.is_nullable());
+        assert_eq!(result.heap_type(), HeapType::Func);
+        assert_eq!(result.as_nullable(), RefType::Func);
+
+        // The long form of funcref is the same type as the shorthand, but
+        // is written back the way it was
+        let result = RefType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(result, RefType::Func);
+        assert_eq!(result.to_string(), "(ref null func)");
+        let mut bytes = Vec::new();
+        result.encode(&mut bytes).unwrap();
+        assert_eq!(bytes, [0x63, 0x70]);
+    }
+
+    #[test]
+    fn test_gc_reftype() {
+        let bytes: [u8; 4] = [0x6e, 0x71, 0x64, 0x6c];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+
+        let result = RefType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(result, RefType::new(true, HeapType::Any));
+        assert_eq!(result.to_string(), "anyref");
+
+        let result = RefType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(result.to_string(), "nullref");
+
+        let result = RefType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(result.to_string(), "(ref i31)");
+    }
 }
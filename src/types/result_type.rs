@@ -1,5 +1,6 @@
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::Encode;
 use crate::parseable::{Parseable, Result};
 use crate::types::val_type::ValType;
 
@@ -16,6 +17,12 @@ impl ResultType {
     }
 }
 
+impl From<Vec<ValType>> for ResultType {
+    fn from(value: Vec<ValType>) -> Self {
+        ResultType(value)
+    }
+}
+
 impl From<ResultType> for Vec<ValType> {
     fn from(value: ResultType) -> Self {
         value.0
@@ -48,3 +55,9 @@ impl Parseable for ResultType {
         Ok(ResultType(Vec::<ValType>::parse(reader)?))
     }
 }
+
+impl Encode for ResultType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.0.encode(writer)
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::comp_type::CompType;
+use crate::types::primitives::TypeIdx;
+
+/// A type definition with its declared supertypes. A composite type written
+/// on its own is final and has no supertypes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubType {
+    is_final: bool,
+    supertypes: Vec<TypeIdx>,
+    comp: CompType,
+}
+
+impl SubType {
+    pub fn new(is_final: bool, supertypes: Vec<TypeIdx>, comp: CompType) -> SubType {
+        SubType {
+            is_final,
+            supertypes,
+            comp,
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.is_final
+    }
+
+    pub fn supertypes(&self) -> &[TypeIdx] {
+        &self.supertypes
+    }
+
+    pub fn comp_type(&self) -> &CompType {
+        &self.comp
+    }
+}
+
+impl From<CompType> for SubType {
+    fn from(value: CompType) -> Self {
+        SubType::new(true, Vec::new(), value)
+    }
+}
+
+impl Parseable for SubType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let first = match reader.fill_buf()?.first() {
+            Some(byte) => *byte,
+            None => return Err(ParseError::Other("unexpected end".to_string())),
+        };
+        let is_final = match first {
+            0x50 => false,
+            0x4F => true,
+            _ => return Ok(SubType::from(CompType::parse(reader)?)),
+        };
+        reader.consume(1);
+        let supertypes = Vec::<TypeIdx>::parse(reader)?;
+        let comp = CompType::parse(reader)?;
+        Ok(SubType {
+            is_final,
+            supertypes,
+            comp,
+        })
+    }
+}
+
+impl Encode for SubType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        if self.is_final && self.supertypes.is_empty() {
+            return self.comp.encode(writer);
+        }
+        writer.write_all(&[if self.is_final { 0x4F } else { 0x50 }])?;
+        self.supertypes.encode(writer)?;
+        self.comp.encode(writer)
+    }
+}
+
+impl Display for SubType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.is_final || !self.supertypes.is_empty() {
+            write!(f, "* sub")?;
+            if self.is_final {
+                write!(f, " final")?;
+            }
+            for supertype in &self.supertypes {
+                write!(f, " {}", supertype.0)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "{}", self.comp)
+    }
+}
@@ -1,14 +1,31 @@
-use std::io::{BufReader, Read};
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::Encode;
 use crate::parseable::{Parseable, Result};
 use crate::types::limits::Limits;
 use crate::types::ref_type::RefType;
 
+#[derive(Debug, PartialEq)]
 pub struct TableType {
     et: RefType,
     lim: Limits,
 }
 
+impl TableType {
+    pub fn new(et: RefType, lim: Limits) -> TableType {
+        TableType { et, lim }
+    }
+
+    pub fn elem_type(&self) -> RefType {
+        self.et
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.lim
+    }
+}
+
 impl Parseable for TableType {
     fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
     where
@@ -20,3 +37,16 @@ impl Parseable for TableType {
         Ok(TableType { et, lim })
     }
 }
+
+impl Encode for TableType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.et.encode(writer)?;
+        self.lim.encode(writer)
+    }
+}
+
+impl Display for TableType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tabletype: {}, {}", self.et, self.lim)
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read, Write};
+
+use crate::encode::Encode;
+use crate::parseable::{ParseError, Parseable, Result};
+use crate::types::primitives::TypeIdx;
+
+/// The type of an exception tag. Its function type gives the types of the
+/// exception's payload as parameters; the results must be empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagType {
+    ty: TypeIdx,
+}
+
+impl TagType {
+    pub fn new(ty: TypeIdx) -> TagType {
+        TagType { ty }
+    }
+
+    pub fn type_idx(&self) -> TypeIdx {
+        self.ty
+    }
+}
+
+impl Parseable for TagType {
+    fn parse(reader: &mut BufReader<dyn Read>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        // The attribute byte is reserved; 0 means "exception".
+        let attribute = u8::parse(reader)?;
+        if attribute != 0 {
+            return Err(ParseError::Other(format!(
+                "invalid tag attribute: {:#04x}",
+                attribute
+            )));
+        }
+        let ty = TypeIdx::parse(reader)?;
+
+        Ok(TagType { ty })
+    }
+}
+
+impl Encode for TagType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        writer.write_all(&[0x00])?;
+        self.ty.encode(writer)
+    }
+}
+
+impl Display for TagType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tagtype: type {}", self.ty.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tag_type() {
+        let bytes: [u8; 4] = [0x00, 0x02, 0x01, 0x02];
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        let val = TagType::parse(&mut reader).expect("The parsed value");
+        assert_eq!(val, TagType::new(TypeIdx(2)));
+
+        let result = TagType::parse(&mut reader);
+        assert_eq!(
+            result,
+            Err(ParseError::Other("invalid tag attribute: 0x01".to_string()))
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use std::fmt::Display;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 
+use crate::encode::Encode;
 use crate::parseable::{Asked, ParseError, Parseable, Received, Result};
+use crate::types::heap_type::HeapType;
 use crate::types::num_type::NumType;
 use crate::types::ref_type::RefType;
 use crate::types::vec_type::VecType;
@@ -13,6 +15,17 @@ pub enum ValType {
     Ref(RefType),
 }
 
+impl ValType {
+    /// The name of the type in the text format, e.g. `i32` or `funcref`.
+    pub fn name(&self) -> String {
+        match self {
+            ValType::Num(t) => t.to_string(),
+            ValType::Vec(t) => t.to_string(),
+            ValType::Ref(t) => t.to_string(),
+        }
+    }
+}
+
 impl Display for ValType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -39,13 +52,38 @@ impl Parseable for ValType {
                 0x7b => Ok(ValType::Vec(VecType::V128)),
                 0x70 => Ok(ValType::Ref(RefType::Func)),
                 0x6f => Ok(ValType::Ref(RefType::Extern)),
-                _ => Err(ParseError::Other("Value is not ValType".to_string())),
+                0x69 => Ok(ValType::Ref(RefType::Exn)),
+                0x63 => Ok(ValType::Ref(RefType::long_form(
+                    true,
+                    HeapType::parse(reader)?,
+                ))),
+                0x64 => Ok(ValType::Ref(RefType::long_form(
+                    false,
+                    HeapType::parse(reader)?,
+                ))),
+                byte => match HeapType::from_abstract_byte(byte) {
+                    Some(heap) => Ok(ValType::Ref(RefType::new(true, heap))),
+                    None => Err(ParseError::Other("Value is not ValType".to_string())),
+                },
             },
             n => Err(ParseError::wrong_num_bytes_read(Asked(1), Received(n))),
         }
     }
 }
 
+impl Encode for ValType {
+    fn encode(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            ValType::Num(NumType::I32) => writer.write_all(&[0x7f]),
+            ValType::Num(NumType::I64) => writer.write_all(&[0x7e]),
+            ValType::Num(NumType::F32) => writer.write_all(&[0x7d]),
+            ValType::Num(NumType::F64) => writer.write_all(&[0x7c]),
+            ValType::Vec(VecType::V128) => writer.write_all(&[0x7b]),
+            ValType::Ref(rt) => rt.encode(writer),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
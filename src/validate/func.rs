@@ -0,0 +1,1438 @@
+use std::collections::HashSet;
+
+use crate::instr::Instr;
+use crate::instr::atomic::AtomicOp;
+use crate::instr::numeric::{LoadOp, NumericInstr, StoreOp};
+use crate::instr::simd::{SimdImm, SimdOp};
+use crate::section::code::Code;
+use crate::types::block_type::BlockType;
+use crate::types::comp_type::CompType;
+use crate::types::field_type::{FieldType, StorageType};
+use crate::types::func_type::FuncType;
+use crate::types::heap_type::HeapType;
+use crate::types::mem_arg::MemArg;
+use crate::types::num_type::NumType;
+use crate::types::primitives::{FuncIdx, LabelIdx, LocalIdx, TypeIdx};
+use crate::types::ref_type::RefType;
+use crate::types::val_type::ValType;
+use crate::types::vec_type::VecType;
+use crate::validate::{IndexSpace, Result, ValidatedModule, ValidationError, check_idx};
+
+const I32: ValType = ValType::Num(NumType::I32);
+const I64: ValType = ValType::Num(NumType::I64);
+const F32: ValType = ValType::Num(NumType::F32);
+const F64: ValType = ValType::Num(NumType::F64);
+const V128: ValType = ValType::Vec(VecType::V128);
+
+/// The type of a value on the operand stack. `None` is the unknown type of
+/// values popped from the stack in unreachable code, which matches any type.
+pub type Operand = Option<ValType>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameKind {
+    Block,
+    Loop,
+    If,
+    Else,
+    Try,
+    Catch,
+    CatchAll,
+    TryTable,
+}
+
+struct Frame {
+    kind: FrameKind,
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+    /// The height of the operand stack when the block was entered.
+    height: usize,
+    /// The number of locals initialized when the block was entered.
+    inits: usize,
+    unreachable: bool,
+    /// The instruction that opened the block.
+    start: usize,
+}
+
+impl Frame {
+    /// The types a branch to this block must provide.
+    fn label_types(&self) -> &[ValType] {
+        if self.kind == FrameKind::Loop {
+            &self.params
+        } else {
+            &self.results
+        }
+    }
+}
+
+/// The parent of the bottom node, and the top of an empty stack.
+const ROOT: u32 = u32::MAX;
+
+/// What the type checker found on its stacks before each instruction of a
+/// function. Instructions share the part of the stacks below what they
+/// change, so each pushed operand and opened block is stored once.
+#[derive(Debug, Clone, Default)]
+pub struct FuncStacks {
+    heights: Vec<u32>,
+    /// Operands as (the operand below, type).
+    operands: Vec<(u32, Operand)>,
+    /// Blocks as (the enclosing block, the instruction that opened it, the
+    /// operand height below it).
+    blocks: Vec<(u32, u32, u32)>,
+    /// The top operand and innermost block before each instruction, or
+    /// `None` where the instruction can't be reached.
+    tops: Vec<Option<(u32, u32)>>,
+}
+
+impl FuncStacks {
+    /// The height of the operand stack before each instruction.
+    pub fn heights(&self) -> &[u32] {
+        &self.heights
+    }
+
+    /// The operand types before instruction `pc`, bottom first. `None` if
+    /// the instruction can't be reached.
+    pub fn operands(&self, pc: usize) -> Option<Vec<ValType>> {
+        let (mut node, _) = (*self.tops.get(pc)?)?;
+        let mut types = Vec::new();
+        while node != ROOT {
+            let (below, t) = self.operands[node as usize];
+            types.push(t?);
+            node = below;
+        }
+        types.reverse();
+        Some(types)
+    }
+
+    /// The blocks open before instruction `pc`, outermost first, as the
+    /// instruction that opened each and the operand height below it. `None`
+    /// if the instruction can't be reached.
+    pub fn blocks(&self, pc: usize) -> Option<Vec<(usize, usize)>> {
+        let (_, mut node) = (*self.tops.get(pc)?)?;
+        let mut blocks = Vec::new();
+        while node != ROOT {
+            let (outer, start, height) = self.blocks[node as usize];
+            blocks.push((start as usize, height as usize));
+            node = outer;
+        }
+        blocks.reverse();
+        Some(blocks)
+    }
+}
+
+fn is_defaultable(t: &ValType) -> bool {
+    match t {
+        ValType::Ref(rt) => rt.is_nullable(),
+        _ => true,
+    }
+}
+
+fn ref_type(nullable: bool, heap: HeapType) -> ValType {
+    ValType::Ref(RefType::new(nullable, heap))
+}
+
+/// Type checks a single function body with the validation algorithm from
+/// the appendix of the specification.
+pub(super) struct FuncValidator<'m, 'a> {
+    module: &'m ValidatedModule<'a>,
+    results: Vec<ValType>,
+    /// Runs of locals as (index after the run, type), parameters first.
+    locals: Vec<(u32, ValType)>,
+    /// Non-defaultable locals that have been set, in the order they were.
+    inits: Vec<u32>,
+    initialized: HashSet<u32>,
+    operands: Vec<Operand>,
+    frames: Vec<Frame>,
+    /// The instruction being checked.
+    pc: usize,
+    /// The lowest the operand and control stacks got during the instruction.
+    low: usize,
+    low_frames: usize,
+    /// The number of frames in unreachable code.
+    dead: usize,
+}
+
+impl<'m, 'a> FuncValidator<'m, 'a> {
+    pub(super) fn new(module: &'m ValidatedModule<'a>, ty: &FuncType, code: &Code) -> Self {
+        let mut locals = Vec::new();
+        let mut end = 0;
+        for t in ty.params() {
+            end += 1;
+            locals.push((end, *t));
+        }
+        for l in code.locals() {
+            end += l.count();
+            locals.push((end, l.val_type()));
+        }
+        FuncValidator {
+            module,
+            results: ty.results().to_vec(),
+            locals,
+            inits: Vec::new(),
+            initialized: HashSet::new(),
+            operands: Vec::new(),
+            frames: vec![Frame {
+                kind: FrameKind::Block,
+                params: Vec::new(),
+                results: ty.results().to_vec(),
+                height: 0,
+                inits: 0,
+                unreachable: false,
+                start: 0,
+            }],
+            pc: 0,
+            low: 0,
+            low_frames: 0,
+            dead: 0,
+        }
+    }
+
+    /// Checks the body of function `func`, returning what is on the stacks
+    /// before each instruction.
+    pub(super) fn check(mut self, func: FuncIdx, code: &Code) -> Result<FuncStacks> {
+        let instrs = code.body().instrs();
+        let mut stacks = FuncStacks {
+            heights: Vec::with_capacity(instrs.len()),
+            tops: Vec::with_capacity(instrs.len()),
+            ..FuncStacks::default()
+        };
+        // The nodes of the operands and blocks on the stacks
+        let (mut operands, mut blocks) = (Vec::new(), Vec::new());
+        // The stack as it was before the current instruction, for errors
+        let mut stack = Vec::new();
+        for (i, instr) in instrs.iter().enumerate() {
+            self.record(&mut stacks, &mut operands, &mut blocks);
+            stack.clone_from(&self.operands);
+            (self.pc, self.low, self.low_frames) = (i, self.operands.len(), self.frames.len());
+            if let Err(error) = self.instr(instr) {
+                return Err(ValidationError::InFunc {
+                    func,
+                    offset: code.offsets().get(i).copied().unwrap_or_default(),
+                    stack,
+                    error: Box::new(error),
+                });
+            }
+        }
+        Ok(stacks)
+    }
+
+    /// Adds the stacks before the next instruction to `stacks`, given the
+    /// nodes of the stacks before the last one.
+    fn record(&self, stacks: &mut FuncStacks, operands: &mut Vec<u32>, blocks: &mut Vec<u32>) {
+        let top = |nodes: &[u32]| nodes.last().copied().unwrap_or(ROOT);
+        operands.truncate(self.low);
+        for t in &self.operands[operands.len()..] {
+            stacks.operands.push((top(operands), *t));
+            operands.push(stacks.operands.len() as u32 - 1);
+        }
+        // The function's own frame is not a block
+        blocks.truncate(self.low_frames.saturating_sub(1));
+        for frame in self.frames.iter().skip(1 + blocks.len()) {
+            let (start, height) = (frame.start as u32, frame.height as u32);
+            stacks.blocks.push((top(blocks), start, height));
+            blocks.push(stacks.blocks.len() as u32 - 1);
+        }
+        stacks.heights.push(self.operands.len() as u32);
+        stacks
+            .tops
+            .push((self.dead == 0).then(|| (top(operands), top(blocks))));
+    }
+
+    fn frame(&self) -> &Frame {
+        // The function's own frame is only popped by the final `end`
+        self.frames.last().expect("A control frame")
+    }
+
+    fn push(&mut self, t: ValType) {
+        self.operands.push(Some(t));
+    }
+
+    fn push_vals(&mut self, types: &[ValType]) {
+        self.operands.extend(types.iter().map(|t| Some(*t)));
+    }
+
+    fn pop(&mut self) -> Result<Operand> {
+        let frame = self.frame();
+        if self.operands.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return Err(ValidationError::StackUnderflow);
+        }
+        let t = self.operands.pop().flatten();
+        self.low = self.low.min(self.operands.len());
+        Ok(t)
+    }
+
+    fn pop_expect(&mut self, expected: ValType) -> Result<Operand> {
+        let actual = match self.pop() {
+            Err(ValidationError::StackUnderflow) => {
+                return Err(ValidationError::TypeMismatch {
+                    expected: vec![expected],
+                    actual: Vec::new(),
+                });
+            }
+            result => result?,
+        };
+        match actual {
+            Some(t) if !self.module.matches(&t, &expected) => Err(ValidationError::TypeMismatch {
+                expected: vec![expected],
+                actual: vec![t],
+            }),
+            _ => Ok(actual),
+        }
+    }
+
+    fn pop_vals(&mut self, types: &[ValType]) -> Result<Vec<Operand>> {
+        // The values of the current block that the types are checked against
+        let height = self.frame().height;
+        let start = self.operands.len().saturating_sub(types.len()).max(height);
+        let top: Vec<ValType> = self.operands[start..].iter().flatten().copied().collect();
+
+        let mut popped = Vec::with_capacity(types.len());
+        for t in types.iter().rev() {
+            match self.pop_expect(*t) {
+                Ok(opd) => popped.push(opd),
+                Err(ValidationError::TypeMismatch { .. }) => {
+                    return Err(ValidationError::TypeMismatch {
+                        expected: types.to_vec(),
+                        actual: top,
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        popped.reverse();
+        Ok(popped)
+    }
+
+    fn pop_ref(&mut self) -> Result<Option<RefType>> {
+        match self.pop()? {
+            Some(ValType::Ref(rt)) => Ok(Some(rt)),
+            Some(t) => Err(ValidationError::ExpectedReference(t)),
+            None => Ok(None),
+        }
+    }
+
+    fn push_ctrl(&mut self, kind: FrameKind, params: Vec<ValType>, results: Vec<ValType>) {
+        self.push_vals(&params);
+        self.frames.push(Frame {
+            kind,
+            height: self.operands.len() - params.len(),
+            inits: self.inits.len(),
+            params,
+            results,
+            unreachable: false,
+            start: self.pc,
+        });
+    }
+
+    /// Starts the next clause of the block opened at instruction `start`.
+    fn push_clause(
+        &mut self,
+        kind: FrameKind,
+        params: Vec<ValType>,
+        results: Vec<ValType>,
+        start: usize,
+    ) {
+        self.push_ctrl(kind, params, results);
+        self.frames.last_mut().expect("A control frame").start = start;
+    }
+
+    fn pop_ctrl(&mut self) -> Result<Frame> {
+        let results = self.frame().results.clone();
+        let height = self.frame().height;
+        let remaining: Vec<ValType> = self.operands[height..].iter().flatten().copied().collect();
+        self.pop_vals(&results)?;
+        if self.operands.len() != height {
+            return Err(ValidationError::TypeMismatch {
+                expected: results,
+                actual: remaining,
+            });
+        }
+        let frame = self.frames.pop().expect("A control frame");
+        self.low_frames = self.low_frames.min(self.frames.len());
+        if frame.unreachable {
+            self.dead -= 1;
+        }
+        // Locals set inside the block are unset again after it
+        for local in self.inits.drain(frame.inits..) {
+            self.initialized.remove(&local);
+        }
+        Ok(frame)
+    }
+
+    fn unreachable(&mut self) {
+        let frame = self.frames.last_mut().expect("A control frame");
+        self.operands.truncate(frame.height);
+        if !frame.unreachable {
+            frame.unreachable = true;
+            self.dead += 1;
+        }
+        self.low = self.low.min(self.operands.len());
+    }
+
+    fn label(&self, l: LabelIdx) -> Result<&Frame> {
+        check_idx(IndexSpace::Label, l.0, self.frames.len())?;
+        Ok(&self.frames[self.frames.len() - 1 - l.0 as usize])
+    }
+
+    fn label_types(&self, l: LabelIdx) -> Result<Vec<ValType>> {
+        Ok(self.label(l)?.label_types().to_vec())
+    }
+
+    fn local(&self, idx: LocalIdx) -> Result<ValType> {
+        let run = self.locals.partition_point(|(end, _)| *end <= idx.0);
+        match self.locals.get(run) {
+            Some((_, t)) => Ok(*t),
+            None => Err(ValidationError::UnknownIndex(IndexSpace::Local, idx.0)),
+        }
+    }
+
+    fn set_local(&mut self, idx: LocalIdx, t: &ValType) {
+        if !is_defaultable(t) && self.initialized.insert(idx.0) {
+            self.inits.push(idx.0);
+        }
+    }
+
+    fn block_type(&self, bt: &BlockType) -> Result<(Vec<ValType>, Vec<ValType>)> {
+        match bt {
+            BlockType::Empty => Ok((Vec::new(), Vec::new())),
+            BlockType::Value(t) => {
+                self.module.check_val_type(t)?;
+                Ok((Vec::new(), vec![*t]))
+            }
+            BlockType::Type(idx) => {
+                let ty = self.module.check_func_type_idx(*idx)?;
+                Ok((ty.params().to_vec(), ty.results().to_vec()))
+            }
+        }
+    }
+
+    fn enter(&mut self, kind: FrameKind, bt: &BlockType) -> Result<()> {
+        let (params, results) = self.block_type(bt)?;
+        self.pop_vals(&params)?;
+        self.push_ctrl(kind, params, results);
+        Ok(())
+    }
+
+    fn types_match(&self, actual: &[ValType], expected: &[ValType]) -> Result<()> {
+        if actual.len() != expected.len()
+            || actual
+                .iter()
+                .zip(expected)
+                .any(|(a, e)| !self.module.matches(a, e))
+        {
+            return Err(ValidationError::TypeMismatch {
+                expected: expected.to_vec(),
+                actual: actual.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, ty: &FuncType) -> Result<()> {
+        self.pop_vals(ty.params())?;
+        self.push_vals(ty.results());
+        Ok(())
+    }
+
+    fn return_call(&mut self, ty: &FuncType) -> Result<()> {
+        self.types_match(ty.results(), &self.results.clone())?;
+        self.pop_vals(ty.params())?;
+        self.unreachable();
+        Ok(())
+    }
+
+    fn tag_params(&self, idx: crate::types::primitives::TagIdx) -> Result<Vec<ValType>> {
+        self.module.check_tag(idx)?;
+        match self.module.tag_type(idx) {
+            Some(ty) => Ok(ty.params().to_vec()),
+            None => Err(ValidationError::UnknownIndex(IndexSpace::Tag, idx.0)),
+        }
+    }
+
+    /// The top of the hierarchy a heap type belongs to.
+    fn top_heap_type(&self, heap: HeapType) -> HeapType {
+        match heap {
+            HeapType::Func | HeapType::NoFunc => HeapType::Func,
+            HeapType::Extern | HeapType::NoExtern => HeapType::Extern,
+            HeapType::Exn | HeapType::NoExn => HeapType::Exn,
+            HeapType::Concrete(idx) => match self.module.func_type_at(idx) {
+                Some(_) => HeapType::Func,
+                None => HeapType::Any,
+            },
+            _ => HeapType::Any,
+        }
+    }
+
+    fn struct_fields(&self, idx: TypeIdx) -> Result<&'a [FieldType]> {
+        self.module.check_type_idx(idx)?;
+        match self.module.sub_type(idx).map(|sub| sub.comp_type()) {
+            Some(CompType::Struct(fields)) => Ok(fields),
+            _ => Err(ValidationError::NotAStructType(idx)),
+        }
+    }
+
+    fn struct_field(
+        &self,
+        idx: TypeIdx,
+        field: crate::types::primitives::FieldIdx,
+    ) -> Result<FieldType> {
+        match self.struct_fields(idx)?.get(field.0 as usize) {
+            Some(field) => Ok(*field),
+            None => Err(ValidationError::UnknownIndex(IndexSpace::Field, field.0)),
+        }
+    }
+
+    fn array_field(&self, idx: TypeIdx) -> Result<FieldType> {
+        self.module.check_type_idx(idx)?;
+        match self.module.sub_type(idx).map(|sub| sub.comp_type()) {
+            Some(CompType::Array(field)) => Ok(*field),
+            _ => Err(ValidationError::NotAnArrayType(idx)),
+        }
+    }
+
+    fn mutable_array_field(&self, idx: TypeIdx) -> Result<FieldType> {
+        let field = self.array_field(idx)?;
+        if !field.is_mutable() {
+            return Err(ValidationError::ImmutableField);
+        }
+        Ok(field)
+    }
+
+    /// Element types of arrays initialized from data segments must be
+    /// numeric or vector types.
+    fn numeric_array_field(&self, idx: TypeIdx) -> Result<FieldType> {
+        let field = self.array_field(idx)?;
+        if let ValType::Ref(_) = field.storage_type().unpacked() {
+            return Err(ValidationError::NotANumericArray(idx));
+        }
+        Ok(field)
+    }
+
+    fn check_packing(field: &FieldType, packed: bool) -> Result<()> {
+        if field.storage_type().is_packed() != packed {
+            return Err(ValidationError::FieldPacking { packed: !packed });
+        }
+        Ok(())
+    }
+
+    fn check_elem_matches(&self, elem: RefType, expected: RefType) -> Result<()> {
+        self.types_match(&[ValType::Ref(elem)], &[ValType::Ref(expected)])
+    }
+
+    fn elem_type(&self, idx: crate::types::primitives::ElemIdx) -> Result<RefType> {
+        self.module.check_elem(idx)?;
+        Ok(self.module.elem_type(idx).expect("A checked elem segment"))
+    }
+
+    fn table_index_type(&self, idx: crate::types::primitives::TableIdx) -> Result<ValType> {
+        Ok(ValidatedModule::index_type(
+            self.module.check_table(idx)?.limits(),
+        ))
+    }
+
+    fn mem_index_type(&self, idx: crate::types::primitives::MemIdx) -> Result<ValType> {
+        Ok(ValType::Num(self.module.check_mem(idx)?.index_type()))
+    }
+
+    fn mem_arg(&self, memarg: &MemArg, size: u32, exact: bool) -> Result<ValType> {
+        let it = self.mem_index_type(memarg.memidx())?;
+        let align = 1u64.checked_shl(memarg.align()).unwrap_or(u64::MAX);
+        if align > u64::from(size) || (exact && align != u64::from(size)) {
+            return Err(ValidationError::InvalidAlignment {
+                align: memarg.align(),
+                size,
+            });
+        }
+        Ok(it)
+    }
+
+    /// The smaller of two index types, used for the length operand of
+    /// copies between a 32-bit and a 64-bit table or memory.
+    fn min_index_type(a: ValType, b: ValType) -> ValType {
+        if a == I32 || b == I32 { I32 } else { I64 }
+    }
+
+    fn instr(&mut self, instr: &Instr) -> Result<()> {
+        match instr {
+            Instr::Unreachable => self.unreachable(),
+            Instr::Nop => {}
+            Instr::Block(bt) => self.enter(FrameKind::Block, bt)?,
+            Instr::Loop(bt) => self.enter(FrameKind::Loop, bt)?,
+            Instr::If(bt) => {
+                self.pop_expect(I32)?;
+                self.enter(FrameKind::If, bt)?;
+            }
+            Instr::Else => {
+                let frame = self.pop_ctrl()?;
+                if frame.kind != FrameKind::If {
+                    return Err(ValidationError::UnexpectedInstr(instr.to_string()));
+                }
+                self.push_clause(FrameKind::Else, frame.params, frame.results, frame.start);
+            }
+            Instr::End => {
+                let frame = self.pop_ctrl()?;
+                // Without an else branch the parameters are passed through
+                if frame.kind == FrameKind::If {
+                    self.types_match(&frame.params, &frame.results)?;
+                }
+                if !self.frames.is_empty() {
+                    self.push_vals(&frame.results);
+                }
+            }
+            Instr::Br(l) => {
+                let types = self.label_types(*l)?;
+                self.pop_vals(&types)?;
+                self.unreachable();
+            }
+            Instr::BrIf(l) => {
+                self.pop_expect(I32)?;
+                let types = self.label_types(*l)?;
+                self.pop_vals(&types)?;
+                self.push_vals(&types);
+            }
+            Instr::BrTable(labels, default) => {
+                self.pop_expect(I32)?;
+                let default_types = self.label_types(*default)?;
+                for l in labels {
+                    let types = self.label_types(*l)?;
+                    if types.len() != default_types.len() {
+                        return Err(ValidationError::TypeMismatch {
+                            expected: default_types,
+                            actual: types,
+                        });
+                    }
+                    let popped = self.pop_vals(&types)?;
+                    self.operands.extend(popped);
+                }
+                self.pop_vals(&default_types)?;
+                self.unreachable();
+            }
+            Instr::Return => {
+                let results = self.results.clone();
+                self.pop_vals(&results)?;
+                self.unreachable();
+            }
+            Instr::Call(idx) | Instr::ReturnCall(idx) => {
+                self.module.check_func(*idx)?;
+                let ty = self
+                    .module
+                    .func_type(*idx)
+                    .ok_or(ValidationError::UnknownIndex(IndexSpace::Func, idx.0))?;
+                if instr.is_tail_call() {
+                    self.return_call(ty)?;
+                } else {
+                    self.call(ty)?;
+                }
+            }
+            Instr::CallIndirect(ty, table) | Instr::ReturnCallIndirect(ty, table) => {
+                let elem = self.module.check_table(*table)?.elem_type();
+                self.check_elem_matches(elem, RefType::Func)?;
+                let it = self.table_index_type(*table)?;
+                let ty = self.module.check_func_type_idx(*ty)?;
+                self.pop_expect(it)?;
+                if instr.is_tail_call() {
+                    self.return_call(ty)?;
+                } else {
+                    self.call(ty)?;
+                }
+            }
+            Instr::CallRef(idx) | Instr::ReturnCallRef(idx) => {
+                let ty = self.module.check_func_type_idx(*idx)?;
+                self.pop_expect(ref_type(true, HeapType::Concrete(*idx)))?;
+                if instr.is_tail_call() {
+                    self.return_call(ty)?;
+                } else {
+                    self.call(ty)?;
+                }
+            }
+            Instr::BrOnNull(l) => {
+                let rt = self.pop_ref()?;
+                let types = self.label_types(*l)?;
+                self.pop_vals(&types)?;
+                self.push_vals(&types);
+                self.operands
+                    .push(rt.map(|rt| ValType::Ref(rt.as_non_nullable())));
+            }
+            Instr::BrOnNonNull(l) => {
+                let actual = self.pop_ref()?.map(|rt| ValType::Ref(rt.as_non_nullable()));
+                let mut types = self.label_types(*l)?;
+                match (types.pop(), actual) {
+                    (Some(ValType::Ref(_)), None) => {}
+                    (Some(label_ref @ ValType::Ref(_)), Some(actual)) => {
+                        self.types_match(&[actual], &[label_ref])?
+                    }
+                    _ => {
+                        return Err(ValidationError::TypeMismatch {
+                            expected: self.label_types(*l)?,
+                            actual: actual.into_iter().collect(),
+                        });
+                    }
+                }
+                self.pop_vals(&types)?;
+                self.push_vals(&types);
+            }
+
+            Instr::TryTable(bt, catches) => {
+                let (params, results) = self.block_type(bt)?;
+                for catch in catches {
+                    let mut types = match catch.tag() {
+                        Some(tag) => self.tag_params(tag)?,
+                        None => Vec::new(),
+                    };
+                    if catch.is_ref() {
+                        types.push(ref_type(false, HeapType::Exn));
+                    }
+                    let label = self.label_types(catch.label())?;
+                    self.types_match(&types, &label)?;
+                }
+                self.pop_vals(&params)?;
+                self.push_ctrl(FrameKind::TryTable, params, results);
+            }
+            Instr::Throw(tag) => {
+                let params = self.tag_params(*tag)?;
+                self.pop_vals(&params)?;
+                self.unreachable();
+            }
+            Instr::ThrowRef => {
+                self.pop_expect(ValType::Ref(RefType::Exn))?;
+                self.unreachable();
+            }
+            Instr::Try(bt) => self.enter(FrameKind::Try, bt)?,
+            Instr::Catch(tag) => {
+                let frame = self.pop_ctrl()?;
+                if !matches!(frame.kind, FrameKind::Try | FrameKind::Catch) {
+                    return Err(ValidationError::UnexpectedInstr(instr.to_string()));
+                }
+                let params = self.tag_params(*tag)?;
+                self.push_clause(FrameKind::Catch, params, frame.results, frame.start);
+            }
+            Instr::CatchAll => {
+                let frame = self.pop_ctrl()?;
+                if !matches!(frame.kind, FrameKind::Try | FrameKind::Catch) {
+                    return Err(ValidationError::UnexpectedInstr(instr.to_string()));
+                }
+                self.push_clause(FrameKind::CatchAll, Vec::new(), frame.results, frame.start);
+            }
+            Instr::Delegate(l) => {
+                let frame = self.pop_ctrl()?;
+                if frame.kind != FrameKind::Try {
+                    return Err(ValidationError::UnexpectedInstr(instr.to_string()));
+                }
+                self.label(*l)?;
+                self.push_vals(&frame.results);
+            }
+            Instr::Rethrow(l) => {
+                if !matches!(self.label(*l)?.kind, FrameKind::Catch | FrameKind::CatchAll) {
+                    return Err(ValidationError::InvalidRethrowLabel(*l));
+                }
+                self.unreachable();
+            }
+
+            Instr::RefNull(heap) => {
+                self.module.check_heap_type(*heap)?;
+                self.push(ref_type(true, *heap));
+            }
+            Instr::RefIsNull => {
+                self.pop_ref()?;
+                self.push(I32);
+            }
+            Instr::RefFunc(idx) => {
+                self.module.check_func(*idx)?;
+                if !self.module.declared_refs.contains(&idx.0) {
+                    return Err(ValidationError::UndeclaredFuncRef(*idx));
+                }
+                let ty = self.module.funcs[idx.0 as usize];
+                self.push(ref_type(false, HeapType::Concrete(ty)));
+            }
+            Instr::RefAsNonNull => {
+                let rt = self.pop_ref()?;
+                self.operands
+                    .push(rt.map(|rt| ValType::Ref(rt.as_non_nullable())));
+            }
+            Instr::RefEq => {
+                self.pop_expect(ref_type(true, HeapType::Eq))?;
+                self.pop_expect(ref_type(true, HeapType::Eq))?;
+                self.push(I32);
+            }
+
+            Instr::StructNew(idx) => {
+                let types: Vec<ValType> = self
+                    .struct_fields(*idx)?
+                    .iter()
+                    .map(|f| f.storage_type().unpacked())
+                    .collect();
+                self.pop_vals(&types)?;
+                self.push(ref_type(false, HeapType::Concrete(*idx)));
+            }
+            Instr::StructNewDefault(idx) => {
+                for field in self.struct_fields(*idx)? {
+                    let t = field.storage_type().unpacked();
+                    if !is_defaultable(&t) {
+                        return Err(ValidationError::NotDefaultable(t));
+                    }
+                }
+                self.push(ref_type(false, HeapType::Concrete(*idx)));
+            }
+            Instr::StructGet(idx, field)
+            | Instr::StructGetS(idx, field)
+            | Instr::StructGetU(idx, field) => {
+                let ft = self.struct_field(*idx, *field)?;
+                Self::check_packing(&ft, !matches!(instr, Instr::StructGet(..)))?;
+                self.pop_expect(ref_type(true, HeapType::Concrete(*idx)))?;
+                self.push(ft.storage_type().unpacked());
+            }
+            Instr::StructSet(idx, field) => {
+                let ft = self.struct_field(*idx, *field)?;
+                if !ft.is_mutable() {
+                    return Err(ValidationError::ImmutableField);
+                }
+                self.pop_expect(ft.storage_type().unpacked())?;
+                self.pop_expect(ref_type(true, HeapType::Concrete(*idx)))?;
+            }
+            Instr::ArrayNew(idx) => {
+                let ft = self.array_field(*idx)?;
+                self.pop_expect(I32)?;
+                self.pop_expect(ft.storage_type().unpacked())?;
+                self.push(ref_type(false, HeapType::Concrete(*idx)));
+            }
+            Instr::ArrayNewDefault(idx) => {
+                let t = self.array_field(*idx)?.storage_type().unpacked();
+                if !is_defaultable(&t) {
+                    return Err(ValidationError::NotDefaultable(t));
+                }
+                self.pop_expect(I32)?;
+                self.push(ref_type(false, HeapType::Concrete(*idx)));
+            }
+            Instr::ArrayNewFixed(idx, n) => {
+                let t = self.array_field(*idx)?.storage_type().unpacked();
+                for _ in 0..*n {
+                    self.pop_expect(t)?;
+                }
+                self.push(ref_type(false, HeapType::Concrete(*idx)));
+            }
+            Instr::ArrayNewData(idx, data) => {
+                self.numeric_array_field(*idx)?;
+                self.module.check_data(*data)?;
+                self.pop_vals(&[I32, I32])?;
+                self.push(ref_type(false, HeapType::Concrete(*idx)));
+            }
+            Instr::ArrayNewElem(idx, elem) => {
+                let t = self.array_field(*idx)?.storage_type().unpacked();
+                let elem = self.elem_type(*elem)?;
+                self.types_match(&[ValType::Ref(elem)], &[t])?;
+                self.pop_vals(&[I32, I32])?;
+                self.push(ref_type(false, HeapType::Concrete(*idx)));
+            }
+            Instr::ArrayGet(idx) | Instr::ArrayGetS(idx) | Instr::ArrayGetU(idx) => {
+                let ft = self.array_field(*idx)?;
+                Self::check_packing(&ft, !matches!(instr, Instr::ArrayGet(_)))?;
+                self.pop_expect(I32)?;
+                self.pop_expect(ref_type(true, HeapType::Concrete(*idx)))?;
+                self.push(ft.storage_type().unpacked());
+            }
+            Instr::ArraySet(idx) => {
+                let t = self.mutable_array_field(*idx)?.storage_type().unpacked();
+                self.pop_expect(t)?;
+                self.pop_expect(I32)?;
+                self.pop_expect(ref_type(true, HeapType::Concrete(*idx)))?;
+            }
+            Instr::ArrayLen => {
+                self.pop_expect(ref_type(true, HeapType::Array))?;
+                self.push(I32);
+            }
+            Instr::ArrayFill(idx) => {
+                let t = self.mutable_array_field(*idx)?.storage_type().unpacked();
+                self.pop_vals(&[ref_type(true, HeapType::Concrete(*idx)), I32, t, I32])?;
+            }
+            Instr::ArrayCopy(dst, src) => {
+                let dst_type = self.mutable_array_field(*dst)?.storage_type();
+                let src_type = self.array_field(*src)?.storage_type();
+                let compatible = match (dst_type, src_type) {
+                    (StorageType::Val(d), StorageType::Val(s)) => self.module.matches(&s, &d),
+                    (d, s) => d == s,
+                };
+                if !compatible {
+                    return Err(ValidationError::TypeMismatch {
+                        expected: vec![dst_type.unpacked()],
+                        actual: vec![src_type.unpacked()],
+                    });
+                }
+                self.pop_vals(&[
+                    ref_type(true, HeapType::Concrete(*dst)),
+                    I32,
+                    ref_type(true, HeapType::Concrete(*src)),
+                    I32,
+                    I32,
+                ])?;
+            }
+            Instr::ArrayInitData(idx, data) => {
+                self.numeric_array_field(*idx)?;
+                self.mutable_array_field(*idx)?;
+                self.module.check_data(*data)?;
+                self.pop_vals(&[ref_type(true, HeapType::Concrete(*idx)), I32, I32, I32])?;
+            }
+            Instr::ArrayInitElem(idx, elem) => {
+                let t = self.mutable_array_field(*idx)?.storage_type().unpacked();
+                let elem = self.elem_type(*elem)?;
+                self.types_match(&[ValType::Ref(elem)], &[t])?;
+                self.pop_vals(&[ref_type(true, HeapType::Concrete(*idx)), I32, I32, I32])?;
+            }
+            Instr::RefTest(rt) | Instr::RefCast(rt) => {
+                self.module.check_heap_type(rt.heap_type())?;
+                let top = self.top_heap_type(rt.heap_type());
+                self.pop_expect(ref_type(true, top))?;
+                match instr {
+                    Instr::RefTest(_) => self.push(I32),
+                    _ => self.push(ValType::Ref(*rt)),
+                }
+            }
+            Instr::BrOnCast(l, rt1, rt2) | Instr::BrOnCastFail(l, rt1, rt2) => {
+                self.module.check_heap_type(rt1.heap_type())?;
+                self.module.check_heap_type(rt2.heap_type())?;
+                self.check_elem_matches(*rt2, *rt1)?;
+                // The type of the operand when the cast fails
+                let diff = if rt2.is_nullable() {
+                    rt1.as_non_nullable()
+                } else {
+                    *rt1
+                };
+                let (branch, fallthrough) = match instr {
+                    Instr::BrOnCast(..) => (*rt2, diff),
+                    _ => (diff, *rt2),
+                };
+                let mut types = self.label_types(*l)?;
+                match types.pop() {
+                    Some(label_ref) => self.types_match(&[ValType::Ref(branch)], &[label_ref])?,
+                    None => {
+                        return Err(ValidationError::TypeMismatch {
+                            expected: Vec::new(),
+                            actual: vec![ValType::Ref(branch)],
+                        });
+                    }
+                }
+                self.pop_expect(ValType::Ref(*rt1))?;
+                self.pop_vals(&types)?;
+                self.push_vals(&types);
+                self.push(ValType::Ref(fallthrough));
+            }
+            Instr::AnyConvertExtern | Instr::ExternConvertAny => {
+                let (from, to) = match instr {
+                    Instr::AnyConvertExtern => (HeapType::Extern, HeapType::Any),
+                    _ => (HeapType::Any, HeapType::Extern),
+                };
+                let nullable = match self.pop_expect(ref_type(true, from))? {
+                    Some(ValType::Ref(rt)) => rt.is_nullable(),
+                    _ => true,
+                };
+                self.push(ref_type(nullable, to));
+            }
+            Instr::RefI31 => {
+                self.pop_expect(I32)?;
+                self.push(ref_type(false, HeapType::I31));
+            }
+            Instr::I31GetS | Instr::I31GetU => {
+                self.pop_expect(ref_type(true, HeapType::I31))?;
+                self.push(I32);
+            }
+
+            Instr::Drop => {
+                self.pop()?;
+            }
+            Instr::Select(None) => {
+                self.pop_expect(I32)?;
+                let t1 = self.pop()?;
+                let t2 = self.pop()?;
+                for t in [t1, t2].into_iter().flatten() {
+                    if let ValType::Ref(_) = t {
+                        return Err(ValidationError::TypeMismatch {
+                            expected: Vec::new(),
+                            actual: vec![t],
+                        });
+                    }
+                }
+                if let (Some(t1), Some(t2)) = (t1, t2)
+                    && t1 != t2
+                {
+                    return Err(ValidationError::TypeMismatch {
+                        expected: vec![t1],
+                        actual: vec![t2],
+                    });
+                }
+                self.operands.push(t1.or(t2));
+            }
+            Instr::Select(Some(types)) => {
+                let t = match types.as_slice() {
+                    [t] => *t,
+                    _ => return Err(ValidationError::InvalidResultArity),
+                };
+                self.module.check_val_type(&t)?;
+                self.pop_vals(&[t, t, I32])?;
+                self.push(t);
+            }
+
+            Instr::LocalGet(idx) => {
+                let t = self.local(*idx)?;
+                if !is_defaultable(&t) && !self.initialized.contains(&idx.0) {
+                    return Err(ValidationError::UninitializedLocal(*idx));
+                }
+                self.push(t);
+            }
+            Instr::LocalSet(idx) | Instr::LocalTee(idx) => {
+                let t = self.local(*idx)?;
+                self.pop_expect(t)?;
+                self.set_local(*idx, &t);
+                if let Instr::LocalTee(_) = instr {
+                    self.push(t);
+                }
+            }
+            Instr::GlobalGet(idx) => {
+                let t = self.module.check_global(*idx)?.val_type();
+                self.push(t);
+            }
+            Instr::GlobalSet(idx) => {
+                let global = self.module.check_global(*idx)?;
+                if !global.is_mutable() {
+                    return Err(ValidationError::ImmutableGlobal(*idx));
+                }
+                self.pop_expect(global.val_type())?;
+            }
+
+            Instr::TableGet(idx) => {
+                let t = ValType::Ref(self.module.check_table(*idx)?.elem_type());
+                let it = self.table_index_type(*idx)?;
+                self.pop_expect(it)?;
+                self.push(t);
+            }
+            Instr::TableSet(idx) => {
+                let t = ValType::Ref(self.module.check_table(*idx)?.elem_type());
+                let it = self.table_index_type(*idx)?;
+                self.pop_vals(&[it, t])?;
+            }
+            Instr::TableSize(idx) => {
+                let it = self.table_index_type(*idx)?;
+                self.push(it);
+            }
+            Instr::TableGrow(idx) => {
+                let t = ValType::Ref(self.module.check_table(*idx)?.elem_type());
+                let it = self.table_index_type(*idx)?;
+                self.pop_vals(&[t, it])?;
+                self.push(it);
+            }
+            Instr::TableFill(idx) => {
+                let t = ValType::Ref(self.module.check_table(*idx)?.elem_type());
+                let it = self.table_index_type(*idx)?;
+                self.pop_vals(&[it, t, it])?;
+            }
+            Instr::TableCopy(dst, src) => {
+                let dst_elem = self.module.check_table(*dst)?.elem_type();
+                let src_elem = self.module.check_table(*src)?.elem_type();
+                self.check_elem_matches(src_elem, dst_elem)?;
+                let dst_it = self.table_index_type(*dst)?;
+                let src_it = self.table_index_type(*src)?;
+                self.pop_vals(&[dst_it, src_it, Self::min_index_type(dst_it, src_it)])?;
+            }
+            Instr::TableInit(elem, table) => {
+                let elem = self.elem_type(*elem)?;
+                let table_elem = self.module.check_table(*table)?.elem_type();
+                self.check_elem_matches(elem, table_elem)?;
+                let it = self.table_index_type(*table)?;
+                self.pop_vals(&[it, I32, I32])?;
+            }
+            Instr::ElemDrop(elem) => self.module.check_elem(*elem)?,
+
+            Instr::Load(op, memarg) => {
+                let (t, size) = load_type(*op);
+                let it = self.mem_arg(memarg, size, false)?;
+                self.pop_expect(it)?;
+                self.push(t);
+            }
+            Instr::Store(op, memarg) => {
+                let (t, size) = store_type(*op);
+                let it = self.mem_arg(memarg, size, false)?;
+                self.pop_vals(&[it, t])?;
+            }
+            Instr::MemorySize(mem) => {
+                let it = self.mem_index_type(*mem)?;
+                self.push(it);
+            }
+            Instr::MemoryGrow(mem) => {
+                let it = self.mem_index_type(*mem)?;
+                self.pop_expect(it)?;
+                self.push(it);
+            }
+            Instr::MemoryFill(mem) => {
+                let it = self.mem_index_type(*mem)?;
+                self.pop_vals(&[it, I32, it])?;
+            }
+            Instr::MemoryCopy(dst, src) => {
+                let dst_it = self.mem_index_type(*dst)?;
+                let src_it = self.mem_index_type(*src)?;
+                self.pop_vals(&[dst_it, src_it, Self::min_index_type(dst_it, src_it)])?;
+            }
+            Instr::MemoryInit(data, mem) => {
+                self.module.check_data(*data)?;
+                let it = self.mem_index_type(*mem)?;
+                self.pop_vals(&[it, I32, I32])?;
+            }
+            Instr::DataDrop(data) => self.module.check_data(*data)?,
+
+            Instr::I32Const(_) => self.push(I32),
+            Instr::I64Const(_) => self.push(I64),
+            Instr::F32Const(_) => self.push(F32),
+            Instr::F64Const(_) => self.push(F64),
+            Instr::Numeric(op) => {
+                let (params, result) = numeric_type(*op);
+                self.pop_vals(params)?;
+                self.push(result);
+            }
+
+            Instr::Simd(op, imm) => {
+                let it = match imm {
+                    SimdImm::MemArg(memarg) | SimdImm::MemArgLane(memarg, _) => {
+                        self.mem_arg(memarg, simd_access_size(*op), false)?
+                    }
+                    _ => I32,
+                };
+                match imm {
+                    SimdImm::Lane(lane) | SimdImm::MemArgLane(_, lane)
+                        if *lane >= simd_lanes(*op) =>
+                    {
+                        return Err(ValidationError::InvalidLaneIndex(*lane));
+                    }
+                    SimdImm::Bytes(lanes) if *op == SimdOp::I8x16Shuffle => {
+                        if let Some(lane) = lanes.iter().find(|lane| **lane >= 32) {
+                            return Err(ValidationError::InvalidLaneIndex(*lane));
+                        }
+                    }
+                    _ => {}
+                }
+                let (params, results) = simd_type(*op, it);
+                self.pop_vals(&params)?;
+                self.push_vals(&results);
+            }
+
+            Instr::Atomic(op, memarg) => {
+                let (params, results, size) = atomic_type(*op);
+                let it = self.mem_arg(memarg, size, true)?;
+                // The address comes first, below the other operands
+                let operands: Vec<ValType> = std::iter::once(it).chain(params).collect();
+                self.pop_vals(&operands)?;
+                self.push_vals(&results);
+            }
+            Instr::AtomicFence => {}
+        }
+        Ok(())
+    }
+}
+
+/// The type loaded and the number of bytes accessed.
+pub(crate) fn load_type(op: LoadOp) -> (ValType, u32) {
+    match op.opcode() {
+        0x28 => (I32, 4),
+        0x29 => (I64, 8),
+        0x2A => (F32, 4),
+        0x2B => (F64, 8),
+        0x2C | 0x2D => (I32, 1),
+        0x2E | 0x2F => (I32, 2),
+        0x30 | 0x31 => (I64, 1),
+        0x32 | 0x33 => (I64, 2),
+        _ => (I64, 4),
+    }
+}
+
+/// The type stored and the number of bytes accessed.
+pub(crate) fn store_type(op: StoreOp) -> (ValType, u32) {
+    match op.opcode() {
+        0x36 => (I32, 4),
+        0x37 => (I64, 8),
+        0x38 => (F32, 4),
+        0x39 => (F64, 8),
+        0x3A => (I32, 1),
+        0x3B => (I32, 2),
+        0x3C => (I64, 1),
+        0x3D => (I64, 2),
+        _ => (I64, 4),
+    }
+}
+
+pub(crate) fn numeric_type(op: NumericInstr) -> (&'static [ValType], ValType) {
+    match op.opcode() {
+        0x45 | 0x67..=0x69 | 0xC0 | 0xC1 => (&[I32], I32),
+        0x46..=0x4F => (&[I32, I32], I32),
+        0x50 | 0xA7 => (&[I64], I32),
+        0x51..=0x5A => (&[I64, I64], I32),
+        0x5B..=0x60 => (&[F32, F32], I32),
+        0x61..=0x66 => (&[F64, F64], I32),
+        0x6A..=0x78 => (&[I32, I32], I32),
+        0x79..=0x7B | 0xC2..=0xC4 => (&[I64], I64),
+        0x7C..=0x8A => (&[I64, I64], I64),
+        0x8B..=0x91 => (&[F32], F32),
+        0x92..=0x98 => (&[F32, F32], F32),
+        0x99..=0x9F => (&[F64], F64),
+        0xA0..=0xA6 => (&[F64, F64], F64),
+        0xA8 | 0xA9 | 0xBC | 0xFC00 | 0xFC01 => (&[F32], I32),
+        0xAA | 0xAB | 0xFC02 | 0xFC03 => (&[F64], I32),
+        0xAC | 0xAD => (&[I32], I64),
+        0xAE | 0xAF | 0xFC04 | 0xFC05 => (&[F32], I64),
+        0xB2 | 0xB3 | 0xBE => (&[I32], F32),
+        0xB4 | 0xB5 => (&[I64], F32),
+        0xB6 => (&[F64], F32),
+        0xB7 | 0xB8 => (&[I32], F64),
+        0xB9 | 0xBA | 0xBF => (&[I64], F64),
+        0xBB => (&[F32], F64),
+        // 0xB0, 0xB1, 0xBD and the 64-bit saturating truncations
+        _ => (&[F64], I64),
+    }
+}
+
+/// The number of bytes a SIMD memory instruction accesses.
+pub(crate) fn simd_access_size(op: SimdOp) -> u32 {
+    match op.opcode() {
+        0x07 | 0x54 | 0x58 => 1,
+        0x08 | 0x55 | 0x59 => 2,
+        0x09 | 0x56 | 0x5A | 0x5C => 4,
+        0x01..=0x06 | 0x0A | 0x57 | 0x5B | 0x5D => 8,
+        _ => 16,
+    }
+}
+
+/// The number of lanes of the vector shape a lane index refers to.
+fn simd_lanes(op: SimdOp) -> u8 {
+    match op.opcode() {
+        0x15..=0x17 | 0x54 | 0x58 => 16,
+        0x18..=0x1A | 0x55 | 0x59 => 8,
+        0x1B | 0x1C | 0x1F | 0x20 | 0x56 | 0x5A => 4,
+        _ => 2,
+    }
+}
+
+/// The operand and result types of a SIMD instruction, given the index
+/// type of the memory it accesses, if any.
+pub(crate) fn simd_type(op: SimdOp, it: ValType) -> (Vec<ValType>, Vec<ValType>) {
+    match op.opcode() {
+        0x00..=0x0A | 0x5C | 0x5D => (vec![it], vec![V128]),
+        0x0B => (vec![it, V128], vec![]),
+        0x0C => (vec![], vec![V128]),
+        0x0F..=0x11 => (vec![I32], vec![V128]),
+        0x12 => (vec![I64], vec![V128]),
+        0x13 => (vec![F32], vec![V128]),
+        0x14 => (vec![F64], vec![V128]),
+        0x15 | 0x16 | 0x18 | 0x19 | 0x1B => (vec![V128], vec![I32]),
+        0x1D => (vec![V128], vec![I64]),
+        0x1F => (vec![V128], vec![F32]),
+        0x21 => (vec![V128], vec![F64]),
+        0x17 | 0x1A | 0x1C => (vec![V128, I32], vec![V128]),
+        0x1E => (vec![V128, I64], vec![V128]),
+        0x20 => (vec![V128, F32], vec![V128]),
+        0x22 => (vec![V128, F64], vec![V128]),
+        0x53 | 0x63 | 0x64 | 0x83 | 0x84 | 0xA3 | 0xA4 | 0xC3 | 0xC4 => (vec![V128], vec![I32]),
+        0x54..=0x57 => (vec![it, V128], vec![V128]),
+        0x58..=0x5B => (vec![it, V128], vec![]),
+        0x6B..=0x6D | 0x8B..=0x8D | 0xAB..=0xAD | 0xCB..=0xCD => (vec![V128, I32], vec![V128]),
+        0x52 | 0x105..=0x10C | 0x113 => (vec![V128, V128, V128], vec![V128]),
+        0x4D
+        | 0x5E..=0x62
+        | 0x67..=0x6A
+        | 0x74
+        | 0x75
+        | 0x7A
+        | 0x7C..=0x81
+        | 0x87..=0x8A
+        | 0x94
+        | 0xA0
+        | 0xA1
+        | 0xA7..=0xAA
+        | 0xC0
+        | 0xC1
+        | 0xC7..=0xCA
+        | 0xE0
+        | 0xE1
+        | 0xE3
+        | 0xEC
+        | 0xED
+        | 0xEF
+        | 0xF8..=0xFF
+        | 0x101..=0x104 => (vec![V128], vec![V128]),
+        _ => (vec![V128, V128], vec![V128]),
+    }
+}
+
+/// The operand types after the address, the result types and the number
+/// of bytes accessed by an atomic instruction.
+pub(crate) fn atomic_type(op: AtomicOp) -> (Vec<ValType>, Vec<ValType>, u32) {
+    let name = op.name();
+    let t = if name.starts_with("i64") { I64 } else { I32 };
+    // e.g. "rmw8.add_u" or "load16_u" after the "iNN.atomic." prefix
+    let access = name.rsplit_once("atomic.").map_or(name, |(_, rest)| rest);
+    let digits: String = access
+        .trim_start_matches(|c: char| c.is_ascii_alphabetic() || c == '.')
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let size = match digits.parse::<u32>() {
+        Ok(bits) => bits / 8,
+        _ if t == I64 => 8,
+        _ => 4,
+    };
+    match op.opcode() {
+        0x00 => (vec![I32], vec![I32], 4),
+        0x01 => (vec![I32, I64], vec![I32], 4),
+        0x02 => (vec![I64, I64], vec![I32], 8),
+        0x10..=0x16 => (vec![], vec![t], size),
+        0x17..=0x1D => (vec![t], vec![], size),
+        0x48..=0x4E => (vec![t, t], vec![t], size),
+        _ => (vec![t], vec![t], size),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::Module;
+    use crate::parseable::ParseError;
+    use crate::validate::validate;
+    use std::io::{BufReader, Cursor};
+
+    // A module with a single function of type [i32] -> [i32] and the given
+    // body, without locals.
+    fn module(body: &[u8]) -> Module {
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x0a,
+        ];
+        bytes.extend_from_slice(&[body.len() as u8 + 3, 0x01, body.len() as u8 + 1, 0x00]);
+        bytes.extend_from_slice(body);
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        match Module::parse(&mut reader) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        }
+    }
+
+    fn check(body: &[u8]) -> Result<Vec<u32>> {
+        let module = module(body);
+        let validated = validate(&module)?;
+        Ok(validated
+            .stack_heights(FuncIdx(0))
+            .expect("The stack heights")
+            .to_vec())
+    }
+
+    fn error(body: &[u8]) -> ValidationError {
+        match check(body) {
+            Ok(_) => panic!("Expected a validation error"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn test_stack_heights() {
+        // local.get 0; i32.const 1; i32.add; end
+        let heights = check(&[0x20, 0x00, 0x41, 0x01, 0x6a, 0x0b]).expect("A valid body");
+        assert_eq!(heights, [0, 1, 2, 1]);
+
+        // block (result i32); local.get 0; br 0; end; end
+        let heights =
+            check(&[0x02, 0x7f, 0x20, 0x00, 0x0c, 0x00, 0x0b, 0x0b]).expect("A valid body");
+        assert_eq!(heights, [0, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_stacks() {
+        // local.get 0; block (result i32); local.get 0; br 0; i32.const 0;
+        // end; i32.add; end
+        let module = module(&[
+            0x20, 0x00, 0x02, 0x7f, 0x20, 0x00, 0x0c, 0x00, 0x41, 0x00, 0x0b, 0x6a, 0x0b,
+        ]);
+        let validated = validate(&module).expect("A valid module");
+        let stacks = validated.stacks(FuncIdx(0)).expect("The stacks");
+        let operands: Vec<_> = (0..8).map(|pc| stacks.operands(pc)).collect();
+        assert_eq!(
+            operands,
+            [
+                Some(vec![]),
+                Some(vec![I32]),
+                Some(vec![I32]),
+                Some(vec![I32, I32]),
+                None,
+                None,
+                Some(vec![I32, I32]),
+                Some(vec![I32]),
+            ]
+        );
+        assert_eq!(stacks.blocks(3), Some(vec![(1, 1)]));
+        assert_eq!(stacks.blocks(6), Some(vec![]));
+        assert_eq!(stacks.operands(8), None);
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        // i64.const 0; end
+        let err = error(&[0x42, 0x00, 0x0b]);
+        assert_eq!(
+            err,
+            ValidationError::InFunc {
+                func: FuncIdx(0),
+                offset: 0x05,
+                stack: vec![Some(I64)],
+                error: Box::new(ValidationError::TypeMismatch {
+                    expected: vec![I32],
+                    actual: vec![I64],
+                }),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "func 0 at 0x0005: type mismatch: expected [i32] but got [i64] (stack: [i64])"
+        );
+
+        // local.get 0; f32.const 0; i32.add; end
+        let err = error(&[0x20, 0x00, 0x43, 0x00, 0x00, 0x00, 0x00, 0x6a, 0x0b]);
+        assert_eq!(
+            err.to_string(),
+            "func 0 at 0x000a: type mismatch: expected [i32 i32] but got [i32 f32] (stack: [i32 f32])"
+        );
+
+        // local.get 0; if (result i32); i32.const 1; end; end -- no else
+        let err = error(&[0x20, 0x00, 0x04, 0x7f, 0x41, 0x01, 0x0b, 0x0b]);
+        match err {
+            ValidationError::InFunc { error, .. } => assert_eq!(
+                *error,
+                ValidationError::TypeMismatch {
+                    expected: vec![I32],
+                    actual: vec![],
+                }
+            ),
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_unreachable() {
+        // unreachable; i32.add; end
+        assert!(check(&[0x00, 0x6a, 0x0b]).is_ok());
+
+        // return; i64.const 0; end -- values after return are still checked
+        let err = error(&[0x0f, 0x42, 0x00, 0x0b]);
+        assert!(matches!(err, ValidationError::InFunc { .. }));
+
+        // local.get 0; br 0; select; end
+        assert!(check(&[0x20, 0x00, 0x0c, 0x00, 0x1b, 0x0b]).is_ok());
+    }
+
+    #[test]
+    fn test_indices() {
+        // local.get 1; end
+        let err = error(&[0x20, 0x01, 0x0b]);
+        match err {
+            ValidationError::InFunc { error, .. } => {
+                assert_eq!(*error, ValidationError::UnknownIndex(IndexSpace::Local, 1))
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,1179 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::features::{Feature, Features};
+use crate::instr::Instr;
+use crate::instr::expr::Expr;
+use crate::instr::numeric::NumericInstr;
+use crate::instr::simd::SimdOp;
+use crate::module::Module;
+use crate::section::code::Code;
+use crate::section::data::DataMode;
+use crate::section::element::{ElemInit, ElemMode};
+use crate::types::comp_type::CompType;
+use crate::types::export_desc::ExportDesc;
+use crate::types::func_type::FuncType;
+use crate::types::global_type::GlobalType;
+use crate::types::heap_type::HeapType;
+use crate::types::import_desc::ImportDesc;
+use crate::types::limits::Limits;
+use crate::types::mem_type::MemType;
+use crate::types::num_type::NumType;
+use crate::types::primitives::{
+    DataIdx, ElemIdx, FuncIdx, GlobalIdx, LabelIdx, LocalIdx, MemIdx, TableIdx, TagIdx, TypeIdx,
+};
+use crate::types::ref_type::RefType;
+use crate::types::sub_type::SubType;
+use crate::types::table_type::TableType;
+use crate::types::val_type::ValType;
+use crate::types::vec_type::VecType;
+
+pub(crate) mod func;
+
+use func::FuncValidator;
+pub use func::{FuncStacks, Operand};
+pub(crate) use func::{
+    atomic_type, load_type, numeric_type, simd_access_size, simd_type, store_type,
+};
+
+/// The index spaces of a module, for reporting out-of-range indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexSpace {
+    Type,
+    Func,
+    Table,
+    Mem,
+    Global,
+    Elem,
+    Data,
+    Tag,
+    Local,
+    Label,
+    Field,
+}
+
+impl Display for IndexSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IndexSpace::Type => "type",
+            IndexSpace::Func => "function",
+            IndexSpace::Table => "table",
+            IndexSpace::Mem => "memory",
+            IndexSpace::Global => "global",
+            IndexSpace::Elem => "elem segment",
+            IndexSpace::Data => "data segment",
+            IndexSpace::Tag => "tag",
+            IndexSpace::Local => "local",
+            IndexSpace::Label => "label",
+            IndexSpace::Field => "field",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Why a module is invalid. The messages follow the wording of the
+/// reference interpreter where there is one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    UnknownIndex(IndexSpace, u32),
+    /// A function type was needed, but the index refers to a struct or
+    /// array type.
+    NotAFuncType(TypeIdx),
+    /// A declared supertype is final, not defined earlier, or of another
+    /// kind.
+    InvalidSupertype(TypeIdx),
+    SizeMinimumExceedsMaximum,
+    MemorySizeTooLarge,
+    SharedMemoryWithoutMax,
+    TagResultNotEmpty(TagIdx),
+    DuplicateExport(String),
+    InvalidStartFunction(FuncIdx),
+    ConstExprRequired,
+    TypeMismatch {
+        expected: Vec<ValType>,
+        actual: Vec<ValType>,
+    },
+    FunctionCodeMismatch {
+        funcs: usize,
+        codes: usize,
+    },
+    DataCountMismatch {
+        count: u32,
+        datas: usize,
+    },
+    DataCountRequired,
+    UndeclaredFuncRef(FuncIdx),
+    TooManyLocals,
+    /// An instruction popped from an empty operand stack.
+    StackUnderflow,
+    ExpectedReference(ValType),
+    /// An `else`, `catch` or `delegate` that does not belong to its block.
+    UnexpectedInstr(String),
+    InvalidRethrowLabel(LabelIdx),
+    NotAStructType(TypeIdx),
+    NotAnArrayType(TypeIdx),
+    /// `array.new_data` and `array.init_data` need numeric elements.
+    NotANumericArray(TypeIdx),
+    ImmutableField,
+    ImmutableGlobal(GlobalIdx),
+    /// A plain get of a packed field, or a signed/unsigned get of an
+    /// unpacked one.
+    FieldPacking {
+        packed: bool,
+    },
+    NotDefaultable(ValType),
+    InvalidResultArity,
+    UninitializedLocal(LocalIdx),
+    InvalidAlignment {
+        align: u32,
+        size: u32,
+    },
+    InvalidLaneIndex(u8),
+    FeatureNotEnabled(Feature),
+    /// An error in a function body, with the offset of the instruction and
+    /// the operand stack at that point.
+    InFunc {
+        func: FuncIdx,
+        offset: usize,
+        stack: Vec<Operand>,
+        error: Box<ValidationError>,
+    },
+}
+
+fn type_list(f: &mut std::fmt::Formatter<'_>, types: &[ValType]) -> std::fmt::Result {
+    write!(f, "[")?;
+    for (i, t) in types.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", t.name())?;
+    }
+    write!(f, "]")
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::UnknownIndex(space, idx) => write!(f, "unknown {} {}", space, idx),
+            ValidationError::NotAFuncType(idx) => {
+                write!(f, "type {} is not a function type", idx.0)
+            }
+            ValidationError::InvalidSupertype(idx) => {
+                write!(f, "sub type {} does not match its super type", idx.0)
+            }
+            ValidationError::SizeMinimumExceedsMaximum => {
+                write!(f, "size minimum must not be greater than maximum")
+            }
+            ValidationError::MemorySizeTooLarge => {
+                write!(f, "memory size must be at most 65536 pages (4GiB)")
+            }
+            ValidationError::SharedMemoryWithoutMax => {
+                write!(f, "shared memory must have maximum")
+            }
+            ValidationError::TagResultNotEmpty(idx) => {
+                write!(f, "non-empty tag result type for tag {}", idx.0)
+            }
+            ValidationError::DuplicateExport(name) => {
+                write!(f, "duplicate export name \"{}\"", name)
+            }
+            ValidationError::InvalidStartFunction(idx) => {
+                write!(f, "start function {} must have type [] -> []", idx.0)
+            }
+            ValidationError::ConstExprRequired => write!(f, "constant expression required"),
+            ValidationError::TypeMismatch { expected, actual } => {
+                write!(f, "type mismatch: expected ")?;
+                type_list(f, expected)?;
+                write!(f, " but got ")?;
+                type_list(f, actual)
+            }
+            ValidationError::FunctionCodeMismatch { funcs, codes } => write!(
+                f,
+                "function and code section have inconsistent lengths ({} and {})",
+                funcs, codes
+            ),
+            ValidationError::DataCountMismatch { count, datas } => write!(
+                f,
+                "data count and data section have inconsistent lengths ({} and {})",
+                count, datas
+            ),
+            ValidationError::DataCountRequired => write!(f, "data count section required"),
+            ValidationError::UndeclaredFuncRef(idx) => {
+                write!(f, "undeclared function reference {}", idx.0)
+            }
+            ValidationError::TooManyLocals => write!(f, "too many locals"),
+            ValidationError::StackUnderflow => {
+                write!(f, "type mismatch: operand stack is empty")
+            }
+            ValidationError::ExpectedReference(t) => {
+                write!(
+                    f,
+                    "type mismatch: expected a reference but got {}",
+                    t.name()
+                )
+            }
+            ValidationError::UnexpectedInstr(instr) => write!(f, "unexpected {}", instr),
+            ValidationError::InvalidRethrowLabel(l) => {
+                write!(f, "invalid rethrow label {}", l.0)
+            }
+            ValidationError::NotAStructType(idx) => {
+                write!(f, "type {} is not a struct type", idx.0)
+            }
+            ValidationError::NotAnArrayType(idx) => {
+                write!(f, "type {} is not an array type", idx.0)
+            }
+            ValidationError::NotANumericArray(idx) => {
+                write!(f, "array type {} is not numeric or vector", idx.0)
+            }
+            ValidationError::ImmutableField => write!(f, "field is immutable"),
+            ValidationError::ImmutableGlobal(idx) => {
+                write!(f, "global {} is immutable", idx.0)
+            }
+            ValidationError::FieldPacking { packed: true } => {
+                write!(f, "field is packed")
+            }
+            ValidationError::FieldPacking { packed: false } => {
+                write!(f, "field is not packed")
+            }
+            ValidationError::NotDefaultable(t) => {
+                write!(f, "type {} is not defaultable", t.name())
+            }
+            ValidationError::InvalidResultArity => write!(f, "invalid result arity"),
+            ValidationError::UninitializedLocal(idx) => {
+                write!(f, "uninitialized local {}", idx.0)
+            }
+            // Atomic accesses must be aligned exactly
+            ValidationError::InvalidAlignment { align, size } => write!(
+                f,
+                "{}: 2**{} for an access of {} bytes",
+                if 1u64
+                    .checked_shl(*align)
+                    .is_none_or(|n| n > u64::from(*size))
+                {
+                    "alignment must not be larger than natural"
+                } else {
+                    "atomic alignment must be natural"
+                },
+                align,
+                size
+            ),
+            ValidationError::InvalidLaneIndex(lane) => write!(f, "invalid lane index {}", lane),
+            ValidationError::FeatureNotEnabled(feature) => {
+                write!(f, "feature {} not enabled", feature)
+            }
+            ValidationError::InFunc {
+                func,
+                offset,
+                stack,
+                error,
+            } => {
+                write!(f, "func {} at {:#06x}: {} (stack: [", func.0, offset, error)?;
+                for (i, t) in stack.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    match t {
+                        Some(t) => write!(f, "{}", t.name())?,
+                        None => write!(f, "_")?,
+                    }
+                }
+                write!(f, "])")
+            }
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ValidationError>;
+
+fn check_idx(space: IndexSpace, idx: u32, len: usize) -> Result<()> {
+    if (idx as usize) < len {
+        Ok(())
+    } else {
+        Err(ValidationError::UnknownIndex(space, idx))
+    }
+}
+
+/// A module that passed validation, together with its index spaces with
+/// imports and definitions combined. Everything an index in the module
+/// refers to is guaranteed to exist.
+pub struct ValidatedModule<'a> {
+    module: &'a Module,
+    types: Vec<&'a SubType>,
+    /// The type of each function, imported functions first.
+    funcs: Vec<TypeIdx>,
+    imported_funcs: usize,
+    tables: Vec<&'a TableType>,
+    mems: Vec<&'a MemType>,
+    globals: Vec<&'a GlobalType>,
+    imported_globals: usize,
+    tags: Vec<TypeIdx>,
+    elems: Vec<RefType>,
+    /// The number of data segments as far as function bodies may assume,
+    /// which is the data count if there is one.
+    datas: Option<u32>,
+    /// Functions that may be referenced with `ref.func` in function bodies.
+    declared_refs: HashSet<u32>,
+    /// The stacks before each instruction, per defined function.
+    stacks: Vec<FuncStacks>,
+}
+
+impl<'a> ValidatedModule<'a> {
+    fn new(module: &'a Module) -> ValidatedModule<'a> {
+        let mut ctx = ValidatedModule {
+            module,
+            types: Vec::new(),
+            funcs: Vec::new(),
+            imported_funcs: 0,
+            tables: Vec::new(),
+            mems: Vec::new(),
+            globals: Vec::new(),
+            imported_globals: 0,
+            tags: Vec::new(),
+            elems: Vec::new(),
+            datas: None,
+            declared_refs: HashSet::new(),
+            stacks: Vec::new(),
+        };
+
+        if let Some(typesec) = &module.typesec {
+            ctx.types = typesec.types().collect();
+        }
+        if let Some(importsec) = &module.importsec {
+            for import in importsec.imports() {
+                match import.desc() {
+                    ImportDesc::Func(ty) => ctx.funcs.push(*ty),
+                    ImportDesc::Table(ty) => ctx.tables.push(ty),
+                    ImportDesc::Mem(ty) => ctx.mems.push(ty),
+                    ImportDesc::Global(ty) => ctx.globals.push(ty),
+                    ImportDesc::Tag(ty) => ctx.tags.push(ty.type_idx()),
+                }
+            }
+        }
+        ctx.imported_funcs = ctx.funcs.len();
+        ctx.imported_globals = ctx.globals.len();
+        if let Some(functionsec) = &module.functionsec {
+            ctx.funcs.extend_from_slice(functionsec.funcs());
+        }
+        if let Some(tablesec) = &module.tablesec {
+            ctx.tables
+                .extend(tablesec.tables().iter().map(|table| table.table_type()));
+        }
+        if let Some(memsec) = &module.memsec {
+            ctx.mems.extend(memsec.mems());
+        }
+        if let Some(globalsec) = &module.globalsec {
+            ctx.globals.extend(
+                globalsec
+                    .globals()
+                    .iter()
+                    .map(|global| global.global_type()),
+            );
+        }
+        if let Some(tagsec) = &module.tagsec {
+            ctx.tags
+                .extend(tagsec.tags().iter().map(|tag| tag.type_idx()));
+        }
+        if let Some(elemsec) = &module.elemsec {
+            ctx.elems = elemsec.elems().iter().map(|elem| elem.ref_type()).collect();
+        }
+        ctx.datas = module.datacountsec.as_ref().map(|sec| sec.count());
+        ctx
+    }
+
+    pub fn module(&self) -> &'a Module {
+        self.module
+    }
+
+    pub fn sub_type(&self, idx: TypeIdx) -> Option<&'a SubType> {
+        self.types.get(idx.0 as usize).copied()
+    }
+
+    pub fn func_type_at(&self, idx: TypeIdx) -> Option<&'a FuncType> {
+        self.sub_type(idx)?.comp_type().as_func()
+    }
+
+    /// The number of functions, imported and defined.
+    pub fn num_funcs(&self) -> usize {
+        self.funcs.len()
+    }
+
+    pub fn num_imported_funcs(&self) -> usize {
+        self.imported_funcs
+    }
+
+    pub fn func_type_idx(&self, idx: FuncIdx) -> Option<TypeIdx> {
+        self.funcs.get(idx.0 as usize).copied()
+    }
+
+    pub fn func_type(&self, idx: FuncIdx) -> Option<&'a FuncType> {
+        self.func_type_at(self.func_type_idx(idx)?)
+    }
+
+    /// The body of a function, or `None` for imported functions.
+    pub fn code(&self, idx: FuncIdx) -> Option<&'a Code> {
+        let defined = (idx.0 as usize).checked_sub(self.imported_funcs)?;
+        self.module.codesec.as_ref()?.codes().get(defined)
+    }
+
+    pub fn table(&self, idx: TableIdx) -> Option<&'a TableType> {
+        self.tables.get(idx.0 as usize).copied()
+    }
+
+    pub fn num_tables(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn mem(&self, idx: MemIdx) -> Option<&'a MemType> {
+        self.mems.get(idx.0 as usize).copied()
+    }
+
+    pub fn num_mems(&self) -> usize {
+        self.mems.len()
+    }
+
+    pub fn global(&self, idx: GlobalIdx) -> Option<&'a GlobalType> {
+        self.globals.get(idx.0 as usize).copied()
+    }
+
+    pub fn num_globals(&self) -> usize {
+        self.globals.len()
+    }
+
+    pub fn num_imported_globals(&self) -> usize {
+        self.imported_globals
+    }
+
+    /// The function type giving a tag's payload as parameters.
+    pub fn tag_type(&self, idx: TagIdx) -> Option<&'a FuncType> {
+        self.func_type_at(*self.tags.get(idx.0 as usize)?)
+    }
+
+    pub fn elem_type(&self, idx: ElemIdx) -> Option<RefType> {
+        self.elems.get(idx.0 as usize).copied()
+    }
+
+    pub fn data_count(&self) -> Option<u32> {
+        self.datas
+    }
+
+    /// The height of the operand stack before each instruction of a
+    /// function body, in the order of `Code::offsets`. `None` for imported
+    /// functions.
+    pub fn stack_heights(&self, idx: FuncIdx) -> Option<&[u32]> {
+        self.stacks(idx).map(FuncStacks::heights)
+    }
+
+    /// The operand types and open blocks before each instruction of a
+    /// function body. `None` for imported functions.
+    pub fn stacks(&self, idx: FuncIdx) -> Option<&FuncStacks> {
+        let defined = (idx.0 as usize).checked_sub(self.imported_funcs)?;
+        self.stacks.get(defined)
+    }
+
+    fn check_type_idx(&self, idx: TypeIdx) -> Result<()> {
+        check_idx(IndexSpace::Type, idx.0, self.types.len())
+    }
+
+    fn check_func_type_idx(&self, idx: TypeIdx) -> Result<&'a FuncType> {
+        self.check_type_idx(idx)?;
+        self.func_type_at(idx)
+            .ok_or(ValidationError::NotAFuncType(idx))
+    }
+
+    fn check_func(&self, idx: FuncIdx) -> Result<()> {
+        check_idx(IndexSpace::Func, idx.0, self.funcs.len())
+    }
+
+    fn check_table(&self, idx: TableIdx) -> Result<&'a TableType> {
+        self.table(idx)
+            .ok_or(ValidationError::UnknownIndex(IndexSpace::Table, idx.0))
+    }
+
+    fn check_mem(&self, idx: MemIdx) -> Result<&'a MemType> {
+        self.mem(idx)
+            .ok_or(ValidationError::UnknownIndex(IndexSpace::Mem, idx.0))
+    }
+
+    fn check_global(&self, idx: GlobalIdx) -> Result<&'a GlobalType> {
+        self.global(idx)
+            .ok_or(ValidationError::UnknownIndex(IndexSpace::Global, idx.0))
+    }
+
+    fn check_tag(&self, idx: TagIdx) -> Result<()> {
+        check_idx(IndexSpace::Tag, idx.0, self.tags.len())
+    }
+
+    fn check_elem(&self, idx: ElemIdx) -> Result<()> {
+        check_idx(IndexSpace::Elem, idx.0, self.elems.len())
+    }
+
+    fn check_data(&self, idx: DataIdx) -> Result<()> {
+        match self.datas {
+            Some(count) => check_idx(IndexSpace::Data, idx.0, count as usize),
+            None => Err(ValidationError::DataCountRequired),
+        }
+    }
+
+    fn check_heap_type(&self, heap: HeapType) -> Result<()> {
+        match heap {
+            HeapType::Concrete(idx) => self.check_type_idx(idx),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_val_type(&self, t: &ValType) -> Result<()> {
+        match t {
+            ValType::Ref(rt) => self.check_heap_type(rt.heap_type()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `a` is a subtype of `b` in the heap type hierarchies.
+    pub fn heap_matches(&self, a: HeapType, b: HeapType) -> bool {
+        if a == b {
+            return true;
+        }
+        match (a, b) {
+            (HeapType::Concrete(x), HeapType::Concrete(_)) => match self.sub_type(x) {
+                Some(sub) => sub
+                    .supertypes()
+                    .iter()
+                    .any(|sup| *sup != x && self.heap_matches(HeapType::Concrete(*sup), b)),
+                None => false,
+            },
+            (HeapType::Concrete(x), b) => match self.sub_type(x).map(|sub| sub.comp_type()) {
+                Some(CompType::Func(_)) => b == HeapType::Func,
+                Some(CompType::Struct(_)) => {
+                    matches!(b, HeapType::Struct | HeapType::Eq | HeapType::Any)
+                }
+                Some(CompType::Array(_)) => {
+                    matches!(b, HeapType::Array | HeapType::Eq | HeapType::Any)
+                }
+                None => false,
+            },
+            (HeapType::None, b) => {
+                self.heap_matches(b, HeapType::Any) || matches!(b, HeapType::None)
+            }
+            (HeapType::NoFunc, b) => self.heap_matches(b, HeapType::Func),
+            (HeapType::NoExtern, b) => b == HeapType::Extern,
+            (HeapType::NoExn, b) => b == HeapType::Exn,
+            (HeapType::I31 | HeapType::Struct | HeapType::Array, b) => {
+                matches!(b, HeapType::Eq | HeapType::Any)
+            }
+            (HeapType::Eq, b) => b == HeapType::Any,
+            _ => false,
+        }
+    }
+
+    /// Whether a value of type `a` may be used where `b` is expected.
+    pub fn matches(&self, a: &ValType, b: &ValType) -> bool {
+        match (a, b) {
+            (ValType::Ref(a), ValType::Ref(b)) => {
+                (!a.is_nullable() || b.is_nullable())
+                    && self.heap_matches(a.heap_type(), b.heap_type())
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    fn check_types(&self) -> Result<()> {
+        let typesec = match &self.module.typesec {
+            Some(typesec) => typesec,
+            None => return Ok(()),
+        };
+        let mut idx = 0;
+        for rec in typesec.rec_groups() {
+            // Types may refer to any type up to the end of their own group
+            let end = idx + rec.types().len();
+            for sub in rec.types() {
+                let refs: Vec<ValType> = match sub.comp_type() {
+                    CompType::Func(func) => func
+                        .params()
+                        .iter()
+                        .chain(func.results())
+                        .copied()
+                        .collect(),
+                    CompType::Struct(fields) => {
+                        fields.iter().map(|f| f.storage_type().unpacked()).collect()
+                    }
+                    CompType::Array(field) => vec![field.storage_type().unpacked()],
+                };
+                for t in &refs {
+                    if let ValType::Ref(rt) = t
+                        && let HeapType::Concrete(x) = rt.heap_type()
+                    {
+                        check_idx(IndexSpace::Type, x.0, end)?;
+                    }
+                }
+                for sup in sub.supertypes() {
+                    let valid = (sup.0 as usize) < idx
+                        && self.types.get(sup.0 as usize).is_some_and(|super_type| {
+                            !super_type.is_final()
+                                && std::mem::discriminant(super_type.comp_type())
+                                    == std::mem::discriminant(sub.comp_type())
+                        });
+                    if !valid {
+                        return Err(ValidationError::InvalidSupertype(TypeIdx(idx as u32)));
+                    }
+                }
+                idx += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_limits(limits: &Limits, max_allowed: u64) -> Result<()> {
+        if let Some(max) = limits.max()
+            && limits.min() > max
+        {
+            return Err(ValidationError::SizeMinimumExceedsMaximum);
+        }
+        if limits.min() > max_allowed || limits.max().is_some_and(|max| max > max_allowed) {
+            return Err(ValidationError::MemorySizeTooLarge);
+        }
+        Ok(())
+    }
+
+    fn check_mem_type(mem: &MemType) -> Result<()> {
+        Self::check_limits(mem.limits(), mem.max_pages())?;
+        if mem.limits().is_shared() && mem.limits().max().is_none() {
+            return Err(ValidationError::SharedMemoryWithoutMax);
+        }
+        Ok(())
+    }
+
+    fn check_table_type(&self, table: &TableType) -> Result<()> {
+        if let Some(max) = table.limits().max()
+            && table.limits().min() > max
+        {
+            return Err(ValidationError::SizeMinimumExceedsMaximum);
+        }
+        self.check_heap_type(table.elem_type().heap_type())
+    }
+
+    fn check_imports(&self) -> Result<()> {
+        let importsec = match &self.module.importsec {
+            Some(importsec) => importsec,
+            None => return Ok(()),
+        };
+        let mut tags = 0;
+        for import in importsec.imports() {
+            match import.desc() {
+                ImportDesc::Func(ty) => {
+                    self.check_func_type_idx(*ty)?;
+                }
+                ImportDesc::Table(ty) => self.check_table_type(ty)?,
+                ImportDesc::Mem(ty) => Self::check_mem_type(ty)?,
+                ImportDesc::Global(ty) => self.check_val_type(&ty.val_type())?,
+                ImportDesc::Tag(ty) => {
+                    self.check_tag_type(TagIdx(tags), ty.type_idx())?;
+                    tags += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_tag_type(&self, tag: TagIdx, ty: TypeIdx) -> Result<()> {
+        if !self.check_func_type_idx(ty)?.results().is_empty() {
+            return Err(ValidationError::TagResultNotEmpty(tag));
+        }
+        Ok(())
+    }
+
+    /// Checks that `expr` is a constant expression producing a value of
+    /// type `expected`. Only the first `globals` globals may be read.
+    fn check_const_expr(&self, expr: &Expr, expected: ValType, globals: usize) -> Result<()> {
+        let i32 = ValType::Num(NumType::I32);
+        let i64 = ValType::Num(NumType::I64);
+        let mut stack: Vec<ValType> = Vec::new();
+        let binary = |stack: &mut Vec<ValType>, t: ValType| -> Result<()> {
+            let operands = stack.split_off(stack.len().saturating_sub(2));
+            if operands != [t, t] {
+                return Err(ValidationError::TypeMismatch {
+                    expected: vec![t, t],
+                    actual: operands,
+                });
+            }
+            stack.push(t);
+            Ok(())
+        };
+        for instr in expr.instrs() {
+            match instr {
+                Instr::I32Const(_) => stack.push(i32),
+                Instr::I64Const(_) => stack.push(i64),
+                Instr::F32Const(_) => stack.push(ValType::Num(NumType::F32)),
+                Instr::F64Const(_) => stack.push(ValType::Num(NumType::F64)),
+                Instr::Simd(SimdOp::V128Const, _) => stack.push(ValType::Vec(VecType::V128)),
+                Instr::RefNull(heap) => {
+                    self.check_heap_type(*heap)?;
+                    stack.push(ValType::Ref(RefType::new(true, *heap)));
+                }
+                Instr::RefFunc(idx) => {
+                    self.check_func(*idx)?;
+                    let ty = self.funcs[idx.0 as usize];
+                    stack.push(ValType::Ref(RefType::new(false, HeapType::Concrete(ty))));
+                }
+                Instr::GlobalGet(idx) => {
+                    check_idx(IndexSpace::Global, idx.0, globals)?;
+                    let global = self.check_global(*idx)?;
+                    if global.is_mutable() {
+                        return Err(ValidationError::ConstExprRequired);
+                    }
+                    stack.push(global.val_type());
+                }
+                // Extended constant expressions
+                Instr::Numeric(
+                    NumericInstr::I32Add | NumericInstr::I32Sub | NumericInstr::I32Mul,
+                ) => binary(&mut stack, i32)?,
+                Instr::Numeric(
+                    NumericInstr::I64Add | NumericInstr::I64Sub | NumericInstr::I64Mul,
+                ) => binary(&mut stack, i64)?,
+                Instr::RefI31 => {
+                    if stack.pop() != Some(i32) {
+                        return Err(ValidationError::TypeMismatch {
+                            expected: vec![i32],
+                            actual: stack,
+                        });
+                    }
+                    stack.push(ValType::Ref(RefType::new(false, HeapType::I31)));
+                }
+                Instr::End => break,
+                _ => return Err(ValidationError::ConstExprRequired),
+            }
+        }
+        match stack.as_slice() {
+            [t] if self.matches(t, &expected) => Ok(()),
+            _ => Err(ValidationError::TypeMismatch {
+                expected: vec![expected],
+                actual: stack,
+            }),
+        }
+    }
+
+    fn check_definitions(&self) -> Result<()> {
+        if let Some(functionsec) = &self.module.functionsec {
+            for ty in functionsec.funcs() {
+                self.check_func_type_idx(*ty)?;
+            }
+        }
+        let funcs = self.funcs.len() - self.imported_funcs;
+        let codes = match &self.module.codesec {
+            Some(codesec) => codesec.codes().len(),
+            None => 0,
+        };
+        if funcs != codes {
+            return Err(ValidationError::FunctionCodeMismatch { funcs, codes });
+        }
+
+        if let Some(tablesec) = &self.module.tablesec {
+            for table in tablesec.tables() {
+                let ty = table.table_type();
+                self.check_table_type(ty)?;
+                let elem_type = ValType::Ref(ty.elem_type());
+                match table.init() {
+                    Some(init) => self.check_const_expr(init, elem_type, self.imported_globals)?,
+                    None if !ty.elem_type().is_nullable() => {
+                        return Err(ValidationError::TypeMismatch {
+                            expected: vec![elem_type],
+                            actual: Vec::new(),
+                        });
+                    }
+                    None => {}
+                }
+            }
+        }
+        if let Some(memsec) = &self.module.memsec {
+            for mem in memsec.mems() {
+                Self::check_mem_type(mem)?;
+            }
+        }
+        if let Some(tagsec) = &self.module.tagsec {
+            let imported = self.tags.len() - tagsec.tags().len();
+            for (i, tag) in tagsec.tags().iter().enumerate() {
+                self.check_tag_type(TagIdx((imported + i) as u32), tag.type_idx())?;
+            }
+        }
+        if let Some(globalsec) = &self.module.globalsec {
+            for (i, global) in globalsec.globals().iter().enumerate() {
+                let ty = global.global_type();
+                self.check_val_type(&ty.val_type())?;
+                // Initializers may read imported and earlier globals
+                self.check_const_expr(global.init(), ty.val_type(), self.imported_globals + i)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_exports(&self) -> Result<()> {
+        let exportsec = match &self.module.exportsec {
+            Some(exportsec) => exportsec,
+            None => return Ok(()),
+        };
+        let mut names = HashSet::new();
+        for export in exportsec.exports() {
+            match export.desc() {
+                ExportDesc::Func(idx) => self.check_func(idx)?,
+                ExportDesc::Table(idx) => {
+                    self.check_table(idx)?;
+                }
+                ExportDesc::Mem(idx) => {
+                    self.check_mem(idx)?;
+                }
+                ExportDesc::Global(idx) => {
+                    self.check_global(idx)?;
+                }
+                ExportDesc::Tag(idx) => self.check_tag(idx)?,
+            }
+            if !names.insert(export.name()) {
+                return Err(ValidationError::DuplicateExport(export.name().to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_start(&self) -> Result<()> {
+        if let Some(startsec) = &self.module.startsec {
+            let func = startsec.func();
+            self.check_func(func)?;
+            match self.func_type(func) {
+                Some(ty) if ty.params().is_empty() && ty.results().is_empty() => {}
+                _ => return Err(ValidationError::InvalidStartFunction(func)),
+            }
+        }
+        Ok(())
+    }
+
+    fn check_segments(&self) -> Result<()> {
+        let globals = self.globals.len();
+        if let Some(elemsec) = &self.module.elemsec {
+            for elem in elemsec.elems() {
+                let ty = ValType::Ref(elem.ref_type());
+                self.check_val_type(&ty)?;
+                if let ElemMode::Active { table, offset } = elem.mode() {
+                    let table = self.check_table(*table)?;
+                    self.check_const_expr(offset, Self::index_type(table.limits()), globals)?;
+                    if !self.matches(&ty, &ValType::Ref(table.elem_type())) {
+                        return Err(ValidationError::TypeMismatch {
+                            expected: vec![ValType::Ref(table.elem_type())],
+                            actual: vec![ty],
+                        });
+                    }
+                }
+                match elem.init() {
+                    ElemInit::Funcs(funcs) => {
+                        for func in funcs {
+                            self.check_func(*func)?;
+                        }
+                    }
+                    ElemInit::Exprs(exprs) => {
+                        for expr in exprs {
+                            self.check_const_expr(expr, ty, globals)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let datas = match &self.module.datasec {
+            Some(datasec) => {
+                for data in datasec.datas() {
+                    if let DataMode::Active { mem, offset } = data.mode() {
+                        let mem = self.check_mem(*mem)?;
+                        self.check_const_expr(offset, ValType::Num(mem.index_type()), globals)?;
+                    }
+                }
+                datasec.datas().len()
+            }
+            None => 0,
+        };
+        if let Some(count) = self.datas
+            && count as usize != datas
+        {
+            return Err(ValidationError::DataCountMismatch { count, datas });
+        }
+        Ok(())
+    }
+
+    fn index_type(limits: &Limits) -> ValType {
+        if limits.is_64() {
+            ValType::Num(NumType::I64)
+        } else {
+            ValType::Num(NumType::I32)
+        }
+    }
+
+    /// Collects the functions that function bodies may take a reference
+    /// to: those referenced outside of function bodies.
+    fn collect_declared_refs(&mut self) {
+        let mut refs = HashSet::new();
+        let add_expr = |refs: &mut HashSet<u32>, expr: &Expr| {
+            for instr in expr.instrs() {
+                if let Instr::RefFunc(idx) = instr {
+                    refs.insert(idx.0);
+                }
+            }
+        };
+        if let Some(elemsec) = &self.module.elemsec {
+            for elem in elemsec.elems() {
+                match elem.init() {
+                    ElemInit::Funcs(funcs) => refs.extend(funcs.iter().map(|f| f.0)),
+                    ElemInit::Exprs(exprs) => {
+                        for expr in exprs {
+                            add_expr(&mut refs, expr);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(globalsec) = &self.module.globalsec {
+            for global in globalsec.globals() {
+                add_expr(&mut refs, global.init());
+            }
+        }
+        if let Some(tablesec) = &self.module.tablesec {
+            for init in tablesec.tables().iter().filter_map(|table| table.init()) {
+                add_expr(&mut refs, init);
+            }
+        }
+        if let Some(exportsec) = &self.module.exportsec {
+            for export in exportsec.exports() {
+                if let ExportDesc::Func(idx) = export.desc() {
+                    refs.insert(idx.0);
+                }
+            }
+        }
+        self.declared_refs = refs;
+    }
+
+    /// Type checks every function body, recording the operand stack
+    /// heights along the way.
+    fn check_codes(&mut self) -> Result<()> {
+        let codesec = match &self.module.codesec {
+            Some(codesec) => codesec,
+            None => return Ok(()),
+        };
+        let mut stacks = Vec::with_capacity(codesec.codes().len());
+        for (i, code) in codesec.codes().iter().enumerate() {
+            let func = FuncIdx((self.imported_funcs + i) as u32);
+            let ty = self
+                .func_type(func)
+                .ok_or(ValidationError::UnknownIndex(IndexSpace::Func, func.0))?;
+            code.locals()
+                .iter()
+                .try_fold(ty.params().len() as u64, |n, l| {
+                    let n = n + u64::from(l.count());
+                    (n <= u64::from(u32::MAX)).then_some(n)
+                })
+                .ok_or(ValidationError::TooManyLocals)?;
+            for l in code.locals() {
+                self.check_val_type(&l.val_type())?;
+            }
+            stacks.push(FuncValidator::new(self, ty, code).check(func, code)?);
+        }
+        self.stacks = stacks;
+        Ok(())
+    }
+}
+
+/// Checks that a parsed module is valid, returning a view of it whose index
+/// spaces can be relied upon.
+pub fn validate(module: &Module) -> Result<ValidatedModule<'_>> {
+    validate_with_features(module, &Features::default())
+}
+
+/// Like `validate`, but also rejects modules that use a proposal that is
+/// not enabled in `features`.
+pub fn validate_with_features<'a>(
+    module: &'a Module,
+    features: &Features,
+) -> Result<ValidatedModule<'a>> {
+    features
+        .check(module)
+        .map_err(ValidationError::FeatureNotEnabled)?;
+    let mut ctx = ValidatedModule::new(module);
+    ctx.check_types()?;
+    ctx.check_imports()?;
+    ctx.check_definitions()?;
+    ctx.check_exports()?;
+    ctx.check_start()?;
+    ctx.check_segments()?;
+    ctx.collect_declared_refs();
+    ctx.check_codes()?;
+    Ok(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parseable::ParseError;
+    use std::io::{BufReader, Cursor};
+
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+    // A single [] -> [] function type
+    const TYPES: [u8; 6] = [0x01, 0x04, 0x01, 0x60, 0x00, 0x00];
+    // One function of type 0
+    const FUNCS: [u8; 4] = [0x03, 0x02, 0x01, 0x00];
+
+    fn parse(sections: &[&[u8]]) -> Module {
+        let mut bytes = HEADER.to_vec();
+        for section in sections {
+            bytes.extend_from_slice(section);
+        }
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        match Module::parse(&mut reader) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        }
+    }
+
+    // A code section with a single body without locals
+    fn code(body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x0a, body.len() as u8 + 3, 0x01, body.len() as u8 + 1, 0x00];
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    fn error(sections: &[&[u8]]) -> ValidationError {
+        match validate(&parse(sections)) {
+            Ok(_) => panic!("Expected a validation error"),
+            Err(err) => err,
+        }
+    }
+
+    // The error in a function body, without its location
+    fn body_error(sections: &[&[u8]]) -> ValidationError {
+        match error(sections) {
+            ValidationError::InFunc { error, .. } => *error,
+            err => panic!("Expected an error in a function body: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_valid_module() {
+        let module = parse(&[&TYPES, &FUNCS, &[0x08, 0x01, 0x00], &code(&[0x0b])]);
+        let validated = validate(&module).expect("A valid module");
+        assert_eq!(validated.num_funcs(), 1);
+        assert_eq!(validated.num_imported_funcs(), 0);
+        let ty = validated.func_type(FuncIdx(0)).expect("A function type");
+        assert!(ty.params().is_empty() && ty.results().is_empty());
+        assert!(validated.code(FuncIdx(0)).is_some());
+        assert!(validated.func_type(FuncIdx(1)).is_none());
+    }
+
+    #[test]
+    fn test_index_spaces() {
+        let err = error(&[&TYPES, &FUNCS, &[0x08, 0x01, 0x01], &code(&[0x0b])]);
+        assert_eq!(err, ValidationError::UnknownIndex(IndexSpace::Func, 1));
+        assert_eq!(err.to_string(), "unknown function 1");
+
+        // Function 0 has type 1, which does not exist
+        let err = error(&[&TYPES, &[0x03, 0x02, 0x01, 0x01], &code(&[0x0b])]);
+        assert_eq!(err, ValidationError::UnknownIndex(IndexSpace::Type, 1));
+
+        // A function without a body
+        let err = error(&[&TYPES, &FUNCS]);
+        assert_eq!(
+            err,
+            ValidationError::FunctionCodeMismatch { funcs: 1, codes: 0 }
+        );
+
+        // br 1 outside of any block
+        let err = body_error(&[&TYPES, &FUNCS, &code(&[0x0c, 0x01, 0x0b])]);
+        assert_eq!(err, ValidationError::UnknownIndex(IndexSpace::Label, 1));
+        let module = parse(&[&TYPES, &FUNCS, &code(&[0x0c, 0x00, 0x0b])]);
+        assert!(validate(&module).is_ok());
+    }
+
+    #[test]
+    fn test_exports() {
+        let exports = [
+            0x07, 0x09, 0x02, 0x01, b'f', 0x00, 0x00, 0x01, b'f', 0x00, 0x00,
+        ];
+        let err = error(&[&TYPES, &FUNCS, &exports, &code(&[0x0b])]);
+        assert_eq!(err, ValidationError::DuplicateExport("f".to_string()));
+    }
+
+    #[test]
+    fn test_limits() {
+        let err = error(&[&[0x05, 0x04, 0x01, 0x01, 0x02, 0x01]]);
+        assert_eq!(err, ValidationError::SizeMinimumExceedsMaximum);
+
+        // 65537 pages
+        let err = error(&[&[0x05, 0x05, 0x01, 0x00, 0x81, 0x80, 0x04]]);
+        assert_eq!(err, ValidationError::MemorySizeTooLarge);
+
+        // Shared without a maximum
+        let err = error(&[&[0x05, 0x03, 0x01, 0x02, 0x01]]);
+        assert_eq!(err, ValidationError::SharedMemoryWithoutMax);
+    }
+
+    #[test]
+    fn test_const_exprs() {
+        // (global i32 (i64.const 0))
+        let err = error(&[&[0x06, 0x06, 0x01, 0x7f, 0x00, 0x42, 0x00, 0x0b]]);
+        assert_eq!(
+            err,
+            ValidationError::TypeMismatch {
+                expected: vec![ValType::Num(NumType::I32)],
+                actual: vec![ValType::Num(NumType::I64)],
+            }
+        );
+
+        // (global i32 (local.get 0))
+        let err = error(&[&[0x06, 0x06, 0x01, 0x7f, 0x00, 0x20, 0x00, 0x0b]]);
+        assert_eq!(err, ValidationError::ConstExprRequired);
+
+        // (global i32 (i32.add (i32.const 1) (i32.const 2)))
+        let module = parse(&[&[
+            0x06, 0x09, 0x01, 0x7f, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x0b,
+        ]]);
+        assert!(validate(&module).is_ok());
+
+        // A global may only read the globals before it
+        let err = error(&[&[
+            0x06, 0x0b, 0x02, 0x7f, 0x00, 0x23, 0x01, 0x0b, 0x7f, 0x00, 0x41, 0x00, 0x0b,
+        ]]);
+        assert_eq!(err, ValidationError::UnknownIndex(IndexSpace::Global, 1));
+    }
+
+    #[test]
+    fn test_data_count() {
+        // data.drop 0 without a data count section
+        let err = body_error(&[&TYPES, &FUNCS, &code(&[0xfc, 0x09, 0x00, 0x0b])]);
+        assert_eq!(err, ValidationError::DataCountRequired);
+
+        // A data count of one, but no data segments
+        let err = error(&[
+            &TYPES,
+            &FUNCS,
+            &[0x0c, 0x01, 0x01],
+            &code(&[0xfc, 0x09, 0x00, 0x0b]),
+        ]);
+        assert_eq!(
+            err,
+            ValidationError::DataCountMismatch { count: 1, datas: 0 }
+        );
+
+        // The data count section came with bulk memory operations
+        let module = parse(&[&TYPES, &FUNCS, &[0x0c, 0x01, 0x00], &code(&[0x0b])]);
+        assert!(validate_with_features(&module, &Features::wasm2()).is_ok());
+        let err = validate_with_features(&module, &Features::mvp()).err();
+        assert_eq!(
+            err,
+            Some(ValidationError::FeatureNotEnabled(Feature::BulkMemory))
+        );
+    }
+
+    #[test]
+    fn test_func_refs() {
+        // ref.func 0; drop
+        let body = code(&[0xd2, 0x00, 0x1a, 0x0b]);
+        let err = body_error(&[&TYPES, &FUNCS, &body]);
+        assert_eq!(err, ValidationError::UndeclaredFuncRef(FuncIdx(0)));
+
+        // Exporting the function declares it
+        let exports = [0x07, 0x05, 0x01, 0x01, b'f', 0x00, 0x00];
+        let module = parse(&[&TYPES, &FUNCS, &exports, &body]);
+        assert!(validate(&module).is_ok());
+    }
+}
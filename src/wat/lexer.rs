@@ -0,0 +1,354 @@
+use crate::parseable::ParserLimits;
+use crate::wat::{Pos, WatError};
+
+/// A token or parenthesized list of the text format. Comments are gone,
+/// and so are annotations other than `@custom`, which is kept as a list
+/// headed by the atom `@custom`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexpr {
+    /// A keyword, number or other run of identifier characters.
+    Atom(String, Pos),
+    /// An identifier, without its `$`.
+    Id(String, Pos),
+    Str(Vec<u8>, Pos),
+    /// The items of a list, where it starts and where its `)` is.
+    List(Vec<Sexpr>, Pos, Pos),
+}
+
+impl Sexpr {
+    pub fn pos(&self) -> Pos {
+        match self {
+            Sexpr::Atom(_, pos) | Sexpr::Id(_, pos) | Sexpr::Str(_, pos) => *pos,
+            Sexpr::List(_, pos, _) => *pos,
+        }
+    }
+
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            Sexpr::Atom(atom, _) => Some(atom),
+            _ => None,
+        }
+    }
+
+    /// The keyword a list starts with, like `func` in `(func ...)`.
+    pub fn head(&self) -> Option<&str> {
+        match self {
+            Sexpr::List(items, _, _) => items.first().and_then(Sexpr::as_atom),
+            _ => None,
+        }
+    }
+}
+
+fn is_idchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&b)
+}
+
+struct Lexer<'a> {
+    text: &'a [u8],
+    at: usize,
+    line: usize,
+    col: usize,
+}
+
+enum Token {
+    Open(Pos),
+    // `(@name`, the start of an annotation
+    Annotation(String, Pos),
+    Close(Pos),
+    Item(Sexpr),
+}
+
+impl<'a> Lexer<'a> {
+    fn pos(&self) -> Pos {
+        Pos {
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.at).copied()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.text.get(self.at + n).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.at += 1;
+        if b == b'\n' {
+            self.line += 1;
+            self.col = 1;
+        } else if b & 0xc0 != 0x80 {
+            // Continuation bytes of UTF-8 don't start a new column
+            self.col += 1;
+        }
+        Some(b)
+    }
+
+    fn error(&self, message: &str) -> WatError {
+        WatError::new(self.pos(), message)
+    }
+
+    // Skips whitespace and comments
+    fn skip(&mut self) -> Result<(), WatError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(b' ' | b'\t' | b'\n' | b'\r'), _) => {
+                    self.bump();
+                }
+                (Some(b';'), Some(b';')) => {
+                    while let Some(b) = self.peek()
+                        && b != b'\n'
+                    {
+                        self.bump();
+                    }
+                }
+                (Some(b'('), Some(b';')) => {
+                    let start = self.pos();
+                    self.bump();
+                    self.bump();
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match (self.peek(), self.peek_at(1)) {
+                            (Some(b'('), Some(b';')) => {
+                                self.bump();
+                                self.bump();
+                                depth += 1;
+                            }
+                            (Some(b';'), Some(b')')) => {
+                                self.bump();
+                                self.bump();
+                                depth -= 1;
+                            }
+                            (Some(_), _) => {
+                                self.bump();
+                            }
+                            (None, _) => {
+                                return Err(WatError::new(start, "unclosed block comment"));
+                            }
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<Option<Token>, WatError> {
+        self.skip()?;
+        let pos = self.pos();
+        let Some(b) = self.peek() else {
+            return Ok(None);
+        };
+        let token = match b {
+            b'(' => {
+                self.bump();
+                if self.peek() == Some(b'@') {
+                    let name = self.idchars();
+                    Token::Annotation(name, pos)
+                } else {
+                    Token::Open(pos)
+                }
+            }
+            b')' => {
+                self.bump();
+                Token::Close(pos)
+            }
+            b'"' => Token::Item(Sexpr::Str(self.string()?, pos)),
+            b'$' if self.peek_at(1) == Some(b'"') => {
+                self.bump();
+                let bytes = self.string()?;
+                match String::from_utf8(bytes) {
+                    Ok(id) if !id.is_empty() => Token::Item(Sexpr::Id(id, pos)),
+                    _ => return Err(WatError::new(pos, "malformed identifier")),
+                }
+            }
+            b'$' => {
+                let atom = self.idchars();
+                if atom.len() == 1 {
+                    return Err(WatError::new(pos, "empty identifier"));
+                }
+                Token::Item(Sexpr::Id(atom[1..].to_string(), pos))
+            }
+            b if is_idchar(b) => Token::Item(Sexpr::Atom(self.idchars(), pos)),
+            _ => return Err(self.error("unexpected character")),
+        };
+        // Tokens must be separated by whitespace or parentheses
+        match self.peek() {
+            None | Some(b' ' | b'\t' | b'\n' | b'\r' | b'(' | b')' | b';') => Ok(Some(token)),
+            Some(_) if matches!(token, Token::Open(_) | Token::Close(_)) => Ok(Some(token)),
+            Some(_) => Err(self.error("unknown operator")),
+        }
+    }
+
+    fn idchars(&mut self) -> String {
+        let start = self.at;
+        while let Some(b) = self.peek()
+            && is_idchar(b)
+        {
+            self.bump();
+        }
+        // Identifier characters are all ASCII
+        String::from_utf8_lossy(&self.text[start..self.at]).into_owned()
+    }
+
+    fn hex_digit(&mut self) -> Result<u32, WatError> {
+        let digit = self.peek().and_then(|b| (b as char).to_digit(16));
+        match digit {
+            Some(digit) => {
+                self.bump();
+                Ok(digit)
+            }
+            None => Err(self.error("malformed escape")),
+        }
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, WatError> {
+        let start = self.pos();
+        self.bump();
+        let mut bytes = Vec::new();
+        loop {
+            let pos = self.pos();
+            match self.bump() {
+                None => return Err(WatError::new(start, "unclosed string")),
+                Some(b'"') => return Ok(bytes),
+                Some(b'\\') => match self.bump() {
+                    Some(b't') => bytes.push(b'\t'),
+                    Some(b'n') => bytes.push(b'\n'),
+                    Some(b'r') => bytes.push(b'\r'),
+                    Some(b'"') => bytes.push(b'"'),
+                    Some(b'\'') => bytes.push(b'\''),
+                    Some(b'\\') => bytes.push(b'\\'),
+                    Some(b'u') => {
+                        if self.bump() != Some(b'{') {
+                            return Err(WatError::new(pos, "malformed escape"));
+                        }
+                        let mut n: u32 = self.hex_digit()?;
+                        while self.peek() != Some(b'}') {
+                            if self.peek() == Some(b'_') {
+                                self.bump();
+                            }
+                            n = n
+                                .checked_mul(16)
+                                .and_then(|n| n.checked_add(self.hex_digit().ok()?))
+                                .ok_or_else(|| WatError::new(pos, "malformed escape"))?;
+                        }
+                        self.bump();
+                        let c = char::from_u32(n)
+                            .ok_or_else(|| WatError::new(pos, "malformed escape"))?;
+                        let mut buf = [0; 4];
+                        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    }
+                    Some(b) if b.is_ascii_hexdigit() => {
+                        let high = (b as char).to_digit(16).unwrap_or(0);
+                        let low = self.hex_digit()?;
+                        bytes.push((high * 16 + low) as u8);
+                    }
+                    _ => return Err(WatError::new(pos, "malformed escape")),
+                },
+                Some(b) if b < 0x20 || b == 0x7f => {
+                    return Err(WatError::new(pos, "control character in string"));
+                }
+                Some(b) => bytes.push(b),
+            }
+        }
+    }
+}
+
+/// Splits `text` into tokens and groups them by their parentheses.
+pub fn sexprs(text: &str) -> Result<Vec<Sexpr>, WatError> {
+    let mut lexer = Lexer {
+        text: text.as_bytes(),
+        at: 0,
+        line: 1,
+        col: 1,
+    };
+    // The lists being read, innermost last, each with the position of its
+    // `(` and whether it is an annotation to be dropped
+    let mut open: Vec<(Vec<Sexpr>, Pos, bool)> = Vec::new();
+    let mut top = Vec::new();
+    // Lists are walked recursively once read, so their depth is limited
+    let max_nesting = ParserLimits::current().max_nesting;
+    while let Some(token) = lexer.token()? {
+        if let Token::Open(pos) | Token::Annotation(_, pos) = &token
+            && open.len() >= max_nesting
+        {
+            return Err(WatError::new(
+                *pos,
+                format!("nesting exceeds the limit of {}", max_nesting),
+            ));
+        }
+        let item = match token {
+            Token::Open(pos) => {
+                open.push((Vec::new(), pos, false));
+                continue;
+            }
+            Token::Annotation(name, pos) => {
+                let dropped = name != "@custom";
+                open.push((vec![Sexpr::Atom(name, pos)], pos, dropped));
+                continue;
+            }
+            Token::Close(end) => match open.pop() {
+                Some((_, _, true)) => continue,
+                Some((items, pos, false)) => Sexpr::List(items, pos, end),
+                None => return Err(WatError::new(end, "unexpected `)`")),
+            },
+            Token::Item(item) => item,
+        };
+        match open.last_mut() {
+            Some((items, _, _)) => items.push(item),
+            None => top.push(item),
+        }
+    }
+    match open.last() {
+        Some((_, pos, _)) => Err(WatError::new(*pos, "unclosed `(`")),
+        None => Ok(top),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line: usize, col: usize) -> Pos {
+        Pos { line, col }
+    }
+
+    #[test]
+    fn test_sexprs() {
+        let text = "(module $m ;; comment\n  (; block (; nested ;) ;) (@name \"x\")\n  (func \"a\\n\\41\\u{e9}\"))";
+        let sexprs = sexprs(text).expect("The s-expressions");
+        assert_eq!(
+            sexprs,
+            vec![Sexpr::List(
+                vec![
+                    Sexpr::Atom("module".to_string(), pos(1, 2)),
+                    Sexpr::Id("m".to_string(), pos(1, 9)),
+                    Sexpr::List(
+                        vec![
+                            Sexpr::Atom("func".to_string(), pos(3, 4)),
+                            Sexpr::Str(b"a\nA\xc3\xa9".to_vec(), pos(3, 9)),
+                        ],
+                        pos(3, 3),
+                        pos(3, 23)
+                    ),
+                ],
+                pos(1, 1),
+                pos(3, 24)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = sexprs("(module\n  (func))\n)").expect_err("An unbalanced list");
+        assert_eq!(err.to_string(), "3:1: unexpected `)`");
+        let err = sexprs("(func \"abc").expect_err("An unclosed string");
+        assert_eq!(err.pos, pos(1, 7));
+        let err = sexprs("(i32.const 1\"x\")").expect_err("Unseparated tokens");
+        assert_eq!(err.pos, pos(1, 13));
+        assert!(sexprs("\"\\q\"").is_err());
+    }
+}
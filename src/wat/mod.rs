@@ -0,0 +1,42 @@
+use std::fmt::Display;
+
+mod lexer;
+mod num;
+pub mod parse;
+pub mod print;
+pub mod wast;
+
+/// A position in the source text. Lines and columns count from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// An error in a module or script in the text format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatError {
+    pub pos: Pos,
+    pub message: String,
+}
+
+impl WatError {
+    pub fn new(pos: Pos, message: impl Into<String>) -> WatError {
+        WatError {
+            pos,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for WatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.pos, self.message)
+    }
+}
@@ -0,0 +1,272 @@
+// Numeric literals of the text format. Each function returns `None` if
+// the text is not a literal of its kind or is out of range.
+
+// Strips the `_` separators from a run of digits, which may only appear
+// between two digits
+fn digits(text: &str, radix: u32) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut prev_digit = false;
+    for (i, c) in text.char_indices() {
+        if c == '_' {
+            let next_digit = text[i + 1..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_digit(radix));
+            if !prev_digit || !next_digit {
+                return None;
+            }
+            prev_digit = false;
+        } else if c.is_digit(radix) {
+            out.push(c);
+            prev_digit = true;
+        } else {
+            return None;
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+fn sign(text: &str) -> (Option<bool>, &str) {
+    if let Some(rest) = text.strip_prefix('-') {
+        (Some(true), rest)
+    } else if let Some(rest) = text.strip_prefix('+') {
+        (Some(false), rest)
+    } else {
+        (None, text)
+    }
+}
+
+/// An unsigned integer without a sign, like an index or an alignment.
+pub fn uint(text: &str) -> Option<u64> {
+    let (radix, text) = match text.strip_prefix("0x") {
+        Some(hex) => (16, hex),
+        None => (10, text),
+    };
+    u64::from_str_radix(&digits(text, radix)?, radix).ok()
+}
+
+/// An integer of `bits` bits, as its two's complement. Without a sign it
+/// may use the whole unsigned range; with one, only the signed range.
+pub fn int(text: &str, bits: u32) -> Option<u64> {
+    let (sign, rest) = sign(text);
+    let n = uint(rest)?;
+    let mask = u64::MAX >> (64 - bits);
+    let half = 1u64 << (bits - 1);
+    match sign {
+        None if n <= mask => Some(n),
+        Some(false) if n < half => Some(n),
+        Some(true) if n <= half => Some(n.wrapping_neg() & mask),
+        _ => None,
+    }
+}
+
+/// The bits of a float with `mant` mantissa bits and `exp` exponent bits.
+fn float(text: &str, mant: u32, exp: u32) -> Option<u64> {
+    let (negative, rest) = sign(text);
+    let sign = u64::from(negative == Some(true)) << (mant + exp);
+    let inf = ((1u64 << exp) - 1) << mant;
+    let bits = if rest == "inf" {
+        inf
+    } else if rest == "nan" {
+        inf | 1 << (mant - 1)
+    } else if let Some(payload) = rest.strip_prefix("nan:0x") {
+        let payload = u64::from_str_radix(&digits(payload, 16)?, 16).ok()?;
+        if payload == 0 || payload >= 1 << mant {
+            return None;
+        }
+        inf | payload
+    } else if let Some(hex) = rest.strip_prefix("0x") {
+        hex_float(hex, mant, exp)?
+    } else {
+        decimal_float(rest, mant)?
+    };
+    Some(sign | bits)
+}
+
+/// Whether `text` has the form of a numeric literal, whether or not it is
+/// in range for any type.
+pub fn is_literal(text: &str) -> bool {
+    let (_, rest) = sign(text);
+    if rest == "inf" || rest == "nan" {
+        return true;
+    }
+    if let Some(payload) = rest.strip_prefix("nan:0x") {
+        return digits(payload, 16).is_some();
+    }
+    match rest.strip_prefix("0x") {
+        Some(hex) => float_parts(hex, 16, 'p').is_some(),
+        None => float_parts(rest, 10, 'e').is_some(),
+    }
+}
+
+/// The bits of an `f32` literal.
+pub fn f32(text: &str) -> Option<u32> {
+    float(text, 23, 8).map(|bits| bits as u32)
+}
+
+/// The bits of an `f64` literal.
+pub fn f64(text: &str) -> Option<u64> {
+    float(text, 52, 11)
+}
+
+// Splits `digits[.digits][e[+-]digits]` into its parts, with the
+// separators removed
+fn float_parts(text: &str, radix: u32, exp_char: char) -> Option<(String, String, Option<String>)> {
+    let (mantissa, exponent) = match text.find([exp_char, exp_char.to_ascii_uppercase()]) {
+        Some(at) => (&text[..at], Some(&text[at + 1..])),
+        None => (text, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, frac),
+        None => (mantissa, ""),
+    };
+    let int = digits(int, radix)?;
+    let frac = if frac.is_empty() {
+        String::new()
+    } else {
+        digits(frac, radix)?
+    };
+    let exponent = match exponent {
+        Some(exponent) => {
+            let (sign, rest) = sign(exponent);
+            let sign = if sign == Some(true) { "-" } else { "" };
+            Some(format!("{}{}", sign, digits(rest, 10)?))
+        }
+        None => None,
+    };
+    Some((int, frac, exponent))
+}
+
+fn decimal_float(text: &str, mant: u32) -> Option<u64> {
+    let (int, frac, exponent) = float_parts(text, 10, 'e')?;
+    let mut canonical = format!("{}.{}", int, frac);
+    if let Some(exponent) = exponent {
+        canonical += &format!("e{}", exponent);
+    }
+    // Rust's parsing rounds to nearest, ties to even, like the text format
+    let bits = if mant == 23 {
+        let v: f32 = canonical.parse().ok()?;
+        (!v.is_infinite()).then_some(u64::from(v.to_bits()))?
+    } else {
+        let v: f64 = canonical.parse().ok()?;
+        (!v.is_infinite()).then_some(v.to_bits())?
+    };
+    Some(bits)
+}
+
+fn hex_float(text: &str, mant: u32, exp: u32) -> Option<u64> {
+    let (int, frac, exponent) = float_parts(text, 16, 'p')?;
+    // The value is m * 2^e, with the digits that don't fit in m kept only
+    // as a sticky bit for rounding
+    let mut m: u64 = 0;
+    let mut e: i64 = 0;
+    let mut sticky = false;
+    for (i, c) in int.chars().chain(frac.chars()).enumerate() {
+        let d = u64::from(c.to_digit(16)?);
+        let in_frac = i >= int.len();
+        if m >> 60 == 0 {
+            m = m << 4 | d;
+            if in_frac {
+                e -= 4;
+            }
+        } else {
+            sticky |= d != 0;
+            if !in_frac {
+                e += 4;
+            }
+        }
+    }
+    if let Some(exponent) = exponent {
+        // Anything beyond this is out of range in any format anyway
+        let exponent: i64 = exponent.parse().unwrap_or(if exponent.starts_with('-') {
+            -100_000
+        } else {
+            100_000
+        });
+        e += exponent.clamp(-100_000, 100_000);
+    }
+    if m == 0 {
+        return Some(0);
+    }
+
+    let bias = (1i64 << (exp - 1)) - 1;
+    let msb = 63 - i64::from(m.leading_zeros());
+    // The exponent of the lowest mantissa bit, for a normal number or the
+    // smallest subnormals
+    let mut low = (msb + e).max(1 - bias) - i64::from(mant);
+    let shift = low - e;
+    let mut r = if shift <= 0 {
+        u128::from(m) << -shift
+    } else if shift >= 100 {
+        // Less than half the smallest subnormal
+        0
+    } else {
+        let m = u128::from(m);
+        let kept = m >> shift;
+        let rest = m & ((1u128 << shift) - 1);
+        let half = 1u128 << (shift - 1);
+        let round_up = rest > half || (rest == half && (sticky || kept & 1 == 1));
+        kept + u128::from(round_up)
+    };
+    if r >> (mant + 1) != 0 {
+        r >>= 1;
+        low += 1;
+    }
+    if r >> mant == 0 {
+        // Subnormal, or zero after rounding
+        return Some(r as u64);
+    }
+    let biased = low + i64::from(mant) + bias;
+    if biased >= (1 << exp) - 1 {
+        return None;
+    }
+    Some((biased as u64) << mant | (r as u64 & ((1 << mant) - 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ints() {
+        assert_eq!(int("-1", 32), Some(0xffff_ffff));
+        assert_eq!(int("0xffff_ffff", 32), Some(0xffff_ffff));
+        assert_eq!(int("-0x8000_0000", 32), Some(0x8000_0000));
+        assert_eq!(int("+0x8000_0000", 32), None);
+        assert_eq!(int("0x1_0000_0000", 32), None);
+        assert_eq!(int("18446744073709551615", 64), Some(u64::MAX));
+        assert_eq!(int("1__0", 32), None);
+        assert_eq!(int("_1", 32), None);
+        assert_eq!(uint("-1"), None);
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(f32("1.5"), Some(1.5f32.to_bits()));
+        assert_eq!(f32("-0"), Some((-0.0f32).to_bits()));
+        assert_eq!(f32("1e3"), Some(1000f32.to_bits()));
+        assert_eq!(f32("1_000.000_1"), Some(1000.0001f32.to_bits()));
+        assert_eq!(f32("1e39"), None);
+        assert_eq!(f32("nan"), Some(0x7fc0_0000));
+        assert_eq!(f32("-nan:0x1"), Some(0xff80_0001));
+        assert_eq!(f32("nan:0x80_0000"), None);
+        assert_eq!(f64("-inf"), Some(f64::NEG_INFINITY.to_bits()));
+        assert_eq!(f32("0x1p-1"), Some(0.5f32.to_bits()));
+        assert_eq!(f32("0x1.8p1"), Some(3.0f32.to_bits()));
+        assert_eq!(f32("0x1p127"), Some(0x7f00_0000));
+        assert_eq!(f32("0x1p128"), None);
+        // The smallest subnormal, and halfway below it rounding to even
+        assert_eq!(f32("0x1p-149"), Some(1));
+        assert_eq!(f32("0x1p-150"), Some(0));
+        assert_eq!(f32("0x1.8p-150"), Some(1));
+        // Rounding at the last mantissa bit, ties to even
+        assert_eq!(f32("0x1.000001p0"), Some(0x3f80_0000));
+        assert_eq!(f32("0x1.000003p0"), Some(0x3f80_0002));
+        assert_eq!(f32("0x1.00000100000000001p0"), Some(0x3f80_0001));
+        assert_eq!(f64("0x1.fffffffffffff8p1023"), None);
+        assert_eq!(f64("0x1p-1074"), Some(1));
+        assert_eq!(f32("1."), Some(1f32.to_bits()));
+        assert_eq!(f32(".5"), None);
+        assert_eq!(f32("1e"), None);
+    }
+}
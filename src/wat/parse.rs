@@ -0,0 +1,2053 @@
+use std::collections::{HashMap, HashSet};
+use std::io::BufReader;
+
+use crate::instr::Instr;
+use crate::instr::atomic::AtomicOp;
+use crate::instr::expr::Expr;
+use crate::instr::numeric::{LoadOp, NumericInstr, StoreOp};
+use crate::instr::simd::{SimdImm, SimdOp};
+use crate::module::{CustomPlacement, Module, SECTION_ORDER};
+use crate::parseable::ParseError;
+use crate::section::code::{Code, CodeSec, Locals};
+use crate::section::custom::CustomSec;
+use crate::section::data::{Data, DataMode, DataSec};
+use crate::section::data_count::DataCountSec;
+use crate::section::element::{Elem, ElemInit, ElemMode, ElemSec};
+use crate::section::export::{Export, ExportSec};
+use crate::section::function::FunctionSec;
+use crate::section::global::{Global, GlobalSec};
+use crate::section::import::{Import, ImportSec};
+use crate::section::memory::MemSec;
+use crate::section::name::NameSpace;
+use crate::section::start::StartSec;
+use crate::section::table::{Table, TableSec};
+use crate::section::tag::TagSec;
+use crate::section::r#type::TypeSec;
+use crate::section::{
+    CODE_SECTION_ID, DATA_COUNT_SECTION_ID, DATA_SECTION_ID, ELEMENT_SECTION_ID, EXPORT_SECTION_ID,
+    FUNCTION_SECTION_ID, GLOBAL_SECTION_ID, IMPORT_SECTION_ID, MEMORY_SECTION_ID, START_SECTION_ID,
+    TABLE_SECTION_ID, TAG_SECTION_ID, TYPE_SECTION_ID,
+};
+use crate::types::block_type::BlockType;
+use crate::types::catch::Catch;
+use crate::types::comp_type::CompType;
+use crate::types::export_desc::ExportDesc;
+use crate::types::field_type::{FieldType, StorageType};
+use crate::types::func_type::FuncType;
+use crate::types::global_type::GlobalType;
+use crate::types::heap_type::HeapType;
+use crate::types::import_desc::ImportDesc;
+use crate::types::limits::Limits;
+use crate::types::mem_arg::MemArg;
+use crate::types::mem_type::{MemType, PAGE_SIZE};
+use crate::types::r#mut::{CONST, VAR};
+use crate::types::num_type::NumType;
+use crate::types::primitives::{
+    DataIdx, ElemIdx, FieldIdx, FuncIdx, GlobalIdx, LabelIdx, LocalIdx, MemIdx, TableIdx, TagIdx,
+    TypeIdx,
+};
+use crate::types::rec_type::RecType;
+use crate::types::ref_type::RefType;
+use crate::types::sub_type::SubType;
+use crate::types::table_type::TableType;
+use crate::types::tag_type::TagType;
+use crate::types::val_type::ValType;
+use crate::types::vec_type::VecType;
+use crate::validate::{atomic_type, load_type, simd_access_size, store_type};
+use crate::wat::lexer::{Sexpr, sexprs};
+use crate::wat::num;
+use crate::wat::{Pos, WatError};
+
+type Result<T> = std::result::Result<T, WatError>;
+
+/// Parses a module in the text format: either a `(module ...)` or just the
+/// fields that would go inside one. Identifiers are resolved to indices;
+/// they don't end up in a name section.
+pub fn parse_module(text: &str) -> Result<Module> {
+    let sexprs = sexprs(text)?;
+    match sexprs.as_slice() {
+        [module] if module.head() == Some("module") => parse_module_sexpr(module),
+        fields => module_fields(Cursor::new(fields, Pos::default())),
+    }
+}
+
+/// Lowers a `(module ...)` list, including the `(module binary ...)` and
+/// `(module quote ...)` forms of scripts.
+pub(crate) fn parse_module_sexpr(module: &Sexpr) -> Result<Module> {
+    let Some(mut cur) = Cursor::of(module) else {
+        return Err(WatError::new(module.pos(), "expected a module"));
+    };
+    if !cur.eat("module") {
+        return Err(cur.error("expected a module"));
+    }
+    cur.id();
+    if cur.eat("binary") {
+        let bytes = strings(&mut cur)?;
+        let mut reader = BufReader::new(std::io::Cursor::new(bytes));
+        return Module::parse(&mut reader)
+            .map_err(|err| WatError::new(module.pos(), ParseError::from(err).to_string()));
+    }
+    if cur.eat("quote") {
+        let bytes = strings(&mut cur)?;
+        let text = String::from_utf8(bytes)
+            .map_err(|_| WatError::new(module.pos(), "malformed UTF-8 encoding"))?;
+        return parse_module(&text);
+    }
+    module_fields(cur)
+}
+
+fn module_fields(cur: Cursor) -> Result<Module> {
+    let fields = &cur.items[cur.at..];
+    if let Some(field) = fields.iter().find(|field| field.head().is_none()) {
+        return Err(WatError::new(field.pos(), "expected a module field"));
+    }
+    let mut lowerer = Lowerer::default();
+    let mut defined = HashSet::new();
+    for field in fields {
+        lowerer.declare(field, &mut defined)?;
+    }
+    for field in fields {
+        lowerer.type_field(field)?;
+    }
+    lowerer.counts.clear();
+    for field in fields {
+        lowerer.field(field)?;
+    }
+    Ok(lowerer.finish())
+}
+
+// The items of a list, read from the front
+struct Cursor<'a> {
+    items: &'a [Sexpr],
+    at: usize,
+    // Where the list ends, for errors about missing items
+    end: Pos,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(items: &'a [Sexpr], end: Pos) -> Cursor<'a> {
+        Cursor { items, at: 0, end }
+    }
+
+    fn of(list: &'a Sexpr) -> Option<Cursor<'a>> {
+        match list {
+            Sexpr::List(items, _, end) => Some(Cursor::new(items, *end)),
+            _ => None,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Sexpr> {
+        self.items.get(self.at)
+    }
+
+    fn peek2(&self) -> Option<&'a Sexpr> {
+        self.items.get(self.at + 1)
+    }
+
+    fn next(&mut self) -> Option<&'a Sexpr> {
+        let item = self.peek()?;
+        self.at += 1;
+        Some(item)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.at >= self.items.len()
+    }
+
+    fn pos(&self) -> Pos {
+        self.peek().map_or(self.end, Sexpr::pos)
+    }
+
+    fn error(&self, message: impl Into<String>) -> WatError {
+        WatError::new(self.pos(), message)
+    }
+
+    fn peek_atom(&self) -> Option<&'a str> {
+        self.peek().and_then(Sexpr::as_atom)
+    }
+
+    fn atom(&mut self) -> Result<(&'a str, Pos)> {
+        match self.peek() {
+            Some(Sexpr::Atom(atom, pos)) => {
+                self.at += 1;
+                Ok((atom, *pos))
+            }
+            _ => Err(self.error("expected a keyword")),
+        }
+    }
+
+    fn eat(&mut self, keyword: &str) -> bool {
+        let found = self.peek_atom() == Some(keyword);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn id(&mut self) -> Option<&'a str> {
+        match self.peek() {
+            Some(Sexpr::Id(id, _)) => {
+                self.at += 1;
+                Some(id)
+            }
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Result<&'a [u8]> {
+        match self.peek() {
+            Some(Sexpr::Str(bytes, _)) => {
+                self.at += 1;
+                Ok(bytes)
+            }
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let pos = self.pos();
+        let bytes = self.string()?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| WatError::new(pos, "malformed UTF-8 encoding"))
+    }
+
+    fn peek_list(&self, head: &str) -> bool {
+        self.peek().is_some_and(|item| item.head() == Some(head))
+    }
+
+    /// The rest of the next item, if it is a list starting with `head`.
+    fn list(&mut self, head: &str) -> Option<Cursor<'a>> {
+        if !self.peek_list(head) {
+            return None;
+        }
+        let mut list = Cursor::of(self.next()?)?;
+        list.at = 1;
+        Some(list)
+    }
+
+    fn finish(&self) -> Result<()> {
+        match self.peek() {
+            Some(item) => Err(WatError::new(item.pos(), "unexpected token")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn strings(cur: &mut Cursor) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while !cur.is_empty() {
+        bytes.extend_from_slice(cur.string()?);
+    }
+    Ok(bytes)
+}
+
+fn is_index(item: &Sexpr) -> bool {
+    match item {
+        Sexpr::Id(..) => true,
+        Sexpr::Atom(atom, _) => atom.starts_with(|c: char| c.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+fn number(item: &Sexpr) -> Result<u32> {
+    item.as_atom()
+        .and_then(num::uint)
+        .and_then(|n| u32::try_from(n).ok())
+        .ok_or_else(|| WatError::new(item.pos(), "malformed index"))
+}
+
+fn space_name(space: NameSpace) -> &'static str {
+    match space {
+        NameSpace::Func => "function",
+        NameSpace::Type => "type",
+        NameSpace::Table => "table",
+        NameSpace::Mem => "memory",
+        NameSpace::Global => "global",
+        NameSpace::Elem => "elem segment",
+        NameSpace::Data => "data segment",
+        NameSpace::Tag => "tag",
+    }
+}
+
+fn space_of(kind: &str) -> Option<NameSpace> {
+    let space = match kind {
+        "func" => NameSpace::Func,
+        "table" => NameSpace::Table,
+        "memory" => NameSpace::Mem,
+        "global" => NameSpace::Global,
+        "tag" => NameSpace::Tag,
+        _ => return None,
+    };
+    Some(space)
+}
+
+fn ref_shorthand(atom: &str) -> Option<RefType> {
+    let heap = match atom {
+        "funcref" => HeapType::Func,
+        "externref" => HeapType::Extern,
+        "exnref" => HeapType::Exn,
+        "anyref" => HeapType::Any,
+        "eqref" => HeapType::Eq,
+        "i31ref" => HeapType::I31,
+        "structref" => HeapType::Struct,
+        "arrayref" => HeapType::Array,
+        "nullref" => HeapType::None,
+        "nullfuncref" => HeapType::NoFunc,
+        "nullexternref" => HeapType::NoExtern,
+        "nullexnref" => HeapType::NoExn,
+        _ => return None,
+    };
+    Some(RefType::new(true, heap))
+}
+
+fn section_id(name: &str) -> Option<TypeIdx> {
+    let id = match name {
+        "type" => TYPE_SECTION_ID,
+        "import" => IMPORT_SECTION_ID,
+        "func" => FUNCTION_SECTION_ID,
+        "table" => TABLE_SECTION_ID,
+        "memory" => MEMORY_SECTION_ID,
+        "tag" => TAG_SECTION_ID,
+        "global" => GLOBAL_SECTION_ID,
+        "export" => EXPORT_SECTION_ID,
+        "start" => START_SECTION_ID,
+        "elem" => ELEMENT_SECTION_ID,
+        "datacount" => DATA_COUNT_SECTION_ID,
+        "code" => CODE_SECTION_ID,
+        "data" => DATA_SECTION_ID,
+        _ => return None,
+    };
+    Some(id)
+}
+
+// A constant expression for the offset of an abbreviated segment
+fn zero_offset(is_64: bool) -> Expr {
+    let zero = if is_64 {
+        Instr::I64Const(0)
+    } else {
+        Instr::I32Const(0)
+    };
+    Expr::new(vec![zero, Instr::End])
+}
+
+fn limits(cur: &mut Cursor, is_64: bool) -> Result<Limits> {
+    let bound = |cur: &mut Cursor| -> Result<u64> {
+        let pos = cur.pos();
+        let (atom, _) = cur.atom()?;
+        match num::uint(atom) {
+            Some(n) if is_64 || n <= u64::from(u32::MAX) => Ok(n),
+            _ if is_64 => Err(WatError::new(pos, "i64 constant out of range")),
+            _ => Err(WatError::new(pos, "i32 constant out of range")),
+        }
+    };
+    let min = bound(cur)?;
+    let max = match cur.peek() {
+        Some(item) if is_index(item) => Some(bound(cur)?),
+        _ => None,
+    };
+    Ok(if is_64 {
+        Limits::new_64(min, max)
+    } else {
+        Limits::new(min, max)
+    })
+}
+
+// Whether an address type follows, `i64` for 64-bit tables and memories
+fn address_type(cur: &mut Cursor) -> bool {
+    let is_64 = cur.eat("i64");
+    if !is_64 {
+        cur.eat("i32");
+    }
+    is_64
+}
+
+// A function body or constant expression being lowered
+#[derive(Default)]
+struct Body<'a> {
+    locals: HashMap<&'a str, u32>,
+    // The identifiers of the enclosing blocks, innermost last
+    labels: Vec<Option<&'a str>>,
+    instrs: Vec<Instr>,
+}
+
+// Lowers the fields of a module into sections. The fields are walked
+// three times: first to give every identifier its index, then to lower
+// the explicit types, which come before any implicit types, and then to
+// lower everything else in the order it appears.
+#[derive(Default)]
+struct Lowerer<'a> {
+    ids: HashMap<(NameSpace, &'a str), u32>,
+    // The number of things given an index so far in each index space
+    counts: HashMap<NameSpace, u32>,
+    // Field identifiers, by struct type
+    fields: HashMap<(u32, &'a str), u32>,
+    types: Vec<RecType>,
+    imports: Vec<Import>,
+    funcs: Vec<TypeIdx>,
+    codes: Vec<Code>,
+    tables: Vec<Table>,
+    mems: Vec<MemType>,
+    tags: Vec<TagType>,
+    globals: Vec<Global>,
+    exports: Vec<Export>,
+    start: Option<FuncIdx>,
+    elems: Vec<Elem>,
+    datas: Vec<Data>,
+    customs: Vec<(CustomSec, CustomPlacement)>,
+}
+
+impl<'a> Lowerer<'a> {
+    fn bump(&mut self, space: NameSpace) -> u32 {
+        let count = self.counts.entry(space).or_default();
+        *count += 1;
+        *count - 1
+    }
+
+    fn declare_id(&mut self, cur: &mut Cursor<'a>, space: NameSpace) -> Result<()> {
+        let idx = self.bump(space);
+        if let Some(Sexpr::Id(id, pos)) = cur.peek() {
+            cur.next();
+            if self.ids.insert((space, id), idx).is_some() {
+                return Err(WatError::new(
+                    *pos,
+                    format!("duplicate {} ${}", space_name(space), id),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, field: &'a Sexpr, defined: &mut HashSet<NameSpace>) -> Result<()> {
+        let Some(mut cur) = Cursor::of(field) else {
+            return Err(WatError::new(field.pos(), "expected a module field"));
+        };
+        let (kind, pos) = cur.atom()?;
+        // Imports take the lowest indices, so they can't follow a
+        // definition of the same kind
+        let mut import = |space: NameSpace, imported: bool| {
+            if !imported {
+                defined.insert(space);
+                Ok(())
+            } else if defined.contains(&space) {
+                Err(WatError::new(
+                    pos,
+                    format!("import after {}", space_name(space)),
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        match kind {
+            "type" => self.declare_id(&mut cur, NameSpace::Type)?,
+            "rec" => {
+                while let Some(mut ty) = cur.list("type") {
+                    self.declare_id(&mut ty, NameSpace::Type)?;
+                }
+            }
+            "func" | "table" | "memory" | "global" | "tag" => {
+                let space = space_of(kind).unwrap_or(NameSpace::Func);
+                self.declare_id(&mut cur, space)?;
+                while cur.list("export").is_some() {}
+                import(space, cur.peek_list("import"))?;
+                let rest = &cur.items[cur.at..];
+                if space == NameSpace::Table && rest.iter().any(|item| item.head() == Some("elem"))
+                {
+                    self.bump(NameSpace::Elem);
+                }
+                if space == NameSpace::Mem && rest.iter().any(|item| item.head() == Some("data")) {
+                    self.bump(NameSpace::Data);
+                }
+            }
+            "import" => {
+                cur.name()?;
+                cur.name()?;
+                let desc = cur.next();
+                let Some(space) = desc.and_then(Sexpr::head).and_then(space_of) else {
+                    return Err(WatError::new(pos, "expected an import description"));
+                };
+                import(space, true)?;
+                if let Some(mut desc) = desc.and_then(Cursor::of) {
+                    desc.at = 1;
+                    self.declare_id(&mut desc, space)?;
+                }
+            }
+            "elem" => self.declare_id(&mut cur, NameSpace::Elem)?,
+            "data" => self.declare_id(&mut cur, NameSpace::Data)?,
+            "export" | "start" | "@custom" => {}
+            _ => return Err(WatError::new(pos, format!("unknown module field {}", kind))),
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, item: &'a Sexpr, space: NameSpace) -> Result<u32> {
+        match item {
+            Sexpr::Id(id, pos) => self.ids.get(&(space, id.as_str())).copied().ok_or_else(|| {
+                WatError::new(*pos, format!("unknown {} ${}", space_name(space), id))
+            }),
+            _ => number(item),
+        }
+    }
+
+    fn index(&self, cur: &mut Cursor<'a>, space: NameSpace) -> Result<u32> {
+        match cur.peek() {
+            Some(item) if is_index(item) => {
+                cur.next();
+                self.resolve(item, space)
+            }
+            _ => Err(cur.error(format!("expected a {} index", space_name(space)))),
+        }
+    }
+
+    fn opt_index(&self, cur: &mut Cursor<'a>, space: NameSpace) -> Result<Option<u32>> {
+        match cur.peek() {
+            Some(item) if is_index(item) => self.index(cur, space).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    // Up to two indices, for instructions whose first index is optional
+    fn indices(&self, cur: &mut Cursor<'a>) -> Vec<&'a Sexpr> {
+        let mut items = Vec::new();
+        while items.len() < 2
+            && let Some(item) = cur.peek()
+            && is_index(item)
+        {
+            cur.next();
+            items.push(item);
+        }
+        items
+    }
+
+    fn sub_type(&self, idx: u32) -> Option<&SubType> {
+        self.types
+            .iter()
+            .flat_map(|rec| rec.types())
+            .nth(idx as usize)
+    }
+
+    fn num_types(&self) -> u32 {
+        self.types.iter().map(|rec| rec.types().len() as u32).sum()
+    }
+
+    fn heap_type(&self, cur: &mut Cursor<'a>) -> Result<HeapType> {
+        let heap = match cur.peek_atom() {
+            Some("func") => HeapType::Func,
+            Some("extern") => HeapType::Extern,
+            Some("exn") => HeapType::Exn,
+            Some("any") => HeapType::Any,
+            Some("eq") => HeapType::Eq,
+            Some("i31") => HeapType::I31,
+            Some("struct") => HeapType::Struct,
+            Some("array") => HeapType::Array,
+            Some("none") => HeapType::None,
+            Some("nofunc") => HeapType::NoFunc,
+            Some("noextern") => HeapType::NoExtern,
+            Some("noexn") => HeapType::NoExn,
+            _ => {
+                return Ok(HeapType::Concrete(TypeIdx(
+                    self.index(cur, NameSpace::Type)?,
+                )));
+            }
+        };
+        cur.next();
+        Ok(heap)
+    }
+
+    fn ref_type(&self, cur: &mut Cursor<'a>) -> Result<RefType> {
+        if let Some(rt) = cur.peek_atom().and_then(ref_shorthand) {
+            cur.next();
+            return Ok(rt);
+        }
+        let Some(mut list) = cur.list("ref") else {
+            return Err(cur.error("expected a reference type"));
+        };
+        let nullable = list.eat("null");
+        let heap = self.heap_type(&mut list)?;
+        list.finish()?;
+        Ok(RefType::new(nullable, heap))
+    }
+
+    fn val_type(&self, cur: &mut Cursor<'a>) -> Result<ValType> {
+        let t = match cur.peek_atom() {
+            Some("i32") => ValType::Num(NumType::I32),
+            Some("i64") => ValType::Num(NumType::I64),
+            Some("f32") => ValType::Num(NumType::F32),
+            Some("f64") => ValType::Num(NumType::F64),
+            Some("v128") => ValType::Vec(VecType::V128),
+            _ if cur.peek_list("ref") => return Ok(ValType::Ref(self.ref_type(cur)?)),
+            atom => match atom.and_then(ref_shorthand) {
+                Some(rt) => ValType::Ref(rt),
+                None => return Err(cur.error("expected a value type")),
+            },
+        };
+        cur.next();
+        Ok(t)
+    }
+
+    fn global_type(&self, cur: &mut Cursor<'a>) -> Result<GlobalType> {
+        match cur.list("mut") {
+            Some(mut list) => {
+                let t = self.val_type(&mut list)?;
+                list.finish()?;
+                Ok(GlobalType::new(t, VAR))
+            }
+            None => Ok(GlobalType::new(self.val_type(cur)?, CONST)),
+        }
+    }
+
+    fn table_type(&self, cur: &mut Cursor<'a>) -> Result<TableType> {
+        let is_64 = address_type(cur);
+        let lim = limits(cur, is_64)?;
+        Ok(TableType::new(self.ref_type(cur)?, lim))
+    }
+
+    fn mem_type(&self, cur: &mut Cursor<'a>) -> Result<MemType> {
+        let is_64 = address_type(cur);
+        let lim = limits(cur, is_64)?;
+        if cur.eat("shared") {
+            Ok(MemType::new(lim.shared()))
+        } else {
+            cur.eat("unshared");
+            Ok(MemType::new(lim))
+        }
+    }
+
+    // The `(param ...)` and `(result ...)` declarations of a signature.
+    // Parameters can only be named in functions and function types.
+    #[allow(clippy::type_complexity)]
+    fn params_results(
+        &self,
+        cur: &mut Cursor<'a>,
+        named: bool,
+    ) -> Result<(Vec<(Option<&'a str>, ValType)>, Vec<ValType>)> {
+        let mut params = Vec::new();
+        while let Some(mut list) = cur.list("param") {
+            if let Some(id) = list.id() {
+                if !named {
+                    return Err(WatError::new(list.items[0].pos(), "unexpected identifier"));
+                }
+                params.push((Some(id), self.val_type(&mut list)?));
+            } else {
+                while !list.is_empty() {
+                    params.push((None, self.val_type(&mut list)?));
+                }
+            }
+            list.finish()?;
+        }
+        let mut results = Vec::new();
+        while let Some(mut list) = cur.list("result") {
+            while !list.is_empty() {
+                results.push(self.val_type(&mut list)?);
+            }
+        }
+        Ok((params, results))
+    }
+
+    // The index of a function type that is on its own in a rec group and
+    // can't be subtyped, adding one if there is none yet
+    fn implicit_type(&mut self, ft: FuncType) -> TypeIdx {
+        let mut idx = 0;
+        for rec in &self.types {
+            if let [sub] = rec.types()
+                && sub.is_final()
+                && sub.supertypes().is_empty()
+                && sub.comp_type().as_func() == Some(&ft)
+            {
+                return TypeIdx(idx);
+            }
+            idx += rec.types().len() as u32;
+        }
+        self.types
+            .push(RecType::new(vec![SubType::from(CompType::Func(ft))]));
+        TypeIdx(idx)
+    }
+
+    // A type use, `(type x)` and/or an inline signature, with the
+    // identifiers of the parameters
+    fn type_use(&mut self, cur: &mut Cursor<'a>) -> Result<(TypeIdx, Vec<Option<&'a str>>)> {
+        let explicit = match cur.list("type") {
+            Some(mut list) => {
+                let pos = list.pos();
+                let idx = self.index(&mut list, NameSpace::Type)?;
+                list.finish()?;
+                Some((idx, pos))
+            }
+            None => None,
+        };
+        let (params, results) = self.params_results(cur, true)?;
+        let ids: Vec<Option<&'a str>> = params.iter().map(|(id, _)| *id).collect();
+        let ft = FuncType::new(params.into_iter().map(|(_, t)| t).collect(), results);
+        let Some((idx, pos)) = explicit else {
+            return Ok((self.implicit_type(ft), ids));
+        };
+
+        let declared = self.sub_type(idx).and_then(|sub| sub.comp_type().as_func());
+        let inline = !ft.params().is_empty() || !ft.results().is_empty();
+        if inline && declared != Some(&ft) {
+            return Err(WatError::new(pos, "inconsistent type"));
+        }
+        let ids = if ft.params().is_empty() {
+            vec![None; declared.map_or(0, |ft| ft.params().len())]
+        } else {
+            ids
+        };
+        Ok((TypeIdx(idx), ids))
+    }
+
+    fn block_type(&mut self, cur: &mut Cursor<'a>) -> Result<BlockType> {
+        if cur.peek_list("type") {
+            let pos = cur.pos();
+            let (ty, ids) = self.type_use(cur)?;
+            if ids.iter().any(Option::is_some) {
+                return Err(WatError::new(pos, "unexpected identifier"));
+            }
+            return Ok(BlockType::Type(ty));
+        }
+        let (params, results) = self.params_results(cur, false)?;
+        let bt = match (params.is_empty(), results.as_slice()) {
+            (true, []) => BlockType::Empty,
+            (true, [t]) => BlockType::Value(*t),
+            _ => {
+                let params = params.into_iter().map(|(_, t)| t).collect();
+                BlockType::Type(self.implicit_type(FuncType::new(params, results)))
+            }
+        };
+        Ok(bt)
+    }
+
+    fn storage_type(&self, cur: &mut Cursor<'a>) -> Result<StorageType> {
+        if cur.eat("i8") {
+            Ok(StorageType::I8)
+        } else if cur.eat("i16") {
+            Ok(StorageType::I16)
+        } else {
+            Ok(StorageType::Val(self.val_type(cur)?))
+        }
+    }
+
+    fn field_type(&self, cur: &mut Cursor<'a>) -> Result<FieldType> {
+        match cur.list("mut") {
+            Some(mut list) => {
+                let st = self.storage_type(&mut list)?;
+                list.finish()?;
+                Ok(FieldType::new(st, VAR))
+            }
+            None => Ok(FieldType::new(self.storage_type(cur)?, CONST)),
+        }
+    }
+
+    fn comp_type(&mut self, cur: &mut Cursor<'a>, idx: u32) -> Result<CompType> {
+        let comp = if let Some(mut list) = cur.list("func") {
+            let (params, results) = self.params_results(&mut list, true)?;
+            list.finish()?;
+            let params = params.into_iter().map(|(_, t)| t).collect();
+            CompType::Func(FuncType::new(params, results))
+        } else if let Some(mut list) = cur.list("struct") {
+            let mut fields = Vec::new();
+            while let Some(mut field) = list.list("field") {
+                if let Some(id) = field.id() {
+                    let pos = field.items[0].pos();
+                    if self.fields.insert((idx, id), fields.len() as u32).is_some() {
+                        return Err(WatError::new(pos, format!("duplicate field ${}", id)));
+                    }
+                    fields.push(self.field_type(&mut field)?);
+                } else {
+                    while !field.is_empty() {
+                        fields.push(self.field_type(&mut field)?);
+                    }
+                }
+                field.finish()?;
+            }
+            list.finish()?;
+            CompType::Struct(fields)
+        } else if let Some(mut list) = cur.list("array") {
+            let field = self.field_type(&mut list)?;
+            list.finish()?;
+            CompType::Array(field)
+        } else {
+            return Err(cur.error("expected a func, struct or array type"));
+        };
+        Ok(comp)
+    }
+
+    // `(type $id? ...)`, given the index it defines
+    fn type_def(&mut self, cur: &mut Cursor<'a>, idx: u32) -> Result<SubType> {
+        cur.id();
+        let sub = match cur.list("sub") {
+            Some(mut list) => {
+                let is_final = list.eat("final");
+                let mut supertypes = Vec::new();
+                while let Some(item) = list.peek()
+                    && is_index(item)
+                {
+                    supertypes.push(TypeIdx(self.index(&mut list, NameSpace::Type)?));
+                }
+                let comp = self.comp_type(&mut list, idx)?;
+                list.finish()?;
+                SubType::new(is_final, supertypes, comp)
+            }
+            None => SubType::from(self.comp_type(cur, idx)?),
+        };
+        cur.finish()?;
+        Ok(sub)
+    }
+
+    fn type_field(&mut self, field: &'a Sexpr) -> Result<()> {
+        let Some(mut cur) = Cursor::of(field) else {
+            return Ok(());
+        };
+        let base = self.num_types();
+        if cur.eat("type") {
+            let sub = self.type_def(&mut cur, base)?;
+            self.types.push(RecType::new(vec![sub]));
+        } else if cur.eat("rec") {
+            let mut subs = Vec::new();
+            while let Some(mut ty) = cur.list("type") {
+                subs.push(self.type_def(&mut ty, base + subs.len() as u32)?);
+            }
+            cur.finish()?;
+            self.types.push(RecType::new(subs));
+        }
+        Ok(())
+    }
+
+    fn label(&self, cur: &mut Cursor<'a>, body: &Body<'a>) -> Result<LabelIdx> {
+        match cur.next() {
+            Some(Sexpr::Id(id, pos)) => body
+                .labels
+                .iter()
+                .rev()
+                .position(|label| *label == Some(id.as_str()))
+                .map(|depth| LabelIdx(depth as u32))
+                .ok_or_else(|| WatError::new(*pos, format!("unknown label ${}", id))),
+            Some(item) if is_index(item) => Ok(LabelIdx(number(item)?)),
+            _ => {
+                cur.at = cur.at.saturating_sub(1);
+                Err(cur.error("expected a label"))
+            }
+        }
+    }
+
+    fn local(&self, cur: &mut Cursor<'a>, body: &Body<'a>) -> Result<LocalIdx> {
+        match cur.peek() {
+            Some(Sexpr::Id(id, pos)) => {
+                cur.next();
+                body.locals
+                    .get(id.as_str())
+                    .map(|idx| LocalIdx(*idx))
+                    .ok_or_else(|| WatError::new(*pos, format!("unknown local ${}", id)))
+            }
+            Some(item) if is_index(item) => {
+                cur.next();
+                Ok(LocalIdx(number(item)?))
+            }
+            _ => Err(cur.error("expected a local index")),
+        }
+    }
+
+    fn field_idx(&self, cur: &mut Cursor<'a>, ty: u32) -> Result<FieldIdx> {
+        match cur.peek() {
+            Some(Sexpr::Id(id, pos)) => {
+                cur.next();
+                self.fields
+                    .get(&(ty, id.as_str()))
+                    .map(|idx| FieldIdx(*idx))
+                    .ok_or_else(|| WatError::new(*pos, format!("unknown field ${}", id)))
+            }
+            Some(item) if is_index(item) => {
+                cur.next();
+                Ok(FieldIdx(number(item)?))
+            }
+            _ => Err(cur.error("expected a field index")),
+        }
+    }
+
+    // A literal of the kind `what` names. The errors follow the reference
+    // interpreter, which tells a number out of range from a token that
+    // isn't a number at all.
+    fn literal<T>(cur: &mut Cursor<'a>, what: &str, f: impl Fn(&str) -> Option<T>) -> Result<T> {
+        let pos = cur.pos();
+        let Some(atom) = cur.peek_atom() else {
+            return Err(WatError::new(
+                pos,
+                format!("unexpected token, expected {}", what),
+            ));
+        };
+        match f(atom) {
+            Some(value) => {
+                cur.next();
+                Ok(value)
+            }
+            None if num::is_literal(atom) => Err(WatError::new(
+                pos,
+                format!("constant out of range for {}", what),
+            )),
+            None => Err(WatError::new(pos, format!("unknown operator {}", atom))),
+        }
+    }
+
+    fn lane(cur: &mut Cursor<'a>) -> Result<u8> {
+        Self::literal(cur, "lane index", |text| {
+            num::uint(text).and_then(|n| u8::try_from(n).ok())
+        })
+    }
+
+    fn v128(cur: &mut Cursor<'a>) -> Result<[u8; 16]> {
+        let (shape, pos) = cur.atom()?;
+        let width = match shape {
+            "i8x16" => 1,
+            "i16x8" => 2,
+            "i32x4" | "f32x4" => 4,
+            "i64x2" | "f64x2" => 8,
+            _ => return Err(WatError::new(pos, "unknown vector shape")),
+        };
+        let mut bytes = [0; 16];
+        for lane in bytes.chunks_mut(width) {
+            let bits = match shape {
+                "f32x4" => Self::literal(cur, "f32", num::f32).map(u64::from)?,
+                "f64x2" => Self::literal(cur, shape, num::f64)?,
+                _ => Self::literal(cur, shape, |text| num::int(text, width as u32 * 8))?,
+            };
+            lane.copy_from_slice(&bits.to_le_bytes()[..width]);
+        }
+        Ok(bytes)
+    }
+
+    // The memarg of a load or store accessing `size` bytes. A lane index
+    // may follow it, in which case a lone number is the lane rather than
+    // the memory.
+    fn mem_arg(&self, cur: &mut Cursor<'a>, size: u32, lane: bool) -> Result<MemArg> {
+        let memarg_follows = |item: Option<&Sexpr>| {
+            item.and_then(Sexpr::as_atom)
+                .is_some_and(|atom| atom.starts_with("offset=") || atom.starts_with("align="))
+        };
+        let explicit = match cur.peek() {
+            Some(Sexpr::Id(..)) => true,
+            Some(item) if is_index(item) => {
+                !lane || cur.peek2().is_some_and(is_index) || memarg_follows(cur.peek2())
+            }
+            _ => false,
+        };
+        let mem = match explicit {
+            true => Some(MemIdx(self.index(cur, NameSpace::Mem)?)),
+            false => None,
+        };
+        let mut offset = 0;
+        if let Some(text) = cur
+            .peek_atom()
+            .and_then(|atom| atom.strip_prefix("offset="))
+        {
+            offset = num::uint(text).ok_or_else(|| cur.error("invalid offset"))?;
+            cur.next();
+        }
+        let mut align = size;
+        if let Some(text) = cur.peek_atom().and_then(|atom| atom.strip_prefix("align=")) {
+            align = num::uint(text)
+                .and_then(|n| u32::try_from(n).ok())
+                .filter(|n| n.is_power_of_two())
+                .ok_or_else(|| cur.error("alignment must be a power of two"))?;
+            cur.next();
+        }
+        Ok(MemArg::new(align.trailing_zeros(), offset, mem))
+    }
+
+    fn simd(&self, op: SimdOp, cur: &mut Cursor<'a>) -> Result<SimdImm> {
+        let imm = if op == SimdOp::V128Const {
+            SimdImm::Bytes(Self::v128(cur)?)
+        } else if op == SimdOp::I8x16Shuffle {
+            let mut lanes = [0; 16];
+            for lane in &mut lanes {
+                *lane = Self::lane(cur)?;
+            }
+            SimdImm::Bytes(lanes)
+        } else if op.has_mem_arg() {
+            let memarg = self.mem_arg(cur, simd_access_size(op), op.has_lane())?;
+            if op.has_lane() {
+                SimdImm::MemArgLane(memarg, Self::lane(cur)?)
+            } else {
+                SimdImm::MemArg(memarg)
+            }
+        } else if op.has_lane() {
+            SimdImm::Lane(Self::lane(cur)?)
+        } else {
+            SimdImm::None
+        };
+        Ok(imm)
+    }
+
+    fn catches(&self, cur: &mut Cursor<'a>, body: &Body<'a>) -> Result<Vec<Catch>> {
+        let mut catches = Vec::new();
+        loop {
+            let (mut list, catch): (_, fn(TagIdx, LabelIdx) -> Catch) =
+                if let Some(list) = cur.list("catch") {
+                    (list, Catch::Catch)
+                } else if let Some(list) = cur.list("catch_ref") {
+                    (list, Catch::CatchRef)
+                } else if let Some(mut list) = cur.list("catch_all") {
+                    catches.push(Catch::CatchAll(self.label(&mut list, body)?));
+                    list.finish()?;
+                    continue;
+                } else if let Some(mut list) = cur.list("catch_all_ref") {
+                    catches.push(Catch::CatchAllRef(self.label(&mut list, body)?));
+                    list.finish()?;
+                    continue;
+                } else {
+                    return Ok(catches);
+                };
+            let tag = TagIdx(self.index(&mut list, NameSpace::Tag)?);
+            catches.push(catch(tag, self.label(&mut list, body)?));
+            list.finish()?;
+        }
+    }
+
+    // An instruction without a block, after its keyword
+    fn plain(
+        &mut self,
+        kw: &'a str,
+        pos: Pos,
+        cur: &mut Cursor<'a>,
+        body: &Body<'a>,
+    ) -> Result<Instr> {
+        let func = |l: &Self, cur: &mut Cursor<'a>| l.index(cur, NameSpace::Func).map(FuncIdx);
+        let ty = |l: &Self, cur: &mut Cursor<'a>| l.index(cur, NameSpace::Type).map(TypeIdx);
+        let elem = |l: &Self, cur: &mut Cursor<'a>| l.index(cur, NameSpace::Elem).map(ElemIdx);
+        let data = |l: &Self, cur: &mut Cursor<'a>| l.index(cur, NameSpace::Data).map(DataIdx);
+        let table = |l: &Self, cur: &mut Cursor<'a>| {
+            l.opt_index(cur, NameSpace::Table)
+                .map(|idx| TableIdx(idx.unwrap_or(0)))
+        };
+        let mem = |l: &Self, cur: &mut Cursor<'a>| {
+            l.opt_index(cur, NameSpace::Mem)
+                .map(|idx| MemIdx(idx.unwrap_or(0)))
+        };
+        let global =
+            |l: &Self, cur: &mut Cursor<'a>| l.index(cur, NameSpace::Global).map(GlobalIdx);
+
+        let instr = match kw {
+            "unreachable" => Instr::Unreachable,
+            "nop" => Instr::Nop,
+            "return" => Instr::Return,
+            "drop" => Instr::Drop,
+            "select" => {
+                let mut types = None;
+                while let Some(mut list) = cur.list("result") {
+                    let types = types.get_or_insert_with(Vec::new);
+                    while !list.is_empty() {
+                        types.push(self.val_type(&mut list)?);
+                    }
+                }
+                Instr::Select(types)
+            }
+            "br" => Instr::Br(self.label(cur, body)?),
+            "br_if" => Instr::BrIf(self.label(cur, body)?),
+            "br_table" => {
+                let mut labels = vec![self.label(cur, body)?];
+                while cur.peek().is_some_and(is_index) {
+                    labels.push(self.label(cur, body)?);
+                }
+                let default = labels.pop().unwrap_or(LabelIdx(0));
+                Instr::BrTable(labels, default)
+            }
+            "br_on_null" => Instr::BrOnNull(self.label(cur, body)?),
+            "br_on_non_null" => Instr::BrOnNonNull(self.label(cur, body)?),
+            "br_on_cast" | "br_on_cast_fail" => {
+                let label = self.label(cur, body)?;
+                let rt1 = self.ref_type(cur)?;
+                let rt2 = self.ref_type(cur)?;
+                if kw == "br_on_cast" {
+                    Instr::BrOnCast(label, rt1, rt2)
+                } else {
+                    Instr::BrOnCastFail(label, rt1, rt2)
+                }
+            }
+            "call" => Instr::Call(func(self, cur)?),
+            "return_call" => Instr::ReturnCall(func(self, cur)?),
+            "ref.func" => Instr::RefFunc(func(self, cur)?),
+            "call_indirect" | "return_call_indirect" => {
+                let table = table(self, cur)?;
+                let at = cur.pos();
+                let (ty, ids) = self.type_use(cur)?;
+                if ids.iter().any(Option::is_some) {
+                    return Err(WatError::new(at, "unexpected identifier"));
+                }
+                if kw == "call_indirect" {
+                    Instr::CallIndirect(ty, table)
+                } else {
+                    Instr::ReturnCallIndirect(ty, table)
+                }
+            }
+            "call_ref" => Instr::CallRef(ty(self, cur)?),
+            "return_call_ref" => Instr::ReturnCallRef(ty(self, cur)?),
+            "throw" => Instr::Throw(TagIdx(self.index(cur, NameSpace::Tag)?)),
+            "throw_ref" => Instr::ThrowRef,
+            "rethrow" => Instr::Rethrow(self.label(cur, body)?),
+            "ref.null" => Instr::RefNull(self.heap_type(cur)?),
+            "ref.is_null" => Instr::RefIsNull,
+            "ref.as_non_null" => Instr::RefAsNonNull,
+            "ref.eq" => Instr::RefEq,
+            "struct.new" => Instr::StructNew(ty(self, cur)?),
+            "struct.new_default" => Instr::StructNewDefault(ty(self, cur)?),
+            "struct.get" | "struct.get_s" | "struct.get_u" | "struct.set" => {
+                let ty = ty(self, cur)?;
+                let field = self.field_idx(cur, ty.0)?;
+                match kw {
+                    "struct.get" => Instr::StructGet(ty, field),
+                    "struct.get_s" => Instr::StructGetS(ty, field),
+                    "struct.get_u" => Instr::StructGetU(ty, field),
+                    _ => Instr::StructSet(ty, field),
+                }
+            }
+            "array.new" => Instr::ArrayNew(ty(self, cur)?),
+            "array.new_default" => Instr::ArrayNewDefault(ty(self, cur)?),
+            "array.new_fixed" => {
+                let ty = ty(self, cur)?;
+                let n = Self::literal(cur, "u32", |text| {
+                    num::uint(text).and_then(|n| u32::try_from(n).ok())
+                })?;
+                Instr::ArrayNewFixed(ty, n)
+            }
+            "array.new_data" => Instr::ArrayNewData(ty(self, cur)?, data(self, cur)?),
+            "array.new_elem" => Instr::ArrayNewElem(ty(self, cur)?, elem(self, cur)?),
+            "array.get" => Instr::ArrayGet(ty(self, cur)?),
+            "array.get_s" => Instr::ArrayGetS(ty(self, cur)?),
+            "array.get_u" => Instr::ArrayGetU(ty(self, cur)?),
+            "array.set" => Instr::ArraySet(ty(self, cur)?),
+            "array.len" => Instr::ArrayLen,
+            "array.fill" => Instr::ArrayFill(ty(self, cur)?),
+            "array.copy" => Instr::ArrayCopy(ty(self, cur)?, ty(self, cur)?),
+            "array.init_data" => Instr::ArrayInitData(ty(self, cur)?, data(self, cur)?),
+            "array.init_elem" => Instr::ArrayInitElem(ty(self, cur)?, elem(self, cur)?),
+            "ref.test" => Instr::RefTest(self.ref_type(cur)?),
+            "ref.cast" => Instr::RefCast(self.ref_type(cur)?),
+            "any.convert_extern" => Instr::AnyConvertExtern,
+            "extern.convert_any" => Instr::ExternConvertAny,
+            "ref.i31" => Instr::RefI31,
+            "i31.get_s" => Instr::I31GetS,
+            "i31.get_u" => Instr::I31GetU,
+            "local.get" => Instr::LocalGet(self.local(cur, body)?),
+            "local.set" => Instr::LocalSet(self.local(cur, body)?),
+            "local.tee" => Instr::LocalTee(self.local(cur, body)?),
+            "global.get" => Instr::GlobalGet(global(self, cur)?),
+            "global.set" => Instr::GlobalSet(global(self, cur)?),
+            "table.get" => Instr::TableGet(table(self, cur)?),
+            "table.set" => Instr::TableSet(table(self, cur)?),
+            "table.grow" => Instr::TableGrow(table(self, cur)?),
+            "table.size" => Instr::TableSize(table(self, cur)?),
+            "table.fill" => Instr::TableFill(table(self, cur)?),
+            "elem.drop" => Instr::ElemDrop(elem(self, cur)?),
+            "table.init" => match self.indices(cur).as_slice() {
+                [table, elem] => Instr::TableInit(
+                    ElemIdx(self.resolve(elem, NameSpace::Elem)?),
+                    TableIdx(self.resolve(table, NameSpace::Table)?),
+                ),
+                [elem] => {
+                    Instr::TableInit(ElemIdx(self.resolve(elem, NameSpace::Elem)?), TableIdx(0))
+                }
+                _ => return Err(cur.error("expected an elem segment index")),
+            },
+            "table.copy" => match self.indices(cur).as_slice() {
+                [dst, src] => Instr::TableCopy(
+                    TableIdx(self.resolve(dst, NameSpace::Table)?),
+                    TableIdx(self.resolve(src, NameSpace::Table)?),
+                ),
+                [] => Instr::TableCopy(TableIdx(0), TableIdx(0)),
+                _ => return Err(cur.error("expected a table index")),
+            },
+            "memory.size" => Instr::MemorySize(mem(self, cur)?),
+            "memory.grow" => Instr::MemoryGrow(mem(self, cur)?),
+            "memory.fill" => Instr::MemoryFill(mem(self, cur)?),
+            "data.drop" => Instr::DataDrop(data(self, cur)?),
+            "memory.init" => match self.indices(cur).as_slice() {
+                [mem, data] => Instr::MemoryInit(
+                    DataIdx(self.resolve(data, NameSpace::Data)?),
+                    MemIdx(self.resolve(mem, NameSpace::Mem)?),
+                ),
+                [data] => {
+                    Instr::MemoryInit(DataIdx(self.resolve(data, NameSpace::Data)?), MemIdx(0))
+                }
+                _ => return Err(cur.error("expected a data segment index")),
+            },
+            "memory.copy" => match self.indices(cur).as_slice() {
+                [dst, src] => Instr::MemoryCopy(
+                    MemIdx(self.resolve(dst, NameSpace::Mem)?),
+                    MemIdx(self.resolve(src, NameSpace::Mem)?),
+                ),
+                [] => Instr::MemoryCopy(MemIdx(0), MemIdx(0)),
+                _ => return Err(cur.error("expected a memory index")),
+            },
+            "i32.const" => {
+                Instr::I32Const(Self::literal(cur, "i32", |text| num::int(text, 32))? as i32)
+            }
+            "i64.const" => {
+                Instr::I64Const(Self::literal(cur, "i64", |text| num::int(text, 64))? as i64)
+            }
+            "f32.const" => Instr::F32Const(f32::from_bits(Self::literal(cur, "f32", num::f32)?)),
+            "f64.const" => Instr::F64Const(f64::from_bits(Self::literal(cur, "f64", num::f64)?)),
+            "atomic.fence" => Instr::AtomicFence,
+            _ => {
+                if let Some(op) = NumericInstr::from_name(kw) {
+                    Instr::Numeric(op)
+                } else if let Some(op) = LoadOp::from_name(kw) {
+                    Instr::Load(op, self.mem_arg(cur, load_type(op).1, false)?)
+                } else if let Some(op) = StoreOp::from_name(kw) {
+                    Instr::Store(op, self.mem_arg(cur, store_type(op).1, false)?)
+                } else if let Some(op) = SimdOp::from_name(kw) {
+                    Instr::Simd(op, self.simd(op, cur)?)
+                } else if let Some(op) = AtomicOp::from_name(kw) {
+                    Instr::Atomic(op, self.mem_arg(cur, atomic_type(op).2, false)?)
+                } else {
+                    return Err(WatError::new(pos, format!("unknown operator {}", kw)));
+                }
+            }
+        };
+        Ok(instr)
+    }
+
+    // Checks the label that may follow `else` or `end` against the one of
+    // the block it belongs to
+    fn end_label(&self, kw: &str, pos: Pos, cur: &mut Cursor<'a>, body: &Body<'a>) -> Result<()> {
+        let Some(label) = body.labels.last() else {
+            return Err(WatError::new(pos, format!("unexpected {}", kw)));
+        };
+        if let Some(Sexpr::Id(id, at)) = cur.peek() {
+            if *label != Some(id.as_str()) {
+                return Err(WatError::new(*at, format!("mismatching label ${}", id)));
+            }
+            cur.next();
+        }
+        Ok(())
+    }
+
+    fn instrs(&mut self, cur: &mut Cursor<'a>, body: &mut Body<'a>) -> Result<()> {
+        while let Some(item) = cur.next() {
+            match item {
+                Sexpr::List(items, _, end) => self.folded(Cursor::new(items, *end), body)?,
+                Sexpr::Atom(kw, pos) => self.flat(kw, *pos, cur, body)?,
+                _ => return Err(WatError::new(item.pos(), "expected an instruction")),
+            }
+        }
+        Ok(())
+    }
+
+    fn flat(
+        &mut self,
+        kw: &'a str,
+        pos: Pos,
+        cur: &mut Cursor<'a>,
+        body: &mut Body<'a>,
+    ) -> Result<()> {
+        let instr = match kw {
+            "block" | "loop" | "if" | "try" | "try_table" => {
+                let label = cur.id();
+                let bt = self.block_type(cur)?;
+                let instr = match kw {
+                    "block" => Instr::Block(bt),
+                    "loop" => Instr::Loop(bt),
+                    "if" => Instr::If(bt),
+                    "try" => Instr::Try(bt),
+                    _ => Instr::TryTable(bt, self.catches(cur, body)?),
+                };
+                body.labels.push(label);
+                instr
+            }
+            "else" => {
+                self.end_label(kw, pos, cur, body)?;
+                Instr::Else
+            }
+            "end" => {
+                self.end_label(kw, pos, cur, body)?;
+                body.labels.pop();
+                Instr::End
+            }
+            "catch" => Instr::Catch(TagIdx(self.index(cur, NameSpace::Tag)?)),
+            "catch_all" => Instr::CatchAll,
+            "delegate" => {
+                if body.labels.pop().is_none() {
+                    return Err(WatError::new(pos, "unexpected delegate"));
+                }
+                Instr::Delegate(self.label(cur, body)?)
+            }
+            _ => self.plain(kw, pos, cur, body)?,
+        };
+        body.instrs.push(instr);
+        Ok(())
+    }
+
+    // An instruction in the folded form, given the items of its list
+    fn folded(&mut self, mut list: Cursor<'a>, body: &mut Body<'a>) -> Result<()> {
+        let (kw, pos) = list
+            .atom()
+            .map_err(|_| list.error("expected an instruction"))?;
+        match kw {
+            "block" | "loop" | "try_table" => {
+                let label = list.id();
+                let bt = self.block_type(&mut list)?;
+                let instr = match kw {
+                    "block" => Instr::Block(bt),
+                    "loop" => Instr::Loop(bt),
+                    _ => Instr::TryTable(bt, self.catches(&mut list, body)?),
+                };
+                body.instrs.push(instr);
+                body.labels.push(label);
+                self.instrs(&mut list, body)?;
+            }
+            "if" => {
+                let label = list.id();
+                let bt = self.block_type(&mut list)?;
+                // The condition comes before the arms
+                while let Some(item) = list.peek()
+                    && item.head() != Some("then")
+                {
+                    match Cursor::of(item) {
+                        Some(cond) => {
+                            list.next();
+                            self.folded(cond, body)?;
+                        }
+                        None => return Err(list.error("expected (then ...)")),
+                    }
+                }
+                body.instrs.push(Instr::If(bt));
+                body.labels.push(label);
+                let Some(mut then) = list.list("then") else {
+                    return Err(list.error("expected (then ...)"));
+                };
+                self.instrs(&mut then, body)?;
+                if let Some(mut els) = list.list("else") {
+                    body.instrs.push(Instr::Else);
+                    self.instrs(&mut els, body)?;
+                }
+                list.finish()?;
+            }
+            "try" => {
+                let label = list.id();
+                let bt = self.block_type(&mut list)?;
+                body.instrs.push(Instr::Try(bt));
+                body.labels.push(label);
+                let Some(mut block) = list.list("do") else {
+                    return Err(list.error("expected (do ...)"));
+                };
+                self.instrs(&mut block, body)?;
+                loop {
+                    if let Some(mut handler) = list.list("catch") {
+                        let tag = TagIdx(self.index(&mut handler, NameSpace::Tag)?);
+                        body.instrs.push(Instr::Catch(tag));
+                        self.instrs(&mut handler, body)?;
+                    } else if let Some(mut handler) = list.list("catch_all") {
+                        body.instrs.push(Instr::CatchAll);
+                        self.instrs(&mut handler, body)?;
+                    } else {
+                        break;
+                    }
+                }
+                if let Some(mut delegate) = list.list("delegate") {
+                    body.labels.pop();
+                    let label = self.label(&mut delegate, body)?;
+                    delegate.finish()?;
+                    list.finish()?;
+                    body.instrs.push(Instr::Delegate(label));
+                    return Ok(());
+                }
+                list.finish()?;
+            }
+            _ => {
+                let instr = self.plain(kw, pos, &mut list, body)?;
+                while let Some(item) = list.next() {
+                    match Cursor::of(item) {
+                        Some(operand) => self.folded(operand, body)?,
+                        None => return Err(WatError::new(item.pos(), "unexpected token")),
+                    }
+                }
+                body.instrs.push(instr);
+                return Ok(());
+            }
+        }
+        body.labels.pop();
+        body.instrs.push(Instr::End);
+        Ok(())
+    }
+
+    fn const_expr(&mut self, cur: &mut Cursor<'a>) -> Result<Expr> {
+        let mut body = Body::default();
+        self.instrs(cur, &mut body)?;
+        if !body.labels.is_empty() {
+            return Err(WatError::new(cur.end, "unclosed block"));
+        }
+        body.instrs.push(Instr::End);
+        Ok(Expr::new(body.instrs))
+    }
+
+    // A single folded instruction, as in an abbreviated offset or element
+    fn folded_expr(&mut self, list: Cursor<'a>) -> Result<Expr> {
+        let mut body = Body::default();
+        self.folded(list, &mut body)?;
+        body.instrs.push(Instr::End);
+        Ok(Expr::new(body.instrs))
+    }
+
+    // `(offset instr*)`, or a single folded instruction
+    fn offset(&mut self, cur: &mut Cursor<'a>) -> Result<Expr> {
+        let Some(mut list) = cur.peek().and_then(Cursor::of) else {
+            return Err(cur.error("expected an offset"));
+        };
+        cur.next();
+        if list.eat("offset") {
+            self.const_expr(&mut list)
+        } else {
+            self.folded_expr(list)
+        }
+    }
+
+    // Element expressions, each `(item instr*)` or a folded instruction
+    fn elem_exprs(&mut self, cur: &mut Cursor<'a>) -> Result<ElemInit> {
+        let mut exprs = Vec::new();
+        while let Some(item) = cur.next() {
+            let Some(mut list) = Cursor::of(item) else {
+                return Err(WatError::new(item.pos(), "expected an element expression"));
+            };
+            if list.eat("item") {
+                exprs.push(self.const_expr(&mut list)?);
+            } else {
+                exprs.push(self.folded_expr(list)?);
+            }
+        }
+        Ok(ElemInit::Exprs(exprs))
+    }
+
+    fn elem_funcs(&mut self, cur: &mut Cursor<'a>) -> Result<ElemInit> {
+        let mut funcs = Vec::new();
+        while !cur.is_empty() {
+            funcs.push(FuncIdx(self.index(cur, NameSpace::Func)?));
+        }
+        Ok(ElemInit::Funcs(funcs))
+    }
+
+    fn inline_exports(&mut self, cur: &mut Cursor<'a>, desc: ExportDesc) -> Result<()> {
+        while let Some(mut list) = cur.list("export") {
+            let name = list.name()?;
+            list.finish()?;
+            self.exports.push(Export::new(&name, desc));
+        }
+        Ok(())
+    }
+
+    fn inline_import(&mut self, cur: &mut Cursor<'a>) -> Result<Option<(String, String)>> {
+        let Some(mut list) = cur.list("import") else {
+            return Ok(None);
+        };
+        let module = list.name()?;
+        let name = list.name()?;
+        list.finish()?;
+        Ok(Some((module, name)))
+    }
+
+    fn field(&mut self, field: &'a Sexpr) -> Result<()> {
+        let Some(mut cur) = Cursor::of(field) else {
+            return Ok(());
+        };
+        let (kind, pos) = cur.atom()?;
+        match kind {
+            "type" | "rec" => return Ok(()),
+            "func" => self.func(&mut cur)?,
+            "table" => self.table(&mut cur)?,
+            "memory" => self.memory(&mut cur)?,
+            "global" => {
+                cur.id();
+                let idx = self.bump(NameSpace::Global);
+                self.inline_exports(&mut cur, ExportDesc::Global(GlobalIdx(idx)))?;
+                let import = self.inline_import(&mut cur)?;
+                let gt = self.global_type(&mut cur)?;
+                match import {
+                    Some((module, name)) => {
+                        self.imports
+                            .push(Import::new(&module, &name, ImportDesc::Global(gt)));
+                    }
+                    None => {
+                        let init = self.const_expr(&mut cur)?;
+                        self.globals.push(Global::new(gt, init));
+                    }
+                }
+            }
+            "tag" => {
+                cur.id();
+                let idx = self.bump(NameSpace::Tag);
+                self.inline_exports(&mut cur, ExportDesc::Tag(TagIdx(idx)))?;
+                let import = self.inline_import(&mut cur)?;
+                let tag = TagType::new(self.type_use(&mut cur)?.0);
+                match import {
+                    Some((module, name)) => {
+                        self.imports
+                            .push(Import::new(&module, &name, ImportDesc::Tag(tag)));
+                    }
+                    None => self.tags.push(tag),
+                }
+            }
+            "import" => self.import(&mut cur)?,
+            "export" => {
+                let name = cur.name()?;
+                let Some(mut list) = cur.peek().and_then(Cursor::of) else {
+                    return Err(cur.error("expected an export description"));
+                };
+                cur.next();
+                let (kind, at) = list.atom()?;
+                let space = space_of(kind)
+                    .ok_or_else(|| WatError::new(at, format!("unknown export kind {}", kind)))?;
+                let idx = self.index(&mut list, space)?;
+                list.finish()?;
+                let desc = match space {
+                    NameSpace::Func => ExportDesc::Func(FuncIdx(idx)),
+                    NameSpace::Table => ExportDesc::Table(TableIdx(idx)),
+                    NameSpace::Mem => ExportDesc::Mem(MemIdx(idx)),
+                    NameSpace::Global => ExportDesc::Global(GlobalIdx(idx)),
+                    _ => ExportDesc::Tag(TagIdx(idx)),
+                };
+                self.exports.push(Export::new(&name, desc));
+            }
+            "start" => {
+                if self.start.is_some() {
+                    return Err(WatError::new(pos, "multiple start sections"));
+                }
+                self.start = Some(FuncIdx(self.index(&mut cur, NameSpace::Func)?));
+            }
+            "elem" => self.elem(&mut cur)?,
+            "data" => self.data(&mut cur)?,
+            "@custom" => self.custom(&mut cur)?,
+            _ => return Err(WatError::new(pos, format!("unknown module field {}", kind))),
+        }
+        cur.finish()
+    }
+
+    fn func(&mut self, cur: &mut Cursor<'a>) -> Result<()> {
+        cur.id();
+        let idx = self.bump(NameSpace::Func);
+        self.inline_exports(cur, ExportDesc::Func(FuncIdx(idx)))?;
+        if let Some((module, name)) = self.inline_import(cur)? {
+            let (ty, _) = self.type_use(cur)?;
+            self.imports
+                .push(Import::new(&module, &name, ImportDesc::Func(ty)));
+            return Ok(());
+        }
+
+        let (ty, params) = self.type_use(cur)?;
+        let mut body = Body::default();
+        let declare = |body: &mut Body<'a>, id: &'a str, idx: u32, pos: Pos| {
+            if body.locals.insert(id, idx).is_some() {
+                return Err(WatError::new(pos, format!("duplicate local ${}", id)));
+            }
+            Ok(())
+        };
+        for (i, id) in params.iter().enumerate() {
+            if let Some(id) = id {
+                declare(&mut body, id, i as u32, cur.pos())?;
+            }
+        }
+        let mut locals: Vec<Locals> = Vec::new();
+        let mut n = params.len() as u32;
+        while let Some(mut list) = cur.list("local") {
+            let mut types = Vec::new();
+            if let Some(id) = list.id() {
+                declare(&mut body, id, n, list.items[0].pos())?;
+                types.push(self.val_type(&mut list)?);
+            } else {
+                while !list.is_empty() {
+                    types.push(self.val_type(&mut list)?);
+                }
+            }
+            list.finish()?;
+            for t in types {
+                match locals.last_mut() {
+                    Some(last) if last.val_type() == t => *last = Locals::new(last.count() + 1, t),
+                    _ => locals.push(Locals::new(1, t)),
+                }
+                n += 1;
+            }
+        }
+
+        self.instrs(cur, &mut body)?;
+        if !body.labels.is_empty() {
+            return Err(WatError::new(cur.end, "unclosed block"));
+        }
+        body.instrs.push(Instr::End);
+        self.funcs.push(ty);
+        self.codes.push(Code::new(locals, Expr::new(body.instrs)));
+        Ok(())
+    }
+
+    fn table(&mut self, cur: &mut Cursor<'a>) -> Result<()> {
+        cur.id();
+        let idx = self.bump(NameSpace::Table);
+        self.inline_exports(cur, ExportDesc::Table(TableIdx(idx)))?;
+        if let Some((module, name)) = self.inline_import(cur)? {
+            let tt = self.table_type(cur)?;
+            self.imports
+                .push(Import::new(&module, &name, ImportDesc::Table(tt)));
+            return Ok(());
+        }
+
+        // `(table i64? reftype (elem ...))` sizes the table to fit the
+        // segment it is initialized with
+        if cur.items[cur.at..]
+            .iter()
+            .any(|item| item.head() == Some("elem"))
+        {
+            let is_64 = address_type(cur);
+            let rt = self.ref_type(cur)?;
+            let Some(mut list) = cur.list("elem") else {
+                return Err(cur.error("expected (elem ...)"));
+            };
+            let init = match list.peek() {
+                Some(Sexpr::List(..)) => self.elem_exprs(&mut list)?,
+                _ => self.elem_funcs(&mut list)?,
+            };
+            let n = match &init {
+                ElemInit::Funcs(funcs) => funcs.len(),
+                ElemInit::Exprs(exprs) => exprs.len(),
+            } as u64;
+            let lim = if is_64 {
+                Limits::new_64(n, Some(n))
+            } else {
+                Limits::new(n, Some(n))
+            };
+            self.tables.push(Table::new(TableType::new(rt, lim), None));
+            self.bump(NameSpace::Elem);
+            let mode = ElemMode::Active {
+                table: TableIdx(idx),
+                offset: zero_offset(is_64),
+            };
+            self.elems.push(Elem::new(rt, init, mode));
+            return Ok(());
+        }
+
+        let tt = self.table_type(cur)?;
+        let init = match cur.is_empty() {
+            true => None,
+            false => Some(self.const_expr(cur)?),
+        };
+        self.tables.push(Table::new(tt, init));
+        Ok(())
+    }
+
+    fn memory(&mut self, cur: &mut Cursor<'a>) -> Result<()> {
+        cur.id();
+        let idx = self.bump(NameSpace::Mem);
+        self.inline_exports(cur, ExportDesc::Mem(MemIdx(idx)))?;
+        if let Some((module, name)) = self.inline_import(cur)? {
+            let mt = self.mem_type(cur)?;
+            self.imports
+                .push(Import::new(&module, &name, ImportDesc::Mem(mt)));
+            return Ok(());
+        }
+
+        // `(memory i64? (data ...))` sizes the memory to fit the data
+        let is_64 = cur.peek_atom() == Some("i64");
+        let data_at = if is_64 { cur.peek2() } else { cur.peek() };
+        if data_at.is_some_and(|item| item.head() == Some("data")) {
+            address_type(cur);
+            let Some(mut list) = cur.list("data") else {
+                return Err(cur.error("expected (data ...)"));
+            };
+            let init = strings(&mut list)?;
+            let pages = (init.len() as u64).div_ceil(PAGE_SIZE);
+            let lim = if is_64 {
+                Limits::new_64(pages, Some(pages))
+            } else {
+                Limits::new(pages, Some(pages))
+            };
+            self.mems.push(MemType::new(lim));
+            self.bump(NameSpace::Data);
+            let mode = DataMode::Active {
+                mem: MemIdx(idx),
+                offset: zero_offset(is_64),
+            };
+            self.datas.push(Data::new(mode, init));
+            return Ok(());
+        }
+
+        let mt = self.mem_type(cur)?;
+        self.mems.push(mt);
+        Ok(())
+    }
+
+    fn import(&mut self, cur: &mut Cursor<'a>) -> Result<()> {
+        let module = cur.name()?;
+        let name = cur.name()?;
+        let Some(mut list) = cur.peek().and_then(Cursor::of) else {
+            return Err(cur.error("expected an import description"));
+        };
+        cur.next();
+        let (kind, pos) = list.atom()?;
+        list.id();
+        let desc = match kind {
+            "func" => {
+                self.bump(NameSpace::Func);
+                ImportDesc::Func(self.type_use(&mut list)?.0)
+            }
+            "table" => {
+                self.bump(NameSpace::Table);
+                ImportDesc::Table(self.table_type(&mut list)?)
+            }
+            "memory" => {
+                self.bump(NameSpace::Mem);
+                ImportDesc::Mem(self.mem_type(&mut list)?)
+            }
+            "global" => {
+                self.bump(NameSpace::Global);
+                ImportDesc::Global(self.global_type(&mut list)?)
+            }
+            "tag" => {
+                self.bump(NameSpace::Tag);
+                ImportDesc::Tag(TagType::new(self.type_use(&mut list)?.0))
+            }
+            _ => return Err(WatError::new(pos, format!("unknown import kind {}", kind))),
+        };
+        list.finish()?;
+        self.imports.push(Import::new(&module, &name, desc));
+        Ok(())
+    }
+
+    fn elem(&mut self, cur: &mut Cursor<'a>) -> Result<()> {
+        cur.id();
+        self.bump(NameSpace::Elem);
+        let mut explicit = false;
+        let mode = if cur.eat("declare") {
+            ElemMode::Declarative
+        } else if let Some(mut list) = cur.list("table") {
+            let table = TableIdx(self.index(&mut list, NameSpace::Table)?);
+            list.finish()?;
+            explicit = true;
+            ElemMode::Active {
+                table,
+                offset: self.offset(cur)?,
+            }
+        } else if cur
+            .peek()
+            .is_some_and(|item| matches!(item, Sexpr::List(..)) && item.head() != Some("ref"))
+        {
+            ElemMode::Active {
+                table: TableIdx(0),
+                offset: self.offset(cur)?,
+            }
+        } else {
+            ElemMode::Passive
+        };
+
+        // The element list; the abbreviated active form leaves out `func`
+        let (ty, init) = if cur.eat("func") || cur.is_empty() || cur.peek().is_some_and(is_index) {
+            (RefType::Func, self.elem_funcs(cur)?)
+        } else {
+            let rt = self.ref_type(cur)?;
+            (rt, self.elem_exprs(cur)?)
+        };
+        let elem = Elem::new(ty, init, mode);
+        self.elems.push(if explicit {
+            elem.with_explicit_table()
+        } else {
+            elem
+        });
+        Ok(())
+    }
+
+    fn data(&mut self, cur: &mut Cursor<'a>) -> Result<()> {
+        cur.id();
+        self.bump(NameSpace::Data);
+        let mut explicit = false;
+        let mode = if let Some(mut list) = cur.list("memory") {
+            let mem = MemIdx(self.index(&mut list, NameSpace::Mem)?);
+            list.finish()?;
+            explicit = true;
+            DataMode::Active {
+                mem,
+                offset: self.offset(cur)?,
+            }
+        } else if let Some(Sexpr::List(..)) = cur.peek() {
+            DataMode::Active {
+                mem: MemIdx(0),
+                offset: self.offset(cur)?,
+            }
+        } else {
+            DataMode::Passive
+        };
+        let data = Data::new(mode, strings(cur)?);
+        self.datas.push(if explicit {
+            data.with_explicit_mem()
+        } else {
+            data
+        });
+        Ok(())
+    }
+
+    // `(@custom "name" (before|after section)? "data"*)`
+    fn custom(&mut self, cur: &mut Cursor<'a>) -> Result<()> {
+        let name = cur.name()?;
+        let mut placement = CustomPlacement::Last;
+        if let Some(mut list) = cur.peek().and_then(Cursor::of) {
+            cur.next();
+            let (side, pos) = list.atom()?;
+            let (section, at) = list.atom()?;
+            list.finish()?;
+            placement = match (side, section) {
+                ("before", "first") | ("after", "first") => CustomPlacement::First,
+                ("after", "last") | ("before", "last") => CustomPlacement::Last,
+                ("after", _) => match section_id(section) {
+                    Some(id) => CustomPlacement::After(id),
+                    None => return Err(WatError::new(at, "unknown section")),
+                },
+                ("before", _) => {
+                    let Some(id) = section_id(section) else {
+                        return Err(WatError::new(at, "unknown section"));
+                    };
+                    // Right before a section is right after the one before
+                    // it in the section order
+                    match SECTION_ORDER.iter().position(|other| *other == id) {
+                        Some(0) | None => CustomPlacement::First,
+                        Some(rank) => CustomPlacement::After(SECTION_ORDER[rank - 1]),
+                    }
+                }
+                _ => return Err(WatError::new(pos, "expected before or after")),
+            };
+        }
+        let data = strings(cur)?;
+        self.customs.push((CustomSec::new(&name, data), placement));
+        Ok(())
+    }
+
+    fn finish(self) -> Module {
+        // Like the builder, only add a data count when a body needs it
+        let needs_data_count = self.codes.iter().any(|code| {
+            code.body()
+                .instrs()
+                .iter()
+                .any(|instr| matches!(instr, Instr::MemoryInit(..) | Instr::DataDrop(_)))
+        });
+
+        let mut module = Module::default();
+        module.version = 1;
+        if !self.types.is_empty() {
+            module.typesec = Some(TypeSec::new(self.types));
+        }
+        if !self.imports.is_empty() {
+            module.importsec = Some(ImportSec::new(self.imports));
+        }
+        if !self.funcs.is_empty() {
+            module.functionsec = Some(FunctionSec::new(self.funcs));
+            module.codesec = Some(CodeSec::new(self.codes));
+        }
+        if !self.tables.is_empty() {
+            module.tablesec = Some(TableSec::new(self.tables));
+        }
+        if !self.mems.is_empty() {
+            module.memsec = Some(MemSec::new(self.mems));
+        }
+        if !self.tags.is_empty() {
+            module.tagsec = Some(TagSec::new(self.tags));
+        }
+        if !self.globals.is_empty() {
+            module.globalsec = Some(GlobalSec::new(self.globals));
+        }
+        if !self.exports.is_empty() {
+            module.exportsec = Some(ExportSec::new(self.exports));
+        }
+        module.startsec = self.start.map(StartSec::new);
+        if !self.elems.is_empty() {
+            module.elemsec = Some(ElemSec::new(self.elems));
+        }
+        if needs_data_count {
+            module.datacountsec = Some(DataCountSec::new(self.datas.len() as u32));
+        }
+        if !self.datas.is_empty() {
+            module.datasec = Some(DataSec::new(self.datas));
+        }
+        let (customs, placements): (Vec<_>, Vec<_>) = self.customs.into_iter().unzip();
+        module.customsecs = customs;
+        module.place_customs(&placements);
+        module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor as IoCursor;
+
+    use super::*;
+    use crate::parseable::ParserLimits;
+    use crate::validate::validate;
+    use crate::wat::print::{PrintOptions, print};
+
+    fn parse(text: &str) -> Module {
+        match parse_module(text) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    fn error(text: &str) -> WatError {
+        match parse_module(text) {
+            Ok(_) => panic!("Expected an error for {:?}", text),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn test_parse_module() {
+        let module = parse(
+            r#"(module
+              (import "env" "log" (func $log (param i32)))
+              (memory (export "mem") (data "hi"))
+              (func $add (export "add") (param $x i32) (param $y i32) (result i32)
+                (local $sum i32)
+                (local.set $sum (i32.add (local.get $x) (local.get $y)))
+                block $out
+                  (br_if $out (i32.eqz (local.get $sum)))
+                  (call $log (local.get $sum))
+                end
+                local.get $sum)
+              (table funcref (elem $add))
+              (global $g (mut i32) (i32.const -1)))"#,
+        );
+
+        let types = module.typesec.as_ref().expect("A type section");
+        assert_eq!(types.types().count(), 2);
+        let imports = module.importsec.as_ref().expect("An import section");
+        assert!(matches!(
+            imports.imports()[0].desc(),
+            ImportDesc::Func(TypeIdx(0))
+        ));
+        let mems = module.memsec.as_ref().expect("A memory section");
+        assert_eq!(mems.mems()[0].limits(), &Limits::new(1, Some(1)));
+        let exports = module.exportsec.as_ref().expect("An export section");
+        let names: Vec<&str> = exports.exports().iter().map(Export::name).collect();
+        assert_eq!(names, ["mem", "add"]);
+
+        let code = &module.codesec.as_ref().expect("A code section").codes()[0];
+        assert_eq!(code.locals(), &[Locals::new(1, ValType::Num(NumType::I32))]);
+        let instrs = code.body().instrs();
+        assert_eq!(
+            &instrs[..4],
+            &[
+                Instr::LocalGet(LocalIdx(0)),
+                Instr::LocalGet(LocalIdx(1)),
+                Instr::Numeric(NumericInstr::I32Add),
+                Instr::LocalSet(LocalIdx(2)),
+            ]
+        );
+        assert!(instrs.contains(&Instr::BrIf(LabelIdx(0))));
+        assert!(instrs.contains(&Instr::Call(FuncIdx(0))));
+
+        let elems = module.elemsec.as_ref().expect("An element section");
+        assert!(matches!(
+            elems.elems()[0].init(),
+            ElemInit::Funcs(funcs) if funcs == &[FuncIdx(1)]
+        ));
+        let datas = module.datasec.as_ref().expect("A data section");
+        assert_eq!(datas.datas()[0].init(), b"hi");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = include_bytes!("../../funcs.wasm");
+        let mut reader = BufReader::new(IoCursor::new(bytes.to_vec()));
+        let module = match Module::parse(&mut reader) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        };
+        for folded in [false, true] {
+            let options = PrintOptions {
+                folded,
+                ..Default::default()
+            };
+            // The binary has an empty import section and empty runs of
+            // locals, which the text can't express, so compare as text
+            let text = print(&module, &options);
+            assert_eq!(print(&parse(&text), &options), text);
+        }
+    }
+
+    #[test]
+    fn test_reprint() {
+        let module = parse(
+            r#"(rec (type $node (struct (field $next (ref null $node)) (field i32))))
+            (type $f (func (param i32) (result i32)))
+            (import "env" "t" (table 1 funcref))
+            (memory $m i64 1 2)
+            (tag $e (param i32))
+            (global (export "g") i64 (i64.const 0x10))
+            (func (type $f)
+              (try_table (result i32) (catch $e 0)
+                (v128.const i32x4 1 2 3 -1)
+                (i8x16.extract_lane_u 15)
+                (v128.load32_lane $m offset=4 1 (i64.const 0) (v128.const i64x2 0 0))
+                drop
+                (call_indirect (type $f) (local.get 0))))
+            (elem (i32.const 0) func 0)
+            (elem declare funcref (ref.func 0))
+            (data $d (i64.const 8) "\00")
+            (func (data.drop $d) (memory.init $d (i64.const 0) (i32.const 0) (i32.const 1)))
+            (@custom "meta" (before func) "x")
+            (@custom "last" "y")"#,
+        );
+        for folded in [false, true] {
+            let options = PrintOptions {
+                folded,
+                ..Default::default()
+            };
+            let text = print(&module, &options);
+            assert_eq!(print(&parse(&text), &options), text);
+        }
+        assert!(module.datacountsec.is_some());
+        if let Err(err) = validate(&module) {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = error("(module\n  (func (call $nope)))");
+        assert_eq!(err.to_string(), "2:15: unknown function $nope");
+        let err = error("(func $f)\n(func $f)");
+        assert_eq!(err.to_string(), "2:7: duplicate function $f");
+        let err = error("(func\n  i32.const 0x1_0000_0000)");
+        assert_eq!(err.to_string(), "2:13: constant out of range for i32");
+        let err = error("(func i32.const 1x)");
+        assert_eq!(err.to_string(), "1:17: unknown operator 1x");
+        let err = error("(func block $a end $b)");
+        assert_eq!(err.to_string(), "1:20: mismatching label $b");
+        let err = error("(func block)");
+        assert_eq!(err.to_string(), "1:12: unclosed block");
+        let err = error("(type (func))\n(func (type 0) (param i32))");
+        assert_eq!(err.pos, Pos { line: 2, col: 13 });
+        let err = error("(func)\n(import \"m\" \"f\" (func))");
+        assert_eq!(err.to_string(), "2:2: import after function");
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // Blocks as deep as the limit allows parse, and print back, without
+        // running out of stack. Unoptimized builds need more than the 2 MiB
+        // test threads have, so this runs with a main thread's 8 MiB.
+        let thread = std::thread::Builder::new().stack_size(8 << 20);
+        let handle = thread.spawn(|| {
+            // Leaving room for the printed `(module (func ...))`
+            let depth = ParserLimits::current().max_nesting - 2;
+            let text = format!("(func {}{})", "(block ".repeat(depth), ")".repeat(depth));
+            let module = parse(&text);
+            assert!(validate(&module).is_ok());
+            let folded = PrintOptions {
+                folded: true,
+                ..PrintOptions::default()
+            };
+            parse(&print(&module, &folded));
+        });
+        handle.expect("A thread").join().expect("No overflow");
+
+        let limits = ParserLimits {
+            max_nesting: 3,
+            ..ParserLimits::default()
+        };
+        let err = limits.apply(|| error("(func (block (block (nop))))"));
+        assert_eq!(err.to_string(), "1:21: nesting exceeds the limit of 3");
+        assert!(
+            limits
+                .apply(|| parse_module("(func (block (nop)))"))
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,1493 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::instr::Instr;
+use crate::instr::expr::Expr;
+use crate::instr::simd::{SimdImm, SimdOp};
+use crate::module::{CustomPlacement, Module};
+use crate::parseable::ParserLimits;
+use crate::section::code::Code;
+use crate::section::data::DataMode;
+use crate::section::element::{ElemInit, ElemMode};
+use crate::section::name::{NameSpace, Names};
+use crate::section::{
+    CODE_SECTION_ID, DATA_COUNT_SECTION_ID, DATA_SECTION_ID, ELEMENT_SECTION_ID, EXPORT_SECTION_ID,
+    FUNCTION_SECTION_ID, GLOBAL_SECTION_ID, IMPORT_SECTION_ID, MEMORY_SECTION_ID, START_SECTION_ID,
+    TABLE_SECTION_ID, TAG_SECTION_ID, TYPE_SECTION_ID,
+};
+use crate::types::block_type::BlockType;
+use crate::types::catch::Catch;
+use crate::types::comp_type::CompType;
+use crate::types::export_desc::ExportDesc;
+use crate::types::func_type::FuncType;
+use crate::types::global_type::GlobalType;
+use crate::types::import_desc::ImportDesc;
+use crate::types::limits::Limits;
+use crate::types::num_type::NumType;
+use crate::types::primitives::{LabelIdx, TypeIdx};
+use crate::types::ref_type::RefType;
+use crate::types::sub_type::SubType;
+use crate::types::table_type::TableType;
+use crate::types::val_type::ValType;
+use crate::validate::{atomic_type, numeric_type, simd_type};
+
+/// How `print` lays out a module.
+#[derive(Debug, Clone, Copy)]
+pub struct PrintOptions {
+    /// Nest the instructions computing an instruction's operands inside it,
+    /// as in `(i32.add (local.get 0) (i32.const 1))`, instead of listing
+    /// the instructions one per line.
+    pub folded: bool,
+    /// Refer to things by the `$names` the name section gives them rather
+    /// than by index.
+    pub names: bool,
+    /// Start each instruction with a comment giving its offset in the code
+    /// section.
+    pub offsets: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            folded: false,
+            names: true,
+            offsets: false,
+        }
+    }
+}
+
+/// Prints `module` in the text format. Assembling the output gives back
+/// the module's binary encoding, with each custom section kept as an
+/// `@custom` annotation.
+pub fn print(module: &Module, options: &PrintOptions) -> String {
+    let mut printer = Printer::new(module, *options);
+    printer.module();
+    printer.out
+}
+
+// The identifiers made from the names in the name section
+#[derive(Default)]
+struct Ids {
+    module: Option<String>,
+    names: HashMap<(NameSpace, u32), String>,
+    locals: HashMap<(u32, u32), String>,
+    labels: HashMap<(u32, u32), String>,
+    fields: HashMap<(u32, u32), String>,
+}
+
+impl Ids {
+    fn new(names: &Names) -> Ids {
+        Ids {
+            module: names.module().map(id),
+            names: unique(names.all(), |(space, _)| space),
+            locals: unique(names.all_locals(), |(func, _)| func),
+            // Labels are allowed to shadow each other
+            labels: names
+                .all_labels()
+                .map(|(key, name)| (key, id(name)))
+                .collect(),
+            fields: unique(names.all_fields(), |(ty, _)| ty),
+        }
+    }
+}
+
+// Makes the names into identifiers, which unlike names have to be unique
+// among the keys in the same group.
+fn unique<'n, K: Copy + Ord + Hash, G: Hash + Eq>(
+    names: impl Iterator<Item = (K, &'n str)>,
+    group: impl Fn(K) -> G,
+) -> HashMap<K, String> {
+    let mut names: Vec<_> = names.collect();
+    names.sort_by_key(|(key, _)| *key);
+
+    let mut taken = HashSet::new();
+    let mut ids = HashMap::new();
+    for (key, name) in names {
+        let base = id(name);
+        let mut id = base.clone();
+        let mut n = 1;
+        while !taken.insert((group(key), id.clone())) {
+            id = format!("{}_{}", base, n);
+            n += 1;
+        }
+        ids.insert(key, id);
+    }
+    ids
+}
+
+// The name as an identifier, with any characters that can't appear in one
+// replaced
+fn id(name: &str) -> String {
+    let chars: String = name
+        .chars()
+        .map(|c| if is_idchar(c) { c } else { '_' })
+        .collect();
+    if chars.is_empty() {
+        "$_".to_string()
+    } else {
+        format!("${}", chars)
+    }
+}
+
+fn is_idchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c)
+}
+
+/// `bytes` as a string literal, with anything but printable ASCII escaped.
+fn string(bytes: &[u8]) -> String {
+    let mut s = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'\t' => s.push_str("\\t"),
+            b'\n' => s.push_str("\\n"),
+            b'\r' => s.push_str("\\r"),
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            0x20..=0x7e => s.push(b as char),
+            _ => s.push_str(&format!("\\{:02x}", b)),
+        }
+    }
+    s.push('"');
+    s
+}
+
+// Floats are printed so that they parse back to the same bits, NaN
+// payloads included.
+fn f32_text(v: f32) -> String {
+    let sign = if v.is_sign_negative() { "-" } else { "" };
+    let payload = v.to_bits() & 0x7f_ffff;
+    if v.is_nan() && payload == 0x40_0000 {
+        format!("{}nan", sign)
+    } else if v.is_nan() {
+        format!("{}nan:{:#x}", sign, payload)
+    } else if v.is_infinite() {
+        format!("{}inf", sign)
+    } else {
+        format!("{:?}", v)
+    }
+}
+
+fn f64_text(v: f64) -> String {
+    let sign = if v.is_sign_negative() { "-" } else { "" };
+    let payload = v.to_bits() & 0xf_ffff_ffff_ffff;
+    if v.is_nan() && payload == 0x8_0000_0000_0000 {
+        format!("{}nan", sign)
+    } else if v.is_nan() {
+        format!("{}nan:{:#x}", sign, payload)
+    } else if v.is_infinite() {
+        format!("{}inf", sign)
+    } else {
+        format!("{:?}", v)
+    }
+}
+
+fn type_names(types: &[ValType]) -> String {
+    types
+        .iter()
+        .map(ValType::name)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// The `(param ...) (result ...)` part of a function type, with a leading
+// space unless it is empty
+fn signature(ft: &FuncType) -> String {
+    let mut s = String::new();
+    if !ft.params().is_empty() {
+        s += &format!(" (param {})", type_names(ft.params()));
+    }
+    if !ft.results().is_empty() {
+        s += &format!(" (result {})", type_names(ft.results()));
+    }
+    s
+}
+
+fn limits(limits: &Limits) -> String {
+    let mut s = String::new();
+    if limits.is_64() {
+        s.push_str("i64 ");
+    }
+    s += &limits.min().to_string();
+    if let Some(max) = limits.max() {
+        s += &format!(" {}", max);
+    }
+    if limits.is_shared() {
+        s.push_str(" shared");
+    }
+    s
+}
+
+fn table_type(tt: &TableType) -> String {
+    format!("{} {}", limits(tt.limits()), tt.elem_type())
+}
+
+fn global_type(gt: &GlobalType) -> String {
+    if gt.is_mutable() {
+        format!("(mut {})", gt.val_type().name())
+    } else {
+        gt.val_type().name()
+    }
+}
+
+// The name custom section placements use for a section
+fn section_name(id: TypeIdx) -> &'static str {
+    match id {
+        TYPE_SECTION_ID => "type",
+        IMPORT_SECTION_ID => "import",
+        FUNCTION_SECTION_ID => "func",
+        TABLE_SECTION_ID => "table",
+        MEMORY_SECTION_ID => "memory",
+        TAG_SECTION_ID => "tag",
+        GLOBAL_SECTION_ID => "global",
+        EXPORT_SECTION_ID => "export",
+        START_SECTION_ID => "start",
+        ELEMENT_SECTION_ID => "elem",
+        DATA_COUNT_SECTION_ID => "datacount",
+        CODE_SECTION_ID => "code",
+        DATA_SECTION_ID => "data",
+        _ => "last",
+    }
+}
+
+// A reference to the label `l` blocks out from the innermost one: its
+// identifier, unless an inner label with the same identifier shadows it.
+fn label_ref(labels: &[Option<&str>], l: LabelIdx) -> String {
+    let depth = l.0 as usize;
+    if depth < labels.len() {
+        let at = labels.len() - 1 - depth;
+        if let Some(id) = labels[at]
+            && !labels[at + 1..].contains(&Some(id))
+        {
+            return id.to_string();
+        }
+    }
+    l.0.to_string()
+}
+
+fn block_type_of(instr: &Instr) -> Option<&BlockType> {
+    match instr {
+        Instr::Block(bt) | Instr::Loop(bt) | Instr::If(bt) | Instr::Try(bt) => Some(bt),
+        Instr::TryTable(bt, _) => Some(bt),
+        _ => None,
+    }
+}
+
+fn indented(lines: Vec<String>) -> impl Iterator<Item = String> {
+    lines.into_iter().map(|line| format!("  {}", line))
+}
+
+// An instruction in the folded form: the instruction, the trees computing
+// its operands and, for a block, the trees in each of its arms.
+struct Tree {
+    instr: usize,
+    // How many values the instruction leaves on the stack, if known
+    pushes: Option<usize>,
+    operands: Vec<Tree>,
+    // Each arm after the first starts with an `else` or `catch`
+    arms: Vec<(Option<usize>, Vec<Tree>)>,
+    // The `end` or `delegate` closing a block
+    end: Option<usize>,
+    // How deeply the tree's lists nest in the folded form
+    depth: usize,
+}
+
+impl Tree {
+    // The instructions in the order the folded form stands for
+    fn order(&self, order: &mut Vec<usize>) {
+        for operand in &self.operands {
+            operand.order(order);
+        }
+        order.push(self.instr);
+        for (sep, body) in &self.arms {
+            order.extend(sep);
+            for tree in body {
+                tree.order(order);
+            }
+        }
+        order.extend(self.end);
+    }
+
+    // Whether the folded form has syntax for the tree's arms and end
+    fn is_well_formed(&self, instrs: &[Instr]) -> bool {
+        let instr = &instrs[self.instr];
+        let seps = self
+            .arms
+            .iter()
+            .skip(1)
+            .map(|(sep, _)| sep.map(|sep| &instrs[sep]));
+        let arms = match instr {
+            Instr::If(_) => {
+                self.arms.len() <= 2 && seps.into_iter().all(|sep| sep == Some(&Instr::Else))
+            }
+            Instr::Try(_) => seps
+                .into_iter()
+                .all(|sep| matches!(sep, Some(Instr::Catch(_) | Instr::CatchAll))),
+            _ => self.arms.len() <= 1,
+        };
+        let end = match self.end.map(|end| &instrs[end]) {
+            None => !instr.opens_block(),
+            Some(Instr::End) => true,
+            Some(Instr::Delegate(_)) => matches!(instr, Instr::Try(_)) && self.arms.len() == 1,
+            Some(_) => false,
+        };
+        arms && end
+            && self.operands.iter().all(|tree| tree.is_well_formed(instrs))
+            && self
+                .arms
+                .iter()
+                .all(|(_, body)| body.iter().all(|tree| tree.is_well_formed(instrs)))
+    }
+}
+
+// Takes the trees at the end of `trees` that compute exactly `pops`
+// operands, if there are such trees.
+fn take(trees: &mut Vec<Tree>, pops: usize) -> Vec<Tree> {
+    let mut sum = 0;
+    let mut n = 0;
+    for tree in trees.iter().rev() {
+        if sum == pops {
+            break;
+        }
+        match tree.pushes {
+            Some(pushes) if pushes > 0 && sum + pushes <= pops => {
+                sum += pushes;
+                n += 1;
+            }
+            _ => break,
+        }
+    }
+    if sum == pops {
+        trees.split_off(trees.len() - n)
+    } else {
+        Vec::new()
+    }
+}
+
+struct Printer<'a> {
+    module: &'a Module,
+    options: PrintOptions,
+    ids: Ids,
+    // The type of every function and tag, imported ones first
+    func_types: Vec<TypeIdx>,
+    tag_types: Vec<TypeIdx>,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn new(module: &'a Module, options: PrintOptions) -> Printer<'a> {
+        let ids = if options.names {
+            Ids::new(&module.names())
+        } else {
+            Ids::default()
+        };
+
+        let mut func_types = Vec::new();
+        let mut tag_types = Vec::new();
+        if let Some(importsec) = &module.importsec {
+            for import in importsec.imports() {
+                match import.desc() {
+                    ImportDesc::Func(ty) => func_types.push(*ty),
+                    ImportDesc::Tag(tag) => tag_types.push(tag.type_idx()),
+                    _ => {}
+                }
+            }
+        }
+        if let Some(functionsec) = &module.functionsec {
+            func_types.extend(functionsec.funcs());
+        }
+        if let Some(tagsec) = &module.tagsec {
+            tag_types.extend(tagsec.tags().iter().map(|tag| tag.type_idx()));
+        }
+
+        Printer {
+            module,
+            options,
+            ids,
+            func_types,
+            tag_types,
+            out: String::new(),
+        }
+    }
+
+    fn line(&mut self, indent: usize, text: &str) {
+        for _ in 0..indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // A reference to an item: its identifier if it has one, otherwise its
+    // index
+    fn idx(&self, space: NameSpace, idx: u32) -> String {
+        match self.ids.names.get(&(space, idx)) {
+            Some(id) => id.clone(),
+            None => idx.to_string(),
+        }
+    }
+
+    fn local(&self, func: Option<u32>, idx: u32) -> String {
+        match func.and_then(|func| self.ids.locals.get(&(func, idx))) {
+            Some(id) => id.clone(),
+            None => idx.to_string(),
+        }
+    }
+
+    // The start of a definition, like `(func $f (;3;)`
+    fn open(&self, kind: &str, space: NameSpace, idx: u32) -> String {
+        match self.ids.names.get(&(space, idx)) {
+            Some(id) => format!("({} {} (;{};)", kind, id, idx),
+            None => format!("({} (;{};)", kind, idx),
+        }
+    }
+
+    fn num_imported(&self, space: NameSpace) -> u32 {
+        let Some(importsec) = &self.module.importsec else {
+            return 0;
+        };
+        let count = importsec
+            .imports()
+            .iter()
+            .filter(|import| {
+                let kind = match import.desc() {
+                    ImportDesc::Func(_) => NameSpace::Func,
+                    ImportDesc::Table(_) => NameSpace::Table,
+                    ImportDesc::Mem(_) => NameSpace::Mem,
+                    ImportDesc::Global(_) => NameSpace::Global,
+                    ImportDesc::Tag(_) => NameSpace::Tag,
+                };
+                kind == space
+            })
+            .count();
+        count as u32
+    }
+
+    fn func_type(&self, ty: TypeIdx) -> Option<&'a FuncType> {
+        self.module.typesec.as_ref()?.func_type(ty)
+    }
+
+    fn type_use(&self, ty: TypeIdx) -> String {
+        format!("(type {})", self.idx(NameSpace::Type, ty.0))
+    }
+
+    // A type use spelled out with the signature, for readability
+    fn func_type_use(&self, ty: TypeIdx) -> String {
+        let sig = self.func_type(ty).map(signature).unwrap_or_default();
+        format!("{}{}", self.type_use(ty), sig)
+    }
+
+    fn block_type(&self, bt: &BlockType) -> String {
+        match bt {
+            BlockType::Type(ty) => format!(" {}", self.type_use(*ty)),
+            _ => bt.to_string(),
+        }
+    }
+
+    fn with_offset(&self, offset: Option<&usize>, text: &str) -> String {
+        match offset {
+            Some(offset) if self.options.offsets => format!("(;{:#06x};) {}", offset, text),
+            _ => text.to_string(),
+        }
+    }
+
+    fn module(&mut self) {
+        let open = match &self.ids.module {
+            Some(id) => format!("(module {}", id),
+            None => "(module".to_string(),
+        };
+        self.line(0, &open);
+        self.types();
+        self.imports();
+        self.funcs();
+        self.tables();
+        self.mems();
+        self.tags();
+        self.globals();
+        self.exports();
+        self.start();
+        self.elems();
+        self.datas();
+        self.customs();
+        self.line(0, ")");
+    }
+
+    fn types(&mut self) {
+        let Some(typesec) = &self.module.typesec else {
+            return;
+        };
+        let mut idx = 0;
+        for rec in typesec.rec_groups() {
+            // A group of one type is the same as the type on its own
+            let grouped = rec.types().len() != 1;
+            if grouped {
+                self.line(1, "(rec");
+            }
+            for sub in rec.types() {
+                let text = format!(
+                    "{} {})",
+                    self.open("type", NameSpace::Type, idx),
+                    self.sub_type(idx, sub)
+                );
+                self.line(if grouped { 2 } else { 1 }, &text);
+                idx += 1;
+            }
+            if grouped {
+                self.line(1, ")");
+            }
+        }
+    }
+
+    fn sub_type(&self, ty: u32, sub: &SubType) -> String {
+        let comp = match sub.comp_type() {
+            CompType::Func(ft) => format!("(func{})", signature(ft)),
+            CompType::Struct(fields) => {
+                let mut s = String::from("(struct");
+                for (i, field) in fields.iter().enumerate() {
+                    match self.ids.fields.get(&(ty, i as u32)) {
+                        Some(id) => s += &format!(" (field {} {})", id, field),
+                        None => s += &format!(" (field {})", field),
+                    }
+                }
+                s + ")"
+            }
+            CompType::Array(field) => format!("(array {})", field),
+        };
+        if sub.is_final() && sub.supertypes().is_empty() {
+            return comp;
+        }
+        let mut s = String::from("(sub");
+        if sub.is_final() {
+            s.push_str(" final");
+        }
+        for supertype in sub.supertypes() {
+            s += &format!(" {}", self.idx(NameSpace::Type, supertype.0));
+        }
+        format!("{} {})", s, comp)
+    }
+
+    fn imports(&mut self) {
+        let Some(importsec) = &self.module.importsec else {
+            return;
+        };
+        let mut counts: HashMap<NameSpace, u32> = HashMap::new();
+        let mut next = |space| {
+            let count = counts.entry(space).or_default();
+            *count += 1;
+            *count - 1
+        };
+        for import in importsec.imports() {
+            let desc = match import.desc() {
+                ImportDesc::Func(ty) => format!(
+                    "{} {})",
+                    self.open("func", NameSpace::Func, next(NameSpace::Func)),
+                    self.func_type_use(*ty)
+                ),
+                ImportDesc::Table(tt) => format!(
+                    "{} {})",
+                    self.open("table", NameSpace::Table, next(NameSpace::Table)),
+                    table_type(tt)
+                ),
+                ImportDesc::Mem(mt) => format!(
+                    "{} {})",
+                    self.open("memory", NameSpace::Mem, next(NameSpace::Mem)),
+                    limits(mt.limits())
+                ),
+                ImportDesc::Global(gt) => format!(
+                    "{} {})",
+                    self.open("global", NameSpace::Global, next(NameSpace::Global)),
+                    global_type(gt)
+                ),
+                ImportDesc::Tag(tag) => format!(
+                    "{} {})",
+                    self.open("tag", NameSpace::Tag, next(NameSpace::Tag)),
+                    self.func_type_use(tag.type_idx())
+                ),
+            };
+            let text = format!(
+                "(import {} {} {})",
+                string(import.module().as_bytes()),
+                string(import.name().as_bytes()),
+                desc
+            );
+            self.line(1, &text);
+        }
+    }
+
+    fn funcs(&mut self) {
+        let module = self.module;
+        let Some(functionsec) = &module.functionsec else {
+            return;
+        };
+        let codes = module.codesec.as_ref().map_or(&[][..], |sec| sec.codes());
+        let imported = self.num_imported(NameSpace::Func);
+        for (i, ty) in functionsec.funcs().iter().enumerate() {
+            self.func(imported + i as u32, *ty, codes.get(i));
+        }
+    }
+
+    // `(param ...)` or `(local ...)` declarations of `types`, the first of
+    // which has index `first`. Named ones need a declaration each.
+    fn decls(&self, kind: &str, func: u32, first: usize, types: &[ValType]) -> String {
+        let mut s = String::new();
+        let mut unnamed = Vec::new();
+        let flush = |s: &mut String, unnamed: &mut Vec<String>| {
+            if !unnamed.is_empty() {
+                *s += &format!(" ({} {})", kind, unnamed.join(" "));
+                unnamed.clear();
+            }
+        };
+        for (i, t) in types.iter().enumerate() {
+            match self.ids.locals.get(&(func, (first + i) as u32)) {
+                Some(id) => {
+                    flush(&mut s, &mut unnamed);
+                    s += &format!(" ({} {} {})", kind, id, t.name());
+                }
+                None => unnamed.push(t.name()),
+            }
+        }
+        flush(&mut s, &mut unnamed);
+        s
+    }
+
+    fn func(&mut self, func: u32, ty: TypeIdx, code: Option<&Code>) {
+        let ft = self.func_type(ty);
+        let params = ft.map_or(&[][..], |ft| ft.params());
+        let mut head = format!(
+            "{} {}{}",
+            self.open("func", NameSpace::Func, func),
+            self.type_use(ty),
+            self.decls("param", func, 0, params)
+        );
+        if let Some(ft) = ft
+            && !ft.results().is_empty()
+        {
+            head += &format!(" (result {})", type_names(ft.results()));
+        }
+        let Some(code) = code else {
+            self.line(1, &(head + ")"));
+            return;
+        };
+        self.line(1, &head);
+
+        let locals: Vec<ValType> = code.local_types().collect();
+        if !locals.is_empty() {
+            let decls = self.decls("local", func, params.len(), &locals);
+            self.line(2, decls.trim_start());
+        }
+        self.body(func, ft.map_or(0, |ft| ft.results().len()), code);
+        self.line(1, ")");
+    }
+
+    fn body(&mut self, func: u32, results: usize, code: &Code) {
+        let instrs = code.body().instrs();
+        // The final `end` is implied by the closing parenthesis
+        let instrs = match instrs.last() {
+            Some(Instr::End) => &instrs[..instrs.len() - 1],
+            _ => instrs,
+        };
+        let texts = self.instr_texts(Some(func), instrs);
+        let offsets = code.offsets();
+
+        if self.options.folded
+            && let Some(trees) = self.fold(instrs, results)
+        {
+            for tree in &trees {
+                for line in self.tree_lines(tree, instrs, &texts, offsets) {
+                    self.line(2, &line);
+                }
+            }
+            return;
+        }
+
+        let mut indent = 2;
+        for (i, instr) in instrs.iter().enumerate() {
+            if instr.closes_block() || instr.continues_block() {
+                indent = (indent - 1).max(2);
+            }
+            let text = self.with_offset(offsets.get(i), &texts[i]);
+            self.line(indent, &text);
+            if instr.opens_block() || instr.continues_block() {
+                indent += 1;
+            }
+        }
+    }
+
+    // Each instruction in the flat form, with identifiers for the things it
+    // refers to. `func` is the function the instructions belong to, if
+    // they are not a constant expression.
+    fn instr_texts(&self, func: Option<u32>, instrs: &[Instr]) -> Vec<String> {
+        let mut labels: Vec<Option<&str>> = Vec::new();
+        let mut blocks = 0;
+        let mut texts = Vec::with_capacity(instrs.len());
+        for instr in instrs {
+            let text = match instr {
+                Instr::Block(bt)
+                | Instr::Loop(bt)
+                | Instr::If(bt)
+                | Instr::Try(bt)
+                | Instr::TryTable(bt, _) => {
+                    let mut text = match instr {
+                        Instr::Block(_) => "block",
+                        Instr::Loop(_) => "loop",
+                        Instr::If(_) => "if",
+                        Instr::Try(_) => "try",
+                        _ => "try_table",
+                    }
+                    .to_string();
+                    let id = func.and_then(|func| self.ids.labels.get(&(func, blocks)));
+                    blocks += 1;
+                    if let Some(id) = id {
+                        text += &format!(" {}", id);
+                    }
+                    text += &self.block_type(bt);
+                    // Catch labels are outside the `try_table`'s own
+                    if let Instr::TryTable(_, catches) = instr {
+                        for catch in catches {
+                            text += &format!(" {}", self.catch(&labels, catch));
+                        }
+                    }
+                    labels.push(id.map(String::as_str));
+                    text
+                }
+                Instr::End => {
+                    labels.pop();
+                    "end".to_string()
+                }
+                Instr::Delegate(l) => {
+                    labels.pop();
+                    format!("delegate {}", label_ref(&labels, *l))
+                }
+                Instr::Br(l) => format!("br {}", label_ref(&labels, *l)),
+                Instr::BrIf(l) => format!("br_if {}", label_ref(&labels, *l)),
+                Instr::BrTable(targets, default) => {
+                    let mut text = String::from("br_table");
+                    for l in targets.iter().chain([default]) {
+                        text += &format!(" {}", label_ref(&labels, *l));
+                    }
+                    text
+                }
+                Instr::BrOnNull(l) => format!("br_on_null {}", label_ref(&labels, *l)),
+                Instr::BrOnNonNull(l) => format!("br_on_non_null {}", label_ref(&labels, *l)),
+                Instr::BrOnCast(l, rt1, rt2) => {
+                    format!("br_on_cast {} {} {}", label_ref(&labels, *l), rt1, rt2)
+                }
+                Instr::BrOnCastFail(l, rt1, rt2) => {
+                    format!("br_on_cast_fail {} {} {}", label_ref(&labels, *l), rt1, rt2)
+                }
+                Instr::Rethrow(l) => format!("rethrow {}", label_ref(&labels, *l)),
+                Instr::Call(f) => format!("call {}", self.idx(NameSpace::Func, f.0)),
+                Instr::ReturnCall(f) => format!("return_call {}", self.idx(NameSpace::Func, f.0)),
+                Instr::RefFunc(f) => format!("ref.func {}", self.idx(NameSpace::Func, f.0)),
+                Instr::CallIndirect(ty, table) => format!(
+                    "call_indirect {} {}",
+                    self.idx(NameSpace::Table, table.0),
+                    self.type_use(*ty)
+                ),
+                Instr::ReturnCallIndirect(ty, table) => format!(
+                    "return_call_indirect {} {}",
+                    self.idx(NameSpace::Table, table.0),
+                    self.type_use(*ty)
+                ),
+                Instr::LocalGet(x) => format!("local.get {}", self.local(func, x.0)),
+                Instr::LocalSet(x) => format!("local.set {}", self.local(func, x.0)),
+                Instr::LocalTee(x) => format!("local.tee {}", self.local(func, x.0)),
+                Instr::GlobalGet(x) => format!("global.get {}", self.idx(NameSpace::Global, x.0)),
+                Instr::GlobalSet(x) => format!("global.set {}", self.idx(NameSpace::Global, x.0)),
+                Instr::Throw(tag) => format!("throw {}", self.idx(NameSpace::Tag, tag.0)),
+                Instr::Catch(tag) => format!("catch {}", self.idx(NameSpace::Tag, tag.0)),
+                Instr::F32Const(v) => format!("f32.const {}", f32_text(*v)),
+                Instr::F64Const(v) => format!("f64.const {}", f64_text(*v)),
+                Instr::Simd(SimdOp::V128Const, SimdImm::Bytes(bytes)) => {
+                    let mut text = String::from("v128.const i8x16");
+                    for b in bytes {
+                        text += &format!(" {}", b);
+                    }
+                    text
+                }
+                _ => instr.to_string(),
+            };
+            texts.push(text);
+        }
+        texts
+    }
+
+    fn catch(&self, labels: &[Option<&str>], catch: &Catch) -> String {
+        match catch {
+            Catch::Catch(tag, l) => format!(
+                "(catch {} {})",
+                self.idx(NameSpace::Tag, tag.0),
+                label_ref(labels, *l)
+            ),
+            Catch::CatchRef(tag, l) => format!(
+                "(catch_ref {} {})",
+                self.idx(NameSpace::Tag, tag.0),
+                label_ref(labels, *l)
+            ),
+            Catch::CatchAll(l) => format!("(catch_all {})", label_ref(labels, *l)),
+            Catch::CatchAllRef(l) => format!("(catch_all_ref {})", label_ref(labels, *l)),
+        }
+    }
+
+    // A constant expression in the flat form, without its `end`
+    fn expr(&self, expr: &Expr) -> String {
+        let instrs = expr.instrs();
+        let instrs = match instrs.last() {
+            Some(Instr::End) => &instrs[..instrs.len() - 1],
+            _ => instrs,
+        };
+        self.instr_texts(None, instrs).join(" ")
+    }
+
+    // The offset of a segment, abbreviated when it is a single instruction
+    fn offset(&self, expr: &Expr) -> String {
+        if expr.instrs().len() == 2 {
+            format!("({})", self.expr(expr))
+        } else {
+            format!("(offset {})", self.expr(expr))
+        }
+    }
+
+    fn sig_arity(&self, ty: TypeIdx) -> Option<(usize, usize)> {
+        self.func_type(ty)
+            .map(|ft| (ft.params().len(), ft.results().len()))
+    }
+
+    // The number of operands an instruction pops and the number of results
+    // it pushes, if they are known without type checking. `labels` has the
+    // number of values a branch to each enclosing label takes.
+    fn arity(&self, instr: &Instr, labels: &[Option<usize>]) -> Option<(usize, usize)> {
+        let label = |l: &LabelIdx| {
+            let at = labels.len().checked_sub(1 + l.0 as usize)?;
+            labels[at]
+        };
+        let func = |f: u32| self.sig_arity(*self.func_types.get(f as usize)?);
+        let arity = match instr {
+            Instr::Unreachable | Instr::Nop | Instr::DataDrop(_) | Instr::ElemDrop(_) => (0, 0),
+            Instr::AtomicFence => (0, 0),
+            Instr::Br(l) => (label(l)?, 0),
+            Instr::BrIf(l) => (label(l)? + 1, label(l)?),
+            Instr::BrTable(_, default) => (label(default)? + 1, 0),
+            Instr::Return => (labels.first().copied()??, 0),
+            Instr::Call(f) => func(f.0)?,
+            Instr::ReturnCall(f) => (func(f.0)?.0, 0),
+            Instr::CallIndirect(ty, _) | Instr::CallRef(ty) => {
+                let (params, results) = self.sig_arity(*ty)?;
+                (params + 1, results)
+            }
+            Instr::ReturnCallIndirect(ty, _) | Instr::ReturnCallRef(ty) => {
+                (self.sig_arity(*ty)?.0 + 1, 0)
+            }
+            Instr::Throw(tag) => (self.sig_arity(*self.tag_types.get(tag.0 as usize)?)?.0, 0),
+            Instr::ThrowRef | Instr::Drop => (1, 0),
+            Instr::RefNull(_) | Instr::RefFunc(_) | Instr::StructNewDefault(_) => (0, 1),
+            Instr::RefIsNull
+            | Instr::RefAsNonNull
+            | Instr::RefTest(_)
+            | Instr::RefCast(_)
+            | Instr::AnyConvertExtern
+            | Instr::ExternConvertAny
+            | Instr::RefI31
+            | Instr::I31GetS
+            | Instr::I31GetU
+            | Instr::ArrayLen
+            | Instr::ArrayNewDefault(_)
+            | Instr::StructGet(..)
+            | Instr::StructGetS(..)
+            | Instr::StructGetU(..) => (1, 1),
+            Instr::RefEq => (2, 1),
+            Instr::StructSet(..) => (2, 0),
+            Instr::ArrayNew(_)
+            | Instr::ArrayNewData(..)
+            | Instr::ArrayNewElem(..)
+            | Instr::ArrayGet(_)
+            | Instr::ArrayGetS(_)
+            | Instr::ArrayGetU(_) => (2, 1),
+            Instr::ArrayNewFixed(_, n) => (*n as usize, 1),
+            Instr::ArraySet(_) => (3, 0),
+            Instr::ArrayFill(_) | Instr::ArrayInitData(..) | Instr::ArrayInitElem(..) => (4, 0),
+            Instr::ArrayCopy(..) => (5, 0),
+            Instr::Select(_) => (3, 1),
+            Instr::LocalGet(_) | Instr::GlobalGet(_) => (0, 1),
+            Instr::LocalSet(_) | Instr::GlobalSet(_) => (1, 0),
+            Instr::LocalTee(_) => (1, 1),
+            Instr::TableGet(_) => (1, 1),
+            Instr::TableSet(_) => (2, 0),
+            Instr::TableSize(_) | Instr::MemorySize(_) => (0, 1),
+            Instr::TableGrow(_) => (2, 1),
+            Instr::TableFill(_) | Instr::TableInit(..) | Instr::TableCopy(..) => (3, 0),
+            Instr::Load(..) | Instr::MemoryGrow(_) => (1, 1),
+            Instr::Store(..) => (2, 0),
+            Instr::MemoryInit(..) | Instr::MemoryCopy(..) | Instr::MemoryFill(_) => (3, 0),
+            Instr::I32Const(_) | Instr::I64Const(_) | Instr::F32Const(_) | Instr::F64Const(_) => {
+                (0, 1)
+            }
+            Instr::Numeric(op) => (numeric_type(*op).0.len(), 1),
+            Instr::Simd(op, _) => {
+                let (params, results) = simd_type(*op, ValType::Num(NumType::I32));
+                (params.len(), results.len())
+            }
+            Instr::Atomic(op, _) => {
+                // The address comes before the other operands
+                let (params, results, _) = atomic_type(*op);
+                (params.len() + 1, results.len())
+            }
+            _ => return None,
+        };
+        Some(arity)
+    }
+
+    // The number of parameters and results of a block, if known
+    fn block_arity(&self, bt: &BlockType) -> (Option<usize>, Option<usize>) {
+        match bt {
+            BlockType::Empty => (Some(0), Some(0)),
+            BlockType::Value(_) => (Some(0), Some(1)),
+            BlockType::Type(ty) => match self.sig_arity(*ty) {
+                Some((params, results)) => (Some(params), Some(results)),
+                None => (None, None),
+            },
+        }
+    }
+
+    // The body of a function as trees, or `None` if the folded form can't
+    // express it. Folding only changes how the instructions are grouped,
+    // so the trees stand for the same instructions in the same order.
+    // Trees nested deeper than the text parser reads aren't built, which
+    // also keeps walking them from running out of stack.
+    fn fold(&self, instrs: &[Instr], results: usize) -> Option<Vec<Tree>> {
+        let mut i = 0;
+        let mut labels = vec![Some(results)];
+        // Inside `(module (func ...))`
+        let limit = ParserLimits::current().max_nesting.saturating_sub(2);
+        let trees = self.fold_seq(instrs, &mut i, &mut labels, limit)?;
+
+        let mut order = Vec::new();
+        for tree in &trees {
+            tree.order(&mut order);
+        }
+        let complete = order.into_iter().eq(0..instrs.len());
+        (complete && trees.iter().all(|tree| tree.is_well_formed(instrs))).then_some(trees)
+    }
+
+    // Folds instructions up to the end of the innermost block or the start
+    // of its next arm, or gives up on a tree nested deeper than `limit`.
+    fn fold_seq(
+        &self,
+        instrs: &[Instr],
+        i: &mut usize,
+        labels: &mut Vec<Option<usize>>,
+        limit: usize,
+    ) -> Option<Vec<Tree>> {
+        let mut trees: Vec<Tree> = Vec::new();
+        while let Some(instr) = instrs.get(*i) {
+            if instr.closes_block() || instr.continues_block() {
+                break;
+            }
+            let at = *i;
+            *i += 1;
+
+            let Some(bt) = block_type_of(instr) else {
+                let (pushes, operands) = match self.arity(instr, labels) {
+                    Some((pops, pushes)) => (Some(pushes), take(&mut trees, pops)),
+                    None => (None, Vec::new()),
+                };
+                let depth = 1 + operands.iter().map(|tree| tree.depth).max().unwrap_or(0);
+                if depth > limit {
+                    return None;
+                }
+                trees.push(Tree {
+                    instr: at,
+                    pushes,
+                    operands,
+                    arms: Vec::new(),
+                    end: None,
+                    depth,
+                });
+                continue;
+            };
+
+            let (params, results) = self.block_arity(bt);
+            // Only the condition of an `if` goes inside it
+            let operands = match instr {
+                Instr::If(_) if params == Some(0) => take(&mut trees, 1),
+                _ => Vec::new(),
+            };
+            labels.push(if matches!(instr, Instr::Loop(_)) {
+                params
+            } else {
+                results
+            });
+            // The arms of an `if` or `try` are lists of their own
+            let arm_depth = usize::from(matches!(instr, Instr::If(_) | Instr::Try(_)));
+            if limit < 1 + arm_depth {
+                return None;
+            }
+            let body = self.fold_seq(instrs, i, labels, limit - 1 - arm_depth)?;
+            let mut arms = vec![(None, body)];
+            let mut end = None;
+            while let Some(next) = instrs.get(*i) {
+                let sep = *i;
+                *i += 1;
+                if next.continues_block() {
+                    let body = self.fold_seq(instrs, i, labels, limit.saturating_sub(2))?;
+                    arms.push((Some(sep), body));
+                } else {
+                    end = Some(sep);
+                    break;
+                }
+            }
+            labels.pop();
+            let arms_depth = arms.iter().map(|(sep, body)| {
+                let open = usize::from(sep.is_some()).max(arm_depth);
+                open + body.iter().map(|tree| tree.depth).max().unwrap_or(0)
+            });
+            // A `delegate` is a list of its own
+            let delegate = end.is_some_and(|end| matches!(instrs[end], Instr::Delegate(_)));
+            let depth = operands
+                .iter()
+                .map(|tree| tree.depth)
+                .chain(arms_depth)
+                .chain([usize::from(delegate)])
+                .max()
+                .map_or(1, |depth| depth + 1);
+            if depth > limit {
+                return None;
+            }
+            trees.push(Tree {
+                instr: at,
+                pushes: results,
+                operands,
+                arms,
+                end,
+                depth,
+            });
+        }
+        Some(trees)
+    }
+
+    // The lines of a tree in the folded form, relative to its indentation
+    fn tree_lines(
+        &self,
+        tree: &Tree,
+        instrs: &[Instr],
+        texts: &[String],
+        offsets: &[usize],
+    ) -> Vec<String> {
+        let head = self.with_offset(offsets.get(tree.instr), &format!("({}", texts[tree.instr]));
+        let operands: Vec<Vec<String>> = tree
+            .operands
+            .iter()
+            .map(|operand| self.tree_lines(operand, instrs, texts, offsets))
+            .collect();
+
+        let instr = &instrs[tree.instr];
+        if !instr.opens_block() {
+            // Short expressions fit on one line
+            if !self.options.offsets && operands.iter().all(|lines| lines.len() == 1) {
+                let mut inline = head.clone();
+                for lines in &operands {
+                    inline += &format!(" {}", lines[0]);
+                }
+                inline.push(')');
+                if inline.len() <= 80 {
+                    return vec![inline];
+                }
+            }
+            let mut lines = vec![head];
+            for operand in operands {
+                lines.extend(indented(operand));
+            }
+            if let Some(last) = lines.last_mut() {
+                last.push(')');
+            }
+            return lines;
+        }
+
+        let mut lines = vec![head];
+        for operand in operands {
+            lines.extend(indented(operand));
+        }
+        for (sep, body) in &tree.arms {
+            let body: Vec<String> = body
+                .iter()
+                .flat_map(|tree| self.tree_lines(tree, instrs, texts, offsets))
+                .collect();
+            let open = match (instr, sep) {
+                (Instr::If(_), None) => "(then".to_string(),
+                (Instr::Try(_), None) => "(do".to_string(),
+                (_, None) => {
+                    lines.extend(indented(body));
+                    continue;
+                }
+                (_, Some(sep)) => self.with_offset(offsets.get(*sep), &format!("({}", texts[*sep])),
+            };
+            // An empty arm is kept, since its `else` or `catch` is
+            if body.is_empty() {
+                lines.push(format!("  {})", open));
+            } else {
+                lines.push(format!("  {}", open));
+                lines.extend(indented(indented(body).collect()));
+                lines.push("  )".to_string());
+            }
+        }
+        if let Some(end) = tree.end
+            && let Instr::Delegate(_) = instrs[end]
+        {
+            lines.push(format!("  ({})", texts[end]));
+        }
+        lines.push(")".to_string());
+        lines
+    }
+
+    fn tables(&mut self) {
+        let Some(tablesec) = &self.module.tablesec else {
+            return;
+        };
+        let imported = self.num_imported(NameSpace::Table);
+        for (i, table) in tablesec.tables().iter().enumerate() {
+            let mut text = format!(
+                "{} {}",
+                self.open("table", NameSpace::Table, imported + i as u32),
+                table_type(table.table_type())
+            );
+            if let Some(init) = table.init() {
+                text += &format!(" {}", self.expr(init));
+            }
+            self.line(1, &(text + ")"));
+        }
+    }
+
+    fn mems(&mut self) {
+        let Some(memsec) = &self.module.memsec else {
+            return;
+        };
+        let imported = self.num_imported(NameSpace::Mem);
+        for (i, mem) in memsec.mems().iter().enumerate() {
+            let text = format!(
+                "{} {})",
+                self.open("memory", NameSpace::Mem, imported + i as u32),
+                limits(mem.limits())
+            );
+            self.line(1, &text);
+        }
+    }
+
+    fn tags(&mut self) {
+        let Some(tagsec) = &self.module.tagsec else {
+            return;
+        };
+        let imported = self.num_imported(NameSpace::Tag);
+        for (i, tag) in tagsec.tags().iter().enumerate() {
+            let text = format!(
+                "{} {})",
+                self.open("tag", NameSpace::Tag, imported + i as u32),
+                self.func_type_use(tag.type_idx())
+            );
+            self.line(1, &text);
+        }
+    }
+
+    fn globals(&mut self) {
+        let Some(globalsec) = &self.module.globalsec else {
+            return;
+        };
+        let imported = self.num_imported(NameSpace::Global);
+        for (i, global) in globalsec.globals().iter().enumerate() {
+            let text = format!(
+                "{} {} {})",
+                self.open("global", NameSpace::Global, imported + i as u32),
+                global_type(global.global_type()),
+                self.expr(global.init())
+            );
+            self.line(1, &text);
+        }
+    }
+
+    fn exports(&mut self) {
+        let Some(exportsec) = &self.module.exportsec else {
+            return;
+        };
+        for export in exportsec.exports() {
+            let (kind, space, idx) = match export.desc() {
+                ExportDesc::Func(idx) => ("func", NameSpace::Func, idx.0),
+                ExportDesc::Table(idx) => ("table", NameSpace::Table, idx.0),
+                ExportDesc::Mem(idx) => ("memory", NameSpace::Mem, idx.0),
+                ExportDesc::Global(idx) => ("global", NameSpace::Global, idx.0),
+                ExportDesc::Tag(idx) => ("tag", NameSpace::Tag, idx.0),
+            };
+            let text = format!(
+                "(export {} ({} {}))",
+                string(export.name().as_bytes()),
+                kind,
+                self.idx(space, idx)
+            );
+            self.line(1, &text);
+        }
+    }
+
+    fn start(&mut self) {
+        if let Some(startsec) = &self.module.startsec {
+            let text = format!("(start {})", self.idx(NameSpace::Func, startsec.func().0));
+            self.line(1, &text);
+        }
+    }
+
+    fn elems(&mut self) {
+        let Some(elemsec) = &self.module.elemsec else {
+            return;
+        };
+        for (i, elem) in elemsec.elems().iter().enumerate() {
+            let mut text = self.open("elem", NameSpace::Elem, i as u32);
+            // Only the abbreviated active form leaves out the element type
+            let explicit = match elem.mode() {
+                ElemMode::Active { table, offset } => {
+                    let explicit =
+                        elem.explicit_table() || table.0 != 0 || elem.ref_type() != RefType::Func;
+                    if explicit {
+                        text += &format!(" (table {})", self.idx(NameSpace::Table, table.0));
+                    }
+                    text += &format!(" {}", self.offset(offset));
+                    explicit
+                }
+                ElemMode::Passive => true,
+                ElemMode::Declarative => {
+                    text.push_str(" declare");
+                    true
+                }
+            };
+            match elem.init() {
+                ElemInit::Funcs(funcs) => {
+                    if explicit {
+                        text.push_str(" func");
+                    }
+                    for f in funcs {
+                        text += &format!(" {}", self.idx(NameSpace::Func, f.0));
+                    }
+                }
+                ElemInit::Exprs(exprs) => {
+                    text += &format!(" {}", elem.ref_type());
+                    for expr in exprs {
+                        text += &format!(" (item {})", self.expr(expr));
+                    }
+                }
+            }
+            self.line(1, &(text + ")"));
+        }
+    }
+
+    // Writes `open`, then `bytes` as strings, then the closing parenthesis,
+    // breaking long contents over several lines
+    fn strings(&mut self, open: String, bytes: &[u8]) {
+        const CHUNK: usize = 64;
+        if bytes.len() <= CHUNK {
+            self.line(1, &format!("{} {})", open, string(bytes)));
+            return;
+        }
+        self.line(1, &open);
+        for chunk in bytes.chunks(CHUNK) {
+            self.line(2, &string(chunk));
+        }
+        self.line(1, ")");
+    }
+
+    fn datas(&mut self) {
+        let Some(datasec) = &self.module.datasec else {
+            return;
+        };
+        for (i, data) in datasec.datas().iter().enumerate() {
+            let mut open = self.open("data", NameSpace::Data, i as u32);
+            if let DataMode::Active { mem, offset } = data.mode() {
+                if data.explicit_mem() || mem.0 != 0 {
+                    open += &format!(" (memory {})", self.idx(NameSpace::Mem, mem.0));
+                }
+                open += &format!(" {}", self.offset(offset));
+            }
+            self.strings(open, data.init());
+        }
+    }
+
+    fn customs(&mut self) {
+        let module = self.module;
+        for (custom, placement) in module.customsecs.iter().zip(module.custom_placements()) {
+            let placement = match placement {
+                CustomPlacement::First => "(before first)".to_string(),
+                CustomPlacement::After(id) => format!("(after {})", section_name(id)),
+                CustomPlacement::Last => "(after last)".to_string(),
+            };
+            let open = format!(
+                "(@custom {} {}",
+                string(custom.name().as_bytes()),
+                placement
+            );
+            self.strings(open, custom.data());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::ModuleBuilder;
+    use crate::instr::numeric::NumericInstr;
+    use crate::parseable::ParseError;
+    use crate::types::mem_type::MemType;
+    use crate::types::primitives::LocalIdx;
+    use std::io::{BufReader, Cursor};
+
+    const I32: ValType = ValType::Num(NumType::I32);
+
+    fn subsection(id: u8, contents: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![id, contents.len() as u8];
+        bytes.extend_from_slice(contents);
+        bytes
+    }
+
+    // A module with an imported function and a function that uses it in a
+    // block, named by a name section
+    fn named_module() -> Module {
+        let mut builder = ModuleBuilder::new();
+        let log = builder
+            .import_func("env", "log", FuncType::new(vec![I32], vec![]))
+            .unwrap();
+        let mem = builder.memory(MemType::new(Limits::new(1, None)));
+        builder.data(mem, vec![Instr::I32Const(16)], b"hi\n\"\\\x01".to_vec());
+        builder.func(FuncType::new(vec![I32], vec![I32]), |body| {
+            body.instrs([
+                Instr::Block(BlockType::Empty),
+                Instr::LocalGet(LocalIdx(0)),
+                Instr::BrIf(LabelIdx(0)),
+                Instr::LocalGet(LocalIdx(0)),
+                Instr::Call(log),
+                Instr::End,
+                Instr::LocalGet(LocalIdx(0)),
+                Instr::I32Const(1),
+                Instr::Numeric(NumericInstr::I32Add),
+            ]);
+        });
+        let mut bytes = Vec::new();
+        builder
+            .build()
+            .encode(&mut bytes)
+            .expect("The encoded module");
+
+        let mut names = b"\x04name".to_vec();
+        names.extend(subsection(1, b"\x02\x00\x03log\x01\x07add one"));
+        names.extend(subsection(2, b"\x01\x01\x01\x00\x01x"));
+        names.extend(subsection(3, b"\x01\x01\x01\x00\x03out"));
+        bytes.extend(subsection(0, &names));
+
+        let mut reader = BufReader::new(Cursor::new(bytes));
+        match Module::parse(&mut reader) {
+            Ok(module) => module,
+            Err(err) => panic!("{}", ParseError::from(err)),
+        }
+    }
+
+    fn assert_lines(text: &str, expected: &[&str]) {
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+        for line in expected {
+            assert!(lines.contains(line), "{:?} not in\n{}", line, text);
+        }
+    }
+
+    #[test]
+    fn test_print_flat() {
+        let module = named_module();
+        let text = print(&module, &PrintOptions::default());
+        assert_lines(
+            &text,
+            &[
+                "(module",
+                "(type (;0;) (func (param i32)))",
+                "(import \"env\" \"log\" (func $log (;0;) (type 0) (param i32)))",
+                "(func $add_one (;1;) (type 1) (param $x i32) (result i32)",
+                "block $out",
+                "br_if $out",
+                "call $log",
+                "i32.add",
+                "(memory (;0;) 1)",
+                "(data (;0;) (i32.const 16) \"hi\\n\\\"\\\\\\01\")",
+            ],
+        );
+        assert!(text.contains("(@custom \"name\" (after last) \"\\01"));
+
+        let options = PrintOptions {
+            names: false,
+            offsets: true,
+            ..Default::default()
+        };
+        let text = print(&module, &options);
+        assert_lines(
+            &text,
+            &[
+                "(func (;1;) (type 1) (param i32) (result i32)",
+                "(;0x0003;) block",
+                "(;0x0007;) br_if 0",
+                "(;0x000b;) call 0",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_print_folded() {
+        let options = PrintOptions {
+            folded: true,
+            ..Default::default()
+        };
+        let text = print(&named_module(), &options);
+        assert_lines(
+            &text,
+            &[
+                "(block $out",
+                "(br_if $out (local.get $x))",
+                "(call $log (local.get $x))",
+                ")",
+                "(i32.add (local.get $x) (i32.const 1))",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_print_deep() {
+        // Expressions nested deeper than the text parser reads are printed
+        // flat, without running out of stack
+        let text = format!(
+            "(func (result i32) i32.const 0 {})",
+            "i32.const 1 i32.add ".repeat(20_000)
+        );
+        let module = crate::wat::parse::parse_module(&text).expect("A module");
+        let options = PrintOptions {
+            folded: true,
+            ..Default::default()
+        };
+        let printed = print(&module, &options);
+        assert!(printed.contains("\n    i32.add\n"));
+        let reparsed = crate::wat::parse::parse_module(&printed).expect("The printed module");
+        let encode = |module: &Module| {
+            let mut bytes = Vec::new();
+            module.encode(&mut bytes).expect("The encoded module");
+            bytes
+        };
+        assert_eq!(encode(&reparsed), encode(&module));
+
+        // Shallower ones are still folded
+        let text = "(func (result i32) i32.const 0 i32.const 1 i32.add)";
+        let module = crate::wat::parse::parse_module(text).expect("A module");
+        assert_lines(
+            &print(&module, &options),
+            &["(i32.add (i32.const 0) (i32.const 1))"],
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(f32_text(1.5), "1.5");
+        assert_eq!(f32_text(f32::from_bits(0xffc0_0000)), "-nan");
+        assert_eq!(f32_text(f32::from_bits(0x7f80_0001)), "nan:0x1");
+        assert_eq!(f64_text(f64::NEG_INFINITY), "-inf");
+        assert_eq!(f64_text(-0.0), "-0.0");
+        assert_eq!(f64_text(1e300), "1e300");
+        assert_eq!(string(b"a\tb\x7f\xff"), "\"a\\tb\\7f\\ff\"");
+        assert_eq!(id("add one"), "$add_one");
+        assert_eq!(id(""), "$_");
+    }
+}
@@ -0,0 +1,853 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::module::Module;
+use crate::runtime::imports::Imports;
+use crate::runtime::instance::{Instance, InstantiationError};
+use crate::runtime::store::{Extern, Store};
+use crate::runtime::trap::{Trap, TrapKind};
+use crate::runtime::value::{Ref, Value};
+use crate::types::heap_type::HeapType;
+use crate::validate::{ValidationError, validate};
+use crate::wat::lexer::{Sexpr, sexprs};
+use crate::wat::num;
+use crate::wat::parse::{parse_module, parse_module_sexpr};
+use crate::wat::{Pos, WatError};
+
+type Result<T> = std::result::Result<T, WatError>;
+
+/// A value passed to or returned from an invoked function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Const {
+    I32(i32),
+    I64(i64),
+    /// The bits of an `f32`, so that NaN payloads compare exactly.
+    F32(u32),
+    F64(u64),
+    V128([u8; 16]),
+    RefNull(HeapType),
+    /// A reference to a host object, `(ref.extern n)`.
+    RefExtern(u32),
+}
+
+/// Which NaNs a `nan:canonical` or `nan:arithmetic` result allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NanPattern {
+    Canonical,
+    Arithmetic,
+}
+
+/// A result an assertion expects.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    Const(Const),
+    F32Nan(NanPattern),
+    F64Nan(NanPattern),
+    /// A vector of floats some of whose lanes are NaN patterns, each lane
+    /// being `F32Nan`/`F64Nan` or an `F32`/`F64` constant.
+    Lanes(Vec<Expected>),
+    /// Any null reference, `(ref.null)`.
+    Null,
+    /// Any non-null reference of a heap type, like `(ref.func)`.
+    Ref(HeapType),
+    /// Any of several results, `(either ...)`.
+    Either(Vec<Expected>),
+}
+
+/// Something a script does to a module instance.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Calls an exported function, of the named module or the last one.
+    Invoke {
+        module: Option<String>,
+        name: String,
+        args: Vec<Const>,
+    },
+    /// Reads an exported global.
+    Get {
+        module: Option<String>,
+        name: String,
+    },
+}
+
+/// A module in a script, or the error parsing it where a script expects
+/// it to be malformed.
+pub type ScriptModule = Result<Module>;
+
+/// A command of a `.wast` script.
+pub enum Directive {
+    Module {
+        name: Option<String>,
+        module: Module,
+    },
+    /// Makes the named or last module's exports importable as `name`.
+    Register {
+        name: String,
+        module: Option<String>,
+    },
+    Action(Action),
+    AssertReturn {
+        action: Action,
+        results: Vec<Expected>,
+    },
+    AssertTrap {
+        action: Action,
+        message: String,
+    },
+    AssertExhaustion {
+        action: Action,
+        message: String,
+    },
+    /// A module that validates but traps while being instantiated.
+    AssertUninstantiable {
+        module: Module,
+        message: String,
+    },
+    AssertUnlinkable {
+        module: Module,
+        message: String,
+    },
+    AssertInvalid {
+        module: ScriptModule,
+        message: String,
+    },
+    AssertMalformed {
+        module: ScriptModule,
+        message: String,
+    },
+}
+
+/// A directive and where it is in the script.
+pub struct Command {
+    pub pos: Pos,
+    pub directive: Directive,
+}
+
+/// A `.wast` script: modules to define and assertions about them.
+pub struct Script {
+    pub commands: Vec<Command>,
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Script> {
+        let commands = sexprs(text)?
+            .iter()
+            .map(command)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Script { commands })
+    }
+}
+
+fn items(item: &Sexpr) -> Result<(&[Sexpr], Pos)> {
+    match item {
+        Sexpr::List(items, _, end) => Ok((items, *end)),
+        _ => Err(WatError::new(item.pos(), "expected a list")),
+    }
+}
+
+fn string(item: Option<&Sexpr>, end: Pos) -> Result<String> {
+    match item {
+        Some(Sexpr::Str(bytes, pos)) => String::from_utf8(bytes.clone())
+            .map_err(|_| WatError::new(*pos, "malformed UTF-8 encoding")),
+        Some(item) => Err(WatError::new(item.pos(), "expected a string")),
+        None => Err(WatError::new(end, "expected a string")),
+    }
+}
+
+fn module_name(item: Option<&Sexpr>) -> Option<String> {
+    match item {
+        Some(Sexpr::Id(id, _)) => Some(id.clone()),
+        _ => None,
+    }
+}
+
+// `(module ...)` and its name. An error parsing it is kept for the
+// directives that expect one.
+fn script_module(item: Option<&Sexpr>, end: Pos) -> Result<(Option<String>, ScriptModule)> {
+    let Some(item) = item.filter(|item| item.head() == Some("module")) else {
+        return Err(WatError::new(
+            item.map_or(end, Sexpr::pos),
+            "expected a module",
+        ));
+    };
+    let (items, _) = items(item)?;
+    Ok((module_name(items.get(1)), parse_module_sexpr(item)))
+}
+
+fn action(item: Option<&Sexpr>, end: Pos) -> Result<Action> {
+    let Some(item) = item else {
+        return Err(WatError::new(end, "expected an action"));
+    };
+    let (items, end) = items(item)?;
+    let (module, rest) = match items.get(1) {
+        Some(Sexpr::Id(id, _)) => (Some(id.clone()), &items[2..]),
+        _ => (None, &items[1..]),
+    };
+    let name = string(rest.first(), end)?;
+    match item.head() {
+        Some("invoke") => {
+            let args = rest[1..].iter().map(constant).collect::<Result<_>>()?;
+            Ok(Action::Invoke { module, name, args })
+        }
+        Some("get") => Ok(Action::Get { module, name }),
+        _ => Err(WatError::new(item.pos(), "expected invoke or get")),
+    }
+}
+
+fn literal<T>(item: Option<&Sexpr>, end: Pos, f: impl Fn(&str) -> Option<T>) -> Result<T> {
+    match item {
+        Some(Sexpr::Atom(atom, pos)) => {
+            f(atom).ok_or_else(|| WatError::new(*pos, "invalid literal"))
+        }
+        Some(item) => Err(WatError::new(item.pos(), "expected a literal")),
+        None => Err(WatError::new(end, "expected a literal")),
+    }
+}
+
+fn heap_type(item: Option<&Sexpr>, end: Pos) -> Result<HeapType> {
+    let heap = match item.and_then(Sexpr::as_atom) {
+        Some("func") => HeapType::Func,
+        Some("extern") => HeapType::Extern,
+        Some("exn") => HeapType::Exn,
+        Some("any") => HeapType::Any,
+        Some("eq") => HeapType::Eq,
+        Some("i31") => HeapType::I31,
+        Some("struct") => HeapType::Struct,
+        Some("array") => HeapType::Array,
+        Some("none") => HeapType::None,
+        Some("nofunc") => HeapType::NoFunc,
+        Some("noextern") => HeapType::NoExtern,
+        Some("noexn") => HeapType::NoExn,
+        _ => {
+            return Err(WatError::new(
+                item.map_or(end, Sexpr::pos),
+                "expected a heap type",
+            ));
+        }
+    };
+    Ok(heap)
+}
+
+// The lanes of a `v128.const`, where float lanes may be NaN patterns
+fn lanes(items: &[Sexpr], end: Pos) -> Result<Vec<Expected>> {
+    let shape = items.get(1).and_then(Sexpr::as_atom);
+    let (count, bits) = match shape {
+        Some("i8x16") => (16, 8),
+        Some("i16x8") => (8, 16),
+        Some("i32x4") | Some("f32x4") => (4, 32),
+        Some("i64x2") | Some("f64x2") => (2, 64),
+        _ => {
+            return Err(WatError::new(
+                items.get(1).map_or(end, Sexpr::pos),
+                "unknown vector shape",
+            ));
+        }
+    };
+    let mut lanes = Vec::with_capacity(count);
+    for i in 0..count {
+        let item = items.get(2 + i);
+        let nan = item.and_then(Sexpr::as_atom).and_then(nan_pattern);
+        let lane = match (shape, nan) {
+            (Some("f32x4"), Some(nan)) => Expected::F32Nan(nan),
+            (Some("f64x2"), Some(nan)) => Expected::F64Nan(nan),
+            (Some("f32x4"), None) => Expected::Const(Const::F32(literal(item, end, num::f32)?)),
+            (Some("f64x2"), None) => Expected::Const(Const::F64(literal(item, end, num::f64)?)),
+            _ => Expected::Const(Const::I64(
+                literal(item, end, |text| num::int(text, bits))? as i64
+            )),
+        };
+        lanes.push(lane);
+    }
+    if let Some(extra) = items.get(2 + count) {
+        return Err(WatError::new(extra.pos(), "too many lanes"));
+    }
+    Ok(lanes)
+}
+
+// The bytes of a vector whose lanes are all constants
+fn lane_bytes(lanes: &[Expected]) -> Option<[u8; 16]> {
+    let width = 16 / lanes.len();
+    let mut bytes = [0; 16];
+    for (chunk, lane) in bytes.chunks_mut(width).zip(lanes) {
+        let bits = match lane {
+            Expected::Const(Const::F32(bits)) => u64::from(*bits),
+            Expected::Const(Const::F64(bits)) => *bits,
+            Expected::Const(Const::I64(bits)) => *bits as u64,
+            _ => return None,
+        };
+        chunk.copy_from_slice(&bits.to_le_bytes()[..width]);
+    }
+    Some(bytes)
+}
+
+fn nan_pattern(text: &str) -> Option<NanPattern> {
+    match text {
+        "nan:canonical" => Some(NanPattern::Canonical),
+        "nan:arithmetic" => Some(NanPattern::Arithmetic),
+        _ => None,
+    }
+}
+
+fn constant(item: &Sexpr) -> Result<Const> {
+    match expected(item)? {
+        Expected::Const(c) => Ok(c),
+        _ => Err(WatError::new(item.pos(), "expected a constant")),
+    }
+}
+
+fn expected(item: &Sexpr) -> Result<Expected> {
+    let (items, end) = items(item)?;
+    let arg = items.get(1);
+    let float_nan = arg.and_then(Sexpr::as_atom).and_then(nan_pattern);
+    let expected = match item.head() {
+        Some("i32.const") => {
+            Expected::Const(Const::I32(
+                literal(arg, end, |text| num::int(text, 32))? as i32
+            ))
+        }
+        Some("i64.const") => {
+            Expected::Const(Const::I64(
+                literal(arg, end, |text| num::int(text, 64))? as i64
+            ))
+        }
+        Some("f32.const") => match float_nan {
+            Some(nan) => Expected::F32Nan(nan),
+            None => Expected::Const(Const::F32(literal(arg, end, num::f32)?)),
+        },
+        Some("f64.const") => match float_nan {
+            Some(nan) => Expected::F64Nan(nan),
+            None => Expected::Const(Const::F64(literal(arg, end, num::f64)?)),
+        },
+        Some("v128.const") => {
+            let lanes = lanes(items, end)?;
+            match lane_bytes(&lanes) {
+                Some(bytes) => Expected::Const(Const::V128(bytes)),
+                None => Expected::Lanes(lanes),
+            }
+        }
+        Some("ref.null") if arg.is_none() => Expected::Null,
+        Some("ref.null") => Expected::Const(Const::RefNull(heap_type(arg, end)?)),
+        Some("ref.extern" | "ref.host") if arg.is_some() => {
+            let n = literal(arg, end, |text| {
+                num::uint(text).and_then(|n| u32::try_from(n).ok())
+            })?;
+            Expected::Const(Const::RefExtern(n))
+        }
+        Some("ref.extern" | "ref.host") => Expected::Ref(HeapType::Extern),
+        Some("ref.func") => Expected::Ref(HeapType::Func),
+        Some("ref.any") => Expected::Ref(HeapType::Any),
+        Some("ref.eq") => Expected::Ref(HeapType::Eq),
+        Some("ref.i31") => Expected::Ref(HeapType::I31),
+        Some("ref.struct") => Expected::Ref(HeapType::Struct),
+        Some("ref.array") => Expected::Ref(HeapType::Array),
+        Some("ref.exn") => Expected::Ref(HeapType::Exn),
+        Some("either") => Expected::Either(items[1..].iter().map(expected).collect::<Result<_>>()?),
+        _ => return Err(WatError::new(item.pos(), "expected a constant")),
+    };
+    Ok(expected)
+}
+
+fn command(item: &Sexpr) -> Result<Command> {
+    let pos = item.pos();
+    let (items, end) = items(item)?;
+    let message = || string(items.get(2), end);
+    let well_formed =
+        |(name, module): (Option<String>, ScriptModule)| module.map(|module| (name, module));
+    let directive = match item.head() {
+        Some("module") => {
+            let (name, module) = well_formed(script_module(Some(item), end)?)?;
+            Directive::Module { name, module }
+        }
+        Some("register") => Directive::Register {
+            name: string(items.get(1), end)?,
+            module: module_name(items.get(2)),
+        },
+        Some("invoke" | "get") => Directive::Action(action(Some(item), end)?),
+        Some("assert_return") => Directive::AssertReturn {
+            action: action(items.get(1), end)?,
+            results: items[2..].iter().map(expected).collect::<Result<_>>()?,
+        },
+        Some("assert_trap") if items.get(1).and_then(Sexpr::head) == Some("module") => {
+            Directive::AssertUninstantiable {
+                module: well_formed(script_module(items.get(1), end)?)?.1,
+                message: message()?,
+            }
+        }
+        Some("assert_trap") => Directive::AssertTrap {
+            action: action(items.get(1), end)?,
+            message: message()?,
+        },
+        Some("assert_exhaustion") => Directive::AssertExhaustion {
+            action: action(items.get(1), end)?,
+            message: message()?,
+        },
+        Some("assert_unlinkable") => Directive::AssertUnlinkable {
+            module: well_formed(script_module(items.get(1), end)?)?.1,
+            message: message()?,
+        },
+        Some("assert_invalid") => Directive::AssertInvalid {
+            module: script_module(items.get(1), end)?.1,
+            message: message()?,
+        },
+        Some("assert_malformed") => Directive::AssertMalformed {
+            module: script_module(items.get(1), end)?.1,
+            message: message()?,
+        },
+        _ => return Err(WatError::new(pos, "unknown directive")),
+    };
+    Ok(Command { pos, directive })
+}
+
+/// The outcome of running a script. Directives that need a module that
+/// failed to instantiate are skipped; ones that need an instruction the
+/// interpreter does not implement fail.
+#[derive(Debug, Default)]
+pub struct WastReport {
+    pub passed: usize,
+    pub skipped: usize,
+    pub failures: Vec<WatError>,
+}
+
+impl Display for WastReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.passed,
+            self.failures.len(),
+            self.skipped
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+// What checking a directive came to
+enum Outcome {
+    Passed,
+    Failed(String),
+    Skipped,
+}
+
+impl Outcome {
+    fn check(ok: bool, message: impl FnOnce() -> String) -> Outcome {
+        if ok {
+            Outcome::Passed
+        } else {
+            Outcome::Failed(message())
+        }
+    }
+}
+
+// Every action gets this much fuel, so that a script whose loops don't end
+// fails instead of hanging the test run
+const FUEL: u64 = 100_000_000;
+
+// The module spec scripts import from as "spectest". Its functions print
+// their arguments in the reference interpreter, but do nothing here.
+const SPECTEST: &str = r#"
+    (global (export "global_i32") i32 (i32.const 666))
+    (global (export "global_i64") i64 (i64.const 666))
+    (global (export "global_f32") f32 (f32.const 666.6))
+    (global (export "global_f64") f64 (f64.const 666.6))
+    (table (export "table") 10 20 funcref)
+    (table (export "table64") i64 10 20 funcref)
+    (memory (export "memory") 1 2)
+    (func (export "print"))
+    (func (export "print_i32") (param i32))
+    (func (export "print_i64") (param i64))
+    (func (export "print_f32") (param f32))
+    (func (export "print_f64") (param f64))
+    (func (export "print_i32_f32") (param i32 f32))
+    (func (export "print_f64_f64") (param f64 f64))
+    (func (export "print_char") (param i32))
+"#;
+
+/// The instances a script has defined so far.
+struct Instances {
+    store: Store,
+    imports: Imports,
+    /// The last module's instance, or `None` if it could not be
+    /// instantiated.
+    current: Option<Instance>,
+    named: HashMap<String, Option<Instance>>,
+}
+
+impl Instances {
+    fn new() -> Instances {
+        let mut store = Store::new();
+        let mut imports = Imports::new();
+        let spectest = parse_module(SPECTEST).expect("The spectest module");
+        let spectest =
+            Instance::new(&mut store, &spectest, &imports).expect("The spectest instance");
+        imports.define_instance(&store, "spectest", spectest);
+        Instances {
+            store,
+            imports,
+            current: None,
+            named: HashMap::new(),
+        }
+    }
+
+    fn instantiate(
+        &mut self,
+        module: &Module,
+    ) -> std::result::Result<Instance, InstantiationError> {
+        self.store.set_fuel(Some(FUEL));
+        Instance::new(&mut self.store, module, &self.imports)
+    }
+
+    fn instance(&self, module: &Option<String>) -> Option<Instance> {
+        match module {
+            Some(name) => self.named.get(name).copied().flatten(),
+            None => self.current,
+        }
+    }
+
+    // The results of an action, or `None` if it can't be performed
+    fn perform(&mut self, action: &Action) -> Option<std::result::Result<Vec<Value>, Trap>> {
+        match action {
+            Action::Invoke { module, name, args } => {
+                let instance = self.instance(module)?;
+                let args: Vec<Value> = args.iter().map(value).collect();
+                self.store.set_fuel(Some(FUEL));
+                Some(instance.invoke(&mut self.store, name, &args))
+            }
+            Action::Get { module, name } => {
+                let instance = self.instance(module)?;
+                match instance.export(&self.store, name) {
+                    Some(Extern::Global(global)) => Some(Ok(vec![self.store.global(global).get()])),
+                    _ => Some(Err(Trap::new(TrapKind::Host(format!(
+                        "unknown global {}",
+                        name
+                    ))))),
+                }
+            }
+        }
+    }
+}
+
+fn value(c: &Const) -> Value {
+    match c {
+        Const::I32(v) => Value::I32(*v),
+        Const::I64(v) => Value::I64(*v),
+        Const::F32(bits) => Value::F32(f32::from_bits(*bits)),
+        Const::F64(bits) => Value::F64(f64::from_bits(*bits)),
+        Const::V128(bytes) => Value::V128(u128::from_le_bytes(*bytes)),
+        Const::RefNull(_) => Value::Ref(Ref::Null),
+        Const::RefExtern(n) => Value::Ref(Ref::Extern(*n)),
+    }
+}
+
+fn f32_nan(bits: u32, nan: NanPattern) -> bool {
+    match nan {
+        NanPattern::Canonical => bits & 0x7fff_ffff == 0x7fc0_0000,
+        NanPattern::Arithmetic => bits & 0x7fc0_0000 == 0x7fc0_0000,
+    }
+}
+
+fn f64_nan(bits: u64, nan: NanPattern) -> bool {
+    match nan {
+        NanPattern::Canonical => bits & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000,
+        NanPattern::Arithmetic => bits & 0x7ff8_0000_0000_0000 == 0x7ff8_0000_0000_0000,
+    }
+}
+
+// Whether a lane of a vector, given as its bits, is what's expected
+fn lane_matches(expected: &Expected, bits: u64) -> bool {
+    match expected {
+        Expected::F32Nan(nan) => f32_nan(bits as u32, *nan),
+        Expected::F64Nan(nan) => f64_nan(bits, *nan),
+        Expected::Const(Const::F32(b)) => bits == u64::from(*b),
+        Expected::Const(Const::F64(b)) => bits == *b,
+        _ => false,
+    }
+}
+
+fn matches(expected: &Expected, v: &Value) -> bool {
+    match (expected, v) {
+        (Expected::Const(c), v) => value(c).same_bits(v),
+        (Expected::F32Nan(nan), Value::F32(f)) => f32_nan(f.to_bits(), *nan),
+        (Expected::F64Nan(nan), Value::F64(f)) => f64_nan(f.to_bits(), *nan),
+        (Expected::Lanes(lanes), Value::V128(v)) => {
+            let width = 128 / lanes.len();
+            lanes.iter().enumerate().all(|(i, lane)| {
+                let bits = (v >> (i * width)) as u64 & (u64::MAX >> (64 - width));
+                lane_matches(lane, bits)
+            })
+        }
+        (Expected::Null, Value::Ref(r)) => r.is_null(),
+        (Expected::Ref(heap), Value::Ref(r)) => matches!(
+            (heap, r),
+            (HeapType::Func, Ref::Func(_))
+                | (HeapType::Extern, Ref::Extern(_))
+                | (HeapType::Exn, Ref::Exn(_))
+        ),
+        (Expected::Either(options), v) => options.iter().any(|e| matches(e, v)),
+        _ => false,
+    }
+}
+
+fn values(values: &[Value]) -> String {
+    let values: Vec<_> = values.iter().map(Value::to_string).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn trapped(trap: &Trap, message: &str) -> Outcome {
+    Outcome::check(trap.to_string().starts_with(message), || {
+        format!("trapped with \"{}\", expected \"{}\"", trap, message)
+    })
+}
+
+fn returned(results: &std::result::Result<Vec<Value>, Trap>, message: &str) -> Outcome {
+    match results {
+        Ok(values) => Outcome::Failed(format!(
+            "returned {}, expected \"{}\"",
+            self::values(values),
+            message
+        )),
+        Err(trap) => trapped(trap, message),
+    }
+}
+
+// Why a module is invalid, without the function and stack it was found in
+fn invalid(err: &ValidationError) -> String {
+    match err {
+        ValidationError::InFunc { error, .. } => invalid(error),
+        err => err.to_string(),
+    }
+}
+
+// A module was rejected as `what`, which passes if the error starts with
+// the message the script expects
+fn rejected(what: &str, err: &str, message: &str) -> Outcome {
+    Outcome::check(err.starts_with(message), || {
+        format!("module is {}: {}, expected \"{}\"", what, err, message)
+    })
+}
+
+/// Runs `script`: checks modules against the parser and validator,
+/// instantiates them and performs the actions and assertions about them.
+pub fn run(script: &Script) -> WastReport {
+    let mut report = WastReport::default();
+    let mut instances = Instances::new();
+    for command in &script.commands {
+        let outcome = match &command.directive {
+            Directive::Module { name, module } => {
+                let (instance, outcome) = match instances.instantiate(module) {
+                    Ok(instance) => (Some(instance), Outcome::Passed),
+                    Err(InstantiationError::Invalid(err)) => {
+                        (None, Outcome::Failed(format!("module is invalid: {}", err)))
+                    }
+                    Err(err) => (
+                        None,
+                        Outcome::Failed(format!("instantiation failed: {}", err)),
+                    ),
+                };
+                instances.current = instance;
+                if let Some(name) = name {
+                    instances.named.insert(name.clone(), instance);
+                }
+                outcome
+            }
+            Directive::Action(action) => match instances.perform(action) {
+                Some(Ok(_)) => Outcome::Passed,
+                Some(Err(trap)) => Outcome::Failed(format!("trapped with \"{}\"", trap)),
+                None => Outcome::Skipped,
+            },
+            Directive::AssertReturn { action, results } => match instances.perform(action) {
+                Some(Ok(values)) => Outcome::check(
+                    values.len() == results.len()
+                        && results.iter().zip(&values).all(|(e, v)| matches(e, v)),
+                    || format!("returned {}, expected {:?}", self::values(&values), results),
+                ),
+                Some(Err(trap)) => Outcome::Failed(format!("trapped with \"{}\"", trap)),
+                None => Outcome::Skipped,
+            },
+            Directive::AssertTrap { action, message }
+            | Directive::AssertExhaustion { action, message } => match instances.perform(action) {
+                Some(results) => returned(&results, message),
+                None => Outcome::Skipped,
+            },
+            Directive::AssertUninstantiable { module, message } => {
+                match instances.instantiate(module) {
+                    Ok(_) => {
+                        Outcome::Failed(format!("module instantiated, expected \"{}\"", message))
+                    }
+                    Err(InstantiationError::Trap(trap)) => trapped(&trap, message),
+                    Err(err) => Outcome::Failed(format!("instantiation failed: {}", err)),
+                }
+            }
+            Directive::AssertUnlinkable { module, message } => {
+                match instances.instantiate(module) {
+                    Ok(_) => Outcome::Failed(format!("module linked, expected \"{}\"", message)),
+                    Err(
+                        err @ (InstantiationError::UnknownImport { .. }
+                        | InstantiationError::IncompatibleImport { .. }),
+                    ) => {
+                        let err = err.to_string();
+                        Outcome::check(err.starts_with(message.as_str()), || {
+                            format!("linking failed with \"{}\", expected \"{}\"", err, message)
+                        })
+                    }
+                    Err(err) => Outcome::Failed(format!("instantiation failed: {}", err)),
+                }
+            }
+            Directive::Register { name, module } => match instances.instance(module) {
+                Some(instance) => {
+                    instances
+                        .imports
+                        .define_instance(&instances.store, name, instance);
+                    Outcome::Passed
+                }
+                None => Outcome::Skipped,
+            },
+            Directive::AssertInvalid { module, message } => match module {
+                Ok(module) => match validate(module) {
+                    Ok(_) => Outcome::Failed(format!("module is valid, expected \"{}\"", message)),
+                    Err(err) => rejected("invalid", &invalid(&err), message),
+                },
+                Err(err) => Outcome::Failed(format!("module is malformed: {}", err.message)),
+            },
+            // Some checks the spec makes while decoding, like the function
+            // and code sections having the same length, are left to the
+            // validator, so an invalid module counts as malformed
+            Directive::AssertMalformed { module, message } => match module {
+                Ok(module) => match validate(module) {
+                    Ok(_) => {
+                        Outcome::Failed(format!("module is well-formed, expected \"{}\"", message))
+                    }
+                    Err(err) => rejected("malformed", &invalid(&err), message),
+                },
+                Err(err) => rejected("malformed", &err.message, message),
+            },
+        };
+        match outcome {
+            Outcome::Passed => report.passed += 1,
+            Outcome::Failed(message) => report.failures.push(WatError::new(command.pos, message)),
+            Outcome::Skipped => report.skipped += 1,
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(text: &str) -> Script {
+        match Script::parse(text) {
+            Ok(script) => script,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script = script(
+            r#"(module $m (func (export "f") (param i32) (result i32) local.get 0))
+            (register "m" $m)
+            (assert_return (invoke $m "f" (i32.const -1)) (i32.const 0xffffffff))
+            (assert_return (invoke "g") (f32.const nan:canonical)
+              (v128.const f64x2 nan:arithmetic 1.0) (either (ref.null) (ref.func)))
+            (assert_trap (invoke "f" (i32.const 0)) "unreachable")
+            (assert_malformed (module quote "(func (i32.const 0x1_0000_0000))") "constant out of range")"#,
+        );
+        assert_eq!(script.commands.len(), 6);
+        assert_eq!(script.commands[2].pos, Pos { line: 3, col: 13 });
+        let Directive::AssertReturn { action, results } = &script.commands[2].directive else {
+            panic!("Expected assert_return");
+        };
+        assert_eq!(
+            action,
+            &Action::Invoke {
+                module: Some("m".to_string()),
+                name: "f".to_string(),
+                args: vec![Const::I32(-1)],
+            }
+        );
+        assert_eq!(results, &[Expected::Const(Const::I32(-1))]);
+        let Directive::AssertReturn { results, .. } = &script.commands[3].directive else {
+            panic!("Expected assert_return");
+        };
+        assert_eq!(
+            results,
+            &[
+                Expected::F32Nan(NanPattern::Canonical),
+                Expected::Lanes(vec![
+                    Expected::F64Nan(NanPattern::Arithmetic),
+                    Expected::Const(Const::F64(1f64.to_bits())),
+                ]),
+                Expected::Either(vec![Expected::Null, Expected::Ref(HeapType::Func)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let report = run(&script(
+            r#"(module (memory 1))
+            (assert_invalid (module (memory 2 1)) "size minimum must not be greater than maximum")
+            (assert_invalid (module (func (result i32))) "type mismatch")
+            (assert_malformed (module binary "\00asm" "\02\00\00\00") "unknown binary version")
+            (assert_malformed (module quote "(func (nop") "unclosed")
+            (assert_malformed (module (func)) "not malformed")
+            (assert_return (invoke "f"))"#,
+        ));
+        assert_eq!(report.passed, 5);
+        assert_eq!(report.skipped, 0);
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.failures[0].pos, Pos { line: 6, col: 13 });
+        assert_eq!(
+            report.failures[1].message,
+            "trapped with \"unknown function f\""
+        );
+    }
+
+    #[test]
+    fn test_run_execution() {
+        let report = run(&script(
+            r#"(module $m
+              (global (export "g") f32 (f32.const nan:0x600000))
+              (func (export "div") (param i32 i32) (result i32)
+                (i32.div_s (local.get 0) (local.get 1)))
+              (func (export "nan") (result f64) (f64.div (f64.const 0) (f64.const 0)))
+              (func $r (export "recurse") (call $r)))
+            (assert_return (invoke "div" (i32.const 7) (i32.const -2)) (i32.const -3))
+            (assert_return (invoke $m "nan") (f64.const nan:arithmetic))
+            (assert_return (get "g") (f32.const nan:arithmetic))
+            (assert_trap (invoke "div" (i32.const 1) (i32.const 0)) "integer divide")
+            (assert_exhaustion (invoke "recurse") "call stack exhausted")
+            (assert_trap (module (func unreachable) (start 0)) "unreachable")
+            (register "m" $m)
+            (module
+              (import "m" "div" (func $div (param i32 i32) (result i32)))
+              (import "spectest" "print_i32" (func (param i32)))
+              (func (export "call") (result i32) (call $div (i32.const 9) (i32.const 3))))
+            (assert_return (invoke "call") (i32.const 3))
+            (assert_unlinkable (module (import "m" "div" (func))) "incompatible import type")
+            (assert_unlinkable (module (import "m" "g" (global i64))) "incompatible import type")
+            (assert_unlinkable (module (import "m" "nope" (func))) "unknown import")
+            (module (import "nowhere" "f" (func)))
+            (assert_return (invoke "call") (i32.const 3))
+            (assert_return (invoke $m "div" (i32.const 1) (i32.const 1)) (i32.const 2))
+            (module (memory 1 1 shared)
+              (func (export "wait") (result i32)
+                (memory.atomic.wait32 (i32.const 0) (i32.const 0) (i64.const 0))))
+            (assert_return (invoke "wait") (i32.const 2))"#,
+        ));
+        assert_eq!(report.passed, 14);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.failures.len(), 3);
+        assert_eq!(report.failures[0].pos, Pos { line: 22, col: 13 });
+        assert_eq!(
+            report.failures[0].message,
+            "instantiation failed: unknown import nowhere.f"
+        );
+        assert_eq!(report.failures[1].pos, Pos { line: 24, col: 13 });
+        // An instruction the interpreter lacks fails the directive
+        assert_eq!(report.failures[2].pos, Pos { line: 28, col: 13 });
+    }
+}
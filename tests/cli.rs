@@ -0,0 +1,53 @@
+use std::fs;
+use std::process::Command;
+
+// Exits with ten times its number of arguments, plus one if a directory
+// was preopened
+const APP: &str = r#"
+    (module
+      (import "wasi_snapshot_preview1" "args_sizes_get"
+        (func $args_sizes_get (param i32 i32) (result i32)))
+      (import "wasi_snapshot_preview1" "fd_prestat_get"
+        (func $fd_prestat_get (param i32 i32) (result i32)))
+      (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+      (memory (export "memory") 1)
+      (func (export "_start")
+        (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+        (call $proc_exit
+          (i32.add
+            (i32.mul (i32.load (i32.const 0)) (i32.const 10))
+            (i32.eqz (call $fd_prestat_get (i32.const 3) (i32.const 8)))))))
+"#;
+
+fn run(args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_wasmdbg2"))
+        .arg("run")
+        .args(args)
+        .output()
+        .expect("The binary runs");
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn test_run_options() {
+    let dir = std::env::temp_dir().join(format!("wasmdbg-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("A temporary directory");
+    let app = dir.join("app.wat");
+    fs::write(&app, APP).expect("The module");
+    let app = app.to_str().expect("A UTF-8 path");
+    let host = dir.to_str().expect("A UTF-8 path");
+
+    // Options go before or after the module, and `--` ends them
+    assert_eq!(run(&["--dir", host, app, "a"]).0, Some(21));
+    assert_eq!(run(&[app, "--dir", host, "a"]).0, Some(21));
+    assert_eq!(run(&[app, "a", "--", "--dir", host]).0, Some(40));
+    assert_eq!(run(&[app]).0, Some(10));
+
+    let (status, stderr) = run(&[app, "--verbose"]);
+    assert_ne!(status, Some(0));
+    assert!(stderr.contains("unknown option --verbose"), "{}", stderr);
+    fs::remove_dir_all(&dir).expect("The temporary directory is removed");
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use wasmdbg2::wat::wast::{Script, run};
+
+// The `.wast` files under `dir`, in its subdirectories too
+fn scripts(dir: &Path, paths: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("The spec test directory") {
+        let path = entry.expect("A directory entry").path();
+        if path.is_dir() {
+            scripts(&path, paths);
+        } else if path.extension().is_some_and(|ext| ext == "wast") {
+            paths.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_spec() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spec");
+    let mut paths = Vec::new();
+    scripts(&dir, &mut paths);
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    // Each line is a directive known to fail, as `<script>:<line>:<col>`
+    let expected = fs::read_to_string(dir.join("expected-failures.txt"))
+        .expect("The list of expected failures");
+    let mut expected: Vec<&str> = expected
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let name = path.strip_prefix(&dir).unwrap_or(path).display();
+        let text = fs::read_to_string(path).expect("The script");
+        let found = match Script::parse(&text) {
+            Ok(script) => {
+                let report = run(&script);
+                println!("{}: {}", name, report);
+                report
+                    .failures
+                    .iter()
+                    .map(|failure| format!("{}:{}", name, failure))
+                    .collect()
+            }
+            Err(err) => vec![format!("{}:{}", name, err)],
+        };
+        for failure in found {
+            match expected
+                .iter()
+                .position(|at| failure.starts_with(&format!("{}:", at)))
+            {
+                Some(i) => {
+                    expected.swap_remove(i);
+                }
+                None => failures.push(failure),
+            }
+        }
+    }
+    failures.extend(
+        expected
+            .iter()
+            .map(|at| format!("{}: expected to fail, but passed", at)),
+    );
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
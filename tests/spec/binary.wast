@@ -0,0 +1,50 @@
+(module binary "\00asm" "\01\00\00\00")
+(module binary "\00asm" "\01\00\00\00" "\00\01\00")
+
+(assert_malformed (module binary "") "unexpected end")
+(assert_malformed (module binary "\01") "unexpected end")
+(assert_malformed (module binary "\00as") "unexpected end")
+(assert_malformed (module binary "asm\00") "magic header not detected")
+(assert_malformed (module binary "msa\00") "magic header not detected")
+(assert_malformed (module binary "\00asm") "unexpected end")
+(assert_malformed (module binary "\00asm" "\01") "unexpected end")
+(assert_malformed (module binary "\00asm" "\00\00\00\01") "unknown binary version")
+(assert_malformed (module binary "\00asm" "\02\00\00\00") "unknown binary version")
+
+;; Sections must have a known id
+(assert_malformed (module binary "\00asm" "\01\00\00\00" "\0e\01\00") "malformed section id")
+(assert_malformed (module binary "\00asm" "\01\00\00\00" "\7f\01\00") "malformed section id")
+
+;; A function section without a code section
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"       ;; Type section
+    "\03\02\01\00"             ;; Function section with 1 function
+  )
+  "function and code section have inconsistent lengths"
+)
+
+;; A function body that ends early
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"       ;; Type section
+    "\03\02\01\00"             ;; Function section
+    "\0a\04\01"                ;; Code section
+    "\02\00\01"                ;; nop, without an end
+  )
+  "unexpected end"
+)
+
+;; A section after one that must follow it
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"       ;; Type section
+    "\03\02\01\00"             ;; Function section
+    "\0a\04\01\02\00\0b"       ;; Code section
+    "\03\02\01\00"             ;; Function section again
+  )
+  "unexpected content after last section"
+)
@@ -0,0 +1,53 @@
+;; Test t.const instructions
+
+(module (func (i32.const 0_123_456_789) drop))
+(module (func (i32.const 0x0_9acf_fBDF) drop))
+(module (func (i32.const 0xffffffff) drop))
+(module (func (i32.const -0x80000000) drop))
+(module (func (i32.const 4294967295) drop))
+(module (func (i32.const -2147483648) drop))
+(assert_malformed (module quote "(func (i32.const) drop)") "unexpected token")
+(assert_malformed (module quote "(func (i32.const 0x) drop)") "unknown operator")
+(assert_malformed (module quote "(func (i32.const 1x) drop)") "unknown operator")
+(assert_malformed (module quote "(func (i32.const 0xg) drop)") "unknown operator")
+(assert_malformed (module quote "(func (i32.const 0x100000000) drop)") "constant out of range")
+(assert_malformed (module quote "(func (i32.const -0x80000001) drop)") "constant out of range")
+(assert_malformed (module quote "(func (i32.const 4294967296) drop)") "constant out of range")
+(assert_malformed (module quote "(func (i32.const -2147483649) drop)") "constant out of range")
+(assert_malformed (module quote "(func (i32.const 1__0) drop)") "unknown operator")
+(assert_malformed (module quote "(func (i32.const _1) drop)") "unknown operator")
+
+(module (func (i64.const 0xffffffffffffffff) drop))
+(module (func (i64.const -0x8000000000000000) drop))
+(module (func (i64.const 18446744073709551615) drop))
+(module (func (i64.const -9223372036854775808) drop))
+(assert_malformed (module quote "(func (i64.const 0x10000000000000000) drop)") "constant out of range")
+(assert_malformed (module quote "(func (i64.const -0x8000000000000001) drop)") "constant out of range")
+
+(module (func (f32.const 0x1p127) drop))
+(module (func (f32.const -0x1p127) drop))
+(module (func (f32.const 0x1.fffffep127) drop))
+(module (func (f32.const 1e38) drop))
+(module (func (f32.const nan:0x7f_ffff) drop))
+(module (func (f32.const -inf) drop))
+(assert_malformed (module quote "(func (f32.const 0x1p128) drop)") "constant out of range")
+(assert_malformed (module quote "(func (f32.const 1e39) drop)") "constant out of range")
+(assert_malformed (module quote "(func (f32.const nan:0x80_0000) drop)") "constant out of range")
+(assert_malformed (module quote "(func (f32.const nan:0) drop)") "unknown operator")
+(assert_malformed (module quote "(func (f32.const .5) drop)") "unknown operator")
+
+(module (func (f64.const 0x1p1023) drop))
+(module (func (f64.const 0x1.fffffffffffffp1023) drop))
+(module (func (f64.const 1e308) drop))
+(module (func (f64.const nan:0xf_ffff_ffff_ffff) drop))
+(assert_malformed (module quote "(func (f64.const 0x1p1024) drop)") "constant out of range")
+(assert_malformed (module quote "(func (f64.const 1e309) drop)") "constant out of range")
+
+;; Rounding behaviour
+
+(module (func (export "f") (result f32) (f32.const 0x1.00000100000000000p-50)))
+(assert_return (invoke "f") (f32.const 0x1.000000p-50))
+(module (func (export "f") (result f32) (f32.const 0x1.00000300000000000p-50)))
+(assert_return (invoke "f") (f32.const 0x1.000004p-50))
+(module (func (export "f") (result f64) (f64.const 0x1.000000000000080000000001p-600)))
+(assert_return (invoke "f") (f64.const 0x1.0000000000001p-600))
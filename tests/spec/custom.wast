@@ -0,0 +1,72 @@
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\00\24\10" "a custom section" "this is the payload"
+  "\00\20\10" "a custom section" "this is payload"
+  "\00\11\10" "a custom section" ""
+  "\00\10\00" "" "this is payload"
+  "\00\01\00" "" ""
+  "\00\24\10" "\00\00custom sectio\00" "this is the payload"
+  "\00\24\10" "\ef\bb\bfa custom sect" "this is the payload"
+  "\00\24\10" "a custom sect\e2\8c\a3" "this is the payload"
+  "\00\1f\16" "module within a module" "\00asm" "\01\00\00\00"
+)
+
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\00\0e\06" "custom" "payload"
+  "\00\0e\06" "custom" "payload"
+  "\01\01\00"                                ;; type section
+  "\00\0e\06" "custom" "payload"
+  "\00\0e\06" "custom" "payload"
+  "\02\01\00"                                ;; import section
+  "\00\0e\06" "custom" "payload"
+  "\03\01\00"                                ;; function section
+  "\00\0e\06" "custom" "payload"
+  "\0a\01\00"                                ;; code section
+  "\00\0e\06" "custom" "payload"
+)
+
+(module
+  (@custom "text" "made in the text format")
+  (@custom "first" (before first) "")
+  (@custom "after types" (after type) "")
+  (type (func))
+  (func (type 0))
+)
+
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\00"
+  )
+  "unexpected end"
+)
+
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\00\00"
+  )
+  "unexpected end"
+)
+
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\00\26\10" "a custom section" "this is the payload"
+  )
+  "length out of bounds"
+)
+
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\00\0a\10" "a custom section" "this is the payload"
+  )
+  "length out of bounds"
+)
+
+(assert_malformed
+  (module quote "(@custom \"a\" (after bogus) \"\")")
+  "unknown section"
+)
@@ -0,0 +1,55 @@
+(module
+  (func (export "block") (result i32)
+    (block $exit (result i32)
+      (br $exit (i32.const 1))
+      (i32.const 0)
+    )
+  )
+  (func (export "loop") (result i32)
+    (local $i i32)
+    (local.set $i (i32.const 0))
+    (block $exit (result i32)
+      (loop $cont (result i32)
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (if (i32.eq (local.get $i) (i32.const 5))
+          (then (br $exit (local.get $i)))
+        )
+        (br $cont)
+      )
+    )
+  )
+  (func (export "switch") (param i32) (result i32)
+    block $a block $b block $c
+      local.get 0
+      br_table $a $b $c
+    end $c
+      i32.const 2
+      return
+    end $b
+    i32.const 1
+    return
+    end $a
+    i32.const 0
+  )
+)
+
+(assert_return (invoke "block") (i32.const 1))
+(assert_return (invoke "loop") (i32.const 5))
+(assert_return (invoke "switch" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "switch" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "switch" (i32.const 7)) (i32.const 2))
+
+(assert_invalid
+  (module (func (br 1)))
+  "unknown label"
+)
+(assert_invalid
+  (module (func (result i32) (block (result i32) (br 0))))
+  "type mismatch"
+)
+
+(assert_malformed (module quote "(func block end $l)") "mismatching label")
+(assert_malformed (module quote "(func block $a end $l)") "mismatching label")
+(assert_malformed (module quote "(func (br $l))") "unknown label")
+(assert_malformed (module quote "(func block)") "unclosed block")
+(assert_malformed (module quote "(func end)") "unexpected end")
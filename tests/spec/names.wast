@@ -0,0 +1,34 @@
+;; Names
+
+(module
+  (func $f1 (export "") (result i32) (i32.const 0))
+  (func $f2 (export "foo") (result i32) (i32.const 1))
+  (func $f3 (export "$\"") (result i32) (i32.const 2))
+  (func $"with space" (export "\u{1f600}") (result i32) (i32.const 3))
+  (func (export "call") (result i32) (call $"with space"))
+)
+
+(assert_return (invoke "") (i32.const 0))
+(assert_return (invoke "foo") (i32.const 1))
+(assert_return (invoke "$\"") (i32.const 2))
+(assert_return (invoke "\u{1f600}") (i32.const 3))
+
+(module $M1
+  (global (export "g") i32 (i32.const 42))
+)
+(register "M1" $M1)
+(module
+  (import "M1" "g" (global $g i32))
+  (func (export "get") (result i32) (global.get $g))
+)
+(assert_return (get $M1 "g") (i32.const 42))
+(assert_return (invoke "get") (i32.const 42))
+
+(assert_malformed (module quote "(func $f) (func $f)") "duplicate func")
+(assert_malformed (module quote "(func (local $x i32) (local $x i32))") "duplicate local")
+(assert_malformed (module quote "(func (call $missing))") "unknown function")
+(assert_malformed (module quote "(export \"\\ff\" (func 0))") "malformed UTF-8 encoding")
+(assert_invalid
+  (module (func) (export "a" (func 0)) (export "a" (func 0)))
+  "duplicate export name"
+)
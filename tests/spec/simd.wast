@@ -0,0 +1,176 @@
+;; Vector instructions, one or two of each kind
+
+(module
+  (memory 1)
+  (data (i32.const 0) "\01\02\03\04\05\06\07\08\f9\fa\fb\fc\fd\fe\ff\00")
+
+  (func (export "i8x16.add_sat_s") (param v128 v128) (result v128)
+    (i8x16.add_sat_s (local.get 0) (local.get 1)))
+  (func (export "i8x16.sub_sat_u") (param v128 v128) (result v128)
+    (i8x16.sub_sat_u (local.get 0) (local.get 1)))
+  (func (export "i8x16.avgr_u") (param v128 v128) (result v128)
+    (i8x16.avgr_u (local.get 0) (local.get 1)))
+  (func (export "i8x16.narrow_i16x8_u") (param v128 v128) (result v128)
+    (i8x16.narrow_i16x8_u (local.get 0) (local.get 1)))
+  (func (export "i8x16.swizzle") (param v128 v128) (result v128)
+    (i8x16.swizzle (local.get 0) (local.get 1)))
+  (func (export "i8x16.shuffle") (param v128 v128) (result v128)
+    (i8x16.shuffle 31 0 30 1 29 2 28 3 27 4 26 5 25 6 24 7 (local.get 0) (local.get 1)))
+  (func (export "i8x16.bitmask") (param v128) (result i32)
+    (i8x16.bitmask (local.get 0)))
+  (func (export "i8x16.popcnt") (param v128) (result v128)
+    (i8x16.popcnt (local.get 0)))
+  (func (export "i16x8.q15mulr_sat_s") (param v128 v128) (result v128)
+    (i16x8.q15mulr_sat_s (local.get 0) (local.get 1)))
+  (func (export "i16x8.extmul_high_i8x16_s") (param v128 v128) (result v128)
+    (i16x8.extmul_high_i8x16_s (local.get 0) (local.get 1)))
+  (func (export "i16x8.shr_s") (param v128 i32) (result v128)
+    (i16x8.shr_s (local.get 0) (local.get 1)))
+  (func (export "i32x4.dot_i16x8_s") (param v128 v128) (result v128)
+    (i32x4.dot_i16x8_s (local.get 0) (local.get 1)))
+  (func (export "i32x4.lt_u") (param v128 v128) (result v128)
+    (i32x4.lt_u (local.get 0) (local.get 1)))
+  (func (export "i32x4.trunc_sat_f64x2_s_zero") (param v128) (result v128)
+    (i32x4.trunc_sat_f64x2_s_zero (local.get 0)))
+  (func (export "i64x2.all_true") (param v128) (result i32)
+    (i64x2.all_true (local.get 0)))
+  (func (export "i64x2.mul") (param v128 v128) (result v128)
+    (i64x2.mul (local.get 0) (local.get 1)))
+  (func (export "f32x4.min") (param v128 v128) (result v128)
+    (f32x4.min (local.get 0) (local.get 1)))
+  (func (export "f32x4.pmax") (param v128 v128) (result v128)
+    (f32x4.pmax (local.get 0) (local.get 1)))
+  (func (export "f64x2.nearest") (param v128) (result v128)
+    (f64x2.nearest (local.get 0)))
+  (func (export "f64x2.promote_low_f32x4") (param v128) (result v128)
+    (f64x2.promote_low_f32x4 (local.get 0)))
+  (func (export "v128.bitselect") (param v128 v128 v128) (result v128)
+    (v128.bitselect (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "v128.any_true") (param v128) (result i32)
+    (v128.any_true (local.get 0)))
+  (func (export "lanes") (result i32)
+    (i32.add
+      (i8x16.extract_lane_u 15 (i8x16.replace_lane 15 (v128.const i64x2 0 0) (i32.const -1)))
+      (i16x8.extract_lane_s 0 (i16x8.splat (i32.const 0xffff)))))
+
+  (func (export "v128.load8x8_s") (param i32) (result v128)
+    (v128.load8x8_s (local.get 0)))
+  (func (export "v128.load16_splat") (param i32) (result v128)
+    (v128.load16_splat (local.get 0)))
+  (func (export "v128.load32_zero") (param i32) (result v128)
+    (v128.load32_zero (local.get 0)))
+  (func (export "v128.load16_lane") (param i32 v128) (result v128)
+    (v128.load16_lane 7 (local.get 0) (local.get 1)))
+  (func (export "v128.store8_lane") (param i32 v128) (result i32)
+    (v128.store8_lane 1 (local.get 0) (local.get 1))
+    (i32.load8_u (local.get 0)))
+  (func (export "v128.round_trip") (param v128) (result v128)
+    (v128.store offset=32 (i32.const 0) (local.get 0))
+    (v128.load offset=32 (i32.const 0)))
+)
+
+(assert_return
+  (invoke "i8x16.add_sat_s"
+    (v128.const i8x16 127 -128 1 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 127 -128 2 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return
+  (invoke "i8x16.sub_sat_u"
+    (v128.const i8x16 0 10 255 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 3 1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 0 7 254 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return
+  (invoke "i8x16.avgr_u"
+    (v128.const i8x16 255 0 1 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 255 1 2 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 255 1 2 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return
+  (invoke "i8x16.narrow_i16x8_u"
+    (v128.const i16x8 -1 256 255 0 0 0 0 0)
+    (v128.const i16x8 1 2 3 4 5 6 7 300))
+  (v128.const i8x16 0 255 255 0 0 0 0 0 1 2 3 4 5 6 7 255))
+(assert_return
+  (invoke "i8x16.swizzle"
+    (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+    (v128.const i8x16 15 14 16 255 0 0 0 0 0 0 0 0 0 0 0 1))
+  (v128.const i8x16 15 14 0 0 0 0 0 0 0 0 0 0 0 0 0 1))
+(assert_return
+  (invoke "i8x16.shuffle"
+    (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+    (v128.const i8x16 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31))
+  (v128.const i8x16 31 0 30 1 29 2 28 3 27 4 26 5 25 6 24 7))
+(assert_return
+  (invoke "i8x16.bitmask" (v128.const i8x16 -1 0 -1 0 0 0 0 0 0 0 0 0 0 0 0 -128))
+  (i32.const 0x8005))
+(assert_return
+  (invoke "i8x16.popcnt" (v128.const i8x16 255 1 3 0 0 0 0 0 0 0 0 0 0 0 0 0x80))
+  (v128.const i8x16 8 1 2 0 0 0 0 0 0 0 0 0 0 0 0 1))
+(assert_return
+  (invoke "i16x8.q15mulr_sat_s"
+    (v128.const i16x8 -32768 16384 0 0 0 0 0 0)
+    (v128.const i16x8 -32768 16384 0 0 0 0 0 0))
+  (v128.const i16x8 32767 8192 0 0 0 0 0 0))
+(assert_return
+  (invoke "i16x8.extmul_high_i8x16_s"
+    (v128.const i8x16 0 0 0 0 0 0 0 0 -128 2 0 0 0 0 0 0)
+    (v128.const i8x16 0 0 0 0 0 0 0 0 -128 -3 0 0 0 0 0 0))
+  (v128.const i16x8 16384 -6 0 0 0 0 0 0))
+(assert_return
+  (invoke "i16x8.shr_s" (v128.const i16x8 -16 16 0 0 0 0 0 0) (i32.const 18))
+  (v128.const i16x8 -4 4 0 0 0 0 0 0))
+(assert_return
+  (invoke "i32x4.dot_i16x8_s"
+    (v128.const i16x8 -32768 -32768 1 2 0 0 0 0)
+    (v128.const i16x8 -32768 -32768 3 4 0 0 0 0))
+  (v128.const i32x4 -2147483648 11 0 0))
+(assert_return
+  (invoke "i32x4.lt_u" (v128.const i32x4 1 -1 0 0) (v128.const i32x4 -1 1 0 1))
+  (v128.const i32x4 -1 0 0 -1))
+(assert_return
+  (invoke "i32x4.trunc_sat_f64x2_s_zero" (v128.const f64x2 -1e10 nan))
+  (v128.const i32x4 -2147483648 0 0 0))
+(assert_return (invoke "i64x2.all_true" (v128.const i64x2 1 0x100000000)) (i32.const 1))
+(assert_return (invoke "i64x2.all_true" (v128.const i64x2 1 0)) (i32.const 0))
+(assert_return
+  (invoke "i64x2.mul" (v128.const i64x2 0x100000000 -3) (v128.const i64x2 0x100000000 5))
+  (v128.const i64x2 0 -15))
+(assert_return
+  (invoke "f32x4.min" (v128.const f32x4 0 -0 nan 1) (v128.const f32x4 -0 0 1 2))
+  (v128.const f32x4 -0 -0 nan:canonical 1))
+(assert_return
+  (invoke "f32x4.pmax" (v128.const f32x4 0 nan 1 2) (v128.const f32x4 -0 1 nan 3))
+  (v128.const f32x4 0 nan:canonical 1 3))
+(assert_return
+  (invoke "f64x2.nearest" (v128.const f64x2 2.5 -3.5))
+  (v128.const f64x2 2 -4))
+(assert_return
+  (invoke "f64x2.promote_low_f32x4" (v128.const f32x4 1.5 -0 7 8))
+  (v128.const f64x2 1.5 -0))
+(assert_return
+  (invoke "v128.bitselect"
+    (v128.const i64x2 -1 -1) (v128.const i64x2 0 0) (v128.const i64x2 0xff 0))
+  (v128.const i64x2 0xff 0))
+(assert_return (invoke "v128.any_true" (v128.const i64x2 0 0)) (i32.const 0))
+(assert_return (invoke "v128.any_true" (v128.const i8x16 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1)) (i32.const 1))
+(assert_return (invoke "lanes") (i32.const 254))
+
+(assert_return
+  (invoke "v128.load8x8_s" (i32.const 6))
+  (v128.const i16x8 7 8 -7 -6 -5 -4 -3 -2))
+(assert_return
+  (invoke "v128.load16_splat" (i32.const 0))
+  (v128.const i16x8 0x201 0x201 0x201 0x201 0x201 0x201 0x201 0x201))
+(assert_return
+  (invoke "v128.load32_zero" (i32.const 1))
+  (v128.const i32x4 0x05040302 0 0 0))
+(assert_return
+  (invoke "v128.load16_lane" (i32.const 0) (v128.const i64x2 0 0))
+  (v128.const i16x8 0 0 0 0 0 0 0 0x201))
+(assert_return
+  (invoke "v128.store8_lane" (i32.const 100) (v128.const i8x16 0 42 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (i32.const 42))
+(assert_return
+  (invoke "v128.round_trip" (v128.const i32x4 1 2 3 4))
+  (v128.const i32x4 1 2 3 4))
+(assert_trap (invoke "v128.load8x8_s" (i32.const 65530)) "out of bounds memory access")
+(assert_trap (invoke "v128.load16_lane" (i32.const 65535) (v128.const i64x2 0 0)) "out of bounds memory access")